@@ -12,9 +12,7 @@ pub use tpms::*;
 pub use tpmt::*;
 pub use tpmu::*;
 
-use crate::{
-    tpm2b, tpm2b_struct, tpm_bool, tpm_enum, tpml, TpmErrorKind, TpmSized, TPM_MAX_COMMAND_SIZE,
-};
+use crate::{TpmErrorKind, TpmSized, TPM_MAX_COMMAND_SIZE};
 use core::{
     convert::TryFrom,
     fmt::{self, Debug, Display, Formatter},
@@ -23,17 +21,38 @@ use core::{
 pub const MAX_DIGEST_SIZE: usize = 64;
 pub const MAX_ECC_KEY_BYTES: usize = 66;
 pub const MAX_SYM_KEY_BYTES: usize = 32;
+#[cfg(not(feature = "rsa-2048"))]
 pub const MAX_RSA_KEY_BYTES: usize = 512;
+#[cfg(feature = "rsa-2048")]
+pub const MAX_RSA_KEY_BYTES: usize = 256;
 pub const MAX_SENSITIVE_DATA: usize = 256;
+#[cfg(not(feature = "buffer-size-2k"))]
 pub const MAX_BUFFER_SIZE: usize = 1024;
+#[cfg(feature = "buffer-size-2k")]
+pub const MAX_BUFFER_SIZE: usize = 2048;
+#[cfg(not(feature = "nv-buffer-size-2k"))]
 pub const MAX_NV_BUFFER_SIZE: usize = 1024;
+#[cfg(feature = "nv-buffer-size-2k")]
+pub const MAX_NV_BUFFER_SIZE: usize = 2048;
 pub const MAX_PRIVATE_SIZE: usize = 1408;
+/// The size of `TPMU_ENCRYPTED_SECRET`: an RSA-OAEP encrypted seed or a
+/// marshaled `TPMS_ECC_POINT`.
+pub const MAX_ENCRYPTED_SECRET_SIZE: usize = if MAX_RSA_KEY_BYTES > 2 * (MAX_ECC_KEY_BYTES + 2) {
+    MAX_RSA_KEY_BYTES
+} else {
+    2 * (MAX_ECC_KEY_BYTES + 2)
+};
+/// The size of `TPMS_ID_OBJECT`: `integrityHMAC` and `encIdentity`, which are
+/// both sized digests. The size field of `encIdentity` is encrypted, so the
+/// structure is kept opaque.
+pub const MAX_ID_OBJECT_SIZE: usize = 2 * (MAX_DIGEST_SIZE + 2);
 
 tpm2b!(Tpm2b, TPM_MAX_COMMAND_SIZE);
 tpm2b!(Tpm2bAuth, MAX_DIGEST_SIZE);
 tpm2b!(Tpm2bDigest, MAX_DIGEST_SIZE);
 tpm2b!(Tpm2bEccParameter, MAX_ECC_KEY_BYTES);
-tpm2b!(Tpm2bEncryptedSecret, MAX_ECC_KEY_BYTES);
+tpm2b!(Tpm2bEncryptedSecret, MAX_ENCRYPTED_SECRET_SIZE);
+tpm2b!(Tpm2bIdObject, MAX_ID_OBJECT_SIZE);
 tpm2b!(Tpm2bMaxBuffer, MAX_BUFFER_SIZE);
 tpm2b!(Tpm2bMaxNvBuffer, MAX_NV_BUFFER_SIZE);
 tpm2b!(Tpm2bName, { MAX_DIGEST_SIZE + 2 });
@@ -76,11 +95,6 @@ tpm2b_struct!(
     Tpm2bNvPublic,
     TpmsNvPublic
 );
-tpm2b_struct!(
-    #[derive(Debug, PartialEq, Eq, Clone, Default)]
-    Tpm2bIdObject,
-    TpmsIdObject
-);
 tpm2b_struct!(
     #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
     Tpm2bEccPoint,
@@ -136,6 +150,7 @@ tpm_enum! {
         (Handles, 0x0000_0001, "TPM_CAP_HANDLES"),
         (Commands, 0x0000_0002, "TPM_CAP_COMMANDS"),
         (Pcrs, 0x0000_0005, "TPM_CAP_PCRS"),
+        (TpmProperties, 0x0000_0006, "TPM_CAP_TPM_PROPERTIES"),
     }
 }
 
@@ -201,6 +216,7 @@ tpm_enum! {
         (LoadExternal, 0x0000_0167, "TPM_CC_LoadExternal"),
         (MakeCredential, 0x0000_0168, "TPM_CC_MakeCredential"),
         (NvReadPublic, 0x0000_0169, "TPM_CC_NV_ReadPublic"),
+        (PolicyAuthorize, 0x0000_016A, "TPM_CC_PolicyAuthorize"),
         (PolicyAuthValue, 0x0000_016B, "TPM_CC_PolicyAuthValue"),
         (PolicyCommandCode, 0x0000_016C, "TPM_CC_PolicyCommandCode"),
         (PolicyCpHash, 0x0000_016E, "TPM_CC_PolicyCpHash"),
@@ -247,6 +263,17 @@ tpm_enum! {
     }
 }
 
+pub const TPM_PT_FIXED: u32 = 0x0100;
+pub const TPM_PT_FAMILY_INDICATOR: u32 = TPM_PT_FIXED;
+pub const TPM_PT_MANUFACTURER: u32 = TPM_PT_FIXED + 5;
+pub const TPM_PT_INPUT_BUFFER: u32 = TPM_PT_FIXED + 13;
+pub const TPM_PT_HR_TRANSIENT_MIN: u32 = TPM_PT_FIXED + 14;
+pub const TPM_PT_PCR_COUNT: u32 = TPM_PT_FIXED + 18;
+pub const TPM_PT_MAX_COMMAND_SIZE: u32 = TPM_PT_FIXED + 30;
+pub const TPM_PT_MAX_RESPONSE_SIZE: u32 = TPM_PT_FIXED + 31;
+pub const TPM_PT_MAX_DIGEST: u32 = TPM_PT_FIXED + 32;
+pub const TPM_PT_NV_BUFFER_MAX: u32 = TPM_PT_FIXED + 44;
+
 pub const TPM_RC_VER1: u32 = 0x0100;
 pub const TPM_RC_FMT1: u32 = 0x0080;
 pub const TPM_RC_WARN: u32 = 0x0900;
@@ -279,17 +306,71 @@ tpm_enum! {
         (BadTag, 0x001E, "TPM_RC_BAD_TAG"),
         (Initialize, TPM_RC_VER1, "TPM_RC_INITIALIZE"),
         (Failure, TPM_RC_VER1 | 0x001, "TPM_RC_FAILURE"),
+        (Sequence, TPM_RC_VER1 | 0x003, "TPM_RC_SEQUENCE"),
+        (Disabled, TPM_RC_VER1 | 0x020, "TPM_RC_DISABLED"),
+        (Exclusive, TPM_RC_VER1 | 0x021, "TPM_RC_EXCLUSIVE"),
+        (AuthType, TPM_RC_VER1 | 0x024, "TPM_RC_AUTH_TYPE"),
         (AuthMissing, TPM_RC_VER1 | 0x025, "TPM_RC_AUTH_MISSING"),
+        (Policy, TPM_RC_VER1 | 0x026, "TPM_RC_POLICY"),
+        (Pcr, TPM_RC_VER1 | 0x027, "TPM_RC_PCR"),
+        (PcrChanged, TPM_RC_VER1 | 0x028, "TPM_RC_PCR_CHANGED"),
+        (AuthUnavailable, TPM_RC_VER1 | 0x02F, "TPM_RC_AUTH_UNAVAILABLE"),
         (CommandSize, TPM_RC_VER1 | 0x042, "TPM_RC_COMMAND_SIZE"),
+        (CommandCode, TPM_RC_VER1 | 0x043, "TPM_RC_COMMAND_CODE"),
+        (AuthSize, TPM_RC_VER1 | 0x044, "TPM_RC_AUTHSIZE"),
+        (AuthContext, TPM_RC_VER1 | 0x045, "TPM_RC_AUTH_CONTEXT"),
+        (NvRange, TPM_RC_VER1 | 0x046, "TPM_RC_NV_RANGE"),
+        (NvSize, TPM_RC_VER1 | 0x047, "TPM_RC_NV_SIZE"),
+        (NvLocked, TPM_RC_VER1 | 0x048, "TPM_RC_NV_LOCKED"),
+        (NvAuthorization, TPM_RC_VER1 | 0x049, "TPM_RC_NV_AUTHORIZATION"),
+        (NvUninitialized, TPM_RC_VER1 | 0x04A, "TPM_RC_NV_UNINITIALIZED"),
+        (NvSpace, TPM_RC_VER1 | 0x04B, "TPM_RC_NV_SPACE"),
+        (NvDefined, TPM_RC_VER1 | 0x04C, "TPM_RC_NV_DEFINED"),
+        (BadContext, TPM_RC_VER1 | 0x050, "TPM_RC_BAD_CONTEXT"),
         (Sensitive, TPM_RC_VER1 | 0x055, "TPM_RC_SENSITIVE"),
         (Asymmetric, TPM_RC_FMT1 | 0x001, "TPM_RC_ASYMMETRIC"),
         (Attributes, TPM_RC_FMT1 | 0x002, "TPM_RC_ATTRIBUTES"),
+        (Hash, TPM_RC_FMT1 | 0x003, "TPM_RC_HASH"),
         (Value, TPM_RC_FMT1 | 0x004, "TPM_RC_VALUE"),
+        (Hierarchy, TPM_RC_FMT1 | 0x005, "TPM_RC_HIERARCHY"),
+        (KeySize, TPM_RC_FMT1 | 0x007, "TPM_RC_KEY_SIZE"),
+        (Mgf, TPM_RC_FMT1 | 0x008, "TPM_RC_MGF"),
+        (Mode, TPM_RC_FMT1 | 0x009, "TPM_RC_MODE"),
+        (Type, TPM_RC_FMT1 | 0x00A, "TPM_RC_TYPE"),
         (Handle, TPM_RC_FMT1 | 0x00B, "TPM_RC_HANDLE"),
+        (Kdf, TPM_RC_FMT1 | 0x00C, "TPM_RC_KDF"),
+        (Range, TPM_RC_FMT1 | 0x00D, "TPM_RC_RANGE"),
         (AuthFail, TPM_RC_FMT1 | 0x00E, "TPM_RC_AUTH_FAIL"),
+        (Nonce, TPM_RC_FMT1 | 0x00F, "TPM_RC_NONCE"),
+        (Scheme, TPM_RC_FMT1 | 0x012, "TPM_RC_SCHEME"),
+        (Size, TPM_RC_FMT1 | 0x015, "TPM_RC_SIZE"),
+        (Symmetric, TPM_RC_FMT1 | 0x016, "TPM_RC_SYMMETRIC"),
+        (Tag, TPM_RC_FMT1 | 0x017, "TPM_RC_TAG"),
+        (Selector, TPM_RC_FMT1 | 0x018, "TPM_RC_SELECTOR"),
+        (Insufficient, TPM_RC_FMT1 | 0x01A, "TPM_RC_INSUFFICIENT"),
+        (Signature, TPM_RC_FMT1 | 0x01B, "TPM_RC_SIGNATURE"),
+        (Key, TPM_RC_FMT1 | 0x01C, "TPM_RC_KEY"),
+        (PolicyFail, TPM_RC_FMT1 | 0x01D, "TPM_RC_POLICY_FAIL"),
+        (Integrity, TPM_RC_FMT1 | 0x01F, "TPM_RC_INTEGRITY"),
+        (Ticket, TPM_RC_FMT1 | 0x020, "TPM_RC_TICKET"),
         (BadAuth, TPM_RC_FMT1 | 0x022, "TPM_RC_BAD_AUTH"),
+        (Expired, TPM_RC_FMT1 | 0x023, "TPM_RC_EXPIRED"),
+        (PolicyCc, TPM_RC_FMT1 | 0x024, "TPM_RC_POLICY_CC"),
+        (Binding, TPM_RC_FMT1 | 0x025, "TPM_RC_BINDING"),
         (Curve, TPM_RC_FMT1 | 0x026, "TPM_RC_CURVE"),
         (ContextGap, TPM_RC_WARN | 0x001, "TPM_RC_CONTEXT_GAP"),
+        (ObjectMemory, TPM_RC_WARN | 0x002, "TPM_RC_OBJECT_MEMORY"),
+        (SessionMemory, TPM_RC_WARN | 0x003, "TPM_RC_SESSION_MEMORY"),
+        (Memory, TPM_RC_WARN | 0x004, "TPM_RC_MEMORY"),
+        (SessionHandles, TPM_RC_WARN | 0x005, "TPM_RC_SESSION_HANDLES"),
+        (ObjectHandles, TPM_RC_WARN | 0x006, "TPM_RC_OBJECT_HANDLES"),
+        (Locality, TPM_RC_WARN | 0x007, "TPM_RC_LOCALITY"),
+        (Yielded, TPM_RC_WARN | 0x008, "TPM_RC_YIELDED"),
+        (Canceled, TPM_RC_WARN | 0x009, "TPM_RC_CANCELED"),
+        (Testing, TPM_RC_WARN | 0x00A, "TPM_RC_TESTING"),
+        (NvRate, TPM_RC_WARN | 0x020, "TPM_RC_NV_RATE"),
+        (Lockout, TPM_RC_WARN | 0x021, "TPM_RC_LOCKOUT"),
+        (Retry, TPM_RC_WARN | 0x022, "TPM_RC_RETRY"),
         (NvUnavailable, TPM_RC_WARN | 0x023, "TPM_RC_NV_UNAVAILABLE"),
     }
 }
@@ -318,6 +399,21 @@ impl TpmRc {
         })
     }
 
+    /// Attaches a handle, parameter or session number to a format-one response
+    /// code. Other response codes are returned unchanged.
+    pub fn with_index(self, index: TpmRcIndex) -> Self {
+        if (self.0 & TPM_RC_FMT1) == 0 {
+            return self;
+        }
+        let base = self.0 & (TPM_RC_FMT1 | TPM_RC_FMT1_ERROR_MASK);
+        let field = match index {
+            TpmRcIndex::Parameter(n) => TPM_RC_P_BIT | (u32::from(n & 0xF) << TPM_RC_N_SHIFT),
+            TpmRcIndex::Handle(n) => u32::from(n & 0x7) << TPM_RC_N_SHIFT,
+            TpmRcIndex::Session(n) => u32::from((n & 0x7) | 0x8) << TPM_RC_N_SHIFT,
+        };
+        Self(base | field)
+    }
+
     #[must_use]
     pub fn index(self) -> Option<TpmRcIndex> {
         let value = self.0;
@@ -344,7 +440,7 @@ impl TpmRc {
     }
     #[must_use]
     pub fn is_warning(self) -> bool {
-        (self.0 & TPM_RC_WARN) == TPM_RC_WARN
+        (self.0 & TPM_RC_FMT1) == 0 && (self.0 & TPM_RC_WARN) == TPM_RC_WARN
     }
     #[must_use]
     pub fn is_error(self) -> bool {
@@ -373,6 +469,30 @@ impl<'a> crate::TpmParse<'a> for TpmRc {
     }
 }
 
+#[cfg(feature = "generate")]
+impl crate::generate::TpmGenerate for TpmRc {
+    fn generate(rng: &mut crate::generate::TpmRng) -> Self {
+        Self::from(TpmRcBase::generate(rng))
+    }
+}
+
+impl crate::visit::TpmVisit for TpmRc {
+    fn visit(&self, field: &'static str, visitor: &mut dyn crate::visit::TpmVisitor) {
+        visitor.value(field, "TpmRc", crate::visit::TpmValue::U32(self.0));
+    }
+}
+
+impl crate::visit::TpmFromSource for TpmRc {
+    fn from_source(
+        field: &'static str,
+        source: &mut dyn crate::visit::TpmSource,
+    ) -> Result<Self, TpmErrorKind> {
+        let value = u32::try_from(source.unsigned(field, "TpmRc")?)
+            .map_err(|_| TpmErrorKind::ValueTooLarge)?;
+        Self::try_from(value)
+    }
+}
+
 impl TryFrom<u32> for TpmRc {
     type Error = TpmErrorKind;
     fn try_from(value: u32) -> Result<Self, Self::Error> {
@@ -419,11 +539,38 @@ tpm_enum! {
         (Lockout, 0x4000_000A, "TPM_RH_LOCKOUT"),
         (Endorsement, 0x4000_000B, "TPM_RH_ENDORSEMENT"),
         (Platform, 0x4000_000C, "TPM_RH_PLATFORM"),
+        (PlatformNv, 0x4000_000D, "TPM_RH_PLATFORM_NV"),
+        (AuthFirst, 0x4000_0010, "TPM_RH_AUTH_00"),
+        (AuthLast, 0x4000_010F, "TPM_RH_AUTH_FF"),
+        (ActFirst, 0x4000_0110, "TPM_RH_ACT_0"),
+        (ActLast, 0x4000_011F, "TPM_RH_ACT_F"),
         (TransientFirst, 0x8000_0000, "First transient handle"),
         (PersistentFirst, 0x8100_0000, "First persistent handle"),
     }
 }
 
+tpm_enum! {
+    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
+    pub enum TpmHt(u8) {
+        (Pcr, 0x00, "TPM_HT_PCR"),
+        (NvIndex, 0x01, "TPM_HT_NV_INDEX"),
+        (HmacSession, 0x02, "TPM_HT_HMAC_SESSION"),
+        (PolicySession, 0x03, "TPM_HT_POLICY_SESSION"),
+        (Permanent, 0x40, "TPM_HT_PERMANENT"),
+        (Transient, 0x80, "TPM_HT_TRANSIENT"),
+        (Persistent, 0x81, "TPM_HT_PERSISTENT"),
+        (Ac, 0x90, "TPM_HT_AC"),
+    }
+}
+
+impl TpmHt {
+    /// Classifies a handle by its most significant octet.
+    #[must_use]
+    pub fn from_handle(handle: u32) -> Option<Self> {
+        Self::try_from(handle.to_be_bytes()[0]).ok()
+    }
+}
+
 tpm_enum! {
     #[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
     pub enum TpmSe(u8) {
@@ -478,3 +625,4 @@ tpml!(TpmlDigest, Tpm2bDigest, 8);
 tpml!(TpmlDigestValues, TpmtHa, 8);
 tpml!(TpmlHandle, u32, 128);
 tpml!(TpmlPcrSelection, TpmsPcrSelection, 8);
+tpml!(TpmlTaggedTpmProperty, TpmsTaggedProperty, 127);
@@ -1,6 +1,8 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 // Copyright (c) 2025 Opinsys Oy
 
+#[cfg(feature = "generate")]
+use crate::generate::{TpmGenerate, TpmGenerateTagged, TpmRng};
 use crate::{
     data::{
         Tpm2b, Tpm2bAuth, Tpm2bData, Tpm2bDigest, Tpm2bEccParameter, Tpm2bMaxNvBuffer, Tpm2bName,
@@ -8,8 +10,10 @@ use crate::{
         TpmaLocality, TpmaNv, TpmaSession, TpmiYesNo, TpmlPcrSelection, TpmtKdfScheme, TpmtScheme,
         TpmtSymDefObject, TpmuCapabilities,
     },
-    tpm_struct, TpmBuffer, TpmBuild, TpmErrorKind, TpmParse, TpmParseTagged, TpmResult, TpmSized,
-    TpmTagged, TpmWriter,
+    tpm_struct,
+    visit::{TpmFromSource, TpmFromSourceTagged, TpmSource, TpmVisit, TpmVisitor},
+    TpmBuffer, TpmBuild, TpmErrorKind, TpmParse, TpmParseTagged, TpmResult, TpmSized, TpmTagged,
+    TpmWriter,
 };
 use core::{convert::TryFrom, mem::size_of, ops::Deref};
 
@@ -76,6 +80,34 @@ impl<'a> TpmParse<'a> for TpmsCapabilityData {
     }
 }
 
+#[cfg(feature = "generate")]
+impl TpmGenerate for TpmsCapabilityData {
+    fn generate(rng: &mut TpmRng) -> Self {
+        let capability = TpmuCapabilities::generate_tag(rng);
+        let data = TpmuCapabilities::generate_tagged(capability, rng);
+        Self { capability, data }
+    }
+}
+
+impl TpmVisit for TpmsCapabilityData {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.enter(field, "TpmsCapabilityData");
+        self.capability.visit("capability", visitor);
+        self.data.visit("data", visitor);
+        visitor.leave();
+    }
+}
+
+impl TpmFromSource for TpmsCapabilityData {
+    fn from_source(field: &'static str, source: &mut dyn TpmSource) -> Result<Self, TpmErrorKind> {
+        source.enter(field, "TpmsCapabilityData")?;
+        let capability = TpmCap::from_source("capability", source)?;
+        let data = TpmuCapabilities::from_source_tagged(capability, "data", source)?;
+        source.leave()?;
+        Ok(Self { capability, data })
+    }
+}
+
 tpm_struct! {
     #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
     pub struct TpmsClockInfo {
@@ -90,7 +122,7 @@ tpm_struct! {
     #[derive(Debug, PartialEq, Eq, Clone)]
     pub struct TpmsContext {
         pub sequence: u64,
-        pub saved_handle: crate::TpmTransient,
+        pub saved_handle: crate::TpmContextHandle,
         pub hierarchy: TpmRh,
         pub context_blob: Tpm2b,
     }
@@ -132,7 +164,7 @@ tpm_struct! {
 tpm_struct! {
     #[derive(Debug, PartialEq, Eq, Clone, Default, Copy)]
     pub struct TpmsNvPublic {
-        pub nv_index: u32,
+        pub nv_index: crate::TpmNvIndex,
         pub name_alg: TpmAlgId,
         pub attributes: TpmaNv,
         pub auth_policy: Tpm2bDigest,
@@ -171,10 +203,10 @@ impl<'a> TpmParse<'a> for TpmsPcrSelection {
         let size = size as usize;
 
         if size > TPM_PCR_SELECT_MAX {
-            return Err(TpmErrorKind::ValueTooLarge);
+            return Err(TpmErrorKind::ValueTooLarge.into());
         }
         if buf.len() < size {
-            return Err(TpmErrorKind::Boundary);
+            return Err(TpmErrorKind::Boundary.into());
         }
 
         let (pcr_bytes, buf) = buf.split_at(size);
@@ -184,6 +216,35 @@ impl<'a> TpmParse<'a> for TpmsPcrSelection {
     }
 }
 
+#[cfg(feature = "generate")]
+impl TpmGenerate for TpmsPcrSelection {
+    fn generate(rng: &mut TpmRng) -> Self {
+        Self {
+            hash: TpmAlgId::generate(rng),
+            pcr_select: TpmsPcrSelect::generate(rng),
+        }
+    }
+}
+
+impl TpmVisit for TpmsPcrSelection {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.enter(field, "TpmsPcrSelection");
+        self.hash.visit("hash", visitor);
+        self.pcr_select.visit("pcr_select", visitor);
+        visitor.leave();
+    }
+}
+
+impl TpmFromSource for TpmsPcrSelection {
+    fn from_source(field: &'static str, source: &mut dyn TpmSource) -> Result<Self, TpmErrorKind> {
+        source.enter(field, "TpmsPcrSelection")?;
+        let hash = TpmAlgId::from_source("hash", source)?;
+        let pcr_select = TpmsPcrSelect::from_source("pcr_select", source)?;
+        source.leave()?;
+        Ok(Self { hash, pcr_select })
+    }
+}
+
 tpm_struct! {
     #[derive(Debug, PartialEq, Eq, Clone, Default)]
     pub struct TpmsSensitiveCreate {
@@ -194,16 +255,16 @@ tpm_struct! {
 
 tpm_struct! {
     #[derive(Debug, PartialEq, Eq, Clone, Default, Copy)]
-    pub struct TpmsIdObject {
-        pub integrity_hmac: Tpm2bDigest,
-        pub enc_identity: Tpm2bDigest,
+    pub struct TpmsSymcipherParms {
+        pub sym: TpmtSymDefObject,
     }
 }
 
 tpm_struct! {
-    #[derive(Debug, PartialEq, Eq, Clone, Default, Copy)]
-    pub struct TpmsSymcipherParms {
-        pub sym: TpmtSymDefObject,
+    #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+    pub struct TpmsTaggedProperty {
+        pub property: u32,
+        pub value: u32,
     }
 }
 
@@ -370,14 +431,16 @@ impl<'a> TpmParse<'a> for TpmsAttest {
             return Err(TpmErrorKind::InvalidMagic {
                 expected: 0xff54_4347,
                 got: magic,
-            });
+            }
+            .into());
         }
         let (attest_type, buf) = TpmSt::parse(buf)?;
         let (qualified_signer, buf) = Tpm2bName::parse(buf)?;
         let (extra_data, buf) = Tpm2bData::parse(buf)?;
         let (clock_info, buf) = TpmsClockInfo::parse(buf)?;
         let (firmware_version, buf) = u64::parse(buf)?;
-        let (attested, buf) = crate::data::TpmuAttest::parse_tagged(attest_type, buf)?;
+        let (attested, buf) = crate::data::TpmuAttest::parse_tagged(attest_type, buf)
+            .map_err(|e| e.in_field("TpmsAttest.attested", buf.len()))?;
 
         Ok((
             Self {
@@ -393,3 +456,63 @@ impl<'a> TpmParse<'a> for TpmsAttest {
         ))
     }
 }
+
+#[cfg(feature = "generate")]
+impl TpmGenerate for TpmsAttest {
+    fn generate(rng: &mut TpmRng) -> Self {
+        let attest_type = crate::data::TpmuAttest::generate_tag(rng);
+        Self {
+            magic: 0xff54_4347,
+            attest_type,
+            qualified_signer: Tpm2bName::generate(rng),
+            extra_data: Tpm2bData::generate(rng),
+            clock_info: TpmsClockInfo::generate(rng),
+            firmware_version: u64::generate(rng),
+            attested: crate::data::TpmuAttest::generate_tagged(attest_type, rng),
+        }
+    }
+}
+
+impl TpmVisit for TpmsAttest {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.enter(field, "TpmsAttest");
+        self.magic.visit("magic", visitor);
+        self.attest_type.visit("attest_type", visitor);
+        self.qualified_signer.visit("qualified_signer", visitor);
+        self.extra_data.visit("extra_data", visitor);
+        self.clock_info.visit("clock_info", visitor);
+        self.firmware_version.visit("firmware_version", visitor);
+        self.attested.visit("attested", visitor);
+        visitor.leave();
+    }
+}
+
+impl TpmFromSource for TpmsAttest {
+    fn from_source(field: &'static str, source: &mut dyn TpmSource) -> Result<Self, TpmErrorKind> {
+        source.enter(field, "TpmsAttest")?;
+        let magic = u32::from_source("magic", source)?;
+        if magic != 0xff54_4347 {
+            return Err(TpmErrorKind::InvalidMagic {
+                expected: 0xff54_4347,
+                got: magic,
+            });
+        }
+        let attest_type = TpmSt::from_source("attest_type", source)?;
+        let qualified_signer = Tpm2bName::from_source("qualified_signer", source)?;
+        let extra_data = Tpm2bData::from_source("extra_data", source)?;
+        let clock_info = TpmsClockInfo::from_source("clock_info", source)?;
+        let firmware_version = u64::from_source("firmware_version", source)?;
+        let attested =
+            crate::data::TpmuAttest::from_source_tagged(attest_type, "attested", source)?;
+        source.leave()?;
+        Ok(Self {
+            magic,
+            attest_type,
+            qualified_signer,
+            extra_data,
+            clock_info,
+            firmware_version,
+            attested,
+        })
+    }
+}
@@ -3,14 +3,18 @@
 
 use super::{
     tpmu::{
-        TpmuHa, TpmuPublicId, TpmuPublicParms, TpmuSensitiveComposite, TpmuSignature,
-        TpmuSymKeyBits, TpmuSymMode,
+        TpmuAsymScheme, TpmuHa, TpmuPublicId, TpmuPublicParms, TpmuSensitiveComposite,
+        TpmuSignature, TpmuSymKeyBits, TpmuSymMode,
     },
     Tpm2bAuth, Tpm2bDigest, TpmAlgId, TpmRh, TpmSt, TpmaObject,
 };
+#[cfg(feature = "generate")]
+use crate::generate::{TpmGenerate, TpmGenerateTagged, TpmRng};
 use crate::{
-    tpm_struct, tpm_tagged_struct, TpmBuild, TpmErrorKind, TpmParse, TpmParseTagged, TpmResult,
-    TpmSized, TpmTagged, TpmWriter, TPM_MAX_COMMAND_SIZE,
+    tpm_struct, tpm_tagged_struct,
+    visit::{TpmFromSource, TpmFromSourceTagged, TpmSource, TpmVisit, TpmVisitor},
+    TpmBuild, TpmErrorKind, TpmParse, TpmParseTagged, TpmResult, TpmSized, TpmTagged, TpmWriter,
+    TPM_MAX_COMMAND_SIZE,
 };
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -60,9 +64,11 @@ impl<'a> TpmParse<'a> for TpmtPublic {
         buf = rest;
         let (auth_policy, rest) = Tpm2bDigest::parse(buf)?;
         buf = rest;
-        let (parameters, rest) = TpmuPublicParms::parse_tagged(object_type, buf)?;
+        let (parameters, rest) = TpmuPublicParms::parse_tagged(object_type, buf)
+            .map_err(|e| e.in_field("TpmtPublic.parameters", buf.len()))?;
         buf = rest;
-        let (unique, rest) = TpmuPublicId::parse_tagged(object_type, buf)?;
+        let (unique, rest) = TpmuPublicId::parse_tagged(object_type, buf)
+            .map_err(|e| e.in_field("TpmtPublic.unique", buf.len()))?;
         buf = rest;
 
         let public_area = Self {
@@ -78,6 +84,55 @@ impl<'a> TpmParse<'a> for TpmtPublic {
     }
 }
 
+#[cfg(feature = "generate")]
+impl TpmGenerate for TpmtPublic {
+    fn generate(rng: &mut TpmRng) -> Self {
+        let object_type = TpmuPublicParms::generate_tag(rng);
+        Self {
+            object_type,
+            name_alg: TpmAlgId::generate(rng),
+            object_attributes: TpmaObject::generate(rng),
+            auth_policy: Tpm2bDigest::generate(rng),
+            parameters: TpmuPublicParms::generate_tagged(object_type, rng),
+            unique: TpmuPublicId::generate_tagged(object_type, rng),
+        }
+    }
+}
+
+impl TpmVisit for TpmtPublic {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.enter(field, "TpmtPublic");
+        self.object_type.visit("object_type", visitor);
+        self.name_alg.visit("name_alg", visitor);
+        self.object_attributes.visit("object_attributes", visitor);
+        self.auth_policy.visit("auth_policy", visitor);
+        self.parameters.visit("parameters", visitor);
+        self.unique.visit("unique", visitor);
+        visitor.leave();
+    }
+}
+
+impl TpmFromSource for TpmtPublic {
+    fn from_source(field: &'static str, source: &mut dyn TpmSource) -> Result<Self, TpmErrorKind> {
+        source.enter(field, "TpmtPublic")?;
+        let object_type = TpmAlgId::from_source("object_type", source)?;
+        let name_alg = TpmAlgId::from_source("name_alg", source)?;
+        let object_attributes = TpmaObject::from_source("object_attributes", source)?;
+        let auth_policy = Tpm2bDigest::from_source("auth_policy", source)?;
+        let parameters = TpmuPublicParms::from_source_tagged(object_type, "parameters", source)?;
+        let unique = TpmuPublicId::from_source_tagged(object_type, "unique", source)?;
+        source.leave()?;
+        Ok(Self {
+            object_type,
+            name_alg,
+            object_attributes,
+            auth_policy,
+            parameters,
+            unique,
+        })
+    }
+}
+
 impl Default for TpmtPublic {
     fn default() -> Self {
         Self {
@@ -91,10 +146,11 @@ impl Default for TpmtPublic {
     }
 }
 
-tpm_struct! {
+tpm_tagged_struct! {
     #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
     pub struct TpmtScheme {
         pub scheme: TpmAlgId,
+        pub details: TpmuAsymScheme,
     }
 }
 
@@ -199,6 +255,48 @@ impl<'a> TpmParse<'a> for TpmtSensitive {
     }
 }
 
+#[cfg(feature = "generate")]
+impl TpmGenerate for TpmtSensitive {
+    fn generate(rng: &mut TpmRng) -> Self {
+        let sensitive_type = TpmuSensitiveComposite::generate_tag(rng);
+        Self {
+            sensitive_type,
+            auth_value: Tpm2bAuth::generate(rng),
+            seed_value: Tpm2bDigest::generate(rng),
+            sensitive: TpmuSensitiveComposite::generate_tagged(sensitive_type, rng),
+        }
+    }
+}
+
+impl TpmVisit for TpmtSensitive {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.enter(field, "TpmtSensitive");
+        self.sensitive_type.visit("sensitive_type", visitor);
+        self.auth_value.visit("auth_value", visitor);
+        self.seed_value.visit("seed_value", visitor);
+        self.sensitive.visit("sensitive", visitor);
+        visitor.leave();
+    }
+}
+
+impl TpmFromSource for TpmtSensitive {
+    fn from_source(field: &'static str, source: &mut dyn TpmSource) -> Result<Self, TpmErrorKind> {
+        source.enter(field, "TpmtSensitive")?;
+        let sensitive_type = TpmAlgId::from_source("sensitive_type", source)?;
+        let auth_value = Tpm2bAuth::from_source("auth_value", source)?;
+        let seed_value = Tpm2bDigest::from_source("seed_value", source)?;
+        let sensitive =
+            TpmuSensitiveComposite::from_source_tagged(sensitive_type, "sensitive", source)?;
+        source.leave()?;
+        Ok(Self {
+            sensitive_type,
+            auth_value,
+            seed_value,
+            sensitive,
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub struct TpmtSymDef {
     pub algorithm: TpmAlgId,
@@ -260,6 +358,43 @@ impl<'a> TpmParse<'a> for TpmtSymDef {
     }
 }
 
+#[cfg(feature = "generate")]
+impl TpmGenerate for TpmtSymDef {
+    fn generate(rng: &mut TpmRng) -> Self {
+        let algorithm = TpmuSymKeyBits::generate_tag(rng);
+        Self {
+            algorithm,
+            key_bits: TpmuSymKeyBits::generate_tagged(algorithm, rng),
+            mode: TpmuSymMode::generate_tagged(algorithm, rng),
+        }
+    }
+}
+
+impl TpmVisit for TpmtSymDef {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.enter(field, "TpmtSymDef");
+        self.algorithm.visit("algorithm", visitor);
+        self.key_bits.visit("key_bits", visitor);
+        self.mode.visit("mode", visitor);
+        visitor.leave();
+    }
+}
+
+impl TpmFromSource for TpmtSymDef {
+    fn from_source(field: &'static str, source: &mut dyn TpmSource) -> Result<Self, TpmErrorKind> {
+        source.enter(field, "TpmtSymDef")?;
+        let algorithm = TpmAlgId::from_source("algorithm", source)?;
+        let key_bits = TpmuSymKeyBits::from_source_tagged(algorithm, "key_bits", source)?;
+        let mode = TpmuSymMode::from_source_tagged(algorithm, "mode", source)?;
+        source.leave()?;
+        Ok(Self {
+            algorithm,
+            key_bits,
+            mode,
+        })
+    }
+}
+
 pub type TpmtSymDefObject = TpmtSymDef;
 
 tpm_struct! {
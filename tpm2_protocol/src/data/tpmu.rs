@@ -1,25 +1,31 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 // Copyright (c) 2025 Opinsys Oy
 
+#[cfg(feature = "generate")]
+use crate::generate::{TpmGenerate, TpmGenerateTagged, TpmRng};
 use crate::{
     data::{
         Tpm2bDigest, Tpm2bEccParameter, Tpm2bPublicKeyRsa, Tpm2bSensitiveData, Tpm2bSymKey,
         TpmAlgId, TpmCap, TpmEccCurve, TpmlAlgProperty, TpmlHandle, TpmlPcrSelection,
-        TpmsCertifyInfo, TpmsCommandAuditInfo, TpmsCreationInfo, TpmsEccPoint, TpmsKeyedhashParms,
-        TpmsNvCertifyInfo, TpmsNvDigestCertifyInfo, TpmsQuoteInfo, TpmsSessionAuditInfo,
-        TpmsSignatureEcc, TpmsSignatureRsa, TpmsSymcipherParms, TpmsTimeAttestInfo, TpmtHa,
-        TpmtKdfScheme,
+        TpmlTaggedTpmProperty, TpmsCertifyInfo, TpmsCommandAuditInfo, TpmsCreationInfo,
+        TpmsEccPoint, TpmsKeyedhashParms, TpmsNvCertifyInfo, TpmsNvDigestCertifyInfo,
+        TpmsQuoteInfo, TpmsSessionAuditInfo, TpmsSignatureEcc, TpmsSignatureRsa,
+        TpmsSymcipherParms, TpmsTimeAttestInfo, TpmtHa, TpmtKdfScheme,
     },
-    tpm_hash_size, TpmBuild, TpmErrorKind, TpmParse, TpmParseTagged, TpmResult, TpmSized,
-    TpmTagged, TpmWriter, TPM_MAX_COMMAND_SIZE,
+    tpm_hash_size,
+    visit::{TpmFromSource, TpmFromSourceTagged, TpmSource, TpmValue, TpmVisit, TpmVisitor},
+    TpmBuild, TpmErrorKind, TpmParse, TpmParseTagged, TpmResult, TpmSized, TpmTagged, TpmWriter,
+    TPM_MAX_COMMAND_SIZE,
 };
 use core::ops::Deref;
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TpmuCapabilities {
     Algs(TpmlAlgProperty),
     Handles(TpmlHandle),
     Pcrs(TpmlPcrSelection),
+    TpmProperties(TpmlTaggedTpmProperty),
 }
 
 impl TpmTagged for TpmuCapabilities {
@@ -34,6 +40,7 @@ impl TpmSized for TpmuCapabilities {
             Self::Algs(algs) => algs.len(),
             Self::Handles(handles) => handles.len(),
             Self::Pcrs(pcrs) => pcrs.len(),
+            Self::TpmProperties(props) => props.len(),
         }
     }
 }
@@ -44,6 +51,7 @@ impl TpmBuild for TpmuCapabilities {
             Self::Algs(algs) => algs.build(writer),
             Self::Handles(handles) => handles.build(writer),
             Self::Pcrs(pcrs) => pcrs.build(writer),
+            Self::TpmProperties(props) => props.build(writer),
         }
     }
 }
@@ -63,6 +71,61 @@ impl<'a> TpmParseTagged<'a> for TpmuCapabilities {
                 let (pcrs, buf) = TpmlPcrSelection::parse(buf)?;
                 Ok((Self::Pcrs(pcrs), buf))
             }
+            TpmCap::TpmProperties => {
+                let (props, buf) = TpmlTaggedTpmProperty::parse(buf)?;
+                Ok((Self::TpmProperties(props), buf))
+            }
+            TpmCap::Commands => Err(TpmErrorKind::InvalidValue.into()),
+        }
+    }
+}
+
+#[cfg(feature = "generate")]
+impl TpmGenerateTagged for TpmuCapabilities {
+    fn generate_tag(rng: &mut TpmRng) -> TpmCap {
+        const TAGS: [TpmCap; 4] = [
+            TpmCap::Algs,
+            TpmCap::Handles,
+            TpmCap::Pcrs,
+            TpmCap::TpmProperties,
+        ];
+        TAGS[rng.below(TAGS.len())]
+    }
+
+    fn generate_tagged(tag: TpmCap, rng: &mut TpmRng) -> Self {
+        match tag {
+            TpmCap::Handles => Self::Handles(TpmlHandle::generate(rng)),
+            TpmCap::Pcrs => Self::Pcrs(TpmlPcrSelection::generate(rng)),
+            TpmCap::TpmProperties => Self::TpmProperties(TpmlTaggedTpmProperty::generate(rng)),
+            TpmCap::Algs | TpmCap::Commands => Self::Algs(TpmlAlgProperty::generate(rng)),
+        }
+    }
+}
+
+impl TpmVisit for TpmuCapabilities {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        match self {
+            Self::Algs(algs) => algs.visit(field, visitor),
+            Self::Handles(handles) => handles.visit(field, visitor),
+            Self::Pcrs(pcrs) => pcrs.visit(field, visitor),
+            Self::TpmProperties(props) => props.visit(field, visitor),
+        }
+    }
+}
+
+impl TpmFromSourceTagged for TpmuCapabilities {
+    fn from_source_tagged(
+        tag: TpmCap,
+        field: &'static str,
+        source: &mut dyn TpmSource,
+    ) -> Result<Self, TpmErrorKind> {
+        match tag {
+            TpmCap::Algs => TpmlAlgProperty::from_source(field, source).map(Self::Algs),
+            TpmCap::Handles => TpmlHandle::from_source(field, source).map(Self::Handles),
+            TpmCap::Pcrs => TpmlPcrSelection::from_source(field, source).map(Self::Pcrs),
+            TpmCap::TpmProperties => {
+                TpmlTaggedTpmProperty::from_source(field, source).map(Self::TpmProperties)
+            }
             TpmCap::Commands => Err(TpmErrorKind::InvalidValue),
         }
     }
@@ -92,7 +155,7 @@ impl<'a> TpmParseTagged<'a> for TpmuHa {
     fn parse_tagged(tag: TpmAlgId, buf: &'a [u8]) -> TpmResult<(Self, &'a [u8])> {
         let digest_size = tpm_hash_size(&tag).ok_or(TpmErrorKind::InvalidValue)?;
         if buf.len() < digest_size {
-            return Err(TpmErrorKind::Boundary);
+            return Err(TpmErrorKind::Boundary.into());
         }
 
         let (digest_bytes, buf) = buf.split_at(digest_size);
@@ -115,7 +178,7 @@ impl<'a> TpmParseTagged<'a> for TpmuHa {
                     TpmAlgId::Sm3_256 => {
                         Self::Sm3_256($bytes.try_into().map_err(|_| TpmErrorKind::InternalError)?)
                     }
-                    _ => return Err(TpmErrorKind::InvalidValue),
+                    _ => return Err(TpmErrorKind::InvalidValue.into()),
                 }
             };
         }
@@ -126,6 +189,60 @@ impl<'a> TpmParseTagged<'a> for TpmuHa {
     }
 }
 
+#[cfg(feature = "generate")]
+impl TpmGenerateTagged for TpmuHa {
+    fn generate_tag(rng: &mut TpmRng) -> TpmAlgId {
+        const TAGS: [TpmAlgId; 5] = [
+            TpmAlgId::Sha1,
+            TpmAlgId::Sha256,
+            TpmAlgId::Sha384,
+            TpmAlgId::Sha512,
+            TpmAlgId::Sm3_256,
+        ];
+        TAGS[rng.below(TAGS.len())]
+    }
+
+    fn generate_tagged(tag: TpmAlgId, rng: &mut TpmRng) -> Self {
+        let mut digest = match tag {
+            TpmAlgId::Sha1 => Self::Sha1([0; 20]),
+            TpmAlgId::Sha384 => Self::Sha384([0; 48]),
+            TpmAlgId::Sha512 => Self::Sha512([0; 64]),
+            TpmAlgId::Sm3_256 => Self::Sm3_256([0; 32]),
+            _ => Self::Sha256([0; 32]),
+        };
+        match &mut digest {
+            Self::Sha1(d) => rng.fill(d),
+            Self::Sha256(d) | Self::Sm3_256(d) => rng.fill(d),
+            Self::Sha384(d) => rng.fill(d),
+            Self::Sha512(d) => rng.fill(d),
+        }
+        digest
+    }
+}
+
+impl TpmVisit for TpmuHa {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.value(field, "TpmuHa", TpmValue::Bytes(self));
+    }
+}
+
+impl TpmFromSourceTagged for TpmuHa {
+    fn from_source_tagged(
+        tag: TpmAlgId,
+        field: &'static str,
+        source: &mut dyn TpmSource,
+    ) -> Result<Self, TpmErrorKind> {
+        match tag {
+            TpmAlgId::Sha1 => TpmFromSource::from_source(field, source).map(Self::Sha1),
+            TpmAlgId::Sha256 => TpmFromSource::from_source(field, source).map(Self::Sha256),
+            TpmAlgId::Sha384 => TpmFromSource::from_source(field, source).map(Self::Sha384),
+            TpmAlgId::Sha512 => TpmFromSource::from_source(field, source).map(Self::Sha512),
+            TpmAlgId::Sm3_256 => TpmFromSource::from_source(field, source).map(Self::Sm3_256),
+            _ => Err(TpmErrorKind::InvalidValue),
+        }
+    }
+}
+
 impl Default for TpmuHa {
     fn default() -> Self {
         Self::Sha256([0; 32])
@@ -158,12 +275,13 @@ impl Deref for TpmuHa {
 }
 
 #[allow(clippy::large_enum_variant)]
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
 pub enum TpmuPublicId {
     KeyedHash(Tpm2bDigest),
     SymCipher(Tpm2bSymKey),
     Rsa(Tpm2bPublicKeyRsa),
     Ecc(TpmsEccPoint),
+    #[default]
     Null,
 }
 
@@ -217,14 +335,54 @@ impl<'a> TpmParseTagged<'a> for TpmuPublicId {
                 Ok((Self::Ecc(point), rest))
             }
             TpmAlgId::Null => Ok((Self::Null, buf)),
-            _ => Err(TpmErrorKind::InvalidValue),
+            _ => Err(TpmErrorKind::InvalidValue.into()),
         }
     }
 }
 
-impl Default for TpmuPublicId {
-    fn default() -> Self {
-        Self::Null
+#[cfg(feature = "generate")]
+impl TpmGenerateTagged for TpmuPublicId {
+    fn generate_tag(rng: &mut TpmRng) -> TpmAlgId {
+        TpmuPublicParms::generate_tag(rng)
+    }
+
+    fn generate_tagged(tag: TpmAlgId, rng: &mut TpmRng) -> Self {
+        match tag {
+            TpmAlgId::KeyedHash => Self::KeyedHash(Tpm2bDigest::generate(rng)),
+            TpmAlgId::SymCipher => Self::SymCipher(Tpm2bSymKey::generate(rng)),
+            TpmAlgId::Rsa => Self::Rsa(Tpm2bPublicKeyRsa::generate(rng)),
+            TpmAlgId::Ecc => Self::Ecc(TpmsEccPoint::generate(rng)),
+            _ => Self::Null,
+        }
+    }
+}
+
+impl TpmVisit for TpmuPublicId {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        match self {
+            Self::KeyedHash(digest) => digest.visit(field, visitor),
+            Self::SymCipher(key) => key.visit(field, visitor),
+            Self::Rsa(key) => key.visit(field, visitor),
+            Self::Ecc(point) => point.visit(field, visitor),
+            Self::Null => visitor.value(field, "TpmuPublicId", TpmValue::Null),
+        }
+    }
+}
+
+impl TpmFromSourceTagged for TpmuPublicId {
+    fn from_source_tagged(
+        tag: TpmAlgId,
+        field: &'static str,
+        source: &mut dyn TpmSource,
+    ) -> Result<Self, TpmErrorKind> {
+        match tag {
+            TpmAlgId::KeyedHash => Tpm2bDigest::from_source(field, source).map(Self::KeyedHash),
+            TpmAlgId::SymCipher => Tpm2bSymKey::from_source(field, source).map(Self::SymCipher),
+            TpmAlgId::Rsa => Tpm2bPublicKeyRsa::from_source(field, source).map(Self::Rsa),
+            TpmAlgId::Ecc => TpmsEccPoint::from_source(field, source).map(Self::Ecc),
+            TpmAlgId::Null => source.null(field, "TpmuPublicId").map(|()| Self::Null),
+            _ => Err(TpmErrorKind::InvalidValue),
+        }
     }
 }
 
@@ -359,6 +517,127 @@ impl<'a> TpmParseTagged<'a> for TpmuPublicParms {
                 ))
             }
             TpmAlgId::Null => Ok((Self::Null, buf)),
+            _ => Err(TpmErrorKind::InvalidValue.into()),
+        }
+    }
+}
+
+#[cfg(feature = "generate")]
+impl TpmGenerateTagged for TpmuPublicParms {
+    fn generate_tag(rng: &mut TpmRng) -> TpmAlgId {
+        const TAGS: [TpmAlgId; 5] = [
+            TpmAlgId::KeyedHash,
+            TpmAlgId::SymCipher,
+            TpmAlgId::Rsa,
+            TpmAlgId::Ecc,
+            TpmAlgId::Null,
+        ];
+        TAGS[rng.below(TAGS.len())]
+    }
+
+    fn generate_tagged(tag: TpmAlgId, rng: &mut TpmRng) -> Self {
+        match tag {
+            TpmAlgId::KeyedHash => Self::KeyedHash {
+                details: TpmsKeyedhashParms::generate(rng),
+            },
+            TpmAlgId::SymCipher => Self::SymCipher {
+                details: TpmsSymcipherParms::generate(rng),
+            },
+            TpmAlgId::Rsa => Self::Rsa {
+                symmetric: crate::data::TpmtSymDefObject::generate(rng),
+                scheme: crate::data::TpmtScheme::generate(rng),
+                key_bits: u16::generate(rng),
+                exponent: u32::generate(rng),
+            },
+            TpmAlgId::Ecc => Self::Ecc {
+                symmetric: crate::data::TpmtSymDefObject::generate(rng),
+                scheme: crate::data::TpmtScheme::generate(rng),
+                curve_id: TpmEccCurve::generate(rng),
+                kdf: TpmtKdfScheme::generate(rng),
+            },
+            _ => Self::Null,
+        }
+    }
+}
+
+impl TpmVisit for TpmuPublicParms {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        match self {
+            Self::KeyedHash { details } => details.visit(field, visitor),
+            Self::SymCipher { details } => details.visit(field, visitor),
+            Self::Rsa {
+                symmetric,
+                scheme,
+                key_bits,
+                exponent,
+            } => {
+                visitor.enter(field, "TpmsRsaParms");
+                symmetric.visit("symmetric", visitor);
+                scheme.visit("scheme", visitor);
+                key_bits.visit("key_bits", visitor);
+                exponent.visit("exponent", visitor);
+                visitor.leave();
+            }
+            Self::Ecc {
+                symmetric,
+                scheme,
+                curve_id,
+                kdf,
+            } => {
+                visitor.enter(field, "TpmsEccParms");
+                symmetric.visit("symmetric", visitor);
+                scheme.visit("scheme", visitor);
+                curve_id.visit("curve_id", visitor);
+                kdf.visit("kdf", visitor);
+                visitor.leave();
+            }
+            Self::Null => visitor.value(field, "TpmuPublicParms", TpmValue::Null),
+        }
+    }
+}
+
+impl TpmFromSourceTagged for TpmuPublicParms {
+    fn from_source_tagged(
+        tag: TpmAlgId,
+        field: &'static str,
+        source: &mut dyn TpmSource,
+    ) -> Result<Self, TpmErrorKind> {
+        match tag {
+            TpmAlgId::KeyedHash => Ok(Self::KeyedHash {
+                details: TpmsKeyedhashParms::from_source(field, source)?,
+            }),
+            TpmAlgId::SymCipher => Ok(Self::SymCipher {
+                details: TpmsSymcipherParms::from_source(field, source)?,
+            }),
+            TpmAlgId::Rsa => {
+                source.enter(field, "TpmsRsaParms")?;
+                let symmetric = crate::data::TpmtSymDefObject::from_source("symmetric", source)?;
+                let scheme = crate::data::TpmtScheme::from_source("scheme", source)?;
+                let key_bits = u16::from_source("key_bits", source)?;
+                let exponent = u32::from_source("exponent", source)?;
+                source.leave()?;
+                Ok(Self::Rsa {
+                    symmetric,
+                    scheme,
+                    key_bits,
+                    exponent,
+                })
+            }
+            TpmAlgId::Ecc => {
+                source.enter(field, "TpmsEccParms")?;
+                let symmetric = crate::data::TpmtSymDefObject::from_source("symmetric", source)?;
+                let scheme = crate::data::TpmtScheme::from_source("scheme", source)?;
+                let curve_id = TpmEccCurve::from_source("curve_id", source)?;
+                let kdf = TpmtKdfScheme::from_source("kdf", source)?;
+                source.leave()?;
+                Ok(Self::Ecc {
+                    symmetric,
+                    scheme,
+                    curve_id,
+                    kdf,
+                })
+            }
+            TpmAlgId::Null => source.null(field, "TpmuPublicParms").map(|()| Self::Null),
             _ => Err(TpmErrorKind::InvalidValue),
         }
     }
@@ -386,6 +665,7 @@ impl Default for TpmuSensitiveComposite {
 
 impl TpmSized for TpmuSensitiveComposite {
     const SIZE: usize = TPM_MAX_COMMAND_SIZE;
+    #[allow(clippy::match_same_arms)]
     fn len(&self) -> usize {
         match self {
             Self::Rsa(val) => val.len(),
@@ -397,6 +677,7 @@ impl TpmSized for TpmuSensitiveComposite {
 }
 
 impl TpmBuild for TpmuSensitiveComposite {
+    #[allow(clippy::match_same_arms)]
     fn build(&self, writer: &mut TpmWriter) -> TpmResult<()> {
         match self {
             Self::Rsa(val) => val.build(writer),
@@ -426,16 +707,68 @@ impl<'a> TpmParseTagged<'a> for TpmuSensitiveComposite {
                 let (val, buf) = Tpm2bSymKey::parse(buf)?;
                 Ok((Self::Sym(val), buf))
             }
+            _ => Err(TpmErrorKind::InvalidValue.into()),
+        }
+    }
+}
+
+#[cfg(feature = "generate")]
+impl TpmGenerateTagged for TpmuSensitiveComposite {
+    fn generate_tag(rng: &mut TpmRng) -> TpmAlgId {
+        const TAGS: [TpmAlgId; 4] = [
+            TpmAlgId::Rsa,
+            TpmAlgId::Ecc,
+            TpmAlgId::KeyedHash,
+            TpmAlgId::SymCipher,
+        ];
+        TAGS[rng.below(TAGS.len())]
+    }
+
+    fn generate_tagged(tag: TpmAlgId, rng: &mut TpmRng) -> Self {
+        match tag {
+            TpmAlgId::Ecc => Self::Ecc(Tpm2bEccParameter::generate(rng)),
+            TpmAlgId::KeyedHash => Self::Bits(Tpm2bSensitiveData::generate(rng)),
+            TpmAlgId::SymCipher => Self::Sym(Tpm2bSymKey::generate(rng)),
+            _ => Self::Rsa(crate::data::Tpm2bPrivateKeyRsa::generate(rng)),
+        }
+    }
+}
+
+impl TpmVisit for TpmuSensitiveComposite {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        match self {
+            Self::Rsa(key) => key.visit(field, visitor),
+            Self::Ecc(key) => key.visit(field, visitor),
+            Self::Bits(data) => data.visit(field, visitor),
+            Self::Sym(key) => key.visit(field, visitor),
+        }
+    }
+}
+
+impl TpmFromSourceTagged for TpmuSensitiveComposite {
+    fn from_source_tagged(
+        tag: TpmAlgId,
+        field: &'static str,
+        source: &mut dyn TpmSource,
+    ) -> Result<Self, TpmErrorKind> {
+        match tag {
+            TpmAlgId::Rsa => {
+                crate::data::Tpm2bPrivateKeyRsa::from_source(field, source).map(Self::Rsa)
+            }
+            TpmAlgId::Ecc => Tpm2bEccParameter::from_source(field, source).map(Self::Ecc),
+            TpmAlgId::KeyedHash => Tpm2bSensitiveData::from_source(field, source).map(Self::Bits),
+            TpmAlgId::SymCipher => Tpm2bSymKey::from_source(field, source).map(Self::Sym),
             _ => Err(TpmErrorKind::InvalidValue),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub enum TpmuSymKeyBits {
     Aes(u16),
     Sm4(u16),
     Camellia(u16),
+    #[default]
     Null,
 }
 
@@ -444,12 +777,6 @@ impl TpmTagged for TpmuSymKeyBits {
     type Value = ();
 }
 
-impl Default for TpmuSymKeyBits {
-    fn default() -> Self {
-        Self::Null
-    }
-}
-
 impl TpmSized for TpmuSymKeyBits {
     const SIZE: usize = core::mem::size_of::<u16>();
     fn len(&self) -> usize {
@@ -476,6 +803,53 @@ impl<'a> TpmParseTagged<'a> for TpmuSymKeyBits {
                 Ok((Self::Camellia(val), buf))
             }
             TpmAlgId::Null => Ok((Self::Null, buf)),
+            _ => Err(TpmErrorKind::InvalidValue.into()),
+        }
+    }
+}
+
+#[cfg(feature = "generate")]
+impl TpmGenerateTagged for TpmuSymKeyBits {
+    fn generate_tag(rng: &mut TpmRng) -> TpmAlgId {
+        const TAGS: [TpmAlgId; 4] = [
+            TpmAlgId::Aes,
+            TpmAlgId::Sm4,
+            TpmAlgId::Camellia,
+            TpmAlgId::Null,
+        ];
+        TAGS[rng.below(TAGS.len())]
+    }
+
+    fn generate_tagged(tag: TpmAlgId, rng: &mut TpmRng) -> Self {
+        match tag {
+            TpmAlgId::Aes => Self::Aes(u16::generate(rng)),
+            TpmAlgId::Sm4 => Self::Sm4(u16::generate(rng)),
+            TpmAlgId::Camellia => Self::Camellia(u16::generate(rng)),
+            _ => Self::Null,
+        }
+    }
+}
+
+impl TpmVisit for TpmuSymKeyBits {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        match self {
+            Self::Aes(bits) | Self::Sm4(bits) | Self::Camellia(bits) => bits.visit(field, visitor),
+            Self::Null => visitor.value(field, "TpmuSymKeyBits", TpmValue::Null),
+        }
+    }
+}
+
+impl TpmFromSourceTagged for TpmuSymKeyBits {
+    fn from_source_tagged(
+        tag: TpmAlgId,
+        field: &'static str,
+        source: &mut dyn TpmSource,
+    ) -> Result<Self, TpmErrorKind> {
+        match tag {
+            TpmAlgId::Aes => u16::from_source(field, source).map(Self::Aes),
+            TpmAlgId::Sm4 => u16::from_source(field, source).map(Self::Sm4),
+            TpmAlgId::Camellia => u16::from_source(field, source).map(Self::Camellia),
+            TpmAlgId::Null => source.null(field, "TpmuSymKeyBits").map(|()| Self::Null),
             _ => Err(TpmErrorKind::InvalidValue),
         }
     }
@@ -490,12 +864,13 @@ impl TpmBuild for TpmuSymKeyBits {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub enum TpmuSymMode {
     Aes(TpmAlgId),
     Sm4(TpmAlgId),
     Camellia(TpmAlgId),
     Xor,
+    #[default]
     Null,
 }
 
@@ -504,12 +879,6 @@ impl TpmTagged for TpmuSymMode {
     type Value = ();
 }
 
-impl Default for TpmuSymMode {
-    fn default() -> Self {
-        Self::Null
-    }
-}
-
 impl TpmSized for TpmuSymMode {
     const SIZE: usize = core::mem::size_of::<u16>();
     fn len(&self) -> usize {
@@ -537,6 +906,56 @@ impl<'a> TpmParseTagged<'a> for TpmuSymMode {
             }
             TpmAlgId::Xor => Ok((Self::Xor, buf)),
             TpmAlgId::Null => Ok((Self::Null, buf)),
+            _ => Err(TpmErrorKind::InvalidValue.into()),
+        }
+    }
+}
+
+#[cfg(feature = "generate")]
+impl TpmGenerateTagged for TpmuSymMode {
+    fn generate_tag(rng: &mut TpmRng) -> TpmAlgId {
+        const TAGS: [TpmAlgId; 5] = [
+            TpmAlgId::Aes,
+            TpmAlgId::Sm4,
+            TpmAlgId::Camellia,
+            TpmAlgId::Xor,
+            TpmAlgId::Null,
+        ];
+        TAGS[rng.below(TAGS.len())]
+    }
+
+    fn generate_tagged(tag: TpmAlgId, rng: &mut TpmRng) -> Self {
+        match tag {
+            TpmAlgId::Aes => Self::Aes(TpmAlgId::generate(rng)),
+            TpmAlgId::Sm4 => Self::Sm4(TpmAlgId::generate(rng)),
+            TpmAlgId::Camellia => Self::Camellia(TpmAlgId::generate(rng)),
+            TpmAlgId::Xor => Self::Xor,
+            _ => Self::Null,
+        }
+    }
+}
+
+impl TpmVisit for TpmuSymMode {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        match self {
+            Self::Aes(mode) | Self::Sm4(mode) | Self::Camellia(mode) => mode.visit(field, visitor),
+            Self::Xor | Self::Null => visitor.value(field, "TpmuSymMode", TpmValue::Null),
+        }
+    }
+}
+
+impl TpmFromSourceTagged for TpmuSymMode {
+    fn from_source_tagged(
+        tag: TpmAlgId,
+        field: &'static str,
+        source: &mut dyn TpmSource,
+    ) -> Result<Self, TpmErrorKind> {
+        match tag {
+            TpmAlgId::Aes => TpmAlgId::from_source(field, source).map(Self::Aes),
+            TpmAlgId::Sm4 => TpmAlgId::from_source(field, source).map(Self::Sm4),
+            TpmAlgId::Camellia => TpmAlgId::from_source(field, source).map(Self::Camellia),
+            TpmAlgId::Xor => source.null(field, "TpmuSymMode").map(|()| Self::Xor),
+            TpmAlgId::Null => source.null(field, "TpmuSymMode").map(|()| Self::Null),
             _ => Err(TpmErrorKind::InvalidValue),
         }
     }
@@ -551,6 +970,190 @@ impl TpmBuild for TpmuSymMode {
     }
 }
 
+/// The details of an asymmetric, signature or keyed hash scheme, selected by
+/// the scheme algorithm.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum TpmuAsymScheme {
+    /// A scheme parameterized by a hash algorithm, e.g. RSASSA, ECDSA or HMAC.
+    Hash(TpmAlgId),
+    Ecdaa {
+        hash_alg: TpmAlgId,
+        count: u16,
+    },
+    Xor {
+        hash_alg: TpmAlgId,
+        kdf: TpmAlgId,
+    },
+    /// A scheme without details, i.e. RSAES or NULL.
+    #[default]
+    Null,
+}
+
+impl TpmuAsymScheme {
+    const HASH_SCHEMES: [TpmAlgId; 9] = [
+        TpmAlgId::Rsassa,
+        TpmAlgId::Rsapss,
+        TpmAlgId::Oaep,
+        TpmAlgId::Ecdsa,
+        TpmAlgId::Sm2,
+        TpmAlgId::Ecschnorr,
+        TpmAlgId::Ecdh,
+        TpmAlgId::Ecmqv,
+        TpmAlgId::Hmac,
+    ];
+
+    /// Returns the hash algorithm of the scheme, if it has one.
+    #[must_use]
+    pub fn hash_alg(&self) -> Option<TpmAlgId> {
+        match self {
+            Self::Hash(hash_alg) | Self::Ecdaa { hash_alg, .. } | Self::Xor { hash_alg, .. } => {
+                Some(*hash_alg)
+            }
+            Self::Null => None,
+        }
+    }
+}
+
+impl TpmTagged for TpmuAsymScheme {
+    type Tag = TpmAlgId;
+    type Value = ();
+}
+
+impl TpmSized for TpmuAsymScheme {
+    const SIZE: usize = 2 * core::mem::size_of::<u16>();
+    fn len(&self) -> usize {
+        match self {
+            Self::Hash(hash_alg) => hash_alg.len(),
+            Self::Ecdaa { hash_alg, count } => hash_alg.len() + count.len(),
+            Self::Xor { hash_alg, kdf } => hash_alg.len() + kdf.len(),
+            Self::Null => 0,
+        }
+    }
+}
+
+impl<'a> TpmParseTagged<'a> for TpmuAsymScheme {
+    fn parse_tagged(tag: TpmAlgId, buf: &'a [u8]) -> TpmResult<(Self, &'a [u8])> {
+        match tag {
+            _ if Self::HASH_SCHEMES.contains(&tag) => {
+                let (hash_alg, buf) = TpmAlgId::parse(buf)?;
+                Ok((Self::Hash(hash_alg), buf))
+            }
+            TpmAlgId::Ecdaa => {
+                let (hash_alg, buf) = TpmAlgId::parse(buf)?;
+                let (count, buf) = u16::parse(buf)?;
+                Ok((Self::Ecdaa { hash_alg, count }, buf))
+            }
+            TpmAlgId::Xor => {
+                let (hash_alg, buf) = TpmAlgId::parse(buf)?;
+                let (kdf, buf) = TpmAlgId::parse(buf)?;
+                Ok((Self::Xor { hash_alg, kdf }, buf))
+            }
+            TpmAlgId::Rsaes | TpmAlgId::Null => Ok((Self::Null, buf)),
+            _ => Err(TpmErrorKind::InvalidValue.into()),
+        }
+    }
+}
+
+#[cfg(feature = "generate")]
+impl TpmGenerateTagged for TpmuAsymScheme {
+    fn generate_tag(rng: &mut TpmRng) -> TpmAlgId {
+        const TAGS: [TpmAlgId; 6] = [
+            TpmAlgId::Rsassa,
+            TpmAlgId::Ecdsa,
+            TpmAlgId::Ecdaa,
+            TpmAlgId::Xor,
+            TpmAlgId::Rsaes,
+            TpmAlgId::Null,
+        ];
+        TAGS[rng.below(TAGS.len())]
+    }
+
+    fn generate_tagged(tag: TpmAlgId, rng: &mut TpmRng) -> Self {
+        match tag {
+            TpmAlgId::Ecdaa => Self::Ecdaa {
+                hash_alg: TpmAlgId::generate(rng),
+                count: u16::generate(rng),
+            },
+            TpmAlgId::Xor => Self::Xor {
+                hash_alg: TpmAlgId::generate(rng),
+                kdf: TpmAlgId::generate(rng),
+            },
+            _ if Self::HASH_SCHEMES.contains(&tag) => Self::Hash(TpmAlgId::generate(rng)),
+            _ => Self::Null,
+        }
+    }
+}
+
+impl TpmVisit for TpmuAsymScheme {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        match self {
+            Self::Hash(hash_alg) => hash_alg.visit(field, visitor),
+            Self::Ecdaa { hash_alg, count } => {
+                visitor.enter(field, "TpmsSchemeEcdaa");
+                hash_alg.visit("hash_alg", visitor);
+                count.visit("count", visitor);
+                visitor.leave();
+            }
+            Self::Xor { hash_alg, kdf } => {
+                visitor.enter(field, "TpmsSchemeXor");
+                hash_alg.visit("hash_alg", visitor);
+                kdf.visit("kdf", visitor);
+                visitor.leave();
+            }
+            Self::Null => visitor.value(field, "TpmuAsymScheme", TpmValue::Null),
+        }
+    }
+}
+
+impl TpmFromSourceTagged for TpmuAsymScheme {
+    fn from_source_tagged(
+        tag: TpmAlgId,
+        field: &'static str,
+        source: &mut dyn TpmSource,
+    ) -> Result<Self, TpmErrorKind> {
+        match tag {
+            _ if Self::HASH_SCHEMES.contains(&tag) => {
+                TpmAlgId::from_source(field, source).map(Self::Hash)
+            }
+            TpmAlgId::Ecdaa => {
+                source.enter(field, "TpmsSchemeEcdaa")?;
+                let hash_alg = TpmAlgId::from_source("hash_alg", source)?;
+                let count = u16::from_source("count", source)?;
+                source.leave()?;
+                Ok(Self::Ecdaa { hash_alg, count })
+            }
+            TpmAlgId::Xor => {
+                source.enter(field, "TpmsSchemeXor")?;
+                let hash_alg = TpmAlgId::from_source("hash_alg", source)?;
+                let kdf = TpmAlgId::from_source("kdf", source)?;
+                source.leave()?;
+                Ok(Self::Xor { hash_alg, kdf })
+            }
+            TpmAlgId::Rsaes | TpmAlgId::Null => {
+                source.null(field, "TpmuAsymScheme").map(|()| Self::Null)
+            }
+            _ => Err(TpmErrorKind::InvalidValue),
+        }
+    }
+}
+
+impl TpmBuild for TpmuAsymScheme {
+    fn build(&self, writer: &mut TpmWriter) -> TpmResult<()> {
+        match self {
+            Self::Hash(hash_alg) => hash_alg.build(writer),
+            Self::Ecdaa { hash_alg, count } => {
+                hash_alg.build(writer)?;
+                count.build(writer)
+            }
+            Self::Xor { hash_alg, kdf } => {
+                hash_alg.build(writer)?;
+                kdf.build(writer)
+            }
+            Self::Null => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TpmuSignature {
     Rsassa(TpmsSignatureRsa),
@@ -623,6 +1226,71 @@ impl<'a> TpmParseTagged<'a> for TpmuSignature {
                 Ok((Self::Hmac(val), buf))
             }
             TpmAlgId::Null => Ok((Self::Null, buf)),
+            _ => Err(TpmErrorKind::InvalidValue.into()),
+        }
+    }
+}
+
+#[cfg(feature = "generate")]
+impl TpmGenerateTagged for TpmuSignature {
+    fn generate_tag(rng: &mut TpmRng) -> TpmAlgId {
+        const TAGS: [TpmAlgId; 8] = [
+            TpmAlgId::Rsassa,
+            TpmAlgId::Rsapss,
+            TpmAlgId::Ecdsa,
+            TpmAlgId::Ecdaa,
+            TpmAlgId::Sm2,
+            TpmAlgId::Ecschnorr,
+            TpmAlgId::Hmac,
+            TpmAlgId::Null,
+        ];
+        TAGS[rng.below(TAGS.len())]
+    }
+
+    fn generate_tagged(tag: TpmAlgId, rng: &mut TpmRng) -> Self {
+        match tag {
+            TpmAlgId::Rsassa => Self::Rsassa(TpmsSignatureRsa::generate(rng)),
+            TpmAlgId::Rsapss => Self::Rsapss(TpmsSignatureRsa::generate(rng)),
+            TpmAlgId::Ecdsa => Self::Ecdsa(TpmsSignatureEcc::generate(rng)),
+            TpmAlgId::Ecdaa => Self::Ecdaa(TpmsSignatureEcc::generate(rng)),
+            TpmAlgId::Sm2 => Self::Sm2(TpmsSignatureEcc::generate(rng)),
+            TpmAlgId::Ecschnorr => Self::Ecschnorr(TpmsSignatureEcc::generate(rng)),
+            TpmAlgId::Hmac => Self::Hmac(TpmtHa::generate(rng)),
+            _ => Self::Null,
+        }
+    }
+}
+
+impl TpmVisit for TpmuSignature {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        match self {
+            Self::Rsassa(sig) | Self::Rsapss(sig) => sig.visit(field, visitor),
+            Self::Ecdsa(sig) | Self::Ecdaa(sig) | Self::Sm2(sig) | Self::Ecschnorr(sig) => {
+                sig.visit(field, visitor);
+            }
+            Self::Hmac(ha) => ha.visit(field, visitor),
+            Self::Null => visitor.value(field, "TpmuSignature", TpmValue::Null),
+        }
+    }
+}
+
+impl TpmFromSourceTagged for TpmuSignature {
+    fn from_source_tagged(
+        tag: TpmAlgId,
+        field: &'static str,
+        source: &mut dyn TpmSource,
+    ) -> Result<Self, TpmErrorKind> {
+        match tag {
+            TpmAlgId::Rsassa => TpmsSignatureRsa::from_source(field, source).map(Self::Rsassa),
+            TpmAlgId::Rsapss => TpmsSignatureRsa::from_source(field, source).map(Self::Rsapss),
+            TpmAlgId::Ecdsa => TpmsSignatureEcc::from_source(field, source).map(Self::Ecdsa),
+            TpmAlgId::Ecdaa => TpmsSignatureEcc::from_source(field, source).map(Self::Ecdaa),
+            TpmAlgId::Sm2 => TpmsSignatureEcc::from_source(field, source).map(Self::Sm2),
+            TpmAlgId::Ecschnorr => {
+                TpmsSignatureEcc::from_source(field, source).map(Self::Ecschnorr)
+            }
+            TpmAlgId::Hmac => TpmtHa::from_source(field, source).map(Self::Hmac),
+            TpmAlgId::Null => source.null(field, "TpmuSignature").map(|()| Self::Null),
             _ => Err(TpmErrorKind::InvalidValue),
         }
     }
@@ -716,7 +1384,95 @@ impl<'a> TpmParseTagged<'a> for TpmuAttest {
                 type_name: "TpmuAttest",
                 expected: 0,
                 got: tag as u16,
-            }),
+            }
+            .into()),
+        }
+    }
+}
+
+#[cfg(feature = "generate")]
+impl TpmGenerateTagged for TpmuAttest {
+    fn generate_tag(rng: &mut TpmRng) -> crate::data::TpmSt {
+        const TAGS: [crate::data::TpmSt; 8] = [
+            crate::data::TpmSt::AttestCertify,
+            crate::data::TpmSt::AttestCreation,
+            crate::data::TpmSt::AttestQuote,
+            crate::data::TpmSt::AttestCommandAudit,
+            crate::data::TpmSt::AttestSessionAudit,
+            crate::data::TpmSt::AttestTime,
+            crate::data::TpmSt::AttestNv,
+            crate::data::TpmSt::AttestNvDigest,
+        ];
+        TAGS[rng.below(TAGS.len())]
+    }
+
+    fn generate_tagged(tag: crate::data::TpmSt, rng: &mut TpmRng) -> Self {
+        match tag {
+            crate::data::TpmSt::AttestCreation => Self::Creation(TpmsCreationInfo::generate(rng)),
+            crate::data::TpmSt::AttestQuote => Self::Quote(TpmsQuoteInfo::generate(rng)),
+            crate::data::TpmSt::AttestCommandAudit => {
+                Self::CommandAudit(TpmsCommandAuditInfo::generate(rng))
+            }
+            crate::data::TpmSt::AttestSessionAudit => {
+                Self::SessionAudit(TpmsSessionAuditInfo::generate(rng))
+            }
+            crate::data::TpmSt::AttestTime => Self::Time(TpmsTimeAttestInfo::generate(rng)),
+            crate::data::TpmSt::AttestNv => Self::Nv(TpmsNvCertifyInfo::generate(rng)),
+            crate::data::TpmSt::AttestNvDigest => {
+                Self::NvDigest(TpmsNvDigestCertifyInfo::generate(rng))
+            }
+            _ => Self::Certify(TpmsCertifyInfo::generate(rng)),
+        }
+    }
+}
+
+impl TpmVisit for TpmuAttest {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        match self {
+            Self::Certify(info) => info.visit(field, visitor),
+            Self::Creation(info) => info.visit(field, visitor),
+            Self::Quote(info) => info.visit(field, visitor),
+            Self::CommandAudit(info) => info.visit(field, visitor),
+            Self::SessionAudit(info) => info.visit(field, visitor),
+            Self::Time(info) => info.visit(field, visitor),
+            Self::Nv(info) => info.visit(field, visitor),
+            Self::NvDigest(info) => info.visit(field, visitor),
+        }
+    }
+}
+
+impl TpmFromSourceTagged for TpmuAttest {
+    fn from_source_tagged(
+        tag: crate::data::TpmSt,
+        field: &'static str,
+        source: &mut dyn TpmSource,
+    ) -> Result<Self, TpmErrorKind> {
+        match tag {
+            crate::data::TpmSt::AttestCertify => {
+                TpmsCertifyInfo::from_source(field, source).map(Self::Certify)
+            }
+            crate::data::TpmSt::AttestCreation => {
+                TpmsCreationInfo::from_source(field, source).map(Self::Creation)
+            }
+            crate::data::TpmSt::AttestQuote => {
+                TpmsQuoteInfo::from_source(field, source).map(Self::Quote)
+            }
+            crate::data::TpmSt::AttestCommandAudit => {
+                TpmsCommandAuditInfo::from_source(field, source).map(Self::CommandAudit)
+            }
+            crate::data::TpmSt::AttestSessionAudit => {
+                TpmsSessionAuditInfo::from_source(field, source).map(Self::SessionAudit)
+            }
+            crate::data::TpmSt::AttestTime => {
+                TpmsTimeAttestInfo::from_source(field, source).map(Self::Time)
+            }
+            crate::data::TpmSt::AttestNv => {
+                TpmsNvCertifyInfo::from_source(field, source).map(Self::Nv)
+            }
+            crate::data::TpmSt::AttestNvDigest => {
+                TpmsNvDigestCertifyInfo::from_source(field, source).map(Self::NvDigest)
+            }
+            _ => Err(TpmErrorKind::InvalidValue),
         }
     }
 }
@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// Copyright (c) 2025 Opinsys Oy
+
+//! Deterministic value generators and a round-trip checker.
+//!
+//! Every type produced by the marshalling macros, and every hand-written
+//! union, implements `TpmGenerate`. The generated values are valid encodings
+//! for which `parse(build(x)) == x` is expected to hold, which makes them
+//! usable as inputs for property tests without external crates.
+//!
+//! The module is available with the `generate` feature.
+
+use crate::{
+    data::TpmCc, message::ROUND_TRIP_MAP, TpmBuffer, TpmBuild, TpmList, TpmParse, TpmProtocolError,
+    TpmSized, TpmTagged, TpmWriter, TPM_MAX_COMMAND_SIZE,
+};
+use core::{fmt, mem::size_of};
+
+/// A seeded `SplitMix64` pseudo-random number generator.
+///
+/// The same seed always produces the same sequence of values on every
+/// platform.
+#[derive(Debug, Clone)]
+pub struct TpmRng {
+    state: u64,
+    max_len: usize,
+}
+
+impl TpmRng {
+    /// The default upper bound for generated buffer lengths and list counts.
+    pub const DEFAULT_MAX_LEN: usize = 32;
+
+    /// Creates a generator from a seed.
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            state: seed,
+            max_len: Self::DEFAULT_MAX_LEN,
+        }
+    }
+
+    /// Sets the upper bound for generated buffer lengths and list counts.
+    ///
+    /// The default bound keeps every generated message within
+    /// `TPM_MAX_COMMAND_SIZE`. With larger bounds, nested buffers may exceed
+    /// the limit of their enclosing size field.
+    #[must_use]
+    pub const fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Returns the next pseudo-random value.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in `0..bound`, or zero if `bound` is zero.
+    pub fn below(&mut self, bound: usize) -> usize {
+        let Ok(bound_u64) = u64::try_from(bound) else {
+            return 0;
+        };
+        if bound_u64 == 0 {
+            return 0;
+        }
+        usize::try_from(self.next_u64() % bound_u64).unwrap_or(0)
+    }
+
+    /// Returns a value in `start..=end`.
+    pub fn between(&mut self, start: u32, end: u32) -> u32 {
+        let span = u64::from(end.saturating_sub(start)) + 1;
+        let offset = u32::try_from(self.next_u64() % span).unwrap_or(0);
+        start.saturating_add(offset)
+    }
+
+    /// Returns `true` or `false` with equal probability.
+    pub fn coin(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+
+    /// Fills a slice with pseudo-random bytes.
+    pub fn fill(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(size_of::<u64>()) {
+            let value = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&value[..chunk.len()]);
+        }
+    }
+
+    /// Returns a length in `0..=capacity`, bounded by the configured maximum.
+    pub fn length(&mut self, capacity: usize) -> usize {
+        let bound = capacity.min(self.max_len);
+        self.below(bound.saturating_add(1))
+    }
+}
+
+/// Generates a pseudo-random value that survives a build and parse cycle.
+pub trait TpmGenerate: Sized {
+    /// Generates a value from the given generator.
+    fn generate(rng: &mut TpmRng) -> Self;
+}
+
+/// Generates the variants of a tagged union.
+pub trait TpmGenerateTagged: TpmTagged + Sized {
+    /// Picks a tag that selects one of the variants of the union.
+    fn generate_tag(rng: &mut TpmRng) -> Self::Tag;
+
+    /// Generates the variant selected by `tag`, which must be a tag returned
+    /// by `generate_tag()`.
+    fn generate_tagged(tag: Self::Tag, rng: &mut TpmRng) -> Self;
+}
+
+macro_rules! tpm_generate_integer {
+    ($ty:ty) => {
+        impl TpmGenerate for $ty {
+            fn generate(rng: &mut TpmRng) -> Self {
+                let bytes = rng.next_u64().to_le_bytes();
+                let mut value = [0u8; size_of::<$ty>()];
+                value.copy_from_slice(&bytes[..size_of::<$ty>()]);
+                <$ty>::from_le_bytes(value)
+            }
+        }
+    };
+}
+
+tpm_generate_integer!(u8);
+tpm_generate_integer!(i32);
+tpm_generate_integer!(u16);
+tpm_generate_integer!(u32);
+tpm_generate_integer!(u64);
+
+impl<const CAPACITY: usize> TpmGenerate for TpmBuffer<CAPACITY> {
+    fn generate(rng: &mut TpmRng) -> Self {
+        let mut buffer = Self::new();
+        let len = rng.length(CAPACITY.min(TPM_MAX_COMMAND_SIZE));
+        rng.fill(&mut buffer.bytes[..len]);
+        buffer.len = u16::try_from(len).unwrap_or(0);
+        buffer
+    }
+}
+
+impl<T: TpmGenerate + Copy + Default, const CAPACITY: usize> TpmGenerate for TpmList<T, CAPACITY> {
+    fn generate(rng: &mut TpmRng) -> Self {
+        let mut list = Self::new();
+        for _ in 0..rng.length(CAPACITY) {
+            if list.try_push(T::generate(rng)).is_err() {
+                break;
+            }
+        }
+        list
+    }
+}
+
+/// A failed round-trip check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TpmRoundTripError {
+    /// Building the value failed
+    Build(TpmProtocolError),
+    /// `len()` does not match the number of bytes written
+    Length { len: usize, written: usize },
+    /// Parsing the built bytes failed
+    Parse(TpmProtocolError),
+    /// The parser left bytes unconsumed
+    TrailingData(usize),
+    /// The parsed value differs from the original
+    Mismatch,
+}
+
+impl fmt::Display for TpmRoundTripError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Build(err) => write!(f, "build failed: {err}"),
+            Self::Length { len, written } => {
+                write!(f, "len() returned {len}, but {written} bytes were written")
+            }
+            Self::Parse(err) => write!(f, "parse failed: {err}"),
+            Self::TrailingData(count) => write!(f, "{count} trailing bytes after parsing"),
+            Self::Mismatch => write!(f, "parsed value differs from the original"),
+        }
+    }
+}
+
+/// Builds a value into `buf`, parses it back and compares the result.
+///
+/// Returns the number of bytes written.
+///
+/// # Errors
+///
+/// Returns a `TpmRoundTripError` describing the first property that did not
+/// hold.
+pub fn tpm_check_round_trip<T>(value: &T, buf: &mut [u8]) -> Result<usize, TpmRoundTripError>
+where
+    T: TpmBuild + TpmSized + for<'a> TpmParse<'a> + PartialEq,
+{
+    let written = {
+        let mut writer = TpmWriter::new(buf);
+        value.build(&mut writer).map_err(TpmRoundTripError::Build)?;
+        writer.len()
+    };
+    if value.len() != written {
+        return Err(TpmRoundTripError::Length {
+            len: value.len(),
+            written,
+        });
+    }
+    let (parsed, tail) = T::parse(&buf[..written]).map_err(TpmRoundTripError::Parse)?;
+    if !tail.is_empty() {
+        return Err(TpmRoundTripError::TrailingData(tail.len()));
+    }
+    if parsed != *value {
+        return Err(TpmRoundTripError::Mismatch);
+    }
+    Ok(written)
+}
+
+/// Generates and round-trips a command and its response.
+pub type TpmRoundTripCheck = fn(&mut TpmRng, &mut [u8]) -> Result<(), TpmRoundTripError>;
+
+/// Runs `tpm_check_round_trip()` for a generated command and response of
+/// every entry in the dispatch table.
+///
+/// # Errors
+///
+/// Returns the command code of the first failing entry together with the
+/// `TpmRoundTripError` describing the failure.
+#[allow(clippy::result_large_err)]
+pub fn tpm_check_dispatch(
+    rng: &mut TpmRng,
+    buf: &mut [u8],
+) -> Result<(), (TpmCc, TpmRoundTripError)> {
+    for (cc, check) in ROUND_TRIP_MAP {
+        check(rng, buf).map_err(|err| (*cc, err))?;
+    }
+    Ok(())
+}
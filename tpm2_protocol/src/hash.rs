@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// Copyright (c) 2025 Opinsys Oy
+// Copyright (c) 2024-2025 Jarkko Sakkinen
+
+//! Name, cpHash and rpHash computation over a caller-supplied hash function.
+
+use crate::{
+    data::{
+        Tpm2bDigest, Tpm2bName, TpmAlgId, TpmCc, TpmHt, TpmRc, TpmsNvPublic, TpmtPublic,
+        MAX_DIGEST_SIZE,
+    },
+    message::MAX_HANDLES,
+    tpm_hash_size, TpmBuild, TpmErrorKind, TpmResult, TpmSized, TpmWriter,
+};
+
+/// A hash function implementation supplied by the caller, such as a software
+/// library or a hardware engine.
+pub trait TpmHashProvider {
+    /// Computes the digest of the concatenation of `chunks` into `out` and
+    /// returns the length of the digest.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if `alg` is not supported.
+    fn digest(&self, alg: TpmAlgId, chunks: &[&[u8]], out: &mut [u8]) -> TpmResult<usize>;
+}
+
+pub(crate) fn digest<H: TpmHashProvider>(
+    hash: &H,
+    alg: TpmAlgId,
+    chunks: &[&[u8]],
+) -> TpmResult<Tpm2bDigest> {
+    let size = tpm_hash_size(&alg).ok_or(TpmErrorKind::InvalidValue)?;
+    let mut out = [0u8; MAX_DIGEST_SIZE];
+    let len = hash.digest(alg, chunks, &mut out[..size])?;
+    if len != size {
+        return Err(TpmErrorKind::InternalError.into());
+    }
+    Ok(Tpm2bDigest::try_from(&out[..len])?)
+}
+
+fn name_from_bytes<H: TpmHashProvider>(
+    hash: &H,
+    name_alg: TpmAlgId,
+    public: &[u8],
+) -> TpmResult<Tpm2bName> {
+    let digest = digest(hash, name_alg, &[public])?;
+    let digest: &[u8] = &digest;
+    let mut name = [0u8; 2 + MAX_DIGEST_SIZE];
+    name[..2].copy_from_slice(&(name_alg as u16).to_be_bytes());
+    name[2..2 + digest.len()].copy_from_slice(digest);
+    Ok(Tpm2bName::try_from(&name[..2 + digest.len()])?)
+}
+
+/// Computes the Name of a handle whose Name is the handle itself: PCRs,
+/// sessions and permanent handles.
+///
+/// # Errors
+///
+/// Returns `TpmErrorKind::InvalidHandle` for transient, persistent and NV
+/// index handles, as their Name is derived from their public area.
+pub fn tpm_handle_name(handle: u32) -> TpmResult<Tpm2bName> {
+    match TpmHt::from_handle(handle) {
+        Some(
+            TpmHt::Pcr | TpmHt::HmacSession | TpmHt::PolicySession | TpmHt::Permanent | TpmHt::Ac,
+        ) => Ok(Tpm2bName::try_from(&handle.to_be_bytes()[..])?),
+        _ => Err(TpmErrorKind::InvalidHandle {
+            type_name: "Tpm2bName",
+            handle,
+        }
+        .into()),
+    }
+}
+
+/// Computes the Name of an object: `nameAlg || H(TPMT_PUBLIC)`.
+///
+/// # Errors
+///
+/// Returns an error if the public area cannot be marshaled or the name
+/// algorithm is not supported.
+pub fn tpm_object_name<H: TpmHashProvider>(hash: &H, public: &TpmtPublic) -> TpmResult<Tpm2bName> {
+    let mut buf = [0u8; TpmtPublic::SIZE];
+    let len = {
+        let mut writer = TpmWriter::new(&mut buf);
+        public.build(&mut writer)?;
+        writer.len()
+    };
+    name_from_bytes(hash, public.name_alg, &buf[..len])
+}
+
+/// Computes the Name of an NV index: `nameAlg || H(TPMS_NV_PUBLIC)`.
+///
+/// # Errors
+///
+/// Returns an error if the public area cannot be marshaled or the name
+/// algorithm is not supported.
+pub fn tpm_nv_name<H: TpmHashProvider>(hash: &H, public: &TpmsNvPublic) -> TpmResult<Tpm2bName> {
+    let mut buf = [0u8; TpmsNvPublic::SIZE];
+    let len = {
+        let mut writer = TpmWriter::new(&mut buf);
+        public.build(&mut writer)?;
+        writer.len()
+    };
+    name_from_bytes(hash, public.name_alg, &buf[..len])
+}
+
+/// Computes the command parameter hash: `H(commandCode || names || parameters)`.
+///
+/// # Errors
+///
+/// Returns `TpmErrorKind::InvalidValue` if `alg` is not supported.
+pub fn tpm_cp_hash<H: TpmHashProvider>(
+    hash: &H,
+    alg: TpmAlgId,
+    cc: TpmCc,
+    names: &[&[u8]],
+    parameters: &[u8],
+) -> TpmResult<Tpm2bDigest> {
+    if names.len() > MAX_HANDLES {
+        return Err(TpmErrorKind::CapacityExceeded.into());
+    }
+    let cc_bytes = (cc as u32).to_be_bytes();
+    let mut chunks: [&[u8]; MAX_HANDLES + 2] = [&[]; MAX_HANDLES + 2];
+    chunks[0] = &cc_bytes;
+    chunks[1..=names.len()].copy_from_slice(names);
+    chunks[names.len() + 1] = parameters;
+    digest(hash, alg, &chunks[..names.len() + 2])
+}
+
+/// Computes the response parameter hash:
+/// `H(responseCode || commandCode || parameters)`.
+///
+/// # Errors
+///
+/// Returns `TpmErrorKind::InvalidValue` if `alg` is not supported.
+pub fn tpm_rp_hash<H: TpmHashProvider>(
+    hash: &H,
+    alg: TpmAlgId,
+    rc: TpmRc,
+    cc: TpmCc,
+    parameters: &[u8],
+) -> TpmResult<Tpm2bDigest> {
+    digest(
+        hash,
+        alg,
+        &[
+            &rc.value().to_be_bytes(),
+            &(cc as u32).to_be_bytes(),
+            parameters,
+        ],
+    )
+}
@@ -17,6 +17,25 @@
 //!
 //! * The crate must compile with GNU make and rustc without any external
 //!   dependencies.
+//!
+//! ## Buffer Sizes
+//!
+//! The fixed-capacity buffers are sized at compile-time. The defaults match
+//! the reference implementation, and the following features select other
+//! limits. When both a smaller and a larger limit are enabled, the larger one
+//! wins.
+//!
+//! * `command-size-2k`, `command-size-8k`: `TPM_MAX_COMMAND_SIZE`.
+//! * `buffer-size-2k`: `MAX_BUFFER_SIZE`.
+//! * `nv-buffer-size-2k`: `MAX_NV_BUFFER_SIZE`.
+//! * `rsa-2048`: `MAX_RSA_KEY_BYTES` for at most 2048-bit keys.
+//!
+//! ## Testing
+//!
+//! The `generate` feature enables the `generate` module, which provides
+//! deterministic value generators and round-trip checks for property tests.
+//! `make test` enables it for the test runner, and so does `cargo test` with
+//! `--features generate`.
 
 #![cfg_attr(not(test), no_std)]
 #![deny(unsafe_code)]
@@ -26,28 +45,281 @@
 #[macro_use]
 pub mod r#macro;
 pub mod data;
+#[cfg(feature = "generate")]
+pub mod generate;
+pub mod hash;
 pub mod message;
+pub mod metadata;
+pub mod policy;
+pub mod visit;
 
-use crate::data::TpmAlgId;
+use crate::data::{TpmAlgId, TpmHt, TpmRh, TPM_PCR_SELECT_MAX};
 use core::{convert::TryFrom, fmt, mem::size_of, ops::Deref, result::Result};
 
 tpm_handle!(
-    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-    TpmTransient
+    /// A transient object handle (`TPM_HT_TRANSIENT`).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    TpmTransient,
+    |handle| TpmHt::from_handle(handle) == Some(TpmHt::Transient),
+    TpmRh::TransientFirst as u32,
+    0x80FF_FFFF
+);
+tpm_handle!(
+    /// A session handle in an authorization area: an HMAC or policy session,
+    /// or `TPM_RS_PW`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    TpmSession,
+    |handle| handle == TpmRh::Password as u32
+        || matches!(
+            TpmHt::from_handle(handle),
+            Some(TpmHt::HmacSession | TpmHt::PolicySession)
+        ),
+    0x0200_0000,
+    0x03FF_FFFF
+);
+tpm_handle!(
+    /// A persistent object handle (`TPM_HT_PERSISTENT`).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    TpmPersistent,
+    |handle| TpmHt::from_handle(handle) == Some(TpmHt::Persistent),
+    TpmRh::PersistentFirst as u32,
+    0x81FF_FFFF
 );
 tpm_handle!(
+    /// A loaded object handle (`TPMI_DH_OBJECT`): a transient or a persistent
+    /// object.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    TpmObjectHandle,
+    |handle| matches!(
+        TpmHt::from_handle(handle),
+        Some(TpmHt::Transient | TpmHt::Persistent)
+    ),
+    TpmRh::TransientFirst as u32,
+    0x81FF_FFFF
+);
+tpm_handle!(
+    /// A handle of a context that can be saved, loaded and flushed
+    /// (`TPMI_DH_CONTEXT`): a transient object, or an HMAC or policy session.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    TpmContextHandle,
+    |handle| matches!(
+        TpmHt::from_handle(handle),
+        Some(TpmHt::Transient | TpmHt::HmacSession | TpmHt::PolicySession)
+    ),
+    0x0200_0000,
+    0x80FF_FFFF
+);
+tpm_handle!(
+    /// A PCR handle (`TPM_HT_PCR`) within the selectable PCR range.
     #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-    TpmSession
+    TpmPcr,
+    |handle| usize::try_from(handle).is_ok_and(|pcr| pcr < TPM_PCR_SELECT_MAX * 8),
+    0,
+    0xFF
 );
 tpm_handle!(
+    /// An NV index handle (`TPM_HT_NV_INDEX`).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    TpmNvIndex,
+    |handle| TpmHt::from_handle(handle) == Some(TpmHt::NvIndex),
+    0x0100_0000,
+    0x01FF_FFFF
+);
+tpm_handle!(
+    /// A permanent handle (`TPM_HT_PERMANENT`): a hierarchy, `TPM_RH_PLATFORM_NV`,
+    /// `TPM_RH_AUTH_xx` or `TPM_RH_ACT_x`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    TpmPermanent,
+    |handle| TpmHt::from_handle(handle) == Some(TpmHt::Permanent)
+        && (TpmRh::try_from(handle).is_ok()
+            || (TpmRh::AuthFirst as u32..=TpmRh::ActLast as u32).contains(&handle)),
+    TpmRh::Owner as u32,
+    TpmRh::ActLast as u32
+);
+tpm_handle!(
+    /// A handle in the handle area of a command. It is converted from one of
+    /// the typed handles, or checked with `TryFrom<u32>`.
     #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-    TpmPersistent
+    TpmHandle,
+    |handle| TpmHt::from_handle(handle).is_some(),
+    TpmRh::TransientFirst as u32,
+    0x80FF_FFFF
+);
+tpm_handle!(
+    /// An HMAC session handle (`TPM_HT_HMAC_SESSION`).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    TpmHmacSession,
+    |handle| TpmHt::from_handle(handle) == Some(TpmHt::HmacSession),
+    0x0200_0000,
+    0x02FF_FFFF
+);
+tpm_handle!(
+    /// A policy session handle (`TPM_HT_POLICY_SESSION`).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    TpmPolicySession,
+    |handle| TpmHt::from_handle(handle) == Some(TpmHt::PolicySession),
+    0x0300_0000,
+    0x03FF_FFFF
+);
+
+impl TpmPermanent {
+    /// `TPM_RH_OWNER`
+    pub const OWNER: Self = Self(TpmRh::Owner as u32);
+    /// `TPM_RH_NULL`
+    pub const NULL: Self = Self(TpmRh::Null as u32);
+    /// `TPM_RH_LOCKOUT`
+    pub const LOCKOUT: Self = Self(TpmRh::Lockout as u32);
+    /// `TPM_RH_ENDORSEMENT`
+    pub const ENDORSEMENT: Self = Self(TpmRh::Endorsement as u32);
+    /// `TPM_RH_PLATFORM`
+    pub const PLATFORM: Self = Self(TpmRh::Platform as u32);
+    /// `TPM_RH_PLATFORM_NV`
+    pub const PLATFORM_NV: Self = Self(TpmRh::PlatformNv as u32);
+
+    /// Returns the index `xx` of a `TPM_RH_AUTH_xx` handle.
+    #[must_use]
+    pub fn auth_index(self) -> Option<u8> {
+        let index = self.0.checked_sub(TpmRh::AuthFirst as u32)?;
+        u8::try_from(index).ok()
+    }
+
+    /// Returns the index `x` of a `TPM_RH_ACT_x` handle.
+    #[must_use]
+    pub fn act_index(self) -> Option<u8> {
+        if self.0 > TpmRh::ActLast as u32 {
+            return None;
+        }
+        let index = self.0.checked_sub(TpmRh::ActFirst as u32)?;
+        u8::try_from(index).ok()
+    }
+}
+
+impl TpmSession {
+    /// `TPM_RS_PW`
+    pub const PASSWORD: Self = Self(TpmRh::Password as u32);
+}
+
+impl Default for TpmNvIndex {
+    fn default() -> Self {
+        Self(u32::from(TpmHt::NvIndex as u8) << 24)
+    }
+}
+
+impl Default for TpmTransient {
+    fn default() -> Self {
+        Self(TpmRh::TransientFirst as u32)
+    }
+}
+
+impl Default for TpmSession {
+    fn default() -> Self {
+        Self::PASSWORD
+    }
+}
+
+impl Default for TpmPersistent {
+    fn default() -> Self {
+        Self(TpmRh::PersistentFirst as u32)
+    }
+}
+
+impl Default for TpmObjectHandle {
+    fn default() -> Self {
+        Self(TpmRh::TransientFirst as u32)
+    }
+}
+
+impl Default for TpmContextHandle {
+    fn default() -> Self {
+        Self(TpmRh::TransientFirst as u32)
+    }
+}
+
+macro_rules! tpm_handle_from {
+    ($($name:ident),*) => {
+        $(
+            impl From<$name> for TpmHandle {
+                fn from(handle: $name) -> Self {
+                    Self(u32::from(handle))
+                }
+            }
+        )*
+    };
+}
+
+tpm_handle_from!(
+    TpmTransient,
+    TpmPersistent,
+    TpmSession,
+    TpmPcr,
+    TpmNvIndex,
+    TpmPermanent,
+    TpmHmacSession,
+    TpmPolicySession,
+    TpmObjectHandle,
+    TpmContextHandle
 );
 
+impl From<TpmTransient> for TpmObjectHandle {
+    fn from(handle: TpmTransient) -> Self {
+        Self(handle.0)
+    }
+}
+
+impl From<TpmPersistent> for TpmObjectHandle {
+    fn from(handle: TpmPersistent) -> Self {
+        Self(handle.0)
+    }
+}
+
+impl From<TpmTransient> for TpmContextHandle {
+    fn from(handle: TpmTransient) -> Self {
+        Self(handle.0)
+    }
+}
+
+impl TryFrom<TpmSession> for TpmContextHandle {
+    type Error = TpmErrorKind;
+
+    /// Fails for `TPM_RS_PW`, which is not a context.
+    fn try_from(handle: TpmSession) -> Result<Self, Self::Error> {
+        Self::try_from(handle.0)
+    }
+}
+
+impl From<TpmHmacSession> for TpmSession {
+    fn from(handle: TpmHmacSession) -> Self {
+        Self(handle.0)
+    }
+}
+
+impl From<TpmPolicySession> for TpmSession {
+    fn from(handle: TpmPolicySession) -> Self {
+        Self(handle.0)
+    }
+}
+
+impl TpmSession {
+    /// Returns the handle type of the session, or `None` if it is not a session
+    /// handle.
+    #[must_use]
+    pub fn handle_type(self) -> Option<TpmHt> {
+        TpmHt::from_handle(self.0)
+            .filter(|ht| matches!(ht, TpmHt::HmacSession | TpmHt::PolicySession))
+    }
+}
+
+/// The maximum size of a TPM command or response buffer.
+#[cfg(feature = "command-size-8k")]
+pub const TPM_MAX_COMMAND_SIZE: usize = 8192;
+/// The maximum size of a TPM command or response buffer.
+#[cfg(all(feature = "command-size-2k", not(feature = "command-size-8k")))]
+pub const TPM_MAX_COMMAND_SIZE: usize = 2048;
 /// The maximum size of a TPM command or response buffer.
+#[cfg(not(any(feature = "command-size-2k", feature = "command-size-8k")))]
 pub const TPM_MAX_COMMAND_SIZE: usize = 4096;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TpmErrorKind {
     /// Insufficient amount of bytes available
     Boundary,
@@ -65,6 +337,11 @@ pub enum TpmErrorKind {
     },
     /// Invalid value
     InvalidValue,
+    /// A handle outside of the range of its handle type
+    InvalidHandle {
+        type_name: &'static str,
+        handle: u32,
+    },
     /// A size or count in the buffer is larger than the maximum allowed value
     ValueTooLarge,
     /// An operation would exceed the fixed capacity of a container
@@ -100,6 +377,9 @@ impl fmt::Display for TpmErrorKind {
                 )
             }
             Self::InvalidValue => write!(f, "A value is invalid or out of the expected range"),
+            Self::InvalidHandle { type_name, handle } => {
+                write!(f, "Invalid handle 0x{handle:08x} for type '{type_name}'")
+            }
             Self::ValueTooLarge => {
                 write!(
                     f,
@@ -119,7 +399,140 @@ impl From<core::num::TryFromIntError> for TpmErrorKind {
     }
 }
 
-pub type TpmResult<T> = Result<T, TpmErrorKind>;
+/// The maximum number of struct fields recorded in a `TpmProtocolError`.
+pub const TPM_ERROR_PATH_MAX: usize = 4;
+
+/// An error with the location where it was detected.
+///
+/// The parsers generated by `tpm_struct!`, `tpm_response!` and `tpm2b_struct!`
+/// record the chain of `Type.field` names leading to the failure, and the
+/// position of the innermost failing field. The position is converted to a byte
+/// offset by `at_input()`, which `tpm_parse_command()` and `tpm_parse_response()`
+/// call on the complete message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TpmProtocolError {
+    kind: TpmErrorKind,
+    path: [&'static str; TPM_ERROR_PATH_MAX],
+    depth: u8,
+    position: TpmErrorPosition,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TpmErrorPosition {
+    Unknown,
+    Remaining(u32),
+    Offset(u32),
+}
+
+impl TpmProtocolError {
+    /// Returns the kind of the error.
+    #[must_use]
+    pub fn kind(&self) -> &TpmErrorKind {
+        &self.kind
+    }
+
+    /// Returns the byte offset of the failing field within the input.
+    #[must_use]
+    pub fn offset(&self) -> Option<usize> {
+        match self.position {
+            TpmErrorPosition::Offset(offset) => Some(offset as usize),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the path has more entries than could be recorded.
+    #[must_use]
+    pub fn is_truncated(&self) -> bool {
+        self.depth as usize > TPM_ERROR_PATH_MAX
+    }
+
+    /// Returns the recorded `Type.field` entries, outermost first.
+    pub fn path(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let len = (self.depth as usize).min(TPM_ERROR_PATH_MAX);
+        self.path[..len].iter().rev().copied()
+    }
+
+    /// Records a `Type.field` entry and the number of bytes left in the input
+    /// at the start of the field.
+    ///
+    /// Entries are pushed from the innermost field outwards, and only the
+    /// innermost position is kept.
+    #[must_use]
+    pub fn in_field(mut self, field: &'static str, remaining: usize) -> Self {
+        let depth = self.depth as usize;
+        if depth < TPM_ERROR_PATH_MAX {
+            self.path[depth] = field;
+        }
+        self.depth = self.depth.saturating_add(1);
+        if self.position == TpmErrorPosition::Unknown {
+            self.position =
+                TpmErrorPosition::Remaining(u32::try_from(remaining).unwrap_or(u32::MAX));
+        }
+        self
+    }
+
+    /// Adjusts the position for an error that occurred in a sub-slice followed
+    /// by `tail_len` bytes of the enclosing buffer.
+    #[must_use]
+    pub fn in_slice(mut self, tail_len: usize) -> Self {
+        if let TpmErrorPosition::Remaining(remaining) = &mut self.position {
+            *remaining = remaining.saturating_add(u32::try_from(tail_len).unwrap_or(u32::MAX));
+        }
+        self
+    }
+
+    /// Resolves the recorded position into a byte offset within an input of
+    /// `input_len` bytes.
+    #[must_use]
+    pub fn at_input(mut self, input_len: usize) -> Self {
+        if let TpmErrorPosition::Remaining(remaining) = self.position {
+            let offset = input_len.saturating_sub(remaining as usize);
+            self.position = TpmErrorPosition::Offset(u32::try_from(offset).unwrap_or(u32::MAX));
+        }
+        self
+    }
+}
+
+impl From<TpmErrorKind> for TpmProtocolError {
+    fn from(kind: TpmErrorKind) -> Self {
+        Self {
+            kind,
+            path: [""; TPM_ERROR_PATH_MAX],
+            depth: 0,
+            position: TpmErrorPosition::Unknown,
+        }
+    }
+}
+
+impl From<core::num::TryFromIntError> for TpmProtocolError {
+    fn from(err: core::num::TryFromIntError) -> Self {
+        TpmErrorKind::from(err).into()
+    }
+}
+
+impl fmt::Display for TpmProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        let mut path = self.path();
+        if let Some(first) = path.next() {
+            write!(f, " in ")?;
+            if self.is_truncated() {
+                write!(f, "...")?;
+            }
+            write!(f, "{first}")?;
+            for entry in path {
+                let field = entry.split_once('.').map_or(entry, |(_, field)| field);
+                write!(f, ".{field}")?;
+            }
+        }
+        if let Some(offset) = self.offset() {
+            write!(f, " at offset {offset}")?;
+        }
+        Ok(())
+    }
+}
+
+pub type TpmResult<T> = Result<T, TpmProtocolError>;
 
 /// Writes into a mutable byte slice.
 pub struct TpmWriter<'a> {
@@ -155,7 +568,7 @@ impl<'a> TpmWriter<'a> {
     pub fn write_bytes(&mut self, bytes: &[u8]) -> TpmResult<()> {
         let end = self.cursor + bytes.len();
         if end > self.buffer.len() {
-            return Err(TpmErrorKind::Boundary);
+            return Err(TpmErrorKind::Boundary.into());
         }
         self.buffer[self.cursor..end].copy_from_slice(bytes);
         self.cursor = end;
@@ -250,7 +663,7 @@ macro_rules! tpm_integer {
             fn parse(buf: &'a [u8]) -> TpmResult<(Self, &'a [u8])> {
                 let size = size_of::<$ty>();
                 if buf.len() < size {
-                    return Err(TpmErrorKind::Boundary);
+                    return Err(TpmErrorKind::Boundary.into());
                 }
                 let (bytes, buf) = buf.split_at(size);
                 let array = bytes.try_into().map_err(|_| TpmErrorKind::InternalError)?;
@@ -302,11 +715,11 @@ pub fn parse_tpm2b(buf: &[u8]) -> TpmResult<(&[u8], &[u8])> {
     let size = size as usize;
 
     if size > TPM_MAX_COMMAND_SIZE {
-        return Err(TpmErrorKind::ValueTooLarge);
+        return Err(TpmErrorKind::ValueTooLarge.into());
     }
 
     if buf.len() < size {
-        return Err(TpmErrorKind::Boundary);
+        return Err(TpmErrorKind::Boundary.into());
     }
     Ok(buf.split_at(size))
 }
@@ -370,7 +783,7 @@ impl<'a, const CAPACITY: usize> TpmParse<'a> for TpmBuffer<CAPACITY> {
     fn parse(buf: &'a [u8]) -> TpmResult<(Self, &'a [u8])> {
         let (bytes, remainder) = parse_tpm2b(buf)?;
         if bytes.len() > CAPACITY {
-            return Err(TpmErrorKind::ValueTooLarge);
+            return Err(TpmErrorKind::ValueTooLarge.into());
         }
         let mut buffer = Self::new();
         buffer.bytes[..bytes.len()].copy_from_slice(bytes);
@@ -485,13 +898,13 @@ impl<'a, T: TpmParse<'a> + Copy + Default, const CAPACITY: usize> TpmParse<'a>
         let (count, mut buf) = u32::parse(buf)?;
         let count_usize = count as usize;
         if count_usize > CAPACITY {
-            return Err(TpmErrorKind::ValueTooLarge);
+            return Err(TpmErrorKind::ValueTooLarge.into());
         }
 
         let mut list = Self::new();
         for i in 0..count_usize {
             if buf.is_empty() {
-                return Err(TpmErrorKind::Boundary);
+                return Err(TpmErrorKind::Boundary.into());
             }
             let (item, rest) = T::parse(buf)?;
             list.items[i] = item;
@@ -525,11 +938,11 @@ impl<'a> TpmParameters<'a> {
         let size = size as usize;
 
         if size > crate::TPM_MAX_COMMAND_SIZE {
-            return Err(TpmErrorKind::ValueTooLarge);
+            return Err(TpmErrorKind::ValueTooLarge.into());
         }
 
         if buf.len() < size {
-            return Err(TpmErrorKind::Boundary);
+            return Err(TpmErrorKind::Boundary.into());
         }
         let (param_data, buf) = buf.split_at(size);
         Ok((Self { buf: param_data }, buf))
@@ -546,6 +959,12 @@ impl<'a> TpmParameters<'a> {
         Ok(value)
     }
 
+    /// Returns the number of bytes left in the parameter buffer.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
     /// Checks if the entire parameter buffer has been consumed.
     #[must_use]
     pub fn is_empty(&self) -> bool {
@@ -45,6 +45,11 @@ macro_rules! tpm_bitflags {
                 (self.0 & other.0) == other.0
             }
 
+            #[must_use]
+            pub const fn intersects(&self, other: Self) -> bool {
+                (self.0 & other.0) != 0
+            }
+
             pub fn flag_names(&self) -> impl Iterator<Item = &'static str> + '_ {
                 [
                     $(
@@ -89,6 +94,42 @@ macro_rules! tpm_bitflags {
                 Self::SIZE
             }
         }
+
+        #[cfg(feature = "generate")]
+        impl $crate::generate::TpmGenerate for $name {
+            fn generate(rng: &mut $crate::generate::TpmRng) -> Self {
+                Self(<$repr as $crate::generate::TpmGenerate>::generate(rng))
+            }
+        }
+
+        impl $crate::visit::TpmVisit for $name {
+            #[allow(clippy::cast_lossless)]
+            fn visit(&self, field: &'static str, visitor: &mut dyn $crate::visit::TpmVisitor) {
+                const NAMES: &[(u64, &str)] = &[$(($name::$field.0 as u64, $string_name),)*];
+                visitor.value(
+                    field,
+                    stringify!($name),
+                    $crate::visit::TpmValue::Flags {
+                        bits: self.0 as u64,
+                        names: NAMES,
+                    },
+                );
+            }
+        }
+
+        impl $crate::visit::TpmFromSource for $name {
+            #[allow(clippy::cast_lossless)]
+            fn from_source(
+                field: &'static str,
+                source: &mut dyn $crate::visit::TpmSource,
+            ) -> Result<Self, $crate::TpmErrorKind> {
+                const NAMES: &[(u64, &str)] = &[$(($name::$field.0 as u64, $string_name),)*];
+                let bits = source.flags(field, stringify!($name), NAMES)?;
+                <$repr>::try_from(bits)
+                    .map(Self)
+                    .map_err(|_| $crate::TpmErrorKind::ValueTooLarge)
+            }
+        }
     };
 }
 
@@ -128,7 +169,7 @@ macro_rules! tpm_bool {
                     _ => Err($crate::TpmErrorKind::InvalidDiscriminant {
                         type_name: stringify!($name),
                         value: u64::from(val),
-                    }),
+                    }.into()),
                 }
             }
         }
@@ -139,6 +180,28 @@ macro_rules! tpm_bool {
                 Self::SIZE
             }
         }
+
+        #[cfg(feature = "generate")]
+        impl $crate::generate::TpmGenerate for $name {
+            fn generate(rng: &mut $crate::generate::TpmRng) -> Self {
+                Self(rng.coin())
+            }
+        }
+
+        impl $crate::visit::TpmVisit for $name {
+            fn visit(&self, field: &'static str, visitor: &mut dyn $crate::visit::TpmVisitor) {
+                visitor.value(field, stringify!($name), $crate::visit::TpmValue::Bool(self.0));
+            }
+        }
+
+        impl $crate::visit::TpmFromSource for $name {
+            fn from_source(
+                field: &'static str,
+                source: &mut dyn $crate::visit::TpmSource,
+            ) -> Result<Self, $crate::TpmErrorKind> {
+                source.boolean(field, stringify!($name)).map(Self)
+            }
+        }
     };
 }
 
@@ -171,6 +234,7 @@ macro_rules! tpm_dispatch {
         }
 
         /// A TPM command
+        #[allow(clippy::large_enum_variant)]
         #[derive(Debug, PartialEq, Eq, Clone)]
         pub enum TpmCommandBody {
             $( $variant($cmd), )*
@@ -201,6 +265,37 @@ macro_rules! tpm_dispatch {
             )*
         }
 
+        impl $crate::visit::TpmVisit for TpmCommandBody {
+            fn visit(&self, field: &'static str, visitor: &mut dyn $crate::visit::TpmVisitor) {
+                match self {
+                    $( Self::$variant(cmd) => $crate::visit::TpmVisit::visit(cmd, field, visitor), )*
+                }
+            }
+        }
+
+        impl $crate::visit::TpmVisit for TpmResponseBody {
+            fn visit(&self, field: &'static str, visitor: &mut dyn $crate::visit::TpmVisitor) {
+                match self {
+                    $( Self::$variant(resp) => $crate::visit::TpmVisit::visit(resp, field, visitor), )*
+                }
+            }
+        }
+
+        $(
+            impl TryFrom<TpmResponseBody> for $resp {
+                type Error = TpmResponseBody;
+
+                #[allow(clippy::result_large_err)]
+                fn try_from(body: TpmResponseBody) -> Result<Self, TpmResponseBody> {
+                    body.$variant()
+                }
+            }
+
+            impl $crate::message::TpmCommand for $cmd {
+                type Response = $resp;
+            }
+        )*
+
         pub type TpmCommandParser = for<'a> fn(&'a [u8]) -> $crate::TpmResult<(TpmCommandBody, &'a [u8])>;
         pub type TpmResponseParser = for<'a> fn(&'a [u8]) -> $crate::TpmResult<(TpmResponseBody, &'a [u8])>;
 
@@ -210,6 +305,18 @@ macro_rules! tpm_dispatch {
         pub(crate) static PARSE_RESPONSE_MAP: &[($crate::data::TpmCc, bool, TpmResponseParser)] =
             &[$(tpm_response_parser!($resp, $variant),)*];
 
+        #[cfg(feature = "generate")]
+        pub(crate) static ROUND_TRIP_MAP: &[($crate::data::TpmCc, $crate::generate::TpmRoundTripCheck)] =
+            &[$((
+                <$cmd as $crate::message::TpmHeader>::COMMAND,
+                |rng, buf| {
+                    let cmd = <$cmd as $crate::generate::TpmGenerate>::generate(rng);
+                    $crate::generate::tpm_check_round_trip(&cmd, buf)?;
+                    let resp = <$resp as $crate::generate::TpmGenerate>::generate(rng);
+                    $crate::generate::tpm_check_round_trip(&resp, buf).map(|_| ())
+                },
+            ),)*];
+
         const _: () = {
             let mut i = 1;
             while i < PARSE_COMMAND_MAP.len() {
@@ -252,6 +359,11 @@ macro_rules! tpm_enum {
             ),*
         }
 
+        impl $name {
+            /// All variants in declaration order.
+            pub const VARIANTS: &'static [Self] = &[$(Self::$variant),*];
+        }
+
         impl TryFrom<$repr> for $name {
             type Error = ();
 
@@ -310,6 +422,48 @@ macro_rules! tpm_enum {
                 Ok((enum_val, buf))
             }
         }
+
+        #[cfg(feature = "generate")]
+        impl $crate::generate::TpmGenerate for $name {
+            fn generate(rng: &mut $crate::generate::TpmRng) -> Self {
+                Self::VARIANTS[rng.below(Self::VARIANTS.len())]
+            }
+        }
+
+        impl $crate::visit::TpmVisit for $name {
+            #[allow(clippy::cast_lossless)]
+            fn visit(&self, field: &'static str, visitor: &mut dyn $crate::visit::TpmVisitor) {
+                let name = match self {
+                    $(Self::$variant => $display),*
+                };
+                visitor.value(
+                    field,
+                    stringify!($name),
+                    $crate::visit::TpmValue::Enum {
+                        name,
+                        value: *self as $repr as u64,
+                    },
+                );
+            }
+        }
+
+        impl $crate::visit::TpmFromSource for $name {
+            #[allow(clippy::cast_lossless)]
+            fn from_source(
+                field: &'static str,
+                source: &mut dyn $crate::visit::TpmSource,
+            ) -> Result<Self, $crate::TpmErrorKind> {
+                const NAMES: &[(u64, &str)] = &[$(($name::$variant as $repr as u64, $display)),*];
+                let value = source.enumeration(field, stringify!($name), NAMES)?;
+                <$repr>::try_from(value)
+                    .ok()
+                    .and_then(|value| Self::try_from(value).ok())
+                    .ok_or($crate::TpmErrorKind::InvalidDiscriminant {
+                        type_name: stringify!($name),
+                        value,
+                    })
+            }
+        }
     };
 }
 
@@ -317,14 +471,39 @@ macro_rules! tpm_enum {
 macro_rules! tpm_handle {
     (
         $(#[$meta:meta])*
-        $name:ident
+        $name:ident,
+        |$handle:ident| $valid:expr,
+        $first:expr, $last:expr
     ) => {
         $(#[$meta])*
-        pub struct $name(pub u32);
+        pub struct $name(u32);
 
-        impl From<u32> for $name {
-            fn from(val: u32) -> Self {
-                Self(val)
+        impl $name {
+            /// Returns `true` if the handle is within the range of this handle type.
+            #[must_use]
+            pub fn is_valid($handle: u32) -> bool {
+                $valid
+            }
+
+            /// Returns the raw handle value.
+            #[must_use]
+            pub const fn value(self) -> u32 {
+                self.0
+            }
+        }
+
+        impl TryFrom<u32> for $name {
+            type Error = $crate::TpmErrorKind;
+
+            fn try_from(handle: u32) -> Result<Self, Self::Error> {
+                if Self::is_valid(handle) {
+                    Ok(Self(handle))
+                } else {
+                    Err($crate::TpmErrorKind::InvalidHandle {
+                        type_name: stringify!($name),
+                        handle,
+                    })
+                }
             }
         }
 
@@ -343,7 +522,37 @@ macro_rules! tpm_handle {
         impl<'a> $crate::TpmParse<'a> for $name {
             fn parse(buf: &'a [u8]) -> $crate::TpmResult<(Self, &'a [u8])> {
                 let (val, buf) = u32::parse(buf)?;
-                Ok((Self(val), buf))
+                Ok((Self::try_from(val)?, buf))
+            }
+        }
+
+        #[cfg(feature = "generate")]
+        impl $crate::generate::TpmGenerate for $name {
+            /// Draws candidates from the declared range until one is valid,
+            /// and falls back to the start of the range.
+            fn generate(rng: &mut $crate::generate::TpmRng) -> Self {
+                for _ in 0..64 {
+                    let handle = rng.between($first, $last);
+                    if Self::is_valid(handle) {
+                        return Self(handle);
+                    }
+                }
+                Self($first)
+            }
+        }
+
+        impl $crate::visit::TpmVisit for $name {
+            fn visit(&self, field: &'static str, visitor: &mut dyn $crate::visit::TpmVisitor) {
+                visitor.value(field, stringify!($name), $crate::visit::TpmValue::Handle(self.0));
+            }
+        }
+
+        impl $crate::visit::TpmFromSource for $name {
+            fn from_source(
+                field: &'static str,
+                source: &mut dyn $crate::visit::TpmSource,
+            ) -> Result<Self, $crate::TpmErrorKind> {
+                Self::try_from(source.handle(field, stringify!($name))?)
             }
         }
 
@@ -411,16 +620,45 @@ macro_rules! tpm2b_struct {
         impl<'a> $crate::TpmParse<'a> for $wrapper_ty {
             fn parse(buf: &'a [u8]) -> $crate::TpmResult<(Self, &'a [u8])> {
                 let (inner_bytes, rest) = $crate::parse_tpm2b(buf)?;
-                let (inner_val, tail) = <$inner_ty>::parse(inner_bytes)?;
+                let (inner_val, tail) = <$inner_ty>::parse(inner_bytes).map_err(|e| {
+                    e.in_field(concat!(stringify!($wrapper_ty), ".inner"), inner_bytes.len())
+                        .in_slice(rest.len())
+                })?;
 
                 if !tail.is_empty() {
-                    return Err($crate::TpmErrorKind::TrailingData);
+                    return Err($crate::TpmErrorKind::TrailingData.into());
                 }
 
                 Ok((Self { inner: inner_val }, rest))
             }
         }
 
+        #[cfg(feature = "generate")]
+        impl $crate::generate::TpmGenerate for $wrapper_ty {
+            fn generate(rng: &mut $crate::generate::TpmRng) -> Self {
+                Self {
+                    inner: <$inner_ty as $crate::generate::TpmGenerate>::generate(rng),
+                }
+            }
+        }
+
+        impl $crate::visit::TpmVisit for $wrapper_ty {
+            fn visit(&self, field: &'static str, visitor: &mut dyn $crate::visit::TpmVisitor) {
+                $crate::visit::TpmVisit::visit(&self.inner, field, visitor);
+            }
+        }
+
+        impl $crate::visit::TpmFromSource for $wrapper_ty {
+            fn from_source(
+                field: &'static str,
+                source: &mut dyn $crate::visit::TpmSource,
+            ) -> Result<Self, $crate::TpmErrorKind> {
+                Ok(Self {
+                    inner: <$inner_ty as $crate::visit::TpmFromSource>::from_source(field, source)?,
+                })
+            }
+        }
+
         impl From<$inner_ty> for $wrapper_ty {
             fn from(inner: $inner_ty) -> Self {
                 Self { inner }
@@ -22,11 +22,12 @@ macro_rules! tpm_response {
             $(pub $param_field: $param_type,)*
         }
 
-        impl $crate::message::TpmHeader<'_> for $name {
+        impl $crate::message::TpmHeader for $name {
             const COMMAND: $crate::data::TpmCc = $cc;
             const NO_SESSIONS: bool = $no_sessions;
             const WITH_SESSIONS: bool = $with_sessions;
             const HANDLES: usize = 0 $(+ {let _ = stringify!($handle_field); 1})*;
+            type Handles = [$crate::TpmHandle; 0 $(+ {let _ = stringify!($handle_field); 1})*];
         }
 
         impl $crate::TpmSized for $name {
@@ -58,17 +59,31 @@ macro_rules! tpm_response {
                 #[allow(unused_mut)]
                 let mut cursor = buf;
                 $(
-                    let ($handle_field, tail) = <$handle_type>::parse(cursor)?;
+                    let ($handle_field, tail) = <$handle_type>::parse(cursor).map_err(|e| {
+                        e.in_field(
+                            concat!(stringify!($name), ".", stringify!($handle_field)),
+                            cursor.len(),
+                        )
+                    })?;
                     cursor = tail;
                 )*
 
                 #[allow(unused_mut)]
                 let (mut params, tail) = $crate::TpmParameters::new(cursor)?;
                 $(
-                    let $param_field = params.parse::<$param_type>()?;
+                    let $param_field = {
+                        let remaining = params.len();
+                        params.parse::<$param_type>().map_err(|e| {
+                            e.in_field(
+                                concat!(stringify!($name), ".", stringify!($param_field)),
+                                remaining,
+                            )
+                            .in_slice(tail.len())
+                        })?
+                    };
                 )*
                 if !params.is_empty() {
-                    return Err($crate::TpmErrorKind::TrailingData);
+                    return Err($crate::TpmErrorKind::TrailingData.into());
                 }
 
                 Ok((
@@ -80,5 +95,51 @@ macro_rules! tpm_response {
                 ))
             }
         }
+
+        #[cfg(feature = "generate")]
+        impl $crate::generate::TpmGenerate for $name {
+            #[allow(unused_variables)]
+            fn generate(rng: &mut $crate::generate::TpmRng) -> Self {
+                Self {
+                    $( $handle_field: <$handle_type as $crate::generate::TpmGenerate>::generate(rng), )*
+                    $( $param_field: <$param_type as $crate::generate::TpmGenerate>::generate(rng), )*
+                }
+            }
+        }
+
+        impl $crate::visit::TpmVisit for $name {
+            fn visit(&self, field: &'static str, visitor: &mut dyn $crate::visit::TpmVisitor) {
+                visitor.enter(field, stringify!($name));
+                $( $crate::visit::TpmVisit::visit(&self.$handle_field, stringify!($handle_field), visitor); )*
+                $( $crate::visit::TpmVisit::visit(&self.$param_field, stringify!($param_field), visitor); )*
+                visitor.leave();
+            }
+        }
+
+        impl $crate::visit::TpmFromSource for $name {
+            fn from_source(
+                field: &'static str,
+                source: &mut dyn $crate::visit::TpmSource,
+            ) -> Result<Self, $crate::TpmErrorKind> {
+                source.enter(field, stringify!($name))?;
+                $(
+                    let $handle_field = <$handle_type as $crate::visit::TpmFromSource>::from_source(
+                        stringify!($handle_field),
+                        source,
+                    )?;
+                )*
+                $(
+                    let $param_field = <$param_type as $crate::visit::TpmFromSource>::from_source(
+                        stringify!($param_field),
+                        source,
+                    )?;
+                )*
+                source.leave()?;
+                Ok(Self {
+                    $( $handle_field, )*
+                    $( $param_field, )*
+                })
+            }
+        }
     };
 }
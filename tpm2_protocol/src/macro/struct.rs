@@ -23,11 +23,12 @@ macro_rules! tpm_struct {
             }
         }
 
-        impl $crate::message::TpmHeader<'_> for $name {
+        impl $crate::message::TpmHeader for $name {
             const COMMAND: $crate::data::TpmCc = $cc;
             const NO_SESSIONS: bool = $no_sessions;
             const WITH_SESSIONS: bool = $with_sessions;
             const HANDLES: usize = $handles;
+            type Handles = [$crate::TpmHandle; $handles];
         }
     };
 
@@ -60,7 +61,14 @@ macro_rules! tpm_struct {
 
         impl<'a> $crate::TpmParse<'a> for $name {
             fn parse(buf: &'a [u8]) -> $crate::TpmResult<(Self, &'a [u8])> {
-                $(let ($field_name, buf) = <$field_type>::parse(buf)?;)*
+                $(
+                    let ($field_name, buf) = <$field_type>::parse(buf).map_err(|e| {
+                        e.in_field(
+                            concat!(stringify!($name), ".", stringify!($field_name)),
+                            buf.len(),
+                        )
+                    })?;
+                )*
                 Ok((
                     Self {
                         $($field_name,)*
@@ -69,6 +77,43 @@ macro_rules! tpm_struct {
                 ))
             }
         }
+
+        #[cfg(feature = "generate")]
+        impl $crate::generate::TpmGenerate for $name {
+            #[allow(unused_variables)]
+            fn generate(rng: &mut $crate::generate::TpmRng) -> Self {
+                Self {
+                    $($field_name: <$field_type as $crate::generate::TpmGenerate>::generate(rng),)*
+                }
+            }
+        }
+
+        impl $crate::visit::TpmVisit for $name {
+            fn visit(&self, field: &'static str, visitor: &mut dyn $crate::visit::TpmVisitor) {
+                visitor.enter(field, stringify!($name));
+                $($crate::visit::TpmVisit::visit(&self.$field_name, stringify!($field_name), visitor);)*
+                visitor.leave();
+            }
+        }
+
+        impl $crate::visit::TpmFromSource for $name {
+            fn from_source(
+                field: &'static str,
+                source: &mut dyn $crate::visit::TpmSource,
+            ) -> Result<Self, $crate::TpmErrorKind> {
+                source.enter(field, stringify!($name))?;
+                $(
+                    let $field_name = <$field_type as $crate::visit::TpmFromSource>::from_source(
+                        stringify!($field_name),
+                        source,
+                    )?;
+                )*
+                source.leave()?;
+                Ok(Self {
+                    $($field_name,)*
+                })
+            }
+        }
     };
 }
 
@@ -108,9 +153,18 @@ macro_rules! tpm_tagged_struct {
 
         impl<'a> $crate::TpmParse<'a> for $name {
             fn parse(buf: &'a [u8]) -> $crate::TpmResult<(Self, &'a [u8])> {
-                let ($tag_field, buf) = <$tag_ty>::parse(buf)?;
+                let ($tag_field, buf) = <$tag_ty>::parse(buf).map_err(|e| {
+                    e.in_field(concat!(stringify!($name), ".", stringify!($tag_field)), buf.len())
+                })?;
                 let ($value_field, buf) =
-                    <$value_ty as $crate::TpmParseTagged>::parse_tagged($tag_field, buf)?;
+                    <$value_ty as $crate::TpmParseTagged>::parse_tagged($tag_field, buf).map_err(
+                        |e| {
+                            e.in_field(
+                                concat!(stringify!($name), ".", stringify!($value_field)),
+                                buf.len(),
+                            )
+                        },
+                    )?;
                 Ok((
                     Self {
                         $tag_field,
@@ -120,5 +174,58 @@ macro_rules! tpm_tagged_struct {
                 ))
             }
         }
+
+        #[cfg(feature = "generate")]
+        impl $crate::generate::TpmGenerate for $name {
+            fn generate(rng: &mut $crate::generate::TpmRng) -> Self {
+                let $tag_field =
+                    <$value_ty as $crate::generate::TpmGenerateTagged>::generate_tag(rng);
+                let $value_field =
+                    <$value_ty as $crate::generate::TpmGenerateTagged>::generate_tagged(
+                        $tag_field, rng,
+                    );
+                Self {
+                    $tag_field,
+                    $value_field,
+                }
+            }
+        }
+
+        impl $crate::visit::TpmVisit for $name {
+            fn visit(&self, field: &'static str, visitor: &mut dyn $crate::visit::TpmVisitor) {
+                visitor.enter(field, stringify!($name));
+                $crate::visit::TpmVisit::visit(&self.$tag_field, stringify!($tag_field), visitor);
+                $crate::visit::TpmVisit::visit(
+                    &self.$value_field,
+                    stringify!($value_field),
+                    visitor,
+                );
+                visitor.leave();
+            }
+        }
+
+        impl $crate::visit::TpmFromSource for $name {
+            fn from_source(
+                field: &'static str,
+                source: &mut dyn $crate::visit::TpmSource,
+            ) -> Result<Self, $crate::TpmErrorKind> {
+                source.enter(field, stringify!($name))?;
+                let $tag_field = <$tag_ty as $crate::visit::TpmFromSource>::from_source(
+                    stringify!($tag_field),
+                    source,
+                )?;
+                let $value_field =
+                    <$value_ty as $crate::visit::TpmFromSourceTagged>::from_source_tagged(
+                        $tag_field,
+                        stringify!($value_field),
+                        source,
+                    )?;
+                source.leave()?;
+                Ok(Self {
+                    $tag_field,
+                    $value_field,
+                })
+            }
+        }
     };
 }
@@ -2,6 +2,8 @@
 // Copyright (c) 2025 Opinsys Oy
 // Copyright (c) 2024-2025 Jarkko Sakkinen
 
+#[cfg(feature = "generate")]
+use crate::generate::{TpmGenerate, TpmRng};
 use crate::{
     data::{
         Tpm2b, Tpm2bAttest, Tpm2bAuth, Tpm2bCreationData, Tpm2bData, Tpm2bDigest, Tpm2bEccPoint,
@@ -10,11 +12,14 @@ use crate::{
         Tpm2bSensitiveCreate, Tpm2bSensitiveData, Tpm2bTimeout, TpmAlgId, TpmCap, TpmCc,
         TpmEccCurve, TpmRc, TpmRh, TpmSe, TpmSt, TpmSu, TpmaLocality, TpmiYesNo, TpmlAlg,
         TpmlDigest, TpmlDigestValues, TpmlPcrSelection, TpmsAlgorithmDetailEcc, TpmsAuthCommand,
-        TpmsAuthResponse, TpmsCapabilityData, TpmsContext, TpmtRsaDecrypt, TpmtSignature,
-        TpmtSymDef, TpmtSymDefObject, TpmtTkAuth, TpmtTkCreation, TpmtTkHashcheck, TpmtTkVerified,
+        TpmsAuthResponse, TpmsCapabilityData, TpmsContext, TpmtRsaDecrypt, TpmtScheme,
+        TpmtSignature, TpmtSymDef, TpmtSymDefObject, TpmtTkAuth, TpmtTkCreation, TpmtTkHashcheck,
+        TpmtTkVerified,
     },
-    tpm_dispatch, tpm_response, tpm_struct, TpmBuild, TpmErrorKind, TpmList, TpmParse,
-    TpmPersistent, TpmResult, TpmSession, TpmSized, TpmTransient, TpmWriter,
+    tpm_response, tpm_struct,
+    visit::{TpmFromSource, TpmSource, TpmVisit, TpmVisitor},
+    TpmBuild, TpmContextHandle, TpmErrorKind, TpmHandle, TpmList, TpmParse, TpmPcr, TpmPersistent,
+    TpmResult, TpmSession, TpmSized, TpmTransient, TpmWriter,
 };
 use core::{convert::TryFrom, fmt::Debug, mem::size_of};
 
@@ -31,11 +36,19 @@ pub type TpmAuthCommands = TpmList<TpmsAuthCommand, MAX_SESSIONS>;
 pub type TpmAuthResponses = TpmList<TpmsAuthResponse, MAX_SESSIONS>;
 
 /// A trait for TPM commands and responses that provides header information.
-pub trait TpmHeader<'a>: TpmBuild + TpmParse<'a> + Debug + TpmSized {
+pub trait TpmHeader: TpmBuild + for<'a> TpmParse<'a> + Debug + TpmSized {
     const COMMAND: TpmCc;
     const NO_SESSIONS: bool;
     const WITH_SESSIONS: bool;
     const HANDLES: usize;
+
+    /// The handle area as a fixed-size array of `HANDLES` elements.
+    type Handles: AsRef<[TpmHandle]> + Copy + Debug + Default;
+}
+
+/// A trait binding a TPM command to the type of its response.
+pub trait TpmCommand: TpmHeader {
+    type Response: TpmHeader + TryFrom<TpmResponseBody, Error = TpmResponseBody>;
 }
 
 /// The result of parsing a TPM response, containing either the successfully parsed
@@ -49,15 +62,15 @@ pub const TPM_HEADER_SIZE: usize = 10;
 /// # Errors
 ///
 /// * `TpmErrorKind::ValueTooLarge` if the command has unknown state
-pub fn tpm_build_command<'a, C>(
+pub fn tpm_build_command<C>(
     command: &C,
     tag: TpmSt,
-    handles: Option<&[u32]>,
+    handles: &C::Handles,
     sessions: &[TpmsAuthCommand],
     writer: &mut crate::TpmWriter,
 ) -> TpmResult<()>
 where
-    C: TpmHeader<'a>,
+    C: TpmHeader,
 {
     match tag {
         TpmSt::NoSessions => {
@@ -66,7 +79,8 @@ where
                     type_name: "TpmSt",
                     expected: TpmSt::Sessions as u16,
                     got: tag as u16,
-                });
+                }
+                .into());
             }
         }
         TpmSt::Sessions => {
@@ -75,18 +89,16 @@ where
                     type_name: "TpmSt",
                     expected: TpmSt::NoSessions as u16,
                     got: tag as u16,
-                });
+                }
+                .into());
             }
         }
         _ => {
-            return Err(TpmErrorKind::InvalidValue);
+            return Err(TpmErrorKind::InvalidValue.into());
         }
     }
 
-    let handles = handles.unwrap_or(&[]);
-    if handles.len() != C::HANDLES {
-        return Err(TpmErrorKind::InternalError);
-    }
+    let handles = handles.as_ref();
 
     let handle_area_len = core::mem::size_of_val(handles);
     let parameters_len = command.len();
@@ -134,7 +146,7 @@ pub fn tpm_build_response<R>(
     writer: &mut crate::TpmWriter,
 ) -> TpmResult<()>
 where
-    R: for<'a> TpmHeader<'a>,
+    R: TpmHeader,
 {
     let tag = if !rc.is_error() && R::WITH_SESSIONS && !sessions.is_empty() {
         TpmSt::Sessions
@@ -178,7 +190,7 @@ where
 /// * `TpmErrorKind::TrailingData` if the command has after spurious data left
 pub fn tpm_parse_command(buf: &[u8]) -> TpmResult<(TpmHandles, TpmCommandBody, TpmAuthCommands)> {
     if buf.len() < TPM_HEADER_SIZE {
-        return Err(TpmErrorKind::Boundary);
+        return Err(TpmErrorKind::Boundary.into());
     }
     let command_len = buf.len();
 
@@ -191,7 +203,7 @@ pub fn tpm_parse_command(buf: &[u8]) -> TpmResult<(TpmHandles, TpmCommandBody, T
     let (cc_raw, mut buf) = u32::parse(buf)?;
 
     if command_len != size as usize {
-        return Err(TpmErrorKind::Boundary);
+        return Err(TpmErrorKind::Boundary.into());
     }
 
     let cc = TpmCc::try_from(cc_raw).map_err(|()| TpmErrorKind::InvalidDiscriminant {
@@ -211,14 +223,16 @@ pub fn tpm_parse_command(buf: &[u8]) -> TpmResult<(TpmHandles, TpmCommandBody, T
             type_name: "TpmSt",
             expected: TpmSt::NoSessions as u16,
             got: tag_raw,
-        });
+        }
+        .into());
     }
     if tag == TpmSt::NoSessions && !dispatch.1 {
         return Err(TpmErrorKind::InvalidTag {
             type_name: "TpmSt",
             expected: TpmSt::Sessions as u16,
             got: tag_raw,
-        });
+        }
+        .into());
     }
 
     let mut handles = TpmHandles::new();
@@ -235,28 +249,29 @@ pub fn tpm_parse_command(buf: &[u8]) -> TpmResult<(TpmHandles, TpmCommandBody, T
         let (auth_area_size, auth_buf) = u32::parse(buf)?;
         let auth_area_size = auth_area_size as usize;
         if auth_buf.len() < auth_area_size {
-            return Err(TpmErrorKind::Boundary);
+            return Err(TpmErrorKind::Boundary.into());
         }
         let (mut auth_area, param_buf) = auth_buf.split_at(auth_area_size);
         while !auth_area.is_empty() {
-            let (session, rest) = TpmsAuthCommand::parse(auth_area)?;
+            let (session, rest) = TpmsAuthCommand::parse(auth_area)
+                .map_err(|e| e.in_slice(param_buf.len()).at_input(command_len))?;
             sessions
                 .try_push(session)
                 .map_err(|_| TpmErrorKind::ValueTooLarge)?;
             auth_area = rest;
         }
         if !auth_area.is_empty() {
-            return Err(TpmErrorKind::TrailingData);
+            return Err(TpmErrorKind::TrailingData.into());
         }
         param_buf
     } else {
         buf
     };
 
-    let (command_data, remainder) = (dispatch.4)(param_buf)?;
+    let (command_data, remainder) = (dispatch.4)(param_buf).map_err(|e| e.at_input(command_len))?;
 
     if !remainder.is_empty() {
-        return Err(TpmErrorKind::TrailingData);
+        return Err(TpmErrorKind::TrailingData.into());
     }
 
     Ok((handles, command_data, sessions))
@@ -270,9 +285,9 @@ pub fn tpm_parse_command(buf: &[u8]) -> TpmResult<(TpmHandles, TpmCommandBody, T
 /// * `TpmErrorKind::InvalidTag` if the tag in the buffer does not match expected
 /// * `TpmErrorKind::InvalidDiscriminant` if the buffer contains an unsupported command code
 /// * `TpmErrorKind::TrailingData` if the response has after spurious data left
-pub fn tpm_parse_response(cc: TpmCc, buf: &[u8]) -> TpmResult<TpmParseResult> {
+pub fn tpm_parse_response(cc: TpmCc, buf: &[u8]) -> TpmResult<TpmParseResult<'_>> {
     if buf.len() < TPM_HEADER_SIZE {
-        return Err(TpmErrorKind::Boundary);
+        return Err(TpmErrorKind::Boundary.into());
     }
 
     let (tag_raw, remainder) = u16::parse(buf)?;
@@ -280,7 +295,7 @@ pub fn tpm_parse_response(cc: TpmCc, buf: &[u8]) -> TpmResult<TpmParseResult> {
     let (code, body_buf) = u32::parse(remainder)?;
 
     if buf.len() != size as usize {
-        return Err(TpmErrorKind::Boundary);
+        return Err(TpmErrorKind::Boundary.into());
     }
 
     let rc = TpmRc::try_from(code)?;
@@ -301,12 +316,13 @@ pub fn tpm_parse_response(cc: TpmCc, buf: &[u8]) -> TpmResult<TpmParseResult> {
             value: u64::from(cc as u32),
         })?;
 
-    let (body, mut session_area) = (dispatch.2)(body_buf)?;
+    let (body, mut session_area) = (dispatch.2)(body_buf).map_err(|e| e.at_input(buf.len()))?;
 
     let mut auth_responses = TpmAuthResponses::new();
     if tag == TpmSt::Sessions {
         while !session_area.is_empty() {
-            let (session, rest) = TpmsAuthResponse::parse(session_area)?;
+            let (session, rest) =
+                TpmsAuthResponse::parse(session_area).map_err(|e| e.at_input(buf.len()))?;
             auth_responses
                 .try_push(session)
                 .map_err(|_| TpmErrorKind::ValueTooLarge)?;
@@ -315,7 +331,7 @@ pub fn tpm_parse_response(cc: TpmCc, buf: &[u8]) -> TpmResult<TpmParseResult> {
     }
 
     if !session_area.is_empty() {
-        return Err(TpmErrorKind::TrailingData);
+        return Err(TpmErrorKind::TrailingData.into());
     }
 
     Ok(Ok((rc, body, auth_responses)))
@@ -405,7 +421,7 @@ tpm_struct!(
     false,
     0,
     {
-        pub flush_handle: u32,
+        pub flush_handle: TpmContextHandle,
     }
 );
 
@@ -582,11 +598,12 @@ tpm_struct!(
 pub struct TpmPolicyGetDigestResponse {
     pub policy_digest: Tpm2bDigest,
 }
-impl TpmHeader<'_> for TpmPolicyGetDigestResponse {
+impl TpmHeader for TpmPolicyGetDigestResponse {
     const COMMAND: TpmCc = TpmCc::PolicyGetDigest;
     const NO_SESSIONS: bool = false;
     const WITH_SESSIONS: bool = true;
     const HANDLES: usize = 0;
+    type Handles = [TpmHandle; 0];
 }
 impl TpmSized for TpmPolicyGetDigestResponse {
     const SIZE: usize = <Tpm2bDigest>::SIZE;
@@ -608,6 +625,31 @@ impl<'a> TpmParse<'a> for TpmPolicyGetDigestResponse {
         Ok((Self { policy_digest }, buf))
     }
 }
+#[cfg(feature = "generate")]
+impl TpmGenerate for TpmPolicyGetDigestResponse {
+    fn generate(rng: &mut TpmRng) -> Self {
+        Self {
+            policy_digest: Tpm2bDigest::generate(rng),
+        }
+    }
+}
+
+impl TpmVisit for TpmPolicyGetDigestResponse {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.enter(field, "TpmPolicyGetDigestResponse");
+        self.policy_digest.visit("policy_digest", visitor);
+        visitor.leave();
+    }
+}
+
+impl TpmFromSource for TpmPolicyGetDigestResponse {
+    fn from_source(field: &'static str, source: &mut dyn TpmSource) -> Result<Self, TpmErrorKind> {
+        source.enter(field, "TpmPolicyGetDigestResponse")?;
+        let policy_digest = Tpm2bDigest::from_source("policy_digest", source)?;
+        source.leave()?;
+        Ok(Self { policy_digest })
+    }
+}
 
 tpm_struct!(
     #[derive(Debug, Default, PartialEq, Eq, Clone)]
@@ -650,7 +692,7 @@ tpm_struct!(
     TpmCc::PolicyRestart,
     false,
     true,
-    0,
+    1,
     {}
 );
 
@@ -715,7 +757,7 @@ tpm_struct!(
     false,
     0,
     {
-        pub loaded_handle: TpmTransient,
+        pub loaded_handle: TpmContextHandle,
     }
 );
 
@@ -841,14 +883,16 @@ tpm_struct!(
     {}
 );
 
-tpm_struct!(
-    #[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+tpm_response!(
+    #[derive(Debug, Default, PartialEq, Eq, Clone)]
     TpmPolicySecretResponse,
     TpmCc::PolicySecret,
     false,
     true,
-    0,
-    {}
+    {
+        pub timeout: Tpm2bTimeout,
+        pub policy_ticket: TpmtTkAuth,
+    }
 );
 
 tpm_response!(
@@ -1286,7 +1330,7 @@ tpm_struct!(
     3,
     {
         pub qualifying_data: Tpm2bData,
-        pub in_scheme: TpmtSignature,
+        pub in_scheme: TpmtScheme,
         pub size: u16,
         pub offset: u16,
     }
@@ -1314,7 +1358,7 @@ tpm_struct! (
     2,
     {
         pub qualifying_data: Tpm2bData,
-        pub in_scheme: TpmtSignature,
+        pub in_scheme: TpmtScheme,
     }
 );
 
@@ -1340,7 +1384,7 @@ tpm_struct! (
     {
         pub qualifying_data: Tpm2bData,
         pub creation_hash: Tpm2bDigest,
-        pub in_scheme: TpmtSignature,
+        pub in_scheme: TpmtScheme,
         pub creation_ticket: TpmtTkCreation,
     }
 );
@@ -1366,7 +1410,7 @@ tpm_struct! (
     1,
     {
         pub qualifying_data: Tpm2bData,
-        pub in_scheme: TpmtSignature,
+        pub in_scheme: TpmtScheme,
         pub pcr_select: TpmlPcrSelection,
     }
 );
@@ -1392,7 +1436,7 @@ tpm_struct! (
     3,
     {
         pub qualifying_data: Tpm2bData,
-        pub in_scheme: TpmtSignature,
+        pub in_scheme: TpmtScheme,
     }
 );
 
@@ -1417,7 +1461,7 @@ tpm_struct! (
     2,
     {
         pub qualifying_data: Tpm2bData,
-        pub in_scheme: TpmtSignature,
+        pub in_scheme: TpmtScheme,
     }
 );
 
@@ -1442,7 +1486,7 @@ tpm_struct! (
     2,
     {
         pub qualifying_data: Tpm2bData,
-        pub in_scheme: TpmtSignature,
+        pub in_scheme: TpmtScheme,
     }
 );
 
@@ -1467,7 +1511,7 @@ tpm_struct!(
     1,
     {
         pub digest: Tpm2bDigest,
-        pub in_scheme: TpmtSignature,
+        pub in_scheme: TpmtScheme,
         pub validation: TpmtTkHashcheck,
     }
 );
@@ -2034,6 +2078,30 @@ tpm_response!(
     {}
 );
 
+tpm_struct! (
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    TpmPolicyAuthorizeCommand,
+    TpmCc::PolicyAuthorize,
+    false,
+    true,
+    1,
+    {
+        pub approved_policy: Tpm2bDigest,
+        pub policy_ref: crate::data::Tpm2bNonce,
+        pub key_sign: Tpm2bName,
+        pub check_ticket: TpmtTkVerified,
+    }
+);
+
+tpm_response!(
+    #[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+    TpmPolicyAuthorizeResponse,
+    TpmCc::PolicyAuthorize,
+    false,
+    true,
+    {}
+);
+
 tpm_struct! (
     #[derive(Debug, PartialEq, Eq, Copy, Clone)]
     TpmPolicyLocalityCommand,
@@ -2295,7 +2363,7 @@ tpm_struct!(
     {
         pub auth_policy: Tpm2bDigest,
         pub hash_alg: TpmAlgId,
-        pub pcr_num: TpmRh,
+        pub pcr_num: TpmPcr,
     }
 );
 
@@ -2408,6 +2476,7 @@ tpm_dispatch! {
     (TpmLoadExternalCommand, TpmLoadExternalResponse, LoadExternal),
     (TpmMakeCredentialCommand, TpmMakeCredentialResponse, MakeCredential),
     (TpmNvReadPublicCommand, TpmNvReadPublicResponse, NvReadPublic),
+    (TpmPolicyAuthorizeCommand, TpmPolicyAuthorizeResponse, PolicyAuthorize),
     (TpmPolicyAuthValueCommand, TpmPolicyAuthValueResponse, PolicyAuthValue),
     (TpmPolicyCommandCodeCommand, TpmPolicyCommandCodeResponse, PolicyCommandCode),
     (TpmPolicyCpHashCommand, TpmPolicyCpHashResponse, PolicyCpHash),
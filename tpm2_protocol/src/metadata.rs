@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// Copyright (c) 2025 Opinsys Oy
+// Copyright (c) 2024-2025 Jarkko Sakkinen
+
+//! Per-command metadata from the TCG TPM 2.0 Part 3 command tables.
+
+use crate::{
+    data::TpmCc,
+    message::{
+        TpmActivateCredentialCommand, TpmCertifyCommand, TpmCertifyCreationCommand,
+        TpmChangeEpsCommand, TpmChangePpsCommand, TpmClearCommand, TpmClearControlCommand,
+        TpmContextLoadCommand, TpmContextSaveCommand, TpmCreateCommand, TpmCreatePrimaryCommand,
+        TpmDictionaryAttackLockResetCommand, TpmDuplicateCommand, TpmEccParametersCommand,
+        TpmEcdhKeyGenCommand, TpmEcdhZGenCommand, TpmEncryptDecrypt2Command,
+        TpmEventSequenceCompleteCommand, TpmEvictControlCommand, TpmFlushContextCommand,
+        TpmGetCapabilityCommand, TpmGetCommandAuditDigestCommand, TpmGetRandomCommand,
+        TpmGetSessionAuditDigestCommand, TpmGetTestResultCommand, TpmGetTimeCommand,
+        TpmHashCommand, TpmHashSequenceStartCommand, TpmHeader, TpmHierarchyChangeAuthCommand,
+        TpmHierarchyControlCommand, TpmImportCommand, TpmIncrementalSelfTestCommand,
+        TpmLoadCommand, TpmLoadExternalCommand, TpmMakeCredentialCommand, TpmNvCertifyCommand,
+        TpmNvChangeAuthCommand, TpmNvDefineSpaceCommand, TpmNvExtendCommand,
+        TpmNvGlobalWriteLockCommand, TpmNvIncrementCommand, TpmNvReadCommand, TpmNvReadLockCommand,
+        TpmNvReadPublicCommand, TpmNvSetBitsCommand, TpmNvUndefineSpaceCommand,
+        TpmNvUndefineSpaceSpecialCommand, TpmNvWriteCommand, TpmNvWriteLockCommand,
+        TpmObjectChangeAuthCommand, TpmPcrAllocateCommand, TpmPcrEventCommand, TpmPcrExtendCommand,
+        TpmPcrReadCommand, TpmPcrResetCommand, TpmPcrSetAuthPolicyCommand,
+        TpmPcrSetAuthValueCommand, TpmPolicyAuthValueCommand, TpmPolicyAuthorizeCommand,
+        TpmPolicyCommandCodeCommand, TpmPolicyCpHashCommand, TpmPolicyGetDigestCommand,
+        TpmPolicyLocalityCommand, TpmPolicyOrCommand, TpmPolicyPasswordCommand,
+        TpmPolicyPcrCommand, TpmPolicyPhysicalPresenceCommand, TpmPolicyRestartCommand,
+        TpmPolicySecretCommand, TpmPolicySignedCommand, TpmPolicyTicketCommand, TpmQuoteCommand,
+        TpmReadPublicCommand, TpmRewrapCommand, TpmRsaDecryptCommand, TpmRsaEncryptCommand,
+        TpmSelfTestCommand, TpmSequenceCompleteCommand, TpmSequenceUpdateCommand,
+        TpmSetPrimaryPolicyCommand, TpmShutdownCommand, TpmSignCommand, TpmStartAuthSessionCommand,
+        TpmStartupCommand, TpmStirRandomCommand, TpmUnsealCommand, TpmVendorTcgTestCommand,
+        TpmVerifySignatureCommand, PARSE_COMMAND_MAP,
+    },
+};
+
+/// The authorization role required for a handle in the handle area.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TpmAuthRole {
+    /// The handle does not require authorization.
+    None,
+    /// Authorization with the `authValue` or `authPolicy` of the entity.
+    User,
+    /// Administrative authorization of an object or NV index.
+    Admin,
+    /// Authorization for duplicating an object.
+    Dup,
+}
+
+/// The effect of a command on the handles loaded in the TPM.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TpmHandleEffect {
+    /// The command does not load or flush handles.
+    None,
+    /// The command returns a newly loaded transient object, sequence or session.
+    Creates,
+    /// The command flushes a loaded handle.
+    Flushes,
+}
+
+/// Metadata for a single command.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TpmCommandMetadata {
+    pub cc: TpmCc,
+    /// The authorization role of each handle in the handle area.
+    pub handles: &'static [TpmAuthRole],
+    /// The first command parameter is a TPM2B and can be encrypted.
+    pub decrypt: bool,
+    /// The first response parameter is a TPM2B and can be encrypted.
+    pub encrypt: bool,
+    pub handle_effect: TpmHandleEffect,
+}
+
+impl TpmCommandMetadata {
+    /// Returns the number of handles that require an authorization session.
+    #[must_use]
+    pub fn auth_handle_count(&self) -> usize {
+        self.handles
+            .iter()
+            .filter(|role| **role != TpmAuthRole::None)
+            .count()
+    }
+}
+
+macro_rules! tpm_command_metadata {
+    ( $( ($cmd:ty, [$($role:ident),*], $decrypt:expr, $encrypt:expr, $effect:ident) ),* $(,)? ) => {
+        static COMMAND_METADATA: &[TpmCommandMetadata] = &[
+            $(
+                TpmCommandMetadata {
+                    cc: <$cmd as TpmHeader>::COMMAND,
+                    handles: &[$(TpmAuthRole::$role),*],
+                    decrypt: $decrypt,
+                    encrypt: $encrypt,
+                    handle_effect: TpmHandleEffect::$effect,
+                },
+            )*
+        ];
+
+        const _: () = {
+            $({
+                let roles: &[TpmAuthRole] = &[$(TpmAuthRole::$role),*];
+                if roles.len() != <$cmd as TpmHeader>::HANDLES {
+                    panic!("COMMAND_METADATA handle count does not match the command.");
+                }
+            })*
+        };
+    };
+}
+
+tpm_command_metadata! {
+    (TpmNvUndefineSpaceSpecialCommand, [Admin, User], false, false, None),
+    (TpmEvictControlCommand, [User, None], false, false, None),
+    (TpmHierarchyControlCommand, [User], false, false, None),
+    (TpmNvUndefineSpaceCommand, [User, None], false, false, None),
+    (TpmChangeEpsCommand, [User], false, false, None),
+    (TpmChangePpsCommand, [User], false, false, None),
+    (TpmClearCommand, [User], false, false, None),
+    (TpmClearControlCommand, [User], false, false, None),
+    (TpmHierarchyChangeAuthCommand, [User], true, false, None),
+    (TpmNvDefineSpaceCommand, [User], true, false, None),
+    (TpmPcrAllocateCommand, [User], false, false, None),
+    (TpmPcrSetAuthPolicyCommand, [User], true, false, None),
+    (TpmSetPrimaryPolicyCommand, [User], true, false, None),
+    (TpmCreatePrimaryCommand, [User], true, true, Creates),
+    (TpmNvGlobalWriteLockCommand, [User], false, false, None),
+    (TpmGetCommandAuditDigestCommand, [User, User], true, true, None),
+    (TpmNvIncrementCommand, [User, None], false, false, None),
+    (TpmNvSetBitsCommand, [User, None], false, false, None),
+    (TpmNvExtendCommand, [User, None], true, false, None),
+    (TpmNvWriteCommand, [User, None], true, false, None),
+    (TpmNvWriteLockCommand, [User, None], false, false, None),
+    (TpmDictionaryAttackLockResetCommand, [User], false, false, None),
+    (TpmNvChangeAuthCommand, [Admin], true, false, None),
+    (TpmPcrEventCommand, [User], true, false, None),
+    (TpmPcrResetCommand, [User], false, false, None),
+    (TpmSequenceCompleteCommand, [User], true, true, Flushes),
+    (TpmIncrementalSelfTestCommand, [], false, false, None),
+    (TpmSelfTestCommand, [], false, false, None),
+    (TpmStartupCommand, [], false, false, None),
+    (TpmShutdownCommand, [], false, false, None),
+    (TpmStirRandomCommand, [], true, false, None),
+    (TpmActivateCredentialCommand, [Admin, User], true, true, None),
+    (TpmCertifyCommand, [Admin, User], true, true, None),
+    (TpmCertifyCreationCommand, [User, None], true, true, None),
+    (TpmDuplicateCommand, [Dup, None], true, true, None),
+    (TpmGetTimeCommand, [User, User], true, true, None),
+    (TpmGetSessionAuditDigestCommand, [User, User, None], true, true, None),
+    (TpmNvReadCommand, [User, None], false, true, None),
+    (TpmNvReadLockCommand, [User, None], false, false, None),
+    (TpmObjectChangeAuthCommand, [Admin, None], true, true, None),
+    (TpmPolicySecretCommand, [User, None], true, true, None),
+    (TpmRewrapCommand, [User, None], true, true, None),
+    (TpmCreateCommand, [User], true, true, None),
+    (TpmEcdhZGenCommand, [User], true, true, None),
+    (TpmImportCommand, [User], true, true, None),
+    (TpmLoadCommand, [User], true, true, Creates),
+    (TpmQuoteCommand, [User], true, true, None),
+    (TpmRsaDecryptCommand, [User], true, true, None),
+    (TpmSequenceUpdateCommand, [User], true, false, None),
+    (TpmSignCommand, [User], true, false, None),
+    (TpmUnsealCommand, [User], false, true, None),
+    (TpmPolicySignedCommand, [None, None], true, true, None),
+    (TpmContextLoadCommand, [], false, false, Creates),
+    (TpmContextSaveCommand, [None], false, false, None),
+    (TpmEcdhKeyGenCommand, [None], false, true, None),
+    (TpmFlushContextCommand, [], false, false, Flushes),
+    (TpmLoadExternalCommand, [], true, true, Creates),
+    (TpmMakeCredentialCommand, [None], true, true, None),
+    (TpmNvReadPublicCommand, [None], false, true, None),
+    (TpmPolicyAuthorizeCommand, [None], true, false, None),
+    (TpmPolicyAuthValueCommand, [None], false, false, None),
+    (TpmPolicyCommandCodeCommand, [None], false, false, None),
+    (TpmPolicyCpHashCommand, [None], true, false, None),
+    (TpmPolicyLocalityCommand, [None], false, false, None),
+    (TpmPolicyOrCommand, [None], false, false, None),
+    (TpmPolicyTicketCommand, [None], true, false, None),
+    (TpmReadPublicCommand, [None], false, true, None),
+    (TpmRsaEncryptCommand, [None], true, true, None),
+    (TpmStartAuthSessionCommand, [None, None], true, true, Creates),
+    (TpmVerifySignatureCommand, [None], true, false, None),
+    (TpmEccParametersCommand, [], false, false, None),
+    (TpmGetCapabilityCommand, [], false, false, None),
+    (TpmGetRandomCommand, [], false, true, None),
+    (TpmGetTestResultCommand, [], false, true, None),
+    (TpmHashCommand, [], true, true, None),
+    (TpmPcrReadCommand, [], false, false, None),
+    (TpmPolicyPcrCommand, [None], true, false, None),
+    (TpmPolicyRestartCommand, [None], false, false, None),
+    (TpmPcrExtendCommand, [User], false, false, None),
+    (TpmPcrSetAuthValueCommand, [User], true, false, None),
+    (TpmNvCertifyCommand, [User, User, None], true, true, None),
+    (TpmEventSequenceCompleteCommand, [User, User], true, false, Flushes),
+    (TpmHashSequenceStartCommand, [], true, false, Creates),
+    (TpmPolicyPhysicalPresenceCommand, [None], false, false, None),
+    (TpmPolicyGetDigestCommand, [None], false, true, None),
+    (TpmPolicyPasswordCommand, [None], false, false, None),
+    (TpmEncryptDecrypt2Command, [User], true, true, None),
+    (TpmVendorTcgTestCommand, [], true, true, None),
+}
+
+const _: () = {
+    assert!(
+        COMMAND_METADATA.len() == PARSE_COMMAND_MAP.len(),
+        "COMMAND_METADATA must cover every command in PARSE_COMMAND_MAP."
+    );
+    let mut i = 0;
+    while i < COMMAND_METADATA.len() {
+        assert!(
+            COMMAND_METADATA[i].cc as u32 == PARSE_COMMAND_MAP[i].0 as u32,
+            "COMMAND_METADATA must be sorted by TpmCc."
+        );
+        i += 1;
+    }
+};
+
+/// Looks up the metadata of a command.
+#[must_use]
+pub fn tpm_command_metadata(cc: TpmCc) -> Option<&'static TpmCommandMetadata> {
+    COMMAND_METADATA
+        .binary_search_by_key(&(cc as u32), |metadata| metadata.cc as u32)
+        .ok()
+        .map(|index| &COMMAND_METADATA[index])
+}
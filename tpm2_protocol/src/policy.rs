@@ -0,0 +1,206 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// Copyright (c) 2025 Opinsys Oy
+// Copyright (c) 2024-2025 Jarkko Sakkinen
+
+//! Policy digest computation without a TPM, as done by a trial session.
+
+use crate::{
+    data::{Tpm2bDigest, TpmAlgId, TpmCc, TpmlPcrSelection, MAX_DIGEST_SIZE},
+    hash::{digest, TpmHashProvider},
+    tpm_hash_size, TpmBuild, TpmErrorKind, TpmResult, TpmSized, TpmWriter,
+};
+
+/// The maximum number of branches in a `TPM2_PolicyOR` assertion.
+pub const MAX_POLICY_OR_BRANCHES: usize = 8;
+
+/// A policy digest accumulator following the update rules of the policy
+/// commands in TPM 2.0 Part 3.
+#[derive(Debug, Clone)]
+pub struct TpmPolicyDigest<'a, H: TpmHashProvider> {
+    hash: &'a H,
+    alg: TpmAlgId,
+    digest: Tpm2bDigest,
+}
+
+impl<'a, H: TpmHashProvider> TpmPolicyDigest<'a, H> {
+    /// Creates an accumulator with an all-zero digest for `alg`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if `alg` is not a hash algorithm.
+    pub fn new(hash: &'a H, alg: TpmAlgId) -> TpmResult<Self> {
+        let size = tpm_hash_size(&alg).ok_or(TpmErrorKind::InvalidValue)?;
+        let zero = [0u8; MAX_DIGEST_SIZE];
+        Ok(Self {
+            hash,
+            alg,
+            digest: Tpm2bDigest::try_from(&zero[..size])?,
+        })
+    }
+
+    /// Returns the hash algorithm of the policy.
+    #[must_use]
+    pub fn alg(&self) -> TpmAlgId {
+        self.alg
+    }
+
+    /// Returns the current policy digest.
+    #[must_use]
+    pub fn digest(&self) -> &Tpm2bDigest {
+        &self.digest
+    }
+
+    fn reset(&mut self) -> TpmResult<()> {
+        *self = Self::new(self.hash, self.alg)?;
+        Ok(())
+    }
+
+    fn extend(&mut self, chunks: &[&[u8]]) -> TpmResult<()> {
+        let mut all: [&[u8]; MAX_POLICY_OR_BRANCHES + 2] = [&[]; MAX_POLICY_OR_BRANCHES + 2];
+        if chunks.len() >= all.len() {
+            return Err(TpmErrorKind::CapacityExceeded.into());
+        }
+        all[0] = &self.digest;
+        all[1..=chunks.len()].copy_from_slice(chunks);
+        self.digest = digest(self.hash, self.alg, &all[..=chunks.len()])?;
+        Ok(())
+    }
+
+    /// `PolicyUpdate()`: extends `commandCode || arg2` and then `arg3`.
+    fn update(&mut self, cc: TpmCc, arg2: &[u8], arg3: &[u8]) -> TpmResult<()> {
+        self.extend(&[&(cc as u32).to_be_bytes(), arg2])?;
+        self.extend(&[arg3])
+    }
+
+    /// Applies `TPM2_PolicyPCR` with a precomputed `pcrDigest`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pcrs` cannot be marshaled.
+    pub fn pcr(&mut self, pcrs: &TpmlPcrSelection, pcr_digest: &[u8]) -> TpmResult<()> {
+        let mut buf = [0u8; TpmlPcrSelection::SIZE];
+        let len = {
+            let mut writer = TpmWriter::new(&mut buf);
+            pcrs.build(&mut writer)?;
+            writer.len()
+        };
+        self.extend(&[
+            &(TpmCc::PolicyPcr as u32).to_be_bytes(),
+            &buf[..len],
+            pcr_digest,
+        ])
+    }
+
+    /// Applies `TPM2_PolicyPCR` with `pcrDigest` computed from the PCR values
+    /// in selection order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pcrs` cannot be marshaled.
+    pub fn pcr_values(&mut self, pcrs: &TpmlPcrSelection, values: &[&[u8]]) -> TpmResult<()> {
+        let pcr_digest = digest(self.hash, self.alg, values)?;
+        self.pcr(pcrs, &pcr_digest)
+    }
+
+    /// Applies `TPM2_PolicyOR`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if there are fewer than two or more
+    /// than eight branches, or a branch digest has the wrong size.
+    pub fn or(&mut self, branches: &[Tpm2bDigest]) -> TpmResult<()> {
+        if !(2..=MAX_POLICY_OR_BRANCHES).contains(&branches.len())
+            || branches
+                .iter()
+                .any(|branch| branch.as_ref().len() != self.digest.as_ref().len())
+        {
+            return Err(TpmErrorKind::InvalidValue.into());
+        }
+        let cc = (TpmCc::PolicyOR as u32).to_be_bytes();
+        let mut chunks: [&[u8]; MAX_POLICY_OR_BRANCHES + 1] = [&[]; MAX_POLICY_OR_BRANCHES + 1];
+        chunks[0] = &cc;
+        for (chunk, branch) in chunks[1..].iter_mut().zip(branches) {
+            *chunk = branch;
+        }
+        self.reset()?;
+        self.extend(&chunks[..=branches.len()])
+    }
+
+    /// Applies `TPM2_PolicySecret` for the entity with the Name `auth_name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the hash computation fails.
+    pub fn secret(&mut self, auth_name: &[u8], policy_ref: &[u8]) -> TpmResult<()> {
+        self.update(TpmCc::PolicySecret, auth_name, policy_ref)
+    }
+
+    /// Applies `TPM2_PolicySigned` for the key with the Name `key_name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the hash computation fails.
+    pub fn signed(&mut self, key_name: &[u8], policy_ref: &[u8]) -> TpmResult<()> {
+        self.update(TpmCc::PolicySigned, key_name, policy_ref)
+    }
+
+    /// Applies `TPM2_PolicyAuthorize` for the key with the Name `key_sign`.
+    /// The result does not depend on the approved policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the hash computation fails.
+    pub fn authorize(&mut self, key_sign: &[u8], policy_ref: &[u8]) -> TpmResult<()> {
+        self.reset()?;
+        self.update(TpmCc::PolicyAuthorize, key_sign, policy_ref)
+    }
+
+    /// Applies `TPM2_PolicyCommandCode`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the hash computation fails.
+    pub fn command_code(&mut self, code: TpmCc) -> TpmResult<()> {
+        self.extend(&[
+            &(TpmCc::PolicyCommandCode as u32).to_be_bytes(),
+            &(code as u32).to_be_bytes(),
+        ])
+    }
+
+    /// Applies `TPM2_PolicyAuthValue` or `TPM2_PolicyPassword`, which produce
+    /// the same digest.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the hash computation fails.
+    pub fn auth_value(&mut self) -> TpmResult<()> {
+        self.extend(&[&(TpmCc::PolicyAuthValue as u32).to_be_bytes()])
+    }
+
+    /// Applies `TPM2_PolicyCpHash`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the hash computation fails.
+    pub fn cp_hash(&mut self, cp_hash_a: &[u8]) -> TpmResult<()> {
+        self.extend(&[&(TpmCc::PolicyCpHash as u32).to_be_bytes(), cp_hash_a])
+    }
+
+    /// Applies `TPM2_PolicyLocality`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the hash computation fails.
+    pub fn locality(&mut self, locality: u8) -> TpmResult<()> {
+        self.extend(&[&(TpmCc::PolicyLocality as u32).to_be_bytes(), &[locality]])
+    }
+
+    /// Applies `TPM2_PolicyPhysicalPresence`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the hash computation fails.
+    pub fn physical_presence(&mut self) -> TpmResult<()> {
+        self.extend(&[&(TpmCc::PolicyPhysicalPresence as u32).to_be_bytes()])
+    }
+}
@@ -0,0 +1,326 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// Copyright (c) 2025 Opinsys Oy
+
+//! Reflection over the protocol types.
+//!
+//! Every type produced by the marshalling macros, and every hand-written
+//! union, implements `TpmVisit`, which walks the value and reports its fields
+//! to a `TpmVisitor`. Printers, encoders and diff tools can be written once
+//! against `TpmVisitor` and cover every command and response.
+//!
+//! Unions and `TPM2B` wrappers are transparent: they report the selected
+//! variant or the wrapped value under the field name of the enclosing
+//! structure. The tag that selects the variant is always a sibling field.
+//!
+//! `TpmFromSource` is the inverse of `TpmVisit`: it constructs a value by
+//! requesting the same fields, in the same order, from a `TpmSource`.
+
+use crate::{TpmBuffer, TpmErrorKind, TpmList, TpmTagged};
+
+/// A leaf value reported to a `TpmVisitor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TpmValue<'a> {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I32(i32),
+    Bool(bool),
+    /// A TPM handle
+    Handle(u32),
+    /// A constant of an enumeration
+    Enum {
+        name: &'static str,
+        value: u64,
+    },
+    /// A set of attributes and the names of all defined flags
+    Flags {
+        bits: u64,
+        names: &'static [(u64, &'static str)],
+    },
+    /// A sized buffer
+    Bytes(&'a [u8]),
+    /// An empty union variant
+    Null,
+}
+
+impl TpmValue<'_> {
+    /// Returns the names of the flags set in a `Flags` value.
+    pub fn flag_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let (bits, names): (u64, &[(u64, &str)]) = match self {
+            Self::Flags { bits, names } => (*bits, names),
+            _ => (0, &[]),
+        };
+        names
+            .iter()
+            .filter(move |(flag, _)| *flag != 0 && bits & flag == *flag)
+            .map(|(_, name)| *name)
+    }
+}
+
+/// Receives the fields of a value walked by `TpmVisit::visit()`.
+///
+/// `field` is the name of the field in the enclosing structure, or an empty
+/// string for list items and the top-level value. `type_name` is the name of
+/// the Rust type.
+pub trait TpmVisitor {
+    /// Called for a leaf value.
+    fn value(&mut self, field: &'static str, type_name: &'static str, value: TpmValue<'_>);
+
+    /// Called before the fields of a structure.
+    fn enter(&mut self, field: &'static str, type_name: &'static str);
+
+    /// Called before the items of a list.
+    fn enter_list(&mut self, field: &'static str, type_name: &'static str, count: usize) {
+        let _ = count;
+        self.enter(field, type_name);
+    }
+
+    /// Called after the last field of a structure or item of a list.
+    fn leave(&mut self);
+}
+
+/// Walks a value and reports its fields to a `TpmVisitor`.
+pub trait TpmVisit {
+    /// Reports this value, named `field`, to `visitor`.
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor);
+}
+
+macro_rules! tpm_visit_integer {
+    ($ty:ty, $variant:ident) => {
+        impl TpmVisit for $ty {
+            fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+                visitor.value(field, stringify!($ty), TpmValue::$variant(*self));
+            }
+        }
+    };
+}
+
+tpm_visit_integer!(u8, U8);
+tpm_visit_integer!(u16, U16);
+tpm_visit_integer!(u32, U32);
+tpm_visit_integer!(u64, U64);
+tpm_visit_integer!(i32, I32);
+
+impl<const N: usize> TpmVisit for [u8; N] {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.value(field, "[u8]", TpmValue::Bytes(self));
+    }
+}
+
+impl<const CAPACITY: usize> TpmVisit for TpmBuffer<CAPACITY> {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.value(field, "TpmBuffer", TpmValue::Bytes(self));
+    }
+}
+
+impl<T: TpmVisit + Copy + Default, const CAPACITY: usize> TpmVisit for TpmList<T, CAPACITY> {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.enter_list(field, "TpmList", self.len());
+        for item in self.iter() {
+            item.visit("", visitor);
+        }
+        visitor.leave();
+    }
+}
+
+/// Supplies the fields of a value constructed by `TpmFromSource`.
+///
+/// The requests mirror the calls made to a `TpmVisitor` when the constructed
+/// value is visited.
+pub trait TpmSource {
+    /// Called before the fields of a structure.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if the field is not a structure.
+    fn enter(&mut self, field: &'static str, type_name: &'static str) -> Result<(), TpmErrorKind>;
+
+    /// Called before the items of a list. Returns the number of items.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if the field is not a list.
+    fn enter_list(
+        &mut self,
+        field: &'static str,
+        type_name: &'static str,
+    ) -> Result<usize, TpmErrorKind>;
+
+    /// Called after the last field of a structure or item of a list.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::TrailingData` if fields or items were left
+    /// unconsumed.
+    fn leave(&mut self) -> Result<(), TpmErrorKind>;
+
+    /// Returns an unsigned integer.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if the field is not an integer.
+    fn unsigned(
+        &mut self,
+        field: &'static str,
+        type_name: &'static str,
+    ) -> Result<u64, TpmErrorKind>;
+
+    /// Returns a signed integer.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if the field is not an integer.
+    fn signed(&mut self, field: &'static str, type_name: &'static str)
+        -> Result<i64, TpmErrorKind>;
+
+    /// Returns a boolean.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if the field is not a boolean.
+    fn boolean(
+        &mut self,
+        field: &'static str,
+        type_name: &'static str,
+    ) -> Result<bool, TpmErrorKind>;
+
+    /// Returns a handle.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if the field is not a handle.
+    fn handle(&mut self, field: &'static str, type_name: &'static str)
+        -> Result<u32, TpmErrorKind>;
+
+    /// Returns the value of an enumeration constant. `names` lists the value
+    /// and the name of every constant.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if the field does not name a
+    /// constant.
+    fn enumeration(
+        &mut self,
+        field: &'static str,
+        type_name: &'static str,
+        names: &'static [(u64, &'static str)],
+    ) -> Result<u64, TpmErrorKind>;
+
+    /// Returns a set of attributes. `names` lists the value and the name of
+    /// every defined flag.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if the field is not a set of
+    /// attributes.
+    fn flags(
+        &mut self,
+        field: &'static str,
+        type_name: &'static str,
+        names: &'static [(u64, &'static str)],
+    ) -> Result<u64, TpmErrorKind>;
+
+    /// Copies a sized buffer into `out` and returns its length.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::CapacityExceeded` if the buffer does not fit
+    /// into `out`, or `TpmErrorKind::InvalidValue` if the field is not a
+    /// buffer.
+    fn bytes(
+        &mut self,
+        field: &'static str,
+        type_name: &'static str,
+        out: &mut [u8],
+    ) -> Result<usize, TpmErrorKind>;
+
+    /// Consumes an empty union variant.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if the field is not empty.
+    fn null(&mut self, field: &'static str, type_name: &'static str) -> Result<(), TpmErrorKind>;
+}
+
+/// Constructs a value from the fields supplied by a `TpmSource`.
+pub trait TpmFromSource: Sized {
+    /// Constructs the value named `field` from `source`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error reported by `source`, or a `TpmErrorKind`
+    /// if the supplied fields do not form a valid value.
+    fn from_source(field: &'static str, source: &mut dyn TpmSource) -> Result<Self, TpmErrorKind>;
+}
+
+/// Constructs the variant of a tagged union selected by a tag.
+pub trait TpmFromSourceTagged: TpmTagged + Sized {
+    /// Constructs the variant selected by `tag` from `source`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TpmErrorKind::InvalidValue` if `tag` does not select a
+    /// variant, or the first error reported by `source`.
+    fn from_source_tagged(
+        tag: Self::Tag,
+        field: &'static str,
+        source: &mut dyn TpmSource,
+    ) -> Result<Self, TpmErrorKind>;
+}
+
+macro_rules! tpm_from_source_integer {
+    ($ty:ty, $method:ident) => {
+        impl TpmFromSource for $ty {
+            fn from_source(
+                field: &'static str,
+                source: &mut dyn TpmSource,
+            ) -> Result<Self, TpmErrorKind> {
+                let value = source.$method(field, stringify!($ty))?;
+                <$ty>::try_from(value).map_err(|_| TpmErrorKind::ValueTooLarge)
+            }
+        }
+    };
+}
+
+tpm_from_source_integer!(u8, unsigned);
+tpm_from_source_integer!(u16, unsigned);
+tpm_from_source_integer!(u32, unsigned);
+tpm_from_source_integer!(u64, unsigned);
+tpm_from_source_integer!(i32, signed);
+
+impl<const N: usize> TpmFromSource for [u8; N] {
+    fn from_source(field: &'static str, source: &mut dyn TpmSource) -> Result<Self, TpmErrorKind> {
+        let mut bytes = [0u8; N];
+        if source.bytes(field, "[u8]", &mut bytes)? != N {
+            return Err(TpmErrorKind::InvalidValue);
+        }
+        Ok(bytes)
+    }
+}
+
+impl<const CAPACITY: usize> TpmFromSource for TpmBuffer<CAPACITY> {
+    fn from_source(field: &'static str, source: &mut dyn TpmSource) -> Result<Self, TpmErrorKind> {
+        let mut buffer = Self::new();
+        let len = source.bytes(field, "TpmBuffer", &mut buffer.bytes)?;
+        buffer.len = u16::try_from(len).map_err(|_| TpmErrorKind::ValueTooLarge)?;
+        Ok(buffer)
+    }
+}
+
+impl<T: TpmFromSource + Copy + Default, const CAPACITY: usize> TpmFromSource
+    for TpmList<T, CAPACITY>
+{
+    fn from_source(field: &'static str, source: &mut dyn TpmSource) -> Result<Self, TpmErrorKind> {
+        let count = source.enter_list(field, "TpmList")?;
+        if count > CAPACITY {
+            return Err(TpmErrorKind::CapacityExceeded);
+        }
+        let mut list = Self::new();
+        for _ in 0..count {
+            list.try_push(T::from_source("", source)?)?;
+        }
+        source.leave()?;
+        Ok(list)
+    }
+}
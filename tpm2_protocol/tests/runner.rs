@@ -8,16 +8,24 @@
 use std::{convert::TryFrom, io::IsTerminal, string::ToString, vec::Vec};
 use tpm2_protocol::{
     data::{
-        Tpm2bAuth, Tpm2bDigest, Tpm2bMaxBuffer, Tpm2bNonce, TpmAlgId, TpmCap, TpmCc, TpmRc,
-        TpmRcBase, TpmRcIndex, TpmRh, TpmaSession, TpmlPcrSelection,
+        Tpm2bAuth, Tpm2bDigest, Tpm2bMaxBuffer, Tpm2bNonce, TpmAlgId, TpmCap, TpmCc, TpmHt, TpmRc,
+        TpmRcBase, TpmRcIndex, TpmRh, TpmaSession, TpmlPcrSelection, TpmtScheme, TpmuAsymScheme,
     },
+    generate::{tpm_check_dispatch, tpm_check_round_trip, TpmGenerate, TpmRng},
+    hash::{tpm_cp_hash, tpm_handle_name, tpm_nv_name, tpm_rp_hash, TpmHashProvider},
     message::{
         tpm_build_command, tpm_build_response, tpm_parse_command, tpm_parse_response,
-        TpmAuthCommands, TpmCommandBody, TpmContextSaveCommand, TpmEvictControlCommand,
-        TpmFlushContextCommand, TpmFlushContextResponse, TpmGetCapabilityCommand, TpmHashCommand,
-        TpmPcrEventResponse, TpmPcrReadCommand, TpmPcrReadResponse,
+        TpmActivateCredentialCommand, TpmAuthCommands, TpmCommand, TpmCommandBody,
+        TpmContextSaveCommand, TpmEvictControlCommand, TpmFlushContextCommand,
+        TpmFlushContextResponse, TpmGetCapabilityCommand, TpmGetCapabilityResponse, TpmHashCommand,
+        TpmHeader, TpmPcrEventResponse, TpmPcrReadCommand, TpmPcrReadResponse,
+        TpmPolicySecretResponse, TpmQuoteCommand, TpmResponseBody,
     },
-    TpmBuild, TpmParse, TpmPersistent, TpmSession, TpmWriter, TPM_MAX_COMMAND_SIZE,
+    metadata::{tpm_command_metadata, TpmAuthRole, TpmHandleEffect},
+    visit::{TpmValue, TpmVisit, TpmVisitor},
+    TpmBuild, TpmContextHandle, TpmErrorKind, TpmHandle, TpmHmacSession, TpmNvIndex,
+    TpmObjectHandle, TpmParse, TpmPcr, TpmPermanent, TpmPersistent, TpmPolicySession, TpmResult,
+    TpmSession, TpmTransient, TpmWriter, TPM_MAX_COMMAND_SIZE,
 };
 
 fn hex_to_bytes(s: &str) -> Result<Vec<u8>, &'static str> {
@@ -88,6 +96,23 @@ fn test_rc_index_from_value() {
     }
 }
 
+fn test_rc_with_index() {
+    let cases = [
+        (TpmRcBase::Handle, TpmRcIndex::Handle(1), 0x018B),
+        (TpmRcBase::Value, TpmRcIndex::Parameter(8), 0x08C4),
+        (TpmRcBase::AuthFail, TpmRcIndex::Session(0), 0x088E),
+        (TpmRcBase::BadAuth, TpmRcIndex::Session(7), 0x0FA2),
+        (TpmRcBase::NvDefined, TpmRcIndex::Handle(1), 0x014C),
+    ];
+
+    for (base, index, raw_rc) in cases {
+        let rc = TpmRc::from(base).with_index(index);
+        assert_eq!(rc.value(), raw_rc, "{base} {index}");
+        assert_eq!(rc.base(), Ok(base));
+        assert!(rc.is_error() && !rc.is_warning(), "{base} {index}");
+    }
+}
+
 fn test_rc_display() {
     let cases = [
         ("TPM_RC_SUCCESS", 0x0000, "TPM_RC_SUCCESS"),
@@ -131,7 +156,7 @@ fn test_build_get_capability_command() {
         tpm_build_command(
             &cmd,
             tpm2_protocol::data::TpmSt::NoSessions,
-            None,
+            &[],
             &[],
             &mut writer,
         )
@@ -156,7 +181,7 @@ fn test_build_hash_command() {
         tpm_build_command(
             &cmd,
             tpm2_protocol::data::TpmSt::NoSessions,
-            None,
+            &[],
             &[],
             &mut writer,
         )
@@ -264,6 +289,229 @@ fn test_parse_tpm_pcr_event_response() {
     assert_eq!(parsed_sessions, sessions);
 }
 
+fn test_parse_get_capability_tpm_properties_response() {
+    let mut props = tpm2_protocol::data::TpmlTaggedTpmProperty::new();
+    props
+        .try_push(tpm2_protocol::data::TpmsTaggedProperty {
+            property: tpm2_protocol::data::TPM_PT_MAX_COMMAND_SIZE,
+            value: 8192,
+        })
+        .unwrap();
+    props
+        .try_push(tpm2_protocol::data::TpmsTaggedProperty {
+            property: tpm2_protocol::data::TPM_PT_MAX_RESPONSE_SIZE,
+            value: 8192,
+        })
+        .unwrap();
+    let original_resp = TpmGetCapabilityResponse {
+        more_data: true.into(),
+        capability_data: tpm2_protocol::data::TpmsCapabilityData {
+            capability: TpmCap::TpmProperties,
+            data: tpm2_protocol::data::TpmuCapabilities::TpmProperties(props),
+        },
+    };
+    let rc = TpmRc::try_from(TpmRcBase::Success as u32).unwrap();
+
+    let generated_bytes = {
+        let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+        let len = {
+            let mut writer = TpmWriter::new(&mut buf);
+            tpm_build_response(&original_resp, &[], rc, &mut writer).unwrap();
+            writer.len()
+        };
+        buf[..len].to_vec()
+    };
+
+    let (_, parsed_resp, _) = tpm_parse_response(TpmCc::GetCapability, &generated_bytes)
+        .unwrap()
+        .unwrap();
+    assert_eq!(parsed_resp.GetCapability().unwrap(), original_resp);
+}
+
+fn test_typed_response_conversion() {
+    let original_resp = TpmFlushContextResponse {};
+    let rc = TpmRc::try_from(TpmRcBase::Success as u32).unwrap();
+    let generated_bytes = {
+        let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+        let len = {
+            let mut writer = TpmWriter::new(&mut buf);
+            tpm_build_response(&original_resp, &[], rc, &mut writer).unwrap();
+            writer.len()
+        };
+        buf[..len].to_vec()
+    };
+
+    let (_, body, _) = tpm_parse_response(TpmCc::FlushContext, &generated_bytes)
+        .unwrap()
+        .unwrap();
+    let resp = <TpmFlushContextCommand as TpmCommand>::Response::try_from(body.clone()).unwrap();
+    assert_eq!(resp, original_resp);
+    assert!(TpmGetCapabilityResponse::try_from(body).is_err());
+
+    let handles: <TpmEvictControlCommand as TpmHeader>::Handles = Default::default();
+    assert_eq!(handles.len(), TpmEvictControlCommand::HANDLES);
+}
+
+fn test_handle_ranges() {
+    assert_eq!(TpmHt::from_handle(0x0150_0001), Some(TpmHt::NvIndex));
+    assert_eq!(TpmHt::from_handle(0x0300_0000), Some(TpmHt::PolicySession));
+    assert_eq!(TpmHt::from_handle(0x4200_0000), None);
+
+    assert!(TpmNvIndex::try_from(0x0150_0001).is_ok());
+    assert!(TpmNvIndex::try_from(0x8100_0001).is_err());
+    assert!(TpmPcr::try_from(23).is_ok());
+    assert!(TpmPcr::try_from(24).is_err());
+    assert!(TpmHmacSession::try_from(0x0200_0000).is_ok());
+    assert!(TpmPolicySession::try_from(0x0200_0000).is_err());
+    assert!(TpmTransient::try_from(0x8000_0001).is_ok());
+    assert!(TpmTransient::try_from(0x8100_0001).is_err());
+    assert!(TpmPersistent::try_from(0x8100_0001).is_ok());
+    assert!(TpmPersistent::try_from(0x8000_0001).is_err());
+    assert!(TpmSession::try_from(0x0300_0000).is_ok());
+    assert!(TpmSession::try_from(0x8000_0000).is_err());
+    assert_eq!(u32::from(TpmSession::PASSWORD), TpmRh::Password as u32);
+
+    let transient = TpmTransient::try_from(0x8000_0001).unwrap();
+    assert_eq!(TpmContextHandle::from(transient).value(), 0x8000_0001);
+    assert!(TpmContextHandle::try_from(TpmSession::PASSWORD).is_err());
+    assert!(TpmContextHandle::try_from(0x8100_0001).is_err());
+    let persistent = TpmPersistent::try_from(0x8100_0001).unwrap();
+    assert_eq!(TpmObjectHandle::from(persistent).value(), 0x8100_0001);
+    assert!(TpmObjectHandle::try_from(0x0200_0000).is_err());
+
+    let auth = TpmPermanent::try_from(0x4000_0015).unwrap();
+    assert_eq!(auth.auth_index(), Some(5));
+    assert_eq!(auth.act_index(), None);
+    let act = TpmPermanent::try_from(TpmRh::ActLast as u32).unwrap();
+    assert_eq!(act.act_index(), Some(0xF));
+    assert_eq!(act.auth_index(), None);
+    assert!(TpmPermanent::try_from(TpmRh::PlatformNv as u32).is_ok());
+    assert!(TpmPermanent::try_from(0x4000_0120).is_err());
+    assert!(TpmPermanent::try_from(TpmRh::TransientFirst as u32).is_err());
+
+    let flush = TpmFlushContextCommand::default();
+    let err = TpmFlushContextCommand::parse(&[0x81, 0x00, 0x00, 0x01]).unwrap_err();
+    assert!(matches!(err.kind(), TpmErrorKind::InvalidHandle { .. }));
+    assert_eq!(u32::from(flush.flush_handle), TpmRh::TransientFirst as u32);
+
+    let err = TpmNvIndex::parse(&[0x81, 0x00, 0x00, 0x01]).unwrap_err();
+    assert_eq!(
+        *err.kind(),
+        TpmErrorKind::InvalidHandle {
+            type_name: "TpmNvIndex",
+            handle: 0x8100_0001,
+        }
+    );
+}
+
+fn test_command_metadata() {
+    let load = tpm_command_metadata(TpmCc::Load).unwrap();
+    assert_eq!(load.handles, &[TpmAuthRole::User]);
+    assert!(load.decrypt && load.encrypt);
+    assert_eq!(load.handle_effect, TpmHandleEffect::Creates);
+
+    let duplicate = tpm_command_metadata(TpmCc::Duplicate).unwrap();
+    assert_eq!(duplicate.handles, &[TpmAuthRole::Dup, TpmAuthRole::None]);
+    assert_eq!(duplicate.auth_handle_count(), 1);
+
+    let read_public = tpm_command_metadata(TpmCc::ReadPublic).unwrap();
+    assert_eq!(read_public.auth_handle_count(), 0);
+    assert!(!read_public.decrypt && read_public.encrypt);
+
+    let flush = tpm_command_metadata(TpmCc::FlushContext).unwrap();
+    assert_eq!(flush.handle_effect, TpmHandleEffect::Flushes);
+}
+
+struct ConcatHash;
+
+impl TpmHashProvider for ConcatHash {
+    fn digest(&self, alg: TpmAlgId, chunks: &[&[u8]], out: &mut [u8]) -> TpmResult<usize> {
+        if alg != TpmAlgId::Sha256 {
+            return Err(TpmErrorKind::InvalidValue.into());
+        }
+        out.fill(0);
+        let data: Vec<u8> = chunks.concat();
+        let len = data.len().min(out.len());
+        out[..len].copy_from_slice(&data[..len]);
+        Ok(out.len())
+    }
+}
+
+fn test_hash_helpers() {
+    let names: [&[u8]; 2] = [&[0x40, 0x00, 0x00, 0x01], &[0x00, 0x0b, 0xaa]];
+    let cp_hash = tpm_cp_hash(
+        &ConcatHash,
+        TpmAlgId::Sha256,
+        TpmCc::Load,
+        &names,
+        &[0x01, 0x02],
+    )
+    .unwrap();
+    assert_eq!(cp_hash.as_ref().len(), 32);
+    assert_eq!(
+        &cp_hash[..13],
+        &[0x00, 0x00, 0x01, 0x57, 0x40, 0x00, 0x00, 0x01, 0x00, 0x0b, 0xaa, 0x01, 0x02]
+    );
+
+    let rc = TpmRc::try_from(TpmRcBase::Success as u32).unwrap();
+    let rp_hash = tpm_rp_hash(&ConcatHash, TpmAlgId::Sha256, rc, TpmCc::Load, &[0xff]).unwrap();
+    assert_eq!(&rp_hash[..9], &[0, 0, 0, 0, 0x00, 0x00, 0x01, 0x57, 0xff]);
+    assert!(tpm_rp_hash(&ConcatHash, TpmAlgId::Sha384, rc, TpmCc::Load, &[]).is_err());
+
+    let nv_public = tpm2_protocol::data::TpmsNvPublic {
+        nv_index: TpmNvIndex::try_from(0x0150_0001).unwrap(),
+        name_alg: TpmAlgId::Sha256,
+        ..Default::default()
+    };
+    let name = tpm_nv_name(&ConcatHash, &nv_public).unwrap();
+    assert_eq!(name.as_ref().len(), 34);
+    assert_eq!(&name[..6], &[0x00, 0x0b, 0x01, 0x50, 0x00, 0x01]);
+
+    assert_eq!(&*tpm_handle_name(0x4000_0001).unwrap(), &[0x40, 0, 0, 1]);
+    assert!(tpm_handle_name(0x8000_0000).is_err());
+}
+
+fn test_parse_error_location() {
+    let mut digests = tpm2_protocol::data::TpmlDigestValues::new();
+    digests
+        .try_push(tpm2_protocol::data::TpmtHa {
+            hash_alg: TpmAlgId::Sha256,
+            digest: tpm2_protocol::data::TpmuHa::Sha256([0xA1; 32]),
+        })
+        .unwrap();
+    let resp = TpmPcrEventResponse { digests };
+    let rc = TpmRc::try_from(TpmRcBase::Success as u32).unwrap();
+
+    let mut generated_bytes = {
+        let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+        let len = {
+            let mut writer = TpmWriter::new(&mut buf);
+            tpm_build_response(&resp, &[], rc, &mut writer).unwrap();
+            writer.len()
+        };
+        buf[..len].to_vec()
+    };
+    generated_bytes[18..20].copy_from_slice(&0x0099u16.to_be_bytes());
+
+    let err = tpm_parse_response(TpmCc::PcrEvent, &generated_bytes).unwrap_err();
+    assert_eq!(
+        *err.kind(),
+        TpmErrorKind::InvalidDiscriminant {
+            type_name: "TpmAlgId",
+            value: 0x99
+        }
+    );
+    assert_eq!(err.offset(), Some(18));
+    assert_eq!(
+        err.path().collect::<Vec<_>>(),
+        ["TpmPcrEventResponse.digests", "TpmtHa.hash_alg"]
+    );
+    assert_eq!(
+        err.to_string(),
+        "Invalid discriminant 0x99 for type 'TpmAlgId' in TpmPcrEventResponse.digests.hash_alg at offset 18"
+    );
+}
+
 fn test_parse_get_capability_command() {
     let cmd = TpmGetCapabilityCommand {
         cap: TpmCap::Algs,
@@ -278,7 +526,7 @@ fn test_parse_get_capability_command() {
             tpm_build_command(
                 &cmd,
                 tpm2_protocol::data::TpmSt::NoSessions,
-                None,
+                &[],
                 &[],
                 &mut writer,
             )
@@ -316,7 +564,7 @@ fn test_parse_hash_command() {
             tpm_build_command(
                 &cmd,
                 tpm2_protocol::data::TpmSt::NoSessions,
-                None,
+                &[],
                 &[],
                 &mut writer,
             )
@@ -343,7 +591,7 @@ fn test_parse_hash_command() {
 
 fn test_parse_flush_context_command() {
     let cmd = TpmFlushContextCommand {
-        flush_handle: 0x8000_0000,
+        flush_handle: TpmContextHandle::try_from(0x8000_0000).unwrap(),
     };
 
     let generated_bytes = {
@@ -353,7 +601,7 @@ fn test_parse_flush_context_command() {
             tpm_build_command(
                 &cmd,
                 tpm2_protocol::data::TpmSt::NoSessions,
-                None,
+                &[],
                 &[],
                 &mut writer,
             )
@@ -398,7 +646,7 @@ fn test_parse_pcr_read_command() {
             tpm_build_command(
                 &cmd,
                 tpm2_protocol::data::TpmSt::NoSessions,
-                None,
+                &[],
                 &[],
                 &mut writer,
             )
@@ -424,8 +672,7 @@ fn test_parse_pcr_read_command() {
 
 fn test_parse_context_save_command() {
     let cmd = TpmContextSaveCommand {};
-    let save_handle = 0x8000_0001;
-    let handles = [save_handle];
+    let handles = [TpmHandle::try_from(0x8000_0001).unwrap()];
 
     let generated_bytes = {
         let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
@@ -434,7 +681,7 @@ fn test_parse_context_save_command() {
             tpm_build_command(
                 &cmd,
                 tpm2_protocol::data::TpmSt::NoSessions,
-                Some(&handles),
+                &handles,
                 &[],
                 &mut writer,
             )
@@ -449,7 +696,7 @@ fn test_parse_context_save_command() {
 
     match tpm_parse_command(&generated_bytes) {
         Ok((res_handles, cmd_data, sessions)) => {
-            assert_eq!(res_handles.as_ref(), handles);
+            assert_eq!(res_handles.as_ref(), handles.map(u32::from));
             assert_eq!(cmd_data, TpmCommandBody::ContextSave(cmd));
             if !sessions.is_empty() {
                 panic!("sessions should be empty");
@@ -461,13 +708,16 @@ fn test_parse_context_save_command() {
 
 fn test_parse_evict_control_command() {
     let cmd = TpmEvictControlCommand {
-        persistent_handle: TpmPersistent(0x8100_0001),
+        persistent_handle: TpmPersistent::try_from(0x8100_0001).unwrap(),
     };
-    let handles = [TpmRh::Owner as u32, 0x8000_0000];
+    let handles = [
+        TpmPermanent::OWNER.into(),
+        TpmHandle::try_from(0x8000_0000).unwrap(),
+    ];
     let mut sessions = TpmAuthCommands::new();
     sessions
         .try_push(tpm2_protocol::data::TpmsAuthCommand {
-            session_handle: TpmSession(TpmRh::Password as u32),
+            session_handle: TpmSession::PASSWORD,
             nonce: tpm2_protocol::data::Tpm2bNonce::default(),
             session_attributes: tpm2_protocol::data::TpmaSession::default(),
             hmac: tpm2_protocol::data::Tpm2bAuth::try_from(&b"123"[..]).unwrap(),
@@ -481,7 +731,7 @@ fn test_parse_evict_control_command() {
             tpm_build_command(
                 &cmd,
                 tpm2_protocol::data::TpmSt::Sessions,
-                Some(&handles),
+                &handles,
                 &sessions,
                 &mut writer,
             )
@@ -493,11 +743,161 @@ fn test_parse_evict_control_command() {
 
     let (res_handles, res_cmd_data, res_sessions) = tpm_parse_command(&generated_bytes).unwrap();
 
-    assert_eq!(res_handles.as_ref(), handles);
+    assert_eq!(res_handles.as_ref(), handles.map(u32::from));
     assert_eq!(res_sessions, sessions);
     assert_eq!(res_cmd_data, TpmCommandBody::EvictControl(cmd));
 }
 
+fn test_parse_quote_command() {
+    let mut pcr_select = TpmlPcrSelection::new();
+    pcr_select
+        .try_push(tpm2_protocol::data::TpmsPcrSelection {
+            hash: TpmAlgId::Sha256,
+            pcr_select: tpm2_protocol::data::TpmsPcrSelect::try_from(&[0x80, 0x00, 0x00][..])
+                .unwrap(),
+        })
+        .unwrap();
+    let cmd = TpmQuoteCommand {
+        qualifying_data: tpm2_protocol::data::Tpm2bData::try_from(&[0xaa, 0xbb][..]).unwrap(),
+        in_scheme: TpmtScheme {
+            scheme: TpmAlgId::Ecdsa,
+            details: TpmuAsymScheme::Hash(TpmAlgId::Sha256),
+        },
+        pcr_select,
+    };
+    let handles = [TpmHandle::try_from(0x8000_0001).unwrap()];
+    let session = tpm2_protocol::data::TpmsAuthCommand {
+        session_handle: TpmSession::PASSWORD,
+        session_attributes: TpmaSession::CONTINUE_SESSION,
+        ..Default::default()
+    };
+
+    let generated_bytes = {
+        let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+        let len = {
+            let mut writer = TpmWriter::new(&mut buf);
+            tpm_build_command(
+                &cmd,
+                tpm2_protocol::data::TpmSt::Sessions,
+                &handles,
+                &[session],
+                &mut writer,
+            )
+            .unwrap();
+            writer.len()
+        };
+        buf[..len].to_vec()
+    };
+
+    let expected_bytes = hex_to_bytes(
+        "80020000002d0000015880000001000000094000000900000100000002aabb0018000b00000001000b03800000",
+    )
+    .unwrap();
+    assert_eq!(generated_bytes, expected_bytes.as_slice());
+
+    match tpm_parse_command(&generated_bytes) {
+        Ok((res_handles, cmd_data, sessions)) => {
+            assert_eq!(res_handles.as_ref(), handles.map(u32::from));
+            assert_eq!(cmd_data, TpmCommandBody::Quote(cmd));
+            assert_eq!(sessions.as_ref(), [session]);
+        }
+        Err(e) => panic!("command parsing failed: {e:?}"),
+    }
+
+    let null = TpmtScheme {
+        scheme: TpmAlgId::Null,
+        details: TpmuAsymScheme::Null,
+    };
+    let mut buf = [0u8; 8];
+    let len = {
+        let mut writer = TpmWriter::new(&mut buf);
+        null.build(&mut writer).unwrap();
+        writer.len()
+    };
+    assert_eq!(&buf[..len], &[0x00, 0x10]);
+    assert_eq!(TpmtScheme::parse(&buf[..len]).unwrap(), (null, &[][..]));
+}
+
+fn test_parse_activate_credential_command() {
+    let cmd = TpmActivateCredentialCommand {
+        credential_blob: tpm2_protocol::data::Tpm2bIdObject::try_from(&[0x11; 68][..]).unwrap(),
+        secret: tpm2_protocol::data::Tpm2bEncryptedSecret::try_from(&[0x22; 256][..]).unwrap(),
+    };
+    let handles = [
+        TpmHandle::try_from(0x8000_0001).unwrap(),
+        TpmHandle::try_from(0x8000_0002).unwrap(),
+    ];
+    let session = tpm2_protocol::data::TpmsAuthCommand {
+        session_handle: TpmSession::PASSWORD,
+        session_attributes: TpmaSession::CONTINUE_SESSION,
+        ..Default::default()
+    };
+
+    let generated_bytes = {
+        let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+        let len = {
+            let mut writer = TpmWriter::new(&mut buf);
+            tpm_build_command(
+                &cmd,
+                tpm2_protocol::data::TpmSt::Sessions,
+                &handles,
+                &[session, session],
+                &mut writer,
+            )
+            .unwrap();
+            writer.len()
+        };
+        buf[..len].to_vec()
+    };
+    assert_eq!(generated_bytes.len(), 10 + 8 + 4 + 2 * 9 + 2 + 68 + 2 + 256);
+
+    match tpm_parse_command(&generated_bytes) {
+        Ok((res_handles, cmd_data, sessions)) => {
+            assert_eq!(res_handles.as_ref(), handles.map(u32::from));
+            assert_eq!(cmd_data, TpmCommandBody::ActivateCredential(cmd));
+            assert_eq!(sessions.as_ref(), [session, session]);
+        }
+        Err(e) => panic!("command parsing failed: {e:?}"),
+    }
+}
+
+fn test_parse_policy_secret_response() {
+    let original_resp = TpmPolicySecretResponse {
+        timeout: tpm2_protocol::data::Tpm2bTimeout::try_from(&[0x01; 8][..]).unwrap(),
+        policy_ticket: tpm2_protocol::data::TpmtTkAuth {
+            tag: tpm2_protocol::data::TpmSt::AuthSecret,
+            hierarchy: TpmRh::Endorsement,
+            digest: Tpm2bDigest::try_from(&[0xCC; 32][..]).unwrap(),
+        },
+    };
+    let mut sessions = tpm2_protocol::message::TpmAuthResponses::new();
+    sessions
+        .try_push(tpm2_protocol::data::TpmsAuthResponse {
+            nonce: Tpm2bNonce::try_from(&[0xAA; 16][..]).unwrap(),
+            session_attributes: TpmaSession::CONTINUE_SESSION,
+            hmac: Tpm2bAuth::default(),
+        })
+        .unwrap();
+    let rc = TpmRc::try_from(TpmRcBase::Success as u32).unwrap();
+
+    let generated_bytes = {
+        let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+        let len = {
+            let mut writer = TpmWriter::new(&mut buf);
+            tpm_build_response(&original_resp, &sessions, rc, &mut writer).unwrap();
+            writer.len()
+        };
+        buf[..len].to_vec()
+    };
+
+    let (_, parsed_resp, parsed_sessions) =
+        tpm_parse_response(TpmCc::PolicySecret, &generated_bytes)
+            .unwrap()
+            .unwrap();
+    assert_eq!(parsed_resp.PolicySecret().unwrap(), original_resp);
+    assert_eq!(parsed_sessions, sessions);
+}
+
 fn test_response_macro_parse_correctness() {
     let mut digests = tpm2_protocol::data::TpmlDigestValues::new();
     digests
@@ -527,6 +927,133 @@ fn test_response_macro_parse_correctness() {
     assert!(tail.is_empty(), "tail data");
 }
 
+fn test_round_trip_generated_types() {
+    fn check<T>(seed: u64)
+    where
+        T: TpmGenerate + TpmBuild + tpm2_protocol::TpmSized + for<'a> TpmParse<'a> + PartialEq,
+        T: std::fmt::Debug,
+    {
+        let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+        let mut rng = TpmRng::new(seed);
+        let value = T::generate(&mut rng);
+        if let Err(err) = tpm_check_round_trip(&value, &mut buf) {
+            panic!("{value:?}: {err}");
+        }
+    }
+
+    for seed in 0..256 {
+        check::<tpm2_protocol::data::TpmtPublic>(seed);
+        check::<tpm2_protocol::data::TpmtSensitive>(seed);
+        check::<tpm2_protocol::data::TpmsAttest>(seed);
+        check::<tpm2_protocol::data::TpmsCapabilityData>(seed);
+        check::<tpm2_protocol::data::TpmtSignature>(seed);
+        check::<tpm2_protocol::data::TpmtScheme>(seed);
+        check::<tpm2_protocol::data::TpmtSymDefObject>(seed);
+        check::<TpmlPcrSelection>(seed);
+        check::<TpmaSession>(seed);
+        check::<TpmRc>(seed);
+        check::<TpmPermanent>(seed);
+        check::<TpmNvIndex>(seed);
+    }
+
+    let first = tpm2_protocol::data::TpmtPublic::generate(&mut TpmRng::new(7));
+    let second = tpm2_protocol::data::TpmtPublic::generate(&mut TpmRng::new(7));
+    assert_eq!(first, second, "generator is not deterministic");
+}
+
+fn test_round_trip_dispatch() {
+    let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+    for seed in 0..64 {
+        let mut rng = TpmRng::new(seed);
+        if let Err((cc, err)) = tpm_check_dispatch(&mut rng, &mut buf) {
+            panic!("seed {seed}: {cc}: {err}");
+        }
+    }
+}
+
+#[derive(Default)]
+struct RecordingVisitor {
+    depth: usize,
+    lines: Vec<String>,
+}
+
+impl TpmVisitor for RecordingVisitor {
+    fn value(&mut self, field: &'static str, type_name: &'static str, value: TpmValue<'_>) {
+        let value = match value {
+            TpmValue::Enum { name, .. } => name.to_string(),
+            TpmValue::Flags { .. } => value.flag_names().collect::<Vec<_>>().join("|"),
+            TpmValue::Bytes(bytes) => format!("{} bytes", bytes.len()),
+            other => format!("{other:?}"),
+        };
+        self.lines.push(format!(
+            "{}{field}: {type_name} = {value}",
+            " ".repeat(self.depth)
+        ));
+    }
+
+    fn enter(&mut self, field: &'static str, type_name: &'static str) {
+        self.lines
+            .push(format!("{}{field}: {type_name}", " ".repeat(self.depth)));
+        self.depth += 1;
+    }
+
+    fn enter_list(&mut self, field: &'static str, type_name: &'static str, count: usize) {
+        self.lines.push(format!(
+            "{}{field}: {type_name}[{count}]",
+            " ".repeat(self.depth)
+        ));
+        self.depth += 1;
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+fn test_visit_fields() {
+    let mut pcr_values = tpm2_protocol::data::TpmlDigest::new();
+    pcr_values
+        .try_push(Tpm2bDigest::try_from(&[0xDE; 32][..]).unwrap())
+        .unwrap();
+    let resp = TpmResponseBody::PcrRead(TpmPcrReadResponse {
+        pcr_update_counter: 1,
+        pcr_selection_out: TpmlPcrSelection::default(),
+        pcr_values,
+    });
+
+    let mut visitor = RecordingVisitor::default();
+    resp.visit("", &mut visitor);
+    assert_eq!(
+        visitor.lines,
+        [
+            ": TpmPcrReadResponse",
+            " pcr_update_counter: u32 = U32(1)",
+            " pcr_selection_out: TpmList[0]",
+            " pcr_values: TpmList[1]",
+            "  : TpmBuffer = 32 bytes",
+        ]
+    );
+
+    let session = tpm2_protocol::data::TpmsAuthCommand {
+        session_handle: TpmSession::try_from(0x0200_0000).unwrap(),
+        session_attributes: TpmaSession::CONTINUE_SESSION,
+        ..Default::default()
+    };
+    let mut visitor = RecordingVisitor::default();
+    session.visit("session", &mut visitor);
+    assert_eq!(
+        visitor.lines,
+        [
+            "session: TpmsAuthCommand",
+            " session_handle: TpmSession = Handle(33554432)",
+            " nonce: TpmBuffer = 0 bytes",
+            " session_attributes: TpmaSession = CONTINUE_SESSION",
+            " hmac: TpmBuffer = 0 bytes",
+        ]
+    );
+    assert_eq!(visitor.depth, 0);
+}
+
 fn print_ok() {
     if std::io::stderr().is_terminal() {
         println!("\x1B[32mOK\x1B[0m");
@@ -547,6 +1074,7 @@ fn run_all_tests() -> usize {
     let tests: &[(&str, fn())] = &[
         ("test_rc_base_from_raw_rc", test_rc_base_from_raw_rc),
         ("test_rc_index_from_value", test_rc_index_from_value),
+        ("test_rc_with_index", test_rc_with_index),
         ("test_rc_display", test_rc_display),
         (
             "test_build_get_capability_command",
@@ -559,6 +1087,18 @@ fn run_all_tests() -> usize {
             "test_parse_tpm_pcr_event_response",
             test_parse_tpm_pcr_event_response,
         ),
+        (
+            "test_parse_get_capability_tpm_properties_response",
+            test_parse_get_capability_tpm_properties_response,
+        ),
+        (
+            "test_typed_response_conversion",
+            test_typed_response_conversion,
+        ),
+        ("test_handle_ranges", test_handle_ranges),
+        ("test_command_metadata", test_command_metadata),
+        ("test_hash_helpers", test_hash_helpers),
+        ("test_parse_error_location", test_parse_error_location),
         (
             "test_parse_get_capability_command",
             test_parse_get_capability_command,
@@ -577,10 +1117,25 @@ fn run_all_tests() -> usize {
             "test_parse_evict_control_command",
             test_parse_evict_control_command,
         ),
+        ("test_parse_quote_command", test_parse_quote_command),
+        (
+            "test_parse_activate_credential_command",
+            test_parse_activate_credential_command,
+        ),
+        (
+            "test_parse_policy_secret_response",
+            test_parse_policy_secret_response,
+        ),
         (
             "test_response_macro_parse_correctness",
             test_response_macro_parse_correctness,
         ),
+        (
+            "test_round_trip_generated_types",
+            test_round_trip_generated_types,
+        ),
+        ("test_round_trip_dispatch", test_round_trip_dispatch),
+        ("test_visit_fields", test_visit_fields),
     ];
 
     let mut failed = 0;
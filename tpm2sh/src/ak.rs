@@ -0,0 +1,212 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! Attestation keys created as children of the endorsement key.
+
+use crate::{
+    cli,
+    credential::{endorsement_policy_auth, flush_session},
+    ek::requires_endorsement_policy,
+    get_auth_sessions, read_public, TpmDevice, TpmError,
+};
+use std::{fmt, str::FromStr};
+use tpm2_protocol::{
+    data::{
+        Tpm2b, Tpm2bAuth, Tpm2bDigest, Tpm2bName, Tpm2bPrivate, Tpm2bPublic, Tpm2bPublicKeyRsa,
+        Tpm2bSensitiveCreate, Tpm2bSensitiveData, TpmAlgId, TpmEccCurve, TpmaObject,
+        TpmlPcrSelection, TpmsEccPoint, TpmsSensitiveCreate, TpmtKdfScheme, TpmtPublic, TpmtScheme,
+        TpmtSymDefObject, TpmuAsymScheme, TpmuPublicId, TpmuPublicParms,
+    },
+    hash::tpm_object_name,
+    message::{TpmCreateCommand, TpmLoadCommand},
+    TpmObjectHandle, TpmTransient,
+};
+
+/// The signing scheme of an attestation key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AkScheme {
+    #[default]
+    Rsassa,
+    Rsapss,
+    Ecdsa,
+}
+
+impl AkScheme {
+    /// Returns the template of a restricted signing key with this scheme and
+    /// `hash_alg`: an RSA 2048 key for RSASSA and RSAPSS, and a NIST P-256
+    /// key for ECDSA.
+    #[must_use]
+    pub fn public(self, hash_alg: TpmAlgId) -> TpmtPublic {
+        let scheme = TpmtScheme {
+            scheme: match self {
+                Self::Rsassa => TpmAlgId::Rsassa,
+                Self::Rsapss => TpmAlgId::Rsapss,
+                Self::Ecdsa => TpmAlgId::Ecdsa,
+            },
+            details: TpmuAsymScheme::Hash(hash_alg),
+        };
+        let (object_type, parameters, unique) = match self {
+            Self::Rsassa | Self::Rsapss => (
+                TpmAlgId::Rsa,
+                TpmuPublicParms::Rsa {
+                    symmetric: TpmtSymDefObject::default(),
+                    scheme,
+                    key_bits: 2048,
+                    exponent: 0,
+                },
+                TpmuPublicId::Rsa(Tpm2bPublicKeyRsa::default()),
+            ),
+            Self::Ecdsa => (
+                TpmAlgId::Ecc,
+                TpmuPublicParms::Ecc {
+                    symmetric: TpmtSymDefObject::default(),
+                    scheme,
+                    curve_id: TpmEccCurve::NistP256,
+                    kdf: TpmtKdfScheme::default(),
+                },
+                TpmuPublicId::Ecc(TpmsEccPoint::default()),
+            ),
+        };
+        TpmtPublic {
+            object_type,
+            name_alg: TpmAlgId::Sha256,
+            object_attributes: TpmaObject::FIXED_TPM
+                | TpmaObject::FIXED_PARENT
+                | TpmaObject::SENSITIVE_DATA_ORIGIN
+                | TpmaObject::USER_WITH_AUTH
+                | TpmaObject::RESTRICTED
+                | TpmaObject::SIGN_ENCRYPT,
+            auth_policy: Tpm2bDigest::default(),
+            parameters,
+            unique,
+        }
+    }
+}
+
+impl FromStr for AkScheme {
+    type Err = TpmError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rsassa" => Ok(Self::Rsassa),
+            "rsapss" => Ok(Self::Rsapss),
+            "ecdsa" => Ok(Self::Ecdsa),
+            _ => Err(TpmError::Execution(format!("invalid AK scheme: {s}"))),
+        }
+    }
+}
+
+impl fmt::Display for AkScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Rsassa => "rsassa",
+            Self::Rsapss => "rsapss",
+            Self::Ecdsa => "ecdsa",
+        })
+    }
+}
+
+/// An attestation key wrapped by the EK.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ak {
+    pub public: TpmtPublic,
+    pub private: Tpm2bPrivate,
+    pub name: Tpm2bName,
+}
+
+/// Creates an attestation key under `ek`. The USER role of a low range EK is
+/// authorized with `TPM2_PolicySecret` of the endorsement hierarchy, and that
+/// of a high range EK with its empty password.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if a TPM command fails.
+pub fn create_ak(
+    chip: &mut TpmDevice,
+    ek: TpmObjectHandle,
+    scheme: AkScheme,
+    hash_alg: TpmAlgId,
+    auth: Option<&str>,
+    endorsement_auth: Option<&str>,
+    log_format: cli::LogFormat,
+) -> Result<Ak, TpmError> {
+    let (ek_public, _) = read_public(chip, ek, log_format)?;
+    let cmd = TpmCreateCommand {
+        in_sensitive: Tpm2bSensitiveCreate {
+            inner: TpmsSensitiveCreate {
+                user_auth: Tpm2bAuth::try_from(auth.unwrap_or_default().as_bytes())?,
+                data: Tpm2bSensitiveData::default(),
+            },
+        },
+        in_public: Tpm2bPublic::from(scheme.public(hash_alg)),
+        outside_info: Tpm2b::default(),
+        creation_pcr: TpmlPcrSelection::default(),
+    };
+    let handles = [ek.into()];
+    let ek_policy = requires_endorsement_policy(&ek_public);
+    let sessions = if ek_policy {
+        vec![endorsement_policy_auth(
+            chip,
+            &ek_public,
+            endorsement_auth,
+            log_format,
+        )?]
+    } else {
+        get_auth_sessions(chip, &cmd, &handles, None, None, log_format)?
+    };
+    let (resp, _) = match chip.execute(&cmd, &handles, &sessions, log_format) {
+        Ok(resp) => resp,
+        Err(err) => {
+            if ek_policy {
+                flush_session(chip, sessions[0].session_handle, log_format);
+            }
+            return Err(err);
+        }
+    };
+    let public = resp.out_public.inner;
+    let name = tpm_object_name(&crate::SoftwareHash, &public)?;
+    Ok(Ak {
+        public,
+        private: resp.out_private,
+        name,
+    })
+}
+
+/// Loads an attestation key created by [`create_ak`] under `ek`.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if a TPM command fails.
+pub fn load_ak(
+    chip: &mut TpmDevice,
+    ek: TpmObjectHandle,
+    ak: &Ak,
+    endorsement_auth: Option<&str>,
+    log_format: cli::LogFormat,
+) -> Result<TpmTransient, TpmError> {
+    let (ek_public, _) = read_public(chip, ek, log_format)?;
+    let cmd = TpmLoadCommand {
+        in_private: ak.private,
+        in_public: Tpm2bPublic::from(ak.public.clone()),
+    };
+    let handles = [ek.into()];
+    let ek_policy = requires_endorsement_policy(&ek_public);
+    let sessions = if ek_policy {
+        vec![endorsement_policy_auth(
+            chip,
+            &ek_public,
+            endorsement_auth,
+            log_format,
+        )?]
+    } else {
+        get_auth_sessions(chip, &cmd, &handles, None, None, log_format)?
+    };
+    match chip.execute(&cmd, &handles, &sessions, log_format) {
+        Ok((resp, _)) => Ok(resp.object_handle),
+        Err(err) => {
+            if ek_policy {
+                flush_session(chip, sessions[0].session_handle, log_format);
+            }
+            Err(err)
+        }
+    }
+}
@@ -6,12 +6,15 @@
 
 use crate::{
     cli::{
-        Algorithms, Cli, Commands, Convert, CreatePrimary, Delete, Import, Load, Objects, PcrEvent,
-        PcrRead, Policy, PrintError, ResetLock, Save, Seal, StartSession, Unseal,
+        ActivateCredential, Algorithms, Cli, Commands, Convert, CreateAk, CreatePrimary, Decode,
+        DecodeInput, Delete, Ek, EkAction, Eventlog, Hash, Ima, Import, Load, MakeCredential,
+        NvDefine, Objects, PcrEvent, PcrRead, Policy, PrintError, Proxy, Quote, ResetLock, Save,
+        Seal, Sign, StartSession, Unseal, VerifyQuote, VerifySignature,
     },
-    parse_hex_u32, parse_persistent_handle, parse_tpm_rc, TpmError,
+    parse_hex_u32, parse_pcr_handle, parse_persistent_handle, parse_tpm_cc, parse_tpm_rc, TpmError,
 };
-use std::{env::Args, fmt::Write};
+use std::{env::Args, fmt::Write, time::Duration};
+use tpm2_protocol::TpmHandle;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -32,26 +35,74 @@ const GLOBAL_OPTIONS: &[CommandLineOption] = &[
         "<SESSION>",
         "Authorization session context",
     ),
+    (
+        None,
+        "--timeout",
+        "<MS>",
+        "Time to wait for a response [default: no timeout]",
+    ),
+    (
+        None,
+        "--retries",
+        "<COUNT>",
+        "Retries for TPM_RC_RETRY and similar warnings [default: 8]",
+    ),
+    (
+        None,
+        "--retry-budget",
+        "<MS>",
+        "Total time to spend retrying [default: 10000]",
+    ),
     (Some("-h"), "--help", "", "Print help information"),
     (Some("-V"), "--version", "", "Print version information"),
 ];
 
+const ACTIVATE_CREDENTIAL_ABOUT: &str = "Decrypts a credential made for a key and the EK";
 const ALGORITHMS_ABOUT: &str = "Lists available algorithms";
 const CONVERT_ABOUT: &str = "Converts keys between ASN.1 and JSON format";
+const CREATE_AK_ABOUT: &str = "Creates an attestation key under the EK";
 const CREATE_PRIMARY_ABOUT: &str = "Creates a primary key";
+const DECODE_ABOUT: &str = "Decodes a TPM command or response";
 const DELETE_ABOUT: &str = "Deletes a transient or persistent object";
+const EK_ABOUT: &str = "Creates the endorsement key and verifies its certificate";
+const EVENTLOG_ABOUT: &str = "Parses the measured boot event log and replays it into PCRs";
+const HASH_ABOUT: &str = "Hashes data of any size with the TPM";
+const IMA_ABOUT: &str = "Parses and verifies the IMA runtime measurement list";
 const IMPORT_ABOUT: &str = "Imports an external key";
 const LOAD_ABOUT: &str = "Loads a TPM key";
+const MAKE_CREDENTIAL_ABOUT: &str = "Makes a credential for a key without a TPM";
+const NV_DEFINE_ABOUT: &str = "Defines an NV index";
 const OBJECTS_ABOUT: &str = "Lists objects in volatile and non-volatile memory";
 const PCR_EVENT_ABOUT: &str = "Extends a PCR with an event";
 const PCR_READ_ABOUT: &str = "Reads PCRs";
 const POLICY_ABOUT: &str = "Builds a policy using a policy expression";
 const PRINT_ERROR_ABOUT: &str = "Encodes and print a TPM error code";
+const PROXY_ABOUT: &str = "Forwards commands from clients to the TPM and logs them";
+const QUOTE_ABOUT: &str = "Signs PCR values with an attestation key";
 const RESET_LOCK_ABOUT: &str = "Resets the dictionary attack lockout timer";
 const SAVE_ABOUT: &str = "Saves to non-volatile memory";
 const SEAL_ABOUT: &str = "Seals a keyedhash object";
+const SIGN_ABOUT: &str = "Signs data with a key";
 const START_SESSION_ABOUT: &str = "Starts an authorization session";
 const UNSEAL_ABOUT: &str = "Unseals a keyedhash object";
+const VERIFY_QUOTE_ABOUT: &str = "Verifies a quote without a TPM";
+const VERIFY_SIGNATURE_ABOUT: &str = "Verifies a signature with a key or without a TPM";
+
+const ACTIVATE_CREDENTIAL_USAGE: &str = "tpm2sh activate-credential [OPTIONS]";
+const ACTIVATE_CREDENTIAL_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--auth",
+        "<AUTH>",
+        "Authorization for the key the credential was made for",
+    ),
+    (
+        None,
+        "--endorsement-auth",
+        "<AUTH>",
+        "Authorization for the endorsement hierarchy",
+    ),
+];
 
 const ALGORITHMS_USAGE: &str = "tpm2sh algorithms [OPTIONS]";
 const ALGORITHMS_OPTIONS: &[CommandLineOption] = &[(
@@ -77,7 +128,31 @@ const CONVERT_OPTIONS: &[CommandLineOption] = &[
     ),
 ];
 
-const CREATE_PRIMARY_USAGE: &str = "tpm2sh create-primary [OPTIONS] --alg <ALG>";
+const CREATE_AK_USAGE: &str = "tpm2sh create-ak [OPTIONS]";
+const CREATE_AK_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--scheme",
+        "<SCHEME>",
+        "[default: rsassa, possible: rsassa, rsapss, ecdsa]",
+    ),
+    (
+        None,
+        "--hash",
+        "<ALG>",
+        "[default: sha256, possible: sha256, sha384, sha512]",
+    ),
+    (None, "--auth", "<AUTH>", "Authorization value for the AK"),
+    (
+        None,
+        "--endorsement-auth",
+        "<AUTH>",
+        "Authorization for the endorsement hierarchy",
+    ),
+];
+
+const CREATE_PRIMARY_USAGE: &str =
+    "tpm2sh create-primary [OPTIONS] <--alg <ALG>|--template <TEMPLATE>>";
 const CREATE_PRIMARY_OPTIONS: &[CommandLineOption] = &[
     (
         Some("-H"),
@@ -91,6 +166,12 @@ const CREATE_PRIMARY_OPTIONS: &[CommandLineOption] = &[
         "<ALGORITHM>",
         "Public key algorithm. Run 'algorithms' for options",
     ),
+    (
+        None,
+        "--template",
+        "<TEMPLATE>",
+        "Public area template as JSON or binary TPM2B_PUBLIC",
+    ),
     (
         None,
         "--persistent",
@@ -105,10 +186,159 @@ const CREATE_PRIMARY_OPTIONS: &[CommandLineOption] = &[
     ),
 ];
 
+const DECODE_USAGE: &str = "tpm2sh decode [OPTIONS] <KIND> [INPUT]";
+const DECODE_ARGS: &[CommandLineArgument] = &[
+    ("<KIND>", "[possible: command, response]"),
+    ("[INPUT]", "Hex or binary input file [default: stdin]"),
+];
+const DECODE_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--cc",
+        "<CC>",
+        "Command code, e.g. 'TPM_CC_ReadPublic' or '0x173' (required for responses)",
+    ),
+    (
+        None,
+        "--format",
+        "<FORMAT>",
+        "Output format [default: pretty, possible: pretty, json]",
+    ),
+    (None, "--hex", "", "Read the input as hex"),
+    (None, "--binary", "", "Read the input as binary"),
+];
+
 const DELETE_USAGE: &str = "tpm2sh delete [OPTIONS] <HANDLE>";
 const DELETE_ARGS: &[CommandLineArgument] = &[("<HANDLE>", "Handle of the object to delete")];
 const DELETE_OPTIONS: &[CommandLineOption] = &[(None, "--auth", "<AUTH>", "Authorization value")];
 
+const EK_USAGE: &str = "tpm2sh ek [OPTIONS] [ACTION] [CERTIFICATE]";
+const EK_ARGS: &[CommandLineArgument] = &[
+    ("[ACTION]", "[default: create, possible: create, verify]"),
+    (
+        "[CERTIFICATE]",
+        "EK certificate to verify [default: the certificate in the pipeline]",
+    ),
+];
+const EK_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--template",
+        "<TEMPLATE>",
+        "[default: rsa2048, possible: rsa2048, ecc-nist-p256, rsa3072, ecc-nist-p384]",
+    ),
+    (
+        None,
+        "--persistent",
+        "<HANDLE>",
+        "Store object to non-volatile memory",
+    ),
+    (
+        None,
+        "--owner-auth",
+        "<AUTH>",
+        "Authorization for the owner hierarchy, used with '--persistent'",
+    ),
+    (
+        None,
+        "--public",
+        "<FILE>",
+        "EK public area, written by 'create' and read by 'verify'",
+    ),
+    (
+        None,
+        "--ca-dir",
+        "<DIR>",
+        "Directory of trusted CA certificates for 'verify'",
+    ),
+    (
+        None,
+        "--auth",
+        "<AUTH>",
+        "Authorization for the endorsement hierarchy",
+    ),
+];
+
+const EVENTLOG_USAGE: &str = "tpm2sh eventlog [OPTIONS] [INPUT]";
+const EVENTLOG_ARGS: &[CommandLineArgument] = &[(
+    "[INPUT]",
+    "Event log [default: /sys/kernel/security/tpm0/binary_bios_measurements]",
+)];
+const EVENTLOG_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--verify",
+        "",
+        "Compare the replayed PCRs with the PCRs of the TPM",
+    ),
+    (
+        None,
+        "--pcrs",
+        "<FILE>",
+        "Compare the replayed PCRs with the output of 'pcr-read'",
+    ),
+];
+
+const HASH_USAGE: &str = "tpm2sh hash [OPTIONS] <INPUT>";
+const HASH_ARGS: &[CommandLineArgument] =
+    &[("<INPUT>", "The data to hash, 'data:<HEX>' or a file path")];
+const HASH_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--hash",
+        "<ALG>",
+        "[default: sha256, possible: sha256, sha384, sha512]",
+    ),
+    (
+        Some("-H"),
+        "--hierarchy",
+        "<HIERARCHY>",
+        "Hierarchy of the ticket [default: owner, possible: owner, platform, endorsement]",
+    ),
+    (
+        None,
+        "--ticket",
+        "<FILE>",
+        "Write the TPMT_TK_HASHCHECK ticket to a file",
+    ),
+    (
+        None,
+        "--event",
+        "",
+        "Hash as an event with the algorithm of each PCR bank",
+    ),
+    (
+        None,
+        "--pcr-handle",
+        "<HANDLE>",
+        "Extend the event digests to a PCR, implies '--event'",
+    ),
+    (None, "--auth", "<AUTH>", "Authorization value of the PCR"),
+];
+
+const IMA_USAGE: &str = "tpm2sh ima [OPTIONS] <ACTION> [INPUT]";
+const IMA_ARGS: &[CommandLineArgument] = &[
+    ("<ACTION>", "[possible: list, verify]"),
+    (
+        "[INPUT]",
+        "Measurement list [default: /sys/kernel/security/ima/binary_runtime_measurements]",
+    ),
+];
+const IMA_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--allowlist",
+        "<FILE>",
+        "Require file digests to be listed, one per line as in 'sha256sum'",
+    ),
+    (
+        None,
+        "--pcrs",
+        "<FILE>",
+        "Compare with the output of 'pcr-read' instead of the TPM",
+    ),
+];
+
 const IMPORT_USAGE: &str = "tpm2sh import [OPTIONS]";
 const IMPORT_OPTIONS: &[CommandLineOption] = &[(
     None,
@@ -122,8 +352,47 @@ const LOAD_OPTIONS: &[CommandLineOption] = &[(
     None,
     "--auth",
     "<AUTH>",
-    "Authorization for the parent object",
+    "Authorization for the parent object, or the endorsement hierarchy for an EK",
+)];
+
+const MAKE_CREDENTIAL_USAGE: &str =
+    "tpm2sh make-credential [OPTIONS] --public <FILE> --name <HEX> <SECRET>";
+const MAKE_CREDENTIAL_ARGS: &[CommandLineArgument] =
+    &[("<SECRET>", "The credential, 'data:<HEX>' or a file path")];
+const MAKE_CREDENTIAL_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--public",
+        "<FILE>",
+        "Public area of the EK, an object or a TPM2B_PUBLIC",
+    ),
+    (
+        None,
+        "--name",
+        "<HEX>",
+        "Name of the key the credential is made for",
+    ),
+];
+
+const NV_DEFINE_USAGE: &str = "tpm2sh nv-define [OPTIONS] <PUBLIC>";
+const NV_DEFINE_ARGS: &[CommandLineArgument] = &[(
+    "<PUBLIC>",
+    "NV public area as JSON or binary TPM2B_NV_PUBLIC",
 )];
+const NV_DEFINE_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--auth",
+        "<AUTH>",
+        "Authorization value for the hierarchy",
+    ),
+    (
+        None,
+        "--index-auth",
+        "<AUTH>",
+        "Authorization value for the new index",
+    ),
+];
 
 const OBJECTS_USAGE: &str = "tpm2sh objects";
 
@@ -152,11 +421,62 @@ const POLICY_OPTIONS: &[CommandLineOption] = &[
         "",
         "Enable partial consumption of the PCR object",
     ),
+    (
+        None,
+        "--offline",
+        "",
+        "Compute the policy digest in software without a TPM",
+    ),
+    (
+        None,
+        "--hash-alg",
+        "<ALG>",
+        "Offline policy hash [default: sha256, possible: sha256, sha384, sha512]",
+    ),
 ];
 
 const PRINT_ERROR_USAGE: &str = "tpm2sh print-error <RC>";
 const PRINT_ERROR_ARGS: &[CommandLineArgument] = &[("<RC>", "TPM error code")];
 
+const PROXY_USAGE: &str = "tpm2sh proxy [OPTIONS] <--tcp <ADDR>|--unix <PATH>>";
+const PROXY_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--tcp",
+        "<ADDR>",
+        "Serve the TPM simulator protocol, e.g. '127.0.0.1:2321'",
+    ),
+    (
+        None,
+        "--unix",
+        "<PATH>",
+        "Serve raw commands on a Unix socket",
+    ),
+    (
+        None,
+        "--log",
+        "<FILE>",
+        "Append the JSON Lines log to a file [default: stdout]",
+    ),
+];
+
+const QUOTE_USAGE: &str = "tpm2sh quote [OPTIONS] <SELECTION>";
+const QUOTE_ARGS: &[CommandLineArgument] = &[("<SELECTION>", "e.g. 'sha256:0,1,2+sha1:0'")];
+const QUOTE_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--nonce",
+        "<HEX>",
+        "Qualifying data from the verifier",
+    ),
+    (
+        None,
+        "--auth",
+        "<AUTH>",
+        "Authorization for the signing key",
+    ),
+];
+
 const RESET_LOCK_USAGE: &str = "tpm2sh reset-lock [OPTIONS]";
 const RESET_LOCK_OPTIONS: &[CommandLineOption] =
     &[(None, "--auth", "<AUTH>", "Authorization value")];
@@ -186,6 +506,26 @@ const SEAL_OPTIONS: &[CommandLineOption] = &[(
     "Authorization value (use once for parent, twice for object)",
 )];
 
+const SIGN_USAGE: &str = "tpm2sh sign [OPTIONS] <INPUT>";
+const SIGN_ARGS: &[CommandLineArgument] =
+    &[("<INPUT>", "The data to sign, 'data:<HEX>' or a file path")];
+const SIGN_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--hash",
+        "<ALG>",
+        "[default: sha256, possible: sha256, sha384, sha512]",
+    ),
+    (
+        None,
+        "--format",
+        "<FORMAT>",
+        "[default: tpm, possible: tpm, raw, der]",
+    ),
+    (None, "--output", "<FILE>", "Write the signature to a file"),
+    (None, "--auth", "<AUTH>", "Authorization value"),
+];
+
 const START_SESSION_USAGE: &str = "tpm2sh start-session [OPTIONS]";
 const START_SESSION_OPTIONS: &[CommandLineOption] = &[
     (
@@ -205,6 +545,54 @@ const START_SESSION_OPTIONS: &[CommandLineOption] = &[
 const UNSEAL_USAGE: &str = "tpm2sh unseal [OPTIONS]";
 const UNSEAL_OPTIONS: &[CommandLineOption] = &[(None, "--auth", "<AUTH>", "Authorization value")];
 
+const VERIFY_QUOTE_USAGE: &str = "tpm2sh verify-quote [OPTIONS] --public <FILE> --nonce <HEX>";
+const VERIFY_QUOTE_OPTIONS: &[CommandLineOption] = &[
+    (
+        None,
+        "--public",
+        "<FILE>",
+        "Public key of the signer, an object or a TPM2B_PUBLIC",
+    ),
+    (None, "--nonce", "<HEX>", "Expected qualifying data"),
+    (
+        None,
+        "--pcrs",
+        "<FILE>",
+        "Expected PCR values in the output format of 'pcr-read' [default: pipeline]",
+    ),
+];
+
+const VERIFY_SIGNATURE_USAGE: &str = "tpm2sh verify-signature [OPTIONS] --signature <FILE> <INPUT>";
+const VERIFY_SIGNATURE_ARGS: &[CommandLineArgument] =
+    &[("<INPUT>", "The signed data, 'data:<HEX>' or a file path")];
+const VERIFY_SIGNATURE_OPTIONS: &[CommandLineOption] = &[
+    (None, "--signature", "<FILE>", "The signature"),
+    (
+        None,
+        "--format",
+        "<FORMAT>",
+        "[default: tpm, possible: tpm, raw, der]",
+    ),
+    (
+        None,
+        "--hash",
+        "<ALG>",
+        "Hash of a raw or DER signature [possible: sha256, sha384, sha512]",
+    ),
+    (
+        None,
+        "--public",
+        "<FILE>",
+        "Verify without a TPM using a public key, an object or a TPM2B_PUBLIC",
+    ),
+    (
+        None,
+        "--ticket",
+        "<FILE>",
+        "Write the TPMT_TK_VERIFIED ticket to a file",
+    ),
+];
+
 fn format_help_section(title: &str, items: &[(String, &str)], max_len: usize) -> String {
     let mut output = format!("\n{title}:\n");
     for (left, right) in items {
@@ -273,6 +661,10 @@ struct Subcommand {
 }
 
 const SUBCOMMANDS: &[Subcommand] = &[
+    Subcommand {
+        name: "activate-credential",
+        about: ACTIVATE_CREDENTIAL_ABOUT,
+    },
     Subcommand {
         name: "algorithms",
         about: ALGORITHMS_ABOUT,
@@ -281,14 +673,38 @@ const SUBCOMMANDS: &[Subcommand] = &[
         name: "convert",
         about: CONVERT_ABOUT,
     },
+    Subcommand {
+        name: "create-ak",
+        about: CREATE_AK_ABOUT,
+    },
     Subcommand {
         name: "create-primary",
         about: CREATE_PRIMARY_ABOUT,
     },
+    Subcommand {
+        name: "decode",
+        about: DECODE_ABOUT,
+    },
     Subcommand {
         name: "delete",
         about: DELETE_ABOUT,
     },
+    Subcommand {
+        name: "ek",
+        about: EK_ABOUT,
+    },
+    Subcommand {
+        name: "eventlog",
+        about: EVENTLOG_ABOUT,
+    },
+    Subcommand {
+        name: "hash",
+        about: HASH_ABOUT,
+    },
+    Subcommand {
+        name: "ima",
+        about: IMA_ABOUT,
+    },
     Subcommand {
         name: "import",
         about: IMPORT_ABOUT,
@@ -297,6 +713,14 @@ const SUBCOMMANDS: &[Subcommand] = &[
         name: "load",
         about: LOAD_ABOUT,
     },
+    Subcommand {
+        name: "make-credential",
+        about: MAKE_CREDENTIAL_ABOUT,
+    },
+    Subcommand {
+        name: "nv-define",
+        about: NV_DEFINE_ABOUT,
+    },
     Subcommand {
         name: "objects",
         about: OBJECTS_ABOUT,
@@ -317,6 +741,14 @@ const SUBCOMMANDS: &[Subcommand] = &[
         name: "print-error",
         about: PRINT_ERROR_ABOUT,
     },
+    Subcommand {
+        name: "proxy",
+        about: PROXY_ABOUT,
+    },
+    Subcommand {
+        name: "quote",
+        about: QUOTE_ABOUT,
+    },
     Subcommand {
         name: "reset-lock",
         about: RESET_LOCK_ABOUT,
@@ -329,6 +761,10 @@ const SUBCOMMANDS: &[Subcommand] = &[
         name: "seal",
         about: SEAL_ABOUT,
     },
+    Subcommand {
+        name: "sign",
+        about: SIGN_ABOUT,
+    },
     Subcommand {
         name: "start-session",
         about: START_SESSION_ABOUT,
@@ -337,6 +773,14 @@ const SUBCOMMANDS: &[Subcommand] = &[
         name: "unseal",
         about: UNSEAL_ABOUT,
     },
+    Subcommand {
+        name: "verify-quote",
+        about: VERIFY_QUOTE_ABOUT,
+    },
+    Subcommand {
+        name: "verify-signature",
+        about: VERIFY_SIGNATURE_ABOUT,
+    },
 ];
 
 struct ArgParser {
@@ -448,6 +892,15 @@ pub fn parse_cli(args: Args) -> Result<Option<Cli>, TpmError> {
             "-d" | "--device" => cli.device = parser.expect_value(&arg)?,
             "--log-format" => cli.log_format = parser.expect_value(&arg)?.parse()?,
             "--session" => cli.session = Some(parser.expect_value(&arg)?),
+            "--timeout" => {
+                let ms = parser.expect_value(&arg)?.parse()?;
+                cli.timeout = Some(Duration::from_millis(ms));
+            }
+            "--retries" => cli.retry_policy.max_retries = parser.expect_value(&arg)?.parse()?,
+            "--retry-budget" => {
+                let ms = parser.expect_value(&arg)?.parse()?;
+                cli.retry_policy.budget = Duration::from_millis(ms);
+            }
             "--" => {
                 subcommand_arg = parser.next();
                 break;
@@ -470,22 +923,36 @@ pub fn parse_cli(args: Args) -> Result<Option<Cli>, TpmError> {
 
 fn parse_subcommand(cmd_name: &str, parser: &mut ArgParser) -> Result<Commands, TpmError> {
     let command = match cmd_name {
+        "activate-credential" => parse_activate_credential(parser)?,
         "algorithms" => parse_algorithms(parser)?,
         "convert" => parse_convert(parser)?,
+        "create-ak" => parse_create_ak(parser)?,
         "create-primary" => parse_create_primary(parser)?,
+        "decode" => parse_decode(parser)?,
         "delete" => parse_delete(parser)?,
+        "ek" => parse_ek(parser)?,
+        "eventlog" => parse_eventlog(parser)?,
+        "hash" => parse_hash(parser)?,
+        "ima" => parse_ima(parser)?,
         "import" => parse_import(parser)?,
         "load" => parse_load(parser)?,
+        "make-credential" => parse_make_credential(parser)?,
+        "nv-define" => parse_nv_define(parser)?,
         "objects" => parse_objects(parser)?,
         "pcr-event" => parse_pcr_event(parser)?,
         "pcr-read" => parse_pcr_read(parser)?,
         "policy" => parse_policy(parser)?,
         "print-error" => parse_print_error(parser)?,
+        "proxy" => parse_proxy(parser)?,
+        "quote" => parse_quote(parser)?,
         "reset-lock" => parse_reset_lock(parser)?,
         "save" => parse_save(parser)?,
         "seal" => parse_seal(parser)?,
+        "sign" => parse_sign(parser)?,
         "start-session" => parse_start_session(parser)?,
         "unseal" => parse_unseal(parser)?,
+        "verify-quote" => parse_verify_quote(parser)?,
+        "verify-signature" => parse_verify_signature(parser)?,
         "-h" | "--help" => {
             print_main_help();
             std::process::exit(0);
@@ -495,20 +962,23 @@ fn parse_subcommand(cmd_name: &str, parser: &mut ArgParser) -> Result<Commands,
     Ok(command)
 }
 
-fn parse_algorithms(parser: &mut ArgParser) -> Result<Commands, TpmError> {
-    let mut args = Algorithms { filter: None };
+fn parse_activate_credential(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = ActivateCredential::default();
     while let Some(arg) = parser.next() {
         match arg.as_str() {
-            "--filter" => args.filter = Some(parser.expect_value(&arg)?),
+            "--auth" => args.auth.auth = Some(parser.expect_value(&arg)?),
+            "--endorsement-auth" => {
+                args.endorsement_auth.auth = Some(parser.expect_value(&arg)?);
+            }
             "-h" | "--help" => {
                 println!(
                     "{}",
                     format_subcommand_help(
-                        "algorithms",
-                        ALGORITHMS_ABOUT,
-                        ALGORITHMS_USAGE,
+                        "activate-credential",
+                        ACTIVATE_CREDENTIAL_ABOUT,
+                        ACTIVATE_CREDENTIAL_USAGE,
                         &[],
-                        ALGORITHMS_OPTIONS
+                        ACTIVATE_CREDENTIAL_OPTIONS
                     )
                 );
                 std::process::exit(0);
@@ -516,15 +986,39 @@ fn parse_algorithms(parser: &mut ArgParser) -> Result<Commands, TpmError> {
             _ => return Err(TpmError::Execution(format!("unknown argument '{arg}'"))),
         }
     }
-    Ok(Commands::Algorithms(args))
+    Ok(Commands::ActivateCredential(args))
 }
 
-fn parse_convert(parser: &mut ArgParser) -> Result<Commands, TpmError> {
-    let mut args = Convert::default();
+fn parse_algorithms(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = Algorithms { filter: None };
     while let Some(arg) = parser.next() {
         match arg.as_str() {
-            "--from" => args.from = parser.expect_value(&arg)?.parse()?,
-            "--to" => args.to = parser.expect_value(&arg)?.parse()?,
+            "--filter" => args.filter = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help(
+                        "algorithms",
+                        ALGORITHMS_ABOUT,
+                        ALGORITHMS_USAGE,
+                        &[],
+                        ALGORITHMS_OPTIONS
+                    )
+                );
+                std::process::exit(0);
+            }
+            _ => return Err(TpmError::Execution(format!("unknown argument '{arg}'"))),
+        }
+    }
+    Ok(Commands::Algorithms(args))
+}
+
+fn parse_convert(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = Convert::default();
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--from" => args.from = parser.expect_value(&arg)?.parse()?,
+            "--to" => args.to = parser.expect_value(&arg)?.parse()?,
             "-h" | "--help" => {
                 println!(
                     "{}",
@@ -544,6 +1038,35 @@ fn parse_convert(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     Ok(Commands::Convert(args))
 }
 
+fn parse_create_ak(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = CreateAk::default();
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--scheme" => args.scheme = parser.expect_value(&arg)?.parse()?,
+            "--hash" => args.hash_alg = parser.expect_value(&arg)?.parse()?,
+            "--auth" => args.auth.auth = Some(parser.expect_value(&arg)?),
+            "--endorsement-auth" => {
+                args.endorsement_auth.auth = Some(parser.expect_value(&arg)?);
+            }
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help(
+                        "create-ak",
+                        CREATE_AK_ABOUT,
+                        CREATE_AK_USAGE,
+                        &[],
+                        CREATE_AK_OPTIONS
+                    )
+                );
+                std::process::exit(0);
+            }
+            _ => return Err(TpmError::Execution(format!("unknown argument '{arg}'"))),
+        }
+    }
+    Ok(Commands::CreateAk(args))
+}
+
 fn parse_create_primary(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     let mut args = CreatePrimary::default();
     let mut alg_set = false;
@@ -557,6 +1080,7 @@ fn parse_create_primary(parser: &mut ArgParser) -> Result<Commands, TpmError> {
                     .map_err(TpmError::Parse)?;
                 alg_set = true;
             }
+            "--template" => args.template = Some(parser.expect_value(&arg)?),
             "--persistent" => {
                 args.persistent = Some(parse_persistent_handle(&parser.expect_value(&arg)?)?);
             }
@@ -577,14 +1101,62 @@ fn parse_create_primary(parser: &mut ArgParser) -> Result<Commands, TpmError> {
             _ => return Err(TpmError::Execution(format!("unknown argument '{arg}'"))),
         }
     }
-    if !alg_set {
+    if alg_set == args.template.is_some() {
         return Err(TpmError::Execution(
-            "the following required arguments were not provided: --alg <ALGORITHM>".to_string(),
+            "exactly one of --alg <ALGORITHM> or --template <TEMPLATE> is required".to_string(),
         ));
     }
     Ok(Commands::CreatePrimary(args))
 }
 
+fn parse_decode(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = Decode::default();
+    let mut kind_arg = None;
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--cc" => args.cc = Some(parse_tpm_cc(&parser.expect_value(&arg)?)?),
+            "--format" => args.format = parser.expect_value(&arg)?.parse()?,
+            "--hex" | "--binary" if args.input_format != DecodeInput::Auto => {
+                return Err(TpmError::Execution(
+                    "--hex and --binary cannot be used together".to_string(),
+                ))
+            }
+            "--hex" => args.input_format = DecodeInput::Hex,
+            "--binary" => args.input_format = DecodeInput::Binary,
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help(
+                        "decode",
+                        DECODE_ABOUT,
+                        DECODE_USAGE,
+                        DECODE_ARGS,
+                        DECODE_OPTIONS
+                    )
+                );
+                std::process::exit(0);
+            }
+            _ if (!arg.starts_with('-') || arg == "-") && kind_arg.is_none() => {
+                kind_arg = Some(arg);
+            }
+            _ if (!arg.starts_with('-') || arg == "-") && args.input.is_none() => {
+                args.input = Some(arg);
+            }
+            _ => {
+                return Err(TpmError::Execution(format!(
+                    "unknown or duplicate argument '{arg}'"
+                )))
+            }
+        }
+    }
+    args.kind = kind_arg
+        .ok_or_else(|| {
+            TpmError::Execution("missing required positional argument <KIND>".to_string())
+        })?
+        .parse()?;
+    Ok(Commands::Decode(args))
+}
+
 fn parse_delete(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     let mut args = Delete::default();
     let mut handle_str = None;
@@ -617,10 +1189,176 @@ fn parse_delete(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     let handle = handle_str.ok_or_else(|| {
         TpmError::Execution("missing required positional argument <HANDLE>".to_string())
     })?;
-    args.handle = parse_hex_u32(&handle)?;
+    args.handle = TpmHandle::try_from(parse_hex_u32(&handle)?)?;
     Ok(Commands::Delete(args))
 }
 
+fn parse_ek(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = Ek::default();
+    let mut action_arg = None;
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--template" => args.template = parser.expect_value(&arg)?.parse()?,
+            "--persistent" => {
+                args.persistent = Some(parse_persistent_handle(&parser.expect_value(&arg)?)?);
+            }
+            "--owner-auth" => args.owner_auth.auth = Some(parser.expect_value(&arg)?),
+            "--public" => args.public = Some(parser.expect_value(&arg)?),
+            "--ca-dir" => args.ca_dir = Some(parser.expect_value(&arg)?),
+            "--auth" => args.auth.auth = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help("ek", EK_ABOUT, EK_USAGE, EK_ARGS, EK_OPTIONS)
+                );
+                std::process::exit(0);
+            }
+            _ if !arg.starts_with('-') && action_arg.is_none() => {
+                action_arg = Some(arg);
+            }
+            _ if (!arg.starts_with('-') || arg == "-") && args.input.is_none() => {
+                args.input = Some(arg);
+            }
+            _ => {
+                return Err(TpmError::Execution(format!(
+                    "unknown or duplicate argument '{arg}'"
+                )))
+            }
+        }
+    }
+    if let Some(action) = action_arg {
+        args.action = action.parse()?;
+    }
+    match args.action {
+        EkAction::Create if args.input.is_some() || args.ca_dir.is_some() => {
+            return Err(TpmError::Execution(
+                "'create' does not take a certificate or '--ca-dir'".to_string(),
+            ));
+        }
+        EkAction::Verify if args.ca_dir.is_none() || args.public.is_none() => {
+            return Err(TpmError::Execution(
+                "'verify' requires '--ca-dir' and '--public'".to_string(),
+            ));
+        }
+        _ => {}
+    }
+    Ok(Commands::Ek(args))
+}
+
+fn parse_eventlog(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = Eventlog::default();
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--verify" => args.verify = true,
+            "--pcrs" => args.pcrs = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help(
+                        "eventlog",
+                        EVENTLOG_ABOUT,
+                        EVENTLOG_USAGE,
+                        EVENTLOG_ARGS,
+                        EVENTLOG_OPTIONS
+                    )
+                );
+                std::process::exit(0);
+            }
+            _ if (!arg.starts_with('-') || arg == "-") && args.input.is_none() => {
+                args.input = Some(arg);
+            }
+            _ => {
+                return Err(TpmError::Execution(format!(
+                    "unknown or duplicate argument '{arg}'"
+                )))
+            }
+        }
+    }
+    if args.verify && args.pcrs.is_some() {
+        return Err(TpmError::Execution(
+            "'--verify' and '--pcrs' are mutually exclusive".to_string(),
+        ));
+    }
+    Ok(Commands::Eventlog(args))
+}
+
+fn parse_hash(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = Hash::default();
+    let mut input = None;
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--hash" => args.hash_alg = parser.expect_value(&arg)?.parse()?,
+            "-H" | "--hierarchy" => args.hierarchy = parser.expect_value(&arg)?.parse()?,
+            "--ticket" => args.ticket = Some(parser.expect_value(&arg)?),
+            "--event" => args.event = true,
+            "--pcr-handle" => {
+                args.pcr_handle = Some(parse_pcr_handle(&parser.expect_value(&arg)?)?);
+                args.event = true;
+            }
+            "--auth" => args.auth.auth = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help("hash", HASH_ABOUT, HASH_USAGE, HASH_ARGS, HASH_OPTIONS)
+                );
+                std::process::exit(0);
+            }
+            _ if !arg.starts_with('-') && input.is_none() => {
+                input = Some(arg);
+            }
+            _ => {
+                return Err(TpmError::Execution(format!(
+                    "unknown or duplicate argument '{arg}'"
+                )))
+            }
+        }
+    }
+    args.input = input.ok_or_else(|| {
+        TpmError::Execution("missing required positional argument <INPUT>".to_string())
+    })?;
+    if args.event && args.ticket.is_some() {
+        return Err(TpmError::Execution(
+            "'--ticket' cannot be used with '--event'".to_string(),
+        ));
+    }
+    Ok(Commands::Hash(args))
+}
+
+fn parse_ima(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = Ima::default();
+    let mut action_arg = None;
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--allowlist" => args.allowlist = Some(parser.expect_value(&arg)?),
+            "--pcrs" => args.pcrs = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help("ima", IMA_ABOUT, IMA_USAGE, IMA_ARGS, IMA_OPTIONS)
+                );
+                std::process::exit(0);
+            }
+            _ if !arg.starts_with('-') && action_arg.is_none() => {
+                action_arg = Some(arg);
+            }
+            _ if (!arg.starts_with('-') || arg == "-") && args.input.is_none() => {
+                args.input = Some(arg);
+            }
+            _ => {
+                return Err(TpmError::Execution(format!(
+                    "unknown or duplicate argument '{arg}'"
+                )))
+            }
+        }
+    }
+    args.action = action_arg
+        .ok_or_else(|| {
+            TpmError::Execution("missing required positional argument <ACTION>".to_string())
+        })?
+        .parse()?;
+    Ok(Commands::Ima(args))
+}
+
 fn parse_import(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     let mut args = Import::default();
     while let Some(arg) = parser.next() {
@@ -663,6 +1401,82 @@ fn parse_load(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     Ok(Commands::Load(args))
 }
 
+fn parse_make_credential(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = MakeCredential::default();
+    let mut public = None;
+    let mut name = None;
+    let mut secret = None;
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--public" => public = Some(parser.expect_value(&arg)?),
+            "--name" => name = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help(
+                        "make-credential",
+                        MAKE_CREDENTIAL_ABOUT,
+                        MAKE_CREDENTIAL_USAGE,
+                        MAKE_CREDENTIAL_ARGS,
+                        MAKE_CREDENTIAL_OPTIONS
+                    )
+                );
+                std::process::exit(0);
+            }
+            _ if !arg.starts_with('-') && secret.is_none() => {
+                secret = Some(arg);
+            }
+            _ => {
+                return Err(TpmError::Execution(format!(
+                    "unknown or duplicate argument '{arg}'"
+                )))
+            }
+        }
+    }
+    args.public = public
+        .ok_or_else(|| TpmError::Execution("missing required option '--public'".to_string()))?;
+    args.name =
+        name.ok_or_else(|| TpmError::Execution("missing required option '--name'".to_string()))?;
+    args.secret = secret.ok_or_else(|| {
+        TpmError::Execution("missing required positional argument <SECRET>".to_string())
+    })?;
+    Ok(Commands::MakeCredential(args))
+}
+
+fn parse_nv_define(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = NvDefine::default();
+    let mut public = None;
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--auth" => args.auth.auth = Some(parser.expect_value(&arg)?),
+            "--index-auth" => args.index_auth = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help(
+                        "nv-define",
+                        NV_DEFINE_ABOUT,
+                        NV_DEFINE_USAGE,
+                        NV_DEFINE_ARGS,
+                        NV_DEFINE_OPTIONS
+                    )
+                );
+                std::process::exit(0);
+            }
+            _ if !arg.starts_with('-') && public.is_none() => public = Some(arg),
+            _ => {
+                return Err(TpmError::Execution(format!(
+                    "unknown or duplicate argument '{arg}'"
+                )))
+            }
+        }
+    }
+    args.public = public.ok_or_else(|| {
+        TpmError::Execution("missing required positional argument <PUBLIC>".to_string())
+    })?;
+    Ok(Commands::NvDefine(args))
+}
+
 fn parse_objects(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     if let Some(arg) = parser.next() {
         if arg == "-h" || arg == "--help" {
@@ -684,7 +1498,7 @@ fn parse_pcr_event(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     let mut data_arg = None;
     while let Some(arg) = parser.next() {
         match arg.as_str() {
-            "--pcr-handle" => args.pcr_handle = parse_hex_u32(&parser.expect_value(&arg)?)?,
+            "--pcr-handle" => args.pcr_handle = parse_pcr_handle(&parser.expect_value(&arg)?)?,
             "--auth" => args.auth.auth = Some(parser.expect_value(&arg)?),
             "-h" | "--help" => {
                 println!(
@@ -743,6 +1557,8 @@ fn parse_policy(parser: &mut ArgParser) -> Result<Commands, TpmError> {
         match arg.as_str() {
             "--auth" => args.auth.auth = Some(parser.expect_value(&arg)?),
             "-p" | "--partial" => args.partial = true,
+            "--offline" => args.offline = true,
+            "--hash-alg" => args.hash_alg = Some(parser.expect_value(&arg)?.parse()?),
             "-h" | "--help" => {
                 println!(
                     "{}",
@@ -769,6 +1585,11 @@ fn parse_policy(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     args.expression = expression_arg.ok_or_else(|| {
         TpmError::Execution("missing required positional argument <EXPRESSION>".to_string())
     })?;
+    if args.hash_alg.is_some() && !args.offline {
+        return Err(TpmError::Execution(
+            "'--hash-alg' requires '--offline'".to_string(),
+        ));
+    }
     Ok(Commands::Policy(args))
 }
 
@@ -795,6 +1616,67 @@ fn parse_print_error(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     }))
 }
 
+fn parse_proxy(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = Proxy::default();
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--tcp" => args.tcp = Some(parser.expect_value(&arg)?),
+            "--unix" => args.unix = Some(parser.expect_value(&arg)?),
+            "--log" => args.log = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help("proxy", PROXY_ABOUT, PROXY_USAGE, &[], PROXY_OPTIONS)
+                );
+                std::process::exit(0);
+            }
+            _ => return Err(TpmError::Execution(format!("unknown argument '{arg}'"))),
+        }
+    }
+    if args.tcp.is_some() == args.unix.is_some() {
+        return Err(TpmError::Execution(
+            "exactly one of '--tcp <ADDR>' and '--unix <PATH>' is required".to_string(),
+        ));
+    }
+    Ok(Commands::Proxy(args))
+}
+
+fn parse_quote(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = Quote::default();
+    let mut selection_arg = None;
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--nonce" => args.nonce = parser.expect_value(&arg)?,
+            "--auth" => args.auth.auth = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help(
+                        "quote",
+                        QUOTE_ABOUT,
+                        QUOTE_USAGE,
+                        QUOTE_ARGS,
+                        QUOTE_OPTIONS
+                    )
+                );
+                std::process::exit(0);
+            }
+            _ if !arg.starts_with('-') && selection_arg.is_none() => {
+                selection_arg = Some(arg);
+            }
+            _ => {
+                return Err(TpmError::Execution(format!(
+                    "unknown or duplicate argument '{arg}'"
+                )))
+            }
+        }
+    }
+    args.selection = selection_arg.ok_or_else(|| {
+        TpmError::Execution("missing required positional argument <SELECTION>".to_string())
+    })?;
+    Ok(Commands::Quote(args))
+}
+
 fn parse_reset_lock(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     let mut args = ResetLock::default();
     while let Some(arg) = parser.next() {
@@ -823,7 +1705,10 @@ fn parse_save(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     let mut args = Save::default();
     while let Some(arg) = parser.next() {
         match arg.as_str() {
-            "--object-handle" => args.object_handle = parse_hex_u32(&parser.expect_value(&arg)?)?,
+            "--object-handle" => {
+                args.object_handle =
+                    TpmHandle::try_from(parse_hex_u32(&parser.expect_value(&arg)?)?)?;
+            }
             "--persistent-handle" => {
                 args.persistent_handle = parse_persistent_handle(&parser.expect_value(&arg)?)?;
             }
@@ -865,6 +1750,38 @@ fn parse_seal(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     Ok(Commands::Seal(args))
 }
 
+fn parse_sign(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = Sign::default();
+    let mut input = None;
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--hash" => args.hash_alg = Some(parser.expect_value(&arg)?.parse()?),
+            "--format" => args.format = parser.expect_value(&arg)?.parse()?,
+            "--output" => args.output = Some(parser.expect_value(&arg)?),
+            "--auth" => args.auth.auth = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help("sign", SIGN_ABOUT, SIGN_USAGE, SIGN_ARGS, SIGN_OPTIONS)
+                );
+                std::process::exit(0);
+            }
+            _ if !arg.starts_with('-') && input.is_none() => {
+                input = Some(arg);
+            }
+            _ => {
+                return Err(TpmError::Execution(format!(
+                    "unknown or duplicate argument '{arg}'"
+                )))
+            }
+        }
+    }
+    args.input = input.ok_or_else(|| {
+        TpmError::Execution("missing required positional argument <INPUT>".to_string())
+    })?;
+    Ok(Commands::Sign(args))
+}
+
 fn parse_start_session(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     let mut args = StartSession::default();
     while let Some(arg) = parser.next() {
@@ -913,3 +1830,82 @@ fn parse_unseal(parser: &mut ArgParser) -> Result<Commands, TpmError> {
     }
     Ok(Commands::Unseal(args))
 }
+
+fn parse_verify_quote(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = VerifyQuote::default();
+    let mut public = None;
+    let mut nonce = None;
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--public" => public = Some(parser.expect_value(&arg)?),
+            "--nonce" => nonce = Some(parser.expect_value(&arg)?),
+            "--pcrs" => args.pcrs = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help(
+                        "verify-quote",
+                        VERIFY_QUOTE_ABOUT,
+                        VERIFY_QUOTE_USAGE,
+                        &[],
+                        VERIFY_QUOTE_OPTIONS
+                    )
+                );
+                std::process::exit(0);
+            }
+            _ => return Err(TpmError::Execution(format!("unknown argument '{arg}'"))),
+        }
+    }
+    args.public = public
+        .ok_or_else(|| TpmError::Execution("missing required option '--public'".to_string()))?;
+    args.nonce = nonce
+        .ok_or_else(|| TpmError::Execution("missing required option '--nonce'".to_string()))?;
+    Ok(Commands::VerifyQuote(args))
+}
+
+fn parse_verify_signature(parser: &mut ArgParser) -> Result<Commands, TpmError> {
+    let mut args = VerifySignature::default();
+    let mut input = None;
+    let mut signature = None;
+    while let Some(arg) = parser.next() {
+        match arg.as_str() {
+            "--signature" => signature = Some(parser.expect_value(&arg)?),
+            "--format" => args.format = parser.expect_value(&arg)?.parse()?,
+            "--hash" => args.hash_alg = Some(parser.expect_value(&arg)?.parse()?),
+            "--public" => args.public = Some(parser.expect_value(&arg)?),
+            "--ticket" => args.ticket = Some(parser.expect_value(&arg)?),
+            "-h" | "--help" => {
+                println!(
+                    "{}",
+                    format_subcommand_help(
+                        "verify-signature",
+                        VERIFY_SIGNATURE_ABOUT,
+                        VERIFY_SIGNATURE_USAGE,
+                        VERIFY_SIGNATURE_ARGS,
+                        VERIFY_SIGNATURE_OPTIONS
+                    )
+                );
+                std::process::exit(0);
+            }
+            _ if !arg.starts_with('-') && input.is_none() => {
+                input = Some(arg);
+            }
+            _ => {
+                return Err(TpmError::Execution(format!(
+                    "unknown or duplicate argument '{arg}'"
+                )))
+            }
+        }
+    }
+    args.input = input.ok_or_else(|| {
+        TpmError::Execution("missing required positional argument <INPUT>".to_string())
+    })?;
+    args.signature = signature
+        .ok_or_else(|| TpmError::Execution("missing required option '--signature'".to_string()))?;
+    if args.public.is_some() && args.ticket.is_some() {
+        return Err(TpmError::Execution(
+            "'--ticket' requires verification with the TPM".to_string(),
+        ));
+    }
+    Ok(Commands::VerifySignature(args))
+}
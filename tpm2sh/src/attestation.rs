@@ -0,0 +1,396 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! Verification of signed `TPMS_ATTEST` structures against a set of
+//! expectations.
+//!
+//! The TPM obfuscates `resetCount`, `restartCount` and `firmwareVersion`
+//! unless the signing key is in the endorsement or the platform hierarchy, so
+//! the expectations for those fields only make sense for such keys.
+
+use crate::{build_to_vec, crypto::verify_signature, TpmError};
+use serde_json::{json, Value};
+use std::fmt;
+use tpm2_protocol::{
+    data::{TpmSt, TpmsAttest, TpmsClockInfo, TpmtPublic, TpmtSignature},
+    TpmParse,
+};
+
+/// The `TPM_GENERATED_VALUE` magic that starts every `TPMS_ATTEST`.
+pub const TPM_GENERATED_VALUE: u32 = 0xff54_4347;
+
+/// A signed attestation: the `TPMS_ATTEST` bytes exactly as returned by the
+/// TPM and the signature over them.
+#[derive(Debug, Clone)]
+pub struct SignedAttest {
+    pub attest: Vec<u8>,
+    pub signature: TpmtSignature,
+}
+
+/// The properties an attestation is expected to have. A `None` field is not
+/// checked.
+#[derive(Debug, Clone, Default)]
+pub struct AttestExpectations {
+    pub attest_type: Option<TpmSt>,
+    /// The qualified name of the signing key.
+    pub qualified_signer: Option<Vec<u8>>,
+    /// The nonce given to the TPM as qualifying data.
+    pub extra_data: Option<Vec<u8>>,
+    /// The clock of an earlier attestation from the same TPM.
+    pub previous_clock: Option<TpmsClockInfo>,
+    pub firmware_version: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttestSeverity {
+    /// An event the caller may want to act on, such as a reboot.
+    Info,
+    /// The attestation must not be trusted.
+    Failure,
+}
+
+impl AttestSeverity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Failure => "failure",
+        }
+    }
+}
+
+/// The result of a single check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttestFinding {
+    Malformed(String),
+    InvalidMagic(u32),
+    InvalidSignature(String),
+    TypeMismatch {
+        expected: TpmSt,
+        actual: TpmSt,
+    },
+    QualifiedSignerMismatch {
+        expected: Vec<u8>,
+        actual: Vec<u8>,
+    },
+    ExtraDataMismatch {
+        expected: Vec<u8>,
+        actual: Vec<u8>,
+    },
+    /// The clock may have been rolled back by an unorderly shutdown.
+    ClockNotSafe,
+    ClockRollback {
+        previous: u64,
+        current: u64,
+    },
+    ResetCountRollback {
+        previous: u32,
+        current: u32,
+    },
+    RestartCountRollback {
+        previous: u32,
+        current: u32,
+    },
+    /// The TPM has been reset, i.e. the machine has rebooted.
+    Reset {
+        previous: u32,
+        current: u32,
+    },
+    /// The TPM has been restarted, i.e. the machine has resumed from
+    /// hibernation.
+    Restart {
+        previous: u32,
+        current: u32,
+    },
+    FirmwareVersionMismatch {
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl AttestFinding {
+    #[must_use]
+    pub fn severity(&self) -> AttestSeverity {
+        match self {
+            Self::ClockNotSafe | Self::Reset { .. } | Self::Restart { .. } => AttestSeverity::Info,
+            _ => AttestSeverity::Failure,
+        }
+    }
+
+    /// A stable identifier for the kind of the finding.
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Malformed(_) => "malformed",
+            Self::InvalidMagic(_) => "invalid_magic",
+            Self::InvalidSignature(_) => "invalid_signature",
+            Self::TypeMismatch { .. } => "type_mismatch",
+            Self::QualifiedSignerMismatch { .. } => "qualified_signer_mismatch",
+            Self::ExtraDataMismatch { .. } => "extra_data_mismatch",
+            Self::ClockNotSafe => "clock_not_safe",
+            Self::ClockRollback { .. } => "clock_rollback",
+            Self::ResetCountRollback { .. } => "reset_count_rollback",
+            Self::RestartCountRollback { .. } => "restart_count_rollback",
+            Self::Reset { .. } => "reset",
+            Self::Restart { .. } => "restart",
+            Self::FirmwareVersionMismatch { .. } => "firmware_version_mismatch",
+        }
+    }
+
+    #[must_use]
+    pub fn to_json(&self) -> Value {
+        json!({
+            "kind": self.kind(),
+            "severity": self.severity().as_str(),
+            "message": self.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for AttestFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(msg) => write!(f, "malformed attestation: {msg}"),
+            Self::InvalidMagic(magic) => {
+                write!(
+                    f,
+                    "attestation was not generated by a TPM: magic {magic:#010x}"
+                )
+            }
+            Self::InvalidSignature(msg) => write!(f, "invalid attestation signature: {msg}"),
+            Self::TypeMismatch { expected, actual } => {
+                write!(f, "attestation type {actual}, expected {expected}")
+            }
+            Self::QualifiedSignerMismatch { expected, actual } => write!(
+                f,
+                "qualified signer {}, expected {}",
+                hex::encode(actual),
+                hex::encode(expected)
+            ),
+            Self::ExtraDataMismatch { expected, actual } => write!(
+                f,
+                "extra data {}, expected {}",
+                hex::encode(actual),
+                hex::encode(expected)
+            ),
+            Self::ClockNotSafe => write!(f, "clock is not safe"),
+            Self::ClockRollback { previous, current } => {
+                write!(f, "clock went backwards from {previous} to {current}")
+            }
+            Self::ResetCountRollback { previous, current } => {
+                write!(f, "reset count went backwards from {previous} to {current}")
+            }
+            Self::RestartCountRollback { previous, current } => {
+                write!(
+                    f,
+                    "restart count went backwards from {previous} to {current}"
+                )
+            }
+            Self::Reset { previous, current } => {
+                write!(f, "TPM was reset: reset count {previous} -> {current}")
+            }
+            Self::Restart { previous, current } => {
+                write!(
+                    f,
+                    "TPM was restarted: restart count {previous} -> {current}"
+                )
+            }
+            Self::FirmwareVersionMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "firmware version {actual:#018x}, expected {expected:#018x}"
+                )
+            }
+        }
+    }
+}
+
+/// The findings for an attestation.
+#[derive(Debug, Clone)]
+pub struct AttestReport {
+    /// The parsed attestation, if it could be parsed.
+    pub attest: Option<TpmsAttest>,
+    pub findings: Vec<AttestFinding>,
+}
+
+impl AttestReport {
+    /// Returns `true` if no finding is a failure.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &AttestFinding> {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity() == AttestSeverity::Failure)
+    }
+
+    /// Converts the first failure into an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError::Execution` if the report contains a failure.
+    pub fn into_result(self) -> Result<TpmsAttest, TpmError> {
+        if let Some(failure) = self.failures().next() {
+            return Err(TpmError::Execution(failure.to_string()));
+        }
+        self.attest
+            .ok_or_else(|| TpmError::Execution("attestation could not be parsed".to_string()))
+    }
+
+    #[must_use]
+    pub fn to_json(&self) -> Value {
+        let mut value = json!({
+            "valid": self.is_valid(),
+            "findings": self.findings.iter().map(AttestFinding::to_json).collect::<Vec<_>>(),
+        });
+        if let Some(attest) = &self.attest {
+            value["type"] = json!(attest.attest_type.to_string());
+            value["qualified_signer"] = json!(hex::encode(&*attest.qualified_signer));
+            value["extra_data"] = json!(hex::encode(&*attest.extra_data));
+            value["clock"] = json!(attest.clock_info.clock);
+            value["reset_count"] = json!(attest.clock_info.reset_count);
+            value["restart_count"] = json!(attest.clock_info.restart_count);
+            value["safe"] = json!(attest.clock_info.safe.0);
+            value["firmware_version"] = json!(format!("{:#018x}", attest.firmware_version));
+        }
+        value
+    }
+}
+
+/// Compares the clock of an attestation with an earlier one. A reset clears
+/// the restart count, so the restart counts are only compared when the reset
+/// counts are equal.
+fn check_clock(
+    previous: &TpmsClockInfo,
+    current: &TpmsClockInfo,
+    findings: &mut Vec<AttestFinding>,
+) {
+    if current.clock < previous.clock {
+        findings.push(AttestFinding::ClockRollback {
+            previous: previous.clock,
+            current: current.clock,
+        });
+    }
+    if current.reset_count < previous.reset_count {
+        findings.push(AttestFinding::ResetCountRollback {
+            previous: previous.reset_count,
+            current: current.reset_count,
+        });
+    } else if current.reset_count > previous.reset_count {
+        findings.push(AttestFinding::Reset {
+            previous: previous.reset_count,
+            current: current.reset_count,
+        });
+    } else if current.restart_count < previous.restart_count {
+        findings.push(AttestFinding::RestartCountRollback {
+            previous: previous.restart_count,
+            current: current.restart_count,
+        });
+    } else if current.restart_count > previous.restart_count {
+        findings.push(AttestFinding::Restart {
+            previous: previous.restart_count,
+            current: current.restart_count,
+        });
+    }
+}
+
+impl SignedAttest {
+    /// Parses a signed attestation from the outputs of a TPM command.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the attestation cannot be built.
+    pub fn new(attest: &TpmsAttest, signature: TpmtSignature) -> Result<Self, TpmError> {
+        Ok(Self {
+            attest: build_to_vec(attest)?,
+            signature,
+        })
+    }
+
+    /// Verifies the attestation with the public key of the signer and checks
+    /// it against `expect`. Every check is run, so that the report lists all
+    /// the findings.
+    #[must_use]
+    pub fn verify(&self, public: &TpmtPublic, expect: &AttestExpectations) -> AttestReport {
+        let mut findings = Vec::new();
+        if let Err(err) = verify_signature(public, &self.attest, &self.signature) {
+            findings.push(AttestFinding::InvalidSignature(err.to_string()));
+        }
+
+        let magic = self
+            .attest
+            .get(..4)
+            .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+        if magic != Some(TPM_GENERATED_VALUE) {
+            findings.push(AttestFinding::InvalidMagic(magic.unwrap_or(0)));
+            return AttestReport {
+                attest: None,
+                findings,
+            };
+        }
+        let attest = match TpmsAttest::parse(&self.attest) {
+            Ok((attest, [])) => attest,
+            Ok(_) => {
+                findings.push(AttestFinding::Malformed(
+                    "trailing data after the attestation".to_string(),
+                ));
+                return AttestReport {
+                    attest: None,
+                    findings,
+                };
+            }
+            Err(err) => {
+                findings.push(AttestFinding::Malformed(err.to_string()));
+                return AttestReport {
+                    attest: None,
+                    findings,
+                };
+            }
+        };
+
+        if let Some(expected) = expect.attest_type {
+            if attest.attest_type != expected {
+                findings.push(AttestFinding::TypeMismatch {
+                    expected,
+                    actual: attest.attest_type,
+                });
+            }
+        }
+        if let Some(expected) = &expect.qualified_signer {
+            if **expected != *attest.qualified_signer {
+                findings.push(AttestFinding::QualifiedSignerMismatch {
+                    expected: expected.clone(),
+                    actual: attest.qualified_signer.to_vec(),
+                });
+            }
+        }
+        if let Some(expected) = &expect.extra_data {
+            if **expected != *attest.extra_data {
+                findings.push(AttestFinding::ExtraDataMismatch {
+                    expected: expected.clone(),
+                    actual: attest.extra_data.to_vec(),
+                });
+            }
+        }
+        if !attest.clock_info.safe.0 {
+            findings.push(AttestFinding::ClockNotSafe);
+        }
+        if let Some(previous) = &expect.previous_clock {
+            check_clock(previous, &attest.clock_info, &mut findings);
+        }
+        if let Some(expected) = expect.firmware_version {
+            if attest.firmware_version != expected {
+                findings.push(AttestFinding::FirmwareVersionMismatch {
+                    expected,
+                    actual: attest.firmware_version,
+                });
+            }
+        }
+
+        AttestReport {
+            attest: Some(attest),
+            findings,
+        }
+    }
+}
@@ -2,7 +2,13 @@
 // Copyright (c) 2025 Opinsys Oy
 // Copyright (c) 2024-2025 Jarkko Sakkinen
 
-use crate::{formats::PcrOutput, Alg, Command, TpmError};
+use crate::{
+    ak::AkScheme,
+    ek::EkTemplate,
+    formats::{CredentialOutput, PcrOutput, QuoteOutput},
+    signature::SignatureFormat,
+    Alg, Command, TpmError, TpmRetryPolicy,
+};
 use serde::{
     de::{self, Deserializer, MapAccess, Visitor},
     ser::{SerializeMap, Serializer},
@@ -10,9 +16,10 @@ use serde::{
 };
 use std::fmt;
 use std::str::FromStr;
+use std::time::Duration;
 use tpm2_protocol::{
-    data::{TpmCap, TpmRc, TpmRh, TpmuCapabilities},
-    TpmPersistent, TpmTransient,
+    data::{TpmCap, TpmCc, TpmRc, TpmRh, TpmuCapabilities},
+    TpmHandle, TpmPcr, TpmPermanent, TpmPersistent, TpmTransient,
 };
 
 #[derive(Debug, Clone)]
@@ -21,6 +28,10 @@ pub enum Object {
     Persistent(TpmPersistent),
     Context(serde_json::Value),
     Pcrs(PcrOutput),
+    Quote(QuoteOutput),
+    Credential(CredentialOutput),
+    Certificate(String),
+    Name(String),
 }
 
 impl Serialize for Object {
@@ -42,6 +53,18 @@ impl Serialize for Object {
             Object::Pcrs(p) => {
                 map.serialize_entry("pcrs", p)?;
             }
+            Object::Quote(q) => {
+                map.serialize_entry("quote", q)?;
+            }
+            Object::Credential(c) => {
+                map.serialize_entry("credential", c)?;
+            }
+            Object::Certificate(c) => {
+                map.serialize_entry("certificate", c)?;
+            }
+            Object::Name(n) => {
+                map.serialize_entry("name", n)?;
+            }
         }
         map.end()
     }
@@ -53,8 +76,10 @@ impl<'de> Visitor<'de> for ObjectVisitor {
     type Value = Object;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter
-            .write_str("an object with a single key: 'handle', 'persistent', 'context', or 'pcrs'")
+        formatter.write_str(
+            "an object with a single key: 'handle', 'persistent', 'context', 'pcrs', 'quote', \
+             'credential', 'certificate', or 'name'",
+        )
     }
 
     fn visit_map<V>(self, mut map: V) -> Result<Object, V::Error>
@@ -69,7 +94,7 @@ impl<'de> Visitor<'de> for ObjectVisitor {
             "handle" => {
                 let s: String = serde_json::from_value(value).map_err(de::Error::custom)?;
                 let handle = crate::parse_hex_u32(&s)
-                    .map(TpmTransient)
+                    .and_then(|handle| Ok(TpmTransient::try_from(handle)?))
                     .map_err(de::Error::custom)?;
                 Ok(Object::Handle(handle))
             }
@@ -83,9 +108,34 @@ impl<'de> Visitor<'de> for ObjectVisitor {
                 let pcrs = serde_json::from_value(value).map_err(de::Error::custom)?;
                 Ok(Object::Pcrs(pcrs))
             }
+            "quote" => {
+                let quote = serde_json::from_value(value).map_err(de::Error::custom)?;
+                Ok(Object::Quote(quote))
+            }
+            "credential" => {
+                let credential = serde_json::from_value(value).map_err(de::Error::custom)?;
+                Ok(Object::Credential(credential))
+            }
+            "certificate" => {
+                let pem = serde_json::from_value(value).map_err(de::Error::custom)?;
+                Ok(Object::Certificate(pem))
+            }
+            "name" => {
+                let name = serde_json::from_value(value).map_err(de::Error::custom)?;
+                Ok(Object::Name(name))
+            }
             _ => Err(de::Error::unknown_field(
                 &key,
-                &["handle", "persistent", "context", "pcrs"],
+                &[
+                    "handle",
+                    "persistent",
+                    "context",
+                    "pcrs",
+                    "quote",
+                    "credential",
+                    "certificate",
+                    "name",
+                ],
             )),
         }
     }
@@ -124,6 +174,8 @@ pub struct Cli {
     pub device: String,
     pub log_format: LogFormat,
     pub session: Option<String>,
+    pub timeout: Option<Duration>,
+    pub retry_policy: TpmRetryPolicy,
     pub command: Option<Commands>,
 }
 
@@ -157,6 +209,16 @@ impl From<Hierarchy> for TpmRh {
     }
 }
 
+impl From<Hierarchy> for TpmPermanent {
+    fn from(h: Hierarchy) -> Self {
+        match h {
+            Hierarchy::Owner => TpmPermanent::OWNER,
+            Hierarchy::Platform => TpmPermanent::PLATFORM,
+            Hierarchy::Endorsement => TpmPermanent::ENDORSEMENT,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum SessionType {
     #[default]
@@ -239,50 +301,177 @@ impl FromStr for KeyFormat {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecodeKind {
+    #[default]
+    Command,
+    Response,
+}
+
+impl FromStr for DecodeKind {
+    type Err = TpmError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "command" => Ok(DecodeKind::Command),
+            "response" => Ok(DecodeKind::Response),
+            _ => Err(TpmError::Execution(format!("invalid message kind: {s}"))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecodeFormat {
+    #[default]
+    Pretty,
+    Json,
+}
+
+impl FromStr for DecodeFormat {
+    type Err = TpmError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(DecodeFormat::Pretty),
+            "json" => Ok(DecodeFormat::Json),
+            _ => Err(TpmError::Execution(format!("invalid output format: {s}"))),
+        }
+    }
+}
+
+/// How `decode` interprets its input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecodeInput {
+    /// Hex if the input consists only of hexadecimal digits and whitespace,
+    /// otherwise binary.
+    #[default]
+    Auto,
+    Hex,
+    Binary,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EkAction {
+    #[default]
+    Create,
+    Verify,
+}
+
+impl FromStr for EkAction {
+    type Err = TpmError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "create" => Ok(EkAction::Create),
+            "verify" => Ok(EkAction::Verify),
+            _ => Err(TpmError::Execution(format!("invalid EK action: {s}"))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImaAction {
+    #[default]
+    List,
+    Verify,
+}
+
+impl FromStr for ImaAction {
+    type Err = TpmError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "list" => Ok(ImaAction::List),
+            "verify" => Ok(ImaAction::Verify),
+            _ => Err(TpmError::Execution(format!("invalid IMA action: {s}"))),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Commands {
+    ActivateCredential(ActivateCredential),
     Algorithms(Algorithms),
     Convert(Convert),
+    CreateAk(CreateAk),
     CreatePrimary(CreatePrimary),
+    Decode(Decode),
     Delete(Delete),
+    Ek(Ek),
+    Eventlog(Eventlog),
+    Hash(Hash),
+    Ima(Ima),
     Import(Import),
     Load(Load),
+    MakeCredential(MakeCredential),
+    NvDefine(NvDefine),
     Objects(Objects),
     PcrEvent(PcrEvent),
     PcrRead(PcrRead),
     Policy(Policy),
     PrintError(PrintError),
+    Proxy(Proxy),
+    Quote(Quote),
     ResetLock(ResetLock),
     Save(Save),
     Seal(Seal),
+    Sign(Sign),
     StartSession(StartSession),
     Unseal(Unseal),
+    VerifyQuote(VerifyQuote),
+    VerifySignature(VerifySignature),
 }
 
 impl Command for Commands {
+    fn is_local(&self) -> bool {
+        match self {
+            Self::Convert(args) => args.is_local(),
+            Self::Decode(args) => args.is_local(),
+            Self::Ek(args) => args.is_local(),
+            Self::Eventlog(args) => args.is_local(),
+            Self::Ima(args) => args.is_local(),
+            Self::MakeCredential(args) => args.is_local(),
+            Self::Policy(args) => args.is_local(),
+            Self::PrintError(args) => args.is_local(),
+            Self::VerifyQuote(args) => args.is_local(),
+            Self::VerifySignature(args) => args.is_local(),
+            _ => false,
+        }
+    }
+
     fn run(
         &self,
-        device: &mut crate::TpmDevice,
+        device: Option<&mut crate::TpmDevice>,
         session: Option<&crate::AuthSession>,
         log_format: crate::cli::LogFormat,
     ) -> Result<(), crate::TpmError> {
         match self {
+            Self::ActivateCredential(args) => args.run(device, session, log_format),
             Self::Algorithms(args) => args.run(device, session, log_format),
             Self::Convert(args) => args.run(device, session, log_format),
+            Self::CreateAk(args) => args.run(device, session, log_format),
             Self::CreatePrimary(args) => args.run(device, session, log_format),
+            Self::Decode(args) => args.run(device, session, log_format),
             Self::Delete(args) => args.run(device, session, log_format),
+            Self::Ek(args) => args.run(device, session, log_format),
+            Self::Eventlog(args) => args.run(device, session, log_format),
+            Self::Hash(args) => args.run(device, session, log_format),
+            Self::Ima(args) => args.run(device, session, log_format),
             Self::Import(args) => args.run(device, session, log_format),
             Self::Load(args) => args.run(device, session, log_format),
+            Self::MakeCredential(args) => args.run(device, session, log_format),
+            Self::NvDefine(args) => args.run(device, session, log_format),
             Self::Objects(args) => args.run(device, session, log_format),
             Self::PcrEvent(args) => args.run(device, session, log_format),
             Self::PcrRead(args) => args.run(device, session, log_format),
             Self::Policy(args) => args.run(device, session, log_format),
             Self::PrintError(args) => args.run(device, session, log_format),
+            Self::Proxy(args) => args.run(device, session, log_format),
+            Self::Quote(args) => args.run(device, session, log_format),
             Self::ResetLock(args) => args.run(device, session, log_format),
             Self::Save(args) => args.run(device, session, log_format),
             Self::Seal(args) => args.run(device, session, log_format),
+            Self::Sign(args) => args.run(device, session, log_format),
             Self::StartSession(args) => args.run(device, session, log_format),
             Self::Unseal(args) => args.run(device, session, log_format),
+            Self::VerifyQuote(args) => args.run(device, session, log_format),
+            Self::VerifySignature(args) => args.run(device, session, log_format),
         }
     }
 }
@@ -292,32 +481,59 @@ pub struct AuthArgs {
     pub auth: Option<String>,
 }
 
+#[derive(Debug, Default)]
+pub struct CreateAk {
+    pub scheme: AkScheme,
+    pub hash_alg: SessionHashAlg,
+    pub auth: AuthArgs,
+    pub endorsement_auth: AuthArgs,
+}
+
 #[derive(Debug, Default)]
 pub struct CreatePrimary {
     pub hierarchy: Hierarchy,
     pub alg: Alg,
+    pub template: Option<String>,
     pub persistent: Option<TpmPersistent>,
     pub auth: AuthArgs,
 }
 
 #[derive(Debug, Default)]
 pub struct Save {
-    pub object_handle: u32,
+    pub object_handle: TpmHandle,
     pub persistent_handle: TpmPersistent,
     pub auth: AuthArgs,
 }
 
 #[derive(Debug, Default)]
 pub struct Delete {
-    pub handle: u32,
+    pub handle: TpmHandle,
     pub auth: AuthArgs,
 }
 
+#[derive(Debug, Default)]
+pub struct Ek {
+    pub action: EkAction,
+    pub template: EkTemplate,
+    pub persistent: Option<TpmPersistent>,
+    pub public: Option<String>,
+    pub ca_dir: Option<String>,
+    pub input: Option<String>,
+    pub auth: AuthArgs,
+    pub owner_auth: AuthArgs,
+}
+
 #[derive(Debug, Default)]
 pub struct Import {
     pub parent_auth: AuthArgs,
 }
 
+#[derive(Debug, Default)]
+pub struct ActivateCredential {
+    pub auth: AuthArgs,
+    pub endorsement_auth: AuthArgs,
+}
+
 #[derive(Debug, Default)]
 pub struct Algorithms {
     pub filter: Option<String>,
@@ -328,6 +544,20 @@ pub struct Load {
     pub parent_auth: AuthArgs,
 }
 
+#[derive(Debug, Default)]
+pub struct MakeCredential {
+    pub public: String,
+    pub name: String,
+    pub secret: String,
+}
+
+#[derive(Debug, Default)]
+pub struct NvDefine {
+    pub public: String,
+    pub index_auth: Option<String>,
+    pub auth: AuthArgs,
+}
+
 #[derive(Debug, Default)]
 pub struct Objects {}
 
@@ -338,7 +568,7 @@ pub struct PcrRead {
 
 #[derive(Debug, Default)]
 pub struct PcrEvent {
-    pub pcr_handle: u32,
+    pub pcr_handle: TpmPcr,
     pub data: String,
     pub auth: AuthArgs,
 }
@@ -348,6 +578,20 @@ pub struct PrintError {
     pub rc: TpmRc,
 }
 
+#[derive(Debug, Default)]
+pub struct Proxy {
+    pub tcp: Option<String>,
+    pub unix: Option<String>,
+    pub log: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Quote {
+    pub selection: String,
+    pub nonce: String,
+    pub auth: AuthArgs,
+}
+
 #[derive(Debug, Default)]
 pub struct ResetLock {
     pub auth: AuthArgs,
@@ -365,22 +609,85 @@ pub struct Seal {
     pub object_auth: AuthArgs,
 }
 
+#[derive(Debug, Default)]
+pub struct Sign {
+    pub input: String,
+    pub hash_alg: Option<SessionHashAlg>,
+    pub format: SignatureFormat,
+    pub output: Option<String>,
+    pub auth: AuthArgs,
+}
+
 #[derive(Debug, Default)]
 pub struct Unseal {
     pub auth: AuthArgs,
 }
 
+#[derive(Debug, Default)]
+pub struct VerifyQuote {
+    pub public: String,
+    pub nonce: String,
+    pub pcrs: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct VerifySignature {
+    pub input: String,
+    pub signature: String,
+    pub format: SignatureFormat,
+    pub hash_alg: Option<SessionHashAlg>,
+    pub public: Option<String>,
+    pub ticket: Option<String>,
+}
+
 #[derive(Debug, Default)]
 pub struct Convert {
     pub from: KeyFormat,
     pub to: KeyFormat,
 }
 
+#[derive(Debug, Default)]
+pub struct Decode {
+    pub kind: DecodeKind,
+    pub cc: Option<TpmCc>,
+    pub format: DecodeFormat,
+    pub input_format: DecodeInput,
+    pub input: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Eventlog {
+    pub input: Option<String>,
+    pub pcrs: Option<String>,
+    pub verify: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Hash {
+    pub input: String,
+    pub hash_alg: SessionHashAlg,
+    pub hierarchy: Hierarchy,
+    pub ticket: Option<String>,
+    pub event: bool,
+    pub pcr_handle: Option<TpmPcr>,
+    pub auth: AuthArgs,
+}
+
+#[derive(Debug, Default)]
+pub struct Ima {
+    pub action: ImaAction,
+    pub input: Option<String>,
+    pub allowlist: Option<String>,
+    pub pcrs: Option<String>,
+}
+
 #[derive(Debug, Default)]
 pub struct Policy {
     pub expression: String,
     pub auth: AuthArgs,
     pub partial: bool,
+    pub offline: bool,
+    pub hash_alg: Option<SessionHashAlg>,
 }
 
 /// Retrieves all handles of a specific type from the TPM.
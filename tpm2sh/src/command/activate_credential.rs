@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    cli::{self, ActivateCredential, Object},
+    credential::{activate_credential, Credential},
+    object_to_handle, AuthSession, Command, CommandIo, TpmDevice, TpmError,
+};
+use std::io::{self, Write};
+
+impl Command for ActivateCredential {
+    /// Runs `activate-credential`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the execution fails
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
+        let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
+
+        let Object::Credential(output) =
+            io.consume_object(|obj| matches!(obj, Object::Credential(_)))?
+        else {
+            unreachable!()
+        };
+        let credential = Credential::from_output(&output)?;
+
+        let is_key = |obj: &Object| {
+            matches!(
+                obj,
+                Object::Handle(_) | Object::Persistent(_) | Object::Context(_)
+            )
+        };
+        let object_obj = io.consume_object(is_key)?;
+        let object_handle = object_to_handle(chip, &object_obj, log_format)?;
+        let ek_obj = io.consume_object(is_key)?;
+        let ek_handle = object_to_handle(chip, &ek_obj, log_format)?;
+
+        let cert_info = activate_credential(
+            chip,
+            object_handle,
+            ek_handle,
+            &credential,
+            io.session,
+            self.auth.auth.as_deref(),
+            self.endorsement_auth.auth.as_deref(),
+            log_format,
+        )?;
+
+        io::stdout().write_all(&cert_info)?;
+
+        io.finalize()
+    }
+}
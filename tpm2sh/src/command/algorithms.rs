@@ -36,10 +36,11 @@ impl Command for Algorithms {
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        device: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         _session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
+        let device = crate::required_device(device)?;
         let chip_algorithms = get_chip_algorithms(device, log_format)?;
         let cli_algorithms = enumerate_all();
 
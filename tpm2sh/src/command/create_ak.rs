@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    ak::create_ak,
+    build_to_vec,
+    cli::{self, CreateAk, Object},
+    object_to_handle, AuthSession, Command, CommandIo, Envelope, ObjectData, TpmDevice, TpmError,
+    ID_LOADABLE_KEY,
+};
+use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
+use std::io;
+use tpm2_protocol::data::Tpm2bPublic;
+
+impl Command for CreateAk {
+    /// Runs `create-ak`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the execution fails
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
+        let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
+
+        let ek_obj =
+            io.consume_object(|obj| matches!(obj, Object::Handle(_) | Object::Persistent(_)))?;
+        let ek_handle = object_to_handle(chip, &ek_obj, log_format)?;
+
+        let ak = create_ak(
+            chip,
+            ek_handle,
+            self.scheme,
+            self.hash_alg.into(),
+            self.auth.auth.as_deref(),
+            self.endorsement_auth.auth.as_deref(),
+            log_format,
+        )?;
+
+        let data = ObjectData {
+            oid: ID_LOADABLE_KEY.to_string(),
+            empty_auth: self.auth.auth.is_none(),
+            parent: format!("{:#010x}", u32::from(ek_handle)),
+            public: base64_engine.encode(build_to_vec(&Tpm2bPublic::from(ak.public))?),
+            private: base64_engine.encode(build_to_vec(&ak.private)?),
+        };
+
+        io.push_object(ek_obj);
+        io.push_object(Object::Context(serde_json::to_value(Envelope {
+            version: 1,
+            object_type: "object".to_string(),
+            data: serde_json::to_value(data)?,
+        })?));
+        io.push_object(Object::Name(hex::encode(&*ak.name)));
+        io.finalize()
+    }
+}
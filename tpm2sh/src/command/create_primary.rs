@@ -3,19 +3,19 @@
 // Copyright (c) 2024-2025 Jarkko Sakkinen
 
 use crate::{
-    build_to_vec, cli, get_auth_sessions, Alg, AlgInfo, AuthSession, Command, ContextData,
-    Envelope, TpmDevice, TpmError,
+    build_to_vec, cli, command::verify_quote::read_public_file, get_auth_sessions, Alg, AlgInfo,
+    AuthSession, Command, ContextData, Envelope, TpmDevice, TpmError,
 };
 use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
 use tpm2_protocol::{
     data::{
         Tpm2b, Tpm2bAuth, Tpm2bDigest, Tpm2bPublic, Tpm2bSensitiveCreate, Tpm2bSensitiveData,
-        TpmAlgId, TpmRh, TpmaObject, TpmlPcrSelection, TpmsEccPoint, TpmsKeyedhashParms,
-        TpmsSensitiveCreate, TpmtKdfScheme, TpmtPublic, TpmtScheme, TpmtSymDefObject, TpmuPublicId,
-        TpmuPublicParms, TpmuSymKeyBits, TpmuSymMode,
+        TpmAlgId, TpmaObject, TpmlPcrSelection, TpmsEccPoint, TpmsKeyedhashParms,
+        TpmsSensitiveCreate, TpmtKdfScheme, TpmtPublic, TpmtScheme, TpmtSymDefObject,
+        TpmuAsymScheme, TpmuPublicId, TpmuPublicParms, TpmuSymKeyBits, TpmuSymMode,
     },
     message::{TpmContextSaveCommand, TpmCreatePrimaryCommand, TpmEvictControlCommand},
-    TpmBuffer, TpmTransient,
+    TpmBuffer, TpmPermanent, TpmTransient,
 };
 
 fn build_public_template(alg_desc: &Alg) -> TpmtPublic {
@@ -62,6 +62,7 @@ fn build_public_template(alg_desc: &Alg) -> TpmtPublic {
                 details: TpmsKeyedhashParms {
                     scheme: TpmtScheme {
                         scheme: TpmAlgId::Null,
+                        details: TpmuAsymScheme::Null,
                     },
                 },
             },
@@ -91,11 +92,7 @@ pub fn save_key_context(
     log_format: cli::LogFormat,
 ) -> Result<String, TpmError> {
     let save_command = TpmContextSaveCommand {};
-    let (resp, _) = chip.execute(&save_command, Some(&[handle.into()]), &[], log_format)?;
-
-    let save_resp = resp
-        .ContextSave()
-        .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+    let (save_resp, _) = chip.execute(&save_command, &[handle.into()], &[], log_format)?;
 
     let context_bytes = build_to_vec(&save_resp.context)?;
 
@@ -118,13 +115,17 @@ impl Command for crate::cli::CreatePrimary {
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        chip: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
-        let primary_handle: TpmRh = self.hierarchy.into();
-        let handles = [primary_handle as u32];
-        let public_template = build_public_template(&self.alg);
+        let chip = crate::required_device(device)?;
+        let primary_handle: TpmPermanent = self.hierarchy.into();
+        let handles = [primary_handle.into()];
+        let public_template = match &self.template {
+            Some(path) => read_public_file(path)?,
+            None => build_public_template(&self.alg),
+        };
         let user_auth = self.auth.auth.as_deref().unwrap_or("").as_bytes();
 
         let cmd = TpmCreatePrimaryCommand {
@@ -141,26 +142,24 @@ impl Command for crate::cli::CreatePrimary {
             creation_pcr: TpmlPcrSelection::default(),
         };
 
-        let sessions = get_auth_sessions(&cmd, &handles, session, self.auth.auth.as_deref())?;
-        let (resp, _) = chip.execute(&cmd, Some(&handles), &sessions, log_format)?;
-
-        let create_primary_resp = resp
-            .CreatePrimary()
-            .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+        let sessions = get_auth_sessions(
+            chip,
+            &cmd,
+            &handles,
+            session,
+            self.auth.auth.as_deref(),
+            log_format,
+        )?;
+        let (create_primary_resp, _) = chip.execute(&cmd, &handles, &sessions, log_format)?;
         let object_handle = create_primary_resp.object_handle;
+        chip.register_object(object_handle.into(), &create_primary_resp.out_public.inner);
 
         if let Some(persistent_handle) = self.persistent {
             let evict_cmd = TpmEvictControlCommand { persistent_handle };
-            let evict_handles = [TpmRh::Owner as u32, object_handle.into()];
-            let evict_sessions = get_auth_sessions(&evict_cmd, &evict_handles, session, None)?;
-            let (resp, _) = chip.execute(
-                &evict_cmd,
-                Some(&evict_handles),
-                &evict_sessions,
-                log_format,
-            )?;
-            resp.EvictControl()
-                .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+            let evict_handles = [TpmPermanent::OWNER.into(), object_handle.into()];
+            let evict_sessions =
+                get_auth_sessions(chip, &evict_cmd, &evict_handles, session, None, log_format)?;
+            chip.execute(&evict_cmd, &evict_handles, &evict_sessions, log_format)?;
             println!("{persistent_handle:#010x}");
         } else {
             let json_out = save_key_context(chip, object_handle, log_format)?;
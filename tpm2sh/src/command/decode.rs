@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    cli,
+    cli::{Decode, DecodeFormat, DecodeInput, DecodeKind},
+    json::tpm_to_json,
+    pretty_printer::pretty_format,
+    read_all, AuthSession, Command, TpmDevice, TpmError,
+};
+use tpm2_protocol::{
+    data::{TpmCc, TpmRc, TpmSt},
+    message::{
+        tpm_parse_command, tpm_parse_response, TpmAuthCommands, TpmAuthResponses, TpmCommandBody,
+        TpmHandles, TpmResponseBody,
+    },
+    visit::{TpmValue, TpmVisit, TpmVisitor},
+    TpmParse, TpmProtocolError,
+};
+
+/// The number of bytes shown after the offset of a parse error.
+const ERROR_CONTEXT_LEN: usize = 16;
+
+/// A command decoded by `decode_command()`.
+#[derive(Debug)]
+pub struct DecodedCommand {
+    pub tag: TpmSt,
+    pub cc: TpmCc,
+    pub handles: TpmHandles,
+    pub sessions: TpmAuthCommands,
+    pub parameters: TpmCommandBody,
+}
+
+impl TpmVisit for DecodedCommand {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.enter(field, "DecodedCommand");
+        self.tag.visit("tag", visitor);
+        self.cc.visit("cc", visitor);
+        visit_handles(&self.handles, visitor);
+        self.sessions.visit("sessions", visitor);
+        self.parameters.visit("parameters", visitor);
+        visitor.leave();
+    }
+}
+
+/// A response decoded by `decode_response()`. An error response has only a
+/// response code.
+#[derive(Debug)]
+pub struct DecodedResponse {
+    pub tag: TpmSt,
+    pub rc: TpmRc,
+    pub parameters: Option<TpmResponseBody>,
+    pub sessions: TpmAuthResponses,
+}
+
+impl TpmVisit for DecodedResponse {
+    fn visit(&self, field: &'static str, visitor: &mut dyn TpmVisitor) {
+        visitor.enter(field, "DecodedResponse");
+        self.tag.visit("tag", visitor);
+        self.rc.visit("rc", visitor);
+        if let Some(parameters) = &self.parameters {
+            parameters.visit("parameters", visitor);
+            self.sessions.visit("sessions", visitor);
+        }
+        visitor.leave();
+    }
+}
+
+/// Reports the handles as handles rather than as plain integers.
+fn visit_handles(handles: &TpmHandles, visitor: &mut dyn TpmVisitor) {
+    visitor.enter_list("handles", "TpmHandles", handles.len());
+    for handle in handles.iter() {
+        visitor.value("", "u32", TpmValue::Handle(*handle));
+    }
+    visitor.leave();
+}
+
+/// Converts a parse error into a `TpmError` that shows the input at the
+/// failing offset.
+fn parse_error(err: TpmProtocolError, buf: &[u8]) -> TpmError {
+    let Some(offset) = err.offset() else {
+        return TpmError::Parse(err.to_string());
+    };
+    let end = buf.len().min(offset.saturating_add(ERROR_CONTEXT_LEN));
+    match buf.get(offset..end) {
+        Some(context) if !context.is_empty() => {
+            TpmError::Parse(format!("{err}: {}", hex::encode(context)))
+        }
+        _ => TpmError::Parse(format!("{err}: end of input")),
+    }
+}
+
+/// Reads the tag at the start of a message.
+fn message_tag(buf: &[u8]) -> Result<TpmSt, TpmError> {
+    let (raw, _) = u16::parse(buf)?;
+    TpmSt::try_from(raw).map_err(|()| TpmError::Parse(format!("invalid tag: {raw:#06x}")))
+}
+
+/// Reads the command code from the header of a command.
+pub(crate) fn command_code(buf: &[u8]) -> Result<TpmCc, TpmError> {
+    let (raw, _) = u32::parse(buf.get(6..).unwrap_or_default())?;
+    TpmCc::try_from(raw).map_err(|()| TpmError::Parse(format!("invalid command code: {raw:#x}")))
+}
+
+/// Converts input into bytes. Hex input may contain whitespace and a `0x`
+/// prefix. With `DecodeInput::Auto`, input that consists only of hexadecimal
+/// digits and whitespace is decoded as hex, anything else is taken as binary.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Parse` if the input is empty, or a `TpmError` if hex
+/// input is malformed.
+pub fn decode_input(input: &[u8], input_format: DecodeInput) -> Result<Vec<u8>, TpmError> {
+    if input_format == DecodeInput::Binary {
+        if input.is_empty() {
+            return Err(TpmError::Parse("empty input".to_string()));
+        }
+        return Ok(input.to_vec());
+    }
+    let text: Vec<u8> = input
+        .iter()
+        .copied()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect();
+    if text.is_empty() {
+        return Err(TpmError::Parse("empty input".to_string()));
+    }
+    let digits = text
+        .strip_prefix(b"0x")
+        .or_else(|| text.strip_prefix(b"0X"))
+        .unwrap_or(&text);
+    if input_format == DecodeInput::Hex || digits.iter().all(u8::is_ascii_hexdigit) {
+        Ok(hex::decode(digits)?)
+    } else {
+        Ok(input.to_vec())
+    }
+}
+
+/// Decodes a TPM command. If `cc` is given, the command code in the header
+/// must match it.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Parse` naming the failing field and offset if the
+/// command is malformed.
+pub fn decode_command(buf: &[u8], cc: Option<TpmCc>) -> Result<DecodedCommand, TpmError> {
+    let (handles, parameters, sessions) =
+        tpm_parse_command(buf).map_err(|err| parse_error(err, buf))?;
+    let command_cc = command_code(buf)?;
+    if let Some(cc) = cc {
+        if cc != command_cc {
+            return Err(TpmError::Parse(format!(
+                "command code mismatch: expected {cc}, got {command_cc}"
+            )));
+        }
+    }
+    Ok(DecodedCommand {
+        tag: message_tag(buf)?,
+        cc: command_cc,
+        handles,
+        sessions,
+        parameters,
+    })
+}
+
+/// Decodes a TPM response to the command `cc`.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Parse` naming the failing field and offset if the
+/// response is malformed.
+pub fn decode_response(buf: &[u8], cc: TpmCc) -> Result<DecodedResponse, TpmError> {
+    let tag = message_tag(buf)?;
+    match tpm_parse_response(cc, buf).map_err(|err| parse_error(err, buf))? {
+        Ok((rc, parameters, sessions)) => Ok(DecodedResponse {
+            tag,
+            rc,
+            parameters: Some(parameters),
+            sessions,
+        }),
+        Err((rc, _)) => Ok(DecodedResponse {
+            tag,
+            rc,
+            parameters: None,
+            sessions: TpmAuthResponses::new(),
+        }),
+    }
+}
+
+fn print_decoded<T: TpmVisit>(value: &T, format: DecodeFormat) -> Result<(), TpmError> {
+    match format {
+        DecodeFormat::Pretty => print!("{}", pretty_format(value, "")),
+        DecodeFormat::Json => println!("{}", serde_json::to_string_pretty(&tpm_to_json(value))?),
+    }
+    Ok(())
+}
+
+impl Command for Decode {
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    /// Runs `decode`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the input cannot be read or decoded.
+    fn run(
+        &self,
+        _device: Option<&mut TpmDevice>,
+        _session: Option<&AuthSession>,
+        _log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let buf = decode_input(&read_all(self.input.as_deref())?, self.input_format)?;
+        match self.kind {
+            DecodeKind::Command => print_decoded(&decode_command(&buf, self.cc)?, self.format),
+            DecodeKind::Response => {
+                let cc = self.cc.ok_or_else(|| {
+                    TpmError::Execution("decoding a response requires '--cc'".to_string())
+                })?;
+                let response = decode_response(&buf, cc)?;
+                print_decoded(&response, self.format)?;
+                if self.format == DecodeFormat::Pretty && response.rc.is_error() {
+                    println!("{}", response.rc);
+                }
+                Ok(())
+            }
+        }
+    }
+}
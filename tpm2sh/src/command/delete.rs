@@ -3,9 +3,9 @@
 
 use crate::{cli, get_auth_sessions, AuthSession, Command, TpmDevice, TpmError};
 use tpm2_protocol::{
-    data::TpmRh,
+    data::TpmHt,
     message::{TpmEvictControlCommand, TpmFlushContextCommand},
-    TpmPersistent, TpmTransient,
+    TpmContextHandle, TpmPermanent, TpmPersistent,
 };
 
 impl Command for cli::Delete {
@@ -16,38 +16,42 @@ impl Command for cli::Delete {
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        chip: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
         let handle = self.handle;
 
-        if handle >= TpmRh::PersistentFirst as u32 {
-            let persistent_handle = TpmPersistent(handle);
-            let auth_handle = TpmRh::Owner;
-            let handles = [auth_handle as u32, persistent_handle.into()];
-            let evict_cmd = TpmEvictControlCommand { persistent_handle };
+        match TpmHt::from_handle(handle.value()) {
+            Some(TpmHt::Persistent) => {
+                let persistent_handle = TpmPersistent::try_from(handle.value())?;
+                let handles = [TpmPermanent::OWNER.into(), handle];
+                let evict_cmd = TpmEvictControlCommand { persistent_handle };
 
-            let sessions =
-                get_auth_sessions(&evict_cmd, &handles, session, self.auth.auth.as_deref())?;
-            let (resp, _) = chip.execute(&evict_cmd, Some(&handles), &sessions, log_format)?;
-            resp.EvictControl()
-                .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
-            println!("{persistent_handle:#010x}");
-        } else if handle >= TpmRh::TransientFirst as u32 {
-            let flush_handle = TpmTransient(handle);
-            let flush_cmd = TpmFlushContextCommand {
-                flush_handle: flush_handle.into(),
-            };
-            let (resp, _) = chip.execute(&flush_cmd, Some(&[]), &[], log_format)?;
-            resp.FlushContext()
-                .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
-            println!("{flush_handle:#010x}");
-        } else {
-            return Err(TpmError::InvalidHandle(format!(
-                "'{:#010x}' is not a transient or persistent handle",
-                self.handle
-            )));
+                let sessions = get_auth_sessions(
+                    chip,
+                    &evict_cmd,
+                    &handles,
+                    session,
+                    self.auth.auth.as_deref(),
+                    log_format,
+                )?;
+                chip.execute(&evict_cmd, &handles, &sessions, log_format)?;
+                println!("{persistent_handle:#010x}");
+            }
+            Some(TpmHt::Transient | TpmHt::HmacSession | TpmHt::PolicySession) => {
+                let flush_cmd = TpmFlushContextCommand {
+                    flush_handle: TpmContextHandle::try_from(handle.value())?,
+                };
+                chip.execute(&flush_cmd, &[], &[], log_format)?;
+                println!("{handle:#010x}");
+            }
+            _ => {
+                return Err(TpmError::InvalidHandle(format!(
+                    "'{handle:#010x}' is not a transient, session or persistent handle"
+                )));
+            }
         }
         Ok(())
     }
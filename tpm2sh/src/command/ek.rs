@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    cli::{self, Ek, EkAction, Object},
+    command::verify_quote::read_public_file,
+    ek::{
+        certificate_to_pem, create_ek, load_ca_directory, read_ek_certificate,
+        verify_ek_certificate,
+    },
+    get_auth_sessions, read_all, write_to_file,
+    x509::Certificate,
+    AuthSession, Command, CommandIo, TpmDevice, TpmError,
+};
+use std::{
+    io,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tpm2_protocol::{data::Tpm2bPublic, message::TpmEvictControlCommand, TpmPermanent};
+
+impl Ek {
+    fn create(
+        &self,
+        chip: &mut TpmDevice,
+        io: &mut CommandIo<'_, io::Stdout>,
+    ) -> Result<(), TpmError> {
+        let log_format = io.log_format;
+        let (ek_handle, public) = create_ek(
+            chip,
+            self.template,
+            io.session,
+            self.auth.auth.as_deref(),
+            log_format,
+        )?;
+        if let Some(path) = &self.public {
+            write_to_file(path, &Tpm2bPublic::from(public))?;
+        }
+
+        if let Some(persistent_handle) = self.persistent {
+            let cmd = TpmEvictControlCommand { persistent_handle };
+            let handles = [TpmPermanent::OWNER.into(), ek_handle.into()];
+            let sessions = get_auth_sessions(
+                chip,
+                &cmd,
+                &handles,
+                io.session,
+                self.owner_auth.auth.as_deref(),
+                log_format,
+            )?;
+            chip.execute(&cmd, &handles, &sessions, log_format)?;
+            io.push_object(Object::Persistent(persistent_handle));
+        } else {
+            io.push_object(Object::Handle(ek_handle));
+        }
+
+        let certificate = read_ek_certificate(chip, self.template, io.session, log_format)?;
+        if let Some(der) = certificate {
+            io.push_object(Object::Certificate(certificate_to_pem(&der)));
+        } else {
+            let index = self.template.certificate_index();
+            tracing::warn!("no EK certificate at {index:#010x}");
+        }
+        Ok(())
+    }
+
+    fn verify(&self, io: &mut CommandIo<'_, io::Stdout>) -> Result<(), TpmError> {
+        let (Some(ca_dir), Some(public)) = (&self.ca_dir, &self.public) else {
+            return Err(TpmError::Execution(
+                "'verify' requires '--ca-dir' and '--public'".to_string(),
+            ));
+        };
+        let data = if let Some(path) = &self.input {
+            read_all(Some(path))?
+        } else {
+            let Object::Certificate(pem) =
+                io.consume_object(|obj| matches!(obj, Object::Certificate(_)))?
+            else {
+                unreachable!()
+            };
+            pem.into_bytes()
+        };
+        let cert = Certificate::parse_all(&data)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| TpmError::Parse("no certificate found".to_string()))?;
+        let public = read_public_file(public)?;
+        let ca = load_ca_directory(Path::new(ca_dir))?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| TpmError::Execution(e.to_string()))?;
+
+        let info = verify_ek_certificate(&cert, &ca, &public, now)?;
+        tracing::info!(
+            manufacturer = %info.manufacturer,
+            model = %info.model,
+            version = %info.version,
+            "EK certificate verified"
+        );
+        for subject in &info.chain {
+            tracing::info!("chain: {subject}");
+        }
+        io.push_object(Object::Certificate(certificate_to_pem(&cert.der)));
+        Ok(())
+    }
+}
+
+impl Command for Ek {
+    fn is_local(&self) -> bool {
+        self.action == EkAction::Verify
+    }
+
+    /// Runs `ek`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the execution fails or if the certificate does
+    /// not verify.
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
+        match self.action {
+            EkAction::Create => self.create(crate::required_device(device)?, &mut io)?,
+            EkAction::Verify => self.verify(&mut io)?,
+        }
+        io.finalize()
+    }
+}
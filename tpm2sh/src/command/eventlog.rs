@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    cli,
+    cli::{Eventlog, Object},
+    command::pcr_read::read_pcrs,
+    event_log::{
+        parse_event_log, tcg_event_type_name, PcrMismatch, PcrReplay, TcgEventLog,
+        BINARY_BIOS_MEASUREMENTS,
+    },
+    formats::PcrOutput,
+    get_pcr_count, parse_pcr_selection, read_all, tpm_alg_id_to_str, AuthSession, Command,
+    TpmDevice, TpmError,
+};
+use std::fmt::Write;
+
+/// Builds a `pcr-read` selection string for the replayed PCRs.
+fn replay_selection(replay: &PcrReplay, pcr_count: usize) -> String {
+    let mut selection = String::new();
+    for (&alg, pcrs) in &replay.banks {
+        let indices: Vec<String> = pcrs
+            .keys()
+            .filter(|&&pcr| (pcr as usize) < pcr_count)
+            .map(u32::to_string)
+            .collect();
+        if indices.is_empty() {
+            continue;
+        }
+        if !selection.is_empty() {
+            selection.push('+');
+        }
+        let _ = write!(
+            selection,
+            "{}:{}",
+            tpm_alg_id_to_str(alg),
+            indices.join(",")
+        );
+    }
+    selection
+}
+
+pub(crate) fn read_pcr_file(path: &str) -> Result<PcrOutput, TpmError> {
+    let input = read_all(Some(path))?;
+    match serde_json::from_slice(&input)? {
+        Object::Pcrs(pcrs) => Ok(pcrs),
+        _ => Err(TpmError::Parse(format!("'{path}' does not contain PCRs"))),
+    }
+}
+
+fn describe(log: &TcgEventLog, mismatch: &PcrMismatch) -> String {
+    let pcr = format!("{}:{}", tpm_alg_id_to_str(mismatch.alg), mismatch.pcr);
+    match mismatch.event {
+        Some(index) => {
+            let event_type = log.events[index].event_type;
+            let name = tcg_event_type_name(event_type)
+                .map_or_else(|| format!("{event_type:#010x}"), str::to_string);
+            format!(
+                "PCR {pcr} does not match the event log, first mismatching event: {index} ({name})"
+            )
+        }
+        None => format!("PCR {pcr} does not match any state of the event log"),
+    }
+}
+
+impl Command for Eventlog {
+    fn is_local(&self) -> bool {
+        !self.verify
+    }
+
+    /// Runs `eventlog`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the event log cannot be read or parsed, or if
+    /// the replayed PCRs do not match the compared PCRs.
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        _session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let input = read_all(Some(
+            self.input.as_deref().unwrap_or(BINARY_BIOS_MEASUREMENTS),
+        ))?;
+        let log = parse_event_log(&input)?;
+        let replay = PcrReplay::new(&log)?;
+
+        for (index, event) in log.events.iter().enumerate() {
+            println!("{}", serde_json::to_string(&event.to_json(index))?);
+        }
+        println!(
+            "{}",
+            serde_json::to_string(&Object::Pcrs(replay.to_pcr_output()))?
+        );
+
+        let pcrs = if self.verify {
+            let chip = crate::required_device(device)?;
+            let pcr_count = get_pcr_count(chip, log_format)?;
+            let selection = replay_selection(&replay, pcr_count);
+            if selection.is_empty() {
+                return Ok(());
+            }
+            let selection = parse_pcr_selection(&selection, pcr_count)?;
+            read_pcrs(chip, &selection, log_format)?
+        } else if let Some(path) = &self.pcrs {
+            read_pcr_file(path)?
+        } else {
+            return Ok(());
+        };
+
+        let mismatches = replay.compare(&log, &pcrs);
+        for mismatch in &mismatches {
+            tracing::warn!("{}", describe(&log, mismatch));
+        }
+        match mismatches.first() {
+            Some(mismatch) => Err(TpmError::Execution(describe(&log, mismatch))),
+            None => Ok(()),
+        }
+    }
+}
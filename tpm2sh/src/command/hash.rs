@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    cli::{self, Hash},
+    hash::{hash_data, hash_event},
+    input_to_bytes, tpm_alg_id_to_str, write_to_file, AuthSession, Command, TpmDevice, TpmError,
+};
+
+impl Command for Hash {
+    /// Runs `hash`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the execution fails
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
+        let data = input_to_bytes(&self.input)?;
+
+        if self.event {
+            let digests = hash_event(
+                chip,
+                self.pcr_handle,
+                &data,
+                session,
+                self.auth.auth.as_deref(),
+                log_format,
+            )?;
+            for digest in digests.iter() {
+                println!(
+                    "{}:{}",
+                    tpm_alg_id_to_str(digest.hash_alg),
+                    hex::encode(&*digest.digest)
+                );
+            }
+            return Ok(());
+        }
+
+        let (digest, ticket) = hash_data(
+            chip,
+            &data,
+            self.hash_alg.into(),
+            self.hierarchy.into(),
+            session,
+            log_format,
+        )?;
+        if let Some(path) = &self.ticket {
+            write_to_file(path, &ticket)?;
+        }
+        println!("{}", hex::encode(digest));
+        Ok(())
+    }
+}
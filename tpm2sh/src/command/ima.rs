@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    cli,
+    cli::{Ima, ImaAction, Object},
+    command::{eventlog::read_pcr_file, pcr_read::read_pcrs},
+    get_pcr_count,
+    ima::{
+        parse_ima_measurement_list, ImaAllowlist, ImaMeasurementList, IMA_BANKS,
+        IMA_BINARY_RUNTIME_MEASUREMENTS, IMA_PCR,
+    },
+    parse_pcr_selection, read_all, tpm_alg_id_to_str, AuthSession, Command, TpmDevice, TpmError,
+};
+
+fn describe_entry(list: &ImaMeasurementList, index: usize) -> String {
+    format!("entry {index} ({})", list.entries[index].file_name)
+}
+
+impl Command for Ima {
+    fn is_local(&self) -> bool {
+        self.action == ImaAction::List || self.pcrs.is_some()
+    }
+
+    /// Runs `ima`.
+    ///
+    /// The PCRs are read before the measurement list, as the list may grow in
+    /// between and a longer list still replays to the PCR values.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the measurement list cannot be read or parsed,
+    /// or if the verification fails.
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        _session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let pcrs = match (self.action, &self.pcrs) {
+            (ImaAction::List, _) => None,
+            (ImaAction::Verify, Some(path)) => Some(read_pcr_file(path)?),
+            (ImaAction::Verify, None) => {
+                let chip = crate::required_device(device)?;
+                let pcr_count = get_pcr_count(chip, log_format)?;
+                let selection = IMA_BANKS
+                    .iter()
+                    .map(|&alg| format!("{}:{IMA_PCR}", tpm_alg_id_to_str(alg)))
+                    .collect::<Vec<_>>()
+                    .join("+");
+                let selection = parse_pcr_selection(&selection, pcr_count)?;
+                Some(read_pcrs(chip, &selection, log_format)?)
+            }
+        };
+
+        let input = read_all(Some(
+            self.input
+                .as_deref()
+                .unwrap_or(IMA_BINARY_RUNTIME_MEASUREMENTS),
+        ))?;
+        let list = parse_ima_measurement_list(&input)?;
+        let replay = list.replay(IMA_BANKS)?;
+
+        if self.action == ImaAction::List {
+            for (index, entry) in list.entries.iter().enumerate() {
+                println!("{}", serde_json::to_string(&entry.to_json(index))?);
+            }
+        }
+        println!(
+            "{}",
+            serde_json::to_string(&Object::Pcrs(replay.to_pcr_output()))?
+        );
+        let Some(pcrs) = pcrs else {
+            return Ok(());
+        };
+
+        let mut failures = Vec::new();
+        for (index, entry) in list.entries.iter().enumerate() {
+            if !entry.is_data_consistent() {
+                failures.push(format!(
+                    "IMA {} does not match its template digest",
+                    describe_entry(&list, index)
+                ));
+            }
+        }
+        for mismatch in list.compare(&replay, &pcrs) {
+            let pcr = format!("{}:{}", tpm_alg_id_to_str(mismatch.alg), mismatch.pcr);
+            failures.push(match mismatch.event {
+                Some(index) => format!(
+                    "PCR {pcr} does not match the IMA measurement list, first mismatching {}",
+                    describe_entry(&list, index)
+                ),
+                None => format!("PCR {pcr} does not match the IMA measurement list"),
+            });
+        }
+        if let Some(path) = &self.allowlist {
+            let text = String::from_utf8_lossy(&read_all(Some(path))?).into_owned();
+            let allowlist = ImaAllowlist::parse(&text)?;
+            for index in allowlist.unlisted(&list) {
+                failures.push(format!(
+                    "IMA {} is not in the allowlist",
+                    describe_entry(&list, index)
+                ));
+            }
+        }
+
+        for failure in &failures {
+            tracing::warn!("{failure}");
+        }
+        match failures.into_iter().next() {
+            Some(failure) => Err(TpmError::Execution(failure)),
+            None => Ok(()),
+        }
+    }
+}
@@ -23,10 +23,11 @@ impl Command for crate::cli::Import {
     #[allow(clippy::too_many_lines)]
     fn run(
         &self,
-        chip: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
         let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
 
         let parent_obj = io.consume_object(|obj| !matches!(obj, Object::Pcrs(_)))?;
@@ -71,16 +72,15 @@ impl Command for crate::cli::Import {
 
         let handles = [parent_handle.into()];
         let sessions = get_auth_sessions(
+            chip,
             &import_cmd,
             &handles,
             io.session,
             self.parent_auth.auth.as_deref(),
+            log_format,
         )?;
 
-        let (resp, _) = chip.execute(&import_cmd, Some(&handles), &sessions, log_format)?;
-        let import_resp = resp.Import().map_err(|e| {
-            TpmError::Execution(format!("unexpected response type for Import: {e:?}"))
-        })?;
+        let (import_resp, _) = chip.execute(&import_cmd, &handles, &sessions, log_format)?;
 
         let pub_key_bytes = build_to_vec(&Tpm2bPublic { inner: public })?;
         let priv_key_bytes = build_to_vec(&import_resp.out_private)?;
@@ -4,8 +4,10 @@
 
 use crate::{
     cli::{self, Load, Object},
-    get_auth_sessions, object_to_handle, pop_object_data, AuthSession, Command, CommandIo,
-    TpmDevice, TpmError,
+    credential::{endorsement_policy_auth, flush_session},
+    ek::requires_endorsement_policy,
+    get_auth_sessions, object_to_handle, pop_object_data, read_public, AuthSession, Command,
+    CommandIo, TpmDevice, TpmError,
 };
 use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
 use std::io;
@@ -23,13 +25,18 @@ impl Command for Load {
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        chip: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
         let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
 
-        let parent_obj = io.consume_object(|obj| !matches!(obj, Object::Pcrs(_)))?;
+        let parent_obj = io.consume_object(|obj| match obj {
+            Object::Handle(_) | Object::Persistent(_) => true,
+            Object::Context(v) => v.is_string(),
+            _ => false,
+        })?;
         let parent_handle = object_to_handle(chip, &parent_obj, log_format)?;
 
         let object_data = pop_object_data(&mut io)?;
@@ -50,17 +57,35 @@ impl Command for Load {
         };
 
         let handles = [parent_handle.into()];
-        let sessions = get_auth_sessions(
-            &load_cmd,
-            &handles,
-            io.session,
-            self.parent_auth.auth.as_deref(),
-        )?;
+        let (parent_public, _) = read_public(chip, parent_handle, log_format)?;
+        let ek_parent = requires_endorsement_policy(&parent_public);
+        let sessions = if ek_parent {
+            vec![endorsement_policy_auth(
+                chip,
+                &parent_public,
+                self.parent_auth.auth.as_deref(),
+                log_format,
+            )?]
+        } else {
+            get_auth_sessions(
+                chip,
+                &load_cmd,
+                &handles,
+                io.session,
+                self.parent_auth.auth.as_deref(),
+                log_format,
+            )?
+        };
 
-        let (resp, _) = chip.execute(&load_cmd, Some(&handles), &sessions, log_format)?;
-        let load_resp = resp
-            .Load()
-            .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+        let load_resp = match chip.execute(&load_cmd, &handles, &sessions, log_format) {
+            Ok((resp, _)) => resp,
+            Err(err) => {
+                if ek_parent {
+                    flush_session(chip, sessions[0].session_handle, log_format);
+                }
+                return Err(err);
+            }
+        };
 
         let new_object = crate::cli::Object::Handle(load_resp.object_handle);
         io.push_object(new_object);
@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    cli::{self, MakeCredential, Object},
+    command::verify_quote::read_public_file,
+    credential::Credential,
+    input_to_bytes, AuthSession, Command, CommandIo, TpmDevice, TpmError,
+};
+use std::io;
+
+impl Command for MakeCredential {
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    /// Runs `make-credential`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the EK is not supported or the inputs are
+    /// malformed.
+    fn run(
+        &self,
+        _device: Option<&mut TpmDevice>,
+        session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
+        let ek_public = read_public_file(&self.public)?;
+        let name = hex::decode(&self.name)?;
+        let secret = input_to_bytes(&self.secret)?;
+
+        let credential = Credential::new(&ek_public, &name, &secret)?;
+        io.push_object(Object::Credential(credential.to_output()?));
+        io.finalize()
+    }
+}
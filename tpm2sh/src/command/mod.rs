@@ -2,19 +2,33 @@
 // Copyright (c) 2024-2025 Jarkko Sakkinen
 // Copyright (c) 2025 Opinsys Oy
 
+pub mod activate_credential;
 pub mod algorithms;
 pub mod convert;
+pub mod create_ak;
 pub mod create_primary;
+pub mod decode;
 pub mod delete;
+pub mod ek;
+pub mod eventlog;
+pub mod hash;
+pub mod ima;
 pub mod import;
 pub mod load;
+pub mod make_credential;
+pub mod nv_define;
 pub mod objects;
 pub mod pcr_event;
 pub mod pcr_read;
 pub mod policy;
 pub mod print_error;
+pub mod proxy;
+pub mod quote;
 pub mod reset_lock;
 pub mod save;
 pub mod seal;
+pub mod sign;
 pub mod start_session;
 pub mod unseal;
+pub mod verify_quote;
+pub mod verify_signature;
@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    cli::{self, NvDefine},
+    get_auth_sessions,
+    json::tpm_from_json,
+    read_all, AuthSession, Command, TpmDevice, TpmError,
+};
+use serde_json::Value;
+use tpm2_protocol::{
+    data::{Tpm2bAuth, Tpm2bNvPublic, TpmaNv, TpmsNvPublic},
+    message::TpmNvDefineSpaceCommand,
+    TpmParse, TpmPermanent,
+};
+
+/// Reads an NV public area either from the JSON encoding of `tpm_to_json()`
+/// or from a binary `TPM2B_NV_PUBLIC`.
+pub(crate) fn read_nv_public_file(path: &str) -> Result<TpmsNvPublic, TpmError> {
+    let input = read_all(Some(path))?;
+    if let Ok(json) = serde_json::from_slice::<Value>(&input) {
+        return tpm_from_json(&json);
+    }
+    let (public, remainder) = Tpm2bNvPublic::parse(&input)?;
+    if !remainder.is_empty() {
+        return Err(TpmError::Parse(format!(
+            "'{path}' contained trailing data after the NV public area"
+        )));
+    }
+    Ok(public.inner)
+}
+
+impl Command for NvDefine {
+    /// Runs `nv-define`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the public area is malformed or the execution
+    /// fails.
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
+        let public = read_nv_public_file(&self.public)?;
+        let nv_index = public.nv_index;
+        let hierarchy = if public.attributes.contains(TpmaNv::PLATFORMCREATE) {
+            TpmPermanent::PLATFORM
+        } else {
+            TpmPermanent::OWNER
+        };
+
+        let cmd = TpmNvDefineSpaceCommand {
+            auth: Tpm2bAuth::try_from(self.index_auth.as_deref().unwrap_or("").as_bytes())?,
+            public_info: Tpm2bNvPublic::from(public),
+        };
+        let handles = [hierarchy.into()];
+        let sessions = get_auth_sessions(
+            chip,
+            &cmd,
+            &handles,
+            session,
+            self.auth.auth.as_deref(),
+            log_format,
+        )?;
+        chip.execute(&cmd, &handles, &sessions, log_format)?;
+        println!("{nv_index:#010x}");
+        Ok(())
+    }
+}
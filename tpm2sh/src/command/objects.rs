@@ -12,20 +12,21 @@ impl Command for Objects {
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        device: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         _session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
+        let device = crate::required_device(device)?;
         let transient_handles = cli::get_handles(device, TpmRh::TransientFirst, log_format)?;
         for handle in transient_handles {
-            let obj = cli::Object::Handle(TpmTransient(handle));
+            let obj = cli::Object::Handle(TpmTransient::try_from(handle)?);
             let json_line = serde_json::to_string(&obj)?;
             println!("{json_line}");
         }
 
         let persistent_handles = cli::get_handles(device, TpmRh::PersistentFirst, log_format)?;
         for handle in persistent_handles {
-            let obj = cli::Object::Persistent(TpmPersistent(handle));
+            let obj = cli::Object::Persistent(TpmPersistent::try_from(handle)?);
             let json_line = serde_json::to_string(&obj)?;
             println!("{json_line}");
         }
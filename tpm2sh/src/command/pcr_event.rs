@@ -13,26 +13,32 @@ impl Command for PcrEvent {
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        chip: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
         if session.is_none() && self.auth.auth.is_none() {
             return Err(TpmError::Execution(
                 "Authorization is required for pcr-event. Use --auth or --session.".to_string(),
             ));
         }
 
-        let handles = [self.pcr_handle];
+        let handles = [self.pcr_handle.into()];
 
         let event_data = Tpm2b::try_from(self.data.as_bytes())?;
         let command = TpmPcrEventCommand { event_data };
 
-        let sessions = get_auth_sessions(&command, &handles, session, self.auth.auth.as_deref())?;
+        let sessions = get_auth_sessions(
+            chip,
+            &command,
+            &handles,
+            session,
+            self.auth.auth.as_deref(),
+            log_format,
+        )?;
 
-        let (resp, _) = chip.execute(&command, Some(&handles), &sessions, log_format)?;
-        resp.PcrEvent()
-            .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+        chip.execute(&command, &handles, &sessions, log_format)?;
 
         println!("{:#010x}", self.pcr_handle);
         Ok(())
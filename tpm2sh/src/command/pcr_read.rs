@@ -10,53 +10,129 @@ use crate::{
     TpmError,
 };
 use std::collections::BTreeMap;
-use tpm2_protocol::message::TpmPcrReadCommand;
+use tpm2_protocol::{
+    data::{TpmAlgId, TpmlPcrSelection, TpmsPcrSelection},
+    message::TpmPcrReadCommand,
+    TpmBuffer,
+};
 
-impl Command for PcrRead {
-    /// Runs `pcr-read`.
-    ///
-    /// # Errors
-    ///
-    /// Returns a `TpmError` if the execution fails
-    fn run(
-        &self,
-        chip: &mut TpmDevice,
-        _session: Option<&AuthSession>,
-        log_format: cli::LogFormat,
-    ) -> Result<(), TpmError> {
-        let pcr_count = get_pcr_count(chip, log_format)?;
-        let pcr_selection_in = parse_pcr_selection(&self.selection, pcr_count)?;
+/// The number of times `read_pcrs` starts over when the PCRs change while
+/// they are being read.
+const PCR_READ_ATTEMPTS: usize = 3;
 
-        let cmd = TpmPcrReadCommand { pcr_selection_in };
-        let (resp, _) = chip.execute(&cmd, None, &[], log_format)?;
+/// Reads the PCRs in `selection` from the TPM. `TPM2_PCR_Read` returns at
+/// most eight digests at a time, so the command is repeated until every
+/// selected PCR has been read. If `pcrUpdateCounter` changes between the
+/// commands, the digests do not belong to the same state and the whole
+/// selection is read again.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if the execution fails, or a `TpmError::Execution`
+/// if the PCRs keep changing while they are being read.
+pub(crate) fn read_pcrs(
+    chip: &mut TpmDevice,
+    selection: &TpmlPcrSelection,
+    log_format: cli::LogFormat,
+) -> Result<PcrOutput, TpmError> {
+    for _ in 0..PCR_READ_ATTEMPTS {
+        if let Some(output) = read_pcrs_once(chip, selection, log_format)? {
+            return Ok(output);
+        }
+        tracing::debug!("PCRs changed while reading, starting over");
+    }
+    Err(TpmError::Execution(
+        "PCRs changed while they were being read".to_string(),
+    ))
+}
 
-        let pcr_read_resp = resp
-            .PcrRead()
-            .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+/// Reads the PCRs in `selection` with as many `TPM2_PCR_Read` commands as
+/// needed. Returns `None` if `pcrUpdateCounter` changes between them.
+fn read_pcrs_once(
+    chip: &mut TpmDevice,
+    selection: &TpmlPcrSelection,
+    log_format: cli::LogFormat,
+) -> Result<Option<PcrOutput>, TpmError> {
+    let mut pending: Vec<(TpmAlgId, Vec<u8>)> = selection
+        .iter()
+        .map(|bank| (bank.hash, bank.pcr_select.to_vec()))
+        .collect();
+    let mut banks: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    let mut update_counter = None;
 
-        let mut banks: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
-        let mut pcr_iter = pcr_read_resp.pcr_values.iter();
+    loop {
+        let mut pcr_selection_in = TpmlPcrSelection::new();
+        for (hash, pcr_select) in &pending {
+            pcr_selection_in.try_push(TpmsPcrSelection {
+                hash: *hash,
+                pcr_select: TpmBuffer::try_from(pcr_select.as_slice())?,
+            })?;
+        }
+        let cmd = TpmPcrReadCommand { pcr_selection_in };
+        let (pcr_read_resp, _) = chip.execute(&cmd, &[], &[], log_format)?;
+        if update_counter.is_some_and(|counter| counter != pcr_read_resp.pcr_update_counter) {
+            return Ok(None);
+        }
+        update_counter = Some(pcr_read_resp.pcr_update_counter);
 
+        let mut pcr_iter = pcr_read_resp.pcr_values.iter();
+        let mut progress = false;
         for selection in pcr_read_resp.pcr_selection_out.iter() {
             let bank = banks
                 .entry(tpm_alg_id_to_str(selection.hash).to_string())
                 .or_default();
+            let mut bank_pending = pending
+                .iter_mut()
+                .find(|(hash, _)| *hash == selection.hash)
+                .map(|(_, pcr_select)| pcr_select);
             for (byte_index, &byte) in selection.pcr_select.iter().enumerate() {
                 for bit_index in 0..8 {
                     if (byte >> bit_index) & 1 == 1 {
                         let pcr_index = byte_index * 8 + bit_index;
                         if let Some(digest) = pcr_iter.next() {
                             bank.insert(pcr_index.to_string(), hex::encode_upper(digest.as_ref()));
+                            progress = true;
+                        }
+                        if let Some(pcr_select) = bank_pending.as_deref_mut() {
+                            if let Some(pending_byte) = pcr_select.get_mut(byte_index) {
+                                *pending_byte &= !(1 << bit_index);
+                            }
                         }
                     }
                 }
             }
         }
 
-        let pcr_output = PcrOutput {
-            update_counter: pcr_read_resp.pcr_update_counter,
-            banks,
-        };
+        let done = pending
+            .iter()
+            .all(|(_, pcr_select)| pcr_select.iter().all(|&byte| byte == 0));
+        if done || !progress {
+            break;
+        }
+    }
+
+    Ok(update_counter.map(|update_counter| PcrOutput {
+        update_counter,
+        banks,
+    }))
+}
+
+impl Command for PcrRead {
+    /// Runs `pcr-read`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the execution fails
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        _session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
+        let pcr_count = get_pcr_count(chip, log_format)?;
+        let pcr_selection_in = parse_pcr_selection(&self.selection, pcr_count)?;
+        let pcr_output = read_pcrs(chip, &pcr_selection_in, log_format)?;
 
         let output_object = Object::Pcrs(pcr_output);
         let json_line = serde_json::to_string(&output_object)?;
@@ -5,19 +5,27 @@ use crate::{
     cli,
     cli::{Object, Policy},
     from_json_str, get_pcr_count, parse_pcr_selection, AuthSession, Command, CommandIo, Envelope,
-    SessionData, TpmDevice, TpmError,
+    PolicyData, SessionData, SoftwareHash, TpmDevice, TpmError,
 };
 use pest::iterators::{Pair, Pairs};
 use pest::Parser;
 use pest_derive::Parser;
 use std::io::{self, Write};
+use std::str::FromStr;
 use tpm2_protocol::{
-    data::{Tpm2b, Tpm2bDigest, TpmAlgId, TpmRh, TpmlDigest, TpmtSymDefObject},
+    data::{
+        Tpm2b, Tpm2bDigest, Tpm2bName, Tpm2bNonce, TpmAlgId, TpmCc, TpmRh, TpmSe, TpmSt,
+        TpmlDigest, TpmtSymDefObject, TpmtTkVerified, TPM_PCR_SELECT_MAX,
+    },
+    hash::{tpm_handle_name, TpmHashProvider},
     message::{
-        TpmFlushContextCommand, TpmPolicyGetDigestCommand, TpmPolicyOrCommand, TpmPolicyPcrCommand,
-        TpmPolicySecretCommand, TpmStartAuthSessionCommand,
+        TpmFlushContextCommand, TpmPolicyAuthValueCommand, TpmPolicyAuthorizeCommand,
+        TpmPolicyCommandCodeCommand, TpmPolicyGetDigestCommand, TpmPolicyOrCommand,
+        TpmPolicyPasswordCommand, TpmPolicyPcrCommand, TpmPolicySecretCommand,
+        TpmStartAuthSessionCommand,
     },
-    TpmSession,
+    policy::TpmPolicyDigest,
+    tpm_hash_size, TpmHandle, TpmPermanent, TpmSession,
 };
 
 #[derive(Parser)]
@@ -35,6 +43,14 @@ enum PolicyAst {
         auth_handle: String,
     },
     Or(Vec<PolicyAst>),
+    And(Vec<PolicyAst>),
+    CommandCode(TpmCc),
+    AuthValue,
+    Password,
+    Authorize {
+        key_name: String,
+        policy_ref: Option<String>,
+    },
 }
 
 fn parse_quoted_string(pair: Pair<'_, Rule>) -> Result<String, TpmError> {
@@ -45,6 +61,20 @@ fn parse_quoted_string(pair: Pair<'_, Rule>) -> Result<String, TpmError> {
     Ok(inner_str.to_string())
 }
 
+fn parse_command_code(s: &str) -> Result<TpmCc, TpmError> {
+    if let Ok(cc) = TpmCc::from_str(s) {
+        return Ok(cc);
+    }
+    let value = crate::parse_hex_u32(s)?;
+    TpmCc::try_from(value).map_err(|()| TpmError::Parse(format!("invalid command code: {s}")))
+}
+
+fn parse_policy_list(pairs: Pairs<'_, Rule>) -> Result<Vec<PolicyAst>, TpmError> {
+    pairs
+        .map(|p| parse_policy_internal(p.into_inner()))
+        .collect()
+}
+
 fn parse_policy_internal(mut pairs: Pairs<'_, Rule>) -> Result<PolicyAst, TpmError> {
     let pair = pairs
         .next()
@@ -54,12 +84,18 @@ fn parse_policy_internal(mut pairs: Pairs<'_, Rule>) -> Result<PolicyAst, TpmErr
         Rule::pcr_expression => {
             let mut inner_pairs = pair.into_inner();
             let selection = parse_quoted_string(inner_pairs.next().unwrap())?;
-            let digest = inner_pairs.next().map(parse_quoted_string).transpose()?;
-            let count = inner_pairs
-                .next()
-                .map(|p| p.as_str().parse::<u32>())
-                .transpose()
-                .map_err(|e| TpmError::Parse(e.to_string()))?;
+            let mut digest = None;
+            let mut count = None;
+            for p in inner_pairs {
+                match p.as_rule() {
+                    Rule::quoted_string => digest = Some(parse_quoted_string(p)?),
+                    Rule::count_parameter => {
+                        let value = p.into_inner().next().unwrap().as_str();
+                        count = Some(value.parse::<u32>()?);
+                    }
+                    _ => unreachable!(),
+                }
+            }
 
             PolicyAst::Pcr {
                 selection,
@@ -71,13 +107,22 @@ fn parse_policy_internal(mut pairs: Pairs<'_, Rule>) -> Result<PolicyAst, TpmErr
             let auth_handle = parse_quoted_string(pair.into_inner().next().unwrap())?;
             PolicyAst::Secret { auth_handle }
         }
-        Rule::or_expression => {
-            let mut or_pairs = pair.into_inner();
-            let policy_list_pairs = or_pairs.next().unwrap().into_inner();
-            let branches = policy_list_pairs
-                .map(|p| parse_policy_internal(p.into_inner()))
-                .collect::<Result<_, _>>()?;
-            PolicyAst::Or(branches)
+        Rule::or_expression => PolicyAst::Or(parse_policy_list(pair.into_inner())?),
+        Rule::and_expression => PolicyAst::And(parse_policy_list(pair.into_inner())?),
+        Rule::command_code_expression => {
+            let code = parse_quoted_string(pair.into_inner().next().unwrap())?;
+            PolicyAst::CommandCode(parse_command_code(&code)?)
+        }
+        Rule::auth_value_expression => PolicyAst::AuthValue,
+        Rule::password_expression => PolicyAst::Password,
+        Rule::authorize_expression => {
+            let mut inner_pairs = pair.into_inner();
+            let key_name = parse_quoted_string(inner_pairs.next().unwrap())?;
+            let policy_ref = inner_pairs.next().map(parse_quoted_string).transpose()?;
+            PolicyAst::Authorize {
+                key_name,
+                policy_ref,
+            }
         }
         _ => {
             return Err(TpmError::Parse(format!(
@@ -101,6 +146,78 @@ fn parse_policy_expression(input: &str) -> Result<PolicyAst, TpmError> {
     parse_policy_internal(root_pairs.next().unwrap().into_inner())
 }
 
+fn decode_hex(s: &str) -> Result<Vec<u8>, TpmError> {
+    hex::decode(s).map_err(|e| TpmError::Parse(e.to_string()))
+}
+
+/// Resolves `pcrDigest` for `TPM2_PolicyPCR`, either from the expression or by
+/// hashing the PCR value found in the input pipeline.
+fn resolve_pcr_digest<W: Write>(
+    io: &mut CommandIo<'_, W>,
+    selection: &str,
+    digest: Option<&String>,
+    count: Option<&u32>,
+    partial: bool,
+    hash_alg: TpmAlgId,
+) -> Result<Vec<u8>, TpmError> {
+    if let Some(digest) = digest {
+        return decode_hex(digest);
+    }
+
+    let pcr_output_obj = io.consume_object(|obj| {
+        if let Object::Pcrs(p) = obj {
+            if let Some(c) = count {
+                return p.update_counter == *c;
+            }
+            true
+        } else {
+            false
+        }
+    })?;
+
+    let Object::Pcrs(pcr_output) = pcr_output_obj else {
+        unreachable!();
+    };
+
+    let (bank_name, pcr_index_str) = selection.split_once(':').ok_or_else(|| {
+        TpmError::Parse(
+            "pcr selection must be in 'alg:pcr' format when sourcing digest from pipeline"
+                .to_string(),
+        )
+    })?;
+
+    let bank = pcr_output.banks.get(bank_name).ok_or_else(|| {
+        TpmError::Execution(format!(
+            "pcr bank '{bank_name}' not found in pipeline object"
+        ))
+    })?;
+
+    let value_hex = bank.get(pcr_index_str).ok_or_else(|| {
+        TpmError::Execution(format!(
+            "pcr index '{pcr_index_str}' not found in bank '{bank_name}' in pipeline object"
+        ))
+    })?;
+
+    if partial {
+        let mut pcr_output_modified = pcr_output.clone();
+        if let Some(b) = pcr_output_modified.banks.get_mut(bank_name) {
+            b.remove(pcr_index_str);
+        }
+
+        if !pcr_output_modified.is_empty() {
+            io.push_object(Object::Pcrs(pcr_output_modified));
+        }
+    }
+
+    let value = decode_hex(value_hex)?;
+    let size = tpm_hash_size(&hash_alg).ok_or_else(|| {
+        TpmError::Execution(format!("unsupported policy hash algorithm: {hash_alg}"))
+    })?;
+    let mut pcr_digest = vec![0u8; size];
+    SoftwareHash.digest(hash_alg, &[&value], &mut pcr_digest)?;
+    Ok(pcr_digest)
+}
+
 struct PolicyExecutor<'a, 'b, 'c, W: Write> {
     chip: &'a mut TpmDevice,
     io: &'b mut CommandIo<'c, W>,
@@ -108,6 +225,8 @@ struct PolicyExecutor<'a, 'b, 'c, W: Write> {
     session: Option<&'c AuthSession>,
     pcr_count: usize,
     partial: bool,
+    trial: bool,
+    hash_alg: TpmAlgId,
     log_format: cli::LogFormat,
 }
 
@@ -119,57 +238,14 @@ impl<W: Write> PolicyExecutor<'_, '_, '_, W> {
         digest: Option<&String>,
         count: Option<&u32>,
     ) -> Result<(), TpmError> {
-        let pcr_output_obj = self.io.consume_object(|obj| {
-            if let Object::Pcrs(p) = obj {
-                if let Some(c) = count {
-                    return p.update_counter == *c;
-                }
-                true
-            } else {
-                false
-            }
-        })?;
-
-        let pcr_digest_bytes = if let Some(digest) = digest {
-            hex::decode(digest).map_err(|e| TpmError::Parse(e.to_string()))?
-        } else {
-            let Object::Pcrs(pcr_output) = pcr_output_obj else {
-                unreachable!();
-            };
-
-            let (bank_name, pcr_index_str) = selection.split_once(':').ok_or_else(|| {
-                TpmError::Parse(
-                    "pcr selection must be in 'alg:pcr' format when sourcing digest from pipeline"
-                        .to_string(),
-                )
-            })?;
-
-            let bank = pcr_output.banks.get(bank_name).ok_or_else(|| {
-                TpmError::Execution(format!(
-                    "pcr bank '{bank_name}' not found in pipeline object"
-                ))
-            })?;
-
-            let digest_hex = bank.get(pcr_index_str).ok_or_else(|| {
-                TpmError::Execution(format!(
-                    "pcr index '{pcr_index_str}' not found in bank '{bank_name}' in pipeline object"
-                ))
-            })?;
-
-            if self.partial {
-                let mut pcr_output_modified = pcr_output.clone();
-                if let Some(b) = pcr_output_modified.banks.get_mut(bank_name) {
-                    b.remove(pcr_index_str);
-                }
-
-                if !pcr_output_modified.is_empty() {
-                    self.io.push_object(Object::Pcrs(pcr_output_modified));
-                }
-            }
-
-            hex::decode(digest_hex).map_err(|e| TpmError::Parse(e.to_string()))?
-        };
-
+        let pcr_digest_bytes = resolve_pcr_digest(
+            self.io,
+            selection,
+            digest,
+            count,
+            self.partial,
+            self.hash_alg,
+        )?;
         let pcr_selection = parse_pcr_selection(selection, self.pcr_count)?;
         let pcr_digest = Tpm2bDigest::try_from(pcr_digest_bytes.as_slice())?;
 
@@ -178,9 +254,16 @@ impl<W: Write> PolicyExecutor<'_, '_, '_, W> {
             pcrs: pcr_selection,
         };
         let handles = [session_handle.into()];
-        let sessions = crate::get_auth_sessions(&cmd, &handles, self.session, None)?;
+        let sessions = crate::get_auth_sessions(
+            self.chip,
+            &cmd,
+            &handles,
+            self.session,
+            None,
+            self.log_format,
+        )?;
         self.chip
-            .execute(&cmd, Some(&handles), &sessions, self.log_format)?;
+            .execute(&cmd, &handles, &sessions, self.log_format)?;
 
         Ok(())
     }
@@ -190,7 +273,7 @@ impl<W: Write> PolicyExecutor<'_, '_, '_, W> {
         session_handle: TpmSession,
         auth_handle_str: &str,
     ) -> Result<(), TpmError> {
-        let auth_handle = crate::parse_hex_u32(auth_handle_str)?;
+        let auth_handle = TpmHandle::try_from(crate::parse_hex_u32(auth_handle_str)?)?;
         let cmd = TpmPolicySecretCommand {
             nonce_tpm: Tpm2b::default(),
             cp_hash_a: Tpm2bDigest::default(),
@@ -198,10 +281,16 @@ impl<W: Write> PolicyExecutor<'_, '_, '_, W> {
             expiration: 0,
         };
         let handles = [auth_handle, session_handle.into()];
-        let sessions =
-            crate::get_auth_sessions(&cmd, &handles, self.session, self.auth.auth.as_deref())?;
+        let sessions = crate::get_auth_sessions(
+            self.chip,
+            &cmd,
+            &handles,
+            self.session,
+            self.auth.auth.as_deref(),
+            self.log_format,
+        )?;
         self.chip
-            .execute(&cmd, Some(&handles), &sessions, self.log_format)?;
+            .execute(&cmd, &handles, &sessions, self.log_format)?;
         Ok(())
     }
 
@@ -212,14 +301,12 @@ impl<W: Write> PolicyExecutor<'_, '_, '_, W> {
     ) -> Result<(), TpmError> {
         let mut branch_digests = TpmlDigest::new();
         for branch_ast in branches {
-            let branch_handle = start_trial_session(
-                self.chip,
-                self.session,
-                cli::SessionType::Trial,
-                self.log_format,
-            )?;
+            let branch_handle = start_trial_session(self.chip, self.hash_alg, self.log_format)?;
 
-            self.execute_policy_ast(branch_handle, branch_ast)?;
+            let trial = std::mem::replace(&mut self.trial, true);
+            let result = self.execute_policy_ast(branch_handle, branch_ast);
+            self.trial = trial;
+            result?;
 
             let digest =
                 get_policy_digest(self.chip, self.session, branch_handle, self.log_format)?;
@@ -231,11 +318,57 @@ impl<W: Write> PolicyExecutor<'_, '_, '_, W> {
         let cmd = TpmPolicyOrCommand {
             p_hash_list: branch_digests,
         };
+        self.execute_simple_policy(session_handle, &cmd)
+    }
+
+    /// Runs `TPM2_PolicyAuthorize` with a NULL ticket, which only a trial
+    /// session accepts.
+    fn execute_authorize_policy(
+        &mut self,
+        session_handle: TpmSession,
+        key_name: &str,
+        policy_ref: Option<&String>,
+    ) -> Result<(), TpmError> {
+        if !self.trial {
+            return Err(TpmError::Execution(
+                "authorize() requires a trial session".to_string(),
+            ));
+        }
+        let approved_policy =
+            get_policy_digest(self.chip, self.session, session_handle, self.log_format)?;
+        let policy_ref = policy_ref.map(|r| decode_hex(r)).transpose()?;
+        let cmd = TpmPolicyAuthorizeCommand {
+            approved_policy,
+            policy_ref: Tpm2bNonce::try_from(policy_ref.as_deref().unwrap_or_default())?,
+            key_sign: Tpm2bName::try_from(decode_hex(key_name)?.as_slice())?,
+            check_ticket: TpmtTkVerified {
+                tag: TpmSt::Verified,
+                hierarchy: TpmRh::Null,
+                digest: Tpm2bDigest::default(),
+            },
+        };
+        self.execute_simple_policy(session_handle, &cmd)
+    }
+
+    fn execute_simple_policy<C>(
+        &mut self,
+        session_handle: TpmSession,
+        cmd: &C,
+    ) -> Result<(), TpmError>
+    where
+        C: tpm2_protocol::message::TpmCommand<Handles = [TpmHandle; 1]> + crate::PrettyTrace,
+    {
         let handles = [session_handle.into()];
-        let sessions = crate::get_auth_sessions(&cmd, &handles, self.session, None)?;
+        let sessions = crate::get_auth_sessions(
+            self.chip,
+            cmd,
+            &handles,
+            self.session,
+            None,
+            self.log_format,
+        )?;
         self.chip
-            .execute(&cmd, Some(&handles), &sessions, self.log_format)?;
-
+            .execute(cmd, &handles, &sessions, self.log_format)?;
         Ok(())
     }
 
@@ -256,34 +389,115 @@ impl<W: Write> PolicyExecutor<'_, '_, '_, W> {
                 self.execute_secret_policy(session_handle, auth_handle)
             }
             PolicyAst::Or(branches) => self.execute_or_policy(session_handle, branches),
+            PolicyAst::And(assertions) => {
+                for assertion in assertions {
+                    self.execute_policy_ast(session_handle, assertion)?;
+                }
+                Ok(())
+            }
+            PolicyAst::CommandCode(code) => self.execute_simple_policy(
+                session_handle,
+                &TpmPolicyCommandCodeCommand { code: *code },
+            ),
+            PolicyAst::AuthValue => {
+                self.execute_simple_policy(session_handle, &TpmPolicyAuthValueCommand {})
+            }
+            PolicyAst::Password => {
+                self.execute_simple_policy(session_handle, &TpmPolicyPasswordCommand {})
+            }
+            PolicyAst::Authorize {
+                key_name,
+                policy_ref,
+            } => self.execute_authorize_policy(session_handle, key_name, policy_ref.as_ref()),
+        }
+    }
+}
+
+/// Evaluates a policy expression in software, mirroring `PolicyExecutor`.
+struct OfflinePolicyEvaluator<'b, 'c, W: Write> {
+    io: &'b mut CommandIo<'c, W>,
+    partial: bool,
+    hash_alg: TpmAlgId,
+}
+
+impl<W: Write> OfflinePolicyEvaluator<'_, '_, W> {
+    fn evaluate(
+        &mut self,
+        policy: &mut TpmPolicyDigest<'_, SoftwareHash>,
+        ast: &PolicyAst,
+    ) -> Result<(), TpmError> {
+        match ast {
+            PolicyAst::Pcr {
+                selection,
+                digest,
+                count,
+            } => {
+                let pcr_digest = resolve_pcr_digest(
+                    self.io,
+                    selection,
+                    digest.as_ref(),
+                    count.as_ref(),
+                    self.partial,
+                    self.hash_alg,
+                )?;
+                let pcr_selection = parse_pcr_selection(selection, TPM_PCR_SELECT_MAX * 8)?;
+                policy.pcr(&pcr_selection, &pcr_digest)?;
+            }
+            PolicyAst::Secret { auth_handle } => {
+                let handle = crate::parse_hex_u32(auth_handle)?;
+                let name = tpm_handle_name(handle).map_err(|_| {
+                    TpmError::InvalidHandle(format!(
+                        "'{handle:#010x}' has no offline Name, use a permanent handle"
+                    ))
+                })?;
+                policy.secret(&name, &[])?;
+            }
+            PolicyAst::Or(branches) => {
+                let mut branch_digests = Vec::with_capacity(branches.len());
+                for branch_ast in branches {
+                    let mut branch = TpmPolicyDigest::new(&SoftwareHash, self.hash_alg)?;
+                    self.evaluate(&mut branch, branch_ast)?;
+                    branch_digests.push(*branch.digest());
+                }
+                policy.or(&branch_digests)?;
+            }
+            PolicyAst::And(assertions) => {
+                for assertion in assertions {
+                    self.evaluate(policy, assertion)?;
+                }
+            }
+            PolicyAst::CommandCode(code) => policy.command_code(*code)?,
+            PolicyAst::AuthValue | PolicyAst::Password => policy.auth_value()?,
+            PolicyAst::Authorize {
+                key_name,
+                policy_ref,
+            } => {
+                let policy_ref = policy_ref.as_deref().map(decode_hex).transpose()?;
+                policy.authorize(&decode_hex(key_name)?, &policy_ref.unwrap_or_default())?;
+            }
         }
+        Ok(())
     }
 }
 
 fn start_trial_session(
     chip: &mut TpmDevice,
-    session: Option<&AuthSession>,
-    session_type: cli::SessionType,
+    auth_hash: TpmAlgId,
     log_format: cli::LogFormat,
 ) -> Result<TpmSession, TpmError> {
-    let auth_hash = session.map_or(TpmAlgId::Sha256, |s| s.auth_hash);
-
     let cmd = TpmStartAuthSessionCommand {
         nonce_caller: Tpm2b::default(),
         encrypted_salt: Tpm2b::default(),
-        session_type: session_type.into(),
+        session_type: cli::SessionType::Trial.into(),
         symmetric: TpmtSymDefObject::default(),
         auth_hash,
     };
-    let (resp, _) = chip.execute(
+    let (start_resp, _) = chip.execute(
         &cmd,
-        Some(&[TpmRh::Null as u32, TpmRh::Null as u32]),
+        &[TpmPermanent::NULL.into(), TpmPermanent::NULL.into()],
         &[],
         log_format,
     )?;
-    let start_resp = resp
-        .StartAuthSession()
-        .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
     Ok(start_resp.session_handle)
 }
 
@@ -293,9 +507,9 @@ fn flush_session(
     log_format: cli::LogFormat,
 ) -> Result<(), TpmError> {
     let cmd = TpmFlushContextCommand {
-        flush_handle: handle.into(),
+        flush_handle: handle.try_into()?,
     };
-    chip.execute(&cmd, Some(&[]), &[], log_format)?;
+    chip.execute(&cmd, &[], &[], log_format)?;
     Ok(())
 }
 
@@ -307,15 +521,43 @@ fn get_policy_digest(
 ) -> Result<Tpm2bDigest, TpmError> {
     let cmd = TpmPolicyGetDigestCommand {};
     let handles = [session_handle.into()];
-    let sessions = crate::get_auth_sessions(&cmd, &handles, session, None)?;
-    let (resp, _) = chip.execute(&cmd, Some(&handles), &sessions, log_format)?;
-    let digest_resp = resp
-        .PolicyGetDigest()
-        .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+    let sessions = crate::get_auth_sessions(chip, &cmd, &handles, session, None, log_format)?;
+    let (digest_resp, _) = chip.execute(&cmd, &handles, &sessions, log_format)?;
     Ok(digest_resp.policy_digest)
 }
 
+impl Policy {
+    fn run_offline<W: Write>(&self, mut io: CommandIo<'_, W>) -> Result<(), TpmError> {
+        let ast = parse_policy_expression(&self.expression)
+            .map_err(|e| TpmError::Parse(format!("failed to parse policy expression: {e}")))?;
+
+        let hash_alg = TpmAlgId::from(self.hash_alg.unwrap_or_default());
+        let mut policy = TpmPolicyDigest::new(&SoftwareHash, hash_alg)?;
+        let mut evaluator = OfflinePolicyEvaluator {
+            io: &mut io,
+            partial: self.partial,
+            hash_alg,
+        };
+        evaluator.evaluate(&mut policy, &ast)?;
+
+        let policy_data = PolicyData {
+            hash_alg: hash_alg as u16,
+            policy_digest: hex::encode(&**policy.digest()),
+        };
+        io.push_object(Object::Context(serde_json::to_value(Envelope {
+            version: 1,
+            object_type: "policy".to_string(),
+            data: serde_json::to_value(policy_data)?,
+        })?));
+        io.finalize()
+    }
+}
+
 impl Command for Policy {
+    fn is_local(&self) -> bool {
+        self.offline
+    }
+
     /// Run 'policy'.
     ///
     /// # Errors
@@ -323,11 +565,15 @@ impl Command for Policy {
     /// Returns a `TpmError` on failure.
     fn run(
         &self,
-        chip: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
         let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
+        if self.offline {
+            return self.run_offline(io);
+        }
+        let chip = crate::required_device(device)?;
 
         let session_obj = io.consume_object(|obj| {
             if let Object::Context(val) = obj {
@@ -343,12 +589,18 @@ impl Command for Policy {
         };
 
         let mut session_data: SessionData = from_json_str(&envelope_value.to_string(), "session")?;
+        let hash_alg = TpmAlgId::try_from(session_data.auth_hash).map_err(|()| {
+            TpmError::Parse(format!(
+                "invalid session hash algorithm: {:#06x}",
+                session_data.auth_hash
+            ))
+        })?;
 
         let ast = parse_policy_expression(&self.expression)
             .map_err(|e| TpmError::Parse(format!("failed to parse policy expression: {e}")))?;
 
         let pcr_count = get_pcr_count(chip, log_format)?;
-        let session_handle = TpmSession(session_data.handle);
+        let session_handle = TpmSession::try_from(session_data.handle)?;
 
         let mut executor = PolicyExecutor {
             chip,
@@ -357,6 +609,8 @@ impl Command for Policy {
             session,
             pcr_count,
             partial: self.partial,
+            trial: session_data.session_type == TpmSe::Trial as u8,
+            hash_alg,
             log_format,
         };
         executor.execute_policy_ast(session_handle, &ast)?;
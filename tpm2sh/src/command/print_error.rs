@@ -5,9 +5,13 @@
 use crate::{cli, cli::PrintError, AuthSession, Command, TpmDevice, TpmError};
 
 impl Command for PrintError {
+    fn is_local(&self) -> bool {
+        true
+    }
+
     fn run(
         &self,
-        _device: &mut TpmDevice,
+        _device: Option<&mut TpmDevice>,
         _session: Option<&AuthSession>,
         _log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    cli,
+    cli::Proxy,
+    proxy::{serve_platform, TpmProxy},
+    required_device, AuthSession, Command, TpmDevice, TpmError,
+};
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    net::{SocketAddr, TcpListener, ToSocketAddrs},
+    os::unix::net::UnixListener,
+    thread,
+};
+use tracing::{info, warn};
+
+/// Opens the JSON Lines log, appending to `path` or writing to stdout.
+fn open_log(path: Option<&str>) -> Result<Box<dyn Write>, TpmError> {
+    match path {
+        Some("-") | None => Ok(Box::new(io::stdout())),
+        Some(path) => OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(|file| Box::new(file) as Box<dyn Write>)
+            .map_err(|e| TpmError::File(path.to_string(), e)),
+    }
+}
+
+/// Accepts platform port connections in the background.
+fn spawn_platform_listener(mut addr: SocketAddr) -> Result<(), TpmError> {
+    addr.set_port(
+        addr.port()
+            .checked_add(1)
+            .ok_or_else(|| TpmError::Execution("no port left for the platform port".to_string()))?,
+    );
+    let listener = TcpListener::bind(addr)?;
+    info!(%addr, "platform port listening");
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream
+                .map_err(TpmError::from)
+                .and_then(|mut stream| serve_platform(&mut stream));
+            if let Err(err) = result {
+                warn!(error = %err, "platform connection failed");
+            }
+        }
+    });
+    Ok(())
+}
+
+fn serve_tcp<W: Write>(proxy: &mut TpmProxy<'_, W>, addr: &str) -> Result<(), TpmError> {
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| TpmError::Execution(format!("cannot resolve '{addr}'")))?;
+    let listener = TcpListener::bind(addr)?;
+    spawn_platform_listener(addr)?;
+    info!(%addr, "TPM port listening");
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        let client = stream
+            .peer_addr()
+            .map_or_else(|_| "tcp".to_string(), |peer| peer.to_string());
+        info!(client, "connected");
+        if let Err(err) = proxy.serve_simulator(&client, &mut stream) {
+            warn!(client, error = %err, "connection failed");
+        }
+    }
+    Ok(())
+}
+
+fn serve_unix<W: Write>(proxy: &mut TpmProxy<'_, W>, path: &str) -> Result<(), TpmError> {
+    let listener = UnixListener::bind(path).map_err(|e| TpmError::File(path.to_string(), e))?;
+    info!(path, "listening");
+    for (id, stream) in listener.incoming().enumerate() {
+        let mut stream = stream?;
+        let client = format!("unix:{id}");
+        info!(client, "connected");
+        if let Err(err) = proxy.serve_raw(&client, &mut stream) {
+            warn!(client, error = %err, "connection failed");
+        }
+    }
+    Ok(())
+}
+
+impl Command for Proxy {
+    /// Runs `proxy`.
+    ///
+    /// Clients are served one at a time, in the order they connect.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if listening fails or the log cannot be written.
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        _session: Option<&AuthSession>,
+        _log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let device = required_device(device)?;
+        let mut proxy = TpmProxy::new(device, open_log(self.log.as_deref())?);
+        match (&self.tcp, &self.unix) {
+            (Some(addr), None) => serve_tcp(&mut proxy, addr),
+            (None, Some(path)) => serve_unix(&mut proxy, path),
+            _ => Err(TpmError::Execution(
+                "exactly one of '--tcp' and '--unix' is required".to_string(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    attestation::SignedAttest,
+    cli::{self, Object, Quote},
+    command::pcr_read::read_pcrs,
+    get_auth_sessions, get_pcr_count, object_to_handle, parse_pcr_selection,
+    quote::quote_scheme,
+    read_public, AuthSession, Command, CommandIo, TpmDevice, TpmError,
+};
+use std::io;
+use tpm2_protocol::{data::Tpm2bData, message::TpmQuoteCommand};
+
+impl Command for Quote {
+    /// Runs `quote`.
+    ///
+    /// The PCRs are read back after the quote is signed. If they are extended
+    /// in between, `verify-quote` reports a digest mismatch.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the execution fails
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
+        let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
+
+        let key_obj = io.consume_object(|obj| {
+            matches!(
+                obj,
+                Object::Handle(_) | Object::Persistent(_) | Object::Context(_)
+            )
+        })?;
+        let key_handle = object_to_handle(chip, &key_obj, log_format)?;
+        let (public, _) = read_public(chip, key_handle, log_format)?;
+
+        let pcr_count = get_pcr_count(chip, log_format)?;
+        let pcr_select = parse_pcr_selection(&self.selection, pcr_count)?;
+        let nonce = hex::decode(&self.nonce)?;
+
+        let cmd = TpmQuoteCommand {
+            qualifying_data: Tpm2bData::try_from(nonce.as_slice())?,
+            in_scheme: quote_scheme(&public)?,
+            pcr_select,
+        };
+        let handles = [key_handle.into()];
+        let sessions = get_auth_sessions(
+            chip,
+            &cmd,
+            &handles,
+            io.session,
+            self.auth.auth.as_deref(),
+            log_format,
+        )?;
+        let (resp, _) = chip.execute(&cmd, &handles, &sessions, log_format)?;
+
+        let quote = SignedAttest::new(&resp.quoted.inner, resp.signature)?;
+        let pcrs = read_pcrs(chip, &pcr_select, log_format)?;
+
+        io.push_object(Object::Quote(quote.to_output()?));
+        io.push_object(Object::Pcrs(pcrs));
+        io.finalize()
+    }
+}
@@ -3,7 +3,7 @@
 // Copyright (c) 2025 Opinsys Oy
 
 use crate::{cli, cli::ResetLock, get_auth_sessions, AuthSession, Command, TpmDevice, TpmError};
-use tpm2_protocol::{data::TpmRh, message::TpmDictionaryAttackLockResetCommand};
+use tpm2_protocol::{message::TpmDictionaryAttackLockResetCommand, TpmPermanent};
 
 impl Command for ResetLock {
     /// Runs `reset-lock`.
@@ -13,18 +13,24 @@ impl Command for ResetLock {
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        chip: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
         let command = TpmDictionaryAttackLockResetCommand {};
-        let handles = [TpmRh::Lockout as u32];
+        let handles = [TpmPermanent::LOCKOUT.into()];
 
-        let sessions = get_auth_sessions(&command, &handles, session, self.auth.auth.as_deref())?;
+        let sessions = get_auth_sessions(
+            chip,
+            &command,
+            &handles,
+            session,
+            self.auth.auth.as_deref(),
+            log_format,
+        )?;
 
-        let (resp, _) = chip.execute(&command, Some(&handles), &sessions, log_format)?;
-        resp.DictionaryAttackLockReset()
-            .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+        chip.execute(&command, &handles, &sessions, log_format)?;
 
         Ok(())
     }
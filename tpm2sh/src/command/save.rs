@@ -3,7 +3,7 @@
 // Copyright (c) 2025 Opinsys Oy
 
 use crate::{cli, cli::Save, get_auth_sessions, AuthSession, Command, TpmDevice, TpmError};
-use tpm2_protocol::{data::TpmRh, message::TpmEvictControlCommand};
+use tpm2_protocol::{message::TpmEvictControlCommand, TpmPermanent};
 
 impl Command for Save {
     /// Runs `save`.
@@ -13,22 +13,27 @@ impl Command for Save {
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        chip: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
-        let auth_handle = TpmRh::Owner;
-        let handles = [auth_handle as u32, self.object_handle];
+        let chip = crate::required_device(device)?;
+        let handles = [TpmPermanent::OWNER.into(), self.object_handle];
 
         let evict_cmd = TpmEvictControlCommand {
             persistent_handle: self.persistent_handle,
         };
 
-        let sessions = get_auth_sessions(&evict_cmd, &handles, session, self.auth.auth.as_deref())?;
+        let sessions = get_auth_sessions(
+            chip,
+            &evict_cmd,
+            &handles,
+            session,
+            self.auth.auth.as_deref(),
+            log_format,
+        )?;
 
-        let (resp, _) = chip.execute(&evict_cmd, Some(&handles), &sessions, log_format)?;
-        resp.EvictControl()
-            .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+        chip.execute(&evict_cmd, &handles, &sessions, log_format)?;
 
         println!("{:#010x}", self.persistent_handle);
         Ok(())
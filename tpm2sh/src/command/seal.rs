@@ -14,7 +14,7 @@ use tpm2_protocol::{
     data::{
         Tpm2b, Tpm2bAuth, Tpm2bDigest, Tpm2bPublic, Tpm2bSensitiveCreate, Tpm2bSensitiveData,
         TpmAlgId, TpmaObject, TpmlPcrSelection, TpmsKeyedhashParms, TpmsSensitiveCreate,
-        TpmtPublic, TpmtScheme, TpmuPublicId, TpmuPublicParms,
+        TpmtPublic, TpmtScheme, TpmuAsymScheme, TpmuPublicId, TpmuPublicParms,
     },
     message::TpmCreateCommand,
 };
@@ -27,10 +27,11 @@ impl Command for Seal {
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        chip: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
         let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
 
         let parent_obj = io.consume_object(|obj| !matches!(obj, Object::Context(_)))?;
@@ -60,6 +61,7 @@ impl Command for Seal {
                 details: TpmsKeyedhashParms {
                     scheme: TpmtScheme {
                         scheme: TpmAlgId::Null,
+                        details: TpmuAsymScheme::Null,
                     },
                 },
             },
@@ -82,14 +84,16 @@ impl Command for Seal {
         };
 
         let handles = [parent_handle.into()];
-        let sessions =
-            get_auth_sessions(&cmd, &handles, io.session, self.parent_auth.auth.as_deref())?;
+        let sessions = get_auth_sessions(
+            chip,
+            &cmd,
+            &handles,
+            io.session,
+            self.parent_auth.auth.as_deref(),
+            log_format,
+        )?;
 
-        let (resp, _) = chip.execute(&cmd, Some(&handles), &sessions, log_format)?;
-
-        let create_resp = resp.Create().map_err(|e| {
-            TpmError::Execution(format!("unexpected response type for Create: {e:?}"))
-        })?;
+        let (create_resp, _) = chip.execute(&cmd, &handles, &sessions, log_format)?;
 
         let pub_bytes = build_to_vec(&create_resp.out_public)?;
         let priv_bytes = build_to_vec(&create_resp.out_private)?;
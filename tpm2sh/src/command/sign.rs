@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    cli::{self, Object, Sign},
+    input_to_bytes, object_to_handle, read_public,
+    signature::{encode_signature, hash_for_signing, sign_digest, signing_scheme},
+    AuthSession, Command, CommandIo, TpmDevice, TpmError,
+};
+use std::{
+    fs,
+    io::{self, Write},
+};
+
+impl Command for Sign {
+    /// Runs `sign`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the execution fails
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
+        let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
+
+        let key_obj = io.consume_object(|obj| {
+            matches!(
+                obj,
+                Object::Handle(_) | Object::Persistent(_) | Object::Context(_)
+            )
+        })?;
+        let key_handle = object_to_handle(chip, &key_obj, log_format)?;
+        let (public, _) = read_public(chip, key_handle, log_format)?;
+
+        let (scheme, hash_alg) = signing_scheme(&public, self.hash_alg.map(Into::into))?;
+        let data = input_to_bytes(&self.input)?;
+        let (digest, validation) = hash_for_signing(chip, &public, &data, hash_alg, log_format)?;
+        let signature = sign_digest(
+            chip,
+            key_handle,
+            scheme,
+            &digest,
+            validation,
+            io.session,
+            self.auth.auth.as_deref(),
+            log_format,
+        )?;
+        let output = encode_signature(&signature, self.format)?;
+
+        if let Some(path) = &self.output {
+            fs::write(path, output)?;
+        } else {
+            io::stdout().write_all(&output)?;
+        }
+        io.finalize()
+    }
+}
@@ -9,8 +9,9 @@ use crate::{
 use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
 use rand::{thread_rng, RngCore};
 use tpm2_protocol::{
-    data::{Tpm2b, TpmAlgId, TpmRh, TpmaSession, TpmtSymDefObject},
+    data::{Tpm2b, TpmAlgId, TpmaSession, TpmtSymDefObject},
     message::TpmStartAuthSessionCommand,
+    TpmPermanent,
 };
 
 impl Command for StartSession {
@@ -21,10 +22,11 @@ impl Command for StartSession {
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        chip: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         _session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
         let mut nonce_bytes = vec![0; 16];
         thread_rng().fill_bytes(&mut nonce_bytes);
 
@@ -39,12 +41,8 @@ impl Command for StartSession {
             auth_hash,
         };
 
-        let handles = [TpmRh::Null as u32, TpmRh::Null as u32];
-        let (response, _) = chip.execute(&cmd, Some(&handles), &[], log_format)?;
-
-        let start_auth_session_resp = response
-            .StartAuthSession()
-            .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+        let handles = [TpmPermanent::NULL.into(), TpmPermanent::NULL.into()];
+        let (start_auth_session_resp, _) = chip.execute(&cmd, &handles, &[], log_format)?;
 
         let digest_len = tpm2_protocol::tpm_hash_size(&auth_hash).ok_or(TpmError::Execution(
             "Unsupported hash algorithm".to_string(),
@@ -57,6 +55,7 @@ impl Command for StartSession {
             hmac_key: base64_engine.encode(Vec::<u8>::new()),
             auth_hash: cmd.auth_hash as u16,
             policy_digest: hex::encode(vec![0; digest_len]),
+            session_type: cmd.session_type as u8,
         };
 
         let envelope = Envelope {
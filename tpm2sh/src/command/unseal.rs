@@ -22,10 +22,11 @@ impl Command for Unseal {
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        chip: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError> {
+        let chip = crate::required_device(device)?;
         let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
         let object_data = pop_object_data(&mut io)?;
 
@@ -53,18 +54,16 @@ impl Command for Unseal {
                 let unseal_cmd = TpmUnsealCommand {};
                 let unseal_handles = [object_handle.into()];
                 let sessions = get_auth_sessions(
+                    chip,
                     &unseal_cmd,
                     &unseal_handles,
                     io.session,
                     self.auth.auth.as_deref(),
+                    log_format,
                 )?;
 
                 let (unseal_resp, _) =
-                    chip.execute(&unseal_cmd, Some(&unseal_handles), &sessions, log_format)?;
-
-                let unseal_resp = unseal_resp
-                    .Unseal()
-                    .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+                    chip.execute(&unseal_cmd, &unseal_handles, &sessions, log_format)?;
 
                 Ok(unseal_resp.out_data.to_vec())
             },
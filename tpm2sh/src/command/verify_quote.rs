@@ -0,0 +1,93 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    attestation::SignedAttest,
+    cli::{self, Object, VerifyQuote},
+    command::eventlog::read_pcr_file,
+    json::tpm_from_json,
+    read_all, AuthSession, Command, CommandIo, Envelope, ObjectData, TpmDevice, TpmError,
+};
+use base64::{engine::general_purpose::STANDARD as base64_engine, Engine};
+use serde_json::Value;
+use std::io;
+use tpm2_protocol::{
+    data::{Tpm2bPublic, TpmtPublic},
+    TpmParse,
+};
+
+/// Reads a public area from an object envelope, from a `TPMT_PUBLIC` in the
+/// JSON encoding of `tpm_to_json()` or from a binary `TPM2B_PUBLIC`.
+pub(crate) fn read_public_file(path: &str) -> Result<TpmtPublic, TpmError> {
+    let input = read_all(Some(path))?;
+    let Ok(json) = serde_json::from_slice::<Value>(&input) else {
+        return parse_public(path, &input);
+    };
+    let bytes = match serde_json::from_value::<Envelope>(json.clone()) {
+        Ok(envelope) if envelope.object_type == "object" => {
+            let data: ObjectData = serde_json::from_value(envelope.data)?;
+            base64_engine
+                .decode(data.public)
+                .map_err(|e| TpmError::Parse(e.to_string()))?
+        }
+        Ok(envelope) => {
+            return Err(TpmError::Execution(format!(
+                "invalid object type: expected 'object', got '{}'",
+                envelope.object_type
+            )))
+        }
+        Err(_) => return tpm_from_json(&json),
+    };
+    parse_public(path, &bytes)
+}
+
+fn parse_public(path: &str, bytes: &[u8]) -> Result<TpmtPublic, TpmError> {
+    let (public, remainder) = Tpm2bPublic::parse(bytes)?;
+    if !remainder.is_empty() {
+        return Err(TpmError::Parse(format!(
+            "'{path}' contained trailing data after the public area"
+        )));
+    }
+    Ok(public.inner)
+}
+
+impl Command for VerifyQuote {
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    /// Runs `verify-quote`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the quote does not verify.
+    fn run(
+        &self,
+        _device: Option<&mut TpmDevice>,
+        session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
+        let public = read_public_file(&self.public)?;
+        let nonce = hex::decode(&self.nonce)?;
+
+        let Object::Quote(output) = io.consume_object(|obj| matches!(obj, Object::Quote(_)))?
+        else {
+            unreachable!()
+        };
+        let pcrs = if let Some(path) = &self.pcrs {
+            read_pcr_file(path)?
+        } else {
+            let Object::Pcrs(pcrs) = io.consume_object(|obj| matches!(obj, Object::Pcrs(_)))?
+            else {
+                unreachable!()
+            };
+            pcrs
+        };
+
+        let quote = SignedAttest::from_output(&output)?;
+        quote.verify_quote(&public, &nonce, &pcrs)?;
+        io.push_object(Object::Pcrs(pcrs));
+        io.finalize()
+    }
+}
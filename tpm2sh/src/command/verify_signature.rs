@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    cli::{self, Object, VerifySignature},
+    command::verify_quote::read_public_file,
+    crypto::{software_digest, verify_signature},
+    input_to_bytes, object_to_handle, read_all, read_public,
+    signature::{decode_signature, signature_hash, verify_with_tpm},
+    write_to_file, AuthSession, Command, CommandIo, TpmDevice, TpmError,
+};
+use std::io;
+
+impl Command for VerifySignature {
+    fn is_local(&self) -> bool {
+        self.public.is_some()
+    }
+
+    /// Runs `verify-signature`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the signature does not verify.
+    fn run(
+        &self,
+        device: Option<&mut TpmDevice>,
+        session: Option<&AuthSession>,
+        log_format: cli::LogFormat,
+    ) -> Result<(), TpmError> {
+        let mut io = CommandIo::new(io::stdin(), io::stdout(), session, log_format)?;
+        let data = input_to_bytes(&self.input)?;
+        let encoded = read_all(Some(&self.signature))?;
+        let hash_alg = self.hash_alg.map(Into::into);
+
+        if let Some(path) = &self.public {
+            let public = read_public_file(path)?;
+            let signature = decode_signature(&encoded, self.format, &public, hash_alg)?;
+            verify_signature(&public, &data, &signature)?;
+            tracing::info!("signature verified");
+            return io.finalize();
+        }
+
+        let chip = crate::required_device(device)?;
+        let key_obj = io.consume_object(|obj| {
+            matches!(
+                obj,
+                Object::Handle(_) | Object::Persistent(_) | Object::Context(_)
+            )
+        })?;
+        let key_handle = object_to_handle(chip, &key_obj, log_format)?;
+        let (public, _) = read_public(chip, key_handle, log_format)?;
+
+        let signature = decode_signature(&encoded, self.format, &public, hash_alg)?;
+        let digest = software_digest(signature_hash(&signature)?, &[&data])?;
+        let ticket = verify_with_tpm(chip, key_handle, &digest, &signature, log_format)?;
+        tracing::info!("signature verified");
+        if let Some(path) = &self.ticket {
+            write_to_file(path, &ticket)?;
+        }
+        io.finalize()
+    }
+}
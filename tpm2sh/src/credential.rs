@@ -0,0 +1,217 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! Credential activation for the privacy CA enrollment of an attestation key.
+
+use crate::{
+    build_password_session, build_to_vec, cli, ek::requires_endorsement_policy,
+    formats::CredentialOutput, get_auth_sessions, make_credential, AuthSession, TpmDevice,
+    TpmError,
+};
+use rand::{thread_rng, RngCore};
+use tpm2_protocol::{
+    data::{
+        Tpm2b, Tpm2bAuth, Tpm2bDigest, Tpm2bEncryptedSecret, Tpm2bIdObject, Tpm2bNonce, TpmAlgId,
+        TpmSe, TpmaSession, TpmsAuthCommand, TpmtPublic, TpmtSymDefObject,
+    },
+    message::{
+        TpmActivateCredentialCommand, TpmFlushContextCommand, TpmPolicySecretCommand,
+        TpmStartAuthSessionCommand,
+    },
+    tpm_hash_size, TpmContextHandle, TpmObjectHandle, TpmParse, TpmPermanent, TpmSession,
+};
+
+/// A credential blob and the encrypted seed that protects it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credential {
+    pub blob: Tpm2bIdObject,
+    pub secret: Tpm2bEncryptedSecret,
+}
+
+impl Credential {
+    /// Creates a credential for the object with the Name `object_name`, which
+    /// can be activated only with the private part of `ek_public`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if `ek_public` is not a supported storage key.
+    pub fn new(
+        ek_public: &TpmtPublic,
+        object_name: &[u8],
+        credential: &[u8],
+    ) -> Result<Self, TpmError> {
+        let (blob, secret) = make_credential(ek_public, object_name, credential)?;
+        Ok(Self { blob, secret })
+    }
+
+    /// Decodes a credential pipeline object.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the hex or the structures are malformed.
+    pub fn from_output(output: &CredentialOutput) -> Result<Self, TpmError> {
+        let blob = hex::decode(&output.blob)?;
+        let secret = hex::decode(&output.secret)?;
+        let (blob, remainder) = Tpm2bIdObject::parse(&blob)?;
+        if !remainder.is_empty() {
+            return Err(TpmError::Parse(
+                "trailing data after the credential blob".to_string(),
+            ));
+        }
+        let (secret, remainder) = Tpm2bEncryptedSecret::parse(&secret)?;
+        if !remainder.is_empty() {
+            return Err(TpmError::Parse(
+                "trailing data after the credential secret".to_string(),
+            ));
+        }
+        Ok(Self { blob, secret })
+    }
+
+    /// Encodes the credential as a pipeline object.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the structures cannot be built.
+    pub fn to_output(&self) -> Result<CredentialOutput, TpmError> {
+        Ok(CredentialOutput {
+            blob: hex::encode(build_to_vec(&self.blob)?),
+            secret: hex::encode(build_to_vec(&self.secret)?),
+        })
+    }
+}
+
+/// Starts a policy session and satisfies `TPM2_PolicySecret` with the
+/// endorsement hierarchy, which is the authorization policy of the standard
+/// EK templates.
+fn start_endorsement_policy(
+    chip: &mut TpmDevice,
+    auth_hash: TpmAlgId,
+    endorsement_auth: Option<&str>,
+    log_format: cli::LogFormat,
+) -> Result<TpmSession, TpmError> {
+    let mut nonce_caller = vec![0; 16];
+    thread_rng().fill_bytes(&mut nonce_caller);
+    let cmd = TpmStartAuthSessionCommand {
+        nonce_caller: Tpm2b::try_from(nonce_caller.as_slice())?,
+        encrypted_salt: Tpm2b::default(),
+        session_type: TpmSe::Policy,
+        symmetric: TpmtSymDefObject::default(),
+        auth_hash,
+    };
+    let (resp, _) = chip.execute(
+        &cmd,
+        &[TpmPermanent::NULL.into(), TpmPermanent::NULL.into()],
+        &[],
+        log_format,
+    )?;
+    let session_handle = resp.session_handle;
+
+    let cmd = TpmPolicySecretCommand {
+        nonce_tpm: Tpm2b::default(),
+        cp_hash_a: Tpm2bDigest::default(),
+        policy_ref: Tpm2b::default(),
+        expiration: 0,
+    };
+    let handles = [TpmPermanent::ENDORSEMENT.into(), session_handle.into()];
+    let result = get_auth_sessions(chip, &cmd, &handles, None, endorsement_auth, log_format)
+        .and_then(|sessions| chip.execute(&cmd, &handles, &sessions, log_format));
+    if let Err(err) = result {
+        flush_session(chip, session_handle, log_format);
+        return Err(err);
+    }
+    Ok(session_handle)
+}
+
+/// Returns the authorization for the USER role of the EK `ek_public`: a
+/// policy session satisfied with `TPM2_PolicySecret` of the endorsement
+/// hierarchy. The session is flushed by the TPM when the command succeeds
+/// and must be flushed by the caller when it fails.
+pub(crate) fn endorsement_policy_auth(
+    chip: &mut TpmDevice,
+    ek_public: &TpmtPublic,
+    endorsement_auth: Option<&str>,
+    log_format: cli::LogFormat,
+) -> Result<TpmsAuthCommand, TpmError> {
+    let auth_hash = ek_public.name_alg;
+    let nonce_size = tpm_hash_size(&auth_hash).ok_or_else(|| {
+        TpmError::Execution(format!("unsupported EK name algorithm: {auth_hash}"))
+    })?;
+    let policy_session = start_endorsement_policy(chip, auth_hash, endorsement_auth, log_format)?;
+    let mut nonce_caller = vec![0; nonce_size];
+    thread_rng().fill_bytes(&mut nonce_caller);
+    Ok(TpmsAuthCommand {
+        session_handle: policy_session,
+        nonce: Tpm2bNonce::try_from(nonce_caller.as_slice())?,
+        session_attributes: TpmaSession::empty(),
+        hmac: Tpm2bAuth::default(),
+    })
+}
+
+pub(crate) fn flush_session(chip: &mut TpmDevice, handle: TpmSession, log_format: cli::LogFormat) {
+    let Ok(flush_handle) = TpmContextHandle::try_from(handle) else {
+        return;
+    };
+    let cmd = TpmFlushContextCommand { flush_handle };
+    if let Err(err) = chip.execute(&cmd, &[], &[], log_format) {
+        tracing::debug!(handle = %u32::from(handle), "failed to flush the policy session: {err}");
+    }
+}
+
+/// Activates a credential with `TPM2_ActivateCredential` and returns the
+/// decrypted credential.
+///
+/// `object` is the key the credential was made for, authorized with
+/// `session` or the password `auth`. `ek` is the key protecting the seed. A
+/// low range EK is authorized with a policy session that is started and
+/// satisfied with `TPM2_PolicySecret` of the endorsement hierarchy, and a
+/// high range EK with its empty password.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if a TPM command fails, e.g. when the credential was
+/// not made for `object` or for `ek`.
+#[allow(clippy::too_many_arguments)]
+pub fn activate_credential(
+    chip: &mut TpmDevice,
+    object: TpmObjectHandle,
+    ek: TpmObjectHandle,
+    credential: &Credential,
+    session: Option<&AuthSession>,
+    auth: Option<&str>,
+    endorsement_auth: Option<&str>,
+    log_format: cli::LogFormat,
+) -> Result<Tpm2bDigest, TpmError> {
+    let (ek_public, _) = crate::read_public(chip, ek, log_format)?;
+    let cmd = TpmActivateCredentialCommand {
+        credential_blob: credential.blob,
+        secret: credential.secret,
+    };
+    let handles = [object.into(), ek.into()];
+    let mut sessions = get_auth_sessions(chip, &cmd, &handles, session, auth, log_format)?;
+
+    let ek_policy = requires_endorsement_policy(&ek_public);
+    if ek_policy {
+        sessions.push(endorsement_policy_auth(
+            chip,
+            &ek_public,
+            endorsement_auth,
+            log_format,
+        )?);
+    } else {
+        sessions.extend(build_password_session(Some(""))?);
+    }
+
+    match chip.execute(&cmd, &handles, &sessions, log_format) {
+        Ok((resp, _)) => Ok(resp.cert_info),
+        Err(err) => {
+            if ek_policy {
+                flush_session(
+                    chip,
+                    sessions[sessions.len() - 1].session_handle,
+                    log_format,
+                );
+            }
+            Err(err)
+        }
+    }
+}
@@ -3,9 +3,9 @@
 // Copyright (c) 2024-2025 Jarkko Sakkinen
 
 use crate::{build_to_vec, TpmError, TpmErrorKind};
-use aes::Aes128;
+use aes::{Aes128, Aes256};
 use cfb_mode::Encryptor;
-use cipher::{generic_array::GenericArray, BlockEncryptMut, KeyIvInit};
+use cipher::{generic_array::GenericArray, AsyncStreamCipher, BlockEncryptMut, KeyIvInit};
 use const_oid::db::rfc5912::{SECP_256_R_1, SECP_384_R_1, SECP_521_R_1};
 use hmac::{Hmac, Mac};
 use num_traits::FromPrimitive;
@@ -31,19 +31,22 @@ use pkcs8::{
     DecodePrivateKey, ObjectIdentifier, PrivateKeyInfo,
 };
 use rand::{thread_rng, RngCore};
-use rsa::{traits::PublicKeyParts, Oaep, RsaPrivateKey, RsaPublicKey};
+use rsa::{traits::PublicKeyParts, Oaep, Pkcs1v15Sign, Pss, RsaPrivateKey, RsaPublicKey};
 use sha1::Sha1;
 use sha2::{Digest, Sha256, Sha384, Sha512};
 use std::str::Utf8Error;
 use tpm2_protocol::{
     data::{
-        Tpm2b, Tpm2bDigest, Tpm2bEccParameter, Tpm2bEncryptedSecret, Tpm2bPrivate,
-        Tpm2bPublicKeyRsa, TpmAlgId, TpmCc, TpmEccCurve, TpmaObject, TpmsAuthCommand, TpmsEccPoint,
-        TpmtKdfScheme, TpmtPublic, TpmtScheme, TpmtSymDefObject, TpmuPublicId, TpmuPublicParms,
+        Tpm2b, Tpm2bDigest, Tpm2bEccParameter, Tpm2bEncryptedSecret, Tpm2bIdObject, Tpm2bName,
+        Tpm2bPrivate, Tpm2bPublicKeyRsa, TpmAlgId, TpmCc, TpmEccCurve, TpmaObject, TpmsAuthCommand,
+        TpmsEccPoint, TpmtKdfScheme, TpmtPublic, TpmtScheme, TpmtSignature, TpmtSymDefObject,
+        TpmuPublicId, TpmuPublicParms, TpmuSignature, TpmuSymKeyBits, TpmuSymMode,
     },
-    TpmBuild, TpmWriter, TPM_MAX_COMMAND_SIZE,
+    hash::{tpm_cp_hash, TpmHashProvider},
+    TpmBuild, TpmResult, TpmWriter, TPM_MAX_COMMAND_SIZE,
 };
 
+pub const ID_LOADABLE_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.23.133.1.3");
 pub const ID_IMPORTABLE_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.23.133.1.4");
 pub const ID_SEALED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.23.133.1.5");
 const UNCOMPRESSED_POINT_TAG: u8 = 0x04;
@@ -326,37 +329,52 @@ impl TpmKey {
     }
 }
 
-fn compute_hmac(
-    auth_hash: TpmAlgId,
-    hmac_key: &[u8],
-    attributes: u8,
-    nonce_tpm: &[u8],
-    nonce_caller: &[u8],
-    cp_hash_payload: &[u8],
-) -> Result<Vec<u8>, TpmError> {
+/// Computes `HMAC(key, chunks[0] || chunks[1] || ...)` with `alg`.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Execution` if `alg` is not supported.
+pub(crate) fn hmac(alg: TpmAlgId, key: &[u8], chunks: &[&[u8]]) -> Result<Vec<u8>, TpmError> {
     macro_rules! do_hmac {
         ($digest:ty) => {{
-            let cp_hash = <$digest as Digest>::digest(cp_hash_payload);
-            let mut mac = <Hmac<$digest> as Mac>::new_from_slice(hmac_key)
+            let mut mac = <Hmac<$digest> as Mac>::new_from_slice(key)
                 .map_err(|e| TpmError::Execution(format!("HMAC init error: {e}")))?;
-            mac.update(&cp_hash);
-            mac.update(nonce_tpm);
-            mac.update(nonce_caller);
-            mac.update(&[attributes]);
+            for chunk in chunks {
+                mac.update(chunk);
+            }
             Ok(mac.finalize().into_bytes().to_vec())
         }};
     }
 
-    match auth_hash {
+    match alg {
+        TpmAlgId::Sha1 => do_hmac!(Sha1),
         TpmAlgId::Sha256 => do_hmac!(Sha256),
         TpmAlgId::Sha384 => do_hmac!(Sha384),
         TpmAlgId::Sha512 => do_hmac!(Sha512),
         _ => Err(TpmError::Execution(format!(
-            "unsupported session hash algorithm: {auth_hash}"
+            "unsupported HMAC hash algorithm: {alg}"
         ))),
     }
 }
 
+/// Computes a session HMAC over `cpHash || nonceNewer || nonceOlder ||
+/// sessionAttributes`. For a command the newer nonce is `nonceCaller`, and
+/// for a response it is `nonceTPM`.
+pub(crate) fn session_hmac(
+    auth_hash: TpmAlgId,
+    hmac_key: &[u8],
+    attributes: u8,
+    nonce_newer: &[u8],
+    nonce_older: &[u8],
+    p_hash: &[u8],
+) -> Result<Vec<u8>, TpmError> {
+    hmac(
+        auth_hash,
+        hmac_key,
+        &[p_hash, nonce_newer, nonce_older, &[attributes]],
+    )
+}
+
 /// Computes the authorization HMAC for a command session.
 ///
 /// # Errors
@@ -367,26 +385,25 @@ pub fn create_auth(
     session: &super::AuthSession,
     nonce_caller: &tpm2_protocol::data::Tpm2bNonce,
     command_code: TpmCc,
-    handles: &[u32],
+    names: &[Tpm2bName],
     parameters: &[u8],
 ) -> Result<TpmsAuthCommand, TpmError> {
-    let cp_hash_payload = {
-        let mut payload = Vec::new();
-        payload.extend_from_slice(&(command_code as u32).to_be_bytes());
-        for handle in handles {
-            payload.extend_from_slice(&handle.to_be_bytes());
-        }
-        payload.extend_from_slice(parameters);
-        payload
-    };
+    let names: Vec<&[u8]> = names.iter().map(|name| &name[..]).collect();
+    let cp_hash = tpm_cp_hash(
+        &SoftwareHash,
+        session.auth_hash,
+        command_code,
+        &names,
+        parameters,
+    )?;
 
-    let hmac_bytes = compute_hmac(
+    let hmac_bytes = session_hmac(
         session.auth_hash,
         &session.hmac_key,
         session.attributes.bits(),
-        &session.nonce_tpm,
         nonce_caller,
-        &cp_hash_payload,
+        &session.nonce_tpm,
+        &cp_hash,
     )?;
 
     Ok(TpmsAuthCommand {
@@ -397,7 +414,38 @@ pub fn create_auth(
     })
 }
 
-fn kdfa(
+/// The `TpmHashProvider` backed by the `sha1` and `sha2` crates.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SoftwareHash;
+
+impl TpmHashProvider for SoftwareHash {
+    fn digest(&self, alg: TpmAlgId, chunks: &[&[u8]], out: &mut [u8]) -> TpmResult<usize> {
+        macro_rules! do_digest {
+            ($digest:ty) => {{
+                let mut hasher = <$digest as Digest>::new();
+                for chunk in chunks {
+                    Digest::update(&mut hasher, chunk);
+                }
+                let digest = hasher.finalize();
+                let target = out
+                    .get_mut(..digest.len())
+                    .ok_or(TpmErrorKind::CapacityExceeded)?;
+                target.copy_from_slice(&digest);
+                Ok(digest.len())
+            }};
+        }
+
+        match alg {
+            TpmAlgId::Sha1 => do_digest!(Sha1),
+            TpmAlgId::Sha256 => do_digest!(Sha256),
+            TpmAlgId::Sha384 => do_digest!(Sha384),
+            TpmAlgId::Sha512 => do_digest!(Sha512),
+            _ => Err(TpmErrorKind::InvalidValue.into()),
+        }
+    }
+}
+
+pub(crate) fn kdfa(
     auth_hash: TpmAlgId,
     hmac_key: &[u8],
     label: &str,
@@ -655,7 +703,7 @@ pub fn create_import_blob(
     };
 
     let parent_name_len_bytes = u16::try_from(parent_name.len())
-        .map_err(|_| TpmError::Build(TpmErrorKind::InvalidValue))?
+        .map_err(|_| TpmError::from(TpmErrorKind::InvalidValue))?
         .to_be_bytes();
 
     let sym_key = kdfa(
@@ -735,6 +783,316 @@ pub fn create_import_blob(
     ))
 }
 
+/// Computes a digest in software.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if `alg` is not a supported hash algorithm.
+pub fn software_digest(alg: TpmAlgId, chunks: &[&[u8]]) -> Result<Vec<u8>, TpmError> {
+    let mut out = [0u8; 64];
+    let len = SoftwareHash.digest(alg, chunks, &mut out)?;
+    Ok(out[..len].to_vec())
+}
+
+/// Constructs an RSA public key from a TPM public area.
+fn rsa_public_key(public: &TpmtPublic) -> Result<RsaPublicKey, TpmError> {
+    let (TpmuPublicId::Rsa(n), TpmuPublicParms::Rsa { exponent, .. }) =
+        (&public.unique, &public.parameters)
+    else {
+        return Err(TpmError::Execution("not an RSA public key".to_string()));
+    };
+    let exponent = if *exponent == 0 { 65537 } else { *exponent };
+    RsaPublicKey::new(rsa::BigUint::from_bytes_be(n), rsa::BigUint::from(exponent))
+        .map_err(|e| TpmError::Execution(format!("failed to construct RSA public key: {e}")))
+}
+
+macro_rules! ecdsa_verify_prehash {
+    ($curve:ident, $point:expr, $digest:expr, $sig:expr) => {{
+        use $curve::ecdsa::signature::hazmat::PrehashVerifier;
+        let size = <$curve::FieldBytes>::default().len();
+        let pad = |bytes: &[u8]| -> Result<$curve::FieldBytes, TpmError> {
+            let offset = size.checked_sub(bytes.len()).ok_or_else(|| {
+                TpmError::Execution("ECC parameter larger than the curve".to_string())
+            })?;
+            let mut out = <$curve::FieldBytes>::default();
+            out[offset..].copy_from_slice(bytes);
+            Ok(out)
+        };
+        let point = $curve::EncodedPoint::from_affine_coordinates(
+            &pad(&$point.x)?,
+            &pad(&$point.y)?,
+            false,
+        );
+        let key = $curve::ecdsa::VerifyingKey::from_encoded_point(&point)
+            .map_err(|_| TpmError::Execution("invalid ECC public key".to_string()))?;
+        let signature = $curve::ecdsa::Signature::from_scalars(
+            pad(&$sig.signature_r)?,
+            pad(&$sig.signature_s)?,
+        )
+        .map_err(|_| TpmError::Execution("invalid ECDSA signature".to_string()))?;
+        key.verify_prehash($digest, &signature).is_ok()
+    }};
+}
+
+/// Verifies a TPM signature over `message` with a public key. Supports
+/// RSASSA, RSAPSS and ECDSA with NIST P-256, P-384 and P-521.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Execution` if the signature does not verify, or if
+/// the key or the signature scheme is not supported.
+pub fn verify_signature(
+    public: &TpmtPublic,
+    message: &[u8],
+    signature: &TpmtSignature,
+) -> Result<(), TpmError> {
+    let hash = crate::quote::signature_hash_alg(signature).ok_or_else(|| {
+        TpmError::Execution(format!(
+            "unsupported signature scheme: {:?}",
+            signature.sig_alg
+        ))
+    })?;
+    let digest = software_digest(hash, &[message])?;
+    verify_digest_signature(public, &digest, signature)
+}
+
+/// Verifies a TPM signature over a `digest` computed with the hash algorithm
+/// of the signature.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Execution` if the signature does not verify, or if
+/// the key or the signature scheme is not supported.
+pub fn verify_digest_signature(
+    public: &TpmtPublic,
+    digest: &[u8],
+    signature: &TpmtSignature,
+) -> Result<(), TpmError> {
+    macro_rules! rsa_verify {
+        ($scheme:ident, $hash:expr, $digest:expr, $sig:expr) => {{
+            let key = rsa_public_key(public)?;
+            match $hash {
+                TpmAlgId::Sha1 => key.verify($scheme::new::<Sha1>(), $digest, $sig),
+                TpmAlgId::Sha256 => key.verify($scheme::new::<Sha256>(), $digest, $sig),
+                TpmAlgId::Sha384 => key.verify($scheme::new::<Sha384>(), $digest, $sig),
+                TpmAlgId::Sha512 => key.verify($scheme::new::<Sha512>(), $digest, $sig),
+                _ => {
+                    return Err(TpmError::Execution(format!(
+                        "unsupported hash: {:?}",
+                        $hash
+                    )))
+                }
+            }
+            .is_ok()
+        }};
+    }
+
+    let valid = match &signature.signature {
+        TpmuSignature::Rsassa(sig) => rsa_verify!(Pkcs1v15Sign, sig.hash, digest, &sig.sig),
+        TpmuSignature::Rsapss(sig) => rsa_verify!(Pss, sig.hash, digest, &sig.sig),
+        TpmuSignature::Ecdsa(sig) => {
+            let (TpmuPublicId::Ecc(point), TpmuPublicParms::Ecc { curve_id, .. }) =
+                (&public.unique, &public.parameters)
+            else {
+                return Err(TpmError::Execution("not an ECC public key".to_string()));
+            };
+            match curve_id {
+                TpmEccCurve::NistP256 => ecdsa_verify_prehash!(p256, point, digest, sig),
+                TpmEccCurve::NistP384 => ecdsa_verify_prehash!(p384, point, digest, sig),
+                TpmEccCurve::NistP521 => ecdsa_verify_prehash!(p521, point, digest, sig),
+                _ => {
+                    return Err(TpmError::Execution(format!(
+                        "unsupported ECC curve: {curve_id:?}"
+                    )))
+                }
+            }
+        }
+        _ => {
+            return Err(TpmError::Execution(format!(
+                "unsupported signature scheme: {:?}",
+                signature.sig_alg
+            )))
+        }
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(TpmError::Execution(
+            "signature verification failed".to_string(),
+        ))
+    }
+}
+
+/// Derives key material from an ECDH shared secret `z` with the `KDFe` of
+/// SP800-56A: `Hash(counter || Z || label || 0 || partyU || partyV)`,
+/// iterated until `key_bits` have been produced.
+pub(crate) fn kdfe(
+    alg: TpmAlgId,
+    z: &[u8],
+    label: &str,
+    party_u: &[u8],
+    party_v: &[u8],
+    key_bits: u16,
+) -> Result<Vec<u8>, TpmError> {
+    let key_bytes = usize::from(key_bits).div_ceil(8);
+    let mut key_stream = Vec::with_capacity(key_bytes);
+    let mut counter: u32 = 1;
+    while key_stream.len() < key_bytes {
+        let digest = software_digest(
+            alg,
+            &[
+                &counter.to_be_bytes(),
+                z,
+                label.as_bytes(),
+                &[0x00],
+                party_u,
+                party_v,
+            ],
+        )?;
+        let to_take = (key_bytes - key_stream.len()).min(digest.len());
+        key_stream.extend_from_slice(&digest[..to_take]);
+        counter += 1;
+    }
+    Ok(key_stream)
+}
+
+/// Runs an ephemeral ECDH against a public point and returns the shared `x`
+/// coordinate and the uncompressed ephemeral point without its tag.
+macro_rules! ecdh_ephemeral {
+    ($curve:ident, $point:expr) => {{
+        let sec1 = [&[UNCOMPRESSED_POINT_TAG][..], &$point.x, &$point.y].concat();
+        let public = $curve::PublicKey::from_sec1_bytes(&sec1)
+            .map_err(|_| TpmError::Execution("invalid ECC public key".to_string()))?;
+        let ephemeral = $curve::SecretKey::random(&mut thread_rng());
+        let z = $curve::ecdh::diffie_hellman(ephemeral.to_nonzero_scalar(), public.as_affine());
+        let encoded = ephemeral.public_key().to_encoded_point(false);
+        (
+            z.raw_secret_bytes().to_vec(),
+            encoded.as_bytes()[1..].to_vec(),
+        )
+    }};
+}
+
+/// Creates a credential for `TPM2_ActivateCredential` in software, as
+/// `TPM2_MakeCredential` would do with the EK loaded.
+///
+/// The seed is protected with RSA-OAEP using the label "IDENTITY" for an RSA
+/// EK, and with an ephemeral ECDH key and `KDFe` for an ECC EK. The credential
+/// is encrypted and bound to `object_name`, so that only the TPM holding the
+/// EK can recover it, and only for the object with that Name.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if the EK is not a supported RSA or ECC storage key,
+/// or if the credential is larger than the digest of its name algorithm.
+pub fn make_credential(
+    ek_public: &TpmtPublic,
+    object_name: &[u8],
+    credential: &[u8],
+) -> Result<(Tpm2bIdObject, Tpm2bEncryptedSecret), TpmError> {
+    let name_alg = ek_public.name_alg;
+    let digest_size = tpm2_protocol::tpm_hash_size(&name_alg)
+        .ok_or_else(|| TpmError::Execution(format!("unsupported EK name algorithm: {name_alg}")))?;
+    if credential.len() > digest_size {
+        return Err(TpmError::Execution(format!(
+            "credential is larger than {digest_size} bytes"
+        )));
+    }
+    let digest_bits = u16::try_from(digest_size * 8)
+        .map_err(|_| TpmError::Execution("hash size conversion error".to_string()))?;
+
+    let (symmetric, seed, secret) = match (&ek_public.parameters, &ek_public.unique) {
+        (TpmuPublicParms::Rsa { symmetric, .. }, TpmuPublicId::Rsa(_)) => {
+            let mut seed = vec![0u8; digest_size];
+            thread_rng().fill_bytes(&mut seed);
+            let key = rsa_public_key(ek_public)?;
+            let mut rng = thread_rng();
+            let label = "IDENTITY\0";
+            let secret = match name_alg {
+                TpmAlgId::Sha1 => {
+                    key.encrypt(&mut rng, Oaep::new_with_label::<Sha1, _>(label), &seed)
+                }
+                TpmAlgId::Sha256 => {
+                    key.encrypt(&mut rng, Oaep::new_with_label::<Sha256, _>(label), &seed)
+                }
+                TpmAlgId::Sha384 => {
+                    key.encrypt(&mut rng, Oaep::new_with_label::<Sha384, _>(label), &seed)
+                }
+                TpmAlgId::Sha512 => {
+                    key.encrypt(&mut rng, Oaep::new_with_label::<Sha512, _>(label), &seed)
+                }
+                _ => {
+                    return Err(TpmError::Execution(format!(
+                        "unsupported EK name algorithm: {name_alg}"
+                    )))
+                }
+            }
+            .map_err(|e| TpmError::Execution(format!("RSA-OAEP encryption failed: {e}")))?;
+            (symmetric, seed, secret)
+        }
+        (
+            TpmuPublicParms::Ecc {
+                symmetric,
+                curve_id,
+                ..
+            },
+            TpmuPublicId::Ecc(point),
+        ) => {
+            let (z, ephemeral) = match curve_id {
+                TpmEccCurve::NistP256 => ecdh_ephemeral!(p256, point),
+                TpmEccCurve::NistP384 => ecdh_ephemeral!(p384, point),
+                TpmEccCurve::NistP521 => ecdh_ephemeral!(p521, point),
+                _ => {
+                    return Err(TpmError::Execution(format!(
+                        "unsupported EK curve: {curve_id:?}"
+                    )))
+                }
+            };
+            let (x, y) = ephemeral.split_at(ephemeral.len() / 2);
+            let seed = kdfe(name_alg, &z, "IDENTITY", x, &point.x, digest_bits)?;
+            let secret = build_to_vec(&TpmsEccPoint {
+                x: Tpm2bEccParameter::try_from(x)?,
+                y: Tpm2bEccParameter::try_from(y)?,
+            })?;
+            (symmetric, seed, secret)
+        }
+        _ => {
+            return Err(TpmError::Execution(
+                "EK must be an RSA or ECC key".to_string(),
+            ))
+        }
+    };
+
+    let (
+        TpmAlgId::Aes,
+        TpmuSymKeyBits::Aes(sym_bits @ (128 | 256)),
+        TpmuSymMode::Aes(TpmAlgId::Cfb),
+    ) = (symmetric.algorithm, symmetric.key_bits, symmetric.mode)
+    else {
+        return Err(TpmError::Execution(
+            "EK must be a storage key with AES-128 or AES-256 in CFB mode".to_string(),
+        ));
+    };
+    let sym_key = kdfa(name_alg, &seed, "STORAGE", object_name, &[], sym_bits)?;
+    let mut enc_identity = build_to_vec(&Tpm2bDigest::try_from(credential)?)?;
+    let iv = [0u8; 16];
+    if sym_bits == 128 {
+        Encryptor::<Aes128>::new(sym_key.as_slice().into(), &iv.into()).encrypt(&mut enc_identity);
+    } else {
+        Encryptor::<Aes256>::new(sym_key.as_slice().into(), &iv.into()).encrypt(&mut enc_identity);
+    }
+
+    let hmac_key = kdfa(name_alg, &seed, "INTEGRITY", &[], &[], digest_bits)?;
+    let integrity = hmac(name_alg, &hmac_key, &[&enc_identity, object_name])?;
+    let mut blob = build_to_vec(&Tpm2bDigest::try_from(integrity.as_slice())?)?;
+    blob.extend_from_slice(&enc_identity);
+
+    Ok((
+        Tpm2bIdObject::try_from(blob.as_slice())?,
+        Tpm2bEncryptedSecret::try_from(secret.as_slice())?,
+    ))
+}
+
 impl From<Utf8Error> for TpmError {
     fn from(err: Utf8Error) -> Self {
         TpmError::Parse(err.to_string())
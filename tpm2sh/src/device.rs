@@ -2,40 +2,149 @@
 // Copyright (c) 2025 Opinsys Oy
 // Copyright (c) 2024-2025 Jarkko Sakkinen
 
-use crate::{cli, pretty_printer::PrettyTrace, TpmError};
+use crate::{
+    cli, pretty_printer::PrettyTrace, resource_manager::TpmResourceManager, SoftwareHash, TpmError,
+};
 use indicatif::{ProgressBar, ProgressStyle};
 use std::{
-    fs::{File, OpenOptions},
+    collections::HashMap,
+    fs::OpenOptions,
     io::{self, IsTerminal, Read, Write},
+    os::unix::fs::OpenOptionsExt,
     path::Path,
-    time::Duration,
+    thread,
+    time::{Duration, Instant},
 };
 use tpm2_protocol::{
     self,
-    data::{self, TpmSt, TpmuCapabilities},
+    data::{
+        self, Tpm2bName, TpmCap, TpmCc, TpmHt, TpmRc, TpmRcBase, TpmSt, TpmtPublic,
+        TpmuCapabilities, TPM_PT_MAX_COMMAND_SIZE, TPM_PT_MAX_RESPONSE_SIZE,
+    },
+    hash::{tpm_handle_name, tpm_object_name},
     message::{
-        TpmGetCapabilityCommand, TpmGetCapabilityResponse, TpmReadPublicCommand, TpmResponseBody,
+        TpmCommand, TpmGetCapabilityCommand, TpmGetCapabilityResponse, TpmNvReadPublicCommand,
+        TpmReadPublicCommand, TPM_HEADER_SIZE,
     },
-    TpmWriter, TPM_MAX_COMMAND_SIZE,
+    metadata::{tpm_command_metadata, TpmHandleEffect},
+    TpmHandle, TpmWriter, TPM_MAX_COMMAND_SIZE,
 };
 use tracing::{trace, warn};
 
 pub const TPM_CAP_PROPERTY_MAX: u32 = 128;
 
+/// The interval at which a non-blocking device is polled for a response.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Controls how `TpmDevice::execute` retries commands that the TPM could not
+/// execute yet: `TPM_RC_RETRY`, `TPM_RC_YIELDED`, `TPM_RC_TESTING`,
+/// `TPM_RC_NV_RATE` and `TPM_RC_NV_UNAVAILABLE`.
+///
+/// The delay starts at `initial_delay` and doubles after every attempt up to
+/// `max_delay`. Retrying stops after `max_retries` attempts, or when the next
+/// delay would exceed `budget` in total.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TpmRetryPolicy {
+    pub max_retries: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub budget: Duration,
+}
+
+impl Default for TpmRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 8,
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_secs(1),
+            budget: Duration::from_secs(10),
+        }
+    }
+}
+
+impl TpmRetryPolicy {
+    /// A policy that never retries.
+    pub const NONE: Self = Self {
+        max_retries: 0,
+        initial_delay: Duration::ZERO,
+        max_delay: Duration::ZERO,
+        budget: Duration::ZERO,
+    };
+
+    /// Returns `true` if a command failing with `rc` can be sent again as is.
+    #[must_use]
+    pub fn is_retryable(rc: TpmRc) -> bool {
+        matches!(
+            rc.base(),
+            Ok(TpmRcBase::Retry
+                | TpmRcBase::Yielded
+                | TpmRcBase::Testing
+                | TpmRcBase::NvRate
+                | TpmRcBase::NvUnavailable)
+        )
+    }
+
+    /// Returns the delay before retry number `attempt`, counting from zero.
+    #[must_use]
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_delay)
+    }
+}
+
+/// A byte stream carrying TPM commands and responses, such as a character
+/// device or an in-process responder. A command is written in full and
+/// flushed before its response is read.
+pub trait TpmTransport: Read + Write {}
+
+impl<T: Read + Write> TpmTransport for T {}
+
+/// Returns `true` if `path` names a TPM character device without a kernel
+/// resource manager, i.e. `tpm<N>` rather than `tpmrm<N>`.
+fn is_raw_device(path: &str) -> bool {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix("tpm"))
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|c| c.is_ascii_digit()))
+}
+
+/// Reads the response code from the header of a response.
+fn response_rc(response: &[u8]) -> Option<TpmRc> {
+    let rc = response.get(6..10)?.try_into().ok()?;
+    TpmRc::try_from(u32::from_be_bytes(rc)).ok()
+}
+
 pub struct TpmDevice {
-    file: File,
+    transport: Box<dyn TpmTransport>,
+    max_command_size: Option<usize>,
+    max_response_size: usize,
+    names: HashMap<u32, Tpm2bName>,
+    retry_policy: TpmRetryPolicy,
+    timeout: Option<Duration>,
 }
 
 impl TpmDevice {
     /// Opens a TPM device for communication.
     ///
+    /// A raw character device such as `/dev/tpm0` has no kernel resource
+    /// manager, so it is opened behind a `TpmResourceManager`. With a
+    /// `timeout`, the device is opened in non-blocking mode and a response
+    /// that does not arrive in time fails the command.
+    ///
     /// # Errors
     ///
     /// Returns a `TpmError::File` if the path cannot be opened.
-    pub fn new(path: &str) -> Result<TpmDevice, TpmError> {
+    pub fn new(path: &str, timeout: Option<Duration>) -> Result<TpmDevice, TpmError> {
         let file = OpenOptions::new()
             .read(true)
             .write(true)
+            .custom_flags(if timeout.is_some() {
+                libc::O_NONBLOCK
+            } else {
+                0
+            })
             .open(Path::new(path))
             .map_err(|e| {
                 TpmError::File(
@@ -44,7 +153,86 @@ impl TpmDevice {
                 )
             })?;
         tracing::debug!(device_path = %path, "opening");
-        Ok(TpmDevice { file })
+        let mut device = Self::from_transport(Box::new(file));
+        device.set_timeout(timeout);
+        if is_raw_device(path) {
+            tracing::debug!(device_path = %path, "using userspace resource manager");
+            return Ok(Self::from_transport(Box::new(TpmResourceManager::new(
+                device,
+            ))));
+        }
+        Ok(device)
+    }
+
+    /// Creates a device that communicates over `transport`.
+    #[must_use]
+    pub fn from_transport(transport: Box<dyn TpmTransport>) -> TpmDevice {
+        TpmDevice {
+            transport,
+            max_command_size: None,
+            max_response_size: TPM_MAX_COMMAND_SIZE,
+            names: HashMap::new(),
+            retry_policy: TpmRetryPolicy::NONE,
+            timeout: None,
+        }
+    }
+
+    /// Sets the policy for retrying commands that the TPM could not execute
+    /// yet. By default, commands are not retried.
+    pub fn set_retry_policy(&mut self, retry_policy: TpmRetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Sets the time to wait for a response before failing with
+    /// `io::ErrorKind::TimedOut`. The transport must not block on reads for
+    /// the timeout to take effect, i.e. it must report `WouldBlock` while the
+    /// response is pending.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the maximum command size accepted by the TPM.
+    ///
+    /// The value is read from `TPM_PT_MAX_COMMAND_SIZE` on the first call and
+    /// capped to `TPM_MAX_COMMAND_SIZE`. If the TPM does not report it, the
+    /// compile-time maximum is used.
+    pub fn max_command_size(&mut self, log_format: cli::LogFormat) -> usize {
+        if let Some(size) = self.max_command_size {
+            return size;
+        }
+        self.max_command_size = Some(TPM_MAX_COMMAND_SIZE);
+        let cmd = TpmGetCapabilityCommand {
+            cap: TpmCap::TpmProperties,
+            property: TPM_PT_MAX_COMMAND_SIZE,
+            property_count: 2,
+        };
+        if let Ok((
+            TpmGetCapabilityResponse {
+                capability_data:
+                    data::TpmsCapabilityData {
+                        data: TpmuCapabilities::TpmProperties(props),
+                        ..
+                    },
+                ..
+            },
+            _,
+        )) = self.execute(&cmd, &[], &[], log_format)
+        {
+            for prop in props.iter() {
+                let value = (prop.value as usize).min(TPM_MAX_COMMAND_SIZE);
+                match prop.property {
+                    TPM_PT_MAX_COMMAND_SIZE => self.max_command_size = Some(value),
+                    TPM_PT_MAX_RESPONSE_SIZE => self.max_response_size = value,
+                    _ => {}
+                }
+            }
+        }
+        tracing::debug!(
+            max_command_size = ?self.max_command_size,
+            max_response_size = self.max_response_size,
+            "limits"
+        );
+        self.max_command_size.unwrap_or(TPM_MAX_COMMAND_SIZE)
     }
 
     /// Sends a command to the TPM and waits for the response.
@@ -54,16 +242,17 @@ impl TpmDevice {
     /// # Errors
     ///
     /// This function will return an error if building the command fails, I/O
-    /// with the device fails, or the TPM itself returns an error.
+    /// with the device fails, the TPM itself returns an error, or the response
+    /// body does not match the command.
     pub fn execute<C>(
         &mut self,
         command: &C,
-        handles: Option<&[u32]>,
+        handles: &C::Handles,
         sessions: &[tpm2_protocol::data::TpmsAuthCommand],
         log_format: cli::LogFormat,
-    ) -> Result<(TpmResponseBody, tpm2_protocol::message::TpmAuthResponses), TpmError>
+    ) -> Result<(C::Response, tpm2_protocol::message::TpmAuthResponses), TpmError>
     where
-        C: for<'a> tpm2_protocol::message::TpmHeader<'a> + PrettyTrace,
+        C: TpmCommand + PrettyTrace,
     {
         let mut command_buf = [0u8; TPM_MAX_COMMAND_SIZE];
         let len = {
@@ -82,6 +271,12 @@ impl TpmDevice {
             )?;
             writer.len()
         };
+        let max_command_size = self.max_command_size(log_format);
+        if len > max_command_size {
+            return Err(TpmError::Execution(format!(
+                "command size {len} exceeds TPM_PT_MAX_COMMAND_SIZE ({max_command_size})"
+            )));
+        }
         let command_bytes = &command_buf[..len];
 
         let maybe_pb = if std::io::stderr().is_terminal() {
@@ -104,34 +299,28 @@ impl TpmDevice {
             }
             cli::LogFormat::Plain => trace!(command = %hex::encode(command_bytes), "Command"),
         }
-        self.file.write_all(command_bytes)?;
-        self.file.flush()?;
-
-        let mut header = [0u8; 10];
-        self.file.read_exact(&mut header)?;
-
-        let Ok(size_bytes): Result<[u8; 4], _> = header[2..6].try_into() else {
-            unreachable!();
-        };
-        let size = u32::from_be_bytes(size_bytes) as usize;
-
-        if size < header.len() || size > TPM_MAX_COMMAND_SIZE {
-            if let Some(pb) = maybe_pb {
-                pb.abandon_with_message("✖ Invalid response size in TPM header.");
+        let resp_buf = match self.transmit_with_retry(command_bytes) {
+            Ok(resp_buf) => resp_buf,
+            Err(err) => {
+                if let Some(pb) = maybe_pb {
+                    pb.abandon_with_message("✖ TPM operation failed.");
+                }
+                return Err(err);
             }
-            return Err(TpmError::Parse(format!(
-                "Invalid response size in header: {size}"
-            )));
-        }
-
-        let mut resp_buf = header.to_vec();
-        resp_buf.resize(size, 0);
-        self.file.read_exact(&mut resp_buf[header.len()..])?;
+        };
 
         if let Some(pb) = maybe_pb {
             pb.finish_with_message("✔ TPM operation complete.");
         }
 
+        if let Some(rc) =
+            response_rc(&resp_buf).filter(|rc| rc.is_warning() && resp_buf.len() <= TPM_HEADER_SIZE)
+        {
+            trace!(response = %hex::encode(&resp_buf), "Warning Response");
+            warn!(rc = %rc, "TPM did not execute the command");
+            return Err(TpmError::TpmRc(rc));
+        }
+
         let result = tpm2_protocol::message::tpm_parse_response(C::COMMAND, &resp_buf)?;
 
         match &result {
@@ -153,31 +342,145 @@ impl TpmDevice {
                 if rc.is_warning() {
                     warn!(rc = %rc, "TPM command completed with a warning");
                 }
+                let response = C::Response::try_from(response)
+                    .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+                let flushes = tpm_command_metadata(C::COMMAND)
+                    .is_some_and(|m| m.handle_effect == TpmHandleEffect::Flushes);
+                if flushes || C::COMMAND == TpmCc::EvictControl {
+                    self.names.clear();
+                }
                 Ok((response, auth))
             }
             Err((rc, _)) => Err(TpmError::TpmRc(rc)),
         }
     }
 
+    /// Sends a marshaled command with `transmit`, and sends it again according
+    /// to the retry policy while the TPM answers with a retryable warning.
+    fn transmit_with_retry(&mut self, command: &[u8]) -> Result<Vec<u8>, TpmError> {
+        let start = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let response = self.transmit(command)?;
+            let Some(rc) = response_rc(&response) else {
+                return Ok(response);
+            };
+            if !TpmRetryPolicy::is_retryable(rc) || attempt >= self.retry_policy.max_retries {
+                return Ok(response);
+            }
+            let delay = self.retry_policy.delay(attempt);
+            if start.elapsed() + delay > self.retry_policy.budget {
+                return Ok(response);
+            }
+            tracing::debug!(rc = %rc, attempt, delay = ?delay, "retrying");
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    /// Sends a marshaled command to the TPM and returns the marshaled
+    /// response without interpreting either.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError::Io` if I/O with the device fails or the response
+    /// does not arrive within the timeout, or a `TpmError::Parse` if the size
+    /// in the response header is invalid.
+    pub fn transmit(&mut self, command: &[u8]) -> Result<Vec<u8>, TpmError> {
+        self.transport.write_all(command)?;
+        self.transport.flush()?;
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+
+        let mut header = [0u8; 10];
+        self.read_response(&mut header, deadline)?;
+
+        let Ok(size_bytes): Result<[u8; 4], _> = header[2..6].try_into() else {
+            unreachable!();
+        };
+        let size = u32::from_be_bytes(size_bytes) as usize;
+
+        if size < header.len() || size > self.max_response_size {
+            return Err(TpmError::Parse(format!(
+                "Invalid response size in header: {size}"
+            )));
+        }
+
+        let mut resp_buf = header.to_vec();
+        resp_buf.resize(size, 0);
+        self.read_response(&mut resp_buf[header.len()..], deadline)?;
+        Ok(resp_buf)
+    }
+
+    /// Fills `buf` from the transport, polling while the transport reports
+    /// `WouldBlock` until `deadline`.
+    fn read_response(&mut self, buf: &mut [u8], deadline: Option<Instant>) -> Result<(), TpmError> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.transport.read(&mut buf[filled..]) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "TPM did not respond in time",
+                        )
+                        .into());
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the Name of a loaded object from its public area, so that
+    /// `get_handle_names` does not need to read it back from the TPM.
+    pub fn register_object(&mut self, handle: u32, public: &TpmtPublic) {
+        match tpm_object_name(&SoftwareHash, public) {
+            Ok(name) => {
+                self.names.insert(handle, name);
+            }
+            Err(e) => tracing::debug!(handle, error = %e, "cannot compute object name"),
+        }
+    }
+
     /// Retrieves the names for a list of handles.
     ///
+    /// PCR, session and permanent handles are their own Name. The Names of
+    /// objects are served from the cache when available, and otherwise read
+    /// from the TPM. NV index Names are always read from the TPM, as they
+    /// change whenever the index is written, locked or redefined.
+    ///
     /// # Errors
     ///
     /// Returns a `TpmError` if the underlying `execute` call fails.
     pub fn get_handle_names(
         &mut self,
-        handles: &[u32],
+        handles: &[TpmHandle],
         log_format: cli::LogFormat,
-    ) -> Result<Vec<Vec<u8>>, TpmError> {
+    ) -> Result<Vec<Tpm2bName>, TpmError> {
         handles
             .iter()
-            .map(|&handle| {
+            .map(|&typed| {
+                let handle = u32::from(typed);
+                if let Ok(name) = tpm_handle_name(handle) {
+                    return Ok(name);
+                }
+                if TpmHt::from_handle(handle) == Some(TpmHt::NvIndex) {
+                    let cmd = TpmNvReadPublicCommand {};
+                    let (resp, _) = self.execute(&cmd, &[typed], &[], log_format)?;
+                    return Ok(resp.nv_name);
+                }
+                if let Some(name) = self.names.get(&handle) {
+                    return Ok(*name);
+                }
                 let cmd = TpmReadPublicCommand {};
-                let (resp, _) = self.execute(&cmd, Some(&[handle]), &[], log_format)?;
-                let read_public_resp = resp
-                    .ReadPublic()
-                    .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
-                Ok(read_public_resp.name.to_vec())
+                let (resp, _) = self.execute(&cmd, &[typed], &[], log_format)?;
+                self.names.insert(handle, resp.name);
+                Ok(resp.name)
             })
             .collect()
     }
@@ -203,18 +506,17 @@ impl TpmDevice {
                 property_count: count,
             };
 
-            let (resp, _) = self.execute(&cmd, None, &[], log_format)?;
+            let (resp, _) = self.execute(&cmd, &[], &[], log_format)?;
             let TpmGetCapabilityResponse {
                 more_data,
                 capability_data,
-            } = resp
-                .GetCapability()
-                .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+            } = resp;
 
             let next_prop = if more_data.into() {
                 match &capability_data.data {
                     TpmuCapabilities::Algs(algs) => algs.last().map(|p| p.alg as u32 + 1),
                     TpmuCapabilities::Handles(handles) => handles.last().map(|&h| h + 1),
+                    TpmuCapabilities::TpmProperties(props) => props.last().map(|p| p.property + 1),
                     TpmuCapabilities::Pcrs(_) => None,
                 }
             } else {
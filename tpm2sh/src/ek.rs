@@ -0,0 +1,519 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! Endorsement keys and certificates of the TCG EK Credential Profile.
+
+use crate::{
+    cli, get_auth_sessions,
+    x509::{same_public_key, Certificate},
+    AuthSession, TpmDevice, TpmError,
+};
+use pkcs8::ObjectIdentifier;
+use std::{fmt, fs, path::Path, str::FromStr, time::Duration};
+use tpm2_protocol::{
+    data::{
+        Tpm2b, Tpm2bAuth, Tpm2bDigest, Tpm2bEccParameter, Tpm2bPublic, Tpm2bSensitiveCreate,
+        Tpm2bSensitiveData, TpmAlgId, TpmEccCurve, TpmRcBase, TpmaObject, TpmlPcrSelection,
+        TpmsEccPoint, TpmsSensitiveCreate, TpmtKdfScheme, TpmtPublic, TpmtScheme, TpmtSymDefObject,
+        TpmuCapabilities, TpmuPublicId, TpmuPublicParms, TpmuSymKeyBits, TpmuSymMode,
+        MAX_NV_BUFFER_SIZE, TPM_PT_NV_BUFFER_MAX,
+    },
+    message::{TpmCreatePrimaryCommand, TpmNvReadCommand, TpmNvReadPublicCommand},
+    TpmBuffer, TpmNvIndex, TpmPermanent, TpmTransient,
+};
+
+/// `PolicySecret(TPM_RH_ENDORSEMENT)` with SHA-256, the policy of the low
+/// range templates.
+pub const EK_POLICY_A_SHA256: [u8; 32] = [
+    0x83, 0x71, 0x97, 0x67, 0x44, 0x84, 0xb3, 0xf8, 0x1a, 0x90, 0xcc, 0x8d, 0x46, 0xa5, 0xd7, 0x24,
+    0xfd, 0x52, 0xd7, 0x6e, 0x06, 0x52, 0x0b, 0x64, 0xf2, 0xa1, 0xda, 0x1b, 0x33, 0x14, 0x69, 0xaa,
+];
+
+/// `PolicyOR(PolicyA, PolicyB)` with SHA-384, the policy of the SHA-384 high
+/// range templates. `PolicyB` is `PolicyAuthorizeNV` of the EK policy index.
+pub const EK_POLICY_C_SHA384: [u8; 48] = [
+    0xb2, 0x6e, 0x7d, 0x28, 0xd1, 0x1a, 0x50, 0xbc, 0x53, 0xd8, 0x82, 0xbc, 0xf5, 0xfd, 0x3a, 0x1a,
+    0x07, 0x41, 0x48, 0xbb, 0x35, 0xd3, 0xb4, 0xe4, 0xcb, 0x1c, 0x0a, 0xd9, 0xbd, 0xe4, 0x19, 0xca,
+    0xcb, 0x47, 0xba, 0x09, 0x69, 0x96, 0x46, 0x15, 0x0f, 0x9f, 0xc0, 0x00, 0xf3, 0xf8, 0x0e, 0x12,
+];
+
+pub const TCG_AT_TPM_MANUFACTURER: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.23.133.2.1");
+pub const TCG_AT_TPM_MODEL: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.23.133.2.2");
+pub const TCG_AT_TPM_VERSION: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.23.133.2.3");
+
+const MAX_CHAIN_DEPTH: usize = 8;
+
+/// An EK template of the TCG EK Credential Profile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EkTemplate {
+    /// Template L-1: RSA 2048 with SHA-256.
+    #[default]
+    Rsa2048,
+    /// Template L-2: ECC NIST P-256 with SHA-256.
+    EccNistP256,
+    /// Template H-6: RSA 3072 with SHA-384.
+    Rsa3072,
+    /// Template H-3: ECC NIST P-384 with SHA-384.
+    EccNistP384,
+}
+
+impl EkTemplate {
+    pub const ALL: [Self; 4] = [
+        Self::Rsa2048,
+        Self::EccNistP256,
+        Self::Rsa3072,
+        Self::EccNistP384,
+    ];
+
+    /// Returns the NV index of the EK certificate for this template.
+    #[must_use]
+    pub fn certificate_index(self) -> u32 {
+        match self {
+            Self::Rsa2048 => 0x01C0_0002,
+            Self::EccNistP256 => 0x01C0_000A,
+            Self::Rsa3072 => 0x01C0_001C,
+            Self::EccNistP384 => 0x01C0_0016,
+        }
+    }
+
+    /// Returns the public template. The low range templates have a zero
+    /// filled unique field, and the high range templates an empty one.
+    #[must_use]
+    pub fn public(self) -> TpmtPublic {
+        let low_range = matches!(self, Self::Rsa2048 | Self::EccNistP256);
+        let (name_alg, aes_bits, auth_policy): (_, _, &[u8]) = if low_range {
+            (TpmAlgId::Sha256, 128, &EK_POLICY_A_SHA256)
+        } else {
+            (TpmAlgId::Sha384, 256, &EK_POLICY_C_SHA384)
+        };
+        let mut object_attributes = TpmaObject::FIXED_TPM
+            | TpmaObject::FIXED_PARENT
+            | TpmaObject::SENSITIVE_DATA_ORIGIN
+            | TpmaObject::ADMIN_WITH_POLICY
+            | TpmaObject::RESTRICTED
+            | TpmaObject::DECRYPT;
+        if !low_range {
+            object_attributes |= TpmaObject::USER_WITH_AUTH;
+        }
+        let symmetric = TpmtSymDefObject {
+            algorithm: TpmAlgId::Aes,
+            key_bits: TpmuSymKeyBits::Aes(aes_bits),
+            mode: TpmuSymMode::Aes(TpmAlgId::Cfb),
+        };
+
+        let (object_type, parameters, unique) = match self {
+            Self::Rsa2048 | Self::Rsa3072 => {
+                let key_bits = if low_range { 2048 } else { 3072 };
+                let unique = if low_range {
+                    TpmBuffer::try_from([0; 256].as_slice()).unwrap_or_default()
+                } else {
+                    TpmBuffer::default()
+                };
+                (
+                    TpmAlgId::Rsa,
+                    TpmuPublicParms::Rsa {
+                        symmetric,
+                        scheme: TpmtScheme::default(),
+                        key_bits,
+                        exponent: 0,
+                    },
+                    TpmuPublicId::Rsa(unique),
+                )
+            }
+            Self::EccNistP256 | Self::EccNistP384 => {
+                let (curve_id, unique) = if low_range {
+                    let zero = Tpm2bEccParameter::try_from([0; 32].as_slice()).unwrap_or_default();
+                    (TpmEccCurve::NistP256, TpmsEccPoint { x: zero, y: zero })
+                } else {
+                    (TpmEccCurve::NistP384, TpmsEccPoint::default())
+                };
+                (
+                    TpmAlgId::Ecc,
+                    TpmuPublicParms::Ecc {
+                        symmetric,
+                        scheme: TpmtScheme::default(),
+                        curve_id,
+                        kdf: TpmtKdfScheme::default(),
+                    },
+                    TpmuPublicId::Ecc(unique),
+                )
+            }
+        };
+
+        TpmtPublic {
+            object_type,
+            name_alg,
+            object_attributes,
+            auth_policy: Tpm2bDigest::try_from(auth_policy).unwrap_or_default(),
+            parameters,
+            unique,
+        }
+    }
+}
+
+impl FromStr for EkTemplate {
+    type Err = TpmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|template| template.to_string() == s)
+            .ok_or_else(|| TpmError::Execution(format!("invalid EK template: {s}")))
+    }
+}
+
+impl fmt::Display for EkTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Rsa2048 => "rsa2048",
+            Self::EccNistP256 => "ecc-nist-p256",
+            Self::Rsa3072 => "rsa3072",
+            Self::EccNistP384 => "ecc-nist-p384",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Returns `true` if the USER role of the object can be authorized only with
+/// `TPM2_PolicySecret` of the endorsement hierarchy, as with the low range
+/// EK templates.
+#[must_use]
+pub fn requires_endorsement_policy(public: &TpmtPublic) -> bool {
+    !public
+        .object_attributes
+        .contains(TpmaObject::USER_WITH_AUTH)
+        && &*public.auth_policy == EK_POLICY_A_SHA256.as_slice()
+}
+
+/// Creates the EK of `template` under the endorsement hierarchy.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if `TPM2_CreatePrimary` fails.
+pub fn create_ek(
+    chip: &mut TpmDevice,
+    template: EkTemplate,
+    session: Option<&AuthSession>,
+    endorsement_auth: Option<&str>,
+    log_format: cli::LogFormat,
+) -> Result<(TpmTransient, TpmtPublic), TpmError> {
+    let cmd = TpmCreatePrimaryCommand {
+        in_sensitive: Tpm2bSensitiveCreate {
+            inner: TpmsSensitiveCreate {
+                user_auth: Tpm2bAuth::default(),
+                data: Tpm2bSensitiveData::default(),
+            },
+        },
+        in_public: Tpm2bPublic::from(template.public()),
+        outside_info: Tpm2b::default(),
+        creation_pcr: TpmlPcrSelection::default(),
+    };
+    let handles = [TpmPermanent::ENDORSEMENT.into()];
+    let sessions = get_auth_sessions(chip, &cmd, &handles, session, endorsement_auth, log_format)?;
+    let (resp, _) = chip.execute(&cmd, &handles, &sessions, log_format)?;
+    chip.register_object(resp.object_handle.into(), &resp.out_public.inner);
+    Ok((resp.object_handle, resp.out_public.inner))
+}
+
+/// Returns the size of the DER encoded element at the start of `data`.
+fn der_length(data: &[u8]) -> Option<usize> {
+    let first = usize::from(*data.get(1)?);
+    if first < 0x80 {
+        return Some(2 + first);
+    }
+    let count = first & 0x7f;
+    if count == 0 || count > 4 {
+        return None;
+    }
+    let len = data
+        .get(2..2 + count)?
+        .iter()
+        .fold(0, |len, &byte| (len << 8) | usize::from(byte));
+    Some(2 + count + len)
+}
+
+/// Reads the contents of an NV index, authorizing with the index itself.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if a TPM command fails.
+pub fn read_nv(
+    chip: &mut TpmDevice,
+    index: TpmNvIndex,
+    session: Option<&AuthSession>,
+    log_format: cli::LogFormat,
+) -> Result<Vec<u8>, TpmError> {
+    let (resp, _) = chip.execute(&TpmNvReadPublicCommand {}, &[index.into()], &[], log_format)?;
+    let size = resp.nv_public.inner.data_size;
+
+    let caps = chip.get_capability(
+        tpm2_protocol::data::TpmCap::TpmProperties,
+        TPM_PT_NV_BUFFER_MAX,
+        1,
+        log_format,
+    )?;
+    let buffer_max = caps
+        .iter()
+        .find_map(|cap| match &cap.data {
+            TpmuCapabilities::TpmProperties(props) => props
+                .iter()
+                .find(|prop| prop.property == TPM_PT_NV_BUFFER_MAX)
+                .and_then(|prop| usize::try_from(prop.value).ok()),
+            _ => None,
+        })
+        .unwrap_or(MAX_NV_BUFFER_SIZE)
+        .clamp(1, MAX_NV_BUFFER_SIZE);
+    let buffer_max = u16::try_from(buffer_max).unwrap_or(u16::MAX);
+
+    let mut data = Vec::with_capacity(usize::from(size));
+    let mut offset = 0;
+    while offset < size {
+        let cmd = TpmNvReadCommand {
+            size: (size - offset).min(buffer_max),
+            offset,
+        };
+        let handles = [index.into(), index.into()];
+        let sessions = get_auth_sessions(chip, &cmd, &handles, session, None, log_format)?;
+        let (resp, _) = chip.execute(&cmd, &handles, &sessions, log_format)?;
+        if resp.data.is_empty() {
+            return Err(TpmError::Execution(format!(
+                "NV index {index:#010x} returned no data at offset {offset}"
+            )));
+        }
+        data.extend_from_slice(&resp.data);
+        offset = offset.saturating_add(u16::try_from(resp.data.len()).unwrap_or(u16::MAX));
+    }
+    Ok(data)
+}
+
+/// Reads the EK certificate of `template` from its NV index and returns it in
+/// DER, without the padding some manufacturers leave after the certificate.
+///
+/// Returns `None` if the index is not defined.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if a TPM command fails, or the index does not contain
+/// a DER encoded certificate.
+pub fn read_ek_certificate(
+    chip: &mut TpmDevice,
+    template: EkTemplate,
+    session: Option<&AuthSession>,
+    log_format: cli::LogFormat,
+) -> Result<Option<Vec<u8>>, TpmError> {
+    let index = template.certificate_index();
+    let mut data = match read_nv(chip, TpmNvIndex::try_from(index)?, session, log_format) {
+        Ok(data) => data,
+        Err(TpmError::TpmRc(rc)) if rc.base() == Ok(TpmRcBase::Handle) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let len = der_length(&data)
+        .filter(|&len| data.first() == Some(&0x30) && len <= data.len())
+        .ok_or_else(|| {
+            TpmError::Parse(format!(
+                "NV index {index:#010x} does not contain a DER certificate"
+            ))
+        })?;
+    data.truncate(len);
+    Ok(Some(data))
+}
+
+/// Encodes a DER certificate as PEM.
+#[must_use]
+pub fn certificate_to_pem(der: &[u8]) -> String {
+    pem::encode(&pem::Pem::new("CERTIFICATE", der))
+}
+
+/// The TPM described by a verified EK certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EkCertificateInfo {
+    /// The TCG vendor ID, e.g. `id:49465800`.
+    pub manufacturer: String,
+    pub model: String,
+    pub version: String,
+    /// The subjects from the EK certificate up to the root CA.
+    pub chain: Vec<String>,
+}
+
+/// Loads the certificates in the PEM or DER files of a directory. Files that
+/// do not contain certificates are skipped.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if the directory cannot be read.
+pub fn load_ca_directory(path: &Path) -> Result<Vec<Certificate>, TpmError> {
+    let file_error = |e| TpmError::File(path.display().to_string(), e);
+    let mut paths = fs::read_dir(path)
+        .map_err(file_error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(file_error)?;
+    paths.sort();
+
+    let mut certificates = Vec::new();
+    for path in paths.iter().filter(|path| path.is_file()) {
+        let data = fs::read(path).map_err(|e| TpmError::File(path.display().to_string(), e))?;
+        match Certificate::parse_all(&data) {
+            Ok(parsed) => certificates.extend(parsed),
+            Err(err) => tracing::debug!(path = %path.display(), "skipping: {err}"),
+        }
+    }
+    Ok(certificates)
+}
+
+/// Returns the TPM manufacturer, model and version from the directory name
+/// in the subject alternative name of an EK certificate.
+fn tpm_identity(cert: &Certificate) -> Result<(String, String, String), TpmError> {
+    let attributes = cert.alt_name_attributes()?;
+    let find = |oid: ObjectIdentifier, name: &str| {
+        attributes
+            .iter()
+            .find(|(attribute, _)| *attribute == oid)
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| {
+                TpmError::Execution(format!(
+                    "EK certificate has no TPM {name} in the subject alternative name"
+                ))
+            })
+    };
+    let manufacturer = find(TCG_AT_TPM_MANUFACTURER, "manufacturer")?;
+    let model = find(TCG_AT_TPM_MODEL, "model")?;
+    let version = find(TCG_AT_TPM_VERSION, "version")?;
+    let vendor_id = manufacturer.strip_prefix("id:").unwrap_or_default();
+    if vendor_id.len() != 8 || !vendor_id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(TpmError::Execution(format!(
+            "invalid TPM manufacturer in the EK certificate: {manufacturer}"
+        )));
+    }
+    Ok((manufacturer, model, version))
+}
+
+/// Checks that `issuer` can have issued `cert`: it must be a CA that may sign
+/// certificates, allow `intermediates` CA certificates below it in the chain,
+/// be valid at `now`, have no unknown critical extensions, and its key must
+/// verify the signature of `cert`.
+fn check_issuer(
+    cert: &Certificate,
+    issuer: &Certificate,
+    now: Duration,
+    intermediates: usize,
+) -> Result<(), TpmError> {
+    let subject = issuer.subject_string();
+    let Some(constraints) = issuer
+        .basic_constraints()?
+        .filter(|constraints| constraints.ca)
+    else {
+        return Err(TpmError::Execution(format!(
+            "issuer is not a CA: {subject}"
+        )));
+    };
+    if !issuer.can_sign_certificates()? {
+        return Err(TpmError::Execution(format!(
+            "CA key usage does not allow signing certificates: {subject}"
+        )));
+    }
+    if constraints
+        .path_len_constraint
+        .is_some_and(|len| intermediates > usize::from(len))
+    {
+        return Err(TpmError::Execution(format!(
+            "CA path length constraint exceeded: {subject}"
+        )));
+    }
+    if !issuer.is_valid_at(now) {
+        return Err(TpmError::Execution(format!(
+            "CA certificate is not valid at this time: {subject}"
+        )));
+    }
+    issuer.check_critical_extensions()?;
+    cert.verify_signed_by(issuer)
+}
+
+/// Finds the issuer of `cert` among the certificates in `ca` that pass
+/// `check_issuer`. If there are candidates with the right name but none of
+/// them passes, the first rejection is returned.
+fn find_issuer<'a>(
+    cert: &Certificate,
+    ca: &'a [Certificate],
+    now: Duration,
+    intermediates: usize,
+) -> Result<&'a Certificate, TpmError> {
+    let mut rejection = None;
+    for issuer in ca.iter().filter(|issuer| issuer.subject() == cert.issuer()) {
+        match check_issuer(cert, issuer, now, intermediates) {
+            Ok(()) => return Ok(issuer),
+            Err(err) => {
+                rejection.get_or_insert(err);
+            }
+        }
+    }
+    Err(rejection.unwrap_or_else(|| {
+        TpmError::Execution(format!(
+            "no trusted issuer for '{}': {}",
+            cert.subject_string(),
+            cert.issuer_string()
+        ))
+    }))
+}
+
+/// Verifies an EK certificate offline.
+///
+/// The certificate must chain up to a self-signed CA certificate in `ca`,
+/// all certificates must be valid at `now` and have no unknown critical
+/// extensions, the issuers must be CAs that may sign certificates within
+/// their path length constraints, the subject alternative name must
+/// identify the TPM, and the certificate key must be the key of
+/// `ek_public`, which in turn must have been created from one of the EK
+/// templates.
+///
+/// # Errors
+///
+/// Returns a `TpmError` describing the first check that fails.
+pub fn verify_ek_certificate(
+    cert: &Certificate,
+    ca: &[Certificate],
+    ek_public: &TpmtPublic,
+    now: Duration,
+) -> Result<EkCertificateInfo, TpmError> {
+    if !EkTemplate::ALL.into_iter().any(|template| {
+        let mut public = template.public();
+        public.unique = ek_public.unique.clone();
+        public == *ek_public
+    }) {
+        return Err(TpmError::Execution(
+            "EK public area does not match any EK template".to_string(),
+        ));
+    }
+    if !same_public_key(&cert.public_key()?, ek_public) {
+        return Err(TpmError::Execution(
+            "EK certificate key does not match the EK".to_string(),
+        ));
+    }
+    if !cert.is_valid_at(now) {
+        return Err(TpmError::Execution(
+            "EK certificate is not valid at this time".to_string(),
+        ));
+    }
+    cert.check_critical_extensions()?;
+    let (manufacturer, model, version) = tpm_identity(cert)?;
+
+    let mut chain = vec![cert.subject_string()];
+    let mut current = cert;
+    for intermediates in 0..MAX_CHAIN_DEPTH {
+        let issuer = find_issuer(current, ca, now, intermediates)?;
+        chain.push(issuer.subject_string());
+        if issuer.is_self_issued() {
+            issuer.verify_signed_by(issuer)?;
+            return Ok(EkCertificateInfo {
+                manufacturer,
+                model,
+                version,
+                chain,
+            });
+        }
+        current = issuer;
+    }
+    Err(TpmError::Execution(format!(
+        "EK certificate chain is longer than {MAX_CHAIN_DEPTH} certificates"
+    )))
+}
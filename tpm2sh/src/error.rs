@@ -2,12 +2,12 @@
 // Copyright (c) 2025 Opinsys Oy
 
 use std::{error::Error, fmt, io::Error as IoError, num::ParseIntError};
-use tpm2_protocol::{data::TpmRc, TpmErrorKind};
+use tpm2_protocol::{data::TpmRc, TpmErrorKind, TpmProtocolError};
 
 #[derive(Debug)]
 pub enum TpmError {
     Base64(base64::DecodeError),
-    Build(TpmErrorKind),
+    Build(TpmProtocolError),
     Der(pkcs8::der::Error),
     Execution(String),
     File(String, IoError),
@@ -44,7 +44,7 @@ impl fmt::Display for TpmError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             TpmError::Base64(err) => write!(f, "Base64 decoding failed: {err}"),
-            TpmError::Build(err) => write!(f, "Failed to build TPM structure: {err}"),
+            TpmError::Build(err) => write!(f, "Failed to build or parse TPM structure: {err}"),
             TpmError::Der(err) => write!(f, "DER encoding/decoding failed: {err}"),
             TpmError::Execution(reason) => write!(f, "Execution failed: {reason}"),
             TpmError::File(path, err) => write!(f, "File operation failed on '{path}': {err}"),
@@ -91,6 +91,12 @@ impl From<ParseIntError> for TpmError {
 
 impl From<TpmErrorKind> for TpmError {
     fn from(err: TpmErrorKind) -> Self {
+        TpmError::Build(err.into())
+    }
+}
+
+impl From<TpmProtocolError> for TpmError {
+    fn from(err: TpmProtocolError) -> Self {
         TpmError::Build(err)
     }
 }
@@ -0,0 +1,663 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! A parser for the TCG PC Client crypto-agile event log, as exported by
+//! Linux in `/sys/kernel/security/tpm0/binary_bios_measurements`, and a replay
+//! of the log into PCR values.
+
+use crate::{formats::PcrOutput, tpm_alg_id_to_str, SoftwareHash, TpmError};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use tpm2_protocol::{
+    data::{TpmAlgId, TpmtHa, TpmuHa},
+    hash::TpmHashProvider,
+    tpm_hash_size, TpmParseTagged,
+};
+
+/// The event log of the first TPM as exported by Linux.
+pub const BINARY_BIOS_MEASUREMENTS: &str = "/sys/kernel/security/tpm0/binary_bios_measurements";
+
+pub const EV_PREBOOT_CERT: u32 = 0x0000_0000;
+pub const EV_POST_CODE: u32 = 0x0000_0001;
+pub const EV_NO_ACTION: u32 = 0x0000_0003;
+pub const EV_SEPARATOR: u32 = 0x0000_0004;
+pub const EV_ACTION: u32 = 0x0000_0005;
+pub const EV_S_CRTM_VERSION: u32 = 0x0000_0008;
+pub const EV_IPL: u32 = 0x0000_000d;
+pub const EV_EFI_VARIABLE_DRIVER_CONFIG: u32 = 0x8000_0001;
+pub const EV_EFI_VARIABLE_BOOT: u32 = 0x8000_0002;
+pub const EV_EFI_BOOT_SERVICES_APPLICATION: u32 = 0x8000_0003;
+pub const EV_EFI_BOOT_SERVICES_DRIVER: u32 = 0x8000_0004;
+pub const EV_EFI_RUNTIME_SERVICES_DRIVER: u32 = 0x8000_0005;
+pub const EV_EFI_GPT_EVENT: u32 = 0x8000_0006;
+pub const EV_EFI_ACTION: u32 = 0x8000_0007;
+pub const EV_EFI_VARIABLE_BOOT2: u32 = 0x8000_000c;
+pub const EV_EFI_VARIABLE_AUTHORITY: u32 = 0x8000_00e0;
+
+const EVENT_TYPES: &[(u32, &str)] = &[
+    (EV_PREBOOT_CERT, "EV_PREBOOT_CERT"),
+    (EV_POST_CODE, "EV_POST_CODE"),
+    (0x0000_0002, "EV_UNUSED"),
+    (EV_NO_ACTION, "EV_NO_ACTION"),
+    (EV_SEPARATOR, "EV_SEPARATOR"),
+    (EV_ACTION, "EV_ACTION"),
+    (0x0000_0006, "EV_EVENT_TAG"),
+    (0x0000_0007, "EV_S_CRTM_CONTENTS"),
+    (EV_S_CRTM_VERSION, "EV_S_CRTM_VERSION"),
+    (0x0000_0009, "EV_CPU_MICROCODE"),
+    (0x0000_000a, "EV_PLATFORM_CONFIG_FLAGS"),
+    (0x0000_000b, "EV_TABLE_OF_DEVICES"),
+    (0x0000_000c, "EV_COMPACT_HASH"),
+    (EV_IPL, "EV_IPL"),
+    (0x0000_000e, "EV_IPL_PARTITION_DATA"),
+    (0x0000_000f, "EV_NONHOST_CODE"),
+    (0x0000_0010, "EV_NONHOST_CONFIG"),
+    (0x0000_0011, "EV_NONHOST_INFO"),
+    (0x0000_0012, "EV_OMIT_BOOT_DEVICE_EVENTS"),
+    (0x0000_0013, "EV_POST_CODE2"),
+    (0x8000_0000, "EV_EFI_EVENT_BASE"),
+    (
+        EV_EFI_VARIABLE_DRIVER_CONFIG,
+        "EV_EFI_VARIABLE_DRIVER_CONFIG",
+    ),
+    (EV_EFI_VARIABLE_BOOT, "EV_EFI_VARIABLE_BOOT"),
+    (
+        EV_EFI_BOOT_SERVICES_APPLICATION,
+        "EV_EFI_BOOT_SERVICES_APPLICATION",
+    ),
+    (EV_EFI_BOOT_SERVICES_DRIVER, "EV_EFI_BOOT_SERVICES_DRIVER"),
+    (
+        EV_EFI_RUNTIME_SERVICES_DRIVER,
+        "EV_EFI_RUNTIME_SERVICES_DRIVER",
+    ),
+    (EV_EFI_GPT_EVENT, "EV_EFI_GPT_EVENT"),
+    (EV_EFI_ACTION, "EV_EFI_ACTION"),
+    (0x8000_0008, "EV_EFI_PLATFORM_FIRMWARE_BLOB"),
+    (0x8000_0009, "EV_EFI_HANDOFF_TABLES"),
+    (0x8000_000a, "EV_EFI_PLATFORM_FIRMWARE_BLOB2"),
+    (0x8000_000b, "EV_EFI_HANDOFF_TABLES2"),
+    (EV_EFI_VARIABLE_BOOT2, "EV_EFI_VARIABLE_BOOT2"),
+    (0x8000_000d, "EV_EFI_GPT_EVENT2"),
+    (0x8000_0010, "EV_EFI_HCRTM_EVENT"),
+    (EV_EFI_VARIABLE_AUTHORITY, "EV_EFI_VARIABLE_AUTHORITY"),
+    (0x8000_00e1, "EV_EFI_SPDM_FIRMWARE_BLOB"),
+    (0x8000_00e2, "EV_EFI_SPDM_FIRMWARE_CONFIG"),
+];
+
+/// Event types whose digest is the digest of the event data, which allows
+/// checking the data against the digest.
+const DATA_DIGEST_EVENTS: &[u32] = &[
+    EV_SEPARATOR,
+    EV_ACTION,
+    EV_EFI_ACTION,
+    EV_EFI_VARIABLE_DRIVER_CONFIG,
+    EV_EFI_GPT_EVENT,
+];
+
+const SPEC_ID_SIGNATURE: &[u8; 16] = b"Spec ID Event03\0";
+const STARTUP_LOCALITY_SIGNATURE: &[u8; 16] = b"StartupLocality\0";
+const EVENT_LOG: &str = "event log";
+const SHA1_DIGEST_SIZE: usize = 20;
+
+/// Returns the name of an event type, such as `EV_SEPARATOR`.
+#[must_use]
+pub fn tcg_event_type_name(event_type: u32) -> Option<&'static str> {
+    EVENT_TYPES
+        .iter()
+        .find(|(value, _)| *value == event_type)
+        .map(|(_, name)| *name)
+}
+
+/// The `TCG_EfiSpecIDEvent` in the header of a crypto-agile event log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcgSpecIdEvent {
+    pub platform_class: u32,
+    pub spec_version_minor: u8,
+    pub spec_version_major: u8,
+    pub spec_errata: u8,
+    pub uintn_size: u8,
+    /// The algorithm identifiers and digest sizes of the digests in events.
+    pub digest_sizes: Vec<(u16, u16)>,
+    pub vendor_info: Vec<u8>,
+}
+
+/// A `TCG_PCR_EVENT2`. Digests of algorithms without a `TpmuHa` variant are
+/// skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcgPcrEvent {
+    pub pcr_index: u32,
+    pub event_type: u32,
+    pub digests: Vec<TpmtHa>,
+    pub data: Vec<u8>,
+}
+
+/// A parsed event log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcgEventLog {
+    pub spec_id: TcgSpecIdEvent,
+    pub events: Vec<TcgPcrEvent>,
+}
+
+/// The event data of the event types tpm2sh understands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TcgEventData {
+    Text(String),
+    Separator(u32),
+    StartupLocality(u8),
+    /// `UEFI_VARIABLE_DATA`.
+    EfiVariable {
+        guid: String,
+        name: String,
+        data: Vec<u8>,
+    },
+    /// `UEFI_IMAGE_LOAD_EVENT`.
+    EfiImageLoad {
+        location: u64,
+        length: u64,
+        link_time_address: u64,
+        device_path: Vec<u8>,
+    },
+    Raw(Vec<u8>),
+}
+
+/// A little-endian reader that reports the offset of truncated fields.
+pub(crate) struct LogReader<'a> {
+    name: &'static str,
+    buf: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> LogReader<'a> {
+    pub(crate) fn new(name: &'static str, buf: &'a [u8]) -> Self {
+        Self {
+            name,
+            buf,
+            offset: 0,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.offset >= self.buf.len()
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize, field: &str) -> Result<&'a [u8], TpmError> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|&end| end <= self.buf.len());
+        let Some(end) = end else {
+            return Err(TpmError::Parse(format!(
+                "{} truncated: {field} at offset {}",
+                self.name, self.offset
+            )));
+        };
+        let bytes = &self.buf[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self, field: &str) -> Result<[u8; N], TpmError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.bytes(N, field)?);
+        Ok(out)
+    }
+
+    fn u8(&mut self, field: &str) -> Result<u8, TpmError> {
+        Ok(self.array::<1>(field)?[0])
+    }
+
+    fn u16(&mut self, field: &str) -> Result<u16, TpmError> {
+        Ok(u16::from_le_bytes(self.array(field)?))
+    }
+
+    pub(crate) fn u32(&mut self, field: &str) -> Result<u32, TpmError> {
+        Ok(u32::from_le_bytes(self.array(field)?))
+    }
+
+    fn u64(&mut self, field: &str) -> Result<u64, TpmError> {
+        Ok(u64::from_le_bytes(self.array(field)?))
+    }
+
+    /// Reads a byte array preceded by its size.
+    pub(crate) fn sized(&mut self, size: u64, field: &str) -> Result<&'a [u8], TpmError> {
+        let size = usize::try_from(size).map_err(|_| {
+            TpmError::Parse(format!(
+                "{}: {field} too large at offset {}",
+                self.name, self.offset
+            ))
+        })?;
+        self.bytes(size, field)
+    }
+}
+
+/// Creates a `TpmtHa` from a digest.
+pub(crate) fn tpmt_ha(alg: TpmAlgId, digest: &[u8]) -> Result<TpmtHa, TpmError> {
+    let (digest, _) = TpmuHa::parse_tagged(alg, digest)?;
+    Ok(TpmtHa {
+        hash_alg: alg,
+        digest,
+    })
+}
+
+fn parse_spec_id(data: &[u8]) -> Result<TcgSpecIdEvent, TpmError> {
+    let mut reader = LogReader::new(EVENT_LOG, data);
+    if reader.bytes(SPEC_ID_SIGNATURE.len(), "signature")? != SPEC_ID_SIGNATURE {
+        return Err(TpmError::Parse(
+            "event log is not in the crypto-agile format".to_string(),
+        ));
+    }
+    let platform_class = reader.u32("platformClass")?;
+    let spec_version_minor = reader.u8("specVersionMinor")?;
+    let spec_version_major = reader.u8("specVersionMajor")?;
+    let spec_errata = reader.u8("specErrata")?;
+    let uintn_size = reader.u8("uintnSize")?;
+    let count = reader.u32("numberOfAlgorithms")?;
+    let mut digest_sizes = Vec::new();
+    for _ in 0..count {
+        let alg = reader.u16("algorithmId")?;
+        let size = reader.u16("digestSize")?;
+        digest_sizes.push((alg, size));
+    }
+    let vendor_info_size = reader.u8("vendorInfoSize")?;
+    let vendor_info = reader
+        .bytes(vendor_info_size.into(), "vendorInfo")?
+        .to_vec();
+    Ok(TcgSpecIdEvent {
+        platform_class,
+        spec_version_minor,
+        spec_version_major,
+        spec_errata,
+        uintn_size,
+        digest_sizes,
+        vendor_info,
+    })
+}
+
+/// Parses a crypto-agile event log.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Parse` naming the field and offset if the log is
+/// truncated or malformed, or if it is in the SHA-1 only format.
+pub fn parse_event_log(buf: &[u8]) -> Result<TcgEventLog, TpmError> {
+    let mut reader = LogReader::new(EVENT_LOG, buf);
+    let _pcr_index = reader.u32("pcrIndex")?;
+    if reader.u32("eventType")? != EV_NO_ACTION {
+        return Err(TpmError::Parse(
+            "event log does not start with a Spec ID event".to_string(),
+        ));
+    }
+    reader.bytes(SHA1_DIGEST_SIZE, "digest")?;
+    let size = reader.u32("eventSize")?;
+    let spec_id = parse_spec_id(reader.sized(size.into(), "event")?)?;
+
+    let mut events = Vec::new();
+    while !reader.is_empty() {
+        let pcr_index = reader.u32("pcrIndex")?;
+        let event_type = reader.u32("eventType")?;
+        let count = reader.u32("digests.count")?;
+        let mut digests = Vec::new();
+        for _ in 0..count {
+            let offset = reader.offset;
+            let alg = reader.u16("digests.hashAlg")?;
+            let Some(&(_, size)) = spec_id.digest_sizes.iter().find(|(id, _)| *id == alg) else {
+                return Err(TpmError::Parse(format!(
+                    "event log: unknown digest algorithm {alg:#06x} at offset {offset}"
+                )));
+            };
+            let digest = reader.bytes(size.into(), "digests.digest")?;
+            if let Ok(alg) = TpmAlgId::try_from(alg) {
+                if tpm_hash_size(&alg) == Some(digest.len()) {
+                    digests.push(tpmt_ha(alg, digest)?);
+                }
+            }
+        }
+        let size = reader.u32("eventSize")?;
+        let data = reader.sized(size.into(), "event")?.to_vec();
+        events.push(TcgPcrEvent {
+            pcr_index,
+            event_type,
+            digests,
+            data,
+        });
+    }
+    Ok(TcgEventLog { spec_id, events })
+}
+
+/// Formats an `EFI_GUID`, whose first three fields are little-endian.
+fn format_guid(guid: &[u8; 16]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{}-{}",
+        u32::from_le_bytes([guid[0], guid[1], guid[2], guid[3]]),
+        u16::from_le_bytes([guid[4], guid[5]]),
+        u16::from_le_bytes([guid[6], guid[7]]),
+        hex::encode(&guid[8..10]),
+        hex::encode(&guid[10..])
+    )
+}
+
+fn utf16_le(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+/// Returns the data as text if it is printable UTF-8 or UTF-16LE.
+fn text(data: &[u8]) -> Option<String> {
+    let is_printable = |s: &str| !s.is_empty() && !s.chars().any(char::is_control);
+    if let Ok(s) = std::str::from_utf8(data) {
+        let s = s.trim_end_matches('\0');
+        if is_printable(s) {
+            return Some(s.to_string());
+        }
+    }
+    if data.len() % 2 == 0 && data.iter().skip(1).step_by(2).all(|&b| b == 0) {
+        let s = utf16_le(data);
+        if is_printable(&s) {
+            return Some(s);
+        }
+    }
+    None
+}
+
+fn decode_efi_variable(data: &[u8]) -> Result<TcgEventData, TpmError> {
+    let mut reader = LogReader::new(EVENT_LOG, data);
+    let guid = format_guid(&reader.array("VariableName")?);
+    let name_len = reader.u64("UnicodeNameLength")?;
+    let data_len = reader.u64("VariableDataLength")?;
+    let name = utf16_le(reader.sized(name_len.saturating_mul(2), "UnicodeName")?);
+    let data = reader.sized(data_len, "VariableData")?.to_vec();
+    Ok(TcgEventData::EfiVariable { guid, name, data })
+}
+
+fn decode_efi_image_load(data: &[u8]) -> Result<TcgEventData, TpmError> {
+    let mut reader = LogReader::new(EVENT_LOG, data);
+    let location = reader.u64("ImageLocationInMemory")?;
+    let length = reader.u64("ImageLengthInMemory")?;
+    let link_time_address = reader.u64("ImageLinkTimeAddress")?;
+    let path_len = reader.u64("LengthOfDevicePath")?;
+    let device_path = reader.sized(path_len, "DevicePath")?.to_vec();
+    Ok(TcgEventData::EfiImageLoad {
+        location,
+        length,
+        link_time_address,
+        device_path,
+    })
+}
+
+impl TcgPcrEvent {
+    /// Decodes the event data according to the event type. Data that cannot
+    /// be decoded is returned raw.
+    #[must_use]
+    pub fn decode(&self) -> TcgEventData {
+        let decoded = match self.event_type {
+            EV_NO_ACTION => self
+                .data
+                .strip_prefix(STARTUP_LOCALITY_SIGNATURE)
+                .and_then(|rest| rest.first())
+                .map(|&locality| Ok(TcgEventData::StartupLocality(locality))),
+            EV_SEPARATOR => <[u8; 4]>::try_from(self.data.as_slice())
+                .ok()
+                .map(|value| Ok(TcgEventData::Separator(u32::from_le_bytes(value)))),
+            EV_ACTION | EV_EFI_ACTION | EV_IPL | EV_POST_CODE | EV_S_CRTM_VERSION => {
+                text(&self.data).map(|text| Ok(TcgEventData::Text(text)))
+            }
+            EV_EFI_VARIABLE_DRIVER_CONFIG
+            | EV_EFI_VARIABLE_BOOT
+            | EV_EFI_VARIABLE_BOOT2
+            | EV_EFI_VARIABLE_AUTHORITY => Some(decode_efi_variable(&self.data)),
+            EV_EFI_BOOT_SERVICES_APPLICATION
+            | EV_EFI_BOOT_SERVICES_DRIVER
+            | EV_EFI_RUNTIME_SERVICES_DRIVER => Some(decode_efi_image_load(&self.data)),
+            _ => None,
+        };
+        match decoded {
+            Some(Ok(data)) => data,
+            _ => TcgEventData::Raw(self.data.clone()),
+        }
+    }
+
+    /// Returns the digest for `alg`.
+    #[must_use]
+    pub fn digest(&self, alg: TpmAlgId) -> Option<&TpmtHa> {
+        self.digests.iter().find(|digest| digest.hash_alg == alg)
+    }
+
+    /// Returns `false` if the event type defines the digest as the digest of
+    /// the event data, and some digest does not match the data.
+    #[must_use]
+    pub fn is_data_consistent(&self) -> bool {
+        if !DATA_DIGEST_EVENTS.contains(&self.event_type) {
+            return true;
+        }
+        self.digests.iter().all(|digest| {
+            let mut out = [0u8; 64];
+            match SoftwareHash.digest(digest.hash_alg, &[&self.data], &mut out) {
+                Ok(len) => out[..len] == *digest.digest,
+                Err(_) => true,
+            }
+        })
+    }
+
+    /// Converts the event into JSON, with `index` as its position in the log.
+    #[must_use]
+    pub fn to_json(&self, index: usize) -> Value {
+        let event_type = tcg_event_type_name(self.event_type)
+            .map_or_else(|| format!("{:#010x}", self.event_type), str::to_string);
+        let digests: Map<String, Value> = self
+            .digests
+            .iter()
+            .map(|digest| {
+                (
+                    tpm_alg_id_to_str(digest.hash_alg).to_string(),
+                    json!(hex::encode(&*digest.digest)),
+                )
+            })
+            .collect();
+        let data = match self.decode() {
+            TcgEventData::Text(text) => json!(text),
+            TcgEventData::Separator(value) => json!(format!("{value:#010x}")),
+            TcgEventData::StartupLocality(locality) => json!({ "startup_locality": locality }),
+            TcgEventData::EfiVariable { guid, name, data } => {
+                json!({ "guid": guid, "name": name, "data": hex::encode(data) })
+            }
+            TcgEventData::EfiImageLoad {
+                location,
+                length,
+                link_time_address,
+                device_path,
+            } => json!({
+                "location": format!("{location:#x}"),
+                "length": length,
+                "link_time_address": format!("{link_time_address:#x}"),
+                "device_path": hex::encode(device_path),
+            }),
+            TcgEventData::Raw(data) => json!(hex::encode(data)),
+        };
+        json!({
+            "event": index,
+            "pcr": self.pcr_index,
+            "type": event_type,
+            "digests": digests,
+            "data": data,
+        })
+    }
+}
+
+/// The values of a PCR during the replay of an event log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayedPcr {
+    pub initial: TpmtHa,
+    /// The index of each event extended into the PCR, and the PCR value after
+    /// it.
+    pub history: Vec<(usize, TpmtHa)>,
+}
+
+impl ReplayedPcr {
+    /// Returns the final value of the PCR.
+    #[must_use]
+    pub fn value(&self) -> &TpmtHa {
+        self.history
+            .last()
+            .map_or(&self.initial, |(_, value)| value)
+    }
+}
+
+/// A PCR whose value in the TPM differs from the replayed value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcrMismatch {
+    pub alg: TpmAlgId,
+    pub pcr: u32,
+    pub replayed: TpmtHa,
+    pub actual: String,
+    /// The index of the first event that does not agree with the TPM, if the
+    /// log explains the value in the TPM up to some event.
+    pub event: Option<usize>,
+}
+
+/// An event log replayed into the PCR banks listed in its header.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PcrReplay {
+    pub banks: BTreeMap<TpmAlgId, BTreeMap<u32, ReplayedPcr>>,
+}
+
+pub(crate) fn extend(value: &TpmtHa, digest: &TpmtHa) -> Result<TpmtHa, TpmError> {
+    let mut out = [0u8; 64];
+    let len = SoftwareHash.digest(value.hash_alg, &[&*value.digest, &*digest.digest], &mut out)?;
+    tpmt_ha(value.hash_alg, &out[..len])
+}
+
+impl PcrReplay {
+    /// Replays the events of `log`. Banks of algorithms that tpm2sh cannot
+    /// compute are skipped. `EV_NO_ACTION` events are not extended, but a
+    /// `StartupLocality` event sets the initial value of PCR 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError::Parse` if an event lacks the digest of a bank.
+    pub fn new(log: &TcgEventLog) -> Result<Self, TpmError> {
+        let locality = log.events.iter().find_map(|event| match event.decode() {
+            TcgEventData::StartupLocality(locality) if event.pcr_index == 0 => Some(locality),
+            _ => None,
+        });
+        let mut replay = Self::default();
+        for &(alg, _) in &log.spec_id.digest_sizes {
+            let Ok(alg) = TpmAlgId::try_from(alg) else {
+                continue;
+            };
+            let Some(size) = tpm_hash_size(&alg) else {
+                continue;
+            };
+            if SoftwareHash.digest(alg, &[], &mut [0u8; 64]).is_err() {
+                tracing::debug!(alg = tpm_alg_id_to_str(alg), "skipping bank");
+                continue;
+            }
+            let bank = replay.banks.entry(alg).or_default();
+            for (index, event) in log.events.iter().enumerate() {
+                if event.event_type == EV_NO_ACTION {
+                    continue;
+                }
+                let digest = event.digest(alg).ok_or_else(|| {
+                    TpmError::Parse(format!(
+                        "event {index} has no {} digest",
+                        tpm_alg_id_to_str(alg)
+                    ))
+                })?;
+                let pcr = match bank.entry(event.pcr_index) {
+                    std::collections::btree_map::Entry::Occupied(entry) => entry.into_mut(),
+                    std::collections::btree_map::Entry::Vacant(entry) => {
+                        let mut initial = vec![0u8; size];
+                        if event.pcr_index == 0 {
+                            initial[size - 1] = locality.unwrap_or(0);
+                        }
+                        entry.insert(ReplayedPcr {
+                            initial: tpmt_ha(alg, &initial)?,
+                            history: Vec::new(),
+                        })
+                    }
+                };
+                let value = extend(pcr.value(), digest)?;
+                pcr.history.push((index, value));
+            }
+        }
+        Ok(replay)
+    }
+
+    /// Returns the replayed PCR values in the format of `pcr-read`.
+    #[must_use]
+    pub fn to_pcr_output(&self) -> PcrOutput {
+        let banks = self
+            .banks
+            .iter()
+            .map(|(&alg, pcrs)| {
+                let pcrs = pcrs
+                    .iter()
+                    .map(|(index, pcr)| {
+                        (index.to_string(), hex::encode_upper(&*pcr.value().digest))
+                    })
+                    .collect();
+                (tpm_alg_id_to_str(alg).to_string(), pcrs)
+            })
+            .collect();
+        PcrOutput {
+            update_counter: 0,
+            banks,
+        }
+    }
+
+    /// Compares the replayed PCRs with `pcrs`, typically read from the TPM.
+    /// PCRs missing from either side are not compared.
+    ///
+    /// The event blamed for a mismatch is the first event of the PCR whose
+    /// data does not match its digest or, failing that, the first event after
+    /// the point where the replay last agreed with the TPM.
+    #[must_use]
+    pub fn compare(&self, log: &TcgEventLog, pcrs: &PcrOutput) -> Vec<PcrMismatch> {
+        let mut mismatches = Vec::new();
+        for (&alg, replayed) in &self.banks {
+            let Some(bank) = pcrs.banks.get(tpm_alg_id_to_str(alg)) else {
+                continue;
+            };
+            for (&index, pcr) in replayed {
+                let Some(actual) = bank.get(&index.to_string()) else {
+                    continue;
+                };
+                let matches =
+                    |value: &TpmtHa| actual.eq_ignore_ascii_case(&hex::encode(&*value.digest));
+                if matches(pcr.value()) {
+                    continue;
+                }
+                let inconsistent = pcr
+                    .history
+                    .iter()
+                    .map(|&(event, _)| event)
+                    .find(|&event| !log.events[event].is_data_consistent());
+                let agreed = pcr
+                    .history
+                    .iter()
+                    .rposition(|(_, value)| matches(value))
+                    .map(|position| position + 1)
+                    .or_else(|| matches(&pcr.initial).then_some(0));
+                let diverged = agreed
+                    .and_then(|position| pcr.history.get(position))
+                    .map(|&(event, _)| event);
+                mismatches.push(PcrMismatch {
+                    alg,
+                    pcr: index,
+                    replayed: *pcr.value(),
+                    actual: actual.clone(),
+                    event: match (inconsistent, diverged) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    },
+                });
+            }
+        }
+        mismatches.sort_by_key(|mismatch| (mismatch.event.unwrap_or(usize::MAX), mismatch.pcr));
+        mismatches
+    }
+}
@@ -17,3 +17,19 @@ impl PcrOutput {
         self.banks.values().all(BTreeMap::is_empty)
     }
 }
+
+/// A credential for `TPM2_ActivateCredential`: the `TPM2B_ID_OBJECT` and the
+/// `TPM2B_ENCRYPTED_SECRET` holding its protected seed, both hex encoded.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CredentialOutput {
+    pub blob: String,
+    pub secret: String,
+}
+
+/// A `TPM2_Quote` result: the signed `TPMS_ATTEST` and its `TPMT_SIGNATURE`,
+/// both hex encoded.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QuoteOutput {
+    pub attest: String,
+    pub signature: String,
+}
@@ -0,0 +1,193 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! Hashing data of any size with the TPM.
+
+use crate::{build_password_session, cli, get_auth_sessions, AuthSession, TpmDevice, TpmError};
+use tpm2_protocol::{
+    data::{
+        Tpm2b, Tpm2bAuth, Tpm2bMaxBuffer, TpmAlgId, TpmCap, TpmRh, TpmlDigestValues,
+        TpmtTkHashcheck, TpmuCapabilities, MAX_BUFFER_SIZE, TPM_PT_INPUT_BUFFER,
+    },
+    message::{
+        TpmEventSequenceCompleteCommand, TpmFlushContextCommand, TpmHashCommand,
+        TpmHashSequenceStartCommand, TpmPcrEventCommand, TpmSequenceCompleteCommand,
+        TpmSequenceUpdateCommand,
+    },
+    TpmPcr, TpmPermanent, TpmTransient,
+};
+
+/// Returns the largest buffer the TPM accepts as a command parameter, as
+/// reported by `TPM_PT_INPUT_BUFFER`.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if `TPM2_GetCapability` fails.
+fn input_buffer_size(chip: &mut TpmDevice, log_format: cli::LogFormat) -> Result<usize, TpmError> {
+    let caps = chip.get_capability(TpmCap::TpmProperties, TPM_PT_INPUT_BUFFER, 1, log_format)?;
+    Ok(caps
+        .iter()
+        .find_map(|cap| match &cap.data {
+            TpmuCapabilities::TpmProperties(props) => props
+                .iter()
+                .find(|prop| prop.property == TPM_PT_INPUT_BUFFER)
+                .and_then(|prop| usize::try_from(prop.value).ok()),
+            _ => None,
+        })
+        .unwrap_or(MAX_BUFFER_SIZE)
+        .clamp(1, MAX_BUFFER_SIZE))
+}
+
+/// Hashes `data` with the TPM and returns the digest and the hash check
+/// ticket for `hierarchy`. Data that does not fit into a single `TPM2_Hash`
+/// is hashed with a hash sequence, which is authorized with `session` or
+/// its empty password.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if a TPM command fails.
+pub fn hash_data(
+    chip: &mut TpmDevice,
+    data: &[u8],
+    hash_alg: TpmAlgId,
+    hierarchy: TpmRh,
+    session: Option<&AuthSession>,
+    log_format: cli::LogFormat,
+) -> Result<(Vec<u8>, TpmtTkHashcheck), TpmError> {
+    let buffer_max = input_buffer_size(chip, log_format)?;
+    if data.len() <= buffer_max {
+        let cmd = TpmHashCommand {
+            data: Tpm2bMaxBuffer::try_from(data)?,
+            hash_alg,
+            hierarchy,
+        };
+        let sessions = get_auth_sessions(chip, &cmd, &[], session, None, log_format)?;
+        let (resp, _) = chip.execute(&cmd, &[], &sessions, log_format)?;
+        return Ok((resp.out_hash.to_vec(), resp.validation));
+    }
+
+    let (sequence, last) = start_sequence(chip, data, hash_alg, buffer_max, session, log_format)?;
+    let cmd = TpmSequenceCompleteCommand {
+        buffer: Tpm2bMaxBuffer::try_from(last)?,
+        hierarchy,
+    };
+    let handles = [sequence.into()];
+    let sessions = match get_auth_sessions(chip, &cmd, &handles, session, None, log_format) {
+        Ok(sessions) => sessions,
+        Err(err) => {
+            flush_sequence(chip, sequence, log_format);
+            return Err(err);
+        }
+    };
+    match chip.execute(&cmd, &handles, &sessions, log_format) {
+        Ok((resp, _)) => Ok((resp.result.to_vec(), resp.validation)),
+        Err(err) => {
+            flush_sequence(chip, sequence, log_format);
+            Err(err)
+        }
+    }
+}
+
+/// Hashes `data` as an event with the algorithm of each active PCR bank and
+/// extends the digests to `pcr`, or only returns them if `pcr` is `None`.
+/// `pcr` is authorized with `session` or the password `pcr_auth`. Data that
+/// does not fit into a single `TPM2_PCR_Event` is hashed with an event
+/// sequence.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if a TPM command fails.
+pub fn hash_event(
+    chip: &mut TpmDevice,
+    pcr: Option<TpmPcr>,
+    data: &[u8],
+    session: Option<&AuthSession>,
+    pcr_auth: Option<&str>,
+    log_format: cli::LogFormat,
+) -> Result<TpmlDigestValues, TpmError> {
+    let pcr_handle = pcr.map_or(TpmPermanent::NULL.into(), Into::into);
+    let buffer_max = input_buffer_size(chip, log_format)?;
+
+    if data.len() <= buffer_max {
+        let cmd = TpmPcrEventCommand {
+            event_data: Tpm2b::try_from(data)?,
+        };
+        let handles = [pcr_handle];
+        let sessions = get_auth_sessions(chip, &cmd, &handles, session, pcr_auth, log_format)?;
+        let (resp, _) = chip.execute(&cmd, &handles, &sessions, log_format)?;
+        return Ok(resp.digests);
+    }
+
+    let (sequence, last) =
+        start_sequence(chip, data, TpmAlgId::Null, buffer_max, session, log_format)?;
+    let cmd = TpmEventSequenceCompleteCommand {
+        buffer: Tpm2bMaxBuffer::try_from(last)?,
+    };
+    let handles = [pcr_handle, sequence.into()];
+    let sessions = get_auth_sessions(chip, &cmd, &handles, session, pcr_auth, log_format).and_then(
+        |mut sessions| {
+            sessions.extend(build_password_session(Some(""))?);
+            Ok(sessions)
+        },
+    );
+    let sessions = match sessions {
+        Ok(sessions) => sessions,
+        Err(err) => {
+            flush_sequence(chip, sequence, log_format);
+            return Err(err);
+        }
+    };
+    match chip.execute(&cmd, &handles, &sessions, log_format) {
+        Ok((resp, _)) => Ok(resp.results),
+        Err(err) => {
+            flush_sequence(chip, sequence, log_format);
+            Err(err)
+        }
+    }
+}
+
+/// Starts a sequence with an empty authorization value and feeds it all but
+/// the last chunk of `data` in chunks of `buffer_max` bytes. The last chunk
+/// is returned for completing the sequence.
+fn start_sequence<'a>(
+    chip: &mut TpmDevice,
+    data: &'a [u8],
+    hash_alg: TpmAlgId,
+    buffer_max: usize,
+    session: Option<&AuthSession>,
+    log_format: cli::LogFormat,
+) -> Result<(TpmTransient, &'a [u8]), TpmError> {
+    let cmd = TpmHashSequenceStartCommand {
+        auth: Tpm2bAuth::default(),
+        hash_alg,
+    };
+    let sessions = get_auth_sessions(chip, &cmd, &[], session, None, log_format)?;
+    let (resp, _) = chip.execute(&cmd, &[], &sessions, log_format)?;
+    let sequence = resp.sequence_handle;
+
+    let split = (data.len() - 1) / buffer_max * buffer_max;
+    let (head, last) = data.split_at(split);
+    for chunk in head.chunks(buffer_max) {
+        let cmd = TpmSequenceUpdateCommand {
+            buffer: Tpm2bMaxBuffer::try_from(chunk)?,
+        };
+        let handles = [sequence.into()];
+        let result = get_auth_sessions(chip, &cmd, &handles, session, None, log_format)
+            .and_then(|sessions| chip.execute(&cmd, &handles, &sessions, log_format));
+        if let Err(err) = result {
+            flush_sequence(chip, sequence, log_format);
+            return Err(err);
+        }
+    }
+    Ok((sequence, last))
+}
+
+/// Flushes a sequence left behind by a failed command.
+fn flush_sequence(chip: &mut TpmDevice, sequence: TpmTransient, log_format: cli::LogFormat) {
+    let cmd = TpmFlushContextCommand {
+        flush_handle: sequence.into(),
+    };
+    if let Err(err) = chip.execute(&cmd, &[], &[], log_format) {
+        tracing::debug!(handle = %u32::from(sequence), "failed to flush the sequence: {err}");
+    }
+}
@@ -0,0 +1,434 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! A parser for the Linux IMA runtime measurement list, in both the binary
+//! and the ASCII format exported in `/sys/kernel/security/ima`, and a replay
+//! of the list into PCR values.
+
+use crate::{
+    event_log::{extend, tpmt_ha, LogReader, PcrMismatch, PcrReplay, ReplayedPcr},
+    formats::PcrOutput,
+    tpm_alg_id_to_str, SoftwareHash, TpmError,
+};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use tpm2_protocol::{
+    data::{TpmAlgId, TpmtHa},
+    hash::TpmHashProvider,
+    tpm_hash_size,
+};
+
+/// The binary measurement list as exported by Linux.
+pub const IMA_BINARY_RUNTIME_MEASUREMENTS: &str =
+    "/sys/kernel/security/ima/binary_runtime_measurements";
+
+/// The PCR used by IMA unless the policy says otherwise.
+pub const IMA_PCR: u32 = 10;
+
+/// The banks into which the measurement list is replayed.
+pub const IMA_BANKS: &[TpmAlgId] = &[
+    TpmAlgId::Sha1,
+    TpmAlgId::Sha256,
+    TpmAlgId::Sha384,
+    TpmAlgId::Sha512,
+];
+
+const MEASUREMENT_LIST: &str = "IMA measurement list";
+const BOOT_AGGREGATE: &str = "boot_aggregate";
+const TEMPLATE_IMA: &str = "ima";
+const TEMPLATE_DIGEST_SIZE: usize = 20;
+/// The size of the file name field hashed for the `ima` template.
+const IMA_EVENT_NAME_LEN: usize = 256;
+
+/// A single entry of the measurement list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImaEntry {
+    pub pcr: u32,
+    /// The SHA-1 digest of `template_data`, or zeros for a violation.
+    pub template_digest: [u8; TEMPLATE_DIGEST_SIZE],
+    pub template_name: String,
+    /// The template data in the form hashed into the template digest.
+    pub template_data: Vec<u8>,
+    /// The kernel name of the algorithm of `file_digest`, e.g. `sha256`.
+    pub file_hash_alg: String,
+    pub file_digest: Vec<u8>,
+    pub file_name: String,
+    /// The file signature of `ima-sig` or the buffer of `ima-buf`.
+    pub extra: Vec<u8>,
+}
+
+/// A parsed measurement list.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ImaMeasurementList {
+    pub entries: Vec<ImaEntry>,
+}
+
+/// Splits a `d-ng` field into the algorithm name and the digest.
+fn split_digest_ng(field: &[u8]) -> (String, Vec<u8>) {
+    match field.windows(2).position(|window| window == b":\0") {
+        Some(pos) => (
+            String::from_utf8_lossy(&field[..pos]).into_owned(),
+            field[pos + 2..].to_vec(),
+        ),
+        None => ("sha1".to_string(), field.to_vec()),
+    }
+}
+
+fn null_terminated(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// Builds the hashed template data of the `ima` template.
+fn ima_template_data(digest: &[u8], name: &str) -> Vec<u8> {
+    let mut data = digest.to_vec();
+    let mut name = name.as_bytes().to_vec();
+    name.resize(IMA_EVENT_NAME_LEN, 0);
+    data.extend_from_slice(&name);
+    data
+}
+
+/// Builds template data from fields, each preceded by its length.
+fn template_data(fields: &[&[u8]]) -> Result<Vec<u8>, TpmError> {
+    let mut data = Vec::new();
+    for field in fields {
+        let len = u32::try_from(field.len())
+            .map_err(|_| TpmError::Parse(format!("{MEASUREMENT_LIST}: field too large")))?;
+        data.extend_from_slice(&len.to_le_bytes());
+        data.extend_from_slice(field);
+    }
+    Ok(data)
+}
+
+fn parse_binary_entry(reader: &mut LogReader) -> Result<ImaEntry, TpmError> {
+    let pcr = reader.u32("PCR index")?;
+    let template_digest = reader.array("template digest")?;
+    let name_size = reader.u32("template name size")?;
+    let template_name =
+        String::from_utf8_lossy(reader.sized(name_size.into(), "template name")?).into_owned();
+
+    if template_name == TEMPLATE_IMA {
+        let file_digest = reader.bytes(TEMPLATE_DIGEST_SIZE, "file digest")?.to_vec();
+        let name_size = reader.u32("file name size")?;
+        let file_name = null_terminated(reader.sized(name_size.into(), "file name")?);
+        return Ok(ImaEntry {
+            pcr,
+            template_digest,
+            template_data: ima_template_data(&file_digest, &file_name),
+            template_name,
+            file_hash_alg: "sha1".to_string(),
+            file_digest,
+            file_name,
+            extra: Vec::new(),
+        });
+    }
+
+    let data_size = reader.u32("template data size")?;
+    let data = reader.sized(data_size.into(), "template data")?;
+    let mut fields = Vec::new();
+    let mut field_reader = LogReader::new(MEASUREMENT_LIST, data);
+    while !field_reader.is_empty() {
+        let size = field_reader.u32("template field size")?;
+        fields.push(field_reader.sized(size.into(), "template field")?);
+    }
+    let (file_hash_alg, file_digest) = fields
+        .first()
+        .map(|field| split_digest_ng(field))
+        .unwrap_or_default();
+    Ok(ImaEntry {
+        pcr,
+        template_digest,
+        template_name,
+        template_data: data.to_vec(),
+        file_hash_alg,
+        file_digest,
+        file_name: fields
+            .get(1)
+            .map(|field| null_terminated(field))
+            .unwrap_or_default(),
+        extra: fields
+            .get(2)
+            .map(|field| field.to_vec())
+            .unwrap_or_default(),
+    })
+}
+
+fn parse_ascii_entry(line: &str) -> Result<ImaEntry, String> {
+    let mut parts = line.splitn(5, ' ');
+    let mut next = |field: &str| parts.next().ok_or_else(|| format!("missing {field}"));
+    let pcr = next("PCR index")?
+        .parse()
+        .map_err(|_| "invalid PCR index".to_string())?;
+    let template_digest: [u8; TEMPLATE_DIGEST_SIZE] = hex::decode(next("template digest")?)
+        .ok()
+        .and_then(|digest| digest.try_into().ok())
+        .ok_or_else(|| "invalid template digest".to_string())?;
+    let template_name = next("template name")?.to_string();
+    let file_digest = next("file digest")?;
+    let rest = next("file name")?;
+
+    if template_name == TEMPLATE_IMA {
+        let file_digest = hex::decode(file_digest).map_err(|_| "invalid file digest")?;
+        return Ok(ImaEntry {
+            pcr,
+            template_digest,
+            template_data: ima_template_data(&file_digest, rest),
+            template_name,
+            file_hash_alg: "sha1".to_string(),
+            file_digest,
+            file_name: rest.to_string(),
+            extra: Vec::new(),
+        });
+    }
+
+    let (file_hash_alg, digest) = file_digest
+        .split_once(':')
+        .ok_or_else(|| "invalid file digest".to_string())?;
+    let digest = hex::decode(digest).map_err(|_| "invalid file digest")?;
+    let (file_name, extra) = match template_name.as_str() {
+        "ima-ng" => (rest, Vec::new()),
+        "ima-sig" | "ima-buf" => match rest.rsplit_once(' ') {
+            Some((name, extra)) => (
+                name,
+                hex::decode(extra).map_err(|_| format!("invalid {template_name} data"))?,
+            ),
+            None => (rest, Vec::new()),
+        },
+        _ => return Err(format!("unsupported template '{template_name}'")),
+    };
+
+    let mut digest_field = format!("{file_hash_alg}:").into_bytes();
+    digest_field.push(0);
+    digest_field.extend_from_slice(&digest);
+    let mut name_field = file_name.as_bytes().to_vec();
+    name_field.push(0);
+    let mut fields = vec![digest_field.as_slice(), name_field.as_slice()];
+    if template_name != "ima-ng" {
+        fields.push(&extra);
+    }
+    let template_data = template_data(&fields).map_err(|err| err.to_string())?;
+    Ok(ImaEntry {
+        pcr,
+        template_digest,
+        template_name,
+        template_data,
+        file_hash_alg: file_hash_alg.to_string(),
+        file_digest: digest,
+        file_name: file_name.to_string(),
+        extra,
+    })
+}
+
+/// Parses a measurement list. The ASCII format is detected from a leading
+/// decimal digit, as a binary list starts with a little-endian PCR index.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Parse` if the list is truncated or malformed.
+pub fn parse_ima_measurement_list(buf: &[u8]) -> Result<ImaMeasurementList, TpmError> {
+    let mut list = ImaMeasurementList::default();
+    if buf.first().is_some_and(u8::is_ascii_digit) {
+        let text = std::str::from_utf8(buf)
+            .map_err(|_| TpmError::Parse(format!("{MEASUREMENT_LIST}: invalid UTF-8")))?;
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = parse_ascii_entry(line).map_err(|err| {
+                TpmError::Parse(format!("{MEASUREMENT_LIST}: line {}: {err}", number + 1))
+            })?;
+            list.entries.push(entry);
+        }
+    } else {
+        let mut reader = LogReader::new(MEASUREMENT_LIST, buf);
+        while !reader.is_empty() {
+            list.entries.push(parse_binary_entry(&mut reader)?);
+        }
+    }
+    Ok(list)
+}
+
+impl ImaEntry {
+    /// Returns `true` if the entry records a measurement violation, which is
+    /// extended as all ones instead of the template digest.
+    #[must_use]
+    pub fn is_violation(&self) -> bool {
+        self.template_digest == [0; TEMPLATE_DIGEST_SIZE]
+    }
+
+    /// Returns `true` if the entry is the aggregate of the boot PCRs, whose
+    /// file digest does not name a file.
+    #[must_use]
+    pub fn is_boot_aggregate(&self) -> bool {
+        self.file_name == BOOT_AGGREGATE
+    }
+
+    /// Returns `false` if the template digest does not match the template
+    /// data.
+    #[must_use]
+    pub fn is_data_consistent(&self) -> bool {
+        if self.is_violation() {
+            return true;
+        }
+        let mut out = [0u8; 64];
+        match SoftwareHash.digest(TpmAlgId::Sha1, &[&self.template_data], &mut out) {
+            Ok(len) => out[..len] == self.template_digest,
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the digest extended into a PCR of the bank `alg`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if `alg` is not a supported hash algorithm.
+    pub fn template_hash(&self, alg: TpmAlgId) -> Result<TpmtHa, TpmError> {
+        let size = tpm_hash_size(&alg).ok_or_else(|| {
+            TpmError::Execution(format!("unsupported bank {}", tpm_alg_id_to_str(alg)))
+        })?;
+        if self.is_violation() {
+            return tpmt_ha(alg, &vec![0xff; size]);
+        }
+        let mut out = [0u8; 64];
+        let len = SoftwareHash.digest(alg, &[&self.template_data], &mut out)?;
+        tpmt_ha(alg, &out[..len])
+    }
+
+    /// Converts the entry into JSON, with `index` as its position in the list.
+    #[must_use]
+    pub fn to_json(&self, index: usize) -> Value {
+        let mut value = json!({
+            "entry": index,
+            "pcr": self.pcr,
+            "template": self.template_name,
+            "template_digest": hex::encode(self.template_digest),
+            "file_digest": format!("{}:{}", self.file_hash_alg, hex::encode(&self.file_digest)),
+            "file_name": self.file_name,
+        });
+        if !self.extra.is_empty() {
+            value["extra"] = json!(hex::encode(&self.extra));
+        }
+        value
+    }
+}
+
+impl ImaMeasurementList {
+    /// Replays the list into `banks`, skipping banks that tpm2sh cannot
+    /// compute. Each bank is extended with the template data hashed with its
+    /// own algorithm.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if an extend fails.
+    pub fn replay(&self, banks: &[TpmAlgId]) -> Result<PcrReplay, TpmError> {
+        let mut replay = PcrReplay::default();
+        for &alg in banks {
+            let Some(size) = tpm_hash_size(&alg) else {
+                continue;
+            };
+            if SoftwareHash.digest(alg, &[], &mut [0u8; 64]).is_err() {
+                continue;
+            }
+            let bank = replay.banks.entry(alg).or_default();
+            for (index, entry) in self.entries.iter().enumerate() {
+                let pcr = match bank.entry(entry.pcr) {
+                    std::collections::btree_map::Entry::Occupied(entry) => entry.into_mut(),
+                    std::collections::btree_map::Entry::Vacant(entry) => {
+                        entry.insert(ReplayedPcr {
+                            initial: tpmt_ha(alg, &vec![0; size])?,
+                            history: Vec::new(),
+                        })
+                    }
+                };
+                let value = extend(pcr.value(), &entry.template_hash(alg)?)?;
+                pcr.history.push((index, value));
+            }
+        }
+        Ok(replay)
+    }
+
+    /// Compares the replay of the list with `pcrs`, typically read from the
+    /// TPM before reading the list. As IMA appends to the list concurrently,
+    /// a PCR matches if its value equals the replayed value after any entry.
+    ///
+    /// The entry blamed for a mismatch is the first entry of the PCR whose
+    /// template digest does not match its template data.
+    #[must_use]
+    pub fn compare(&self, replay: &PcrReplay, pcrs: &PcrOutput) -> Vec<PcrMismatch> {
+        let mut mismatches = Vec::new();
+        for (&alg, replayed) in &replay.banks {
+            let Some(bank) = pcrs.banks.get(tpm_alg_id_to_str(alg)) else {
+                continue;
+            };
+            for (&index, pcr) in replayed {
+                let Some(actual) = bank.get(&index.to_string()) else {
+                    continue;
+                };
+                let matches = std::iter::once(&pcr.initial)
+                    .chain(pcr.history.iter().map(|(_, value)| value))
+                    .any(|value| actual.eq_ignore_ascii_case(&hex::encode(&*value.digest)));
+                if matches {
+                    continue;
+                }
+                mismatches.push(PcrMismatch {
+                    alg,
+                    pcr: index,
+                    replayed: *pcr.value(),
+                    actual: actual.clone(),
+                    event: pcr
+                        .history
+                        .iter()
+                        .map(|&(entry, _)| entry)
+                        .find(|&entry| !self.entries[entry].is_data_consistent()),
+                });
+            }
+        }
+        mismatches.sort_by_key(|mismatch| (mismatch.event.unwrap_or(usize::MAX), mismatch.pcr));
+        mismatches
+    }
+}
+
+/// A set of file digests that are allowed to appear in the measurement list.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ImaAllowlist {
+    digests: HashSet<Vec<u8>>,
+}
+
+impl ImaAllowlist {
+    /// Parses an allowlist in the output format of `sha256sum` and similar
+    /// tools. A digest may be prefixed with its algorithm, e.g. `sha256:`.
+    /// Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError::Parse` if a digest is not valid hex.
+    pub fn parse(text: &str) -> Result<Self, TpmError> {
+        let mut digests = HashSet::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let digest = line.split_whitespace().next().unwrap_or_default();
+            let digest = digest.split_once(':').map_or(digest, |(_, digest)| digest);
+            let digest = hex::decode(digest).map_err(|_| {
+                TpmError::Parse(format!("allowlist: line {}: invalid digest", number + 1))
+            })?;
+            digests.insert(digest);
+        }
+        Ok(Self { digests })
+    }
+
+    /// Returns the indices of the measurements whose file digest is not in
+    /// the allowlist. Violations and the boot aggregate are not checked.
+    #[must_use]
+    pub fn unlisted(&self, list: &ImaMeasurementList) -> Vec<usize> {
+        list.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !entry.is_violation() && !entry.is_boot_aggregate())
+            .filter(|(_, entry)| !self.digests.contains(&entry.file_digest))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
@@ -0,0 +1,331 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! Canonical JSON encoding for TPM structures.
+//!
+//! Structures are objects keyed by field name and lists are arrays. Integers
+//! are numbers, handles are hexadecimal strings, enumeration constants are
+//! their TCG names, attributes are arrays of flag names and buffers are
+//! hexadecimal strings. Empty union variants are `null`. The encoding is
+//! lossless: decoding it and marshalling the result gives back the original
+//! bytes.
+
+use crate::TpmError;
+use serde_json::{Map, Value};
+use std::vec::Vec;
+use tpm2_protocol::{
+    visit::{TpmFromSource, TpmSource, TpmValue, TpmVisit, TpmVisitor},
+    TpmErrorKind,
+};
+
+/// Encodes a TPM structure as JSON.
+#[must_use]
+pub fn tpm_to_json<T: TpmVisit + ?Sized>(value: &T) -> Value {
+    let mut visitor = JsonVisitor::default();
+    value.visit("", &mut visitor);
+    visitor.root.unwrap_or(Value::Null)
+}
+
+/// Decodes a TPM structure from JSON produced by `tpm_to_json()`.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Parse` naming the offending field if the JSON does
+/// not describe a valid `T`.
+pub fn tpm_from_json<T: TpmFromSource>(json: &Value) -> Result<T, TpmError> {
+    let mut source = JsonSource {
+        root: Some(json),
+        stack: Vec::new(),
+        path: Vec::new(),
+        field: String::new(),
+    };
+    T::from_source("", &mut source).map_err(|err| {
+        let path = source.path();
+        if path.is_empty() {
+            TpmError::Parse(err.to_string())
+        } else {
+            TpmError::Parse(format!("{path}: {err}"))
+        }
+    })
+}
+
+fn hex_value(value: u64) -> Value {
+    Value::String(format!("{value:#x}"))
+}
+
+fn parse_hex(s: &str) -> Option<u64> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))?;
+    u64::from_str_radix(digits, 16).ok()
+}
+
+#[derive(Default)]
+struct JsonVisitor {
+    stack: Vec<(&'static str, Value)>,
+    root: Option<Value>,
+}
+
+impl JsonVisitor {
+    fn insert(&mut self, field: &'static str, value: Value) {
+        match self.stack.last_mut() {
+            Some((_, Value::Object(map))) => {
+                map.insert(field.to_string(), value);
+            }
+            Some((_, Value::Array(items))) => items.push(value),
+            _ => self.root = Some(value),
+        }
+    }
+}
+
+impl TpmVisitor for JsonVisitor {
+    fn value(&mut self, field: &'static str, _type_name: &'static str, value: TpmValue<'_>) {
+        let json = match value {
+            TpmValue::U8(v) => Value::from(v),
+            TpmValue::U16(v) => Value::from(v),
+            TpmValue::U32(v) => Value::from(v),
+            TpmValue::U64(v) => Value::from(v),
+            TpmValue::I32(v) => Value::from(v),
+            TpmValue::Bool(v) => Value::Bool(v),
+            TpmValue::Handle(v) => Value::String(format!("{v:#010x}")),
+            TpmValue::Enum { name, .. } => Value::String(name.to_string()),
+            TpmValue::Flags { bits, names } => {
+                let mut flags: Vec<Value> = Vec::new();
+                let mut rest = bits;
+                for name in value.flag_names() {
+                    flags.push(Value::String(name.to_string()));
+                    if let Some((flag, _)) = names.iter().find(|(_, n)| *n == name) {
+                        rest &= !flag;
+                    }
+                }
+                if rest != 0 {
+                    flags.push(hex_value(rest));
+                }
+                Value::Array(flags)
+            }
+            TpmValue::Bytes(bytes) => Value::String(hex::encode(bytes)),
+            TpmValue::Null => Value::Null,
+        };
+        self.insert(field, json);
+    }
+
+    fn enter(&mut self, field: &'static str, _type_name: &'static str) {
+        self.stack.push((field, Value::Object(Map::new())));
+    }
+
+    fn enter_list(&mut self, field: &'static str, _type_name: &'static str, count: usize) {
+        self.stack
+            .push((field, Value::Array(Vec::with_capacity(count))));
+    }
+
+    fn leave(&mut self) {
+        if let Some((field, value)) = self.stack.pop() {
+            self.insert(field, value);
+        }
+    }
+}
+
+enum Frame<'a> {
+    Object {
+        map: &'a Map<String, Value>,
+        used: usize,
+    },
+    Array {
+        items: &'a [Value],
+        next: usize,
+    },
+}
+
+struct JsonSource<'a> {
+    root: Option<&'a Value>,
+    stack: Vec<Frame<'a>>,
+    path: Vec<String>,
+    field: String,
+}
+
+impl<'a> JsonSource<'a> {
+    /// Returns the path of the field that was requested last.
+    fn path(&self) -> String {
+        let mut path = String::new();
+        for part in self.path.iter().chain([&self.field]) {
+            if part.is_empty() {
+                continue;
+            }
+            if !path.is_empty() && !part.starts_with('[') {
+                path.push('.');
+            }
+            path.push_str(part);
+        }
+        path
+    }
+
+    fn take(&mut self, field: &'static str) -> Result<&'a Value, TpmErrorKind> {
+        match self.stack.last_mut() {
+            None => {
+                self.field = field.to_string();
+                self.root.take().ok_or(TpmErrorKind::InvalidValue)
+            }
+            Some(Frame::Object { map, used }) => {
+                self.field = field.to_string();
+                let value = map.get(field).ok_or(TpmErrorKind::InvalidValue)?;
+                *used += 1;
+                Ok(value)
+            }
+            Some(Frame::Array { items, next }) => {
+                self.field = format!("[{next}]");
+                let value = items.get(*next).ok_or(TpmErrorKind::InvalidValue)?;
+                *next += 1;
+                Ok(value)
+            }
+        }
+    }
+
+    fn push(&mut self, frame: Frame<'a>) {
+        self.path.push(std::mem::take(&mut self.field));
+        self.stack.push(frame);
+    }
+
+    fn number(value: &Value) -> Option<u64> {
+        match value {
+            Value::Number(n) => n.as_u64(),
+            Value::String(s) => parse_hex(s),
+            _ => None,
+        }
+    }
+}
+
+impl TpmSource for JsonSource<'_> {
+    fn enter(&mut self, field: &'static str, _type_name: &'static str) -> Result<(), TpmErrorKind> {
+        let map = self
+            .take(field)?
+            .as_object()
+            .ok_or(TpmErrorKind::InvalidValue)?;
+        self.push(Frame::Object { map, used: 0 });
+        Ok(())
+    }
+
+    fn enter_list(
+        &mut self,
+        field: &'static str,
+        _type_name: &'static str,
+    ) -> Result<usize, TpmErrorKind> {
+        let items = self
+            .take(field)?
+            .as_array()
+            .ok_or(TpmErrorKind::InvalidValue)?;
+        self.push(Frame::Array { items, next: 0 });
+        Ok(items.len())
+    }
+
+    fn leave(&mut self) -> Result<(), TpmErrorKind> {
+        let complete = match self.stack.pop() {
+            Some(Frame::Object { map, used }) => used == map.len(),
+            Some(Frame::Array { items, next }) => next == items.len(),
+            None => return Err(TpmErrorKind::InternalError),
+        };
+        if !complete {
+            return Err(TpmErrorKind::TrailingData);
+        }
+        self.field = self.path.pop().unwrap_or_default();
+        Ok(())
+    }
+
+    fn unsigned(
+        &mut self,
+        field: &'static str,
+        _type_name: &'static str,
+    ) -> Result<u64, TpmErrorKind> {
+        Self::number(self.take(field)?).ok_or(TpmErrorKind::InvalidValue)
+    }
+
+    fn signed(
+        &mut self,
+        field: &'static str,
+        _type_name: &'static str,
+    ) -> Result<i64, TpmErrorKind> {
+        self.take(field)?.as_i64().ok_or(TpmErrorKind::InvalidValue)
+    }
+
+    fn boolean(
+        &mut self,
+        field: &'static str,
+        _type_name: &'static str,
+    ) -> Result<bool, TpmErrorKind> {
+        self.take(field)?
+            .as_bool()
+            .ok_or(TpmErrorKind::InvalidValue)
+    }
+
+    fn handle(
+        &mut self,
+        field: &'static str,
+        _type_name: &'static str,
+    ) -> Result<u32, TpmErrorKind> {
+        let value = Self::number(self.take(field)?).ok_or(TpmErrorKind::InvalidValue)?;
+        u32::try_from(value).map_err(|_| TpmErrorKind::ValueTooLarge)
+    }
+
+    fn enumeration(
+        &mut self,
+        field: &'static str,
+        _type_name: &'static str,
+        names: &'static [(u64, &'static str)],
+    ) -> Result<u64, TpmErrorKind> {
+        match self.take(field)? {
+            Value::String(s) => names
+                .iter()
+                .find(|(_, name)| name == s)
+                .map(|(value, _)| *value)
+                .or_else(|| parse_hex(s)),
+            other => Self::number(other),
+        }
+        .ok_or(TpmErrorKind::InvalidValue)
+    }
+
+    fn flags(
+        &mut self,
+        field: &'static str,
+        _type_name: &'static str,
+        names: &'static [(u64, &'static str)],
+    ) -> Result<u64, TpmErrorKind> {
+        match self.take(field)? {
+            Value::Array(flags) => {
+                let mut bits = 0;
+                for flag in flags {
+                    let s = flag.as_str().ok_or(TpmErrorKind::InvalidValue)?;
+                    bits |= names
+                        .iter()
+                        .find(|(_, name)| *name == s)
+                        .map(|(value, _)| *value)
+                        .or_else(|| parse_hex(s))
+                        .ok_or(TpmErrorKind::InvalidValue)?;
+                }
+                Ok(bits)
+            }
+            other => Self::number(other).ok_or(TpmErrorKind::InvalidValue),
+        }
+    }
+
+    fn bytes(
+        &mut self,
+        field: &'static str,
+        _type_name: &'static str,
+        out: &mut [u8],
+    ) -> Result<usize, TpmErrorKind> {
+        let s = self
+            .take(field)?
+            .as_str()
+            .ok_or(TpmErrorKind::InvalidValue)?;
+        let bytes = hex::decode(s).map_err(|_| TpmErrorKind::InvalidValue)?;
+        let dest = out
+            .get_mut(..bytes.len())
+            .ok_or(TpmErrorKind::CapacityExceeded)?;
+        dest.copy_from_slice(&bytes);
+        Ok(bytes.len())
+    }
+
+    fn null(&mut self, field: &'static str, _type_name: &'static str) -> Result<(), TpmErrorKind> {
+        if !self.take(field)?.is_null() {
+            return Err(TpmErrorKind::InvalidValue);
+        }
+        Ok(())
+    }
+}
@@ -10,29 +10,50 @@ use pest::Parser as PestParser;
 use pest_derive::Parser;
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, fs, io::Write, str::FromStr, vec::Vec};
+use std::{
+    cmp::Ordering,
+    fs,
+    io::{Read, Write},
+    str::FromStr,
+    vec::Vec,
+};
 use tpm2_protocol::{
     self,
-    data::{self, Tpm2bAuth, TpmAlgId, TpmEccCurve, TpmRc, TpmRh, TpmtPublic},
+    data::{self, Tpm2bAuth, TpmAlgId, TpmEccCurve, TpmRc, TpmtPublic},
     message::{
         TpmContextLoadCommand, TpmFlushContextCommand, TpmHeader, TpmLoadCommand,
         TpmReadPublicCommand,
     },
-    TpmBuild, TpmErrorKind, TpmParse, TpmPersistent, TpmSession, TpmTransient, TpmWriter,
-    TPM_MAX_COMMAND_SIZE,
+    metadata::tpm_command_metadata,
+    TpmBuild, TpmErrorKind, TpmHandle, TpmObjectHandle, TpmParse, TpmPcr, TpmPersistent,
+    TpmSession, TpmTransient, TpmWriter, TPM_MAX_COMMAND_SIZE,
 };
 use tracing::debug;
 
+pub mod ak;
 pub mod arg_parser;
+pub mod attestation;
 pub mod cli;
 pub mod command;
 pub mod command_io;
+pub mod credential;
 pub mod crypto;
 pub mod device;
+pub mod ek;
 pub mod error;
+pub mod event_log;
 pub mod formats;
+pub mod hash;
+pub mod ima;
+pub mod json;
 pub mod pretty_printer;
+pub mod proxy;
+pub mod quote;
+pub mod resource_manager;
+pub mod signature;
+pub mod soft_tpm;
 pub mod tpm_stack;
+pub mod x509;
 
 pub use self::arg_parser::parse_cli;
 pub use self::command_io::CommandIo;
@@ -52,7 +73,59 @@ pub(crate) fn parse_hex_u32(s: &str) -> Result<u32, TpmError> {
 }
 
 pub(crate) fn parse_persistent_handle(s: &str) -> Result<TpmPersistent, TpmError> {
-    parse_hex_u32(s).map(TpmPersistent)
+    let handle = parse_hex_u32(s)?;
+    if data::TpmHt::from_handle(handle) != Some(data::TpmHt::Persistent) {
+        return Err(TpmError::InvalidHandle(format!(
+            "'{handle:#010x}' is not a persistent handle"
+        )));
+    }
+    Ok(TpmPersistent::try_from(handle)?)
+}
+
+pub(crate) fn parse_pcr_handle(s: &str) -> Result<TpmPcr, TpmError> {
+    let handle = parse_hex_u32(s)?;
+    TpmPcr::try_from(handle).map_err(|e| TpmError::InvalidHandle(e.to_string()))
+}
+
+/// Returns the TPM device passed to a command.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Execution` if the command was run without a device.
+pub(crate) fn required_device(device: Option<&mut TpmDevice>) -> Result<&mut TpmDevice, TpmError> {
+    device.ok_or_else(|| TpmError::Execution("TPM device is required".to_string()))
+}
+
+/// Parses a command code given either as a TCG name or as a hexadecimal value.
+pub(crate) fn parse_tpm_cc(s: &str) -> Result<data::TpmCc, TpmError> {
+    if let Ok(cc) = s.parse() {
+        return Ok(cc);
+    }
+    let raw_cc = parse_hex_u32(s)?;
+    data::TpmCc::try_from(raw_cc)
+        .map_err(|()| TpmError::Parse(format!("unknown command code: {s}")))
+}
+
+/// Reads all bytes from a file, or from stdin if `path` is `None` or `-`.
+///
+/// # Errors
+///
+/// Returns a `TpmError::File` if reading fails.
+pub(crate) fn read_all(path: Option<&str>) -> Result<Vec<u8>, TpmError> {
+    let mut buf = Vec::new();
+    match path {
+        Some("-") | None => {
+            std::io::stdin()
+                .read_to_end(&mut buf)
+                .map_err(|e| TpmError::File("stdin".to_string(), e))?;
+        }
+        Some(file_path) => {
+            fs::File::open(file_path)
+                .and_then(|mut f| f.read_to_end(&mut buf))
+                .map_err(|e| TpmError::File(file_path.to_string(), e))?;
+        }
+    }
+    Ok(buf)
 }
 
 pub(crate) fn parse_tpm_rc(s: &str) -> Result<TpmRc, TpmError> {
@@ -62,14 +135,19 @@ pub(crate) fn parse_tpm_rc(s: &str) -> Result<TpmRc, TpmError> {
 
 /// The callback API for subcommands
 pub trait Command {
-    /// Runs a command.
+    /// Returns `true` if the command can run without a TPM device.
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    /// Runs a command. `device` is `None` only for local commands.
     ///
     /// # Errors
     ///
     /// Returns a `TpmError` if the execution fails
     fn run(
         &self,
-        device: &mut TpmDevice,
+        device: Option<&mut TpmDevice>,
         session: Option<&AuthSession>,
         log_format: cli::LogFormat,
     ) -> Result<(), TpmError>;
@@ -86,9 +164,13 @@ pub fn execute_cli() -> Result<(), TpmError> {
     };
 
     if let Some(command) = cli.command {
-        let mut device = TpmDevice::new(&cli.device)?;
         let session = load_session(cli.session.as_deref())?;
-        command.run(&mut device, session.as_ref(), cli.log_format)
+        if command.is_local() {
+            return command.run(None, session.as_ref(), cli.log_format);
+        }
+        let mut device = TpmDevice::new(&cli.device, cli.timeout)?;
+        device.set_retry_policy(cli.retry_policy);
+        command.run(Some(&mut device), session.as_ref(), cli.log_format)
     } else {
         Ok(())
     }
@@ -296,6 +378,14 @@ pub struct SessionData {
     pub hmac_key: String,
     pub auth_hash: u16,
     pub policy_digest: String,
+    #[serde(default)]
+    pub session_type: u8,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PolicyData {
+    pub hash_alg: u16,
+    pub policy_digest: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -397,9 +487,8 @@ pub fn pop_object_data<W: Write>(io: &mut CommandIo<W>) -> Result<ObjectData, Tp
 ///
 /// Returns a `TpmError::Parse` if the hex string is invalid.
 pub fn parse_parent_handle_from_json(object_data: &ObjectData) -> Result<TpmTransient, TpmError> {
-    u32::from_str_radix(object_data.parent.trim_start_matches("0x"), 16)
-        .map_err(Into::into)
-        .map(TpmTransient)
+    let handle = u32::from_str_radix(object_data.parent.trim_start_matches("0x"), 16)?;
+    Ok(TpmTransient::try_from(handle)?)
 }
 
 /// Loads a TPM object, executes an operation with its handle, and ensures it's flushed.
@@ -434,29 +523,24 @@ where
     };
     let parent_handles = [parent_handle.into()];
     let parent_sessions = get_auth_sessions(
+        chip,
         &load_cmd,
         &parent_handles,
         session,
         parent_auth.auth.as_deref(),
-    )?;
-
-    let (load_resp, _) = chip.execute(
-        &load_cmd,
-        Some(&parent_handles),
-        &parent_sessions,
         log_format,
     )?;
-    let load_resp = load_resp
-        .Load()
-        .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+
+    let (load_resp, _) = chip.execute(&load_cmd, &parent_handles, &parent_sessions, log_format)?;
     let object_handle = load_resp.object_handle;
+    chip.register_object(object_handle.into(), &load_cmd.in_public.inner);
 
     let op_result = op(chip, object_handle);
 
     let flush_cmd = TpmFlushContextCommand {
         flush_handle: object_handle.into(),
     };
-    let flush_err = chip.execute(&flush_cmd, Some(&[]), &[], log_format).err();
+    let flush_err = chip.execute(&flush_cmd, &[], &[], log_format).err();
 
     if let Some(e) = flush_err {
         tracing::debug!(handle = ?object_handle, error = %e, "failed to flush object context after operation");
@@ -511,10 +595,10 @@ pub fn object_to_handle(
     chip: &mut TpmDevice,
     obj: &cli::Object,
     log_format: cli::LogFormat,
-) -> Result<TpmTransient, TpmError> {
+) -> Result<TpmObjectHandle, TpmError> {
     match obj {
-        cli::Object::Handle(handle) => Ok(*handle),
-        cli::Object::Persistent(handle) => Ok(TpmTransient(handle.0)),
+        cli::Object::Handle(handle) => Ok((*handle).into()),
+        cli::Object::Persistent(handle) => Ok((*handle).into()),
         cli::Object::Context(v) => {
             let s = v.as_str().ok_or_else(|| {
                 TpmError::Parse("context object must contain a string value".to_string())
@@ -522,15 +606,26 @@ pub fn object_to_handle(
             let context_blob = input_to_bytes(s)?;
             let (context, _) = data::TpmsContext::parse(&context_blob)?;
             let load_cmd = TpmContextLoadCommand { context };
-            let (resp, _) = chip.execute(&load_cmd, None, &[], log_format)?;
-            let load_resp = resp
-                .ContextLoad()
-                .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
-            Ok(load_resp.loaded_handle)
+            let (load_resp, _) = chip.execute(&load_cmd, &[], &[], log_format)?;
+            Ok(TpmObjectHandle::try_from(u32::from(
+                load_resp.loaded_handle,
+            ))?)
         }
         cli::Object::Pcrs(_) => Err(TpmError::Execution(
             "cannot convert a PCR object to a handle".to_string(),
         )),
+        cli::Object::Quote(_) => Err(TpmError::Execution(
+            "cannot convert a quote object to a handle".to_string(),
+        )),
+        cli::Object::Credential(_) => Err(TpmError::Execution(
+            "cannot convert a credential object to a handle".to_string(),
+        )),
+        cli::Object::Certificate(_) => Err(TpmError::Execution(
+            "cannot convert a certificate object to a handle".to_string(),
+        )),
+        cli::Object::Name(_) => Err(TpmError::Execution(
+            "cannot convert a name object to a handle".to_string(),
+        )),
     }
 }
 
@@ -541,14 +636,11 @@ pub fn object_to_handle(
 /// Returns `TpmError` if the `ReadPublic` command fails.
 pub fn read_public(
     chip: &mut TpmDevice,
-    handle: TpmTransient,
+    handle: TpmObjectHandle,
     log_format: cli::LogFormat,
 ) -> Result<(TpmtPublic, data::Tpm2bName), TpmError> {
     let cmd = TpmReadPublicCommand {};
-    let (resp, _) = chip.execute(&cmd, Some(&[handle.into()]), &[], log_format)?;
-    let read_public_resp = resp
-        .ReadPublic()
-        .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}")))?;
+    let (read_public_resp, _) = chip.execute(&cmd, &[handle.into()], &[], log_format)?;
     Ok((read_public_resp.out_public.inner, read_public_resp.name))
 }
 
@@ -586,7 +678,7 @@ pub fn load_session(session_arg: Option<&str>) -> Result<Option<AuthSession>, Tp
     let data: SessionData = from_json_str(&json_str, "session")?;
 
     Ok(Some(AuthSession {
-        handle: TpmSession(data.handle),
+        handle: TpmSession::try_from(data.handle)?,
         nonce_tpm: data::Tpm2bNonce::try_from(base64_engine.decode(data.nonce_tpm)?.as_slice())?,
         attributes: data::TpmaSession::from_bits_truncate(data.attributes),
         hmac_key: data::Tpm2bAuth::try_from(base64_engine.decode(data.hmac_key)?.as_slice())?,
@@ -605,7 +697,7 @@ pub fn build_password_session(auth: Option<&str>) -> Result<Vec<data::TpmsAuthCo
         Some(password) => {
             debug!(auth_len = password.len(), "building password session");
             Ok(vec![data::TpmsAuthCommand {
-                session_handle: TpmSession(TpmRh::Password as u32),
+                session_handle: TpmSession::PASSWORD,
                 nonce: data::Tpm2bNonce::default(),
                 session_attributes: data::TpmaSession::empty(),
                 hmac: Tpm2bAuth::try_from(password.as_bytes())?,
@@ -618,20 +710,36 @@ pub fn build_password_session(auth: Option<&str>) -> Result<Vec<data::TpmsAuthCo
 /// Prepares the authorization sessions for a command, handling either a full
 /// `AuthSession` context or a simple password.
 ///
+/// Commands that can be sent without sessions and have no handles requiring
+/// authorization get no password session, and only get the `AuthSession` if
+/// it is used for auditing or parameter encryption.
+///
 /// # Errors
 ///
 /// Returns a `TpmError` if building the command parameters or creating the
 /// authorization HMAC fails.
 pub fn get_auth_sessions<'a, C>(
+    chip: &mut TpmDevice,
     command: &C,
-    handles: &[u32],
+    handles: &[TpmHandle],
     session: Option<&'a AuthSession>,
     password: Option<&'a str>,
+    log_format: cli::LogFormat,
 ) -> Result<Vec<data::TpmsAuthCommand>, TpmError>
 where
-    C: for<'b> TpmHeader<'b>,
+    C: TpmHeader,
 {
+    let needs_auth = !C::NO_SESSIONS
+        || tpm_command_metadata(C::COMMAND)
+            .map_or(true, |metadata| metadata.auth_handle_count() > 0);
+
     if let Some(session) = session {
+        let detached =
+            data::TpmaSession::AUDIT | data::TpmaSession::DECRYPT | data::TpmaSession::ENCRYPT;
+        if !needs_auth && !session.attributes.intersects(detached) {
+            return Ok(Vec::new());
+        }
+
         let params = build_to_vec(command)?;
 
         let nonce_size = tpm2_protocol::tpm_hash_size(&session.auth_hash).ok_or_else(|| {
@@ -645,8 +753,11 @@ where
         rand::thread_rng().fill_bytes(&mut nonce_bytes);
         let nonce_caller = data::Tpm2bNonce::try_from(nonce_bytes.as_slice())?;
 
-        let auth = create_auth(session, &nonce_caller, C::COMMAND, handles, &params)?;
+        let names = chip.get_handle_names(handles, log_format)?;
+        let auth = create_auth(session, &nonce_caller, C::COMMAND, &names, &params)?;
         Ok(vec![auth])
+    } else if !needs_auth {
+        Ok(Vec::new())
     } else {
         let effective_password = if C::WITH_SESSIONS && password.is_none() {
             Some("")
@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! A proxy that forwards TPM commands from clients to a `TpmDevice` and logs
+//! the traffic as JSON Lines.
+//!
+//! Clients connect either with the TCP protocol of the TPM reference
+//! simulator, as used by the `mssim` and `swtpm` TCTIs, or with raw command
+//! buffers over a Unix socket, as they would write them to `/dev/tpmrm0`.
+
+use crate::{
+    command::decode::{command_code, decode_command, decode_response},
+    json::tpm_to_json,
+    soft_tpm::error_response,
+    TpmDevice, TpmError,
+};
+use serde_json::{json, Map, Value};
+use std::{
+    io::{self, Read, Write},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use tpm2_protocol::{
+    data::{TpmRc, TpmRcBase},
+    message::TPM_HEADER_SIZE,
+    TpmErrorKind, TPM_MAX_COMMAND_SIZE,
+};
+
+/// Simulator command: send a TPM command and receive the response.
+pub const TPM_SEND_COMMAND: u32 = 8;
+/// Simulator command: exchange protocol versions.
+pub const TPM_REMOTE_HANDSHAKE: u32 = 15;
+/// Simulator command: close the connection.
+pub const TPM_SESSION_END: u32 = 20;
+/// Simulator command: stop the simulator.
+pub const TPM_STOP: u32 = 21;
+
+/// Simulator commands that carry no payload and are acknowledged without
+/// being forwarded: `_TPM_Hash_Start`, `_TPM_Hash_End`, power, cancel and
+/// NV signals, and the physical presence signals.
+const SIMULATOR_SIGNALS: &[u32] = &[1, 2, 3, 4, 5, 7, 9, 10, 11, 12];
+/// Simulator command: data for `_TPM_Hash_Data`.
+const TPM_HASH_DATA: u32 = 6;
+/// The simulator protocol version reported in the handshake.
+const SIMULATOR_VERSION: u32 = 1;
+/// Handshake flag: the platform port is available.
+const SIMULATOR_PLATFORM_AVAILABLE: u32 = 1;
+
+/// Forwards commands to a TPM device and writes a JSON Lines record of each
+/// exchange to a log.
+pub struct TpmProxy<'a, W: Write> {
+    device: &'a mut TpmDevice,
+    log: W,
+    sequence: u64,
+}
+
+impl<'a, W: Write> TpmProxy<'a, W> {
+    /// Creates a proxy that forwards to `device` and logs into `log`.
+    pub fn new(device: &'a mut TpmDevice, log: W) -> Self {
+        Self {
+            device,
+            log,
+            sequence: 0,
+        }
+    }
+
+    /// Returns the log.
+    pub fn log(&self) -> &W {
+        &self.log
+    }
+
+    /// Forwards a marshaled command to the device, logs the exchange and
+    /// returns the marshaled response.
+    ///
+    /// If the device fails, the client receives a `TPM_RC_FAILURE` response
+    /// and the failure is recorded in the log.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if writing the log fails.
+    pub fn forward(&mut self, client: &str, command: &[u8]) -> Result<Vec<u8>, TpmError> {
+        self.sequence += 1;
+        let start = Instant::now();
+        let result = self.device.transmit(command);
+        let latency = start.elapsed();
+
+        let mut record = Map::new();
+        record.insert("seq".to_string(), json!(self.sequence));
+        record.insert("time".to_string(), json!(unix_time()));
+        record.insert("client".to_string(), json!(client));
+        record.insert(
+            "latency_us".to_string(),
+            json!(u64::try_from(latency.as_micros()).unwrap_or(u64::MAX)),
+        );
+        insert_decoded(
+            &mut record,
+            "command",
+            command,
+            decode_command(command, None).map(|command| tpm_to_json(&command)),
+        );
+
+        let response = match result {
+            Ok(response) => {
+                let decoded = command_code(command)
+                    .and_then(|cc| decode_response(&response, cc))
+                    .map(|response| tpm_to_json(&response));
+                insert_decoded(&mut record, "response", &response, decoded);
+                response
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, "TPM device failed");
+                record.insert("error".to_string(), json!(err.to_string()));
+                error_response(TpmRc::from(TpmRcBase::Failure))
+            }
+        };
+
+        serde_json::to_writer(&mut self.log, &Value::Object(record))?;
+        self.log.write_all(b"\n")?;
+        self.log.flush()?;
+        Ok(response)
+    }
+
+    /// Serves a client speaking the TPM simulator protocol until it ends the
+    /// session or closes the connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` on I/O failure or an unsupported simulator
+    /// command.
+    pub fn serve_simulator<S: Read + Write>(
+        &mut self,
+        client: &str,
+        stream: &mut S,
+    ) -> Result<(), TpmError> {
+        loop {
+            let Some(request) = read_u32_or_eof(stream)? else {
+                return Ok(());
+            };
+            match request {
+                TPM_SEND_COMMAND => {
+                    let mut locality = [0u8; 1];
+                    stream.read_exact(&mut locality)?;
+                    let command = read_sized(stream)?;
+                    let response = self.forward(client, &command)?;
+                    write_sized(stream, &response)?;
+                    write_u32(stream, 0)?;
+                }
+                TPM_REMOTE_HANDSHAKE => {
+                    let _client_version = read_u32(stream)?;
+                    write_u32(stream, SIMULATOR_VERSION)?;
+                    write_u32(stream, SIMULATOR_PLATFORM_AVAILABLE)?;
+                    write_u32(stream, 0)?;
+                }
+                TPM_HASH_DATA => {
+                    read_sized(stream)?;
+                    write_u32(stream, 0)?;
+                }
+                TPM_SESSION_END | TPM_STOP => return Ok(()),
+                _ if SIMULATOR_SIGNALS.contains(&request) => write_u32(stream, 0)?,
+                _ => {
+                    return Err(TpmError::Execution(format!(
+                        "unsupported simulator command: {request}"
+                    )))
+                }
+            }
+            stream.flush()?;
+        }
+    }
+
+    /// Serves a client that writes raw command buffers, as to a TPM character
+    /// device, until it closes the connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` on I/O failure or a malformed command header.
+    pub fn serve_raw<S: Read + Write>(
+        &mut self,
+        client: &str,
+        stream: &mut S,
+    ) -> Result<(), TpmError> {
+        loop {
+            let mut header = [0u8; TPM_HEADER_SIZE];
+            if !read_exact_or_eof(stream, &mut header)? {
+                return Ok(());
+            }
+            let size = u32::from_be_bytes([header[2], header[3], header[4], header[5]]) as usize;
+            if !(TPM_HEADER_SIZE..=TPM_MAX_COMMAND_SIZE).contains(&size) {
+                return Err(TpmError::Parse(format!(
+                    "Invalid command size in header: {size}"
+                )));
+            }
+            let mut command = header.to_vec();
+            command.resize(size, 0);
+            stream.read_exact(&mut command[TPM_HEADER_SIZE..])?;
+            let response = self.forward(client, &command)?;
+            stream.write_all(&response)?;
+            stream.flush()?;
+        }
+    }
+}
+
+/// Serves the platform port of the TPM simulator protocol by acknowledging
+/// every signal. The proxied device has no platform to control.
+///
+/// # Errors
+///
+/// Returns a `TpmError` on I/O failure.
+pub fn serve_platform<S: Read + Write>(stream: &mut S) -> Result<(), TpmError> {
+    while let Some(request) = read_u32_or_eof(stream)? {
+        if request == TPM_SESSION_END || request == TPM_STOP {
+            break;
+        }
+        write_u32(stream, 0)?;
+        stream.flush()?;
+    }
+    Ok(())
+}
+
+/// Adds the decoded message under `key`, and the raw bytes under
+/// `<key>_raw`. A decoding failure is logged in place of the message.
+fn insert_decoded(
+    record: &mut Map<String, Value>,
+    key: &str,
+    raw: &[u8],
+    decoded: Result<Value, TpmError>,
+) {
+    match decoded {
+        Ok(value) => {
+            record.insert(key.to_string(), value);
+        }
+        Err(err) => {
+            record.insert(key.to_string(), Value::Null);
+            record.insert(format!("{key}_error"), json!(err.to_string()));
+        }
+    }
+    record.insert(format!("{key}_raw"), json!(hex::encode(raw)));
+}
+
+fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs_f64())
+        .unwrap_or_default()
+}
+
+/// Fills `buf` and returns `true`, or returns `false` if the stream ended
+/// before the first byte.
+fn read_exact_or_eof<R: Read>(stream: &mut R, buf: &mut [u8]) -> Result<bool, TpmError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match stream.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(true)
+}
+
+fn read_u32_or_eof<R: Read>(stream: &mut R) -> Result<Option<u32>, TpmError> {
+    let mut buf = [0u8; 4];
+    Ok(read_exact_or_eof(stream, &mut buf)?.then(|| u32::from_be_bytes(buf)))
+}
+
+fn read_u32<R: Read>(stream: &mut R) -> Result<u32, TpmError> {
+    let mut buf = [0u8; 4];
+    stream.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_sized<R: Read>(stream: &mut R) -> Result<Vec<u8>, TpmError> {
+    let size = read_u32(stream)? as usize;
+    if size > TPM_MAX_COMMAND_SIZE {
+        return Err(TpmError::Parse(format!("Invalid buffer size: {size}")));
+    }
+    let mut buf = vec![0u8; size];
+    stream.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn write_u32<W: Write>(stream: &mut W, value: u32) -> Result<(), TpmError> {
+    stream.write_all(&value.to_be_bytes())?;
+    Ok(())
+}
+
+fn write_sized<W: Write>(stream: &mut W, buf: &[u8]) -> Result<(), TpmError> {
+    let size = u32::try_from(buf.len()).map_err(|_| TpmErrorKind::ValueTooLarge)?;
+    write_u32(stream, size)?;
+    stream.write_all(buf)?;
+    Ok(())
+}
@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! Offline verification of `TPM2_Quote` attestations.
+
+use crate::{
+    attestation::{AttestExpectations, AttestFinding, SignedAttest},
+    build_to_vec,
+    crypto::software_digest,
+    formats::{PcrOutput, QuoteOutput},
+    tpm_alg_id_to_str, TpmError,
+};
+use tpm2_protocol::{
+    data::{
+        TpmAlgId, TpmSt, TpmlPcrSelection, TpmsAttest, TpmtPublic, TpmtScheme, TpmtSignature,
+        TpmuAsymScheme, TpmuAttest, TpmuPublicParms, TpmuSignature,
+    },
+    TpmParse,
+};
+
+/// Returns the hash algorithm of a signature.
+#[must_use]
+pub fn signature_hash_alg(signature: &TpmtSignature) -> Option<TpmAlgId> {
+    match &signature.signature {
+        TpmuSignature::Rsassa(sig) | TpmuSignature::Rsapss(sig) => Some(sig.hash),
+        TpmuSignature::Ecdsa(sig)
+        | TpmuSignature::Ecdaa(sig)
+        | TpmuSignature::Sm2(sig)
+        | TpmuSignature::Ecschnorr(sig) => Some(sig.hash),
+        TpmuSignature::Hmac(ha) => Some(ha.hash_alg),
+        TpmuSignature::Null => None,
+    }
+}
+
+/// Returns the scheme for signing a quote with `public`. A key with a fixed
+/// scheme must be given `TPM_ALG_NULL`, otherwise RSASSA or ECDSA with the
+/// name algorithm of the key is selected.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Execution` if `public` is not an RSA or ECC key.
+pub fn quote_scheme(public: &TpmtPublic) -> Result<TpmtScheme, TpmError> {
+    let (scheme, fallback) = match &public.parameters {
+        TpmuPublicParms::Rsa { scheme, .. } => (scheme, TpmAlgId::Rsassa),
+        TpmuPublicParms::Ecc { scheme, .. } => (scheme, TpmAlgId::Ecdsa),
+        _ => {
+            return Err(TpmError::Execution(
+                "quote requires an RSA or ECC signing key".to_string(),
+            ))
+        }
+    };
+    if scheme.scheme == TpmAlgId::Null {
+        Ok(TpmtScheme {
+            scheme: fallback,
+            details: TpmuAsymScheme::Hash(public.name_alg),
+        })
+    } else {
+        Ok(TpmtScheme::default())
+    }
+}
+
+/// Computes the `pcrDigest` of a quote: a digest over the selected PCR
+/// values, concatenated bank by bank in the order of `selection`.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Execution` if a selected PCR is missing from `pcrs`.
+pub fn quote_pcr_digest(
+    alg: TpmAlgId,
+    selection: &TpmlPcrSelection,
+    pcrs: &PcrOutput,
+) -> Result<Vec<u8>, TpmError> {
+    let mut values = Vec::new();
+    for bank in selection.iter() {
+        let name = tpm_alg_id_to_str(bank.hash);
+        for (byte_index, &byte) in bank.pcr_select.iter().enumerate() {
+            for bit_index in 0..8 {
+                if (byte >> bit_index) & 1 == 0 {
+                    continue;
+                }
+                let pcr = byte_index * 8 + bit_index;
+                let value = pcrs
+                    .banks
+                    .get(name)
+                    .and_then(|bank| bank.get(&pcr.to_string()))
+                    .ok_or_else(|| {
+                        TpmError::Execution(format!("PCR {name}:{pcr} is missing from the input"))
+                    })?;
+                values.push(hex::decode(value)?);
+            }
+        }
+    }
+    let chunks: Vec<&[u8]> = values.iter().map(Vec::as_slice).collect();
+    software_digest(alg, &chunks)
+}
+
+impl SignedAttest {
+    /// Decodes a quote pipeline object.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the hex or the signature is malformed.
+    pub fn from_output(output: &QuoteOutput) -> Result<Self, TpmError> {
+        let attest = hex::decode(&output.attest)?;
+        let signature = hex::decode(&output.signature)?;
+        let (signature, remainder) = TpmtSignature::parse(&signature)?;
+        if !remainder.is_empty() {
+            return Err(TpmError::Parse(
+                "trailing data after the quote signature".to_string(),
+            ));
+        }
+        Ok(Self { attest, signature })
+    }
+
+    /// Encodes the quote as a pipeline object.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the signature cannot be built.
+    pub fn to_output(&self) -> Result<QuoteOutput, TpmError> {
+        Ok(QuoteOutput {
+            attest: hex::encode(&self.attest),
+            signature: hex::encode(build_to_vec(&self.signature)?),
+        })
+    }
+
+    /// Verifies the quote with the public key of the signer: the signature,
+    /// the magic and the type of the attestation, the qualifying nonce and
+    /// the PCR digest against `pcrs`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError::Execution` describing the first failed check.
+    pub fn verify_quote(
+        &self,
+        public: &TpmtPublic,
+        nonce: &[u8],
+        pcrs: &PcrOutput,
+    ) -> Result<TpmsAttest, TpmError> {
+        let expect = AttestExpectations {
+            attest_type: Some(TpmSt::AttestQuote),
+            extra_data: Some(nonce.to_vec()),
+            ..Default::default()
+        };
+        let report = self.verify(public, &expect);
+        if let Some(failure) = report.failures().next() {
+            return Err(TpmError::Execution(match failure {
+                AttestFinding::TypeMismatch { actual, .. } => {
+                    format!("attestation is not a quote: {actual}")
+                }
+                AttestFinding::ExtraDataMismatch { .. } => "quote nonce does not match".to_string(),
+                failure => failure.to_string(),
+            }));
+        }
+        let attest = report.into_result()?;
+        let TpmuAttest::Quote(info) = &attest.attested else {
+            unreachable!()
+        };
+
+        let alg = signature_hash_alg(&self.signature).ok_or_else(|| {
+            TpmError::Execution("quote signature has no hash algorithm".to_string())
+        })?;
+        if quote_pcr_digest(alg, &info.pcr_select, pcrs)? != *info.pcr_digest {
+            return Err(TpmError::Execution(
+                "quote PCR digest does not match the PCR values".to_string(),
+            ));
+        }
+        Ok(attest)
+    }
+}
@@ -0,0 +1,592 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! A userspace resource manager for TPMs accessed without the kernel resource
+//! manager, such as `/dev/tpm0` or a simulator.
+//!
+//! The client sees virtual transient handles. Objects stay loaded until the
+//! TPM runs out of memory, at which point the least recently used objects and
+//! sessions not needed by the current command are saved and flushed, and
+//! loaded back when the client uses them again. Everything the client created
+//! is flushed when the resource manager is dropped.
+
+use crate::{soft_tpm::error_response, TpmDevice, TpmError};
+use std::{
+    collections::BTreeMap,
+    io::{self, Cursor, Read, Write},
+};
+use tpm2_protocol::{
+    data::{
+        TpmCap, TpmCc, TpmHt, TpmRc, TpmRcBase, TpmRcIndex, TpmRh, TpmSt, TpmaSession, TpmlHandle,
+        TpmsAuthCommand, TpmsCapabilityData, TpmsContext, TpmuCapabilities,
+    },
+    message::{
+        tpm_build_command, tpm_build_response, tpm_parse_command, TpmCommand, TpmCommandBody,
+        TpmContextLoadCommand, TpmContextSaveCommand, TpmFlushContextCommand,
+        TpmGetCapabilityResponse, TPM_HEADER_SIZE,
+    },
+    metadata::{tpm_command_metadata, TpmHandleEffect},
+    TpmContextHandle, TpmHandle, TpmParse, TpmWriter, TPM_MAX_COMMAND_SIZE,
+};
+use tracing::debug;
+
+/// The first virtual transient handle given to the client.
+const VIRTUAL_TRANSIENT_FIRST: u32 = TpmRh::TransientFirst as u32 | 0x00ff_0000;
+/// The number of times a command is retried after freeing TPM memory.
+const MAX_EVICTIONS: usize = 64;
+
+/// A transient object owned by the client.
+struct ManagedObject {
+    /// The handle in the TPM, if the object is loaded.
+    handle: Option<u32>,
+    /// The saved context, if the object is not loaded.
+    context: Option<TpmsContext>,
+    last_used: u64,
+}
+
+/// A session owned by the client.
+struct ManagedSession {
+    /// The saved context, if the session is not loaded.
+    context: Option<TpmsContext>,
+    last_used: u64,
+}
+
+/// A resource manager in front of a raw TPM. It implements `Read` and `Write`,
+/// so it can back a `TpmDevice` like the device it wraps.
+pub struct TpmResourceManager {
+    device: TpmDevice,
+    objects: BTreeMap<u32, ManagedObject>,
+    sessions: BTreeMap<u32, ManagedSession>,
+    next_handle: u32,
+    clock: u64,
+    command: Vec<u8>,
+    response: Cursor<Vec<u8>>,
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    buf.get(offset..offset + 4)
+        .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+        .map(u32::from_be_bytes)
+}
+
+fn write_u32(buf: &mut [u8], offset: usize, value: u32) {
+    if let Some(bytes) = buf.get_mut(offset..offset + 4) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn response_rc(response: &[u8]) -> TpmRc {
+    read_u32(response, 6)
+        .and_then(|rc| TpmRc::try_from(rc).ok())
+        .unwrap_or(TpmRc::from(TpmRcBase::Failure))
+}
+
+fn rc_error(base: TpmRcBase) -> TpmError {
+    TpmError::TpmRc(TpmRc::from(base))
+}
+
+fn is_rc(rc: TpmRc, base: TpmRcBase) -> bool {
+    rc.base() == Ok(base)
+}
+
+fn is_session(handle: u32) -> bool {
+    matches!(
+        TpmHt::from_handle(handle),
+        Some(TpmHt::HmacSession | TpmHt::PolicySession)
+    )
+}
+
+fn is_transient(handle: u32) -> bool {
+    TpmHt::from_handle(handle) == Some(TpmHt::Transient)
+}
+
+impl TpmResourceManager {
+    /// Creates a resource manager in front of `device`, which must not have a
+    /// resource manager of its own.
+    #[must_use]
+    pub fn new(device: TpmDevice) -> Self {
+        Self {
+            device,
+            objects: BTreeMap::new(),
+            sessions: BTreeMap::new(),
+            next_handle: VIRTUAL_TRANSIENT_FIRST,
+            clock: 0,
+            command: Vec::new(),
+            response: Cursor::new(Vec::new()),
+        }
+    }
+
+    /// Executes a marshaled command on behalf of the client and returns the
+    /// marshaled response.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if I/O with the TPM fails.
+    pub fn execute(&mut self, command: &[u8]) -> Result<Vec<u8>, TpmError> {
+        match self.dispatch(command) {
+            Ok(response) => Ok(response),
+            Err(TpmError::TpmRc(rc)) => {
+                debug!(rc = %rc, "resource manager rejected command");
+                Ok(error_response(rc))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn dispatch(&mut self, command: &[u8]) -> Result<Vec<u8>, TpmError> {
+        let Some(cc) = read_u32(command, 6).and_then(|cc| TpmCc::try_from(cc).ok()) else {
+            return self.device.transmit(command);
+        };
+        let Some(metadata) = tpm_command_metadata(cc) else {
+            return self.device.transmit(command);
+        };
+        match cc {
+            TpmCc::FlushContext => return self.flush_context(command),
+            TpmCc::GetCapability => {
+                if let Some(response) = self.get_capability(command)? {
+                    return Ok(response);
+                }
+            }
+            _ => {}
+        }
+
+        let mut command = command.to_vec();
+        let handle_count = metadata.handles.len();
+        let mut objects = Vec::new();
+        let mut sessions = Vec::new();
+        for i in 0..handle_count {
+            let offset = TPM_HEADER_SIZE + i * 4;
+            let handle =
+                read_u32(&command, offset).ok_or_else(|| rc_error(TpmRcBase::CommandSize))?;
+            if is_transient(handle) {
+                if !self.objects.contains_key(&handle) {
+                    return Err(TpmError::TpmRc(
+                        TpmRc::from(TpmRcBase::Handle)
+                            .with_index(TpmRcIndex::Handle(u8::try_from(i + 1).unwrap_or(0))),
+                    ));
+                }
+                objects.push((offset, handle));
+            } else if is_session(handle) && self.sessions.contains_key(&handle) {
+                sessions.push(handle);
+            }
+        }
+        let auths = Self::parse_auths(&command, handle_count);
+        sessions.extend(
+            auths
+                .iter()
+                .map(|auth| u32::from(auth.session_handle))
+                .filter(|handle| self.sessions.contains_key(handle)),
+        );
+
+        let mut response = Vec::new();
+        for _ in 0..MAX_EVICTIONS {
+            let pinned: Vec<u32> = objects.iter().map(|&(_, handle)| handle).collect();
+            for &(offset, handle) in &objects {
+                let real = self.load_object(handle, &pinned, &sessions)?;
+                write_u32(&mut command, offset, real);
+            }
+            for &handle in &sessions {
+                self.load_session(handle, &pinned, &sessions)?;
+            }
+
+            response = self.device.transmit(&command)?;
+            let rc = response_rc(&response);
+            if is_rc(rc, TpmRcBase::ContextGap) {
+                self.regap()?;
+            } else if is_rc(rc, TpmRcBase::ObjectMemory)
+                || is_rc(rc, TpmRcBase::SessionMemory)
+                || is_rc(rc, TpmRcBase::Memory)
+            {
+                if !self.evict(&pinned, &sessions)? {
+                    break;
+                }
+            } else {
+                break;
+            }
+        }
+
+        if response_rc(&response).value() == 0 {
+            let handles: Vec<u32> = objects.iter().map(|&(_, handle)| handle).collect();
+            self.complete(cc, metadata.handle_effect, &handles, &auths, &mut response);
+        }
+        Ok(response)
+    }
+
+    /// Returns the sessions in the authorization area of a command.
+    fn parse_auths(command: &[u8], handle_count: usize) -> Vec<TpmsAuthCommand> {
+        let mut auths = Vec::new();
+        if read_u32(command, 0).map(|word| (word >> 16) as u16) != Some(TpmSt::Sessions as u16) {
+            return auths;
+        }
+        let offset = TPM_HEADER_SIZE + handle_count * 4;
+        let Some(size) = read_u32(command, offset) else {
+            return auths;
+        };
+        let Some(mut area) = command.get(offset + 4..offset + 4 + size as usize) else {
+            return auths;
+        };
+        while let Ok((auth, rest)) = TpmsAuthCommand::parse(area) {
+            auths.push(auth);
+            area = rest;
+        }
+        auths
+    }
+
+    /// Updates the managed handles after a successful command.
+    fn complete(
+        &mut self,
+        cc: TpmCc,
+        effect: TpmHandleEffect,
+        handles: &[u32],
+        auths: &[TpmsAuthCommand],
+        response: &mut [u8],
+    ) {
+        let now = self.tick();
+        for auth in auths {
+            let handle = u32::from(auth.session_handle);
+            if !auth
+                .session_attributes
+                .contains(TpmaSession::CONTINUE_SESSION)
+            {
+                self.sessions.remove(&handle);
+            }
+        }
+        match effect {
+            TpmHandleEffect::Creates => {
+                let Some(handle) = read_u32(response, TPM_HEADER_SIZE) else {
+                    return;
+                };
+                if is_transient(handle) {
+                    let virtual_handle = self.allocate_handle();
+                    self.objects.insert(
+                        virtual_handle,
+                        ManagedObject {
+                            handle: Some(handle),
+                            context: None,
+                            last_used: now,
+                        },
+                    );
+                    write_u32(response, TPM_HEADER_SIZE, virtual_handle);
+                } else if is_session(handle) {
+                    self.sessions.insert(
+                        handle,
+                        ManagedSession {
+                            context: None,
+                            last_used: now,
+                        },
+                    );
+                }
+            }
+            TpmHandleEffect::Flushes if cc != TpmCc::FlushContext => {
+                for handle in handles {
+                    self.objects.remove(handle);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn allocate_handle(&mut self) -> u32 {
+        while self.objects.contains_key(&self.next_handle) {
+            self.next_handle = self
+                .next_handle
+                .wrapping_add(1)
+                .max(VIRTUAL_TRANSIENT_FIRST);
+        }
+        let handle = self.next_handle;
+        self.next_handle = self
+            .next_handle
+            .wrapping_add(1)
+            .max(VIRTUAL_TRANSIENT_FIRST);
+        handle
+    }
+
+    /// Ensures that an object is loaded and returns its handle in the TPM.
+    fn load_object(
+        &mut self,
+        virtual_handle: u32,
+        pinned: &[u32],
+        sessions: &[u32],
+    ) -> Result<u32, TpmError> {
+        let now = self.tick();
+        let object = self
+            .objects
+            .get_mut(&virtual_handle)
+            .ok_or_else(|| rc_error(TpmRcBase::Handle))?;
+        object.last_used = now;
+        if let Some(handle) = object.handle {
+            return Ok(handle);
+        }
+        let context = object
+            .context
+            .clone()
+            .ok_or_else(|| rc_error(TpmRcBase::Failure))?;
+        let handle = self.context_load(&context, pinned, sessions)?;
+        if let Some(object) = self.objects.get_mut(&virtual_handle) {
+            object.handle = Some(handle);
+            object.context = None;
+        }
+        Ok(handle)
+    }
+
+    /// Ensures that a session is loaded.
+    fn load_session(
+        &mut self,
+        handle: u32,
+        pinned: &[u32],
+        sessions: &[u32],
+    ) -> Result<(), TpmError> {
+        let now = self.tick();
+        let Some(session) = self.sessions.get_mut(&handle) else {
+            return Ok(());
+        };
+        session.last_used = now;
+        let Some(context) = session.context.clone() else {
+            return Ok(());
+        };
+        self.context_load(&context, pinned, sessions)?;
+        if let Some(session) = self.sessions.get_mut(&handle) {
+            session.context = None;
+        }
+        Ok(())
+    }
+
+    /// Loads a context, freeing TPM memory as needed.
+    fn context_load(
+        &mut self,
+        context: &TpmsContext,
+        pinned: &[u32],
+        sessions: &[u32],
+    ) -> Result<u32, TpmError> {
+        for _ in 0..MAX_EVICTIONS {
+            let cmd = TpmContextLoadCommand {
+                context: context.clone(),
+            };
+            match self.call(&cmd, &[]) {
+                Ok(resp) => return Ok(resp.loaded_handle.into()),
+                Err(TpmError::TpmRc(rc))
+                    if (is_rc(rc, TpmRcBase::ObjectMemory)
+                        || is_rc(rc, TpmRcBase::SessionMemory)
+                        || is_rc(rc, TpmRcBase::Memory))
+                        && self.evict(pinned, sessions)? => {}
+                Err(TpmError::TpmRc(rc)) if is_rc(rc, TpmRcBase::ContextGap) => self.regap()?,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(rc_error(TpmRcBase::ObjectMemory))
+    }
+
+    /// Saves and unloads the least recently used object or session that the
+    /// current command does not need. Returns `false` if there is none.
+    fn evict(&mut self, pinned: &[u32], sessions: &[u32]) -> Result<bool, TpmError> {
+        let object = self
+            .objects
+            .iter()
+            .filter(|(handle, object)| object.handle.is_some() && !pinned.contains(handle))
+            .map(|(&handle, object)| (object.last_used, handle, false));
+        let session = self
+            .sessions
+            .iter()
+            .filter(|(handle, session)| session.context.is_none() && !sessions.contains(handle))
+            .map(|(&handle, session)| (session.last_used, handle, true));
+        let Some((_, handle, is_session)) = object.chain(session).min() else {
+            return Ok(false);
+        };
+        if is_session {
+            debug!(handle = format!("{handle:#010x}"), "saving session");
+            let context = self
+                .call(&TpmContextSaveCommand {}, &[TpmHandle::try_from(handle)?])?
+                .context;
+            if let Some(session) = self.sessions.get_mut(&handle) {
+                session.context = Some(context);
+            }
+        } else if let Some(real) = self.objects.get(&handle).and_then(|object| object.handle) {
+            debug!(handle = format!("{handle:#010x}"), "saving object");
+            let context = self
+                .call(&TpmContextSaveCommand {}, &[TpmHandle::try_from(real)?])?
+                .context;
+            let flush_handle = TpmContextHandle::try_from(real)?;
+            self.call(&TpmFlushContextCommand { flush_handle }, &[])?;
+            if let Some(object) = self.objects.get_mut(&handle) {
+                object.handle = None;
+                object.context = Some(context);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Resolves `TPM_RC_CONTEXT_GAP` by loading and saving again the saved
+    /// sessions, oldest first, which gives them new context sequence numbers.
+    fn regap(&mut self) -> Result<(), TpmError> {
+        let mut saved: Vec<(u64, u32)> = self
+            .sessions
+            .iter()
+            .filter_map(|(&handle, session)| Some((session.context.as_ref()?.sequence, handle)))
+            .collect();
+        saved.sort_unstable();
+        if saved.is_empty() {
+            return Err(rc_error(TpmRcBase::ContextGap));
+        }
+        for (_, handle) in saved {
+            debug!(handle = format!("{handle:#010x}"), "regapping session");
+            let Some(context) = self
+                .sessions
+                .get_mut(&handle)
+                .and_then(|session| session.context.take())
+            else {
+                continue;
+            };
+            self.call(&TpmContextLoadCommand { context }, &[])?;
+            let context = self
+                .call(&TpmContextSaveCommand {}, &[TpmHandle::try_from(handle)?])?
+                .context;
+            if let Some(session) = self.sessions.get_mut(&handle) {
+                session.context = Some(context);
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles `TPM2_FlushContext`, which takes its handle as a parameter.
+    fn flush_context(&mut self, command: &[u8]) -> Result<Vec<u8>, TpmError> {
+        let handle =
+            read_u32(command, TPM_HEADER_SIZE).ok_or_else(|| rc_error(TpmRcBase::CommandSize))?;
+        if is_session(handle) {
+            self.sessions.remove(&handle);
+            return self.device.transmit(command);
+        }
+        if !is_transient(handle) {
+            return self.device.transmit(command);
+        }
+        let object = self.objects.remove(&handle).ok_or_else(|| {
+            TpmError::TpmRc(TpmRc::from(TpmRcBase::Handle).with_index(TpmRcIndex::Parameter(1)))
+        })?;
+        match object.handle {
+            Some(real) => {
+                let mut command = command.to_vec();
+                write_u32(&mut command, TPM_HEADER_SIZE, real);
+                self.device.transmit(&command)
+            }
+            None => Ok(error_response(TpmRc::from(TpmRcBase::Success))),
+        }
+    }
+
+    /// Answers `TPM2_GetCapability` for transient handles with the virtual
+    /// handles. Returns `None` for other capabilities.
+    fn get_capability(&mut self, command: &[u8]) -> Result<Option<Vec<u8>>, TpmError> {
+        let Ok((_, TpmCommandBody::GetCapability(cmd), auths)) = tpm_parse_command(command) else {
+            return Ok(None);
+        };
+        if cmd.cap != TpmCap::Handles || !is_transient(cmd.property) || !auths.is_empty() {
+            return Ok(None);
+        }
+        let mut handles = TpmlHandle::new();
+        let mut remaining = self
+            .objects
+            .keys()
+            .copied()
+            .filter(|&handle| handle >= cmd.property);
+        for handle in remaining.by_ref().take(cmd.property_count as usize) {
+            handles.try_push(handle)?;
+        }
+        let response = TpmGetCapabilityResponse {
+            more_data: remaining.next().is_some().into(),
+            capability_data: TpmsCapabilityData {
+                capability: TpmCap::Handles,
+                data: TpmuCapabilities::Handles(handles),
+            },
+        };
+        let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+        let len = {
+            let mut writer = TpmWriter::new(&mut buf);
+            tpm_build_response(&response, &[], TpmRc::from(TpmRcBase::Success), &mut writer)?;
+            writer.len()
+        };
+        Ok(Some(buf[..len].to_vec()))
+    }
+
+    /// Executes a command of the resource manager itself.
+    fn call<C: TpmCommand>(
+        &mut self,
+        command: &C,
+        handles: &C::Handles,
+    ) -> Result<C::Response, TpmError> {
+        let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+        let len = {
+            let mut writer = TpmWriter::new(&mut buf);
+            tpm_build_command(command, TpmSt::NoSessions, handles, &[], &mut writer)?;
+            writer.len()
+        };
+        let response = self.device.transmit(&buf[..len])?;
+        let rc = response_rc(&response);
+        if rc.value() != 0 {
+            return Err(TpmError::TpmRc(rc));
+        }
+        match tpm2_protocol::message::tpm_parse_response(C::COMMAND, &response)? {
+            Ok((_, body, _)) => C::Response::try_from(body)
+                .map_err(|e| TpmError::UnexpectedResponse(format!("{e:?}"))),
+            Err((rc, _)) => Err(TpmError::TpmRc(rc)),
+        }
+    }
+
+    /// Flushes every object and session created by the client.
+    fn flush_all(&mut self) {
+        let objects: Vec<u32> = self
+            .objects
+            .values()
+            .filter_map(|object| object.handle)
+            .collect();
+        let sessions: Vec<u32> = self.sessions.keys().copied().collect();
+        self.objects.clear();
+        self.sessions.clear();
+        for handle in objects.into_iter().chain(sessions) {
+            let Ok(flush_handle) = TpmContextHandle::try_from(handle) else {
+                continue;
+            };
+            let cmd = TpmFlushContextCommand { flush_handle };
+            if let Err(err) = self.call(&cmd, &[]) {
+                debug!(handle = format!("{handle:#010x}"), error = %err, "flush failed");
+            }
+        }
+    }
+}
+
+impl Drop for TpmResourceManager {
+    fn drop(&mut self) {
+        self.flush_all();
+    }
+}
+
+impl Write for TpmResourceManager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.command.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Executes the buffered command.
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.command.is_empty() {
+            let command = std::mem::take(&mut self.command);
+            let response = self.execute(&command).map_err(|err| match err {
+                TpmError::Io(err) => err,
+                err => io::Error::other(err.to_string()),
+            })?;
+            self.response = Cursor::new(response);
+        }
+        Ok(())
+    }
+}
+
+impl Read for TpmResourceManager {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.command.is_empty() {
+            self.flush()?;
+        }
+        self.response.read(buf)
+    }
+}
@@ -0,0 +1,315 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! Signing with TPM keys and verifying the signatures.
+
+use crate::{
+    build_to_vec, cli, crypto::software_digest, get_auth_sessions, hash::hash_data,
+    quote::signature_hash_alg, AuthSession, TpmDevice, TpmError,
+};
+use pkcs8::der::{asn1::UintRef, Decode, Encode, Header, Length, Reader, SliceReader, Tag};
+use std::str::FromStr;
+use tpm2_protocol::{
+    data::{
+        Tpm2bDigest, Tpm2bEccParameter, Tpm2bPublicKeyRsa, TpmAlgId, TpmRh, TpmSt, TpmaObject,
+        TpmsSignatureEcc, TpmsSignatureRsa, TpmtPublic, TpmtScheme, TpmtSignature, TpmtTkHashcheck,
+        TpmtTkVerified, TpmuAsymScheme, TpmuPublicParms, TpmuSignature,
+    },
+    message::{TpmSignCommand, TpmVerifySignatureCommand},
+    TpmObjectHandle, TpmParse,
+};
+
+/// The encoding of a signature.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SignatureFormat {
+    /// A `TPMT_SIGNATURE`.
+    #[default]
+    Tpm,
+    /// The RSA signature, or `r || s` of an ECDSA signature.
+    Raw,
+    /// An ECDSA signature as a DER encoded `Ecdsa-Sig-Value`.
+    Der,
+}
+
+impl FromStr for SignatureFormat {
+    type Err = TpmError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tpm" => Ok(Self::Tpm),
+            "raw" => Ok(Self::Raw),
+            "der" => Ok(Self::Der),
+            _ => Err(TpmError::Execution(format!(
+                "invalid signature format: {s}"
+            ))),
+        }
+    }
+}
+
+/// Returns the scheme to pass to `TPM2_Sign` for `public` and the hash
+/// algorithm of the signature. A key with a fixed scheme must be given
+/// `TPM_ALG_NULL`, otherwise RSASSA or ECDSA with `hash_alg` is selected.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Execution` if `public` is not an RSA or ECC key, or
+/// if `hash_alg` conflicts with the scheme of the key.
+pub fn signing_scheme(
+    public: &TpmtPublic,
+    hash_alg: Option<TpmAlgId>,
+) -> Result<(TpmtScheme, TpmAlgId), TpmError> {
+    let (scheme, fallback) = match &public.parameters {
+        TpmuPublicParms::Rsa { scheme, .. } => (scheme, TpmAlgId::Rsassa),
+        TpmuPublicParms::Ecc { scheme, .. } => (scheme, TpmAlgId::Ecdsa),
+        _ => {
+            return Err(TpmError::Execution(
+                "signing requires an RSA or ECC key".to_string(),
+            ))
+        }
+    };
+    if scheme.scheme == TpmAlgId::Null {
+        let hash = hash_alg.unwrap_or(TpmAlgId::Sha256);
+        let scheme = TpmtScheme {
+            scheme: fallback,
+            details: TpmuAsymScheme::Hash(hash),
+        };
+        return Ok((scheme, hash));
+    }
+    let TpmuAsymScheme::Hash(hash) = scheme.details else {
+        return Err(TpmError::Execution(format!(
+            "unsupported signing scheme: {}",
+            scheme.scheme
+        )));
+    };
+    match hash_alg {
+        Some(alg) if alg != hash => Err(TpmError::Execution(format!(
+            "the key requires {hash}, not {alg}"
+        ))),
+        _ => Ok((TpmtScheme::default(), hash)),
+    }
+}
+
+/// Hashes `data` for signing with `public`. A restricted key signs only
+/// digests with a hash check ticket, so the data is hashed by the TPM.
+/// Otherwise it is hashed locally and a NULL ticket is returned.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if hashing with the TPM fails.
+pub fn hash_for_signing(
+    chip: &mut TpmDevice,
+    public: &TpmtPublic,
+    data: &[u8],
+    hash_alg: TpmAlgId,
+    log_format: cli::LogFormat,
+) -> Result<(Vec<u8>, TpmtTkHashcheck), TpmError> {
+    if !public.object_attributes.contains(TpmaObject::RESTRICTED) {
+        let ticket = TpmtTkHashcheck {
+            tag: TpmSt::HashCheck,
+            hierarchy: TpmRh::Null,
+            digest: Tpm2bDigest::default(),
+        };
+        return Ok((software_digest(hash_alg, &[data])?, ticket));
+    }
+    hash_data(chip, data, hash_alg, TpmRh::Owner, None, log_format)
+}
+
+/// Signs `digest` with `key` using `TPM2_Sign`.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if the TPM command fails.
+#[allow(clippy::too_many_arguments)]
+pub fn sign_digest(
+    chip: &mut TpmDevice,
+    key: TpmObjectHandle,
+    scheme: TpmtScheme,
+    digest: &[u8],
+    validation: TpmtTkHashcheck,
+    session: Option<&AuthSession>,
+    auth: Option<&str>,
+    log_format: cli::LogFormat,
+) -> Result<TpmtSignature, TpmError> {
+    let cmd = TpmSignCommand {
+        digest: Tpm2bDigest::try_from(digest)?,
+        in_scheme: scheme,
+        validation,
+    };
+    let handles = [key.into()];
+    let sessions = get_auth_sessions(chip, &cmd, &handles, session, auth, log_format)?;
+    let (resp, _) = chip.execute(&cmd, &handles, &sessions, log_format)?;
+    Ok(resp.signature)
+}
+
+/// Verifies a signature over `digest` with `TPM2_VerifySignature` and returns
+/// the verification ticket.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if the signature does not verify.
+pub fn verify_with_tpm(
+    chip: &mut TpmDevice,
+    key: TpmObjectHandle,
+    digest: &[u8],
+    signature: &TpmtSignature,
+    log_format: cli::LogFormat,
+) -> Result<TpmtTkVerified, TpmError> {
+    let cmd = TpmVerifySignatureCommand {
+        digest: Tpm2bDigest::try_from(digest)?,
+        signature: signature.clone(),
+    };
+    let (resp, _) = chip.execute(&cmd, &[key.into()], &[], log_format)?;
+    Ok(resp.validation)
+}
+
+/// Returns the size of the ECC parameters of a curve that can hold both `r`
+/// and `s`.
+fn ecc_parameter_size(sig: &TpmsSignatureEcc) -> usize {
+    let len = sig.signature_r.len().max(sig.signature_s.len());
+    [32, 48, 66]
+        .into_iter()
+        .find(|&size| size >= len)
+        .unwrap_or(len)
+}
+
+fn pad(bytes: &[u8], size: usize) -> Vec<u8> {
+    let mut out = vec![0; size.saturating_sub(bytes.len())];
+    out.extend(bytes);
+    out
+}
+
+/// Encodes a signature.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if the signature cannot be encoded in `format`.
+pub fn encode_signature(
+    signature: &TpmtSignature,
+    format: SignatureFormat,
+) -> Result<Vec<u8>, TpmError> {
+    match (format, &signature.signature) {
+        (SignatureFormat::Tpm, _) => build_to_vec(signature),
+        (SignatureFormat::Raw, TpmuSignature::Rsassa(sig) | TpmuSignature::Rsapss(sig)) => {
+            Ok(sig.sig.to_vec())
+        }
+        (SignatureFormat::Raw, TpmuSignature::Ecdsa(sig)) => {
+            let size = ecc_parameter_size(sig);
+            let mut out = pad(&sig.signature_r, size);
+            out.extend(pad(&sig.signature_s, size));
+            Ok(out)
+        }
+        (SignatureFormat::Der, TpmuSignature::Ecdsa(sig)) => {
+            let mut content = UintRef::new(&sig.signature_r)?.to_der()?;
+            content.extend(UintRef::new(&sig.signature_s)?.to_der()?);
+            let mut out = Header::new(Tag::Sequence, Length::try_from(content.len())?)?.to_der()?;
+            out.extend(content);
+            Ok(out)
+        }
+        (SignatureFormat::Der, _) => Err(TpmError::Execution(
+            "DER format is only for ECDSA signatures".to_string(),
+        )),
+        _ => Err(TpmError::Execution(format!(
+            "unsupported signature scheme: {}",
+            signature.sig_alg
+        ))),
+    }
+}
+
+/// Decodes a signature made with `public`. The scheme and the hash of a raw
+/// or DER signature are those of [`signing_scheme`].
+///
+/// # Errors
+///
+/// Returns a `TpmError` if the signature is malformed.
+pub fn decode_signature(
+    data: &[u8],
+    format: SignatureFormat,
+    public: &TpmtPublic,
+    hash_alg: Option<TpmAlgId>,
+) -> Result<TpmtSignature, TpmError> {
+    if format == SignatureFormat::Tpm {
+        let (signature, remainder) = TpmtSignature::parse(data)?;
+        if !remainder.is_empty() {
+            return Err(TpmError::Parse(
+                "trailing data after the signature".to_string(),
+            ));
+        }
+        return Ok(signature);
+    }
+
+    let (scheme, hash) = signing_scheme(public, hash_alg)?;
+    let sig_alg = if scheme.scheme == TpmAlgId::Null {
+        match &public.parameters {
+            TpmuPublicParms::Rsa { scheme, .. } | TpmuPublicParms::Ecc { scheme, .. } => {
+                scheme.scheme
+            }
+            _ => unreachable!(),
+        }
+    } else {
+        scheme.scheme
+    };
+    let signature = match (sig_alg, format) {
+        (TpmAlgId::Rsassa | TpmAlgId::Rsapss, SignatureFormat::Raw) => {
+            let sig = TpmsSignatureRsa {
+                hash,
+                sig: Tpm2bPublicKeyRsa::try_from(data)?,
+            };
+            if sig_alg == TpmAlgId::Rsassa {
+                TpmuSignature::Rsassa(sig)
+            } else {
+                TpmuSignature::Rsapss(sig)
+            }
+        }
+        (TpmAlgId::Ecdsa, SignatureFormat::Raw) => {
+            if data.len() % 2 != 0 {
+                return Err(TpmError::Parse(
+                    "raw ECDSA signature has an odd length".to_string(),
+                ));
+            }
+            let (r, s) = data.split_at(data.len() / 2);
+            TpmuSignature::Ecdsa(TpmsSignatureEcc {
+                hash,
+                signature_r: Tpm2bEccParameter::try_from(r)?,
+                signature_s: Tpm2bEccParameter::try_from(s)?,
+            })
+        }
+        (TpmAlgId::Ecdsa, SignatureFormat::Der) => {
+            let mut reader = SliceReader::new(data)?;
+            let (r, s) = reader.sequence(|reader| {
+                let r = UintRef::decode(reader)?.as_bytes().to_vec();
+                let s = UintRef::decode(reader)?.as_bytes().to_vec();
+                Ok((r, s))
+            })?;
+            reader.finish(())?;
+            TpmuSignature::Ecdsa(TpmsSignatureEcc {
+                hash,
+                signature_r: Tpm2bEccParameter::try_from(r.as_slice())?,
+                signature_s: Tpm2bEccParameter::try_from(s.as_slice())?,
+            })
+        }
+        (_, SignatureFormat::Der) => {
+            return Err(TpmError::Execution(
+                "DER format is only for ECDSA signatures".to_string(),
+            ))
+        }
+        _ => {
+            return Err(TpmError::Execution(format!(
+                "unsupported signature scheme: {sig_alg}"
+            )))
+        }
+    };
+    Ok(TpmtSignature { sig_alg, signature })
+}
+
+/// Returns the hash algorithm of a signature.
+///
+/// # Errors
+///
+/// Returns a `TpmError::Execution` if the signature has no hash algorithm.
+pub fn signature_hash(signature: &TpmtSignature) -> Result<TpmAlgId, TpmError> {
+    signature_hash_alg(signature).ok_or_else(|| {
+        TpmError::Execution(format!(
+            "unsupported signature scheme: {}",
+            signature.sig_alg
+        ))
+    })
+}
@@ -0,0 +1,2099 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use crate::{
+    build_to_vec,
+    crypto::{hmac, kdfa, kdfe, session_hmac, verify_digest_signature},
+    SoftwareHash, TpmTransport,
+};
+use aes::Aes128;
+use cfb_mode::{Decryptor, Encryptor};
+use cipher::{AsyncStreamCipher, KeyIvInit};
+use p256::{
+    ecdh::diffie_hellman,
+    ecdsa::{signature::hazmat::PrehashSigner, Signature, SigningKey},
+    elliptic_curve::sec1::ToEncodedPoint,
+    PublicKey, SecretKey,
+};
+use rand::{rngs::StdRng, thread_rng, RngCore, SeedableRng};
+use std::{
+    collections::BTreeMap,
+    io::{self, Cursor, Read, Write},
+};
+use tpm2_protocol::{
+    data::{
+        Tpm2b, Tpm2bAuth, Tpm2bCreationData, Tpm2bData, Tpm2bDigest, Tpm2bEccParameter,
+        Tpm2bMaxNvBuffer, Tpm2bName, Tpm2bNonce, Tpm2bNvPublic, Tpm2bPrivate, Tpm2bPublic,
+        Tpm2bSensitive, Tpm2bSensitiveData, Tpm2bTimeout, TpmAlgId, TpmCap, TpmCc, TpmEccCurve,
+        TpmHt, TpmRc, TpmRcBase, TpmRcIndex, TpmRh, TpmSe, TpmSt, TpmSu, TpmaAlgorithm,
+        TpmaLocality, TpmaNv, TpmaObject, TpmaSession, TpmiYesNo, TpmlDigest, TpmlDigestValues,
+        TpmlPcrSelection, TpmsAlgProperty, TpmsAuthCommand, TpmsAuthResponse, TpmsCapabilityData,
+        TpmsContext, TpmsCreationData, TpmsEccPoint, TpmsNvPublic, TpmsPcrSelection,
+        TpmsSensitiveCreate, TpmsSignatureEcc, TpmsTaggedProperty, TpmtHa, TpmtPublic,
+        TpmtSensitive, TpmtSignature, TpmtTkAuth, TpmtTkCreation, TpmtTkHashcheck, TpmtTkVerified,
+        TpmuAsymScheme, TpmuCapabilities, TpmuHa, TpmuPublicId, TpmuPublicParms,
+        TpmuSensitiveComposite, TpmuSignature, TpmuSymKeyBits, MAX_BUFFER_SIZE, MAX_DIGEST_SIZE,
+        MAX_NV_BUFFER_SIZE, TPM_PT_FAMILY_INDICATOR, TPM_PT_HR_TRANSIENT_MIN, TPM_PT_INPUT_BUFFER,
+        TPM_PT_MANUFACTURER, TPM_PT_MAX_COMMAND_SIZE, TPM_PT_MAX_DIGEST, TPM_PT_MAX_RESPONSE_SIZE,
+        TPM_PT_NV_BUFFER_MAX, TPM_PT_PCR_COUNT,
+    },
+    hash::{tpm_cp_hash, tpm_handle_name, tpm_nv_name, tpm_object_name, tpm_rp_hash},
+    message::{
+        tpm_build_response, tpm_parse_command, TpmActivateCredentialCommand,
+        TpmActivateCredentialResponse, TpmAuthCommands, TpmCommandBody, TpmContextLoadCommand,
+        TpmContextLoadResponse, TpmContextSaveResponse, TpmCreateCommand, TpmCreatePrimaryCommand,
+        TpmCreatePrimaryResponse, TpmCreateResponse, TpmEventSequenceCompleteCommand,
+        TpmEventSequenceCompleteResponse, TpmFlushContextCommand, TpmFlushContextResponse,
+        TpmGetCapabilityCommand, TpmGetCapabilityResponse, TpmGetRandomCommand,
+        TpmGetRandomResponse, TpmHandles, TpmHashCommand, TpmHashResponse,
+        TpmHashSequenceStartCommand, TpmHashSequenceStartResponse, TpmHeader, TpmLoadCommand,
+        TpmLoadResponse, TpmNvDefineSpaceCommand, TpmNvDefineSpaceResponse, TpmNvReadCommand,
+        TpmNvReadPublicResponse, TpmNvReadResponse, TpmNvUndefineSpaceResponse, TpmNvWriteCommand,
+        TpmNvWriteResponse, TpmPcrEventCommand, TpmPcrEventResponse, TpmPcrExtendCommand,
+        TpmPcrExtendResponse, TpmPcrReadCommand, TpmPcrReadResponse, TpmPolicySecretCommand,
+        TpmPolicySecretResponse, TpmReadPublicResponse, TpmSelfTestResponse,
+        TpmSequenceCompleteCommand, TpmSequenceCompleteResponse, TpmSequenceUpdateCommand,
+        TpmSequenceUpdateResponse, TpmShutdownResponse, TpmSignCommand, TpmSignResponse,
+        TpmStartAuthSessionCommand, TpmStartAuthSessionResponse, TpmStartupCommand,
+        TpmStartupResponse, TpmUnsealResponse, TpmVerifySignatureCommand,
+        TpmVerifySignatureResponse, TPM_HEADER_SIZE,
+    },
+    metadata::{tpm_command_metadata, TpmAuthRole},
+    tpm_hash_size, TpmBuffer, TpmContextHandle, TpmErrorKind, TpmList, TpmParse, TpmParseTagged,
+    TpmProtocolError, TpmSession, TpmTransient, TPM_MAX_COMMAND_SIZE,
+};
+
+/// The number of PCRs in each bank.
+const PCR_COUNT: usize = 24;
+/// The allocated PCR banks.
+const PCR_BANKS: [TpmAlgId; 2] = [TpmAlgId::Sha1, TpmAlgId::Sha256];
+/// The number of transient objects that can be loaded at the same time.
+const MAX_OBJECTS: usize = 16;
+/// The number of sessions that can be loaded at the same time.
+const MAX_SESSIONS: usize = 8;
+/// The largest accepted NV index.
+const MAX_NV_INDEX_SIZE: u16 = 2048;
+/// The value that marks data as generated by the TPM, which must not receive a
+/// hash check ticket.
+const TPM_GENERATED_VALUE: u32 = 0xff54_4347;
+
+const HMAC_SESSION_FIRST: u32 = (TpmHt::HmacSession as u32) << 24;
+const POLICY_SESSION_FIRST: u32 = (TpmHt::PolicySession as u32) << 24;
+const TRANSIENT_FIRST: u32 = TpmRh::TransientFirst as u32;
+const HANDLE_INDEX_MASK: u32 = 0x00ff_ffff;
+
+const PERMANENT_HANDLES: [TpmRh; 7] = [
+    TpmRh::Owner,
+    TpmRh::Null,
+    TpmRh::Password,
+    TpmRh::Lockout,
+    TpmRh::Endorsement,
+    TpmRh::Platform,
+    TpmRh::PlatformNv,
+];
+
+const ALGORITHMS: [(TpmAlgId, TpmaAlgorithm); 12] = [
+    (TpmAlgId::Sha1, TpmaAlgorithm::HASH),
+    (TpmAlgId::Hmac, TpmaAlgorithm::from_bits_truncate(0x0104)),
+    (TpmAlgId::Aes, TpmaAlgorithm::SYMMETRIC),
+    (
+        TpmAlgId::KeyedHash,
+        TpmaAlgorithm::from_bits_truncate(0x030C),
+    ),
+    (TpmAlgId::Sha256, TpmaAlgorithm::HASH),
+    (TpmAlgId::Sha384, TpmaAlgorithm::HASH),
+    (TpmAlgId::Sha512, TpmaAlgorithm::HASH),
+    (TpmAlgId::Null, TpmaAlgorithm::empty()),
+    (TpmAlgId::Ecdsa, TpmaAlgorithm::from_bits_truncate(0x0101)),
+    (TpmAlgId::Ecdh, TpmaAlgorithm::from_bits_truncate(0x0401)),
+    (TpmAlgId::Ecc, TpmaAlgorithm::from_bits_truncate(0x0009)),
+    (TpmAlgId::Cfb, TpmaAlgorithm::from_bits_truncate(0x0202)),
+];
+
+type SoftResult<T> = Result<T, TpmRc>;
+
+fn rc(base: TpmRcBase) -> TpmRc {
+    TpmRc::from(base)
+}
+
+fn rc_handle(base: TpmRcBase, n: usize) -> TpmRc {
+    rc(base).with_index(TpmRcIndex::Handle(u8::try_from(n).unwrap_or(0)))
+}
+
+fn rc_param(base: TpmRcBase, n: usize) -> TpmRc {
+    rc(base).with_index(TpmRcIndex::Parameter(u8::try_from(n).unwrap_or(0)))
+}
+
+fn rc_session(base: TpmRcBase, n: usize) -> TpmRc {
+    rc(base).with_index(TpmRcIndex::Session(u8::try_from(n).unwrap_or(0)))
+}
+
+fn failure<E>(_: E) -> TpmRc {
+    rc(TpmRcBase::Failure)
+}
+
+fn parse_rc(err: &TpmProtocolError) -> TpmRc {
+    match err.kind() {
+        TpmErrorKind::InvalidDiscriminant {
+            type_name: "TpmCc", ..
+        } => rc(TpmRcBase::CommandCode),
+        TpmErrorKind::InvalidDiscriminant {
+            type_name: "TpmSt", ..
+        }
+        | TpmErrorKind::InvalidTag { .. } => rc(TpmRcBase::BadTag),
+        TpmErrorKind::Boundary | TpmErrorKind::TrailingData => rc(TpmRcBase::CommandSize),
+        TpmErrorKind::ValueTooLarge | TpmErrorKind::CapacityExceeded => rc(TpmRcBase::Size),
+        _ => rc(TpmRcBase::Value),
+    }
+}
+
+fn digest(alg: TpmAlgId, chunks: &[&[u8]]) -> SoftResult<Vec<u8>> {
+    use tpm2_protocol::hash::TpmHashProvider;
+    let mut out = [0u8; MAX_DIGEST_SIZE];
+    let len = SoftwareHash
+        .digest(alg, chunks, &mut out)
+        .map_err(|_| rc(TpmRcBase::Hash))?;
+    Ok(out[..len].to_vec())
+}
+
+fn hash_size(alg: TpmAlgId, rc: TpmRc) -> SoftResult<usize> {
+    tpm_hash_size(&alg).ok_or(rc)
+}
+
+fn to_bits(bytes: usize) -> SoftResult<u16> {
+    u16::try_from(bytes * 8).map_err(failure)
+}
+
+fn aes_cfb(key: &[u8], data: &mut [u8], encrypt: bool) -> SoftResult<()> {
+    let iv = [0u8; 16];
+    if encrypt {
+        Encryptor::<Aes128>::new_from_slices(key, &iv)
+            .map_err(failure)?
+            .encrypt(data);
+    } else {
+        Decryptor::<Aes128>::new_from_slices(key, &iv)
+            .map_err(failure)?
+            .decrypt(data);
+    }
+    Ok(())
+}
+
+/// Strips the handle area and, when present, the `parameterSize` field from a
+/// marshaled response body.
+fn response_parameters<R: TpmHeader>(body: &[u8]) -> &[u8] {
+    let params = body.get(R::HANDLES * 4..).unwrap_or_default();
+    match params
+        .get(..4)
+        .and_then(|size| <[u8; 4]>::try_from(size).ok())
+    {
+        Some(size) if u32::from_be_bytes(size) as usize == params.len() - 4 => &params[4..],
+        _ => params,
+    }
+}
+
+/// Fills a list with at most `count` items and reports whether items were
+/// left over.
+fn fill_list<T: Copy + Default, const N: usize>(
+    items: impl Iterator<Item = T>,
+    count: usize,
+) -> (bool, TpmList<T, N>) {
+    let mut list = TpmList::new();
+    let mut items = items.peekable();
+    while list.len() < count {
+        let Some(&item) = items.peek() else {
+            break;
+        };
+        if list.try_push(item).is_err() {
+            break;
+        }
+        items.next();
+    }
+    (items.peek().is_some(), list)
+}
+
+/// Builds a response that carries only a response code.
+pub(crate) fn error_response(rc: TpmRc) -> Vec<u8> {
+    let mut response = Vec::with_capacity(TPM_HEADER_SIZE);
+    response.extend_from_slice(&(TpmSt::NoSessions as u16).to_be_bytes());
+    response.extend_from_slice(
+        &u32::try_from(TPM_HEADER_SIZE)
+            .unwrap_or_default()
+            .to_be_bytes(),
+    );
+    response.extend_from_slice(&rc.value().to_be_bytes());
+    response
+}
+
+fn is_storage_parent(public: &TpmtPublic) -> bool {
+    public
+        .object_attributes
+        .contains(TpmaObject::RESTRICTED | TpmaObject::DECRYPT)
+        && !public.object_attributes.contains(TpmaObject::SIGN_ENCRYPT)
+}
+
+#[derive(Clone)]
+struct SoftObject {
+    public: TpmtPublic,
+    sensitive: TpmtSensitive,
+    name: Tpm2bName,
+    hierarchy: TpmRh,
+}
+
+/// A hash or event sequence. The data is kept until the sequence completes.
+struct SoftSequence {
+    /// The hash algorithm, or `TPM_ALG_NULL` for an event sequence.
+    hash_alg: TpmAlgId,
+    auth: Tpm2bAuth,
+    data: Vec<u8>,
+}
+
+struct SoftNvIndex {
+    public: TpmsNvPublic,
+    auth: Tpm2bAuth,
+    data: Vec<u8>,
+}
+
+struct SoftSession {
+    auth_hash: TpmAlgId,
+    nonce_tpm: Tpm2bNonce,
+    session_key: Vec<u8>,
+    bind: Option<Tpm2bName>,
+    /// The `policyDigest` of a policy session, `None` for an HMAC session.
+    policy_digest: Option<Vec<u8>>,
+}
+
+/// An authorization checked for a command, kept for building the response
+/// authorization.
+enum SessionUse {
+    Password,
+    Hmac {
+        handle: u32,
+        nonce_caller: Tpm2bNonce,
+        attributes: TpmaSession,
+        hmac_key: Vec<u8>,
+    },
+    Policy {
+        handle: u32,
+        attributes: TpmaSession,
+    },
+}
+
+/// An in-process software TPM implementing a functional subset of TPM 2.0.
+///
+/// It implements `Read` and `Write`, so it can back a `TpmDevice` in place of a
+/// character device. The supported commands are `Startup`, `Shutdown`,
+/// `SelfTest`, `GetCapability`, `GetRandom`, `Hash`, the hash and event
+/// sequence commands, `PCR_Read`, `PCR_Extend`, `PCR_Event`, the ordinary NV
+/// index commands, `CreatePrimary`, `Create` and `Load` of keyed hash and
+/// NIST P-256 objects, `Unseal`, `ReadPublic`,
+/// `ActivateCredential`, `Sign` and `VerifySignature` with ECDSA,
+/// `ContextSave`, `ContextLoad`, `FlushContext`, `StartAuthSession` for
+/// unsalted HMAC and policy sessions, and `PolicySecret`. Other commands
+/// return `TPM_RC_COMMAND_CODE`.
+///
+/// The hierarchies have an empty authorization value. All state is kept in
+/// memory and lost when the value is dropped.
+pub struct SoftTpm {
+    rng: StdRng,
+    primary_seed: [u8; 32],
+    started: bool,
+    pcrs: Vec<(TpmAlgId, Vec<Vec<u8>>)>,
+    pcr_update_counter: u32,
+    objects: BTreeMap<u32, SoftObject>,
+    sequences: BTreeMap<u32, SoftSequence>,
+    sessions: BTreeMap<u32, SoftSession>,
+    nv: BTreeMap<u32, SoftNvIndex>,
+    context_sequence: u64,
+    saved_objects: BTreeMap<u64, SoftObject>,
+    saved_sessions: BTreeMap<u32, (u64, SoftSession)>,
+    command: Vec<u8>,
+    response: Cursor<Vec<u8>>,
+}
+
+impl Default for SoftTpm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SoftTpm {
+    /// Creates a TPM with a random primary seed.
+    #[must_use]
+    pub fn new() -> Self {
+        let mut seed = [0u8; 32];
+        thread_rng().fill_bytes(&mut seed);
+        Self::from_seed(seed)
+    }
+
+    /// Creates a TPM whose primary objects and random numbers are determined
+    /// by `seed`, which makes test runs reproducible.
+    #[must_use]
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let mut tpm = Self {
+            rng: StdRng::from_seed(seed),
+            primary_seed: seed,
+            started: false,
+            pcrs: Vec::new(),
+            pcr_update_counter: 0,
+            objects: BTreeMap::new(),
+            sequences: BTreeMap::new(),
+            sessions: BTreeMap::new(),
+            nv: BTreeMap::new(),
+            context_sequence: 0,
+            saved_objects: BTreeMap::new(),
+            saved_sessions: BTreeMap::new(),
+            command: Vec::new(),
+            response: Cursor::new(Vec::new()),
+        };
+        tpm.reset_pcrs();
+        tpm
+    }
+
+    /// Wraps the TPM into a transport for `TpmDevice::from_transport`.
+    #[must_use]
+    pub fn into_transport(self) -> Box<dyn TpmTransport> {
+        Box::new(self)
+    }
+
+    /// Executes a marshaled command and returns the marshaled response.
+    pub fn execute(&mut self, command: &[u8]) -> Vec<u8> {
+        match self.dispatch(command) {
+            Ok(response) => response,
+            Err(rc) => {
+                tracing::debug!(rc = %rc, "soft TPM command failed");
+                error_response(rc)
+            }
+        }
+    }
+
+    fn reset_pcrs(&mut self) {
+        self.pcrs = PCR_BANKS
+            .iter()
+            .map(|&alg| {
+                let size = tpm_hash_size(&alg).unwrap_or_default();
+                (alg, vec![vec![0u8; size]; PCR_COUNT])
+            })
+            .collect();
+        self.pcr_update_counter = 0;
+    }
+
+    fn random(&mut self, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; len];
+        self.rng.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    /// Derives a secret bound to this TPM instance.
+    fn proof(&self, label: &str, context: &[u8]) -> SoftResult<Vec<u8>> {
+        kdfa(
+            TpmAlgId::Sha256,
+            &self.primary_seed,
+            label,
+            context,
+            &[],
+            256,
+        )
+        .map_err(failure)
+    }
+
+    fn dispatch(&mut self, command: &[u8]) -> SoftResult<Vec<u8>> {
+        let (handles, body, auths) = tpm_parse_command(command).map_err(|e| parse_rc(&e))?;
+        let cc_bytes = command
+            .get(6..10)
+            .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+            .ok_or(rc(TpmRcBase::CommandSize))?;
+        let cc = TpmCc::try_from(u32::from_be_bytes(cc_bytes))
+            .map_err(|()| rc(TpmRcBase::CommandCode))?;
+        if !self.started && cc != TpmCc::Startup {
+            return Err(rc(TpmRcBase::Initialize));
+        }
+        let uses = self.authorize(cc, &handles, &auths, command)?;
+
+        macro_rules! respond {
+            ($result:expr) => {{
+                let response = $result?;
+                self.respond(&response, &uses)
+            }};
+        }
+
+        match body {
+            TpmCommandBody::Startup(cmd) => respond!(self.startup(cmd)),
+            TpmCommandBody::Shutdown(_) => respond!(SoftResult::Ok(TpmShutdownResponse {})),
+            TpmCommandBody::SelfTest(_) => respond!(SoftResult::Ok(TpmSelfTestResponse {})),
+            TpmCommandBody::GetCapability(cmd) => respond!(self.get_capability(&cmd)),
+            TpmCommandBody::GetRandom(cmd) => respond!(self.get_random(cmd)),
+            TpmCommandBody::Hash(cmd) => respond!(self.hash(&cmd)),
+            TpmCommandBody::HashSequenceStart(cmd) => respond!(self.hash_sequence_start(&cmd)),
+            TpmCommandBody::SequenceUpdate(cmd) => {
+                respond!(self.sequence_update(handles[0], &cmd))
+            }
+            TpmCommandBody::SequenceComplete(cmd) => {
+                respond!(self.sequence_complete(handles[0], &cmd))
+            }
+            TpmCommandBody::EventSequenceComplete(cmd) => {
+                respond!(self.event_sequence_complete(&handles, &cmd))
+            }
+            TpmCommandBody::PcrRead(cmd) => respond!(self.pcr_read(&cmd)),
+            TpmCommandBody::PcrExtend(cmd) => respond!(self.pcr_extend(handles[0], &cmd)),
+            TpmCommandBody::PcrEvent(cmd) => respond!(self.pcr_event(handles[0], &cmd)),
+            TpmCommandBody::NvDefineSpace(cmd) => {
+                respond!(self.nv_define_space(handles[0], &cmd))
+            }
+            TpmCommandBody::NvUndefineSpace(_) => respond!(self.nv_undefine_space(&handles)),
+            TpmCommandBody::NvWrite(cmd) => respond!(self.nv_write(&handles, &cmd)),
+            TpmCommandBody::NvRead(cmd) => respond!(self.nv_read(&handles, cmd)),
+            TpmCommandBody::NvReadPublic(_) => respond!(self.nv_read_public(handles[0])),
+            TpmCommandBody::CreatePrimary(cmd) => {
+                respond!(self.create_primary(handles[0], &cmd))
+            }
+            TpmCommandBody::Create(cmd) => respond!(self.create(handles[0], &cmd)),
+            TpmCommandBody::Load(cmd) => respond!(self.load(handles[0], &cmd)),
+            TpmCommandBody::Unseal(_) => respond!(self.unseal(handles[0])),
+            TpmCommandBody::ReadPublic(_) => respond!(self.read_public(handles[0])),
+            TpmCommandBody::ContextSave(_) => respond!(self.context_save(handles[0])),
+            TpmCommandBody::ContextLoad(cmd) => respond!(self.context_load(&cmd)),
+            TpmCommandBody::FlushContext(cmd) => respond!(self.flush_context(cmd)),
+            TpmCommandBody::StartAuthSession(cmd) => {
+                respond!(self.start_auth_session(&handles, &cmd))
+            }
+            TpmCommandBody::PolicySecret(cmd) => respond!(self.policy_secret(&handles, &cmd)),
+            TpmCommandBody::ActivateCredential(cmd) => {
+                respond!(self.activate_credential(&handles, &cmd))
+            }
+            TpmCommandBody::Sign(cmd) => respond!(self.sign(handles[0], &cmd)),
+            TpmCommandBody::VerifySignature(cmd) => {
+                respond!(self.verify_signature(handles[0], &cmd))
+            }
+            _ => Err(rc(TpmRcBase::CommandCode)),
+        }
+    }
+
+    /// Returns the Name of a handle in the handle area. A sequence object has
+    /// an empty Name.
+    fn name_of(&self, handle: u32) -> Option<Tpm2bName> {
+        if let Ok(name) = tpm_handle_name(handle) {
+            return Some(name);
+        }
+        match TpmHt::from_handle(handle)? {
+            TpmHt::Transient => match self.objects.get(&handle) {
+                Some(object) => Some(object.name),
+                None => self
+                    .sequences
+                    .contains_key(&handle)
+                    .then(Tpm2bName::default),
+            },
+            TpmHt::NvIndex => {
+                let index = self.nv.get(&handle)?;
+                tpm_nv_name(&SoftwareHash, &index.public).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the `authValue` of an entity.
+    fn auth_value_of(&self, handle: u32) -> Option<Vec<u8>> {
+        match TpmHt::from_handle(handle)? {
+            TpmHt::Pcr => Some(Vec::new()),
+            TpmHt::Permanent => PERMANENT_HANDLES
+                .iter()
+                .any(|&rh| rh as u32 == handle)
+                .then(Vec::new),
+            TpmHt::Transient => match self.objects.get(&handle) {
+                Some(object) => Some(object.sensitive.auth_value.to_vec()),
+                None => self
+                    .sequences
+                    .get(&handle)
+                    .map(|sequence| sequence.auth.to_vec()),
+            },
+            TpmHt::NvIndex => self.nv.get(&handle).map(|index| index.auth.to_vec()),
+            _ => None,
+        }
+    }
+
+    /// Checks the authorization sessions of a command against the handles
+    /// that require authorization.
+    fn authorize(
+        &self,
+        cc: TpmCc,
+        handles: &TpmHandles,
+        auths: &TpmAuthCommands,
+        command: &[u8],
+    ) -> SoftResult<Vec<SessionUse>> {
+        let metadata = tpm_command_metadata(cc).ok_or(rc(TpmRcBase::CommandCode))?;
+        let auth_handles: Vec<(usize, u32, TpmAuthRole)> = metadata
+            .handles
+            .iter()
+            .zip(handles.iter())
+            .enumerate()
+            .filter(|(_, (role, _))| **role != TpmAuthRole::None)
+            .map(|(i, (&role, &handle))| (i, handle, role))
+            .collect();
+        if auths.len() < auth_handles.len() {
+            return Err(rc(TpmRcBase::AuthMissing));
+        }
+        if auths.len() > auth_handles.len() {
+            return Err(rc(TpmRcBase::AuthContext));
+        }
+        if auths.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = Vec::with_capacity(handles.len());
+        for (i, &handle) in handles.iter().enumerate() {
+            names.push(
+                self.name_of(handle)
+                    .ok_or(rc_handle(TpmRcBase::Handle, i + 1))?,
+            );
+        }
+        let auth_size_offset = TPM_HEADER_SIZE + handles.len() * 4;
+        let auth_size = command
+            .get(auth_size_offset..auth_size_offset + 4)
+            .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+            .map(u32::from_be_bytes)
+            .ok_or(rc(TpmRcBase::AuthSize))?;
+        let parameters = command
+            .get(auth_size_offset + 4 + auth_size as usize..)
+            .ok_or(rc(TpmRcBase::AuthSize))?;
+
+        let mut uses = Vec::with_capacity(auths.len());
+        for (i, (auth, &(handle_index, handle, role))) in
+            auths.iter().zip(&auth_handles).enumerate()
+        {
+            uses.push(self.check_session(
+                i + 1,
+                auth,
+                (handle_index, handle, role),
+                cc,
+                &names,
+                parameters,
+            )?);
+        }
+        Ok(uses)
+    }
+
+    /// Returns true if an object requires a policy session for `role`: the
+    /// user role without `userWithAuth`, the admin role with
+    /// `adminWithPolicy`, or the duplication role.
+    fn policy_required(&self, handle: u32, role: TpmAuthRole) -> bool {
+        let Some(object) = self.objects.get(&handle) else {
+            return false;
+        };
+        let attributes = object.public.object_attributes;
+        match role {
+            TpmAuthRole::User => !attributes.contains(TpmaObject::USER_WITH_AUTH),
+            TpmAuthRole::Admin => attributes.contains(TpmaObject::ADMIN_WITH_POLICY),
+            TpmAuthRole::Dup => true,
+            TpmAuthRole::None => false,
+        }
+    }
+
+    fn check_session(
+        &self,
+        session_index: usize,
+        auth: &TpmsAuthCommand,
+        (handle_index, handle, role): (usize, u32, TpmAuthRole),
+        cc: TpmCc,
+        names: &[Tpm2bName],
+        parameters: &[u8],
+    ) -> SoftResult<SessionUse> {
+        let unsupported = TpmaSession::DECRYPT | TpmaSession::ENCRYPT | TpmaSession::AUDIT;
+        if auth.session_attributes.intersects(unsupported) {
+            return Err(rc_session(TpmRcBase::Attributes, session_index));
+        }
+        let auth_value = self
+            .auth_value_of(handle)
+            .ok_or(rc_handle(TpmRcBase::Handle, handle_index + 1))?;
+
+        let session_handle = u32::from(auth.session_handle);
+        let session = self.sessions.get(&session_handle);
+        if let Some(policy_digest) = session.and_then(|session| session.policy_digest.as_ref()) {
+            let auth_policy = self
+                .objects
+                .get(&handle)
+                .map(|object| object.public.auth_policy.to_vec())
+                .unwrap_or_default();
+            if auth_policy.is_empty() || *policy_digest != auth_policy {
+                return Err(rc_session(TpmRcBase::PolicyFail, session_index));
+            }
+            return Ok(SessionUse::Policy {
+                handle: session_handle,
+                attributes: auth.session_attributes,
+            });
+        }
+        if self.policy_required(handle, role) {
+            return Err(rc_handle(TpmRcBase::AuthUnavailable, handle_index + 1));
+        }
+        if session_handle == TpmRh::Password as u32 {
+            if !auth.nonce.is_empty() {
+                return Err(rc_session(TpmRcBase::Nonce, session_index));
+            }
+            if *auth.hmac != *auth_value {
+                return Err(rc_session(TpmRcBase::AuthFail, session_index));
+            }
+            return Ok(SessionUse::Password);
+        }
+
+        let session = session.ok_or(rc_session(TpmRcBase::Handle, session_index))?;
+        let mut hmac_key = session.session_key.clone();
+        if session.bind.as_ref() != Some(&names[handle_index]) {
+            hmac_key.extend_from_slice(&auth_value);
+        }
+        let names: Vec<&[u8]> = names.iter().map(|name| &name[..]).collect();
+        let cp_hash = tpm_cp_hash(&SoftwareHash, session.auth_hash, cc, &names, parameters)
+            .map_err(failure)?;
+        let expected = session_hmac(
+            session.auth_hash,
+            &hmac_key,
+            auth.session_attributes.bits(),
+            &auth.nonce,
+            &session.nonce_tpm,
+            &cp_hash,
+        )
+        .map_err(failure)?;
+        if expected != *auth.hmac {
+            return Err(rc_session(TpmRcBase::AuthFail, session_index));
+        }
+        Ok(SessionUse::Hmac {
+            handle: session_handle,
+            nonce_caller: auth.nonce,
+            attributes: auth.session_attributes,
+            hmac_key,
+        })
+    }
+
+    /// Marshals a successful response with its authorization area, rolling
+    /// `nonceTPM` of each session that was used. A policy session without
+    /// an `authValue` assertion has an empty response HMAC, and its
+    /// `policyDigest` is reset for the next command.
+    fn respond<R: TpmHeader>(&mut self, response: &R, uses: &[SessionUse]) -> SoftResult<Vec<u8>> {
+        let success = rc(TpmRcBase::Success);
+        let body = build_to_vec(response).map_err(failure)?;
+        let parameters = response_parameters::<R>(&body);
+
+        let mut auths = Vec::with_capacity(uses.len());
+        for session_use in uses {
+            let auth = match session_use {
+                SessionUse::Password => TpmsAuthResponse {
+                    nonce: Tpm2bNonce::default(),
+                    session_attributes: TpmaSession::CONTINUE_SESSION,
+                    hmac: Tpm2bAuth::default(),
+                },
+                SessionUse::Hmac {
+                    handle,
+                    nonce_caller,
+                    attributes,
+                    hmac_key,
+                } => {
+                    let auth_hash = self
+                        .sessions
+                        .get(handle)
+                        .ok_or(rc(TpmRcBase::Failure))?
+                        .auth_hash;
+                    let nonce = self.random(hash_size(auth_hash, rc(TpmRcBase::Failure))?);
+                    let nonce_tpm = Tpm2bNonce::try_from(nonce.as_slice()).map_err(failure)?;
+                    let rp_hash =
+                        tpm_rp_hash(&SoftwareHash, auth_hash, success, R::COMMAND, parameters)
+                            .map_err(failure)?;
+                    let hmac = session_hmac(
+                        auth_hash,
+                        hmac_key,
+                        attributes.bits(),
+                        &nonce_tpm,
+                        nonce_caller,
+                        &rp_hash,
+                    )
+                    .map_err(failure)?;
+                    if attributes.contains(TpmaSession::CONTINUE_SESSION) {
+                        if let Some(session) = self.sessions.get_mut(handle) {
+                            session.nonce_tpm = nonce_tpm;
+                        }
+                    } else {
+                        self.sessions.remove(handle);
+                    }
+                    TpmsAuthResponse {
+                        nonce: nonce_tpm,
+                        session_attributes: *attributes,
+                        hmac: Tpm2bAuth::try_from(hmac.as_slice()).map_err(failure)?,
+                    }
+                }
+                SessionUse::Policy { handle, attributes } => {
+                    let session = self.sessions.get(handle).ok_or(rc(TpmRcBase::Failure))?;
+                    let size = hash_size(session.auth_hash, rc(TpmRcBase::Failure))?;
+                    let nonce_tpm =
+                        Tpm2bNonce::try_from(self.random(size).as_slice()).map_err(failure)?;
+                    if attributes.contains(TpmaSession::CONTINUE_SESSION) {
+                        if let Some(session) = self.sessions.get_mut(handle) {
+                            session.nonce_tpm = nonce_tpm;
+                            session.policy_digest = Some(vec![0u8; size]);
+                        }
+                    } else {
+                        self.sessions.remove(handle);
+                    }
+                    TpmsAuthResponse {
+                        nonce: nonce_tpm,
+                        session_attributes: *attributes,
+                        hmac: Tpm2bAuth::default(),
+                    }
+                }
+            };
+            auths.push(auth);
+        }
+
+        let mut buf = vec![0u8; TPM_MAX_COMMAND_SIZE];
+        let len = {
+            let mut writer = tpm2_protocol::TpmWriter::new(&mut buf);
+            tpm_build_response(response, &auths, success, &mut writer).map_err(failure)?;
+            writer.len()
+        };
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    fn startup(&mut self, cmd: TpmStartupCommand) -> SoftResult<TpmStartupResponse> {
+        if self.started {
+            return Err(rc(TpmRcBase::Initialize));
+        }
+        if cmd.startup_type == TpmSu::Clear {
+            self.reset_pcrs();
+            self.objects.clear();
+            self.sequences.clear();
+            self.sessions.clear();
+        }
+        self.started = true;
+        Ok(TpmStartupResponse {})
+    }
+
+    fn get_capability(
+        &self,
+        cmd: &TpmGetCapabilityCommand,
+    ) -> SoftResult<TpmGetCapabilityResponse> {
+        let count = cmd.property_count as usize;
+        let (more_data, data) = match cmd.cap {
+            TpmCap::Algs => {
+                let algs = ALGORITHMS
+                    .iter()
+                    .filter(|(alg, _)| u32::from(*alg as u16) >= cmd.property)
+                    .map(|&(alg, alg_properties)| TpmsAlgProperty {
+                        alg,
+                        alg_properties,
+                    });
+                let (more_data, list) = fill_list(algs, count);
+                (more_data, TpmuCapabilities::Algs(list))
+            }
+            TpmCap::Handles => {
+                let handles = self
+                    .handles_of_type(cmd.property)
+                    .into_iter()
+                    .filter(|&handle| handle >= cmd.property);
+                let (more_data, list) = fill_list(handles, count);
+                (more_data, TpmuCapabilities::Handles(list))
+            }
+            TpmCap::Pcrs => (false, TpmuCapabilities::Pcrs(Self::all_pcrs()?)),
+            TpmCap::TpmProperties => {
+                let properties = Self::properties()?
+                    .into_iter()
+                    .filter(|&(property, _)| property >= cmd.property)
+                    .map(|(property, value)| TpmsTaggedProperty { property, value });
+                let (more_data, list) = fill_list(properties, count);
+                (more_data, TpmuCapabilities::TpmProperties(list))
+            }
+            TpmCap::Commands => return Err(rc_param(TpmRcBase::Value, 1)),
+        };
+        Ok(TpmGetCapabilityResponse {
+            more_data: TpmiYesNo::from(more_data),
+            capability_data: TpmsCapabilityData {
+                capability: cmd.cap,
+                data,
+            },
+        })
+    }
+
+    fn handles_of_type(&self, property: u32) -> Vec<u32> {
+        match TpmHt::from_handle(property) {
+            Some(TpmHt::Pcr) => (0u32..).take(PCR_COUNT).collect(),
+            Some(TpmHt::NvIndex) => self.nv.keys().copied().collect(),
+            Some(TpmHt::HmacSession) => self.sessions.keys().copied().collect(),
+            Some(TpmHt::Transient) => self.objects.keys().copied().collect(),
+            Some(TpmHt::Permanent) => {
+                let mut handles: Vec<u32> = PERMANENT_HANDLES.iter().map(|&rh| rh as u32).collect();
+                handles.sort_unstable();
+                handles
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn all_pcrs() -> SoftResult<TpmlPcrSelection> {
+        let mut list = TpmlPcrSelection::new();
+        let select = [0xffu8; PCR_COUNT / 8];
+        for alg in PCR_BANKS {
+            list.try_push(TpmsPcrSelection {
+                hash: alg,
+                pcr_select: TpmBuffer::try_from(&select[..]).map_err(failure)?,
+            })
+            .map_err(failure)?;
+        }
+        Ok(list)
+    }
+
+    fn properties() -> SoftResult<[(u32, u32); 9]> {
+        let to_u32 = |value: usize| u32::try_from(value).map_err(failure);
+        Ok([
+            (TPM_PT_FAMILY_INDICATOR, u32::from_be_bytes(*b"2.0\0")),
+            (TPM_PT_MANUFACTURER, u32::from_be_bytes(*b"SOFT")),
+            (TPM_PT_INPUT_BUFFER, to_u32(MAX_BUFFER_SIZE)?),
+            (TPM_PT_HR_TRANSIENT_MIN, to_u32(MAX_OBJECTS)?),
+            (TPM_PT_PCR_COUNT, to_u32(PCR_COUNT)?),
+            (TPM_PT_MAX_COMMAND_SIZE, to_u32(TPM_MAX_COMMAND_SIZE)?),
+            (TPM_PT_MAX_RESPONSE_SIZE, to_u32(TPM_MAX_COMMAND_SIZE)?),
+            (TPM_PT_MAX_DIGEST, to_u32(MAX_DIGEST_SIZE)?),
+            (TPM_PT_NV_BUFFER_MAX, to_u32(MAX_NV_BUFFER_SIZE)?),
+        ])
+    }
+
+    fn get_random(&mut self, cmd: TpmGetRandomCommand) -> SoftResult<TpmGetRandomResponse> {
+        let bytes = self.random(usize::from(cmd.bytes_requested).min(MAX_DIGEST_SIZE));
+        Ok(TpmGetRandomResponse {
+            random_bytes: Tpm2bDigest::try_from(bytes.as_slice()).map_err(failure)?,
+        })
+    }
+
+    fn hash(&self, cmd: &TpmHashCommand) -> SoftResult<TpmHashResponse> {
+        let out_hash =
+            digest(cmd.hash_alg, &[&cmd.data]).map_err(|_| rc_param(TpmRcBase::Hash, 2))?;
+        Ok(TpmHashResponse {
+            out_hash: Tpm2bDigest::try_from(out_hash.as_slice()).map_err(failure)?,
+            validation: self.hashcheck_ticket(cmd.hash_alg, cmd.hierarchy, &cmd.data, &out_hash)?,
+        })
+    }
+
+    /// Returns the hash check ticket of `digest` over `data`. A NULL ticket is
+    /// returned for `TPM_RH_NULL` and for data that starts with
+    /// `TPM_GENERATED_VALUE`.
+    fn hashcheck_ticket(
+        &self,
+        hash_alg: TpmAlgId,
+        hierarchy: TpmRh,
+        data: &[u8],
+        digest: &[u8],
+    ) -> SoftResult<TpmtTkHashcheck> {
+        let generated = data.get(..4) == Some(&TPM_GENERATED_VALUE.to_be_bytes()[..]);
+        if hierarchy == TpmRh::Null || generated {
+            return Ok(TpmtTkHashcheck {
+                tag: TpmSt::HashCheck,
+                hierarchy: TpmRh::Null,
+                digest: Tpm2bDigest::default(),
+            });
+        }
+        let proof = self.proof("PROOF", &(hierarchy as u32).to_be_bytes())?;
+        let ticket = hmac(
+            hash_alg,
+            &proof,
+            &[&(TpmSt::HashCheck as u16).to_be_bytes(), digest],
+        )
+        .map_err(failure)?;
+        Ok(TpmtTkHashcheck {
+            tag: TpmSt::HashCheck,
+            hierarchy,
+            digest: Tpm2bDigest::try_from(ticket.as_slice()).map_err(failure)?,
+        })
+    }
+
+    fn hash_sequence_start(
+        &mut self,
+        cmd: &TpmHashSequenceStartCommand,
+    ) -> SoftResult<TpmHashSequenceStartResponse> {
+        if cmd.hash_alg != TpmAlgId::Null && tpm_hash_size(&cmd.hash_alg).is_none() {
+            return Err(rc_param(TpmRcBase::Hash, 2));
+        }
+        let handle = self.free_transient()?;
+        self.sequences.insert(
+            handle,
+            SoftSequence {
+                hash_alg: cmd.hash_alg,
+                auth: cmd.auth,
+                data: Vec::new(),
+            },
+        );
+        Ok(TpmHashSequenceStartResponse {
+            sequence_handle: TpmTransient::try_from(handle).map_err(failure)?,
+        })
+    }
+
+    fn sequence_update(
+        &mut self,
+        handle: u32,
+        cmd: &TpmSequenceUpdateCommand,
+    ) -> SoftResult<TpmSequenceUpdateResponse> {
+        let sequence = self
+            .sequences
+            .get_mut(&handle)
+            .ok_or(rc_handle(TpmRcBase::Mode, 1))?;
+        sequence.data.extend_from_slice(&cmd.buffer);
+        Ok(TpmSequenceUpdateResponse {})
+    }
+
+    fn sequence_complete(
+        &mut self,
+        handle: u32,
+        cmd: &TpmSequenceCompleteCommand,
+    ) -> SoftResult<TpmSequenceCompleteResponse> {
+        match self.sequences.get(&handle) {
+            Some(sequence) if sequence.hash_alg != TpmAlgId::Null => {}
+            _ => return Err(rc_handle(TpmRcBase::Mode, 1)),
+        }
+        let mut sequence = self.sequences.remove(&handle).ok_or(failure(()))?;
+        sequence.data.extend_from_slice(&cmd.buffer);
+        let result = digest(sequence.hash_alg, &[&sequence.data])?;
+        let validation =
+            self.hashcheck_ticket(sequence.hash_alg, cmd.hierarchy, &sequence.data, &result)?;
+        Ok(TpmSequenceCompleteResponse {
+            result: Tpm2bDigest::try_from(result.as_slice()).map_err(failure)?,
+            validation,
+        })
+    }
+
+    fn event_sequence_complete(
+        &mut self,
+        handles: &TpmHandles,
+        cmd: &TpmEventSequenceCompleteCommand,
+    ) -> SoftResult<TpmEventSequenceCompleteResponse> {
+        let pcr = Self::pcr_index(handles[0])?;
+        match self.sequences.get(&handles[1]) {
+            Some(sequence) if sequence.hash_alg == TpmAlgId::Null => {}
+            _ => return Err(rc_handle(TpmRcBase::Mode, 2)),
+        }
+        let mut sequence = self.sequences.remove(&handles[1]).ok_or(failure(()))?;
+        sequence.data.extend_from_slice(&cmd.buffer);
+        let results = self.extend_event(pcr, &sequence.data)?;
+        Ok(TpmEventSequenceCompleteResponse { results })
+    }
+
+    fn pcr_bank(&self, alg: TpmAlgId) -> Option<&Vec<Vec<u8>>> {
+        self.pcrs
+            .iter()
+            .find(|(bank, _)| *bank == alg)
+            .map(|(_, values)| values)
+    }
+
+    fn selected_pcrs(selection: &TpmsPcrSelection) -> impl Iterator<Item = usize> + '_ {
+        (0..PCR_COUNT).filter(|&pcr| {
+            selection
+                .pcr_select
+                .get(pcr / 8)
+                .is_some_and(|byte| byte & (1 << (pcr % 8)) != 0)
+        })
+    }
+
+    /// Computes the digest of the selected PCR values in selection order.
+    fn pcr_digest(&self, alg: TpmAlgId, selection: &TpmlPcrSelection) -> SoftResult<Vec<u8>> {
+        let mut values: Vec<&[u8]> = Vec::new();
+        for bank_selection in selection.iter() {
+            let bank = self
+                .pcr_bank(bank_selection.hash)
+                .ok_or(rc(TpmRcBase::Pcr))?;
+            for pcr in Self::selected_pcrs(bank_selection) {
+                values.push(&bank[pcr]);
+            }
+        }
+        digest(alg, &values)
+    }
+
+    fn pcr_read(&self, cmd: &TpmPcrReadCommand) -> SoftResult<TpmPcrReadResponse> {
+        let mut pcr_selection_out = TpmlPcrSelection::new();
+        let mut pcr_values = TpmlDigest::new();
+        for selection in cmd.pcr_selection_in.iter() {
+            let Some(bank) = self.pcr_bank(selection.hash) else {
+                continue;
+            };
+            let mut select = vec![0u8; selection.pcr_select.len()];
+            for pcr in Self::selected_pcrs(selection) {
+                let value = Tpm2bDigest::try_from(bank[pcr].as_slice()).map_err(failure)?;
+                if pcr_values.try_push(value).is_err() {
+                    break;
+                }
+                select[pcr / 8] |= 1 << (pcr % 8);
+            }
+            pcr_selection_out
+                .try_push(TpmsPcrSelection {
+                    hash: selection.hash,
+                    pcr_select: TpmBuffer::try_from(select.as_slice()).map_err(failure)?,
+                })
+                .map_err(failure)?;
+        }
+        Ok(TpmPcrReadResponse {
+            pcr_update_counter: self.pcr_update_counter,
+            pcr_selection_out,
+            pcr_values,
+        })
+    }
+
+    /// Returns the PCR index of a PCR handle, or `None` for `TPM_RH_NULL`.
+    fn pcr_index(handle: u32) -> SoftResult<Option<usize>> {
+        if handle == TpmRh::Null as u32 {
+            return Ok(None);
+        }
+        match usize::try_from(handle) {
+            Ok(pcr) if pcr < PCR_COUNT => Ok(Some(pcr)),
+            _ => Err(rc_handle(TpmRcBase::Value, 1)),
+        }
+    }
+
+    fn extend_pcr(&mut self, pcr: usize, alg: TpmAlgId, data: &[u8]) -> SoftResult<()> {
+        if let Some((_, bank)) = self.pcrs.iter_mut().find(|(bank, _)| *bank == alg) {
+            bank[pcr] = digest(alg, &[&bank[pcr], data])?;
+        }
+        Ok(())
+    }
+
+    fn pcr_extend(
+        &mut self,
+        handle: u32,
+        cmd: &TpmPcrExtendCommand,
+    ) -> SoftResult<TpmPcrExtendResponse> {
+        if let Some(pcr) = Self::pcr_index(handle)? {
+            for value in cmd.digests.iter() {
+                self.extend_pcr(pcr, value.hash_alg, &value.digest)?;
+            }
+            self.pcr_update_counter = self.pcr_update_counter.wrapping_add(1);
+        }
+        Ok(TpmPcrExtendResponse {})
+    }
+
+    fn pcr_event(
+        &mut self,
+        handle: u32,
+        cmd: &TpmPcrEventCommand,
+    ) -> SoftResult<TpmPcrEventResponse> {
+        if cmd.event_data.len() > 1024 {
+            return Err(rc_param(TpmRcBase::Size, 1));
+        }
+        let pcr = Self::pcr_index(handle)?;
+        let digests = self.extend_event(pcr, &cmd.event_data)?;
+        Ok(TpmPcrEventResponse { digests })
+    }
+
+    /// Hashes an event with the algorithm of each PCR bank and extends the
+    /// digests to `pcr` unless it is `None`.
+    fn extend_event(&mut self, pcr: Option<usize>, data: &[u8]) -> SoftResult<TpmlDigestValues> {
+        let mut digests = TpmlDigestValues::new();
+        for alg in PCR_BANKS {
+            let event_digest = digest(alg, &[data])?;
+            let (digest, _) = TpmuHa::parse_tagged(alg, &event_digest).map_err(failure)?;
+            digests
+                .try_push(TpmtHa {
+                    hash_alg: alg,
+                    digest,
+                })
+                .map_err(failure)?;
+            if let Some(pcr) = pcr {
+                self.extend_pcr(pcr, alg, &event_digest)?;
+            }
+        }
+        if pcr.is_some() {
+            self.pcr_update_counter = self.pcr_update_counter.wrapping_add(1);
+        }
+        Ok(digests)
+    }
+
+    fn check_provisioning_auth(handle: u32) -> SoftResult<()> {
+        if handle == TpmRh::Owner as u32 || handle == TpmRh::Platform as u32 {
+            Ok(())
+        } else {
+            Err(rc_handle(TpmRcBase::Hierarchy, 1))
+        }
+    }
+
+    fn nv_define_space(
+        &mut self,
+        auth_handle: u32,
+        cmd: &TpmNvDefineSpaceCommand,
+    ) -> SoftResult<TpmNvDefineSpaceResponse> {
+        Self::check_provisioning_auth(auth_handle)?;
+        let public = cmd.public_info.inner;
+        let index = u32::from(public.nv_index);
+        if TpmHt::from_handle(index) != Some(TpmHt::NvIndex) {
+            return Err(rc_param(TpmRcBase::Value, 2));
+        }
+        let name_size = hash_size(public.name_alg, rc_param(TpmRcBase::Hash, 2))?;
+        if cmd.auth.len() > name_size {
+            return Err(rc_param(TpmRcBase::Size, 1));
+        }
+        let unsupported = TpmaNv::TPM_NT_COUNTER
+            | TpmaNv::TPM_NT_BITS
+            | TpmaNv::TPM_NT_EXTEND
+            | TpmaNv::WRITTEN
+            | TpmaNv::READLOCKED
+            | TpmaNv::WRITELOCKED;
+        if public.attributes.intersects(unsupported) {
+            return Err(rc_param(TpmRcBase::Attributes, 2));
+        }
+        if public.data_size > MAX_NV_INDEX_SIZE {
+            return Err(rc_param(TpmRcBase::Size, 2));
+        }
+        if self.nv.contains_key(&index) {
+            return Err(rc(TpmRcBase::NvDefined));
+        }
+        self.nv.insert(
+            index,
+            SoftNvIndex {
+                public,
+                auth: cmd.auth,
+                data: vec![0xff; usize::from(public.data_size)],
+            },
+        );
+        Ok(TpmNvDefineSpaceResponse {})
+    }
+
+    fn nv_undefine_space(
+        &mut self,
+        handles: &TpmHandles,
+    ) -> SoftResult<TpmNvUndefineSpaceResponse> {
+        Self::check_provisioning_auth(handles[0])?;
+        self.nv
+            .remove(&handles[1])
+            .ok_or(rc_handle(TpmRcBase::Handle, 2))?;
+        Ok(TpmNvUndefineSpaceResponse {})
+    }
+
+    /// Checks that `auth_handle` is allowed to read or write the NV index
+    /// `index`, and returns the index.
+    fn nv_access(
+        &mut self,
+        auth_handle: u32,
+        index: u32,
+        by_owner: TpmaNv,
+        by_platform: TpmaNv,
+        by_auth: TpmaNv,
+    ) -> SoftResult<&mut SoftNvIndex> {
+        let nv = self
+            .nv
+            .get_mut(&index)
+            .ok_or(rc_handle(TpmRcBase::Handle, 2))?;
+        let required = if auth_handle == index {
+            by_auth
+        } else if auth_handle == TpmRh::Owner as u32 {
+            by_owner
+        } else if auth_handle == TpmRh::Platform as u32 {
+            by_platform
+        } else {
+            return Err(rc(TpmRcBase::NvAuthorization));
+        };
+        if !nv.public.attributes.contains(required) {
+            return Err(rc(TpmRcBase::NvAuthorization));
+        }
+        Ok(nv)
+    }
+
+    fn nv_range(nv: &SoftNvIndex, offset: u16, len: usize) -> SoftResult<std::ops::Range<usize>> {
+        let start = usize::from(offset);
+        let end = start + len;
+        if end > nv.data.len() {
+            return Err(rc(TpmRcBase::NvRange));
+        }
+        Ok(start..end)
+    }
+
+    fn nv_write(
+        &mut self,
+        handles: &TpmHandles,
+        cmd: &TpmNvWriteCommand,
+    ) -> SoftResult<TpmNvWriteResponse> {
+        let nv = self.nv_access(
+            handles[0],
+            handles[1],
+            TpmaNv::OWNERWRITE,
+            TpmaNv::PPWRITE,
+            TpmaNv::AUTHWRITE,
+        )?;
+        if nv.public.attributes.contains(TpmaNv::WRITELOCKED) {
+            return Err(rc(TpmRcBase::NvLocked));
+        }
+        let range = Self::nv_range(nv, cmd.offset, cmd.data.len())?;
+        nv.data[range].copy_from_slice(&cmd.data);
+        nv.public.attributes |= TpmaNv::WRITTEN;
+        Ok(TpmNvWriteResponse {})
+    }
+
+    fn nv_read(
+        &mut self,
+        handles: &TpmHandles,
+        cmd: TpmNvReadCommand,
+    ) -> SoftResult<TpmNvReadResponse> {
+        let nv = self.nv_access(
+            handles[0],
+            handles[1],
+            TpmaNv::OWNERREAD,
+            TpmaNv::PPREAD,
+            TpmaNv::AUTHREAD,
+        )?;
+        if nv.public.attributes.contains(TpmaNv::READLOCKED) {
+            return Err(rc(TpmRcBase::NvLocked));
+        }
+        if !nv.public.attributes.contains(TpmaNv::WRITTEN) {
+            return Err(rc(TpmRcBase::NvUninitialized));
+        }
+        let range = Self::nv_range(nv, cmd.offset, usize::from(cmd.size))?;
+        Ok(TpmNvReadResponse {
+            data: Tpm2bMaxNvBuffer::try_from(&nv.data[range]).map_err(|_| rc(TpmRcBase::NvSize))?,
+        })
+    }
+
+    fn nv_read_public(&self, index: u32) -> SoftResult<TpmNvReadPublicResponse> {
+        let nv = self.nv.get(&index).ok_or(rc_handle(TpmRcBase::Handle, 1))?;
+        Ok(TpmNvReadPublicResponse {
+            nv_public: Tpm2bNvPublic::from(nv.public),
+            nv_name: tpm_nv_name(&SoftwareHash, &nv.public).map_err(failure)?,
+        })
+    }
+
+    /// Returns key material, derived from `seed` and `context` for primary
+    /// objects and random otherwise.
+    fn material(
+        &mut self,
+        derivation: Option<(&[u8], &[u8])>,
+        label: &str,
+        len: usize,
+    ) -> SoftResult<Vec<u8>> {
+        match derivation {
+            Some((seed, context)) => {
+                kdfa(TpmAlgId::Sha256, seed, label, context, &[], to_bits(len)?).map_err(failure)
+            }
+            None => Ok(self.random(len)),
+        }
+    }
+
+    /// Generates the sensitive area for a template and fills in the unique
+    /// field of the public area.
+    fn generate_object(
+        &mut self,
+        template: &TpmtPublic,
+        create: &TpmsSensitiveCreate,
+        derivation: Option<(&[u8], &[u8])>,
+    ) -> SoftResult<(TpmtPublic, TpmtSensitive)> {
+        let name_size = hash_size(template.name_alg, rc_param(TpmRcBase::Hash, 2))?;
+        if create.user_auth.len() > name_size {
+            return Err(rc_param(TpmRcBase::Size, 1));
+        }
+        let attributes = template.object_attributes;
+        let mut public = template.clone();
+        let (seed_value, sensitive) = match (&template.parameters, template.object_type) {
+            (TpmuPublicParms::KeyedHash { .. }, TpmAlgId::KeyedHash) => {
+                let is_key = attributes.intersects(TpmaObject::SIGN_ENCRYPT | TpmaObject::DECRYPT);
+                let origin = attributes.contains(TpmaObject::SENSITIVE_DATA_ORIGIN);
+                let data = if create.data.is_empty() && is_key && origin {
+                    self.material(derivation, "KEYEDHASH", name_size)?
+                } else if !origin || create.data.is_empty() {
+                    create.data.to_vec()
+                } else {
+                    return Err(rc_param(TpmRcBase::Attributes, 2));
+                };
+                let seed_value = self.material(derivation, "SEED", name_size)?;
+                public.unique = TpmuPublicId::KeyedHash(
+                    Tpm2bDigest::try_from(
+                        digest(template.name_alg, &[&seed_value, &data])?.as_slice(),
+                    )
+                    .map_err(failure)?,
+                );
+                let sensitive = TpmuSensitiveComposite::Bits(
+                    Tpm2bSensitiveData::try_from(data.as_slice())
+                        .map_err(|_| rc_param(TpmRcBase::Size, 1))?,
+                );
+                (seed_value, sensitive)
+            }
+            (TpmuPublicParms::Ecc { curve_id, .. }, TpmAlgId::Ecc) => {
+                if *curve_id != TpmEccCurve::NistP256 {
+                    return Err(rc_param(TpmRcBase::Curve, 2));
+                }
+                if !create.data.is_empty() {
+                    return Err(rc_param(TpmRcBase::Value, 1));
+                }
+                let (secret, scalar) = self.generate_p256(derivation)?;
+                let point = secret.public_key().to_encoded_point(false);
+                let (Some(x), Some(y)) = (point.x(), point.y()) else {
+                    return Err(rc(TpmRcBase::Failure));
+                };
+                public.unique = TpmuPublicId::Ecc(TpmsEccPoint {
+                    x: Tpm2bEccParameter::try_from(x.as_slice()).map_err(failure)?,
+                    y: Tpm2bEccParameter::try_from(y.as_slice()).map_err(failure)?,
+                });
+                let seed_value = if is_storage_parent(template) {
+                    self.material(derivation, "SEED", name_size)?
+                } else {
+                    Vec::new()
+                };
+                let sensitive = TpmuSensitiveComposite::Ecc(
+                    Tpm2bEccParameter::try_from(scalar.as_slice()).map_err(failure)?,
+                );
+                (seed_value, sensitive)
+            }
+            _ => return Err(rc_param(TpmRcBase::Type, 2)),
+        };
+        let sensitive = TpmtSensitive {
+            sensitive_type: template.object_type,
+            auth_value: create.user_auth,
+            seed_value: Tpm2bDigest::try_from(seed_value.as_slice()).map_err(failure)?,
+            sensitive,
+        };
+        Ok((public, sensitive))
+    }
+
+    fn generate_p256(
+        &mut self,
+        derivation: Option<(&[u8], &[u8])>,
+    ) -> SoftResult<(SecretKey, Vec<u8>)> {
+        for counter in 0u32..100 {
+            let scalar = match derivation {
+                Some((seed, context)) => kdfa(
+                    TpmAlgId::Sha256,
+                    seed,
+                    "ECC",
+                    context,
+                    &counter.to_be_bytes(),
+                    256,
+                )
+                .map_err(failure)?,
+                None => self.random(32),
+            };
+            if let Ok(secret) = SecretKey::from_slice(&scalar) {
+                return Ok((secret, scalar));
+            }
+        }
+        Err(rc(TpmRcBase::Failure))
+    }
+
+    /// Checks that the sensitive area of a loaded object matches its public
+    /// area.
+    fn check_binding(public: &TpmtPublic, sensitive: &TpmtSensitive) -> SoftResult<()> {
+        let bound = match (&public.unique, &sensitive.sensitive) {
+            (TpmuPublicId::KeyedHash(unique), TpmuSensitiveComposite::Bits(data)) => {
+                digest(public.name_alg, &[&sensitive.seed_value, data])? == **unique
+            }
+            (TpmuPublicId::Ecc(unique), TpmuSensitiveComposite::Ecc(scalar)) => {
+                SecretKey::from_slice(scalar).is_ok_and(|secret| {
+                    let point = secret.public_key().to_encoded_point(false);
+                    point.x().map(AsRef::as_ref) == Some(&unique.x[..])
+                        && point.y().map(AsRef::as_ref) == Some(&unique.y[..])
+                })
+            }
+            _ => false,
+        };
+        if bound {
+            Ok(())
+        } else {
+            Err(rc_param(TpmRcBase::Binding, 2))
+        }
+    }
+
+    /// Returns a free transient handle for an object or a sequence.
+    fn free_transient(&self) -> SoftResult<u32> {
+        if self.objects.len() + self.sequences.len() >= MAX_OBJECTS {
+            return Err(rc(TpmRcBase::ObjectMemory));
+        }
+        (TRANSIENT_FIRST..=TRANSIENT_FIRST | HANDLE_INDEX_MASK)
+            .find(|handle| {
+                !self.objects.contains_key(handle) && !self.sequences.contains_key(handle)
+            })
+            .ok_or(rc(TpmRcBase::ObjectMemory))
+    }
+
+    fn insert_object(&mut self, object: SoftObject) -> SoftResult<u32> {
+        let handle = self.free_transient()?;
+        self.objects.insert(handle, object);
+        Ok(handle)
+    }
+
+    /// Builds the creation data, creation hash and creation ticket of a new
+    /// object.
+    fn creation_info(
+        &self,
+        public: &TpmtPublic,
+        name: &Tpm2bName,
+        parent: (TpmAlgId, Tpm2bName, TpmRh),
+        outside_info: &Tpm2b,
+        creation_pcr: &TpmlPcrSelection,
+    ) -> SoftResult<(Tpm2bCreationData, Tpm2bDigest, TpmtTkCreation)> {
+        let (parent_name_alg, parent_name, hierarchy) = parent;
+        let pcr_digest = if creation_pcr.is_empty() {
+            Tpm2bDigest::default()
+        } else {
+            Tpm2bDigest::try_from(self.pcr_digest(public.name_alg, creation_pcr)?.as_slice())
+                .map_err(failure)?
+        };
+        let creation_data = TpmsCreationData {
+            pcr_select: *creation_pcr,
+            pcr_digest,
+            locality: TpmaLocality::TPM_LOC_ZERO,
+            parent_name_alg,
+            parent_name,
+            parent_qualified_name: parent_name,
+            outside_info: Tpm2bData::try_from(&outside_info[..])
+                .map_err(|_| rc_param(TpmRcBase::Size, 3))?,
+        };
+        let creation_hash = digest(
+            public.name_alg,
+            &[&build_to_vec(&creation_data).map_err(failure)?],
+        )?;
+        let proof = self.proof("PROOF", &(hierarchy as u32).to_be_bytes())?;
+        let ticket = hmac(
+            public.name_alg,
+            &proof,
+            &[
+                &(TpmSt::Creation as u16).to_be_bytes(),
+                name,
+                &creation_hash,
+            ],
+        )
+        .map_err(failure)?;
+        Ok((
+            Tpm2bCreationData::from(creation_data),
+            Tpm2bDigest::try_from(creation_hash.as_slice()).map_err(failure)?,
+            TpmtTkCreation {
+                tag: TpmSt::Creation,
+                hierarchy,
+                digest: Tpm2bDigest::try_from(ticket.as_slice()).map_err(failure)?,
+            },
+        ))
+    }
+
+    fn create_primary(
+        &mut self,
+        hierarchy_handle: u32,
+        cmd: &TpmCreatePrimaryCommand,
+    ) -> SoftResult<TpmCreatePrimaryResponse> {
+        let Ok(hierarchy @ (TpmRh::Owner | TpmRh::Endorsement | TpmRh::Platform | TpmRh::Null)) =
+            TpmRh::try_from(hierarchy_handle)
+        else {
+            return Err(rc_handle(TpmRcBase::Hierarchy, 1));
+        };
+        let template = &cmd.in_public.inner;
+        let seed = self.proof("PRIMARY", &hierarchy_handle.to_be_bytes())?;
+        let context = digest(
+            TpmAlgId::Sha256,
+            &[
+                &build_to_vec(template).map_err(failure)?,
+                &cmd.in_sensitive.inner.data,
+            ],
+        )?;
+        let (public, sensitive) =
+            self.generate_object(template, &cmd.in_sensitive.inner, Some((&seed, &context)))?;
+        let name = tpm_object_name(&SoftwareHash, &public).map_err(failure)?;
+        let hierarchy_name = tpm_handle_name(hierarchy_handle).map_err(failure)?;
+        let (creation_data, creation_hash, creation_ticket) = self.creation_info(
+            &public,
+            &name,
+            (TpmAlgId::Null, hierarchy_name, hierarchy),
+            &cmd.outside_info,
+            &cmd.creation_pcr,
+        )?;
+        let handle = self.insert_object(SoftObject {
+            public: public.clone(),
+            sensitive,
+            name,
+            hierarchy,
+        })?;
+        Ok(TpmCreatePrimaryResponse {
+            object_handle: TpmTransient::try_from(handle).map_err(failure)?,
+            out_public: Tpm2bPublic::from(public),
+            creation_data,
+            creation_hash,
+            creation_ticket,
+            name,
+        })
+    }
+
+    fn storage_parent(&self, handle: u32) -> SoftResult<&SoftObject> {
+        let parent = self
+            .objects
+            .get(&handle)
+            .ok_or(rc_handle(TpmRcBase::Handle, 1))?;
+        if !is_storage_parent(&parent.public) {
+            return Err(rc_handle(TpmRcBase::Type, 1));
+        }
+        Ok(parent)
+    }
+
+    /// Protects a sensitive area for storage outside the TPM under a parent.
+    /// The result is `integrity || encSensitive` with keys derived from the
+    /// TPM proof, the parent Name and the object Name.
+    fn wrap(
+        &self,
+        parent_name: &[u8],
+        name: &[u8],
+        sensitive: &TpmtSensitive,
+    ) -> SoftResult<Tpm2bPrivate> {
+        let mut enc_sensitive =
+            build_to_vec(&Tpm2bSensitive::from(sensitive.clone())).map_err(failure)?;
+        let sym_key = kdfa(
+            TpmAlgId::Sha256,
+            &self.proof("STORAGE", parent_name)?,
+            "STORAGE",
+            name,
+            &[],
+            128,
+        )
+        .map_err(failure)?;
+        aes_cfb(&sym_key, &mut enc_sensitive, true)?;
+        let hmac_key = self.proof("INTEGRITY", parent_name)?;
+        let integrity =
+            hmac(TpmAlgId::Sha256, &hmac_key, &[&enc_sensitive, name]).map_err(failure)?;
+        let mut private =
+            build_to_vec(&Tpm2bDigest::try_from(integrity.as_slice()).map_err(failure)?)
+                .map_err(failure)?;
+        private.extend_from_slice(&enc_sensitive);
+        Tpm2bPrivate::try_from(private.as_slice()).map_err(|_| rc(TpmRcBase::Size))
+    }
+
+    /// Reverses `wrap`.
+    fn unwrap(&self, parent_name: &[u8], name: &[u8], private: &[u8]) -> SoftResult<TpmtSensitive> {
+        let integrity_error = rc_param(TpmRcBase::Integrity, 1);
+        let (integrity, enc_sensitive) =
+            Tpm2bDigest::parse(private).map_err(|_| integrity_error)?;
+        let hmac_key = self.proof("INTEGRITY", parent_name)?;
+        let expected =
+            hmac(TpmAlgId::Sha256, &hmac_key, &[enc_sensitive, name]).map_err(failure)?;
+        if expected != *integrity {
+            return Err(integrity_error);
+        }
+        let sym_key = kdfa(
+            TpmAlgId::Sha256,
+            &self.proof("STORAGE", parent_name)?,
+            "STORAGE",
+            name,
+            &[],
+            128,
+        )
+        .map_err(failure)?;
+        let mut plain = enc_sensitive.to_vec();
+        aes_cfb(&sym_key, &mut plain, false)?;
+        let (sensitive, tail) = Tpm2bSensitive::parse(&plain).map_err(|_| integrity_error)?;
+        if !tail.is_empty() {
+            return Err(integrity_error);
+        }
+        Ok(sensitive.inner)
+    }
+
+    fn create(
+        &mut self,
+        parent_handle: u32,
+        cmd: &TpmCreateCommand,
+    ) -> SoftResult<TpmCreateResponse> {
+        let parent = self.storage_parent(parent_handle)?;
+        let parent_info = (parent.public.name_alg, parent.name, parent.hierarchy);
+        let (public, sensitive) =
+            self.generate_object(&cmd.in_public.inner, &cmd.in_sensitive.inner, None)?;
+        let name = tpm_object_name(&SoftwareHash, &public).map_err(failure)?;
+        let out_private = self.wrap(&parent_info.1, &name, &sensitive)?;
+        let (creation_data, creation_hash, creation_ticket) = self.creation_info(
+            &public,
+            &name,
+            parent_info,
+            &cmd.outside_info,
+            &cmd.creation_pcr,
+        )?;
+        Ok(TpmCreateResponse {
+            out_private,
+            out_public: Tpm2bPublic::from(public),
+            creation_data,
+            creation_hash,
+            creation_ticket,
+        })
+    }
+
+    fn load(&mut self, parent_handle: u32, cmd: &TpmLoadCommand) -> SoftResult<TpmLoadResponse> {
+        let parent = self.storage_parent(parent_handle)?;
+        let (parent_name, hierarchy) = (parent.name, parent.hierarchy);
+        let public = cmd.in_public.inner.clone();
+        let name =
+            tpm_object_name(&SoftwareHash, &public).map_err(|_| rc_param(TpmRcBase::Hash, 2))?;
+        let sensitive = self.unwrap(&parent_name, &name, &cmd.in_private)?;
+        if sensitive.sensitive_type != public.object_type {
+            return Err(rc_param(TpmRcBase::Type, 2));
+        }
+        Self::check_binding(&public, &sensitive)?;
+        let handle = self.insert_object(SoftObject {
+            public,
+            sensitive,
+            name,
+            hierarchy,
+        })?;
+        Ok(TpmLoadResponse {
+            object_handle: TpmTransient::try_from(handle).map_err(failure)?,
+            name,
+        })
+    }
+
+    fn unseal(&self, handle: u32) -> SoftResult<TpmUnsealResponse> {
+        let object = self
+            .objects
+            .get(&handle)
+            .ok_or(rc_handle(TpmRcBase::Handle, 1))?;
+        let TpmuSensitiveComposite::Bits(data) = &object.sensitive.sensitive else {
+            return Err(rc_handle(TpmRcBase::Type, 1));
+        };
+        if object
+            .public
+            .object_attributes
+            .intersects(TpmaObject::SIGN_ENCRYPT | TpmaObject::DECRYPT)
+        {
+            return Err(rc_handle(TpmRcBase::Attributes, 1));
+        }
+        Ok(TpmUnsealResponse {
+            out_data: Tpm2b::try_from(&data[..]).map_err(failure)?,
+        })
+    }
+
+    fn read_public(&self, handle: u32) -> SoftResult<TpmReadPublicResponse> {
+        let object = self
+            .objects
+            .get(&handle)
+            .ok_or(rc_handle(TpmRcBase::Handle, 1))?;
+        Ok(TpmReadPublicResponse {
+            out_public: Tpm2bPublic::from(object.public.clone()),
+            name: object.name,
+            qualified_name: object.name,
+        })
+    }
+
+    /// Computes the integrity value of a saved context.
+    fn context_integrity(&self, sequence: u64, handle: u32) -> SoftResult<Tpm2b> {
+        let integrity = hmac(
+            TpmAlgId::Sha256,
+            &self.primary_seed,
+            &[&sequence.to_be_bytes(), &handle.to_be_bytes()],
+        )
+        .map_err(failure)?;
+        Tpm2b::try_from(integrity.as_slice()).map_err(failure)
+    }
+
+    /// Saves the context of an object or a session. An object stays loaded,
+    /// while a session is unloaded until its context is loaded back.
+    fn context_save(&mut self, handle: u32) -> SoftResult<TpmContextSaveResponse> {
+        let sequence = self.context_sequence;
+        let hierarchy = match TpmHt::from_handle(handle) {
+            Some(TpmHt::Transient) => {
+                let object = self
+                    .objects
+                    .get(&handle)
+                    .ok_or(rc_handle(TpmRcBase::Handle, 1))?;
+                let hierarchy = object.hierarchy;
+                self.saved_objects.insert(sequence, object.clone());
+                hierarchy
+            }
+            Some(TpmHt::HmacSession | TpmHt::PolicySession) => {
+                let session = self
+                    .sessions
+                    .remove(&handle)
+                    .ok_or(rc_handle(TpmRcBase::Handle, 1))?;
+                self.saved_sessions.insert(handle, (sequence, session));
+                TpmRh::Null
+            }
+            _ => return Err(rc_handle(TpmRcBase::Handle, 1)),
+        };
+        self.context_sequence += 1;
+        Ok(TpmContextSaveResponse {
+            context: TpmsContext {
+                sequence,
+                saved_handle: TpmContextHandle::try_from(handle).map_err(failure)?,
+                hierarchy,
+                context_blob: self.context_integrity(sequence, handle)?,
+            },
+        })
+    }
+
+    /// Loads a saved context. An object context can be loaded any number of
+    /// times, a session context only once.
+    fn context_load(&mut self, cmd: &TpmContextLoadCommand) -> SoftResult<TpmContextLoadResponse> {
+        let context = &cmd.context;
+        let saved_handle = u32::from(context.saved_handle);
+        if context.context_blob != self.context_integrity(context.sequence, saved_handle)? {
+            return Err(rc_param(TpmRcBase::Integrity, 1));
+        }
+        let handle = match TpmHt::from_handle(saved_handle) {
+            Some(TpmHt::Transient) => {
+                let object = self
+                    .saved_objects
+                    .get(&context.sequence)
+                    .cloned()
+                    .ok_or(rc_param(TpmRcBase::Handle, 1))?;
+                self.insert_object(object)?
+            }
+            Some(TpmHt::HmacSession | TpmHt::PolicySession) => {
+                match self.saved_sessions.get(&saved_handle) {
+                    Some((sequence, _)) if *sequence == context.sequence => {}
+                    _ => return Err(rc_param(TpmRcBase::Handle, 1)),
+                }
+                if self.sessions.len() >= MAX_SESSIONS {
+                    return Err(rc(TpmRcBase::SessionMemory));
+                }
+                let (_, session) = self
+                    .saved_sessions
+                    .remove(&saved_handle)
+                    .ok_or(rc_param(TpmRcBase::Handle, 1))?;
+                self.sessions.insert(saved_handle, session);
+                saved_handle
+            }
+            _ => return Err(rc_param(TpmRcBase::Handle, 1)),
+        };
+        Ok(TpmContextLoadResponse {
+            loaded_handle: TpmContextHandle::try_from(handle).map_err(failure)?,
+        })
+    }
+
+    fn flush_context(
+        &mut self,
+        cmd: TpmFlushContextCommand,
+    ) -> SoftResult<TpmFlushContextResponse> {
+        let handle = u32::from(cmd.flush_handle);
+        let flushed = match TpmHt::from_handle(handle) {
+            Some(TpmHt::Transient) => {
+                self.objects.remove(&handle).is_some() || self.sequences.remove(&handle).is_some()
+            }
+            Some(TpmHt::HmacSession | TpmHt::PolicySession) => {
+                self.sessions.remove(&handle).is_some()
+                    || self.saved_sessions.remove(&handle).is_some()
+            }
+            _ => false,
+        };
+        if flushed {
+            Ok(TpmFlushContextResponse {})
+        } else {
+            Err(rc_param(TpmRcBase::Handle, 1))
+        }
+    }
+
+    fn start_auth_session(
+        &mut self,
+        handles: &TpmHandles,
+        cmd: &TpmStartAuthSessionCommand,
+    ) -> SoftResult<TpmStartAuthSessionResponse> {
+        if handles[0] != TpmRh::Null as u32 {
+            return Err(rc_handle(TpmRcBase::Handle, 1));
+        }
+        let bind = if handles[1] == TpmRh::Null as u32 {
+            None
+        } else {
+            let name = self
+                .name_of(handles[1])
+                .ok_or(rc_handle(TpmRcBase::Handle, 2))?;
+            let auth_value = self
+                .auth_value_of(handles[1])
+                .ok_or(rc_handle(TpmRcBase::Handle, 2))?;
+            Some((name, auth_value))
+        };
+        if !(16..=MAX_DIGEST_SIZE).contains(&cmd.nonce_caller.len()) {
+            return Err(rc_param(TpmRcBase::Size, 1));
+        }
+        if !cmd.encrypted_salt.is_empty() {
+            return Err(rc_param(TpmRcBase::Value, 2));
+        }
+        let first_handle = match cmd.session_type {
+            TpmSe::Hmac => HMAC_SESSION_FIRST,
+            TpmSe::Policy => POLICY_SESSION_FIRST,
+            TpmSe::Trial => return Err(rc_param(TpmRcBase::Value, 3)),
+        };
+        if cmd.symmetric.algorithm != TpmAlgId::Null {
+            return Err(rc_param(TpmRcBase::Symmetric, 4));
+        }
+        if !matches!(
+            cmd.auth_hash,
+            TpmAlgId::Sha256 | TpmAlgId::Sha384 | TpmAlgId::Sha512
+        ) {
+            return Err(rc_param(TpmRcBase::Hash, 5));
+        }
+        if self.sessions.len() >= MAX_SESSIONS {
+            return Err(rc(TpmRcBase::SessionMemory));
+        }
+        let digest_size = hash_size(cmd.auth_hash, rc_param(TpmRcBase::Hash, 5))?;
+        let nonce_tpm = self.random(digest_size);
+        let session_key = match &bind {
+            Some((_, auth_value)) => kdfa(
+                cmd.auth_hash,
+                auth_value,
+                "ATH",
+                &nonce_tpm,
+                &cmd.nonce_caller,
+                to_bits(digest_size)?,
+            )
+            .map_err(failure)?,
+            None => Vec::new(),
+        };
+        let handle = (first_handle..=first_handle | HANDLE_INDEX_MASK)
+            .find(|handle| {
+                !self.sessions.contains_key(handle) && !self.saved_sessions.contains_key(handle)
+            })
+            .ok_or(rc(TpmRcBase::SessionHandles))?;
+        self.sessions.insert(
+            handle,
+            SoftSession {
+                auth_hash: cmd.auth_hash,
+                nonce_tpm: Tpm2bNonce::try_from(nonce_tpm.as_slice()).map_err(failure)?,
+                session_key,
+                bind: bind.map(|(name, _)| name),
+                policy_digest: (cmd.session_type == TpmSe::Policy).then(|| vec![0u8; digest_size]),
+            },
+        );
+        Ok(TpmStartAuthSessionResponse {
+            session_handle: TpmSession::try_from(handle).map_err(failure)?,
+            nonce_tpm: Tpm2b::try_from(nonce_tpm.as_slice()).map_err(failure)?,
+        })
+    }
+
+    fn policy_secret(
+        &mut self,
+        handles: &TpmHandles,
+        cmd: &TpmPolicySecretCommand,
+    ) -> SoftResult<TpmPolicySecretResponse> {
+        let auth_name = self
+            .name_of(handles[0])
+            .ok_or(rc_handle(TpmRcBase::Handle, 1))?;
+        let session = self
+            .sessions
+            .get_mut(&handles[1])
+            .ok_or(rc_handle(TpmRcBase::Handle, 2))?;
+        let Some(policy_digest) = session.policy_digest.as_mut() else {
+            return Err(rc_handle(TpmRcBase::Type, 2));
+        };
+        if !cmd.nonce_tpm.is_empty() && *cmd.nonce_tpm != *session.nonce_tpm {
+            return Err(rc_param(TpmRcBase::Value, 1));
+        }
+        if !cmd.cp_hash_a.is_empty() {
+            return Err(rc_param(TpmRcBase::Value, 2));
+        }
+        let updated = digest(
+            session.auth_hash,
+            &[
+                policy_digest,
+                &(TpmCc::PolicySecret as u32).to_be_bytes(),
+                &auth_name,
+            ],
+        )?;
+        *policy_digest = digest(session.auth_hash, &[&updated, &cmd.policy_ref])?;
+        Ok(TpmPolicySecretResponse {
+            timeout: Tpm2bTimeout::default(),
+            policy_ticket: TpmtTkAuth {
+                tag: TpmSt::AuthSecret,
+                hierarchy: TpmRh::Null,
+                digest: Tpm2bDigest::default(),
+            },
+        })
+    }
+
+    /// Recovers the seed of a credential with the ECDH private key of a
+    /// P-256 storage key, and returns the credential if it is bound to the
+    /// Name of the activated object.
+    fn activate_credential(
+        &self,
+        handles: &TpmHandles,
+        cmd: &TpmActivateCredentialCommand,
+    ) -> SoftResult<TpmActivateCredentialResponse> {
+        let name = self
+            .objects
+            .get(&handles[0])
+            .ok_or(rc_handle(TpmRcBase::Handle, 1))?
+            .name;
+        let key = self
+            .objects
+            .get(&handles[1])
+            .ok_or(rc_handle(TpmRcBase::Handle, 2))?;
+        let (
+            TpmuPublicParms::Ecc { symmetric, .. },
+            TpmuPublicId::Ecc(unique),
+            TpmuSensitiveComposite::Ecc(scalar),
+        ) = (
+            &key.public.parameters,
+            &key.public.unique,
+            &key.sensitive.sensitive,
+        )
+        else {
+            return Err(rc_handle(TpmRcBase::Type, 2));
+        };
+        if !is_storage_parent(&key.public) {
+            return Err(rc_handle(TpmRcBase::Type, 2));
+        }
+        if symmetric.key_bits != TpmuSymKeyBits::Aes(128) {
+            return Err(rc_handle(TpmRcBase::Symmetric, 2));
+        }
+        let name_alg = key.public.name_alg;
+        let digest_size = hash_size(name_alg, rc_handle(TpmRcBase::Hash, 2))?;
+
+        let (point, tail) =
+            TpmsEccPoint::parse(&cmd.secret).map_err(|_| rc_param(TpmRcBase::Value, 2))?;
+        if !tail.is_empty() {
+            return Err(rc_param(TpmRcBase::Value, 2));
+        }
+        let sec1 = [&[0x04][..], &point.x, &point.y].concat();
+        let ephemeral =
+            PublicKey::from_sec1_bytes(&sec1).map_err(|_| rc_param(TpmRcBase::Value, 2))?;
+        let secret = SecretKey::from_slice(scalar).map_err(failure)?;
+        let z = diffie_hellman(secret.to_nonzero_scalar(), ephemeral.as_affine());
+        let seed = kdfe(
+            name_alg,
+            z.raw_secret_bytes(),
+            "IDENTITY",
+            &point.x,
+            &unique.x,
+            to_bits(digest_size)?,
+        )
+        .map_err(failure)?;
+
+        let integrity_error = rc_param(TpmRcBase::Integrity, 1);
+        let (integrity, enc_identity) =
+            Tpm2bDigest::parse(&cmd.credential_blob).map_err(|_| integrity_error)?;
+        let hmac_key = kdfa(
+            name_alg,
+            &seed,
+            "INTEGRITY",
+            &[],
+            &[],
+            to_bits(digest_size)?,
+        )
+        .map_err(failure)?;
+        let expected = hmac(name_alg, &hmac_key, &[enc_identity, &name]).map_err(failure)?;
+        if expected != *integrity {
+            return Err(integrity_error);
+        }
+        let sym_key = kdfa(name_alg, &seed, "STORAGE", &name, &[], 128).map_err(failure)?;
+        let mut plain = enc_identity.to_vec();
+        aes_cfb(&sym_key, &mut plain, false)?;
+        let (cert_info, tail) =
+            Tpm2bDigest::parse(&plain).map_err(|_| rc_param(TpmRcBase::Size, 1))?;
+        if !tail.is_empty() || cert_info.len() > digest_size {
+            return Err(rc_param(TpmRcBase::Size, 1));
+        }
+        Ok(TpmActivateCredentialResponse { cert_info })
+    }
+
+    /// Signs a digest with an ECDSA P-256 key. A restricted key signs only
+    /// digests with a hash check ticket from `TPM2_Hash`.
+    fn sign(&self, handle: u32, cmd: &TpmSignCommand) -> SoftResult<TpmSignResponse> {
+        let object = self
+            .objects
+            .get(&handle)
+            .ok_or(rc_handle(TpmRcBase::Handle, 1))?;
+        let attributes = object.public.object_attributes;
+        let (TpmuPublicParms::Ecc { scheme, .. }, TpmuSensitiveComposite::Ecc(scalar)) =
+            (&object.public.parameters, &object.sensitive.sensitive)
+        else {
+            return Err(rc_handle(TpmRcBase::Key, 1));
+        };
+        if !attributes.contains(TpmaObject::SIGN_ENCRYPT) {
+            return Err(rc_handle(TpmRcBase::Key, 1));
+        }
+        let scheme = match (scheme.scheme, cmd.in_scheme.scheme) {
+            (TpmAlgId::Null, _) => &cmd.in_scheme,
+            (_, TpmAlgId::Null) => scheme,
+            _ if *scheme == cmd.in_scheme => scheme,
+            _ => return Err(rc_param(TpmRcBase::Scheme, 2)),
+        };
+        let (TpmAlgId::Ecdsa, TpmuAsymScheme::Hash(hash)) = (scheme.scheme, scheme.details) else {
+            return Err(rc_param(TpmRcBase::Scheme, 2));
+        };
+        if cmd.digest.len() != hash_size(hash, rc_param(TpmRcBase::Hash, 2))? {
+            return Err(rc_param(TpmRcBase::Size, 1));
+        }
+        if attributes.contains(TpmaObject::RESTRICTED) {
+            let validation = &cmd.validation;
+            if validation.tag != TpmSt::HashCheck || validation.hierarchy == TpmRh::Null {
+                return Err(rc_param(TpmRcBase::Ticket, 3));
+            }
+            let proof = self.proof("PROOF", &(validation.hierarchy as u32).to_be_bytes())?;
+            let ticket = hmac(
+                hash,
+                &proof,
+                &[&(TpmSt::HashCheck as u16).to_be_bytes(), &cmd.digest],
+            )
+            .map_err(failure)?;
+            if *validation.digest != *ticket {
+                return Err(rc_param(TpmRcBase::Ticket, 3));
+            }
+        }
+
+        let key = SigningKey::from_slice(scalar).map_err(failure)?;
+        let signature: Signature = key.sign_prehash(&cmd.digest).map_err(failure)?;
+        let (r, s) = signature.split_bytes();
+        Ok(TpmSignResponse {
+            signature: TpmtSignature {
+                sig_alg: TpmAlgId::Ecdsa,
+                signature: TpmuSignature::Ecdsa(TpmsSignatureEcc {
+                    hash,
+                    signature_r: Tpm2bEccParameter::try_from(r.as_slice()).map_err(failure)?,
+                    signature_s: Tpm2bEccParameter::try_from(s.as_slice()).map_err(failure)?,
+                }),
+            },
+        })
+    }
+
+    /// Verifies a signature with a loaded key and returns a verification
+    /// ticket for the hierarchy of the key.
+    fn verify_signature(
+        &self,
+        handle: u32,
+        cmd: &TpmVerifySignatureCommand,
+    ) -> SoftResult<TpmVerifySignatureResponse> {
+        let object = self
+            .objects
+            .get(&handle)
+            .ok_or(rc_handle(TpmRcBase::Handle, 1))?;
+        if !object
+            .public
+            .object_attributes
+            .contains(TpmaObject::SIGN_ENCRYPT)
+        {
+            return Err(rc_handle(TpmRcBase::Attributes, 1));
+        }
+        verify_digest_signature(&object.public, &cmd.digest, &cmd.signature)
+            .map_err(|_| rc_param(TpmRcBase::Signature, 2))?;
+
+        let validation = if object.hierarchy == TpmRh::Null {
+            TpmtTkVerified {
+                tag: TpmSt::Verified,
+                hierarchy: TpmRh::Null,
+                digest: Tpm2bDigest::default(),
+            }
+        } else {
+            let proof = self.proof("PROOF", &(object.hierarchy as u32).to_be_bytes())?;
+            let ticket = hmac(
+                object.public.name_alg,
+                &proof,
+                &[
+                    &(TpmSt::Verified as u16).to_be_bytes(),
+                    &cmd.digest,
+                    &object.name,
+                ],
+            )
+            .map_err(failure)?;
+            TpmtTkVerified {
+                tag: TpmSt::Verified,
+                hierarchy: object.hierarchy,
+                digest: Tpm2bDigest::try_from(ticket.as_slice()).map_err(failure)?,
+            }
+        };
+        Ok(TpmVerifySignatureResponse { validation })
+    }
+}
+
+impl Write for SoftTpm {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.command.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Executes the buffered command.
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.command.is_empty() {
+            let command = std::mem::take(&mut self.command);
+            self.response = Cursor::new(self.execute(&command));
+        }
+        Ok(())
+    }
+}
+
+impl Read for SoftTpm {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.command.is_empty() {
+            self.flush()?;
+        }
+        self.response.read(buf)
+    }
+}
@@ -11,6 +11,7 @@ pub struct TpmStack {
 
 impl TpmStack {
     /// Creates a `TpmStack` directly from a vector of bytes.
+    #[must_use]
     pub fn from_vec(bytes: Vec<u8>) -> Self {
         Self { stack: bytes }
     }
@@ -56,7 +57,7 @@ impl TpmStack {
         let writer_len = writer.len();
         let new_bytes = &buffer[..writer_len];
 
-        self.stack.splice(0..0, new_bytes.iter().cloned());
+        self.stack.splice(0..0, new_bytes.iter().copied());
         Ok(())
     }
 
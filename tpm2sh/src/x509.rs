@@ -0,0 +1,423 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! X.509 certificate checks for validating EK certificate chains. Parsing is
+//! done by `x509-cert`.
+
+use crate::{crypto::verify_signature, TpmError};
+use const_oid::db::rfc5912::{
+    ECDSA_WITH_SHA_256, ECDSA_WITH_SHA_384, ECDSA_WITH_SHA_512, ID_CE_BASIC_CONSTRAINTS,
+    ID_CE_KEY_USAGE, ID_CE_SUBJECT_ALT_NAME, ID_EC_PUBLIC_KEY, RSA_ENCRYPTION, SECP_256_R_1,
+    SECP_384_R_1, SECP_521_R_1, SHA_1_WITH_RSA_ENCRYPTION, SHA_256_WITH_RSA_ENCRYPTION,
+    SHA_384_WITH_RSA_ENCRYPTION, SHA_512_WITH_RSA_ENCRYPTION,
+};
+use num_traits::ToPrimitive;
+use pkcs8::{
+    der::{
+        asn1::{AnyRef, UintRef},
+        Any, Decode, Encode, Reader, SliceReader, Tag, Tagged,
+    },
+    spki::SubjectPublicKeyInfoRef,
+    ObjectIdentifier,
+};
+use rsa::{pkcs1::DecodeRsaPublicKey, traits::PublicKeyParts, RsaPublicKey};
+use std::time::Duration;
+use tpm2_protocol::data::{
+    Tpm2bDigest, Tpm2bEccParameter, Tpm2bPublicKeyRsa, TpmAlgId, TpmEccCurve, TpmaObject,
+    TpmsEccPoint, TpmsSignatureEcc, TpmsSignatureRsa, TpmtKdfScheme, TpmtPublic, TpmtScheme,
+    TpmtSignature, TpmtSymDefObject, TpmuPublicId, TpmuPublicParms, TpmuSignature,
+};
+use x509_cert::{
+    ext::pkix::{name::GeneralName, BasicConstraints, KeyUsage, SubjectAltName},
+    name::Name,
+};
+
+/// The extensions that the checks in this module understand. A certificate
+/// with any other critical extension is rejected.
+const KNOWN_CRITICAL_EXTENSIONS: [ObjectIdentifier; 3] = [
+    ID_CE_BASIC_CONSTRAINTS,
+    ID_CE_KEY_USAGE,
+    ID_CE_SUBJECT_ALT_NAME,
+];
+
+/// A parsed X.509 certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Certificate {
+    pub der: Vec<u8>,
+    /// The signed `TBSCertificate` as it appears in `der`. Decoding sorts the
+    /// `SET OF` values in names, so the certificate is not encoded again for
+    /// verifying the signature.
+    pub tbs: Vec<u8>,
+    pub inner: x509_cert::Certificate,
+}
+
+/// Converts an attribute value to a string. Values that are not strings are
+/// shown in hex.
+fn attribute_value(value: &Any) -> String {
+    match value.tag() {
+        Tag::Utf8String
+        | Tag::PrintableString
+        | Tag::Ia5String
+        | Tag::VisibleString
+        | Tag::TeletexString => String::from_utf8_lossy(value.value()).into_owned(),
+        _ => hex::encode(value.value()),
+    }
+}
+
+/// Returns the attributes of a `Name` in order.
+#[must_use]
+pub fn name_attributes(name: &Name) -> Vec<(ObjectIdentifier, String)> {
+    name.0
+        .iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .map(|attribute| (attribute.oid, attribute_value(&attribute.value)))
+        .collect()
+}
+
+/// Formats a `Name` as a comma separated list of attributes.
+#[must_use]
+pub fn name_to_string(name: &Name) -> String {
+    name_attributes(name)
+        .iter()
+        .map(|(oid, value)| {
+            let key = match oid.to_string().as_str() {
+                "2.5.4.3" => "CN".to_string(),
+                "2.5.4.5" => "serialNumber".to_string(),
+                "2.5.4.6" => "C".to_string(),
+                "2.5.4.7" => "L".to_string(),
+                "2.5.4.8" => "ST".to_string(),
+                "2.5.4.10" => "O".to_string(),
+                "2.5.4.11" => "OU".to_string(),
+                other => other.to_string(),
+            };
+            format!("{key}={value}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Converts a DER encoded `SubjectPublicKeyInfo` to a public area with the
+/// key and its parameters. Only RSA and ECC keys of the NIST curves are
+/// supported, the latter as uncompressed points `0x04 || x || y`.
+///
+/// # Errors
+///
+/// Returns a `TpmError` if the key is malformed or not supported.
+pub fn spki_to_public(spki: &[u8]) -> Result<TpmtPublic, TpmError> {
+    let info = SubjectPublicKeyInfoRef::from_der(spki)?;
+    let key = info.subject_public_key.raw_bytes();
+    let (object_type, parameters, unique) = match info.algorithm.oid {
+        RSA_ENCRYPTION => {
+            let rsa = RsaPublicKey::from_pkcs1_der(key)
+                .map_err(|e| TpmError::Parse(format!("invalid RSA public key: {e}")))?;
+            let n = rsa.n().to_bytes_be();
+            let exponent = rsa
+                .e()
+                .to_u32()
+                .ok_or_else(|| TpmError::Parse("RSA exponent is too large".to_string()))?;
+            let key_bits = u16::try_from(rsa.n().bits())
+                .map_err(|_| TpmError::Parse("RSA key is too large".to_string()))?;
+            (
+                TpmAlgId::Rsa,
+                TpmuPublicParms::Rsa {
+                    symmetric: TpmtSymDefObject::default(),
+                    scheme: TpmtScheme::default(),
+                    key_bits,
+                    exponent,
+                },
+                TpmuPublicId::Rsa(Tpm2bPublicKeyRsa::try_from(n.as_slice())?),
+            )
+        }
+        ID_EC_PUBLIC_KEY => {
+            let curve: ObjectIdentifier = info
+                .algorithm
+                .parameters
+                .ok_or_else(|| TpmError::Parse("missing ECC curve parameters".to_string()))?
+                .decode_as()?;
+            let curve_id = match curve {
+                SECP_256_R_1 => TpmEccCurve::NistP256,
+                SECP_384_R_1 => TpmEccCurve::NistP384,
+                SECP_521_R_1 => TpmEccCurve::NistP521,
+                _ => return Err(TpmError::Parse(format!("unsupported ECC curve: {curve}"))),
+            };
+            if key.len() % 2 != 1 || key.first() != Some(&0x04) {
+                return Err(TpmError::Parse("unsupported ECC point format".to_string()));
+            }
+            let (x, y) = key[1..].split_at(key.len() / 2);
+            (
+                TpmAlgId::Ecc,
+                TpmuPublicParms::Ecc {
+                    symmetric: TpmtSymDefObject::default(),
+                    scheme: TpmtScheme::default(),
+                    curve_id,
+                    kdf: TpmtKdfScheme::default(),
+                },
+                TpmuPublicId::Ecc(TpmsEccPoint {
+                    x: Tpm2bEccParameter::try_from(x)?,
+                    y: Tpm2bEccParameter::try_from(y)?,
+                }),
+            )
+        }
+        oid => {
+            return Err(TpmError::Parse(format!(
+                "unsupported public key algorithm: {oid}"
+            )))
+        }
+    };
+    Ok(TpmtPublic {
+        object_type,
+        name_alg: TpmAlgId::Null,
+        object_attributes: TpmaObject::empty(),
+        auth_policy: Tpm2bDigest::default(),
+        parameters,
+        unique,
+    })
+}
+
+/// Returns `true` if `a` and `b` have the same public key, regardless of the
+/// other fields of the public areas.
+#[must_use]
+pub fn same_public_key(a: &TpmtPublic, b: &TpmtPublic) -> bool {
+    fn strip(bytes: &[u8]) -> &[u8] {
+        let zeros = bytes.iter().take_while(|&&byte| byte == 0).count();
+        &bytes[zeros..]
+    }
+    match (&a.parameters, &a.unique, &b.parameters, &b.unique) {
+        (
+            TpmuPublicParms::Rsa { exponent: e1, .. },
+            TpmuPublicId::Rsa(n1),
+            TpmuPublicParms::Rsa { exponent: e2, .. },
+            TpmuPublicId::Rsa(n2),
+        ) => {
+            let exponent = |e: u32| if e == 0 { 65537 } else { e };
+            exponent(*e1) == exponent(*e2) && strip(n1) == strip(n2)
+        }
+        (
+            TpmuPublicParms::Ecc { curve_id: c1, .. },
+            TpmuPublicId::Ecc(p1),
+            TpmuPublicParms::Ecc { curve_id: c2, .. },
+            TpmuPublicId::Ecc(p2),
+        ) => c1 == c2 && strip(&p1.x) == strip(&p2.x) && strip(&p1.y) == strip(&p2.y),
+        _ => false,
+    }
+}
+
+impl Certificate {
+    /// Parses a DER encoded certificate.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the certificate is malformed.
+    pub fn from_der(data: &[u8]) -> Result<Self, TpmError> {
+        let inner = x509_cert::Certificate::from_der(data)?;
+        let mut reader = SliceReader::new(data)?;
+        let tbs = reader.sequence(|reader| {
+            let tbs = AnyRef::decode(reader)?.to_der()?;
+            while !reader.is_finished() {
+                AnyRef::decode(reader)?;
+            }
+            Ok(tbs)
+        })?;
+        reader.finish(())?;
+        Ok(Self {
+            der: data.to_vec(),
+            tbs,
+            inner,
+        })
+    }
+
+    /// Parses all certificates in a PEM file, or a single DER certificate.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if a certificate is malformed.
+    pub fn parse_all(data: &[u8]) -> Result<Vec<Self>, TpmError> {
+        if !data.starts_with(b"-----BEGIN") && !data.starts_with(b"\n-----BEGIN") {
+            return Ok(vec![Self::from_der(data)?]);
+        }
+        pem::parse_many(data)?
+            .iter()
+            .filter(|block| block.tag() == "CERTIFICATE")
+            .map(|block| Self::from_der(block.contents()))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn subject(&self) -> &Name {
+        &self.inner.tbs_certificate.subject
+    }
+
+    #[must_use]
+    pub fn issuer(&self) -> &Name {
+        &self.inner.tbs_certificate.issuer
+    }
+
+    #[must_use]
+    pub fn subject_string(&self) -> String {
+        name_to_string(self.subject())
+    }
+
+    #[must_use]
+    pub fn issuer_string(&self) -> String {
+        name_to_string(self.issuer())
+    }
+
+    #[must_use]
+    pub fn is_self_issued(&self) -> bool {
+        self.subject() == self.issuer()
+    }
+
+    #[must_use]
+    pub fn is_valid_at(&self, now: Duration) -> bool {
+        let validity = &self.inner.tbs_certificate.validity;
+        validity.not_before.to_unix_duration() <= now
+            && now <= validity.not_after.to_unix_duration()
+    }
+
+    /// Returns the basic constraints extension, if present.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the extension is malformed or repeated.
+    pub fn basic_constraints(&self) -> Result<Option<BasicConstraints>, TpmError> {
+        let ext = self.inner.tbs_certificate.get::<BasicConstraints>()?;
+        Ok(ext.map(|(_, constraints)| constraints))
+    }
+
+    /// Returns `true` if the basic constraints mark the certificate as a CA.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the extension is malformed.
+    pub fn is_ca(&self) -> Result<bool, TpmError> {
+        Ok(self
+            .basic_constraints()?
+            .is_some_and(|constraints| constraints.ca))
+    }
+
+    /// Returns `true` if the key may sign certificates, i.e. the key usage
+    /// extension is absent or has `keyCertSign` set.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the extension is malformed or repeated.
+    pub fn can_sign_certificates(&self) -> Result<bool, TpmError> {
+        let ext = self.inner.tbs_certificate.get::<KeyUsage>()?;
+        Ok(ext.map_or(true, |(_, usage)| usage.key_cert_sign()))
+    }
+
+    /// Checks that the certificate has no critical extension that is not in
+    /// `KNOWN_CRITICAL_EXTENSIONS`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError::Execution` naming the first unknown critical
+    /// extension.
+    pub fn check_critical_extensions(&self) -> Result<(), TpmError> {
+        let extensions = self.inner.tbs_certificate.extensions.as_deref();
+        if let Some(ext) = extensions
+            .unwrap_or_default()
+            .iter()
+            .find(|ext| ext.critical && !KNOWN_CRITICAL_EXTENSIONS.contains(&ext.extn_id))
+        {
+            return Err(TpmError::Execution(format!(
+                "unsupported critical extension {} in '{}'",
+                ext.extn_id,
+                self.subject_string()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the attributes of the directory names in the subject
+    /// alternative name extension.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the extension is malformed.
+    pub fn alt_name_attributes(&self) -> Result<Vec<(ObjectIdentifier, String)>, TpmError> {
+        let Some((_, SubjectAltName(names))) = self.inner.tbs_certificate.get()? else {
+            return Ok(Vec::new());
+        };
+        Ok(names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DirectoryName(name) => Some(name_attributes(name)),
+                _ => None,
+            })
+            .flatten()
+            .collect())
+    }
+
+    /// Returns the public key as a public area.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the key is malformed or not supported.
+    pub fn public_key(&self) -> Result<TpmtPublic, TpmError> {
+        spki_to_public(
+            &self
+                .inner
+                .tbs_certificate
+                .subject_public_key_info
+                .to_der()?,
+        )
+    }
+
+    /// Verifies that the certificate is signed with the key of `issuer`.
+    /// Supports PKCS#1 v1.5 and ECDSA signatures.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TpmError` if the signature does not verify or the algorithm
+    /// is not supported.
+    pub fn verify_signed_by(&self, issuer: &Certificate) -> Result<(), TpmError> {
+        let public = issuer.public_key()?;
+        let (hash, rsa) = match self.inner.signature_algorithm.oid {
+            SHA_1_WITH_RSA_ENCRYPTION => (TpmAlgId::Sha1, true),
+            SHA_256_WITH_RSA_ENCRYPTION => (TpmAlgId::Sha256, true),
+            SHA_384_WITH_RSA_ENCRYPTION => (TpmAlgId::Sha384, true),
+            SHA_512_WITH_RSA_ENCRYPTION => (TpmAlgId::Sha512, true),
+            ECDSA_WITH_SHA_256 => (TpmAlgId::Sha256, false),
+            ECDSA_WITH_SHA_384 => (TpmAlgId::Sha384, false),
+            ECDSA_WITH_SHA_512 => (TpmAlgId::Sha512, false),
+            oid => {
+                return Err(TpmError::Execution(format!(
+                    "unsupported signature algorithm: {oid}"
+                )))
+            }
+        };
+        let signature = self.inner.signature.raw_bytes();
+        let signature = if rsa {
+            TpmtSignature {
+                sig_alg: TpmAlgId::Rsassa,
+                signature: TpmuSignature::Rsassa(TpmsSignatureRsa {
+                    hash,
+                    sig: Tpm2bPublicKeyRsa::try_from(signature)?,
+                }),
+            }
+        } else {
+            let mut reader = SliceReader::new(signature)?;
+            let (r, s) = reader.sequence(|reader| {
+                let r = UintRef::decode(reader)?.as_bytes().to_vec();
+                let s = UintRef::decode(reader)?.as_bytes().to_vec();
+                Ok((r, s))
+            })?;
+            reader.finish(())?;
+            TpmtSignature {
+                sig_alg: TpmAlgId::Ecdsa,
+                signature: TpmuSignature::Ecdsa(TpmsSignatureEcc {
+                    hash,
+                    signature_r: Tpm2bEccParameter::try_from(r.as_slice())?,
+                    signature_s: Tpm2bEccParameter::try_from(s.as_slice())?,
+                }),
+            }
+        };
+        if rsa != (public.object_type == TpmAlgId::Rsa) {
+            return Err(TpmError::Execution(
+                "signature algorithm does not match the issuer key".to_string(),
+            ));
+        }
+        verify_signature(&public, &self.tbs, &signature)
+    }
+}
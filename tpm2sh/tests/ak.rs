@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{
+    ak::{create_ak, load_ak, AkScheme},
+    credential::{activate_credential, Credential},
+    ek::{create_ek, requires_endorsement_policy, EkTemplate, EK_POLICY_C_SHA384},
+    read_public, SoftwareHash, TpmDevice,
+};
+use common::{create_primary, started_device, LOG};
+use rstest::rstest;
+use tpm2_protocol::{
+    data::{TpmAlgId, TpmaObject, TpmtScheme, TpmuAsymScheme, TpmuPublicParms},
+    hash::tpm_object_name,
+    TpmPermanent,
+};
+
+fn device() -> TpmDevice {
+    started_device([0x48; 32])
+}
+
+#[test]
+fn test_ak_templates() {
+    for (name, scheme, object_type) in [
+        ("rsassa", AkScheme::Rsassa, TpmAlgId::Rsa),
+        ("rsapss", AkScheme::Rsapss, TpmAlgId::Rsa),
+        ("ecdsa", AkScheme::Ecdsa, TpmAlgId::Ecc),
+    ] {
+        assert_eq!(name.parse::<AkScheme>().unwrap(), scheme);
+        assert_eq!(scheme.to_string(), name);
+
+        let public = scheme.public(TpmAlgId::Sha384);
+        assert_eq!(public.object_type, object_type);
+        assert!(public.object_attributes.contains(
+            TpmaObject::RESTRICTED | TpmaObject::SIGN_ENCRYPT | TpmaObject::USER_WITH_AUTH
+        ));
+        assert!(!public.object_attributes.contains(TpmaObject::DECRYPT));
+        let scheme = match public.parameters {
+            TpmuPublicParms::Rsa { scheme, .. } | TpmuPublicParms::Ecc { scheme, .. } => scheme,
+            _ => panic!("unexpected parameters"),
+        };
+        assert_eq!(
+            scheme.details,
+            TpmuAsymScheme::Hash(TpmAlgId::Sha384),
+            "{name}"
+        );
+        assert_ne!(scheme, TpmtScheme::default());
+    }
+    assert!("rsaes".parse::<AkScheme>().is_err());
+}
+
+#[test]
+fn test_ak_enrollment() {
+    let mut device = device();
+    let (ek, ek_public) = create_ek(&mut device, EkTemplate::EccNistP256, None, None, LOG).unwrap();
+    let ek = ek.into();
+    assert!(requires_endorsement_policy(&ek_public));
+    assert!(!requires_endorsement_policy(
+        &AkScheme::Ecdsa.public(TpmAlgId::Sha256)
+    ));
+
+    let ak = create_ak(
+        &mut device,
+        ek,
+        AkScheme::Ecdsa,
+        TpmAlgId::Sha256,
+        None,
+        None,
+        LOG,
+    )
+    .unwrap();
+    assert_eq!(ak.name, tpm_object_name(&SoftwareHash, &ak.public).unwrap());
+
+    let handle = load_ak(&mut device, ek, &ak, None, LOG).unwrap().into();
+    let (public, name) = read_public(&mut device, handle, LOG).unwrap();
+    assert_eq!(public, ak.public);
+    assert_eq!(name, ak.name);
+
+    let credential = Credential::new(&ek_public, &ak.name, b"ak").unwrap();
+    let cert_info =
+        activate_credential(&mut device, handle, ek, &credential, None, None, None, LOG).unwrap();
+    assert_eq!(&*cert_info, b"ak");
+}
+
+#[test]
+fn test_ak_endorsement_auth() {
+    let mut device = device();
+    let (ek, _) = create_ek(&mut device, EkTemplate::EccNistP256, None, None, LOG).unwrap();
+    let ek = ek.into();
+    let result = create_ak(
+        &mut device,
+        ek,
+        AkScheme::Ecdsa,
+        TpmAlgId::Sha256,
+        None,
+        Some("wrong"),
+        LOG,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_ak_ek_templates() {
+    for (template, policy) in [
+        (EkTemplate::Rsa2048, true),
+        (EkTemplate::EccNistP256, true),
+        (EkTemplate::Rsa3072, false),
+        (EkTemplate::EccNistP384, false),
+    ] {
+        let public = template.public();
+        assert_eq!(requires_endorsement_policy(&public), policy, "{template}");
+        if !policy {
+            assert_eq!(&*public.auth_policy, EK_POLICY_C_SHA384.as_slice());
+            assert!(public
+                .object_attributes
+                .contains(TpmaObject::USER_WITH_AUTH));
+        }
+    }
+}
+
+#[rstest]
+#[case(TpmaObject::empty(), true)]
+#[case(TpmaObject::USER_WITH_AUTH, false)]
+fn test_ak_ek_authorization(#[case] attributes: TpmaObject, #[case] policy: bool) {
+    let mut device = device();
+    let mut template = EkTemplate::EccNistP256.public();
+    template.object_attributes |= attributes;
+    let ek = create_primary(&mut device, TpmPermanent::ENDORSEMENT, template)
+        .object_handle
+        .into();
+    let (ek_public, _) = read_public(&mut device, ek, LOG).unwrap();
+    assert_eq!(requires_endorsement_policy(&ek_public), policy);
+
+    let ak = create_ak(
+        &mut device,
+        ek,
+        AkScheme::Ecdsa,
+        TpmAlgId::Sha256,
+        None,
+        Some("wrong"),
+        LOG,
+    );
+    assert_eq!(ak.is_err(), policy);
+
+    let ak = create_ak(
+        &mut device,
+        ek,
+        AkScheme::Ecdsa,
+        TpmAlgId::Sha256,
+        None,
+        None,
+        LOG,
+    )
+    .unwrap();
+    let handle = load_ak(&mut device, ek, &ak, Some("wrong"), LOG);
+    assert_eq!(handle.is_err(), policy);
+    let handle = load_ak(&mut device, ek, &ak, None, LOG).unwrap().into();
+
+    let credential = Credential::new(&ek_public, &ak.name, b"ak").unwrap();
+    let cert_info = activate_credential(
+        &mut device,
+        handle,
+        ek,
+        &credential,
+        None,
+        None,
+        Some("wrong"),
+        LOG,
+    );
+    assert_eq!(cert_info.is_err(), policy);
+    let cert_info =
+        activate_credential(&mut device, handle, ek, &credential, None, None, None, LOG).unwrap();
+    assert_eq!(&*cert_info, b"ak");
+}
@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use cli::attestation::{AttestExpectations, AttestFinding, AttestSeverity, SignedAttest};
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use tpm2_protocol::{
+    data::{
+        Tpm2bData, Tpm2bDigest, Tpm2bEccParameter, Tpm2bName, TpmAlgId, TpmEccCurve, TpmSt,
+        TpmaObject, TpmiYesNo, TpmsAttest, TpmsCertifyInfo, TpmsClockInfo, TpmsEccPoint,
+        TpmsSignatureEcc, TpmsTimeAttestInfo, TpmsTimeInfo, TpmtKdfScheme, TpmtPublic, TpmtScheme,
+        TpmtSignature, TpmtSymDefObject, TpmuAsymScheme, TpmuAttest, TpmuPublicId, TpmuPublicParms,
+        TpmuSignature,
+    },
+    TpmBuild, TpmWriter, TPM_MAX_COMMAND_SIZE,
+};
+
+const SIGNER: &[u8] = &[0x00, 0x0b, 0xaa, 0xbb];
+const NONCE: &[u8] = b"nonce";
+const FIRMWARE: u64 = 0x0007_0002_0000_0001;
+
+fn build<T: TpmBuild>(obj: &T) -> Vec<u8> {
+    let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+    let mut writer = TpmWriter::new(&mut buf);
+    obj.build(&mut writer).unwrap();
+    let len = writer.len();
+    buf[..len].to_vec()
+}
+
+fn clock(clock: u64, reset_count: u32, restart_count: u32) -> TpmsClockInfo {
+    TpmsClockInfo {
+        clock,
+        reset_count,
+        restart_count,
+        safe: TpmiYesNo(true),
+    }
+}
+
+fn certify(clock_info: TpmsClockInfo) -> TpmsAttest {
+    TpmsAttest {
+        magic: 0xff54_4347,
+        attest_type: TpmSt::AttestCertify,
+        qualified_signer: Tpm2bName::try_from(SIGNER).unwrap(),
+        extra_data: Tpm2bData::try_from(NONCE).unwrap(),
+        clock_info,
+        firmware_version: FIRMWARE,
+        attested: TpmuAttest::Certify(TpmsCertifyInfo {
+            name: Tpm2bName::try_from(&[0x00, 0x0b, 0x01][..]).unwrap(),
+            qualified_name: Tpm2bName::try_from(&[0x00, 0x0b, 0x02][..]).unwrap(),
+        }),
+    }
+}
+
+fn signer() -> (SigningKey, TpmtPublic) {
+    let key = SigningKey::from_slice(&[0x17; 32]).unwrap();
+    let point = key.verifying_key().to_encoded_point(false);
+    let public = TpmtPublic {
+        object_type: TpmAlgId::Ecc,
+        name_alg: TpmAlgId::Sha256,
+        object_attributes: TpmaObject::SIGN_ENCRYPT | TpmaObject::RESTRICTED,
+        auth_policy: Tpm2bDigest::default(),
+        parameters: TpmuPublicParms::Ecc {
+            symmetric: TpmtSymDefObject::default(),
+            scheme: TpmtScheme {
+                scheme: TpmAlgId::Ecdsa,
+                details: TpmuAsymScheme::Hash(TpmAlgId::Sha256),
+            },
+            curve_id: TpmEccCurve::NistP256,
+            kdf: TpmtKdfScheme::default(),
+        },
+        unique: TpmuPublicId::Ecc(TpmsEccPoint {
+            x: Tpm2bEccParameter::try_from(point.x().unwrap().as_slice()).unwrap(),
+            y: Tpm2bEccParameter::try_from(point.y().unwrap().as_slice()).unwrap(),
+        }),
+    };
+    (key, public)
+}
+
+fn sign(attest: Vec<u8>) -> (SignedAttest, TpmtPublic) {
+    let (key, public) = signer();
+    let signature: Signature = key.sign(&attest);
+    let (r, s) = signature.split_bytes();
+    let signature = TpmtSignature {
+        sig_alg: TpmAlgId::Ecdsa,
+        signature: TpmuSignature::Ecdsa(TpmsSignatureEcc {
+            hash: TpmAlgId::Sha256,
+            signature_r: Tpm2bEccParameter::try_from(r.as_slice()).unwrap(),
+            signature_s: Tpm2bEccParameter::try_from(s.as_slice()).unwrap(),
+        }),
+    };
+    (SignedAttest { attest, signature }, public)
+}
+
+fn expectations(previous_clock: TpmsClockInfo) -> AttestExpectations {
+    AttestExpectations {
+        attest_type: Some(TpmSt::AttestCertify),
+        qualified_signer: Some(SIGNER.to_vec()),
+        extra_data: Some(NONCE.to_vec()),
+        previous_clock: Some(previous_clock),
+        firmware_version: Some(FIRMWARE),
+    }
+}
+
+#[test]
+fn test_attestation_valid() {
+    let (signed, public) = sign(build(&certify(clock(5000, 3, 1))));
+    let report = signed.verify(&public, &expectations(clock(4000, 3, 1)));
+    assert!(report.findings.is_empty(), "{:?}", report.findings);
+    assert!(report.is_valid());
+    let json = report.to_json();
+    assert_eq!(json["valid"], true);
+    assert_eq!(json["type"], "TPM_ST_ATTEST_CERTIFY");
+    assert_eq!(json["reset_count"], 3);
+
+    let attest = report.into_result().unwrap();
+    assert!(matches!(attest.attested, TpmuAttest::Certify(_)));
+}
+
+#[test]
+fn test_attestation_time() {
+    let attest = TpmsAttest {
+        attest_type: TpmSt::AttestTime,
+        attested: TpmuAttest::Time(TpmsTimeAttestInfo {
+            time: TpmsTimeInfo {
+                time: 77,
+                clock_info: clock(5000, 3, 1),
+            },
+            firmware_version: FIRMWARE,
+        }),
+        ..certify(clock(5000, 3, 1))
+    };
+    let (signed, public) = sign(build(&attest));
+    let mut expect = expectations(clock(5000, 3, 1));
+    assert!(!signed.verify(&public, &expect).is_valid());
+    expect.attest_type = Some(TpmSt::AttestTime);
+    assert!(signed.verify(&public, &expect).is_valid());
+}
+
+#[test]
+fn test_attestation_clock() {
+    let (signed, public) = sign(build(&certify(clock(5000, 3, 1))));
+    let findings = |previous| signed.verify(&public, &expectations(previous)).findings;
+
+    assert_eq!(
+        findings(clock(6000, 3, 1)),
+        vec![AttestFinding::ClockRollback {
+            previous: 6000,
+            current: 5000
+        }]
+    );
+    assert_eq!(
+        findings(clock(4000, 2, 7)),
+        vec![AttestFinding::Reset {
+            previous: 2,
+            current: 3
+        }]
+    );
+    assert_eq!(
+        findings(clock(4000, 3, 0)),
+        vec![AttestFinding::Restart {
+            previous: 0,
+            current: 1
+        }]
+    );
+    assert_eq!(
+        findings(clock(4000, 3, 2)),
+        vec![AttestFinding::RestartCountRollback {
+            previous: 2,
+            current: 1
+        }]
+    );
+    assert_eq!(
+        findings(clock(4000, 4, 0)),
+        vec![AttestFinding::ResetCountRollback {
+            previous: 4,
+            current: 3
+        }]
+    );
+
+    let report = signed.verify(&public, &expectations(clock(4000, 2, 0)));
+    assert!(report.is_valid());
+    assert_eq!(report.findings[0].severity(), AttestSeverity::Info);
+
+    let mut unsafe_clock = clock(5000, 3, 1);
+    unsafe_clock.safe = TpmiYesNo(false);
+    let (signed, public) = sign(build(&certify(unsafe_clock)));
+    let report = signed.verify(&public, &AttestExpectations::default());
+    assert_eq!(report.findings, vec![AttestFinding::ClockNotSafe]);
+    assert!(report.is_valid());
+}
+
+#[test]
+fn test_attestation_failures() {
+    let (mut signed, public) = sign(build(&certify(clock(5000, 3, 1))));
+    signed.attest[10] ^= 1;
+    let expect = AttestExpectations {
+        qualified_signer: Some(vec![0x00, 0x0b]),
+        extra_data: Some(b"other".to_vec()),
+        firmware_version: Some(FIRMWARE + 1),
+        ..Default::default()
+    };
+    let report = signed.verify(&public, &expect);
+    assert!(!report.is_valid());
+    let kinds: Vec<&str> = report.findings.iter().map(AttestFinding::kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            "invalid_signature",
+            "qualified_signer_mismatch",
+            "extra_data_mismatch",
+            "firmware_version_mismatch"
+        ]
+    );
+    let json = report.to_json();
+    assert_eq!(json["valid"], false);
+    assert_eq!(json["findings"][0]["severity"], "failure");
+
+    let mut forged = build(&certify(clock(5000, 3, 1)));
+    forged[..4].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+    let (signed, public) = sign(forged);
+    let report = signed.verify(&public, &AttestExpectations::default());
+    assert!(report.attest.is_none());
+    assert_eq!(
+        report.findings,
+        vec![AttestFinding::InvalidMagic(0xdead_beef)]
+    );
+    assert!(report.into_result().is_err());
+
+    let mut truncated = build(&certify(clock(5000, 3, 1)));
+    truncated.truncate(truncated.len() - 1);
+    let (signed, public) = sign(truncated);
+    let report = signed.verify(&public, &AttestExpectations::default());
+    assert!(matches!(
+        report.findings.as_slice(),
+        [AttestFinding::Malformed(_)]
+    ));
+}
@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+//! Helpers shared by the integration tests. Each test binary uses only some
+//! of them.
+
+#![allow(dead_code)]
+
+use cli::{cli::LogFormat, soft_tpm::SoftTpm, TpmDevice, TpmError};
+use tpm2_protocol::{
+    data::{
+        Tpm2b, Tpm2bAuth, Tpm2bNonce, Tpm2bPublic, Tpm2bSensitiveCreate, TpmRc, TpmSu, TpmaSession,
+        TpmlPcrSelection, TpmsAuthCommand, TpmtPublic,
+    },
+    message::{TpmCreatePrimaryCommand, TpmCreatePrimaryResponse, TpmStartupCommand},
+    TpmBuild, TpmPermanent, TpmResult, TpmSession, TpmWriter, TPM_MAX_COMMAND_SIZE,
+};
+
+pub const LOG: LogFormat = LogFormat::Plain;
+
+/// Returns a device backed by a `SoftTpm` derived from `seed`.
+pub fn device(seed: [u8; 32]) -> TpmDevice {
+    TpmDevice::from_transport(Box::new(SoftTpm::from_seed(seed)))
+}
+
+/// Returns [`device`] started with `TPM2_Startup(TPM_SU_CLEAR)`.
+pub fn started_device(seed: [u8; 32]) -> TpmDevice {
+    let mut device = device(seed);
+    let cmd = TpmStartupCommand {
+        startup_type: TpmSu::Clear,
+    };
+    device.execute(&cmd, &[], &[], LOG).unwrap();
+    device
+}
+
+/// Returns a password session for `auth`.
+pub fn password(auth: &[u8]) -> TpmsAuthCommand {
+    TpmsAuthCommand {
+        session_handle: TpmSession::PASSWORD,
+        nonce: Tpm2bNonce::default(),
+        session_attributes: TpmaSession::CONTINUE_SESSION,
+        hmac: Tpm2bAuth::try_from(auth).unwrap(),
+    }
+}
+
+/// Returns the response code of a failed TPM command.
+pub fn rc_of(err: TpmError) -> TpmRc {
+    match err {
+        TpmError::TpmRc(rc) => rc,
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+/// Creates a primary object from `template` under `hierarchy` with an empty
+/// authorization value.
+pub fn create_primary(
+    device: &mut TpmDevice,
+    hierarchy: TpmPermanent,
+    template: TpmtPublic,
+) -> TpmCreatePrimaryResponse {
+    let cmd = TpmCreatePrimaryCommand {
+        in_sensitive: Tpm2bSensitiveCreate::default(),
+        in_public: Tpm2bPublic::from(template),
+        outside_info: Tpm2b::default(),
+        creation_pcr: TpmlPcrSelection::default(),
+    };
+    let (resp, _) = device
+        .execute(&cmd, &[hierarchy.into()], &[password(b"")], LOG)
+        .unwrap();
+    resp
+}
+
+/// Returns the bytes written by `f`.
+pub fn build_with(f: impl FnOnce(&mut TpmWriter) -> TpmResult<()>) -> Vec<u8> {
+    let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+    let mut writer = TpmWriter::new(&mut buf);
+    f(&mut writer).unwrap();
+    let len = writer.len();
+    buf[..len].to_vec()
+}
+
+/// Marshals `value`.
+pub fn build<T: TpmBuild>(value: &T) -> Vec<u8> {
+    build_with(|writer| value.build(writer))
+}
@@ -0,0 +1,194 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{
+    cli::Object,
+    credential::{activate_credential, Credential},
+    make_credential, SoftwareHash, TpmDevice, TpmError,
+};
+use common::{password, rc_of, started_device, LOG};
+use rsa::{traits::PublicKeyParts, Oaep, RsaPrivateKey};
+use sha2::Sha256;
+use tpm2_protocol::{
+    data::{
+        Tpm2bDigest, Tpm2bName, TpmAlgId, TpmEccCurve, TpmRcBase, TpmRh, TpmaObject, TpmsEccPoint,
+        TpmtKdfScheme, TpmtPublic, TpmtScheme, TpmtSymDefObject, TpmuPublicId, TpmuPublicParms,
+        TpmuSymKeyBits, TpmuSymMode,
+    },
+    hash::tpm_handle_name,
+    message::TpmActivateCredentialCommand,
+    policy::TpmPolicyDigest,
+    TpmBuffer, TpmParse, TpmPermanent, TpmTransient,
+};
+
+const SECRET: &[u8] = b"enrollment secret";
+/// `TPM2_PolicySecret(TPM_RH_ENDORSEMENT)`, the policy of the standard EKs.
+const EK_POLICY: &str = "837197674484b3f81a90cc8d46a5d724fd52d76e06520b64f2a1da1b331469aa";
+
+fn aes_128_cfb() -> TpmtSymDefObject {
+    TpmtSymDefObject {
+        algorithm: TpmAlgId::Aes,
+        key_bits: TpmuSymKeyBits::Aes(128),
+        mode: TpmuSymMode::Aes(TpmAlgId::Cfb),
+    }
+}
+
+fn ek_template() -> TpmtPublic {
+    TpmtPublic {
+        object_type: TpmAlgId::Ecc,
+        name_alg: TpmAlgId::Sha256,
+        object_attributes: TpmaObject::FIXED_TPM
+            | TpmaObject::FIXED_PARENT
+            | TpmaObject::SENSITIVE_DATA_ORIGIN
+            | TpmaObject::ADMIN_WITH_POLICY
+            | TpmaObject::RESTRICTED
+            | TpmaObject::DECRYPT,
+        auth_policy: Tpm2bDigest::try_from(hex::decode(EK_POLICY).unwrap().as_slice()).unwrap(),
+        parameters: TpmuPublicParms::Ecc {
+            symmetric: aes_128_cfb(),
+            scheme: TpmtScheme::default(),
+            curve_id: TpmEccCurve::NistP256,
+            kdf: TpmtKdfScheme::default(),
+        },
+        unique: TpmuPublicId::Ecc(TpmsEccPoint::default()),
+    }
+}
+
+fn key_template() -> TpmtPublic {
+    TpmtPublic {
+        object_attributes: TpmaObject::FIXED_TPM
+            | TpmaObject::FIXED_PARENT
+            | TpmaObject::SENSITIVE_DATA_ORIGIN
+            | TpmaObject::USER_WITH_AUTH
+            | TpmaObject::RESTRICTED
+            | TpmaObject::DECRYPT,
+        auth_policy: Tpm2bDigest::default(),
+        ..ek_template()
+    }
+}
+
+fn create_primary(
+    device: &mut TpmDevice,
+    hierarchy: TpmPermanent,
+    template: TpmtPublic,
+) -> (TpmTransient, TpmtPublic, Tpm2bName) {
+    let resp = common::create_primary(device, hierarchy, template);
+    (resp.object_handle, resp.out_public.inner, resp.name)
+}
+
+/// Starts a TPM and loads an EK and a key the credential is made for.
+fn setup() -> (TpmDevice, TpmTransient, TpmTransient, TpmtPublic, Tpm2bName) {
+    let mut device = started_device([0x33; 32]);
+    let (ek, ek_public, _) = create_primary(&mut device, TpmPermanent::ENDORSEMENT, ek_template());
+    let (key, _, key_name) = create_primary(&mut device, TpmPermanent::OWNER, key_template());
+    (device, key, ek, ek_public, key_name)
+}
+
+fn activate(
+    device: &mut TpmDevice,
+    key: TpmTransient,
+    ek: TpmTransient,
+    credential: &Credential,
+) -> Result<Tpm2bDigest, TpmError> {
+    activate_credential(
+        device,
+        key.into(),
+        ek.into(),
+        credential,
+        None,
+        None,
+        None,
+        LOG,
+    )
+}
+
+#[test]
+fn test_credential_ek_policy() {
+    let mut policy = TpmPolicyDigest::new(&SoftwareHash, TpmAlgId::Sha256).unwrap();
+    let endorsement = tpm_handle_name(TpmRh::Endorsement as u32).unwrap();
+    policy.secret(&endorsement, &[]).unwrap();
+    assert_eq!(hex::encode(&**policy.digest()), EK_POLICY);
+}
+
+#[test]
+fn test_credential_activate() {
+    let (mut device, key, ek, ek_public, key_name) = setup();
+    let credential = Credential::new(&ek_public, &key_name, SECRET).unwrap();
+    let line = serde_json::to_string(&Object::Credential(credential.to_output().unwrap())).unwrap();
+    let Object::Credential(output) = serde_json::from_str(&line).unwrap() else {
+        panic!("not a credential: {line}");
+    };
+    let credential = Credential::from_output(&output).unwrap();
+
+    let cert_info = activate(&mut device, key, ek, &credential).unwrap();
+    assert_eq!(&*cert_info, SECRET);
+}
+
+#[test]
+fn test_credential_activate_failures() {
+    let (mut device, key, ek, ek_public, key_name) = setup();
+
+    let credential = Credential::new(&ek_public, &key_name[..4], SECRET).unwrap();
+    let rc = rc_of(activate(&mut device, key, ek, &credential).unwrap_err());
+    assert_eq!(rc.base(), Ok(TpmRcBase::Integrity));
+
+    let mut credential = Credential::new(&ek_public, &key_name, SECRET).unwrap();
+    let mut blob = credential.blob.to_vec();
+    let last = blob.len() - 1;
+    blob[last] ^= 1;
+    credential.blob = blob.as_slice().try_into().unwrap();
+    let rc = rc_of(activate(&mut device, key, ek, &credential).unwrap_err());
+    assert_eq!(rc.base(), Ok(TpmRcBase::Integrity));
+
+    let (_, other_public, _) = create_primary(&mut device, TpmPermanent::OWNER, key_template());
+    let credential = Credential::new(&other_public, &key_name, SECRET).unwrap();
+    let rc = rc_of(activate(&mut device, key, ek, &credential).unwrap_err());
+    assert_eq!(rc.base(), Ok(TpmRcBase::Integrity));
+
+    let credential = Credential::new(&ek_public, &key_name, SECRET).unwrap();
+    let cmd = TpmActivateCredentialCommand {
+        credential_blob: credential.blob,
+        secret: credential.secret,
+    };
+    let rc = rc_of(
+        device
+            .execute(
+                &cmd,
+                &[key.into(), ek.into()],
+                &[password(b""), password(b"")],
+                LOG,
+            )
+            .unwrap_err(),
+    );
+    assert_eq!(rc.base(), Ok(TpmRcBase::AuthUnavailable));
+
+    let err = Credential::new(&ek_public, &key_name, &[0x55; 33]).unwrap_err();
+    assert!(err.to_string().contains("larger than 32 bytes"), "{err}");
+}
+
+#[test]
+fn test_credential_rsa_seed() {
+    let key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+    let ek_public = TpmtPublic {
+        object_type: TpmAlgId::Rsa,
+        parameters: TpmuPublicParms::Rsa {
+            symmetric: aes_128_cfb(),
+            scheme: TpmtScheme::default(),
+            key_bits: 1024,
+            exponent: 0,
+        },
+        unique: TpmuPublicId::Rsa(TpmBuffer::try_from(key.n().to_bytes_be().as_slice()).unwrap()),
+        ..ek_template()
+    };
+    let (blob, secret) = make_credential(&ek_public, &[0x00, 0x0b, 0x01], SECRET).unwrap();
+    let seed = key
+        .decrypt(Oaep::new_with_label::<Sha256, _>("IDENTITY\0"), &secret)
+        .unwrap();
+    assert_eq!(seed.len(), 32);
+
+    let (integrity, enc_identity) = Tpm2bDigest::parse(&blob).unwrap();
+    assert_eq!(integrity.len(), 32);
+    assert_eq!(enc_identity.len(), 2 + SECRET.len());
+}
@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{
+    cli::DecodeInput,
+    command::decode::{decode_command, decode_input, decode_response},
+    json::tpm_to_json,
+    pretty_printer::pretty_format,
+};
+use common::build_with;
+use rstest::rstest;
+use serde_json::json;
+use tpm2_protocol::{
+    data::{
+        Tpm2b, Tpm2bAuth, Tpm2bNonce, TpmCap, TpmCc, TpmRc, TpmRcBase, TpmSt, TpmaSession,
+        TpmsAuthCommand, TpmsAuthResponse,
+    },
+    message::{
+        tpm_build_command, tpm_build_response, TpmGetCapabilityCommand, TpmUnsealCommand,
+        TpmUnsealResponse,
+    },
+    TpmSession, TpmTransient,
+};
+
+fn unseal_command() -> Vec<u8> {
+    let session = TpmsAuthCommand {
+        session_handle: TpmSession::try_from(0x0200_0000).unwrap(),
+        nonce: Tpm2bNonce::try_from(&[0xaa; 4][..]).unwrap(),
+        session_attributes: TpmaSession::CONTINUE_SESSION,
+        hmac: Tpm2bAuth::default(),
+    };
+    build_with(|writer| {
+        tpm_build_command(
+            &TpmUnsealCommand {},
+            TpmSt::Sessions,
+            &[TpmTransient::try_from(0x8000_0001).unwrap().into()],
+            &[session],
+            writer,
+        )
+    })
+}
+
+fn get_capability_command() -> Vec<u8> {
+    let command = TpmGetCapabilityCommand {
+        cap: TpmCap::Handles,
+        property: 0x8000_0000,
+        property_count: 16,
+    };
+    build_with(|writer| tpm_build_command(&command, TpmSt::NoSessions, &[], &[], writer))
+}
+
+#[test]
+fn test_decode_command() {
+    let decoded = decode_command(&unseal_command(), Some(TpmCc::Unseal)).unwrap();
+    let json = tpm_to_json(&decoded);
+    assert_eq!(json["tag"], "TPM_ST_SESSIONS");
+    assert_eq!(json["cc"], "TPM_CC_Unseal");
+    assert_eq!(json["handles"], json!(["0x80000001"]));
+    assert_eq!(json["sessions"][0]["session_handle"], "0x02000000");
+    assert_eq!(json["sessions"][0]["nonce"], "aaaaaaaa");
+    assert_eq!(
+        json["sessions"][0]["session_attributes"],
+        json!(["CONTINUE_SESSION"])
+    );
+    assert_eq!(json["parameters"], json!({}));
+
+    let text = pretty_format(&decoded, "");
+    assert!(
+        text.contains("handles: (count=1)\n  0x80000001\n"),
+        "{text}"
+    );
+    assert!(
+        text.contains("session_attributes: CONTINUE_SESSION (0x1)"),
+        "{text}"
+    );
+}
+
+#[test]
+fn test_decode_command_cc_mismatch() {
+    let err = decode_command(&unseal_command(), Some(TpmCc::Load)).unwrap_err();
+    assert!(err.to_string().contains("mismatch"), "{err}");
+}
+
+#[test]
+fn test_decode_command_error_location() {
+    let mut buf = get_capability_command();
+    buf[10..14].copy_from_slice(&0xffu32.to_be_bytes());
+    let err = decode_command(&buf, None).unwrap_err().to_string();
+    assert!(err.contains("cap"), "{err}");
+    assert!(err.contains("at offset 10"), "{err}");
+    assert!(err.contains("000000ff80000000"), "{err}");
+}
+
+#[test]
+fn test_decode_response() {
+    let response = TpmUnsealResponse {
+        out_data: Tpm2b::try_from(&b"secret"[..]).unwrap(),
+    };
+    let session = TpmsAuthResponse {
+        session_attributes: TpmaSession::CONTINUE_SESSION,
+        ..Default::default()
+    };
+    let buf = build_with(|writer| {
+        tpm_build_response(
+            &response,
+            &[session],
+            TpmRc::from(TpmRcBase::Success),
+            writer,
+        )
+    });
+    let json = tpm_to_json(&decode_response(&buf, TpmCc::Unseal).unwrap());
+    assert_eq!(json["tag"], "TPM_ST_SESSIONS");
+    assert_eq!(json["rc"], 0);
+    assert_eq!(json["parameters"]["out_data"], hex::encode(b"secret"));
+    assert_eq!(json["sessions"].as_array().unwrap().len(), 1);
+
+    let buf = hex::decode("80010000000a00000101").unwrap();
+    let json = tpm_to_json(&decode_response(&buf, TpmCc::Unseal).unwrap());
+    assert_eq!(json, json!({"tag": "TPM_ST_NO_SESSIONS", "rc": 0x101}));
+}
+
+#[rstest]
+#[case(b"80010000000a00000101".as_slice(), DecodeInput::Auto)]
+#[case(b"0x80 01 00 00 00 0a\n00 00 01 01\n".as_slice(), DecodeInput::Auto)]
+#[case(b"\x80\x01\x00\x00\x00\x0a\x00\x00\x01\x01".as_slice(), DecodeInput::Auto)]
+#[case(b"80010000000a00000101".as_slice(), DecodeInput::Hex)]
+#[case(b"\x80\x01\x00\x00\x00\x0a\x00\x00\x01\x01".as_slice(), DecodeInput::Binary)]
+fn test_decode_input(#[case] input: &[u8], #[case] input_format: DecodeInput) {
+    assert_eq!(
+        decode_input(input, input_format).unwrap(),
+        hex::decode("80010000000a00000101").unwrap()
+    );
+}
+
+#[test]
+fn test_decode_input_format() {
+    let input = b"80010000000a00000101";
+    assert_eq!(decode_input(input, DecodeInput::Binary).unwrap(), input);
+    assert!(decode_input(b"\x80\x01", DecodeInput::Hex).is_err());
+    assert!(decode_input(b"", DecodeInput::Binary).is_err());
+}
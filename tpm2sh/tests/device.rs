@@ -0,0 +1,158 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use cli::{cli::LogFormat, TpmDevice, TpmError, TpmRetryPolicy};
+use rstest::rstest;
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    io::{self, Read, Write},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use tpm2_protocol::{
+    data::{TpmRc, TpmRcBase},
+    message::{tpm_build_response, TpmGetRandomCommand, TpmGetRandomResponse},
+    TpmWriter, TPM_MAX_COMMAND_SIZE,
+};
+
+const LOG: LogFormat = LogFormat::Plain;
+
+/// Answers each command with the next scripted response, and reports
+/// `WouldBlock` when the script runs out.
+struct Script {
+    responses: VecDeque<Vec<u8>>,
+    current: io::Cursor<Vec<u8>>,
+    commands: Rc<Cell<usize>>,
+}
+
+impl Read for Script {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.current.read(buf)? {
+            0 => Err(io::ErrorKind::WouldBlock.into()),
+            n => Ok(n),
+        }
+    }
+}
+
+impl Write for Script {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.commands.set(self.commands.get() + 1);
+        self.current = io::Cursor::new(self.responses.pop_front().unwrap_or_default());
+        Ok(())
+    }
+}
+
+fn warning(base: TpmRcBase) -> Vec<u8> {
+    let mut buf = vec![0x80, 0x01, 0, 0, 0, 10];
+    buf.extend_from_slice(&TpmRc::from(base).value().to_be_bytes());
+    buf
+}
+
+fn success() -> Vec<u8> {
+    let response = TpmGetRandomResponse {
+        random_bytes: [0xaa; 4][..].try_into().unwrap(),
+    };
+    let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+    let mut writer = TpmWriter::new(&mut buf);
+    tpm_build_response(&response, &[], TpmRc::from(TpmRcBase::Success), &mut writer).unwrap();
+    let len = writer.len();
+    buf[..len].to_vec()
+}
+
+/// Returns a device that answers with `responses`, and the number of
+/// commands sent to it. The `TPM_PT_MAX_COMMAND_SIZE` query sent by the
+/// device is left to time out before the first response.
+fn scripted(responses: Vec<Vec<u8>>) -> (TpmDevice, Rc<Cell<usize>>) {
+    let commands = Rc::new(Cell::new(0));
+    let mut responses = VecDeque::from(responses);
+    responses.push_front(Vec::new());
+    let script = Script {
+        responses,
+        current: io::Cursor::new(Vec::new()),
+        commands: commands.clone(),
+    };
+    let mut device = TpmDevice::from_transport(Box::new(script));
+    device.set_timeout(Some(Duration::from_millis(20)));
+    device.max_command_size(LOG);
+    device.set_retry_policy(TpmRetryPolicy {
+        initial_delay: Duration::from_millis(1),
+        ..TpmRetryPolicy::default()
+    });
+    commands.set(0);
+    (device, commands)
+}
+
+fn get_random(device: &mut TpmDevice) -> Result<(), TpmError> {
+    let cmd = TpmGetRandomCommand { bytes_requested: 4 };
+    device.execute(&cmd, &[], &[], LOG).map(|_| ())
+}
+
+#[rstest]
+#[case(TpmRcBase::Retry)]
+#[case(TpmRcBase::Yielded)]
+#[case(TpmRcBase::Testing)]
+#[case(TpmRcBase::NvRate)]
+#[case(TpmRcBase::NvUnavailable)]
+fn test_device_retries_warning(#[case] base: TpmRcBase) {
+    let (mut device, commands) = scripted(vec![warning(base), warning(base), success()]);
+    get_random(&mut device).unwrap();
+    assert_eq!(commands.get(), 3);
+}
+
+#[test]
+fn test_device_retry_limit() {
+    let responses = vec![warning(TpmRcBase::Retry); 16];
+    let (mut device, commands) = scripted(responses);
+    device.set_retry_policy(TpmRetryPolicy {
+        max_retries: 2,
+        initial_delay: Duration::from_millis(1),
+        ..TpmRetryPolicy::default()
+    });
+    let err = get_random(&mut device).unwrap_err();
+    assert!(
+        matches!(err, TpmError::TpmRc(rc) if rc == TpmRc::from(TpmRcBase::Retry)),
+        "{err}"
+    );
+    assert_eq!(commands.get(), 3);
+}
+
+#[test]
+fn test_device_retry_budget() {
+    let responses = vec![warning(TpmRcBase::Yielded); 16];
+    let (mut device, commands) = scripted(responses);
+    device.set_retry_policy(TpmRetryPolicy {
+        initial_delay: Duration::from_millis(20),
+        budget: Duration::from_millis(50),
+        ..TpmRetryPolicy::default()
+    });
+    get_random(&mut device).unwrap_err();
+    assert_eq!(commands.get(), 2);
+}
+
+#[test]
+fn test_device_no_retry_for_other_warnings() {
+    let (mut device, commands) = scripted(vec![warning(TpmRcBase::ObjectMemory), success()]);
+    let err = get_random(&mut device).unwrap_err();
+    assert!(
+        matches!(err, TpmError::TpmRc(rc) if rc == TpmRc::from(TpmRcBase::ObjectMemory)),
+        "{err}"
+    );
+    assert_eq!(commands.get(), 1);
+}
+
+#[test]
+fn test_device_timeout() {
+    let (mut device, _) = scripted(Vec::new());
+    let start = Instant::now();
+    let err = get_random(&mut device).unwrap_err();
+    assert!(
+        matches!(&err, TpmError::Io(err) if err.kind() == io::ErrorKind::TimedOut),
+        "{err}"
+    );
+    assert!(start.elapsed() >= Duration::from_millis(20));
+}
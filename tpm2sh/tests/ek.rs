@@ -0,0 +1,518 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{
+    credential::{activate_credential, Credential},
+    ek::{
+        certificate_to_pem, create_ek, load_ca_directory, read_ek_certificate,
+        verify_ek_certificate, EkCertificateInfo, EkTemplate, EK_POLICY_A_SHA256,
+        TCG_AT_TPM_MANUFACTURER, TCG_AT_TPM_MODEL, TCG_AT_TPM_VERSION,
+    },
+    x509::Certificate,
+    SoftwareHash, TpmDevice, TpmError,
+};
+use common::{create_primary, password, started_device, LOG};
+use const_oid::{
+    db::{
+        rfc4519::CN,
+        rfc5912::{
+            ID_CE_BASIC_CONSTRAINTS, ID_CE_KEY_USAGE, ID_CE_SUBJECT_ALT_NAME, ID_EC_PUBLIC_KEY,
+            SECP_256_R_1, SHA_256_WITH_RSA_ENCRYPTION,
+        },
+    },
+    ObjectIdentifier,
+};
+use rsa::{pkcs8::EncodePublicKey, Pkcs1v15Sign, RsaPrivateKey};
+use sha2::{Digest, Sha256};
+use std::{slice, time::Duration};
+use tpm2_protocol::{
+    data::{
+        Tpm2bAuth, Tpm2bDigest, Tpm2bMaxNvBuffer, Tpm2bNvPublic, TpmAlgId, TpmRh, TpmaNv,
+        TpmaObject, TpmsNvPublic, TpmtPublic, TpmuPublicId,
+    },
+    hash::tpm_handle_name,
+    message::{TpmNvDefineSpaceCommand, TpmNvWriteCommand},
+    policy::TpmPolicyDigest,
+    TpmNvIndex, TpmPermanent,
+};
+
+fn device() -> TpmDevice {
+    started_device([0x46; 32])
+}
+
+/// Provisions `data` to an NV index the way a manufacturer would, with room
+/// to spare after it.
+fn provision(device: &mut TpmDevice, index: u32, data: &[u8]) {
+    let index = TpmNvIndex::try_from(index).unwrap();
+    let owner = TpmPermanent::OWNER.into();
+    let cmd = TpmNvDefineSpaceCommand {
+        auth: Tpm2bAuth::default(),
+        public_info: Tpm2bNvPublic::from(TpmsNvPublic {
+            nv_index: index,
+            name_alg: TpmAlgId::Sha256,
+            attributes: TpmaNv::OWNERWRITE | TpmaNv::OWNERREAD | TpmaNv::AUTHREAD,
+            auth_policy: Tpm2bDigest::default(),
+            data_size: u16::try_from(data.len() + 64).unwrap(),
+        }),
+    };
+    device
+        .execute(&cmd, &[owner], &[password(b"")], LOG)
+        .unwrap();
+    for (i, chunk) in data.chunks(512).enumerate() {
+        let cmd = TpmNvWriteCommand {
+            data: Tpm2bMaxNvBuffer::try_from(chunk).unwrap(),
+            offset: u16::try_from(i * 512).unwrap(),
+        };
+        device
+            .execute(&cmd, &[owner, index.into()], &[password(b"")], LOG)
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_ek_templates() {
+    for template in EkTemplate::ALL {
+        assert_eq!(
+            template.to_string().parse::<EkTemplate>().unwrap(),
+            template
+        );
+        let public = template.public();
+        let low_range = matches!(template, EkTemplate::Rsa2048 | EkTemplate::EccNistP256);
+        assert_eq!(
+            public.object_attributes.bits(),
+            if low_range { 0x0003_00b2 } else { 0x0003_00f2 },
+            "{template}"
+        );
+        let name_alg = if low_range {
+            TpmAlgId::Sha256
+        } else {
+            TpmAlgId::Sha384
+        };
+        assert_eq!(public.name_alg, name_alg, "{template}");
+        assert_eq!(
+            public.auth_policy.len(),
+            tpm2_protocol::tpm_hash_size(&name_alg).unwrap()
+        );
+        let unique_len = match &public.unique {
+            TpmuPublicId::Rsa(n) => n.len(),
+            TpmuPublicId::Ecc(point) => point.x.len() + point.y.len(),
+            _ => panic!("unexpected unique field"),
+        };
+        assert_eq!(unique_len == 0, !low_range, "{template}");
+    }
+    assert!("rsa4096".parse::<EkTemplate>().is_err());
+
+    let mut policy = TpmPolicyDigest::new(&SoftwareHash, TpmAlgId::Sha256).unwrap();
+    let endorsement = tpm_handle_name(TpmRh::Endorsement as u32).unwrap();
+    policy.secret(&endorsement, &[]).unwrap();
+    assert_eq!(&**policy.digest(), EK_POLICY_A_SHA256.as_slice());
+}
+
+#[test]
+fn test_ek_create() {
+    let mut device = device();
+    let (ek, public) = create_ek(&mut device, EkTemplate::EccNistP256, None, None, LOG).unwrap();
+    let (_, again) = create_ek(&mut device, EkTemplate::EccNistP256, None, None, LOG).unwrap();
+    assert_eq!(public, again);
+    assert!(public
+        .object_attributes
+        .contains(TpmaObject::ADMIN_WITH_POLICY));
+
+    let mut template = EkTemplate::EccNistP256.public();
+    template.object_attributes = TpmaObject::FIXED_TPM
+        | TpmaObject::FIXED_PARENT
+        | TpmaObject::SENSITIVE_DATA_ORIGIN
+        | TpmaObject::USER_WITH_AUTH
+        | TpmaObject::RESTRICTED
+        | TpmaObject::DECRYPT;
+    template.auth_policy = Tpm2bDigest::default();
+    let resp = create_primary(&mut device, TpmPermanent::OWNER, template);
+    let key = resp.object_handle;
+
+    let credential = Credential::new(&public, &resp.name, b"ek").unwrap();
+    let cert_info = activate_credential(
+        &mut device,
+        key.into(),
+        ek.into(),
+        &credential,
+        None,
+        None,
+        None,
+        LOG,
+    )
+    .unwrap();
+    assert_eq!(&*cert_info, b"ek");
+}
+
+#[test]
+fn test_ek_certificate() {
+    let mut device = device();
+    let template = EkTemplate::EccNistP256;
+    assert_eq!(
+        read_ek_certificate(&mut device, template, None, LOG).unwrap(),
+        None
+    );
+
+    let mut der = vec![0x30, 0x82, 0x05, 0xdc];
+    der.extend((0..1500).map(|i| (i % 251) as u8));
+    provision(&mut device, template.certificate_index(), &der);
+    let cert = read_ek_certificate(&mut device, template, None, LOG)
+        .unwrap()
+        .unwrap();
+    assert_eq!(cert, der);
+
+    let pem = certificate_to_pem(&cert);
+    assert!(pem.starts_with("-----BEGIN CERTIFICATE-----"));
+    assert_eq!(pem::parse(&pem).unwrap().contents(), der.as_slice());
+
+    provision(
+        &mut device,
+        EkTemplate::Rsa2048.certificate_index(),
+        b"not a certificate",
+    );
+    let err = read_ek_certificate(&mut device, EkTemplate::Rsa2048, None, LOG).unwrap_err();
+    assert!(matches!(err, TpmError::Parse(_)), "{err}");
+}
+
+/// 2025-06-01 00:00:00 UTC.
+const NOW: Duration = Duration::from_secs(1_748_736_000);
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(u8::try_from(len).unwrap());
+    } else {
+        let bytes = u32::try_from(len).unwrap().to_be_bytes();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | u8::try_from(4 - skip).unwrap());
+        out.extend(&bytes[skip..]);
+    }
+    out.extend(content);
+    out
+}
+
+fn seq(parts: &[Vec<u8>]) -> Vec<u8> {
+    tlv(0x30, &parts.concat())
+}
+
+fn oid(oid: ObjectIdentifier) -> Vec<u8> {
+    tlv(0x06, oid.as_bytes())
+}
+
+fn name(attributes: &[(ObjectIdentifier, &str)]) -> Vec<u8> {
+    let sets: Vec<_> = attributes
+        .iter()
+        .map(|&(id, value)| tlv(0x31, &seq(&[oid(id), tlv(0x0c, value.as_bytes())])))
+        .collect();
+    seq(&sets)
+}
+
+fn extension(id: ObjectIdentifier, critical: bool, value: &[u8]) -> Vec<u8> {
+    let mut parts = vec![oid(id)];
+    if critical {
+        parts.push(tlv(0x01, &[0xff]));
+    }
+    parts.push(tlv(0x04, value));
+    seq(&parts)
+}
+
+fn ecc_spki(public: &TpmtPublic) -> Vec<u8> {
+    let TpmuPublicId::Ecc(point) = &public.unique else {
+        panic!("not an ECC key");
+    };
+    let mut key = vec![0x00, 0x04];
+    key.extend(&*point.x);
+    key.extend(&*point.y);
+    seq(&[
+        seq(&[oid(ID_EC_PUBLIC_KEY), oid(SECP_256_R_1)]),
+        tlv(0x03, &key),
+    ])
+}
+
+/// Builds a certificate signed with `key` using SHA-256 and PKCS#1 v1.5.
+fn certificate(
+    serial: u8,
+    issuer: &[u8],
+    subject: &[u8],
+    spki: &[u8],
+    extensions: &[Vec<u8>],
+    key: &RsaPrivateKey,
+) -> Vec<u8> {
+    let algorithm = seq(&[oid(SHA_256_WITH_RSA_ENCRYPTION), tlv(0x05, &[])]);
+    let validity = seq(&[tlv(0x17, b"250101000000Z"), tlv(0x18, b"20351231235959Z")]);
+    let tbs = seq(&[
+        tlv(0xa0, &tlv(0x02, &[0x02])),
+        tlv(0x02, &[serial]),
+        algorithm.clone(),
+        issuer.to_vec(),
+        validity,
+        subject.to_vec(),
+        spki.to_vec(),
+        tlv(0xa3, &seq(extensions)),
+    ]);
+    let digest = Sha256::digest(&tbs);
+    let signature = key.sign(Pkcs1v15Sign::new::<Sha256>(), &digest).unwrap();
+    let mut bits = vec![0x00];
+    bits.extend(signature);
+    seq(&[tbs, algorithm, tlv(0x03, &bits)])
+}
+
+/// A CA certificate and its key.
+struct Ca {
+    name: Vec<u8>,
+    der: Vec<u8>,
+    key: RsaPrivateKey,
+}
+
+/// The basic constraints of a CA, with an optional path length constraint.
+fn ca_constraints(path_len: Option<u8>) -> Vec<u8> {
+    let mut value = vec![tlv(0x01, &[0xff])];
+    value.extend(path_len.map(|len| tlv(0x02, &[len])));
+    extension(ID_CE_BASIC_CONSTRAINTS, true, &seq(&value))
+}
+
+/// A key usage extension with the bits of `usage`, e.g. `0x06` for
+/// `keyCertSign` and `cRLSign`.
+fn key_usage(unused_bits: u8, usage: u8) -> Vec<u8> {
+    extension(ID_CE_KEY_USAGE, true, &tlv(0x03, &[unused_bits, usage]))
+}
+
+/// Issues a CA certificate for `cn` with `extensions`, signed by `issuer`
+/// or self-signed.
+fn issue_ca(issuer: Option<&Ca>, cn: &str, extensions: &[Vec<u8>]) -> Ca {
+    let key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+    let spki = key.to_public_key().to_public_key_der().unwrap();
+    let name = name(&[(CN, cn)]);
+    let (issuer_name, issuer_key) = issuer.map_or((&name, &key), |ca| (&ca.name, &ca.key));
+    let der = certificate(
+        1,
+        issuer_name,
+        &name,
+        spki.as_bytes(),
+        extensions,
+        issuer_key,
+    );
+    Ca { name, der, key }
+}
+
+/// Issues an EK certificate for the EK of `device()`.
+fn issue_ek(
+    issuer: &Ca,
+    tpm_attributes: &[(ObjectIdentifier, &str)],
+    extensions: &[Vec<u8>],
+) -> (Vec<u8>, TpmtPublic) {
+    let mut device = device();
+    let (_, ek_public) = create_ek(&mut device, EkTemplate::EccNistP256, None, None, LOG).unwrap();
+    let alt_name = seq(&[tlv(0xa4, &name(tpm_attributes))]);
+    let mut extensions = extensions.to_vec();
+    extensions.push(extension(ID_CE_SUBJECT_ALT_NAME, true, &alt_name));
+    let ek = certificate(
+        2,
+        &issuer.name,
+        &seq(&[]),
+        &ecc_spki(&ek_public),
+        &extensions,
+        &issuer.key,
+    );
+    (ek, ek_public)
+}
+
+struct Pki {
+    root: Vec<u8>,
+    ek: Vec<u8>,
+    ek_public: TpmtPublic,
+}
+
+fn build_pki(tpm_attributes: &[(ObjectIdentifier, &str)]) -> Pki {
+    let root = issue_ca(None, "Test EK Root CA", &[ca_constraints(None)]);
+    let (ek, ek_public) = issue_ek(&root, tpm_attributes, &[]);
+    Pki {
+        root: root.der,
+        ek,
+        ek_public,
+    }
+}
+
+fn verify(ek: &[u8], ca: &[&[u8]], ek_public: &TpmtPublic) -> Result<EkCertificateInfo, TpmError> {
+    let ek = Certificate::from_der(ek).unwrap();
+    let ca: Vec<_> = ca
+        .iter()
+        .map(|der| Certificate::from_der(der).unwrap())
+        .collect();
+    verify_ek_certificate(&ek, &ca, ek_public, NOW)
+}
+
+const TPM_ATTRIBUTES: &[(ObjectIdentifier, &str)] = &[
+    (TCG_AT_TPM_MANUFACTURER, "id:49465800"),
+    (TCG_AT_TPM_MODEL, "SLB9670"),
+    (TCG_AT_TPM_VERSION, "id:00070055"),
+];
+
+#[test]
+fn test_ek_verify() {
+    let pki = build_pki(TPM_ATTRIBUTES);
+    let root = Certificate::from_der(&pki.root).unwrap();
+    let ek = Certificate::from_der(&pki.ek).unwrap();
+    assert!(root.is_ca().unwrap());
+    assert!(!ek.is_ca().unwrap());
+    assert_eq!(root.subject_string(), "CN=Test EK Root CA");
+
+    let info = verify_ek_certificate(&ek, slice::from_ref(&root), &pki.ek_public, NOW).unwrap();
+    assert_eq!(info.manufacturer, "id:49465800");
+    assert_eq!(info.model, "SLB9670");
+    assert_eq!(info.version, "id:00070055");
+    assert_eq!(info.chain.len(), 2);
+    assert_eq!(info.chain[1], "CN=Test EK Root CA");
+
+    assert!(verify_ek_certificate(&ek, &[], &pki.ek_public, NOW).is_err());
+    assert!(verify_ek_certificate(&ek, slice::from_ref(&ek), &pki.ek_public, NOW).is_err());
+
+    let expired = Duration::from_secs(2_100_000_000);
+    assert!(verify_ek_certificate(&ek, slice::from_ref(&root), &pki.ek_public, expired).is_err());
+
+    let mut other = started_device([0x47; 32]);
+    let (_, other_public) =
+        create_ek(&mut other, EkTemplate::EccNistP256, None, None, LOG).unwrap();
+    assert!(verify_ek_certificate(&ek, slice::from_ref(&root), &other_public, NOW).is_err());
+
+    let mut public = pki.ek_public.clone();
+    public.auth_policy = Tpm2bDigest::default();
+    assert!(verify_ek_certificate(&ek, slice::from_ref(&root), &public, NOW).is_err());
+
+    let mut tampered = pki.ek.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    let tampered = Certificate::from_der(&tampered).unwrap();
+    assert!(verify_ek_certificate(&tampered, &[root], &pki.ek_public, NOW).is_err());
+}
+
+#[test]
+fn test_ek_verify_tpm_attributes() {
+    let pki = build_pki(&TPM_ATTRIBUTES[..2]);
+    let root = Certificate::from_der(&pki.root).unwrap();
+    let ek = Certificate::from_der(&pki.ek).unwrap();
+    let err = verify_ek_certificate(&ek, &[root], &pki.ek_public, NOW).unwrap_err();
+    assert!(err.to_string().contains("version"), "{err}");
+
+    let pki = build_pki(&[
+        (TCG_AT_TPM_MANUFACTURER, "Infineon"),
+        TPM_ATTRIBUTES[1],
+        TPM_ATTRIBUTES[2],
+    ]);
+    let root = Certificate::from_der(&pki.root).unwrap();
+    let ek = Certificate::from_der(&pki.ek).unwrap();
+    let err = verify_ek_certificate(&ek, &[root], &pki.ek_public, NOW).unwrap_err();
+    assert!(err.to_string().contains("manufacturer"), "{err}");
+}
+
+#[test]
+fn test_ek_ca_directory() {
+    let pki = build_pki(TPM_ATTRIBUTES);
+    let dir = std::env::temp_dir().join(format!("tpm2sh-ek-ca-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("root.pem"), certificate_to_pem(&pki.root)).unwrap();
+    std::fs::write(dir.join("root.der"), &pki.root).unwrap();
+    std::fs::write(dir.join("README"), "not a certificate").unwrap();
+    let ca = load_ca_directory(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let ca = ca.unwrap();
+    assert_eq!(ca.len(), 2);
+    let ek = Certificate::parse_all(certificate_to_pem(&pki.ek).as_bytes()).unwrap();
+    assert_eq!(ek.len(), 1);
+    verify_ek_certificate(&ek[0], &ca, &pki.ek_public, NOW).unwrap();
+}
+
+#[test]
+fn test_ek_verify_critical_extensions() {
+    let root = issue_ca(None, "Test EK Root CA", &[ca_constraints(None)]);
+    let unknown = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.55555.1");
+    let (ek, ek_public) = issue_ek(
+        &root,
+        TPM_ATTRIBUTES,
+        &[extension(unknown, true, &[0x05, 0x00])],
+    );
+    let err = verify(&ek, &[&root.der], &ek_public).unwrap_err();
+    assert!(err.to_string().contains("critical extension"), "{err}");
+
+    let (ek, ek_public) = issue_ek(
+        &root,
+        TPM_ATTRIBUTES,
+        &[extension(unknown, false, &[0x05, 0x00])],
+    );
+    verify(&ek, &[&root.der], &ek_public).unwrap();
+
+    let root = issue_ca(
+        None,
+        "Test EK Root CA",
+        &[
+            ca_constraints(None),
+            extension(unknown, true, &[0x05, 0x00]),
+        ],
+    );
+    let (ek, ek_public) = issue_ek(&root, TPM_ATTRIBUTES, &[]);
+    let err = verify(&ek, &[&root.der], &ek_public).unwrap_err();
+    assert!(err.to_string().contains("critical extension"), "{err}");
+}
+
+#[test]
+fn test_ek_verify_key_usage() {
+    let root = issue_ca(
+        None,
+        "Test EK Root CA",
+        &[ca_constraints(None), key_usage(1, 0x06)],
+    );
+    let (ek, ek_public) = issue_ek(&root, TPM_ATTRIBUTES, &[]);
+    verify(&ek, &[&root.der], &ek_public).unwrap();
+
+    let root = issue_ca(
+        None,
+        "Test EK Root CA",
+        &[ca_constraints(None), key_usage(7, 0x80)],
+    );
+    let (ek, ek_public) = issue_ek(&root, TPM_ATTRIBUTES, &[]);
+    let err = verify(&ek, &[&root.der], &ek_public).unwrap_err();
+    assert!(err.to_string().contains("key usage"), "{err}");
+}
+
+#[test]
+fn test_ek_verify_path_length() {
+    for (path_len, valid) in [(Some(0), false), (Some(1), true), (None, true)] {
+        let root = issue_ca(None, "Test EK Root CA", &[ca_constraints(path_len)]);
+        let intermediate = issue_ca(
+            Some(&root),
+            "Test EK Intermediate CA",
+            &[ca_constraints(Some(0))],
+        );
+        let (ek, ek_public) = issue_ek(&intermediate, TPM_ATTRIBUTES, &[]);
+        let result = verify(&ek, &[&root.der, &intermediate.der], &ek_public);
+        if valid {
+            assert_eq!(result.unwrap().chain.len(), 3);
+        } else {
+            let err = result.unwrap_err();
+            assert!(err.to_string().contains("path length"), "{err}");
+        }
+    }
+}
+
+#[test]
+fn test_ek_verify_issuer_selection() {
+    let root = issue_ca(None, "Test EK Root CA", &[ca_constraints(None)]);
+    let (ek, ek_public) = issue_ek(&root, TPM_ATTRIBUTES, &[]);
+    let expired_root = Certificate::from_der(&root.der).unwrap();
+    let not_ca = issue_ca(None, "Test EK Root CA", &[]);
+
+    let info = verify(&ek, &[&not_ca.der, &root.der], &ek_public).unwrap();
+    assert_eq!(info.chain.len(), 2);
+
+    let err = verify(&ek, &[&not_ca.der], &ek_public).unwrap_err();
+    assert!(err.to_string().contains("not a CA"), "{err}");
+
+    let later = Duration::from_secs(2_100_000_000);
+    let ek = Certificate::from_der(&ek).unwrap();
+    let err = verify_ek_certificate(&ek, &[expired_root], &ek_public, later).unwrap_err();
+    assert!(err.to_string().contains("not valid"), "{err}");
+}
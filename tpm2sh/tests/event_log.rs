@@ -0,0 +1,297 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{
+    cli::Eventlog,
+    event_log::{
+        parse_event_log, PcrReplay, TcgEventData, EV_EFI_BOOT_SERVICES_APPLICATION,
+        EV_EFI_VARIABLE_DRIVER_CONFIG, EV_NO_ACTION, EV_SEPARATOR, EV_S_CRTM_VERSION,
+    },
+    Command, TpmError,
+};
+use common::{password, started_device, LOG};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tpm2_protocol::{
+    data::{TpmAlgId, TpmlDigestValues, TpmtHa, TpmuHa},
+    message::TpmPcrExtendCommand,
+    TpmParseTagged, TpmPcr,
+};
+const SECURE_BOOT_GUID: [u8; 16] = [
+    0x61, 0xdf, 0xe4, 0x8b, 0xca, 0x93, 0xd2, 0x11, 0xaa, 0x0d, 0x00, 0xe0, 0x98, 0x03, 0x2b, 0x8c,
+];
+
+fn spec_id_event() -> Vec<u8> {
+    let mut data = b"Spec ID Event03\0".to_vec();
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&[0, 2, 0, 2]);
+    data.extend_from_slice(&2u32.to_le_bytes());
+    data.extend_from_slice(&(TpmAlgId::Sha1 as u16).to_le_bytes());
+    data.extend_from_slice(&20u16.to_le_bytes());
+    data.extend_from_slice(&(TpmAlgId::Sha256 as u16).to_le_bytes());
+    data.extend_from_slice(&32u16.to_le_bytes());
+    data.push(0);
+
+    let mut event = 0u32.to_le_bytes().to_vec();
+    event.extend_from_slice(&EV_NO_ACTION.to_le_bytes());
+    event.extend_from_slice(&[0; 20]);
+    event.extend_from_slice(&u32::try_from(data.len()).unwrap().to_le_bytes());
+    event.extend_from_slice(&data);
+    event
+}
+
+/// A `TCG_PCR_EVENT2` with digests computed over `measured`.
+fn event(pcr: u32, event_type: u32, measured: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut event = pcr.to_le_bytes().to_vec();
+    event.extend_from_slice(&event_type.to_le_bytes());
+    event.extend_from_slice(&2u32.to_le_bytes());
+    event.extend_from_slice(&(TpmAlgId::Sha1 as u16).to_le_bytes());
+    event.extend_from_slice(&Sha1::digest(measured));
+    event.extend_from_slice(&(TpmAlgId::Sha256 as u16).to_le_bytes());
+    event.extend_from_slice(&Sha256::digest(measured));
+    event.extend_from_slice(&u32::try_from(data.len()).unwrap().to_le_bytes());
+    event.extend_from_slice(data);
+    event
+}
+
+fn efi_variable(name: &str, value: &[u8]) -> Vec<u8> {
+    let name: Vec<u16> = name.encode_utf16().collect();
+    let mut data = SECURE_BOOT_GUID.to_vec();
+    data.extend_from_slice(&(name.len() as u64).to_le_bytes());
+    data.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    for unit in name {
+        data.extend_from_slice(&unit.to_le_bytes());
+    }
+    data.extend_from_slice(value);
+    data
+}
+
+fn image_load() -> Vec<u8> {
+    let mut data = 0x1000u64.to_le_bytes().to_vec();
+    data.extend_from_slice(&0x2000u64.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&4u64.to_le_bytes());
+    data.extend_from_slice(&[0x7f, 0xff, 0x04, 0x00]);
+    data
+}
+
+/// A log measuring into PCRs 4 and 7.
+fn event_log(secure_boot: &[u8]) -> Vec<u8> {
+    let variable = efi_variable("SecureBoot", secure_boot);
+    let separator = 0u32.to_le_bytes();
+    let mut log = spec_id_event();
+    log.extend(event(
+        7,
+        EV_EFI_VARIABLE_DRIVER_CONFIG,
+        &variable,
+        &variable,
+    ));
+    log.extend(event(7, EV_SEPARATOR, &separator, &separator));
+    log.extend(event(4, EV_SEPARATOR, &separator, &separator));
+    log.extend(event(
+        4,
+        EV_EFI_BOOT_SERVICES_APPLICATION,
+        b"image",
+        &image_load(),
+    ));
+    log
+}
+
+fn pcr_value(digests: &[&[u8]]) -> String {
+    let mut value = [0u8; 32];
+    for digest in digests {
+        value = Sha256::new()
+            .chain_update(value)
+            .chain_update(digest)
+            .finalize()
+            .into();
+    }
+    hex::encode_upper(value)
+}
+
+#[test]
+fn test_event_log_parse() {
+    let log = parse_event_log(&event_log(&[1])).unwrap();
+    assert_eq!(log.spec_id.digest_sizes, vec![(0x0004, 20), (0x000b, 32)]);
+    assert_eq!(log.events.len(), 4);
+    assert_eq!(log.events[0].digests.len(), 2);
+    assert_eq!(
+        log.events[0].decode(),
+        TcgEventData::EfiVariable {
+            guid: "8be4df61-93ca-11d2-aa0d-00e098032b8c".to_string(),
+            name: "SecureBoot".to_string(),
+            data: vec![1],
+        }
+    );
+    assert_eq!(log.events[1].decode(), TcgEventData::Separator(0));
+    assert_eq!(
+        log.events[3].decode(),
+        TcgEventData::EfiImageLoad {
+            location: 0x1000,
+            length: 0x2000,
+            link_time_address: 0,
+            device_path: vec![0x7f, 0xff, 0x04, 0x00],
+        }
+    );
+    let json = log.events[0].to_json(0);
+    assert_eq!(json["type"], "EV_EFI_VARIABLE_DRIVER_CONFIG");
+    assert_eq!(json["data"]["name"], "SecureBoot");
+}
+
+#[test]
+fn test_event_log_text_and_locality() {
+    let mut locality = b"StartupLocality\0".to_vec();
+    locality.push(3);
+    let version: Vec<u8> = "1.0\0".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let mut buf = spec_id_event();
+    buf.extend(event(0, EV_NO_ACTION, &[], &locality));
+    buf.extend(event(0, EV_S_CRTM_VERSION, &version, &version));
+    let log = parse_event_log(&buf).unwrap();
+    assert_eq!(log.events[0].decode(), TcgEventData::StartupLocality(3));
+    assert_eq!(
+        log.events[1].decode(),
+        TcgEventData::Text("1.0".to_string())
+    );
+
+    let replay = PcrReplay::new(&log).unwrap();
+    let pcr = &replay.banks[&TpmAlgId::Sha256][&0];
+    assert_eq!(pcr.initial.digest[31], 3);
+    assert_eq!(pcr.history.len(), 1);
+}
+
+#[test]
+fn test_event_log_truncated() {
+    let buf = event_log(&[1]);
+    let err = parse_event_log(&buf[..buf.len() - 3]).unwrap_err();
+    assert!(
+        matches!(&err, TpmError::Parse(msg) if msg.contains("offset")),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_event_log_replay() {
+    let log = parse_event_log(&event_log(&[1])).unwrap();
+    let replay = PcrReplay::new(&log).unwrap();
+    let output = replay.to_pcr_output();
+    let variable = efi_variable("SecureBoot", &[1]);
+    let separator = Sha256::digest(0u32.to_le_bytes());
+    assert_eq!(
+        output.banks["sha256"]["7"],
+        pcr_value(&[&Sha256::digest(&variable), &separator])
+    );
+    assert_eq!(
+        output.banks["sha256"]["4"],
+        pcr_value(&[&separator, &Sha256::digest(b"image")])
+    );
+    assert_eq!(output.banks["sha1"].len(), 2);
+    assert!(replay.compare(&log, &output).is_empty());
+}
+
+#[test]
+fn test_event_log_compare_mismatch() {
+    let log = parse_event_log(&event_log(&[1])).unwrap();
+    let replay = PcrReplay::new(&log).unwrap();
+
+    let mut pcrs = replay.to_pcr_output();
+    let variable = efi_variable("SecureBoot", &[1]);
+    let partial = pcr_value(&[&Sha256::digest(&variable)]);
+    pcrs.banks
+        .get_mut("sha256")
+        .unwrap()
+        .insert("7".to_string(), partial);
+    let mismatches = replay.compare(&log, &pcrs);
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].pcr, 7);
+    assert_eq!(mismatches[0].event, Some(1));
+
+    let mut buf = event_log(&[0]);
+    let value = buf
+        .windows(variable.len() - 1)
+        .position(|window| window == &variable[..variable.len() - 1])
+        .unwrap()
+        + variable.len()
+        - 1;
+    buf[value] = 1;
+    let tampered = parse_event_log(&buf).unwrap();
+    assert!(!tampered.events[0].is_data_consistent());
+    let mismatches = PcrReplay::new(&tampered).unwrap().compare(&tampered, &pcrs);
+    assert_eq!(mismatches.len(), 2);
+    assert!(mismatches
+        .iter()
+        .all(|mismatch| mismatch.pcr == 7 && mismatch.event == Some(0)));
+}
+
+#[test]
+fn test_event_log_compare_trailing_events() {
+    let log = parse_event_log(&event_log(&[1])).unwrap();
+    let pcrs = PcrReplay::new(&log).unwrap().to_pcr_output();
+
+    let separator = 0u32.to_le_bytes();
+    let mut buf = event_log(&[1]);
+    buf.extend(event(7, EV_SEPARATOR, &separator, &separator));
+    buf.extend(event(4, EV_SEPARATOR, &separator, &separator));
+    let extended = parse_event_log(&buf).unwrap();
+    let mut mismatches = PcrReplay::new(&extended).unwrap().compare(&extended, &pcrs);
+    mismatches.sort_by_key(|mismatch| mismatch.pcr);
+    assert_eq!(mismatches.len(), 4);
+    for mismatch in &mismatches {
+        let expected = if mismatch.pcr == 4 { 5 } else { 4 };
+        assert_eq!(mismatch.event, Some(expected));
+    }
+}
+
+#[test]
+fn test_event_log_verify() {
+    let buf = event_log(&[1]);
+    let path = std::env::temp_dir().join(format!("tpm2sh-event-log-{}", std::process::id()));
+    std::fs::write(&path, &buf).unwrap();
+
+    let mut device = started_device([0x41; 32]);
+    let log = parse_event_log(&buf).unwrap();
+    for event in &log.events {
+        let mut digests = TpmlDigestValues::new();
+        for digest in &event.digests {
+            digests.try_push(*digest).unwrap();
+        }
+        let cmd = TpmPcrExtendCommand { digests };
+        device
+            .execute(
+                &cmd,
+                &[TpmPcr::try_from(event.pcr_index).unwrap().into()],
+                &[password(b"")],
+                LOG,
+            )
+            .unwrap();
+    }
+
+    let args = Eventlog {
+        input: Some(path.to_string_lossy().to_string()),
+        pcrs: None,
+        verify: true,
+    };
+    args.run(Some(&mut device), None, LOG).unwrap();
+
+    let digest = TpmtHa {
+        hash_alg: TpmAlgId::Sha256,
+        digest: TpmuHa::parse_tagged(TpmAlgId::Sha256, &[0xaa; 32])
+            .unwrap()
+            .0,
+    };
+    let mut digests = TpmlDigestValues::new();
+    digests.try_push(digest).unwrap();
+    device
+        .execute(
+            &TpmPcrExtendCommand { digests },
+            &[TpmPcr::try_from(7).unwrap().into()],
+            &[password(b"")],
+            LOG,
+        )
+        .unwrap();
+    let err = args.run(Some(&mut device), None, LOG).unwrap_err();
+    assert!(err.to_string().contains("sha256:7"), "{err}");
+
+    std::fs::remove_file(&path).unwrap();
+}
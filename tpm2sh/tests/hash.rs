@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{
+    ak::{create_ak, load_ak, AkScheme},
+    ek::{create_ek, EkTemplate},
+    hash::{hash_data, hash_event},
+    signature::{hash_for_signing, sign_digest, signing_scheme},
+    software_digest, verify_signature, AuthSession, TpmDevice,
+};
+use common::{started_device, LOG};
+use rstest::rstest;
+use tpm2_protocol::{
+    data::{
+        Tpm2b, Tpm2bAuth, Tpm2bNonce, TpmAlgId, TpmRh, TpmSe, TpmSt, TpmaSession, TpmlPcrSelection,
+        TpmsPcrSelection, TpmtSymDefObject,
+    },
+    message::{TpmPcrReadCommand, TpmStartAuthSessionCommand},
+    TpmBuffer, TpmPcr, TpmPermanent,
+};
+
+fn device() -> TpmDevice {
+    started_device([0x50; 32])
+}
+
+/// Starts an unbound and unsalted HMAC session.
+fn hmac_session(device: &mut TpmDevice) -> AuthSession {
+    let cmd = TpmStartAuthSessionCommand {
+        nonce_caller: Tpm2b::try_from(&[0x11; 32][..]).unwrap(),
+        encrypted_salt: Tpm2b::default(),
+        session_type: TpmSe::Hmac,
+        symmetric: TpmtSymDefObject::default(),
+        auth_hash: TpmAlgId::Sha256,
+    };
+    let null = TpmPermanent::NULL.into();
+    let (resp, _) = device.execute(&cmd, &[null, null], &[], LOG).unwrap();
+    AuthSession {
+        handle: resp.session_handle,
+        nonce_tpm: Tpm2bNonce::try_from(&resp.nonce_tpm[..]).unwrap(),
+        attributes: TpmaSession::CONTINUE_SESSION,
+        hmac_key: Tpm2bAuth::default(),
+        auth_hash: TpmAlgId::Sha256,
+    }
+}
+
+fn read_pcr16(device: &mut TpmDevice) -> Vec<u8> {
+    let mut pcr_selection_in = TpmlPcrSelection::new();
+    pcr_selection_in
+        .try_push(TpmsPcrSelection {
+            hash: TpmAlgId::Sha256,
+            pcr_select: TpmBuffer::try_from(&[0x00, 0x00, 0x01][..]).unwrap(),
+        })
+        .unwrap();
+    let cmd = TpmPcrReadCommand { pcr_selection_in };
+    let (resp, _) = device.execute(&cmd, &[], &[], LOG).unwrap();
+    resp.pcr_values[0].to_vec()
+}
+
+fn data(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+#[rstest]
+#[case(0)]
+#[case(1024)]
+#[case(1025)]
+#[case(2048)]
+#[case(5000)]
+fn test_hash_data(#[case] len: usize) {
+    let mut device = device();
+    let data = data(len);
+    let (digest, ticket) = hash_data(
+        &mut device,
+        &data,
+        TpmAlgId::Sha384,
+        TpmRh::Owner,
+        None,
+        LOG,
+    )
+    .unwrap();
+    assert_eq!(digest, software_digest(TpmAlgId::Sha384, &[&data]).unwrap());
+    assert_eq!(ticket.tag, TpmSt::HashCheck);
+    assert_eq!(ticket.hierarchy, TpmRh::Owner);
+    assert!(!ticket.digest.is_empty());
+
+    let (_, ticket) =
+        hash_data(&mut device, &data, TpmAlgId::Sha384, TpmRh::Null, None, LOG).unwrap();
+    assert_eq!(ticket.hierarchy, TpmRh::Null);
+}
+
+#[test]
+fn test_hash_sign_large() {
+    let mut device = device();
+    let (ek, _) = create_ek(&mut device, EkTemplate::EccNistP256, None, None, LOG).unwrap();
+    let ek = ek.into();
+    let ak = create_ak(
+        &mut device,
+        ek,
+        AkScheme::Ecdsa,
+        TpmAlgId::Sha256,
+        None,
+        None,
+        LOG,
+    )
+    .unwrap();
+    let key = load_ak(&mut device, ek, &ak, None, LOG).unwrap().into();
+
+    let data = data(4096);
+    let (scheme, hash_alg) = signing_scheme(&ak.public, None).unwrap();
+    let (digest, validation) =
+        hash_for_signing(&mut device, &ak.public, &data, hash_alg, LOG).unwrap();
+    let signature = sign_digest(
+        &mut device,
+        key,
+        scheme,
+        &digest,
+        validation,
+        None,
+        None,
+        LOG,
+    )
+    .unwrap();
+    verify_signature(&ak.public, &data, &signature).unwrap();
+}
+
+#[rstest]
+#[case(16)]
+#[case(3000)]
+fn test_hash_event(#[case] len: usize) {
+    let mut device = device();
+    let data = data(len);
+
+    let digests = hash_event(&mut device, None, &data, None, None, LOG).unwrap();
+    assert_eq!(digests.len(), 2);
+    for digest in digests.iter() {
+        assert_eq!(
+            &*digest.digest,
+            software_digest(digest.hash_alg, &[&data]).unwrap()
+        );
+    }
+
+    let pcr = TpmPcr::try_from(16).unwrap();
+    hash_event(&mut device, Some(pcr), &data, None, None, LOG).unwrap();
+    let event_digest = software_digest(TpmAlgId::Sha256, &[&data]).unwrap();
+    let expected = software_digest(TpmAlgId::Sha256, &[&[0u8; 32], &event_digest]).unwrap();
+    assert_eq!(read_pcr16(&mut device), expected);
+}
+
+#[test]
+fn test_hash_event_session() {
+    let mut device = device();
+    let session = hmac_session(&mut device);
+    let pcr = TpmPcr::try_from(16).unwrap();
+    hash_event(&mut device, Some(pcr), b"event", Some(&session), None, LOG).unwrap();
+    let event_digest = software_digest(TpmAlgId::Sha256, &[b"event"]).unwrap();
+    let expected = software_digest(TpmAlgId::Sha256, &[&[0u8; 32], &event_digest]).unwrap();
+    assert_eq!(read_pcr16(&mut device), expected);
+
+    let session = AuthSession {
+        hmac_key: Tpm2bAuth::try_from(&b"wrong"[..]).unwrap(),
+        ..hmac_session(&mut device)
+    };
+    assert!(hash_event(&mut device, Some(pcr), b"event", Some(&session), None, LOG).is_err());
+}
@@ -0,0 +1,262 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{
+    cli::{Ima, ImaAction},
+    ima::{parse_ima_measurement_list, ImaAllowlist, IMA_BANKS},
+    Command, TpmError,
+};
+use common::{password, started_device, LOG};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tpm2_protocol::{
+    data::{TpmAlgId, TpmlDigestValues},
+    message::TpmPcrExtendCommand,
+    TpmPcr,
+};
+
+fn field(data: &[u8]) -> Vec<u8> {
+    let mut field = u32::try_from(data.len()).unwrap().to_le_bytes().to_vec();
+    field.extend_from_slice(data);
+    field
+}
+
+/// The template data of an `ima-ng` entry.
+fn ima_ng_data(digest: &[u8], name: &str) -> Vec<u8> {
+    let mut digest_field = b"sha256:\0".to_vec();
+    digest_field.extend_from_slice(digest);
+    let mut name_field = name.as_bytes().to_vec();
+    name_field.push(0);
+    let mut data = field(&digest_field);
+    data.extend(field(&name_field));
+    data
+}
+
+fn binary_entry(template: &str, template_digest: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut entry = 10u32.to_le_bytes().to_vec();
+    entry.extend_from_slice(template_digest);
+    entry.extend(field(template.as_bytes()));
+    entry.extend(field(data));
+    entry
+}
+
+/// A list with a boot aggregate, two files and a violation, in both the
+/// binary and the ASCII format.
+fn measurement_list() -> (Vec<u8>, String) {
+    let files = [
+        ("boot_aggregate", Sha256::digest(b"pcrs")),
+        ("/usr/bin/kmod", Sha256::digest(b"kmod")),
+        ("/etc/my file", Sha256::digest(b"config")),
+    ];
+    let mut binary = Vec::new();
+    let mut ascii = String::new();
+    for (name, digest) in &files {
+        let data = ima_ng_data(digest, name);
+        let template_digest = Sha1::digest(&data);
+        binary.extend(binary_entry("ima-ng", &template_digest, &data));
+        ascii.push_str(&format!(
+            "10 {} ima-ng sha256:{} {name}\n",
+            hex::encode(template_digest),
+            hex::encode(digest)
+        ));
+    }
+    let data = ima_ng_data(&[0; 32], "/var/log/open-writers");
+    binary.extend(binary_entry("ima-ng", &[0; 20], &data));
+    ascii.push_str(&format!(
+        "10 {} ima-ng sha256:{} /var/log/open-writers\n",
+        hex::encode([0; 20]),
+        hex::encode([0; 32])
+    ));
+    (binary, ascii)
+}
+
+fn pcr10_sha256(list: &[u8]) -> [u8; 32] {
+    let list = parse_ima_measurement_list(list).unwrap();
+    let mut value = [0u8; 32];
+    for entry in &list.entries {
+        let hash: [u8; 32] = if entry.is_violation() {
+            [0xff; 32]
+        } else {
+            Sha256::digest(&entry.template_data).into()
+        };
+        value = Sha256::new()
+            .chain_update(value)
+            .chain_update(hash)
+            .finalize()
+            .into();
+    }
+    value
+}
+
+#[test]
+fn test_ima_parse_binary() {
+    let (binary, _) = measurement_list();
+    let list = parse_ima_measurement_list(&binary).unwrap();
+    assert_eq!(list.entries.len(), 4);
+    assert!(list.entries[0].is_boot_aggregate());
+    assert_eq!(list.entries[1].file_name, "/usr/bin/kmod");
+    assert_eq!(list.entries[1].file_hash_alg, "sha256");
+    assert_eq!(
+        list.entries[1].file_digest,
+        Sha256::digest(b"kmod").to_vec()
+    );
+    assert!(list.entries[3].is_violation());
+    assert!(list.entries.iter().all(|entry| entry.is_data_consistent()));
+}
+
+#[test]
+fn test_ima_parse_ascii() {
+    let (binary, ascii) = measurement_list();
+    let from_binary = parse_ima_measurement_list(&binary).unwrap();
+    let from_ascii = parse_ima_measurement_list(ascii.as_bytes()).unwrap();
+    assert_eq!(from_ascii, from_binary);
+    assert_eq!(from_ascii.entries[2].file_name, "/etc/my file");
+}
+
+#[test]
+fn test_ima_parse_legacy_and_sig() {
+    let digest = Sha1::digest(b"init");
+    let mut data = digest.to_vec();
+    let mut name = b"/init".to_vec();
+    name.resize(256, 0);
+    data.extend_from_slice(&name);
+    let template_digest = Sha1::digest(&data);
+
+    let mut binary = 10u32.to_le_bytes().to_vec();
+    binary.extend_from_slice(&template_digest);
+    binary.extend(field(b"ima"));
+    binary.extend_from_slice(&digest);
+    binary.extend(field(b"/init"));
+
+    let mut sig_data = ima_ng_data(&Sha256::digest(b"kmod"), "/usr/bin/kmod");
+    sig_data.extend(field(&[0x03, 0x02, 0x01]));
+    let sig_digest = Sha1::digest(&sig_data);
+    binary.extend(binary_entry("ima-sig", &sig_digest, &sig_data));
+
+    let list = parse_ima_measurement_list(&binary).unwrap();
+    assert_eq!(list.entries[0].file_name, "/init");
+    assert_eq!(list.entries[0].file_hash_alg, "sha1");
+    assert_eq!(list.entries[1].extra, vec![0x03, 0x02, 0x01]);
+    assert!(list.entries.iter().all(|entry| entry.is_data_consistent()));
+
+    let ascii = format!(
+        "10 {} ima {} /init\n10 {} ima-sig sha256:{} /usr/bin/kmod 030201\n",
+        hex::encode(template_digest),
+        hex::encode(digest),
+        hex::encode(sig_digest),
+        hex::encode(Sha256::digest(b"kmod")),
+    );
+    assert_eq!(parse_ima_measurement_list(ascii.as_bytes()).unwrap(), list);
+}
+
+#[test]
+fn test_ima_truncated() {
+    let (binary, _) = measurement_list();
+    let err = parse_ima_measurement_list(&binary[..binary.len() - 5]).unwrap_err();
+    assert!(
+        matches!(&err, TpmError::Parse(msg) if msg.contains("offset")),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_ima_replay_and_compare() {
+    let (binary, _) = measurement_list();
+    let list = parse_ima_measurement_list(&binary).unwrap();
+    let replay = list.replay(IMA_BANKS).unwrap();
+    let output = replay.to_pcr_output();
+    assert_eq!(
+        output.banks["sha256"]["10"],
+        hex::encode_upper(pcr10_sha256(&binary))
+    );
+    assert_eq!(output.banks.len(), IMA_BANKS.len());
+    assert!(list.compare(&replay, &output).is_empty());
+
+    let mut earlier = output.clone();
+    let prefix = list.replay(IMA_BANKS).unwrap();
+    let value = &prefix.banks[&TpmAlgId::Sha256][&10].history[1].1;
+    earlier
+        .banks
+        .get_mut("sha256")
+        .unwrap()
+        .insert("10".to_string(), hex::encode(&*value.digest));
+    assert!(list.compare(&replay, &earlier).is_empty());
+
+    let mut tampered = list.clone();
+    tampered.entries[1].file_digest = Sha256::digest(b"rootkit").to_vec();
+    tampered.entries[1].template_data =
+        ima_ng_data(&tampered.entries[1].file_digest, "/usr/bin/kmod");
+    let replay = tampered.replay(IMA_BANKS).unwrap();
+    let mismatches = tampered.compare(&replay, &output);
+    assert_eq!(mismatches.len(), IMA_BANKS.len());
+    assert!(mismatches.iter().all(|mismatch| mismatch.event == Some(1)));
+}
+
+#[test]
+fn test_ima_allowlist() {
+    let (binary, _) = measurement_list();
+    let list = parse_ima_measurement_list(&binary).unwrap();
+    let allowlist = ImaAllowlist::parse(&format!(
+        "# comment\n\n{}  /usr/bin/kmod\n",
+        hex::encode(Sha256::digest(b"kmod"))
+    ))
+    .unwrap();
+    assert_eq!(allowlist.unlisted(&list), vec![2]);
+
+    let allowlist = ImaAllowlist::parse(&format!(
+        "sha256:{}\n{}\n",
+        hex::encode(Sha256::digest(b"kmod")),
+        hex::encode(Sha256::digest(b"config"))
+    ))
+    .unwrap();
+    assert!(allowlist.unlisted(&list).is_empty());
+    assert!(ImaAllowlist::parse("xyz /bin/sh\n").is_err());
+}
+
+#[test]
+fn test_ima_verify() {
+    let (binary, _) = measurement_list();
+    let dir = std::env::temp_dir();
+    let input = dir.join(format!("tpm2sh-ima-{}", std::process::id()));
+    let allowlist = dir.join(format!("tpm2sh-ima-allowlist-{}", std::process::id()));
+    std::fs::write(&input, &binary).unwrap();
+    std::fs::write(
+        &allowlist,
+        format!("{}  /usr/bin/kmod\n", hex::encode(Sha256::digest(b"kmod"))),
+    )
+    .unwrap();
+
+    let mut device = started_device([0x42; 32]);
+    let list = parse_ima_measurement_list(&binary).unwrap();
+    for entry in &list.entries {
+        let mut digests = TpmlDigestValues::new();
+        for alg in [TpmAlgId::Sha1, TpmAlgId::Sha256] {
+            digests.try_push(entry.template_hash(alg).unwrap()).unwrap();
+        }
+        device
+            .execute(
+                &TpmPcrExtendCommand { digests },
+                &[TpmPcr::try_from(10).unwrap().into()],
+                &[password(b"")],
+                LOG,
+            )
+            .unwrap();
+    }
+
+    let mut args = Ima {
+        action: ImaAction::Verify,
+        input: Some(input.to_string_lossy().to_string()),
+        ..Default::default()
+    };
+    assert!(!args.is_local());
+    args.run(Some(&mut device), None, LOG).unwrap();
+
+    args.allowlist = Some(allowlist.to_string_lossy().to_string());
+    let err = args.run(Some(&mut device), None, LOG).unwrap_err();
+    assert!(err.to_string().contains("/etc/my file"), "{err}");
+
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&allowlist).unwrap();
+}
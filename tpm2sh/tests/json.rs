@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{
+    cli::{CreatePrimary, NvDefine},
+    json::{tpm_from_json, tpm_to_json},
+    Command,
+};
+use common::{build, started_device, LOG};
+use rstest::rstest;
+use serde_json::json;
+use std::fmt::Debug;
+use tpm2_protocol::{
+    data::{
+        Tpm2bDigest, TpmAlgId, TpmEccCurve, TpmaNv, TpmaObject, TpmlPcrSelection, TpmsAttest,
+        TpmsEccPoint, TpmsNvPublic, TpmtKdfScheme, TpmtPublic, TpmtScheme, TpmtSignature,
+        TpmtSymDefObject, TpmuAsymScheme, TpmuPublicId, TpmuPublicParms,
+    },
+    generate::{TpmGenerate, TpmRng},
+    message::{TpmNvReadPublicCommand, TpmReadPublicCommand},
+    visit::{TpmFromSource, TpmVisit},
+    TpmBuild, TpmNvIndex, TpmTransient,
+};
+
+fn round_trip<T>(seed: u64)
+where
+    T: TpmGenerate + TpmVisit + TpmFromSource + TpmBuild + PartialEq + Debug,
+{
+    let value = T::generate(&mut TpmRng::new(seed));
+    let text = serde_json::to_string(&tpm_to_json(&value)).unwrap();
+    let decoded: T = tpm_from_json(&serde_json::from_str(&text).unwrap()).unwrap();
+    assert_eq!(decoded, value);
+    assert_eq!(build(&decoded), build(&value));
+}
+
+#[rstest]
+#[case(0)]
+#[case(1)]
+#[case(2)]
+#[case(3)]
+#[case(42)]
+#[case(0xdead_beef)]
+fn test_json_round_trip(#[case] seed: u64) {
+    round_trip::<TpmtPublic>(seed);
+    round_trip::<TpmsAttest>(seed);
+    round_trip::<TpmtSignature>(seed);
+    round_trip::<TpmtScheme>(seed);
+    round_trip::<TpmsNvPublic>(seed);
+    round_trip::<TpmlPcrSelection>(seed);
+}
+
+fn ecc_template() -> TpmtPublic {
+    TpmtPublic {
+        object_type: TpmAlgId::Ecc,
+        name_alg: TpmAlgId::Sha256,
+        object_attributes: TpmaObject::FIXED_TPM | TpmaObject::SIGN_ENCRYPT,
+        auth_policy: Tpm2bDigest::default(),
+        parameters: TpmuPublicParms::Ecc {
+            symmetric: TpmtSymDefObject::default(),
+            scheme: TpmtScheme {
+                scheme: TpmAlgId::Ecdsa,
+                details: TpmuAsymScheme::Hash(TpmAlgId::Sha256),
+            },
+            curve_id: TpmEccCurve::NistP256,
+            kdf: TpmtKdfScheme {
+                scheme: TpmAlgId::Null,
+            },
+        },
+        unique: TpmuPublicId::Null,
+    }
+}
+
+#[test]
+fn test_json_public_encoding() {
+    let json = tpm_to_json(&ecc_template());
+    assert_eq!(json["object_type"], "TPM_ALG_ECC");
+    assert_eq!(
+        json["object_attributes"],
+        json!(["FIXED_TPM", "SIGN_ENCRYPT"])
+    );
+    assert_eq!(json["auth_policy"], "");
+    assert_eq!(json["parameters"]["scheme"]["scheme"], "TPM_ALG_ECDSA");
+    assert_eq!(json["parameters"]["scheme"]["details"], "TPM_ALG_SHA256");
+    assert_eq!(json["parameters"]["symmetric"]["key_bits"], json!(null));
+    assert_eq!(json["unique"], json!(null));
+}
+
+#[test]
+fn test_json_decode_errors() {
+    let mut json = tpm_to_json(&ecc_template());
+    json["parameters"]["curve_id"] = json!("TPM_ECC_BOGUS");
+    let err = tpm_from_json::<TpmtPublic>(&json).unwrap_err();
+    assert!(err.to_string().contains("parameters.curve_id"), "{err}");
+
+    let mut json = tpm_to_json(&ecc_template());
+    json["extra"] = json!(1);
+    assert!(tpm_from_json::<TpmtPublic>(&json).is_err());
+}
+
+/// Writes `value` as JSON to a temporary file and returns its path.
+fn write_json(name: &str, value: &impl TpmVisit) -> String {
+    let path = std::env::temp_dir().join(format!("tpm2sh-{name}-{}.json", std::process::id()));
+    std::fs::write(&path, tpm_to_json(value).to_string()).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+#[test]
+fn test_json_command_inputs() {
+    let mut device = started_device([0x5a; 32]);
+
+    let mut template = ecc_template();
+    template.object_attributes |= TpmaObject::SENSITIVE_DATA_ORIGIN | TpmaObject::USER_WITH_AUTH;
+    template.unique = TpmuPublicId::Ecc(TpmsEccPoint::default());
+    let args = CreatePrimary {
+        template: Some(write_json("template", &template)),
+        ..Default::default()
+    };
+    args.run(Some(&mut device), None, LOG).unwrap();
+    let handle = TpmTransient::try_from(0x8000_0000).unwrap();
+    let (resp, _) = device
+        .execute(&TpmReadPublicCommand {}, &[handle.into()], &[], LOG)
+        .unwrap();
+    assert_eq!(
+        resp.out_public.inner.object_attributes,
+        template.object_attributes
+    );
+    assert_eq!(resp.out_public.inner.parameters, template.parameters);
+
+    let public = TpmsNvPublic {
+        nv_index: TpmNvIndex::try_from(0x0100_0100).unwrap(),
+        name_alg: TpmAlgId::Sha256,
+        attributes: TpmaNv::OWNERWRITE | TpmaNv::OWNERREAD | TpmaNv::AUTHREAD,
+        auth_policy: Tpm2bDigest::default(),
+        data_size: 32,
+    };
+    let args = NvDefine {
+        public: write_json("nv-public", &public),
+        ..Default::default()
+    };
+    args.run(Some(&mut device), None, LOG).unwrap();
+    let (resp, _) = device
+        .execute(
+            &TpmNvReadPublicCommand {},
+            &[public.nv_index.into()],
+            &[],
+            LOG,
+        )
+        .unwrap();
+    assert_eq!(resp.nv_public.inner, public);
+}
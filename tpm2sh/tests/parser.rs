@@ -39,6 +39,12 @@ fn test_pcr_selection_parser_invalid(#[case] input: &str) {
 #[case("or(pcr(\"sha256:0\"), secret(\"0x40000001\"))")]
 #[case("or(pcr(\"s:0\"), pcr(\"s:1\"), pcr(\"s:2\"))")]
 #[case("or(pcr(\"s:0\"), or(secret(\"h:1\"), pcr(\"s:2\")))")]
+#[case("pcr(\"sha256:0\", count=5)")]
+#[case("and(pcr(\"sha256:0\"), command_code(\"TPM_CC_Unseal\"))")]
+#[case("auth_value()")]
+#[case("password()")]
+#[case("authorize(\"000bcafe\")")]
+#[case("authorize(\"000bcafe\", \"beef\")")]
 fn test_policy_parser_valid(#[case] input: &str) {
     PolicyParser::parse(PolicyRule::policy_expression, input).expect(input);
 }
@@ -54,6 +60,10 @@ fn test_policy_parser_valid(#[case] input: &str) {
 #[case("or(pcr(\"s:0\"))")]
 #[case("or(pcr(\"s:0\"), )")]
 #[case("foo(\"bar\")")]
+#[case("and(pcr(\"s:0\"))")]
+#[case("command_code()")]
+#[case("auth_value(\"x\")")]
+#[case("authorize()")]
 #[case("pcr(\"unterminated string)")]
 fn test_policy_parser_invalid(#[case] input: &str) {
     assert!(PolicyParser::parse(PolicyRule::policy_expression, input).is_err());
@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use cli::SoftwareHash;
+use rstest::rstest;
+use tpm2_protocol::{
+    data::{TpmAlgId, TpmCc, TpmRh},
+    hash::tpm_handle_name,
+    policy::TpmPolicyDigest,
+};
+
+#[test]
+fn test_policy_secret_endorsement() {
+    let name = tpm_handle_name(TpmRh::Endorsement as u32).unwrap();
+    let mut policy = TpmPolicyDigest::new(&SoftwareHash, TpmAlgId::Sha256).unwrap();
+    policy.secret(&name, &[]).unwrap();
+    assert_eq!(
+        hex::encode(&**policy.digest()),
+        "837197674484b3f81a90cc8d46a5d724fd52d76e06520b64f2a1da1b331469aa"
+    );
+}
+
+#[test]
+fn test_policy_auth_value() {
+    let mut policy = TpmPolicyDigest::new(&SoftwareHash, TpmAlgId::Sha256).unwrap();
+    policy.auth_value().unwrap();
+    assert_eq!(
+        hex::encode(&**policy.digest()),
+        "8fcd2169ab92694e0c633f1ab772842b8241bbc20288981fc7ac1eddc1fddb0e"
+    );
+}
+
+#[test]
+fn test_policy_authorize_resets_digest() {
+    let key_name = [0x00, 0x0b, 0xaa, 0xbb];
+    let mut fresh = TpmPolicyDigest::new(&SoftwareHash, TpmAlgId::Sha256).unwrap();
+    fresh.authorize(&key_name, &[]).unwrap();
+    let mut chained = TpmPolicyDigest::new(&SoftwareHash, TpmAlgId::Sha256).unwrap();
+    chained.command_code(TpmCc::Unseal).unwrap();
+    chained.authorize(&key_name, &[]).unwrap();
+    assert_eq!(fresh.digest(), chained.digest());
+}
+
+#[rstest]
+#[case(0)]
+#[case(1)]
+#[case(9)]
+fn test_policy_or_branch_count(#[case] count: usize) {
+    let branch = *TpmPolicyDigest::new(&SoftwareHash, TpmAlgId::Sha256)
+        .unwrap()
+        .digest();
+    let branches = vec![branch; count];
+    let mut policy = TpmPolicyDigest::new(&SoftwareHash, TpmAlgId::Sha256).unwrap();
+    assert!(policy.or(&branches).is_err());
+}
@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{
+    proxy::{TpmProxy, TPM_REMOTE_HANDSHAKE, TPM_SEND_COMMAND, TPM_SESSION_END},
+    TpmDevice,
+};
+use common::build_with;
+use rstest::{fixture, rstest};
+use serde_json::Value;
+use std::io::{self, Cursor, Read, Write};
+use tpm2_protocol::{
+    data::{TpmSt, TpmSu},
+    message::{tpm_build_command, TpmGetRandomCommand, TpmStartupCommand},
+};
+
+const SEED: [u8; 32] = [0x5a; 32];
+
+/// A connection that replays scripted input and records the output.
+struct Script {
+    input: Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+impl Script {
+    fn new(input: Vec<u8>) -> Self {
+        Self {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Read for Script {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for Script {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[fixture]
+fn device() -> TpmDevice {
+    common::device(SEED)
+}
+
+fn startup() -> Vec<u8> {
+    let command = TpmStartupCommand {
+        startup_type: TpmSu::Clear,
+    };
+    build_with(|writer| tpm_build_command(&command, TpmSt::NoSessions, &[], &[], writer))
+}
+
+fn get_random() -> Vec<u8> {
+    let command = TpmGetRandomCommand { bytes_requested: 8 };
+    build_with(|writer| tpm_build_command(&command, TpmSt::NoSessions, &[], &[], writer))
+}
+
+fn log_records(log: &[u8]) -> Vec<Value> {
+    log.split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect()
+}
+
+#[rstest]
+fn test_proxy_raw(mut device: TpmDevice) {
+    let mut input = startup();
+    input.extend(get_random());
+    let mut stream = Script::new(input);
+    let mut proxy = TpmProxy::new(&mut device, Vec::new());
+    proxy.serve_raw("test", &mut stream).unwrap();
+
+    let records = log_records(proxy.log());
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["seq"], 1);
+    assert_eq!(records[0]["client"], "test");
+    assert_eq!(records[0]["command"]["cc"], "TPM_CC_Startup");
+    assert_eq!(
+        records[0]["command"]["parameters"]["startup_type"],
+        "TPM_SU_CLEAR"
+    );
+    assert_eq!(records[0]["response"]["rc"], 0);
+    assert!(records[0]["latency_us"].is_u64());
+    assert_eq!(records[1]["command"]["cc"], "TPM_CC_GetRandom");
+    let random = records[1]["response"]["parameters"]["random_bytes"]
+        .as_str()
+        .unwrap();
+    assert_eq!(random.len(), 16);
+
+    let responses = hex::decode(
+        records
+            .iter()
+            .map(|r| r["response_raw"].as_str().unwrap())
+            .collect::<String>(),
+    )
+    .unwrap();
+    assert_eq!(stream.output, responses);
+}
+
+#[rstest]
+fn test_proxy_simulator(mut device: TpmDevice) {
+    let command = startup();
+    let mut input = Vec::new();
+    input.extend(TPM_REMOTE_HANDSHAKE.to_be_bytes());
+    input.extend(1u32.to_be_bytes());
+    input.extend(TPM_SEND_COMMAND.to_be_bytes());
+    input.push(0);
+    input.extend(u32::try_from(command.len()).unwrap().to_be_bytes());
+    input.extend(&command);
+    input.extend(TPM_SESSION_END.to_be_bytes());
+    let mut stream = Script::new(input);
+    let mut proxy = TpmProxy::new(&mut device, Vec::new());
+    proxy.serve_simulator("test", &mut stream).unwrap();
+
+    let records = log_records(proxy.log());
+    assert_eq!(records.len(), 1);
+    let response = hex::decode(records[0]["response_raw"].as_str().unwrap()).unwrap();
+
+    let mut expected = Vec::new();
+    expected.extend(1u32.to_be_bytes());
+    expected.extend(1u32.to_be_bytes());
+    expected.extend(0u32.to_be_bytes());
+    expected.extend(u32::try_from(response.len()).unwrap().to_be_bytes());
+    expected.extend(&response);
+    expected.extend(0u32.to_be_bytes());
+    assert_eq!(stream.output, expected);
+}
+
+#[rstest]
+fn test_proxy_logs_malformed_command(mut device: TpmDevice) {
+    let command = hex::decode("80010000000a00000fff").unwrap();
+    let mut proxy = TpmProxy::new(&mut device, Vec::new());
+    proxy.forward("test", &command).unwrap();
+
+    let records = log_records(proxy.log());
+    assert!(records[0]["command"].is_null());
+    assert!(records[0]["command_error"].as_str().is_some());
+    assert_eq!(records[0]["command_raw"], "80010000000a00000fff");
+    assert!(records[0]["response_error"].as_str().is_some());
+}
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+use cli::{
+    attestation::SignedAttest, cli::Object, formats::PcrOutput, quote::quote_scheme,
+    verify_signature,
+};
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rsa::{traits::PublicKeyParts, Pkcs1v15Sign, RsaPrivateKey};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use tpm2_protocol::{
+    data::{
+        Tpm2bData, Tpm2bDigest, Tpm2bEccParameter, Tpm2bName, TpmAlgId, TpmEccCurve, TpmSt,
+        TpmaObject, TpmiYesNo, TpmlPcrSelection, TpmsAttest, TpmsCertifyInfo, TpmsClockInfo,
+        TpmsEccPoint, TpmsPcrSelection, TpmsQuoteInfo, TpmsSignatureEcc, TpmsSignatureRsa,
+        TpmtKdfScheme, TpmtPublic, TpmtScheme, TpmtSignature, TpmtSymDefObject, TpmuAsymScheme,
+        TpmuAttest, TpmuPublicId, TpmuPublicParms, TpmuSignature,
+    },
+    TpmBuffer, TpmBuild, TpmWriter, TPM_MAX_COMMAND_SIZE,
+};
+
+const NONCE: &[u8] = b"verifier nonce";
+
+fn build<T: TpmBuild>(obj: &T) -> Vec<u8> {
+    let mut buf = [0u8; TPM_MAX_COMMAND_SIZE];
+    let mut writer = TpmWriter::new(&mut buf);
+    obj.build(&mut writer).unwrap();
+    let len = writer.len();
+    buf[..len].to_vec()
+}
+
+/// PCRs sha256:0,7 and sha1:1.
+fn pcrs() -> (TpmlPcrSelection, PcrOutput) {
+    let mut selection = TpmlPcrSelection::new();
+    selection
+        .try_push(TpmsPcrSelection {
+            hash: TpmAlgId::Sha256,
+            pcr_select: TpmBuffer::try_from(&[0x81, 0, 0][..]).unwrap(),
+        })
+        .unwrap();
+    selection
+        .try_push(TpmsPcrSelection {
+            hash: TpmAlgId::Sha1,
+            pcr_select: TpmBuffer::try_from(&[0x02, 0, 0][..]).unwrap(),
+        })
+        .unwrap();
+    let mut banks = BTreeMap::new();
+    banks.insert(
+        "sha256".to_string(),
+        BTreeMap::from([
+            ("0".to_string(), hex::encode_upper([0x11; 32])),
+            ("7".to_string(), hex::encode_upper([0x77; 32])),
+        ]),
+    );
+    banks.insert(
+        "sha1".to_string(),
+        BTreeMap::from([("1".to_string(), hex::encode_upper([0x22; 20]))]),
+    );
+    (
+        selection,
+        PcrOutput {
+            update_counter: 3,
+            banks,
+        },
+    )
+}
+
+fn attest_with(attest_type: TpmSt, nonce: &[u8], attested: TpmuAttest) -> Vec<u8> {
+    build(&TpmsAttest {
+        magic: 0xff54_4347,
+        attest_type,
+        qualified_signer: Tpm2bName::try_from(&[0x00, 0x0b][..]).unwrap(),
+        extra_data: Tpm2bData::try_from(nonce).unwrap(),
+        clock_info: TpmsClockInfo {
+            clock: 1000,
+            reset_count: 1,
+            restart_count: 0,
+            safe: TpmiYesNo(true),
+        },
+        firmware_version: 0x0001_0002,
+        attested,
+    })
+}
+
+fn attest(nonce: &[u8], pcr_digest: &[u8]) -> Vec<u8> {
+    let (pcr_select, _) = pcrs();
+    attest_with(
+        TpmSt::AttestQuote,
+        nonce,
+        TpmuAttest::Quote(TpmsQuoteInfo {
+            pcr_select,
+            pcr_digest: Tpm2bDigest::try_from(pcr_digest).unwrap(),
+        }),
+    )
+}
+
+fn expected_pcr_digest() -> Vec<u8> {
+    Sha256::new()
+        .chain_update([0x11; 32])
+        .chain_update([0x77; 32])
+        .chain_update([0x22; 20])
+        .finalize()
+        .to_vec()
+}
+
+fn ecc_key() -> (SigningKey, TpmtPublic) {
+    let key = SigningKey::from_slice(&[0x42; 32]).unwrap();
+    let point = key.verifying_key().to_encoded_point(false);
+    let public = TpmtPublic {
+        object_type: TpmAlgId::Ecc,
+        name_alg: TpmAlgId::Sha256,
+        object_attributes: TpmaObject::SIGN_ENCRYPT | TpmaObject::RESTRICTED,
+        auth_policy: Tpm2bDigest::default(),
+        parameters: TpmuPublicParms::Ecc {
+            symmetric: TpmtSymDefObject::default(),
+            scheme: TpmtScheme {
+                scheme: TpmAlgId::Ecdsa,
+                details: TpmuAsymScheme::Hash(TpmAlgId::Sha256),
+            },
+            curve_id: TpmEccCurve::NistP256,
+            kdf: TpmtKdfScheme::default(),
+        },
+        unique: TpmuPublicId::Ecc(TpmsEccPoint {
+            x: Tpm2bEccParameter::try_from(point.x().unwrap().as_slice()).unwrap(),
+            y: Tpm2bEccParameter::try_from(point.y().unwrap().as_slice()).unwrap(),
+        }),
+    };
+    (key, public)
+}
+
+fn ecdsa_sign(key: &SigningKey, message: &[u8]) -> TpmtSignature {
+    let signature: Signature = key.sign(message);
+    let (r, s) = signature.split_bytes();
+    TpmtSignature {
+        sig_alg: TpmAlgId::Ecdsa,
+        signature: TpmuSignature::Ecdsa(TpmsSignatureEcc {
+            hash: TpmAlgId::Sha256,
+            signature_r: Tpm2bEccParameter::try_from(r.as_slice()).unwrap(),
+            signature_s: Tpm2bEccParameter::try_from(s.as_slice()).unwrap(),
+        }),
+    }
+}
+
+fn signed_quote(attest: Vec<u8>) -> (SignedAttest, TpmtPublic) {
+    let (key, public) = ecc_key();
+    let signature = ecdsa_sign(&key, &attest);
+    (SignedAttest { attest, signature }, public)
+}
+
+#[test]
+fn test_quote_verify() {
+    let (_, pcr_output) = pcrs();
+    let (quote, public) = signed_quote(attest(NONCE, &expected_pcr_digest()));
+    let attest = quote.verify_quote(&public, NONCE, &pcr_output).unwrap();
+    assert_eq!(attest.clock_info.clock, 1000);
+    assert_eq!(attest.firmware_version, 0x0001_0002);
+}
+
+#[test]
+fn test_quote_pipeline_object() {
+    let (quote, public) = signed_quote(attest(NONCE, &expected_pcr_digest()));
+    let line = serde_json::to_string(&Object::Quote(quote.to_output().unwrap())).unwrap();
+    let Object::Quote(output) = serde_json::from_str(&line).unwrap() else {
+        panic!("not a quote: {line}");
+    };
+    let decoded = SignedAttest::from_output(&output).unwrap();
+    assert_eq!(decoded.attest, quote.attest);
+    assert_eq!(decoded.signature, quote.signature);
+    let (_, pcr_output) = pcrs();
+    decoded.verify_quote(&public, NONCE, &pcr_output).unwrap();
+}
+
+#[test]
+fn test_quote_verify_failures() {
+    let (_, pcr_output) = pcrs();
+    let digest = expected_pcr_digest();
+
+    let (quote, public) = signed_quote(attest(NONCE, &digest));
+    let err = quote
+        .verify_quote(&public, b"stale nonce", &pcr_output)
+        .unwrap_err();
+    assert!(err.to_string().contains("nonce"), "{err}");
+
+    let mut extended = pcr_output.clone();
+    extended
+        .banks
+        .get_mut("sha256")
+        .unwrap()
+        .insert("7".to_string(), hex::encode_upper([0x78; 32]));
+    let err = quote.verify_quote(&public, NONCE, &extended).unwrap_err();
+    assert!(err.to_string().contains("PCR digest"), "{err}");
+
+    let mut missing = pcr_output.clone();
+    missing.banks.remove("sha1");
+    let err = quote.verify_quote(&public, NONCE, &missing).unwrap_err();
+    assert!(err.to_string().contains("sha1:1"), "{err}");
+
+    let mut tampered = quote.clone();
+    let last = tampered.attest.len() - 1;
+    tampered.attest[last] ^= 1;
+    let err = tampered
+        .verify_quote(&public, NONCE, &pcr_output)
+        .unwrap_err();
+    assert!(err.to_string().contains("signature"), "{err}");
+
+    let (certify, public) = signed_quote(attest_with(
+        TpmSt::AttestCertify,
+        NONCE,
+        TpmuAttest::Certify(TpmsCertifyInfo::default()),
+    ));
+    let err = certify
+        .verify_quote(&public, NONCE, &pcr_output)
+        .unwrap_err();
+    assert!(err.to_string().contains("not a quote"), "{err}");
+
+    let (key, public) = ecc_key();
+    let mut forged = attest(NONCE, &digest);
+    forged[0] = 0;
+    let signature = ecdsa_sign(&key, &forged);
+    let forged = SignedAttest {
+        attest: forged,
+        signature,
+    };
+    let err = forged
+        .verify_quote(&public, NONCE, &pcr_output)
+        .unwrap_err();
+    assert!(err.to_string().contains("not generated by a TPM"), "{err}");
+}
+
+#[test]
+fn test_quote_rsa_signature() {
+    let key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+    let public = TpmtPublic {
+        object_type: TpmAlgId::Rsa,
+        name_alg: TpmAlgId::Sha256,
+        object_attributes: TpmaObject::SIGN_ENCRYPT,
+        auth_policy: Tpm2bDigest::default(),
+        parameters: TpmuPublicParms::Rsa {
+            symmetric: TpmtSymDefObject::default(),
+            scheme: TpmtScheme::default(),
+            key_bits: 1024,
+            exponent: 0,
+        },
+        unique: TpmuPublicId::Rsa(TpmBuffer::try_from(key.n().to_bytes_be().as_slice()).unwrap()),
+    };
+    assert_eq!(
+        quote_scheme(&public).unwrap(),
+        TpmtScheme {
+            scheme: TpmAlgId::Rsassa,
+            details: TpmuAsymScheme::Hash(TpmAlgId::Sha256),
+        }
+    );
+
+    let message = attest(NONCE, &expected_pcr_digest());
+    let sig = key
+        .sign(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(&message))
+        .unwrap();
+    let signature = TpmtSignature {
+        sig_alg: TpmAlgId::Rsassa,
+        signature: TpmuSignature::Rsassa(TpmsSignatureRsa {
+            hash: TpmAlgId::Sha256,
+            sig: TpmBuffer::try_from(sig.as_slice()).unwrap(),
+        }),
+    };
+    verify_signature(&public, &message, &signature).unwrap();
+    let (_, pcr_output) = pcrs();
+    SignedAttest {
+        attest: message.clone(),
+        signature: signature.clone(),
+    }
+    .verify_quote(&public, NONCE, &pcr_output)
+    .unwrap();
+
+    let mut tampered = message;
+    tampered[10] ^= 1;
+    assert!(verify_signature(&public, &tampered, &signature).is_err());
+}
@@ -0,0 +1,274 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{
+    create_auth, resource_manager::TpmResourceManager, soft_tpm::SoftTpm, AuthSession, TpmDevice,
+    TpmError,
+};
+use common::{password, rc_of, LOG};
+use rstest::{fixture, rstest};
+use std::{
+    cell::RefCell,
+    io::{self, Read, Write},
+    rc::Rc,
+};
+use tpm2_protocol::{
+    data::{
+        Tpm2b, Tpm2bAuth, Tpm2bDigest, Tpm2bNonce, Tpm2bPublic, Tpm2bSensitiveCreate,
+        Tpm2bSensitiveData, TpmAlgId, TpmCap, TpmEccCurve, TpmRcBase, TpmRh, TpmSe, TpmSu,
+        TpmaObject, TpmaSession, TpmlPcrSelection, TpmsEccPoint, TpmsKeyedhashParms,
+        TpmsSensitiveCreate, TpmtKdfScheme, TpmtPublic, TpmtScheme, TpmtSymDefObject,
+        TpmuAsymScheme, TpmuCapabilities, TpmuPublicId, TpmuPublicParms, TpmuSymKeyBits,
+        TpmuSymMode,
+    },
+    message::{
+        TpmCreateCommand, TpmFlushContextCommand, TpmGetCapabilityCommand, TpmHeader,
+        TpmLoadCommand, TpmReadPublicCommand, TpmStartAuthSessionCommand, TpmStartupCommand,
+        TpmUnsealCommand,
+    },
+    TpmBuffer, TpmContextHandle, TpmPermanent, TpmTransient,
+};
+
+const SEED: [u8; 32] = [0x5a; 32];
+/// More objects than `SoftTpm` can hold at once.
+const OBJECT_COUNT: usize = 20;
+/// More sessions than `SoftTpm` can hold at once.
+const SESSION_COUNT: usize = 10;
+
+/// A `SoftTpm` that stays reachable after the resource manager is dropped.
+#[derive(Clone)]
+struct Shared(Rc<RefCell<SoftTpm>>);
+
+impl Read for Shared {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+fn storage_template() -> TpmtPublic {
+    TpmtPublic {
+        object_type: TpmAlgId::Ecc,
+        name_alg: TpmAlgId::Sha256,
+        object_attributes: TpmaObject::FIXED_TPM
+            | TpmaObject::FIXED_PARENT
+            | TpmaObject::SENSITIVE_DATA_ORIGIN
+            | TpmaObject::USER_WITH_AUTH
+            | TpmaObject::RESTRICTED
+            | TpmaObject::DECRYPT,
+        auth_policy: Tpm2bDigest::default(),
+        parameters: TpmuPublicParms::Ecc {
+            symmetric: TpmtSymDefObject {
+                algorithm: TpmAlgId::Aes,
+                key_bits: TpmuSymKeyBits::Aes(128),
+                mode: TpmuSymMode::Aes(TpmAlgId::Cfb),
+            },
+            scheme: TpmtScheme::default(),
+            curve_id: TpmEccCurve::NistP256,
+            kdf: TpmtKdfScheme::default(),
+        },
+        unique: TpmuPublicId::Ecc(TpmsEccPoint::default()),
+    }
+}
+
+fn sealed_template() -> TpmtPublic {
+    TpmtPublic {
+        object_type: TpmAlgId::KeyedHash,
+        name_alg: TpmAlgId::Sha256,
+        object_attributes: TpmaObject::FIXED_TPM
+            | TpmaObject::FIXED_PARENT
+            | TpmaObject::USER_WITH_AUTH,
+        auth_policy: Tpm2bDigest::default(),
+        parameters: TpmuPublicParms::KeyedHash {
+            details: TpmsKeyedhashParms {
+                scheme: TpmtScheme {
+                    scheme: TpmAlgId::Null,
+                    details: TpmuAsymScheme::Null,
+                },
+            },
+        },
+        unique: TpmuPublicId::KeyedHash(TpmBuffer::default()),
+    }
+}
+
+fn create_primary(device: &mut TpmDevice) -> u32 {
+    common::create_primary(device, TpmPermanent::OWNER, storage_template())
+        .object_handle
+        .value()
+}
+
+fn read_public(device: &mut TpmDevice, handle: u32) -> Result<(), TpmError> {
+    device
+        .execute(
+            &TpmReadPublicCommand {},
+            &[TpmTransient::try_from(handle).unwrap().into()],
+            &[],
+            LOG,
+        )
+        .map(|_| ())
+}
+
+fn flush(device: &mut TpmDevice, handle: u32) {
+    let cmd = TpmFlushContextCommand {
+        flush_handle: TpmContextHandle::try_from(handle).unwrap(),
+    };
+    device.execute(&cmd, &[], &[], LOG).unwrap();
+}
+
+fn transient_handles(device: &mut TpmDevice) -> Vec<u32> {
+    let cmd = TpmGetCapabilityCommand {
+        cap: TpmCap::Handles,
+        property: TpmRh::TransientFirst as u32,
+        property_count: 64,
+    };
+    let (resp, _) = device.execute(&cmd, &[], &[], LOG).unwrap();
+    match resp.capability_data.data {
+        TpmuCapabilities::Handles(handles) => handles.iter().copied().collect(),
+        _ => panic!("unexpected capability data"),
+    }
+}
+
+#[fixture]
+fn soft_tpm() -> Shared {
+    let shared = Shared(Rc::new(RefCell::new(SoftTpm::from_seed(SEED))));
+    let mut device = TpmDevice::from_transport(Box::new(shared.clone()));
+    let cmd = TpmStartupCommand {
+        startup_type: TpmSu::Clear,
+    };
+    device.execute(&cmd, &[], &[], LOG).unwrap();
+    shared
+}
+
+fn managed(soft_tpm: &Shared) -> TpmDevice {
+    let device = TpmDevice::from_transport(Box::new(soft_tpm.clone()));
+    TpmDevice::from_transport(Box::new(TpmResourceManager::new(device)))
+}
+
+#[rstest]
+fn test_resource_manager_evicts_objects(soft_tpm: Shared) {
+    let mut device = managed(&soft_tpm);
+    let handles: Vec<u32> = (0..OBJECT_COUNT)
+        .map(|_| create_primary(&mut device))
+        .collect();
+    for &handle in &handles {
+        assert_eq!(handle & 0xffff_0000, 0x80ff_0000);
+        read_public(&mut device, handle).unwrap();
+    }
+    assert_eq!(transient_handles(&mut device), handles);
+
+    flush(&mut device, handles[0]);
+    flush(&mut device, handles[OBJECT_COUNT - 1]);
+    let err = read_public(&mut device, handles[0]).unwrap_err();
+    assert_eq!(rc_of(err).base(), Ok(TpmRcBase::Handle));
+    assert_eq!(transient_handles(&mut device).len(), OBJECT_COUNT - 2);
+}
+
+#[rstest]
+fn test_resource_manager_unknown_handle(soft_tpm: Shared) {
+    let mut device = managed(&soft_tpm);
+    let err = read_public(&mut device, TpmRh::TransientFirst as u32).unwrap_err();
+    assert_eq!(rc_of(err).base(), Ok(TpmRcBase::Handle));
+}
+
+#[rstest]
+fn test_resource_manager_flushes_on_drop(soft_tpm: Shared) {
+    let mut device = managed(&soft_tpm);
+    for _ in 0..OBJECT_COUNT {
+        create_primary(&mut device);
+    }
+    drop(device);
+
+    let mut device = TpmDevice::from_transport(Box::new(soft_tpm.clone()));
+    assert!(transient_handles(&mut device).is_empty());
+}
+
+#[rstest]
+fn test_resource_manager_evicts_sessions(soft_tpm: Shared) {
+    let mut device = managed(&soft_tpm);
+    let parent = create_primary(&mut device);
+    let cmd = TpmCreateCommand {
+        in_sensitive: Tpm2bSensitiveCreate::from(TpmsSensitiveCreate {
+            user_auth: Tpm2bAuth::try_from(&b"pass"[..]).unwrap(),
+            data: Tpm2bSensitiveData::try_from(&b"secret"[..]).unwrap(),
+        }),
+        in_public: Tpm2bPublic::from(sealed_template()),
+        outside_info: Tpm2b::default(),
+        creation_pcr: TpmlPcrSelection::default(),
+    };
+    let (created, _) = device
+        .execute(
+            &cmd,
+            &[TpmTransient::try_from(parent).unwrap().into()],
+            &[password(b"")],
+            LOG,
+        )
+        .unwrap();
+    let cmd = TpmLoadCommand {
+        in_private: created.out_private,
+        in_public: created.out_public,
+    };
+    let (loaded, _) = device
+        .execute(
+            &cmd,
+            &[TpmTransient::try_from(parent).unwrap().into()],
+            &[password(b"")],
+            LOG,
+        )
+        .unwrap();
+
+    let null = TpmPermanent::NULL.into();
+    let mut sessions = Vec::new();
+    for _ in 0..SESSION_COUNT {
+        let cmd = TpmStartAuthSessionCommand {
+            nonce_caller: Tpm2b::try_from(&[0x11; 32][..]).unwrap(),
+            encrypted_salt: Tpm2b::default(),
+            session_type: TpmSe::Hmac,
+            symmetric: TpmtSymDefObject::default(),
+            auth_hash: TpmAlgId::Sha256,
+        };
+        let (resp, _) = device.execute(&cmd, &[null, null], &[], LOG).unwrap();
+        sessions.push(AuthSession {
+            handle: resp.session_handle,
+            nonce_tpm: Tpm2bNonce::try_from(&resp.nonce_tpm[..]).unwrap(),
+            attributes: TpmaSession::CONTINUE_SESSION,
+            hmac_key: Tpm2bAuth::try_from(&b"pass"[..]).unwrap(),
+            auth_hash: TpmAlgId::Sha256,
+        });
+    }
+
+    for session in &sessions {
+        let nonce_caller = Tpm2bNonce::try_from(&[0x22; 32][..]).unwrap();
+        let auth = create_auth(
+            session,
+            &nonce_caller,
+            TpmUnsealCommand::COMMAND,
+            &[loaded.name],
+            &[],
+        )
+        .unwrap();
+        let (resp, _) = device
+            .execute(
+                &TpmUnsealCommand {},
+                &[loaded.object_handle.into()],
+                &[auth],
+                LOG,
+            )
+            .unwrap();
+        assert_eq!(&resp.out_data[..], b"secret");
+    }
+
+    for session in &sessions {
+        flush(&mut device, session.handle.value());
+    }
+}
@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{
+    ak::{create_ak, load_ak, AkScheme},
+    ek::{create_ek, EkTemplate},
+    read_public,
+    signature::{
+        decode_signature, encode_signature, hash_for_signing, sign_digest, signature_hash,
+        signing_scheme, verify_with_tpm, SignatureFormat,
+    },
+    software_digest, verify_signature, TpmDevice,
+};
+use common::{create_primary, started_device, LOG};
+use tpm2_protocol::{
+    data::{
+        Tpm2bDigest, TpmAlgId, TpmEccCurve, TpmRh, TpmSt, TpmaObject, TpmsEccPoint, TpmtKdfScheme,
+        TpmtPublic, TpmtScheme, TpmtSymDefObject, TpmtTkHashcheck, TpmuPublicId, TpmuPublicParms,
+        TpmuSignature,
+    },
+    TpmObjectHandle, TpmPermanent,
+};
+
+const DATA: &[u8] = b"signed data";
+
+fn device() -> TpmDevice {
+    started_device([0x49; 32])
+}
+
+/// Creates an unrestricted NIST P-256 signing key without a scheme.
+fn signing_key(device: &mut TpmDevice) -> (TpmObjectHandle, TpmtPublic) {
+    let template = TpmtPublic {
+        object_type: TpmAlgId::Ecc,
+        name_alg: TpmAlgId::Sha256,
+        object_attributes: TpmaObject::FIXED_TPM
+            | TpmaObject::FIXED_PARENT
+            | TpmaObject::SENSITIVE_DATA_ORIGIN
+            | TpmaObject::USER_WITH_AUTH
+            | TpmaObject::SIGN_ENCRYPT,
+        auth_policy: Tpm2bDigest::default(),
+        parameters: TpmuPublicParms::Ecc {
+            symmetric: TpmtSymDefObject::default(),
+            scheme: TpmtScheme::default(),
+            curve_id: TpmEccCurve::NistP256,
+            kdf: TpmtKdfScheme::default(),
+        },
+        unique: TpmuPublicId::Ecc(TpmsEccPoint::default()),
+    };
+    let resp = create_primary(device, TpmPermanent::OWNER, template);
+    (resp.object_handle.into(), resp.out_public.inner)
+}
+
+/// Creates and loads an ECDSA attestation key, which is restricted.
+fn attestation_key(device: &mut TpmDevice) -> (TpmObjectHandle, TpmtPublic) {
+    let (ek, _) = create_ek(device, EkTemplate::EccNistP256, None, None, LOG).unwrap();
+    let ek = ek.into();
+    let ak = create_ak(
+        device,
+        ek,
+        AkScheme::Ecdsa,
+        TpmAlgId::Sha256,
+        None,
+        None,
+        LOG,
+    )
+    .unwrap();
+    let handle = load_ak(device, ek, &ak, None, LOG).unwrap();
+    (handle.into(), ak.public)
+}
+
+#[test]
+fn test_signature_unrestricted() {
+    let mut device = device();
+    let (key, public) = signing_key(&mut device);
+
+    let (scheme, hash_alg) = signing_scheme(&public, Some(TpmAlgId::Sha384)).unwrap();
+    assert_eq!(scheme.scheme, TpmAlgId::Ecdsa);
+    assert_eq!(hash_alg, TpmAlgId::Sha384);
+
+    let (digest, validation) = hash_for_signing(&mut device, &public, DATA, hash_alg, LOG).unwrap();
+    assert_eq!(digest, software_digest(TpmAlgId::Sha384, &[DATA]).unwrap());
+    assert_eq!(validation.hierarchy, TpmRh::Null);
+
+    let signature = sign_digest(
+        &mut device,
+        key,
+        scheme,
+        &digest,
+        validation,
+        None,
+        None,
+        LOG,
+    )
+    .unwrap();
+    assert_eq!(signature_hash(&signature).unwrap(), TpmAlgId::Sha384);
+    verify_signature(&public, DATA, &signature).unwrap();
+    assert!(verify_signature(&public, b"other data", &signature).is_err());
+
+    let ticket = verify_with_tpm(&mut device, key, &digest, &signature, LOG).unwrap();
+    assert_eq!(ticket.tag, TpmSt::Verified);
+    assert_eq!(ticket.hierarchy, TpmRh::Owner);
+    let other = software_digest(TpmAlgId::Sha384, &[b"other data"]).unwrap();
+    assert!(verify_with_tpm(&mut device, key, &other, &signature, LOG).is_err());
+}
+
+#[test]
+fn test_signature_restricted() {
+    let mut device = device();
+    let (key, public) = attestation_key(&mut device);
+
+    let (scheme, hash_alg) = signing_scheme(&public, None).unwrap();
+    assert_eq!(scheme, TpmtScheme::default());
+    assert_eq!(hash_alg, TpmAlgId::Sha256);
+    assert!(signing_scheme(&public, Some(TpmAlgId::Sha512)).is_err());
+
+    let (digest, validation) = hash_for_signing(&mut device, &public, DATA, hash_alg, LOG).unwrap();
+    assert_eq!(validation.tag, TpmSt::HashCheck);
+    assert_ne!(validation.hierarchy, TpmRh::Null);
+
+    let null_ticket = TpmtTkHashcheck {
+        tag: TpmSt::HashCheck,
+        hierarchy: TpmRh::Null,
+        digest: Tpm2bDigest::default(),
+    };
+    assert!(sign_digest(
+        &mut device,
+        key,
+        scheme,
+        &digest,
+        null_ticket,
+        None,
+        None,
+        LOG
+    )
+    .is_err());
+
+    let signature = sign_digest(
+        &mut device,
+        key,
+        scheme,
+        &digest,
+        validation,
+        None,
+        None,
+        LOG,
+    )
+    .unwrap();
+    verify_signature(&public, DATA, &signature).unwrap();
+    let (read_back, _) = read_public(&mut device, key, LOG).unwrap();
+    assert_eq!(read_back, public);
+    verify_with_tpm(&mut device, key, &digest, &signature, LOG).unwrap();
+}
+
+#[test]
+fn test_signature_formats() {
+    let mut device = device();
+    let (key, public) = signing_key(&mut device);
+    let (scheme, hash_alg) = signing_scheme(&public, None).unwrap();
+    let (digest, validation) = hash_for_signing(&mut device, &public, DATA, hash_alg, LOG).unwrap();
+    let signature = sign_digest(
+        &mut device,
+        key,
+        scheme,
+        &digest,
+        validation,
+        None,
+        None,
+        LOG,
+    )
+    .unwrap();
+
+    for format in [
+        SignatureFormat::Tpm,
+        SignatureFormat::Raw,
+        SignatureFormat::Der,
+    ] {
+        let encoded = encode_signature(&signature, format).unwrap();
+        let decoded = decode_signature(&encoded, format, &public, None).unwrap();
+        verify_signature(&public, DATA, &decoded).unwrap();
+    }
+
+    let raw = encode_signature(&signature, SignatureFormat::Raw).unwrap();
+    assert_eq!(raw.len(), 64);
+    let der = encode_signature(&signature, SignatureFormat::Der).unwrap();
+    assert_eq!(der[0], 0x30);
+    assert!(p256::ecdsa::Signature::from_der(&der).is_ok());
+
+    let decoded =
+        decode_signature(&raw, SignatureFormat::Raw, &public, Some(TpmAlgId::Sha384)).unwrap();
+    assert!(verify_signature(&public, DATA, &decoded).is_err());
+
+    let mut tampered = raw.clone();
+    tampered[40] ^= 1;
+    let decoded = decode_signature(&tampered, SignatureFormat::Raw, &public, None).unwrap();
+    assert!(matches!(decoded.signature, TpmuSignature::Ecdsa(_)));
+    assert!(verify_signature(&public, DATA, &decoded).is_err());
+
+    assert!(decode_signature(&raw[1..], SignatureFormat::Raw, &public, None).is_err());
+    assert!("pem".parse::<SignatureFormat>().is_err());
+}
@@ -0,0 +1,308 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+// Copyright (c) 2025 Opinsys Oy
+
+mod common;
+
+use cli::{create_auth, AuthSession, TpmDevice, TpmError};
+use common::{password, rc_of, started_device, LOG};
+use rstest::{fixture, rstest};
+use sha2::{Digest, Sha256};
+use tpm2_protocol::{
+    data::{
+        Tpm2b, Tpm2bAuth, Tpm2bDigest, Tpm2bMaxNvBuffer, Tpm2bName, Tpm2bNonce, Tpm2bNvPublic,
+        Tpm2bPublic, Tpm2bSensitiveCreate, Tpm2bSensitiveData, TpmAlgId, TpmEccCurve, TpmRc,
+        TpmRcBase, TpmSe, TpmaNv, TpmaObject, TpmaSession, TpmlDigestValues, TpmlPcrSelection,
+        TpmsEccPoint, TpmsKeyedhashParms, TpmsNvPublic, TpmsPcrSelection, TpmsSensitiveCreate,
+        TpmtHa, TpmtKdfScheme, TpmtPublic, TpmtScheme, TpmtSymDefObject, TpmuAsymScheme, TpmuHa,
+        TpmuPublicId, TpmuPublicParms, TpmuSymKeyBits, TpmuSymMode,
+    },
+    message::{
+        TpmCreateCommand, TpmFlushContextCommand, TpmGetRandomCommand, TpmHeader, TpmLoadCommand,
+        TpmNvDefineSpaceCommand, TpmNvReadCommand, TpmNvUndefineSpaceCommand, TpmNvWriteCommand,
+        TpmPcrExtendCommand, TpmPcrReadCommand, TpmStartAuthSessionCommand, TpmUnsealCommand,
+    },
+    TpmBuffer, TpmHandle, TpmNvIndex, TpmParseTagged, TpmPcr, TpmPermanent, TpmTransient,
+};
+
+const SEED: [u8; 32] = [0x5a; 32];
+
+fn storage_template() -> TpmtPublic {
+    TpmtPublic {
+        object_type: TpmAlgId::Ecc,
+        name_alg: TpmAlgId::Sha256,
+        object_attributes: TpmaObject::FIXED_TPM
+            | TpmaObject::FIXED_PARENT
+            | TpmaObject::SENSITIVE_DATA_ORIGIN
+            | TpmaObject::USER_WITH_AUTH
+            | TpmaObject::RESTRICTED
+            | TpmaObject::DECRYPT,
+        auth_policy: Tpm2bDigest::default(),
+        parameters: TpmuPublicParms::Ecc {
+            symmetric: TpmtSymDefObject {
+                algorithm: TpmAlgId::Aes,
+                key_bits: TpmuSymKeyBits::Aes(128),
+                mode: TpmuSymMode::Aes(TpmAlgId::Cfb),
+            },
+            scheme: TpmtScheme::default(),
+            curve_id: TpmEccCurve::NistP256,
+            kdf: TpmtKdfScheme::default(),
+        },
+        unique: TpmuPublicId::Ecc(TpmsEccPoint::default()),
+    }
+}
+
+fn sealed_template() -> TpmtPublic {
+    TpmtPublic {
+        object_type: TpmAlgId::KeyedHash,
+        name_alg: TpmAlgId::Sha256,
+        object_attributes: TpmaObject::FIXED_TPM
+            | TpmaObject::FIXED_PARENT
+            | TpmaObject::USER_WITH_AUTH,
+        auth_policy: Tpm2bDigest::default(),
+        parameters: TpmuPublicParms::KeyedHash {
+            details: TpmsKeyedhashParms {
+                scheme: TpmtScheme {
+                    scheme: TpmAlgId::Null,
+                    details: TpmuAsymScheme::Null,
+                },
+            },
+        },
+        unique: TpmuPublicId::KeyedHash(TpmBuffer::default()),
+    }
+}
+
+fn create_primary(device: &mut TpmDevice) -> (TpmTransient, Tpm2bName) {
+    let resp = common::create_primary(device, TpmPermanent::OWNER, storage_template());
+    (resp.object_handle, resp.name)
+}
+
+/// Seals `secret` under a new primary key and loads the sealed object.
+fn load_sealed(device: &mut TpmDevice, auth: &[u8], secret: &[u8]) -> (TpmHandle, Tpm2bName) {
+    let (parent, _) = create_primary(device);
+    let cmd = TpmCreateCommand {
+        in_sensitive: Tpm2bSensitiveCreate::from(TpmsSensitiveCreate {
+            user_auth: Tpm2bAuth::try_from(auth).unwrap(),
+            data: Tpm2bSensitiveData::try_from(secret).unwrap(),
+        }),
+        in_public: Tpm2bPublic::from(sealed_template()),
+        outside_info: Tpm2b::default(),
+        creation_pcr: TpmlPcrSelection::default(),
+    };
+    let (created, _) = device
+        .execute(&cmd, &[parent.into()], &[password(b"")], LOG)
+        .unwrap();
+    let cmd = TpmLoadCommand {
+        in_private: created.out_private,
+        in_public: created.out_public,
+    };
+    let (loaded, _) = device
+        .execute(&cmd, &[parent.into()], &[password(b"")], LOG)
+        .unwrap();
+    (loaded.object_handle.into(), loaded.name)
+}
+
+#[fixture]
+fn device() -> TpmDevice {
+    started_device(SEED)
+}
+
+#[rstest]
+fn test_soft_tpm_requires_startup() {
+    let mut device = common::device(SEED);
+    let cmd = TpmGetRandomCommand { bytes_requested: 8 };
+    let err = device.execute(&cmd, &[], &[], LOG).unwrap_err();
+    assert_eq!(rc_of(err), TpmRc::from(TpmRcBase::Initialize));
+}
+
+#[rstest]
+fn test_soft_tpm_get_random(mut device: TpmDevice) {
+    let cmd = TpmGetRandomCommand {
+        bytes_requested: 16,
+    };
+    let (first, _) = device.execute(&cmd, &[], &[], LOG).unwrap();
+    let (second, _) = device.execute(&cmd, &[], &[], LOG).unwrap();
+    assert_eq!(first.random_bytes.len(), 16);
+    assert_ne!(first.random_bytes, second.random_bytes);
+}
+
+#[rstest]
+fn test_soft_tpm_pcr_extend(mut device: TpmDevice) {
+    let value = [0xab; 32];
+    let mut digests = TpmlDigestValues::new();
+    digests
+        .try_push(TpmtHa {
+            hash_alg: TpmAlgId::Sha256,
+            digest: TpmuHa::parse_tagged(TpmAlgId::Sha256, &value).unwrap().0,
+        })
+        .unwrap();
+    let cmd = TpmPcrExtendCommand { digests };
+    let pcr = TpmPcr::try_from(7).unwrap();
+    device
+        .execute(&cmd, &[pcr.into()], &[password(b"")], LOG)
+        .unwrap();
+
+    let mut pcr_selection_in = TpmlPcrSelection::new();
+    pcr_selection_in
+        .try_push(TpmsPcrSelection {
+            hash: TpmAlgId::Sha256,
+            pcr_select: TpmBuffer::try_from(&[0x80, 0x00, 0x00][..]).unwrap(),
+        })
+        .unwrap();
+    let cmd = TpmPcrReadCommand { pcr_selection_in };
+    let (resp, _) = device.execute(&cmd, &[], &[], LOG).unwrap();
+
+    let expected = Sha256::new()
+        .chain_update([0u8; 32])
+        .chain_update(value)
+        .finalize();
+    assert_eq!(resp.pcr_update_counter, 1);
+    assert_eq!(resp.pcr_values.len(), 1);
+    assert_eq!(&resp.pcr_values[0][..], &expected[..]);
+}
+
+#[rstest]
+fn test_soft_tpm_nv_round_trip(mut device: TpmDevice) {
+    let index = TpmNvIndex::try_from(0x0100_0010).unwrap();
+    let owner = TpmPermanent::OWNER.into();
+    let cmd = TpmNvDefineSpaceCommand {
+        auth: Tpm2bAuth::default(),
+        public_info: Tpm2bNvPublic::from(TpmsNvPublic {
+            nv_index: index,
+            name_alg: TpmAlgId::Sha256,
+            attributes: TpmaNv::OWNERWRITE | TpmaNv::OWNERREAD,
+            auth_policy: Tpm2bDigest::default(),
+            data_size: 8,
+        }),
+    };
+    device
+        .execute(&cmd, &[owner], &[password(b"")], LOG)
+        .unwrap();
+    let err = device
+        .execute(&cmd, &[owner], &[password(b"")], LOG)
+        .unwrap_err();
+    assert_eq!(rc_of(err), TpmRc::from(TpmRcBase::NvDefined));
+
+    let read = TpmNvReadCommand { size: 4, offset: 2 };
+    let err = device
+        .execute(&read, &[owner, index.into()], &[password(b"")], LOG)
+        .unwrap_err();
+    assert_eq!(rc_of(err), TpmRc::from(TpmRcBase::NvUninitialized));
+
+    let unwritten = device.get_handle_names(&[index.into()], LOG).unwrap();
+    let cmd = TpmNvWriteCommand {
+        data: Tpm2bMaxNvBuffer::try_from(&b"abcd"[..]).unwrap(),
+        offset: 2,
+    };
+    device
+        .execute(&cmd, &[owner, index.into()], &[password(b"")], LOG)
+        .unwrap();
+    let written = device.get_handle_names(&[index.into()], LOG).unwrap();
+    assert_ne!(unwritten, written);
+    let (resp, _) = device
+        .execute(&read, &[owner, index.into()], &[password(b"")], LOG)
+        .unwrap();
+    assert_eq!(&resp.data[..], b"abcd");
+
+    let cmd = TpmNvUndefineSpaceCommand {};
+    device
+        .execute(&cmd, &[owner, index.into()], &[password(b"")], LOG)
+        .unwrap();
+    let err = device
+        .execute(&read, &[owner, index.into()], &[password(b"")], LOG)
+        .unwrap_err();
+    assert!(matches!(err, TpmError::TpmRc(_)));
+}
+
+#[rstest]
+fn test_soft_tpm_seal_unseal(mut device: TpmDevice) {
+    let (handle, _) = load_sealed(&mut device, b"pass", b"secret");
+    let (resp, _) = device
+        .execute(&TpmUnsealCommand {}, &[handle], &[password(b"pass")], LOG)
+        .unwrap();
+    assert_eq!(&resp.out_data[..], b"secret");
+
+    let err = device
+        .execute(&TpmUnsealCommand {}, &[handle], &[password(b"wrong")], LOG)
+        .unwrap_err();
+    assert_eq!(rc_of(err).base(), Ok(TpmRcBase::AuthFail));
+}
+
+#[rstest]
+fn test_soft_tpm_hmac_session(mut device: TpmDevice) {
+    let (handle, name) = load_sealed(&mut device, b"pass", b"secret");
+    let null = TpmPermanent::NULL.into();
+    let cmd = TpmStartAuthSessionCommand {
+        nonce_caller: Tpm2b::try_from(&[0x11; 32][..]).unwrap(),
+        encrypted_salt: Tpm2b::default(),
+        session_type: TpmSe::Hmac,
+        symmetric: TpmtSymDefObject::default(),
+        auth_hash: TpmAlgId::Sha256,
+    };
+    let (resp, _) = device.execute(&cmd, &[null, null], &[], LOG).unwrap();
+    let mut session = AuthSession {
+        handle: resp.session_handle,
+        nonce_tpm: Tpm2bNonce::try_from(&resp.nonce_tpm[..]).unwrap(),
+        attributes: TpmaSession::CONTINUE_SESSION,
+        hmac_key: Tpm2bAuth::try_from(&b"pass"[..]).unwrap(),
+        auth_hash: TpmAlgId::Sha256,
+    };
+
+    for nonce in [0x22, 0x33] {
+        let nonce_caller = Tpm2bNonce::try_from(&[nonce; 32][..]).unwrap();
+        let auth = create_auth(
+            &session,
+            &nonce_caller,
+            TpmUnsealCommand::COMMAND,
+            &[name],
+            &[],
+        )
+        .unwrap();
+        let (resp, auths) = device
+            .execute(&TpmUnsealCommand {}, &[handle], &[auth], LOG)
+            .unwrap();
+        assert_eq!(&resp.out_data[..], b"secret");
+        assert_ne!(auths[0].nonce, session.nonce_tpm);
+        session.nonce_tpm = auths[0].nonce;
+    }
+
+    let nonce_caller = Tpm2bNonce::try_from(&[0x44; 32][..]).unwrap();
+    let mut auth = create_auth(
+        &session,
+        &nonce_caller,
+        TpmUnsealCommand::COMMAND,
+        &[name],
+        &[],
+    )
+    .unwrap();
+    auth.hmac = Tpm2bAuth::try_from(&[0u8; 32][..]).unwrap();
+    let err = device
+        .execute(&TpmUnsealCommand {}, &[handle], &[auth], LOG)
+        .unwrap_err();
+    assert_eq!(rc_of(err).base(), Ok(TpmRcBase::AuthFail));
+
+    let cmd = TpmFlushContextCommand {
+        flush_handle: session.handle.try_into().unwrap(),
+    };
+    device.execute(&cmd, &[], &[], LOG).unwrap();
+}
+
+#[rstest]
+fn test_soft_tpm_deterministic_primary() {
+    let mut names = Vec::new();
+    for _ in 0..2 {
+        let mut device = device();
+        let (handle, name) = create_primary(&mut device);
+        let (_, again) = create_primary(&mut device);
+        assert_eq!(name, again);
+        let cmd = TpmFlushContextCommand {
+            flush_handle: handle.into(),
+        };
+        device.execute(&cmd, &[], &[], LOG).unwrap();
+        names.push(name);
+    }
+    assert_eq!(names[0], names[1]);
+
+    let mut other = started_device([0x33; 32]);
+    let (_, name) = create_primary(&mut other);
+    assert_ne!(name, names[0]);
+}